@@ -0,0 +1,29 @@
+//! Encodes the image the example shows.
+
+use q565::encode::Q565EncodeContext;
+use std::{env, fs, path::PathBuf};
+
+const WIDTH: u16 = 240;
+const HEIGHT: u16 = 240;
+
+fn main() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+
+    // color bars over a vertical gradient, like a test card
+    let pixels: Vec<u16> = (0..HEIGHT)
+        .flat_map(|y| {
+            (0..WIDTH).map(move |x| {
+                let level = y * 32 / HEIGHT;
+                match x * 4 / WIDTH {
+                    0 => level << 11,
+                    1 => (level * 2) << 5,
+                    2 => level,
+                    _ => level << 11 | (level * 2) << 5 | level,
+                }
+            })
+        })
+        .collect();
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(WIDTH, HEIGHT, &pixels, &mut encoded).unwrap();
+    fs::write(out.join("image.q565"), encoded).unwrap();
+}
@@ -0,0 +1,176 @@
+//! Streams a Q565 image from flash to an ST7789 SPI display on the ESP32-C3, decoding it with the
+//! streaming decoder while DMA sends the previous chunk.
+//!
+//! The image is read in small pieces, like from a flash chip that isn't memory-mapped, and
+//! decoded into two chunk buffers in turn: one is filled while the SPI DMA transfers the other one
+//! through `q565::decode::DmaPixelSink`. The image is encoded by `build.rs`.
+//!
+//! Wiring: SCK on GPIO6, MOSI on GPIO7, CS on GPIO10, D/C on GPIO4, RESET on GPIO5.
+//!
+//! ```sh
+//! cargo run --release
+//! ```
+
+#![no_std]
+#![no_main]
+
+use core::mem;
+use esp_hal::{
+    delay::Delay,
+    dma::{aligned::DmaAlignedMut, DmaDescriptor, DmaTxBuf},
+    gpio::{Level, Output, OutputConfig},
+    spi::{
+        master::{Config, Spi, SpiDma, SpiDmaTransfer},
+        Mode,
+    },
+    time::Rate,
+    Blocking,
+};
+use panic_halt as _;
+use q565::{
+    byteorder::BigEndian,
+    decode::{streaming_no_header::Q565StreamingDecodeContext, DmaPixelSink, Q565DecodeContext},
+};
+
+esp_bootloader_esp_idf::esp_app_desc!();
+
+/// The image, 240x240 like the display.
+static IMAGE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/image.q565"));
+
+/// Pixels per chunk buffer: one row of the display.
+const CHUNK_LEN: usize = 240;
+/// Bytes read from flash at a time.
+const READ_LEN: usize = 64;
+
+/// Sends chunks to the display with SPI DMA, one transfer at a time.
+struct SpiDmaSink {
+    idle: Option<SpiDma<'static, Blocking>>,
+    busy: Option<SpiDmaTransfer<'static, Blocking, DmaTxBuf>>,
+    /// The descriptors of the buffers that aren't being transferred.
+    descriptors: [Option<&'static mut [DmaDescriptor]>; 2],
+}
+
+impl DmaPixelSink<u16> for SpiDmaSink {
+    fn start(&mut self, buffer: &'static mut [u16], len: usize) {
+        let descriptors = self.descriptors.iter_mut().find_map(Option::take).unwrap();
+        let bytes: &'static mut [u8] = bytemuck::cast_slice_mut(buffer);
+        let mut buf = DmaTxBuf::new(
+            DmaAlignedMut::new(descriptors).unwrap(),
+            DmaAlignedMut::new(bytes).unwrap(),
+        )
+        .unwrap();
+        buf.set_length(len * 2);
+
+        let spi = self.idle.take().unwrap();
+        match spi.write_buffer(len * 2, buf) {
+            Ok(transfer) => self.busy = Some(transfer),
+            Err(_) => panic!("SPI DMA transfer failed to start"),
+        }
+    }
+
+    fn wait(&mut self) -> &'static mut [u16] {
+        let (spi, buf) = self.busy.take().unwrap().wait();
+        self.idle = Some(spi);
+
+        let (descriptors, bytes) = buf.split();
+        let slot = self.descriptors.iter_mut().find(|slot| slot.is_none());
+        *slot.unwrap() = Some(descriptors.into_inner());
+        bytemuck::cast_slice_mut(bytes.into_inner())
+    }
+}
+
+/// Writes a command and its parameters, while setting up the display.
+fn command(spi: &mut Spi<'static, Blocking>, dc: &mut Output<'static>, cmd: u8, params: &[u8]) {
+    dc.set_low();
+    spi.write(&[cmd]).unwrap();
+    dc.set_high();
+    if !params.is_empty() {
+        spi.write(params).unwrap();
+    }
+}
+
+/// Allocates a chunk buffer in DMA-capable memory, with its descriptors.
+macro_rules! chunk_buffer {
+    () => {{
+        let (_, _, buffer, descriptors) = esp_hal::dma_buffers!(0, CHUNK_LEN * 2);
+        let buffer: &'static mut [u16] = bytemuck::cast_slice_mut(buffer);
+        (buffer, descriptors)
+    }};
+}
+
+#[esp_hal::main]
+fn main() -> ! {
+    let peripherals = esp_hal::init(esp_hal::Config::default());
+    let delay = Delay::new();
+
+    let mut spi = Spi::new(
+        peripherals.SPI2,
+        Config::default()
+            .with_frequency(Rate::from_mhz(40))
+            .with_mode(Mode::_0),
+    )
+    .unwrap()
+    .with_sck(peripherals.GPIO6)
+    .with_mosi(peripherals.GPIO7)
+    .with_cs(peripherals.GPIO10);
+    let mut dc = Output::new(peripherals.GPIO4, Level::High, OutputConfig::default());
+    let mut reset = Output::new(peripherals.GPIO5, Level::Low, OutputConfig::default());
+
+    delay.delay_millis(10);
+    reset.set_high();
+    delay.delay_millis(120);
+
+    // sleep out, 16-bit pixels, inverted colors as most ST7789 panels need, display on
+    command(&mut spi, &mut dc, 0x11, &[]);
+    delay.delay_millis(120);
+    command(&mut spi, &mut dc, 0x3A, &[0x55]);
+    command(&mut spi, &mut dc, 0x21, &[]);
+    command(&mut spi, &mut dc, 0x29, &[]);
+    // the whole screen, then start writing pixels
+    command(&mut spi, &mut dc, 0x2A, &[0, 0, 0, 239]);
+    command(&mut spi, &mut dc, 0x2B, &[0, 0, 0, 239]);
+    command(&mut spi, &mut dc, 0x2C, &[]);
+
+    let (front, front_descriptors) = chunk_buffer!();
+    let (back, back_descriptors) = chunk_buffer!();
+    let mut sink = SpiDmaSink {
+        idle: Some(spi.with_dma(peripherals.DMA_CH0)),
+        busy: None,
+        descriptors: [Some(front_descriptors), Some(back_descriptors)],
+    };
+
+    // the display takes big-endian pixels, which the streaming decoder writes directly
+    let (_header, mut input) = Q565DecodeContext::decode_header(IMAGE).unwrap();
+    let mut ctx = Q565StreamingDecodeContext::new();
+    let mut buffer = front;
+    let mut spare = Some(back);
+    let mut filled = 0;
+    loop {
+        let piece = &input[..input.len().min(READ_LEN)];
+        let progress = ctx.streaming_decode_to_slice::<BigEndian>(piece, &mut buffer[filled..]);
+        input = &input[progress.bytes_consumed..];
+        filled += progress.pixels_written;
+
+        let done = progress.finished || input.is_empty();
+        if filled > 0 && (filled == buffer.len() || done) {
+            // wait for the other buffer, unless it's still unused
+            let next = match spare.take() {
+                Some(spare) => spare,
+                None => sink.wait(),
+            };
+            sink.start(mem::replace(&mut buffer, next), filled);
+            filled = 0;
+        }
+        if done {
+            break;
+        }
+    }
+    // the last transfer, unless the image was empty
+    if spare.is_none() {
+        sink.wait();
+    }
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
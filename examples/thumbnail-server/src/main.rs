@@ -0,0 +1,193 @@
+//! Small HTTP service that accepts Q565 uploads and turns them into PNG thumbnails.
+//!
+//! - `POST /thumbnail?scale=half|quarter`: returns the downscaled image as PNG
+//! - `POST /metadata`: returns the header and color statistics as JSON
+//!
+//! Uploads are checked against [`MAX_UPLOAD_BYTES`] while receiving, and against [`MAX_PIXELS`]
+//! based on the header alone, before any pixels are decoded.
+//!
+//! ```sh
+//! cargo run -p thumbnail-server
+//! curl --data-binary @image.q565 'localhost:3000/thumbnail?scale=quarter' -o thumb.png
+//! curl --data-binary @image.q565 localhost:3000/metadata
+//! ```
+//!
+//! See `README.md` for why the JSON responses are built from the example's own types.
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use q565::{
+    analyze::analyze,
+    byteorder::BigEndian,
+    decode::{DownscaleFactor, Q565DecodeContext},
+    HeaderInfo, Rgb888,
+};
+use serde::{Deserialize, Serialize};
+
+/// Largest accepted upload, in bytes.
+const MAX_UPLOAD_BYTES: usize = 4 * 1024 * 1024;
+/// Largest accepted image, in pixels (e.g. 2048x2048).
+const MAX_PIXELS: usize = 2048 * 2048;
+
+#[tokio::main]
+async fn main() {
+    let app = Router::new()
+        .route("/thumbnail", post(thumbnail))
+        .route("/metadata", post(metadata))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
+        .await
+        .unwrap();
+    println!("listening on {}", listener.local_addr().unwrap());
+    axum::serve(listener, app).await.unwrap();
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Scale {
+    #[default]
+    Half,
+    Quarter,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThumbnailParams {
+    #[serde(default)]
+    scale: Scale,
+}
+
+async fn thumbnail(
+    Query(params): Query<ThumbnailParams>,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    validate(&body)?;
+
+    let factor = match params.scale {
+        Scale::Half => DownscaleFactor::Half,
+        Scale::Quarter => DownscaleFactor::Quarter,
+    };
+
+    // decoding is CPU-bound, keep it off the async workers
+    let png = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, ApiError> {
+        let mut pixels = Vec::new();
+        let header =
+            Q565DecodeContext::decode_downscaled::<BigEndian, Rgb888>(&body, factor, &mut pixels)
+                .map_err(|e| ApiError::invalid(e.to_string()))?;
+
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(
+                pixels.as_flattened(),
+                u32::from(header.width),
+                u32::from(header.height),
+                ColorType::Rgb8,
+            )
+            .map_err(|e| ApiError::internal(e.to_string()))?;
+        Ok(png)
+    })
+    .await
+    .map_err(|e| ApiError::internal(e.to_string()))??;
+
+    Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response())
+}
+
+#[derive(Debug, Serialize)]
+struct Metadata {
+    width: u16,
+    height: u16,
+    color_array_entries: usize,
+    encoded_bytes: usize,
+    unique_colors: usize,
+    dominant_colors: Vec<DominantColor>,
+}
+
+#[derive(Debug, Serialize)]
+struct DominantColor {
+    rgb565: u16,
+    pixels: u32,
+}
+
+async fn metadata(body: Bytes) -> Result<Json<Metadata>, ApiError> {
+    let header = validate(&body)?;
+    let encoded_bytes = body.len();
+
+    let stats = tokio::task::spawn_blocking(move || analyze(&body))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(|e| ApiError::invalid(e.to_string()))?;
+
+    Ok(Json(Metadata {
+        width: header.width,
+        height: header.height,
+        color_array_entries: header.color_array_size.entries(),
+        encoded_bytes,
+        unique_colors: stats.unique_colors,
+        dominant_colors: stats
+            .dominant_colors
+            .iter()
+            .map(|&(rgb565, pixels)| DominantColor { rgb565, pixels })
+            .collect(),
+    }))
+}
+
+/// Checks the header of an upload, before decoding any pixels.
+fn validate(data: &[u8]) -> Result<HeaderInfo, ApiError> {
+    let (header, _) =
+        Q565DecodeContext::decode_header(data).map_err(|e| ApiError::invalid(e.to_string()))?;
+
+    let too_large = || ApiError {
+        status: StatusCode::PAYLOAD_TOO_LARGE,
+        message: format!(
+            "image is {}x{} pixels, at most {MAX_PIXELS} pixels are accepted",
+            header.width, header.height
+        ),
+    };
+    // `None` if the pixel count doesn't even fit into a `usize`
+    let pixels = header.pixel_count().ok_or_else(too_large)?;
+    if pixels > MAX_PIXELS {
+        return Err(too_large());
+    }
+
+    Ok(header)
+}
+
+/// Error response, sent as `{"error": "..."}`.
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn invalid(message: String) -> Self {
+        Self {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            message,
+        }
+    }
+
+    fn internal(message: String) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            Json(serde_json::json!({ "error": self.message })),
+        )
+            .into_response()
+    }
+}
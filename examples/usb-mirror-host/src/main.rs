@@ -0,0 +1,137 @@
+//! Host side of the USB screen-mirroring protocol, see `q565::mirror`.
+//!
+//! Finds the first device with a mirroring interface, reads its frames from the bulk IN endpoint
+//! and prints them as they come in. With an output path, every frame is also saved there as PNG,
+//! overwriting the previous one.
+//!
+//! ```sh
+//! cargo run -p usb-mirror-host -- screen.png
+//! ```
+//!
+//! The device side is `q565::mirror::MirrorClass`, with the `usb-device` feature.
+
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use nusb::{
+    descriptors::TransferType,
+    transfer::{Buffer, Bulk, Direction, In, Out, TransferError},
+    Endpoint, MaybeFuture,
+};
+use q565::{
+    byteorder::NativeEndian,
+    mirror::{MirrorReceiver, INTERFACE_PROTOCOL, INTERFACE_SUBCLASS, REQUEST_KEYFRAME},
+    utils::{decode_565, rgb565_to_rgb888},
+};
+use std::{error::Error, fs::File, io::BufWriter, time::Duration};
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The bulk endpoints of a claimed mirroring interface.
+struct Mirror {
+    ep_in: Endpoint<Bulk, In>,
+    ep_out: Endpoint<Bulk, Out>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let output = std::env::args_os().nth(1);
+    let mut mirror = open_mirror()?.ok_or("no device with a mirroring interface found")?;
+
+    let mut receiver = MirrorReceiver::new();
+    let mut buf = Buffer::new(16 * 1024);
+    let mut keyframe_requested = false;
+    loop {
+        if receiver.needs_keyframe() && !keyframe_requested {
+            let request = Buffer::from(vec![REQUEST_KEYFRAME]);
+            mirror.ep_out.transfer_blocking(request, TIMEOUT).status?;
+            keyframe_requested = true;
+        }
+
+        buf.clear();
+        let completion = mirror.ep_in.transfer_blocking(buf, TIMEOUT);
+        buf = completion.buffer;
+        match completion.status {
+            Ok(()) => receiver.push(&buf),
+            // timed out, ask again if the request got lost
+            Err(TransferError::Cancelled) => keyframe_requested = false,
+            Err(error) => return Err(error.into()),
+        }
+
+        loop {
+            let frame = match receiver.next_frame::<NativeEndian>() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(error) => {
+                    eprintln!("dropped a frame: {error}");
+                    keyframe_requested = false;
+                    continue;
+                }
+            };
+
+            println!(
+                "frame {:5}: {}x{}{}",
+                frame.sequence,
+                frame.header.width,
+                frame.header.height,
+                if frame.keyframe { " (keyframe)" } else { "" }
+            );
+
+            if let Some(output) = &output {
+                let rgb: Vec<u8> = frame
+                    .pixels
+                    .iter()
+                    .flat_map(|&pixel| rgb565_to_rgb888(decode_565(pixel)))
+                    .collect();
+                PngEncoder::new(BufWriter::new(File::create(output)?)).write_image(
+                    &rgb,
+                    frame.header.width.into(),
+                    frame.header.height.into(),
+                    ColorType::Rgb8,
+                )?;
+            }
+        }
+    }
+}
+
+/// Opens the first device with a mirroring interface and claims the interface.
+fn open_mirror() -> Result<Option<Mirror>, nusb::Error> {
+    for info in nusb::list_devices().wait()? {
+        let Some(interface) = info.interfaces().find(|interface| {
+            (
+                interface.class(),
+                interface.subclass(),
+                interface.protocol(),
+            ) == (0xFF, INTERFACE_SUBCLASS, INTERFACE_PROTOCOL)
+        }) else {
+            continue;
+        };
+
+        let device = info.open().wait()?;
+        let interface = device
+            .detach_and_claim_interface(interface.interface_number())
+            .wait()?;
+        let Some(descriptor) = interface.descriptor() else {
+            continue;
+        };
+
+        let bulk_endpoint = |direction| {
+            descriptor
+                .endpoints()
+                .find(|endpoint| {
+                    endpoint.transfer_type() == TransferType::Bulk
+                        && endpoint.direction() == direction
+                })
+                .map(|endpoint| endpoint.address())
+        };
+        let (Some(ep_in), Some(ep_out)) =
+            (bulk_endpoint(Direction::In), bulk_endpoint(Direction::Out))
+        else {
+            continue;
+        };
+
+        return Ok(Some(Mirror {
+            ep_in: interface.endpoint(ep_in)?,
+            ep_out: interface.endpoint(ep_out)?,
+        }));
+    }
+
+    Ok(None)
+}
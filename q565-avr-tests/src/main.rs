@@ -0,0 +1,184 @@
+//! The checks of `q565/tests/dimensions.rs` on the ATmega2560, whose `usize` is 16 bits: pixel
+//! counts and lengths read from the data don't fit into it there, and must be rejected or
+//! saturated instead of wrapping around. CI runs this in simavr.
+//!
+//! The results go out over USART0, which simavr prints, and the simulation ends after the last
+//! check, or the first one that fails.
+//!
+//! ```sh
+//! cargo run --release
+//! ```
+
+#![no_std]
+#![no_main]
+#![feature(asm_experimental_arch)]
+
+use core::{
+    fmt::{self, Write},
+    mem::MaybeUninit,
+    panic::PanicInfo,
+    ptr,
+};
+use q565::{
+    byteorder::LittleEndian,
+    container::{ContainerReader, NoTransform, CONTAINER_MAGIC},
+    decode::{DecodeError, MiniDecoder, Q565DecodeContext, Q565Ref, UninitSliceDecodeOutput},
+    encode::{encode_fast_rle, StridedPixels},
+    max_stream_len, min_stream_len, pixel_count, ColorArraySize, HeaderInfo, Rgb565,
+};
+
+const UCSR0A: *mut u8 = 0xC0 as *mut u8;
+const UCSR0B: *mut u8 = 0xC1 as *mut u8;
+const UDR0: *mut u8 = 0xC6 as *mut u8;
+const UDRE0: u8 = 1 << 5;
+const TXEN0: u8 = 1 << 3;
+
+struct Usart;
+
+impl Write for Usart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            // SAFETY: the USART0 registers of the ATmega2560, only used from here
+            unsafe {
+                while ptr::read_volatile(UCSR0A) & UDRE0 == 0 {}
+                ptr::write_volatile(UDR0, byte);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Ends the simulation: simavr quits once the CPU sleeps with interrupts disabled.
+fn exit() -> ! {
+    loop {
+        // SAFETY: only stops the CPU
+        unsafe { core::arch::asm!("cli", "sleep") };
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let _ = writeln!(Usart, "FAILED\n{info}");
+    exit()
+}
+
+fn header(raw: bool) -> HeaderInfo {
+    HeaderInfo {
+        width: u16::MAX,
+        height: u16::MAX,
+        // so that the mini decoder takes it, too
+        color_array_size: if raw {
+            ColorArraySize::Entries64
+        } else {
+            ColorArraySize::NoArray
+        },
+        raw,
+    }
+}
+
+/// A 65535x65535 image that ends after a few runs, and its length.
+fn truncated(raw: bool) -> ([u8; 16], usize) {
+    let (header, len) = header(raw).to_bytes();
+    let mut data = [0; 16];
+    data[..len].copy_from_slice(&header[..len]);
+    data[len..len + 4].fill(0b1111_1101);
+    if raw {
+        (data, len + 4)
+    } else {
+        data[len + 4] = 0xFF;
+        (data, len + 5)
+    }
+}
+
+fn pixel_counts() {
+    assert_eq!(pixel_count(0, u16::MAX), Some(0));
+    assert_eq!(pixel_count(255, 257), Some(65535));
+    assert_eq!(pixel_count(256, 256), None);
+    assert_eq!(pixel_count(u16::MAX, u16::MAX), None);
+    assert_eq!(header(false).pixel_count(), None);
+}
+
+fn stream_lengths_saturate() {
+    assert_eq!(min_stream_len(u16::MAX, u16::MAX), usize::MAX);
+    assert_eq!(max_stream_len(u16::MAX, u16::MAX), usize::MAX);
+    assert_eq!(max_stream_len(1, 1), 9 + 3 + 1);
+}
+
+fn decoders_reject_without_overflowing() {
+    for raw in [false, true] {
+        let (data, len) = truncated(raw);
+        let data = &data[..len];
+
+        let mut output = [MaybeUninit::uninit(); 64];
+        assert!(matches!(
+            Q565DecodeContext::decode_to_uninit::<LittleEndian, Rgb565>(data, &mut output),
+            Err(DecodeError::DimensionsTooLarge)
+        ));
+        assert!(matches!(
+            MiniDecoder::decode::<LittleEndian>(
+                data,
+                UninitSliceDecodeOutput::<Rgb565>::new(&mut output)
+            ),
+            Err(DecodeError::DimensionsTooLarge)
+        ));
+        assert!(matches!(
+            Q565Ref::new(data),
+            Err(DecodeError::DimensionsTooLarge)
+        ));
+    }
+}
+
+fn container_lengths_saturate() {
+    // a chunk of 65536 bytes, which would be an empty final chunk if the length wrapped around
+    let mut data = [0; 8];
+    data[..4].copy_from_slice(CONTAINER_MAGIC);
+    data[4..].copy_from_slice(&0x1_0000_u32.to_le_bytes());
+
+    let mut reader = ContainerReader::new(NoTransform);
+    assert!(matches!(reader.push(&mut data, |_| {}), Ok(8)));
+    assert!(!reader.is_finished());
+
+    let mut payload = [0; 32];
+    let mut received = 0;
+    assert!(matches!(
+        reader.push(&mut payload, |payload| received += payload.len()),
+        Ok(32)
+    ));
+    assert_eq!(received, 32);
+    assert!(!reader.is_finished());
+}
+
+fn encoders_check_pixel_counts_without_overflowing() {
+    let pixels = [0; 16];
+    assert!(encode_fast_rle(u16::MAX, u16::MAX, &pixels, &mut [0; 64]).is_none());
+    assert!(StridedPixels::new(&pixels, 4, u16::MAX, usize::MAX).is_none());
+    assert!(StridedPixels::new(&pixels, 4, 4, 4).is_some());
+}
+
+const CHECKS: [(&str, fn()); 5] = [
+    ("pixel_counts", pixel_counts),
+    ("stream_lengths_saturate", stream_lengths_saturate),
+    (
+        "decoders_reject_without_overflowing",
+        decoders_reject_without_overflowing,
+    ),
+    ("container_lengths_saturate", container_lengths_saturate),
+    (
+        "encoders_check_pixel_counts_without_overflowing",
+        encoders_check_pixel_counts_without_overflowing,
+    ),
+];
+
+#[no_mangle]
+extern "C" fn main() -> ! {
+    // SAFETY: enables the transmitter of USART0, before anything is written
+    unsafe { ptr::write_volatile(UCSR0B, TXEN0) };
+
+    for (name, check) in CHECKS {
+        let _ = write!(Usart, "{name} ... ");
+        check();
+        let _ = writeln!(Usart, "ok");
+    }
+    let _ = writeln!(Usart, "all {} checks passed", CHECKS.len());
+    exit()
+}
@@ -1,3 +1,33 @@
+//! C API for the Q565 decoders and the RGB888 encoder.
+//!
+//! # Stack usage
+//!
+//! All exported functions are free of recursion, and the decode functions are leaf functions. The
+//! context structs are provided by the caller and don't count towards the stack usage. Measured
+//! with the `clib` profile (rustc 1.95):
+//!
+//! | Function                                                     | `thumbv6m-none-eabi` | `thumbv7em-none-eabihf` |
+//! |--------------------------------------------------------------|----------------------|-------------------------|
+//! | `q565_decode_le`, `q565_decode_be`                           | 44, 48 bytes         | 56, 52 bytes            |
+//! | `q565_decode_le_rgb888`, `q565_decode_be_rgb888`             | 64 bytes (1)         | 56 bytes                |
+//! | `q565_streaming_decode_le`, `q565_streaming_decode_be`       | 48 bytes             | 36 bytes                |
+//! | `q565_streaming_decode_ex_le`, `q565_streaming_decode_ex_be` | 60 bytes             | 36 bytes                |
+//! | `q565_encode_rgb888_le`, `q565_encode_rgb888_be`             | 768, 792 bytes (2)   | 824, 864 bytes (2)      |
+//!
+//! (1) plus a call to `__aeabi_uidiv`, as ARMv6-M has no division instruction.
+//!
+//! (2) including the functions they call, besides `__aeabi_memclr4`, `__aeabi_memclr8`, and
+//! `__aeabi_memcpy4`. Most of it is the encoder state, which lives on the stack for the duration of
+//! the call.
+//!
+//! To check the numbers for another target or compiler version, look for the prologue of the
+//! functions in the disassembly, e.g.:
+//!
+//! ```sh
+//! cargo build -p q565-c --profile clib --no-default-features --target thumbv7em-none-eabihf
+//! llvm-objdump -d target/thumbv7em-none-eabihf/clib/libq565_c.a | grep -A3 "<q565_"
+//! ```
+
 #![cfg_attr(not(feature = "std"), no_std)]
 
 #[cfg(feature = "std")]
@@ -195,6 +225,8 @@ const _: () = {
 /// doesn't accumulate over multiple calls. You'll need to keep track of the number of pixels
 /// written and pass the correct output pointer to further calls.
 ///
+/// See `q565_streaming_decode_ex_le` to also learn how much of the input was processed.
+///
 /// # Safety
 ///
 /// Behavior is undefined if:
@@ -219,6 +251,44 @@ pub unsafe extern "C" fn q565_streaming_decode_le(
     ) as isize
 }
 
+/// Like `q565_streaming_decode_le`, but also reports how much of the input was processed,
+/// and whether the image is complete.
+///
+/// - `input_consumed`: If not null, receives the number of input bytes that were processed. This
+///   is `input_len`, unless the end marker was reached: any bytes after it are not processed.
+/// - `finished`: If not null, receives whether the end marker was reached. Once it was, the image
+///   is complete, and further calls with the same context don't process any input.
+///
+/// # Safety
+///
+/// Same as `q565_streaming_decode_le`.
+#[no_mangle]
+pub unsafe extern "C" fn q565_streaming_decode_ex_le(
+    context: *mut Q565StreamingDecodeContext,
+    input: *const u8,
+    input_len: usize,
+    output: *mut u16,
+    output_len: usize,
+    input_consumed: *mut usize,
+    finished: *mut bool,
+) -> isize {
+    let input = unsafe { core::slice::from_raw_parts(input, input_len) };
+    let output = unsafe { core::slice::from_raw_parts_mut(output, output_len) };
+
+    let state =
+        &mut *context.cast::<q565::decode::streaming_no_header::Q565StreamingDecodeContext>();
+    let progress =
+        state.streaming_decode_to_slice_with_progress_unchecked::<LittleEndian>(input, output);
+
+    if !input_consumed.is_null() {
+        *input_consumed = progress.bytes_consumed;
+    }
+    if !finished.is_null() {
+        *finished = progress.finished;
+    }
+    progress.pixels_written as isize
+}
+
 /// Decodes a Q565 image (*without header*) from the given input buffer into the given output
 /// buffer that is RGB565 (big-endian).
 ///
@@ -233,6 +303,8 @@ pub unsafe extern "C" fn q565_streaming_decode_le(
 /// doesn't accumulate over multiple calls. You'll need to keep track of the number of pixels
 /// written and pass the correct output pointer to further calls.
 ///
+/// See `q565_streaming_decode_ex_be` to also learn how much of the input was processed.
+///
 /// # Safety
 ///
 /// Behavior is undefined if:
@@ -256,3 +328,125 @@ pub unsafe extern "C" fn q565_streaming_decode_be(
         output,
     ) as isize
 }
+
+/// Like `q565_streaming_decode_be`, but also reports how much of the input was processed,
+/// and whether the image is complete.
+///
+/// - `input_consumed`: If not null, receives the number of input bytes that were processed. This
+///   is `input_len`, unless the end marker was reached: any bytes after it are not processed.
+/// - `finished`: If not null, receives whether the end marker was reached. Once it was, the image
+///   is complete, and further calls with the same context don't process any input.
+///
+/// # Safety
+///
+/// Same as `q565_streaming_decode_be`.
+#[no_mangle]
+pub unsafe extern "C" fn q565_streaming_decode_ex_be(
+    context: *mut Q565StreamingDecodeContext,
+    input: *const u8,
+    input_len: usize,
+    output: *mut u16,
+    output_len: usize,
+    input_consumed: *mut usize,
+    finished: *mut bool,
+) -> isize {
+    let input = unsafe { core::slice::from_raw_parts(input, input_len) };
+    let output = unsafe { core::slice::from_raw_parts_mut(output, output_len) };
+
+    let state =
+        &mut *context.cast::<q565::decode::streaming_no_header::Q565StreamingDecodeContext>();
+    let progress =
+        state.streaming_decode_to_slice_with_progress_unchecked::<BigEndian>(input, output);
+
+    if !input_consumed.is_null() {
+        *input_consumed = progress.bytes_consumed;
+    }
+    if !finished.is_null() {
+        *finished = progress.finished;
+    }
+    progress.pixels_written as isize
+}
+
+/// Encodes an RGB888 image (little-endian, so BGR888 in memory) from the given input buffer into
+/// the given output buffer, including the header.
+///
+/// - `input`: Pointer to the input buffer, holding `width * height` pixels of 3 bytes each
+/// - `width`, `height`: Size of the image
+/// - `output`: Pointer to the output buffer
+/// - `output_len`: Length of the output buffer, in bytes. `q565_max_stream_len(width, height)`
+///   bytes are always enough.
+///
+/// Returns the number of bytes written to the output buffer, if successful, or -1 if the encoded
+/// image doesn't fit into the output buffer, or the image has more pixels than fit into `size_t`.
+///
+/// # Safety
+///
+/// Behavior is undefined if the input buffer is smaller than `width * height * 3` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn q565_encode_rgb888_le(
+    input: *const u8,
+    width: u16,
+    height: u16,
+    output: *mut u8,
+    output_len: usize,
+) -> isize {
+    let Some(pixel_count) = q565::pixel_count(width, height) else {
+        return -1;
+    };
+    let input = unsafe { core::slice::from_raw_parts(input.cast(), pixel_count) };
+    let output = unsafe { core::slice::from_raw_parts_mut(output, output_len) };
+
+    match q565::pipeline::Pipeline::new(width, height).encode_frame::<LittleEndian>(input, output) {
+        Ok(len) => len as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Encodes an RGB888 image (big-endian) from the given input buffer into the given output buffer,
+/// including the header.
+///
+/// - `input`: Pointer to the input buffer, holding `width * height` pixels of 3 bytes each
+/// - `width`, `height`: Size of the image
+/// - `output`: Pointer to the output buffer
+/// - `output_len`: Length of the output buffer, in bytes. `q565_max_stream_len(width, height)`
+///   bytes are always enough.
+///
+/// Returns the number of bytes written to the output buffer, if successful, or -1 if the encoded
+/// image doesn't fit into the output buffer, or the image has more pixels than fit into `size_t`.
+///
+/// # Safety
+///
+/// Behavior is undefined if the input buffer is smaller than `width * height * 3` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn q565_encode_rgb888_be(
+    input: *const u8,
+    width: u16,
+    height: u16,
+    output: *mut u8,
+    output_len: usize,
+) -> isize {
+    let Some(pixel_count) = q565::pixel_count(width, height) else {
+        return -1;
+    };
+    let input = unsafe { core::slice::from_raw_parts(input.cast(), pixel_count) };
+    let output = unsafe { core::slice::from_raw_parts_mut(output, output_len) };
+
+    match q565::pipeline::Pipeline::new(width, height).encode_frame::<BigEndian>(input, output) {
+        Ok(len) => len as isize,
+        Err(_) => -1,
+    }
+}
+
+/// Returns the size of the shortest valid Q565 image of the given size, in bytes. Input shorter
+/// than this can't be a complete image.
+#[no_mangle]
+pub extern "C" fn q565_min_stream_len(width: u16, height: u16) -> usize {
+    q565::min_stream_len(width, height)
+}
+
+/// Returns the size of the longest valid Q565 image of the given size, in bytes. An output buffer
+/// of this size is large enough for any image the encode functions produce.
+#[no_mangle]
+pub extern "C" fn q565_max_stream_len(width: u16, height: u16) -> usize {
+    q565::max_stream_len(width, height)
+}
@@ -0,0 +1,109 @@
+//! Per-project defaults from a `q565.toml`, e.g.:
+//!
+//! ```toml
+//! dither = "ordered"
+//! quantization = "luma"
+//! speed = "best"
+//! color-array = 32
+//! output-dir = "build/assets"
+//! naming = "{stem}_{width}x{height}.q565"
+//! encoder-version = 1
+//! ```
+//!
+//! All keys are optional, and options given on the command line take precedence.
+
+use crate::{CliError, ErrorKind};
+use q565::encode::{Dither, Speed};
+use q565::utils::Quantization;
+use serde::{Deserialize, Deserializer};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const CONFIG_FILE_NAME: &str = "q565.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    #[serde(deserialize_with = "parse")]
+    pub dither: Option<Dither>,
+    #[serde(deserialize_with = "parse")]
+    pub quantization: Option<Quantization>,
+    #[serde(deserialize_with = "parse")]
+    pub speed: Option<Speed>,
+    /// Number of color array entries, see `--color-array`.
+    pub color_array: Option<usize>,
+    /// Directory for outputs that aren't given on the command line, relative to the config file.
+    pub output_dir: Option<PathBuf>,
+    /// File name pattern for outputs that aren't given on the command line, see
+    /// [`Config::output_path`].
+    pub naming: Option<String>,
+    /// Encoder version, see `q565::encode::EncoderVersion`. Pinning it keeps committed assets
+    /// byte-identical when newer encoders are released.
+    pub encoder_version: Option<u8>,
+
+    /// The file this config was loaded from.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Loads the config from `path` if given, or else from the first `q565.toml` in the working
+    /// directory or one of its parents. Returns the defaults if there is none.
+    pub fn load(path: Option<&Path>) -> Result<Self, CliError> {
+        let path = match path {
+            Some(path) => Some(path.to_owned()),
+            None => std::env::current_dir()?
+                .ancestors()
+                .map(|dir| dir.join(CONFIG_FILE_NAME))
+                .find(|path| path.is_file()),
+        };
+        let Some(path) = path else {
+            return Ok(Config::default());
+        };
+
+        let mut config: Config = toml::from_str(&std::fs::read_to_string(&path)?)
+            .map_err(|e| CliError::new(ErrorKind::Usage, format!("`{}`: {e}", path.display())))?;
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    /// Returns the path for an output that isn't given on the command line: the `naming` pattern
+    /// in the `output-dir`, or next to the input if there is none.
+    ///
+    /// The pattern defaults to `{stem}.q565`, where `{stem}` is the input file name without its
+    /// extension. `{width}` and `{height}` are replaced by the image dimensions.
+    pub fn output_path(&self, input: &str, width: u16, height: u16) -> PathBuf {
+        let input = Path::new(input);
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let name = self
+            .naming
+            .as_deref()
+            .unwrap_or("{stem}.q565")
+            .replace("{stem}", &stem)
+            .replace("{width}", &width.to_string())
+            .replace("{height}", &height.to_string());
+
+        let dir = match (&self.output_dir, &self.path) {
+            (Some(dir), Some(path)) => path.parent().unwrap_or(Path::new("")).join(dir),
+            (Some(dir), None) => dir.clone(),
+            (None, _) => input.parent().unwrap_or(Path::new("")).to_owned(),
+        };
+        dir.join(name)
+    }
+}
+
+/// Deserializes a value from the same string as on the command line, e.g. `speed = "best"`.
+fn parse<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
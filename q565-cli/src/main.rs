@@ -1,14 +1,41 @@
 use argh::FromArgs;
+use config::Config;
 use image::{ImageFormat, RgbImage};
+use q565::encode::{Dither, Encoder, EncoderVersion, Speed};
+use q565::snapshot::StateSnapshot;
+use q565::utils::Quantization;
 use q565::{
     byteorder::{BigEndian, LittleEndian},
-    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
-    Rgb565, Rgb888,
+    encode::{PixelSource, StridedPixels},
+    utils::{decode_565, rgb565_to_rgb888},
+    ColorArraySize, Rgb565, Rgb888,
 };
-use std::{fs::File, io::BufReader, num::NonZeroU16, str::FromStr};
+use serde_json::{json, Value};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufReader, Read},
+    net::TcpStream,
+    num::NonZeroU16,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
+
+mod config;
+#[cfg(feature = "probe-rs")]
+mod rtt;
 
 /// Q565 cli encoder and decoder.
 #[derive(FromArgs)]
+#[argh(
+    error_code(1, "unexpected error"),
+    error_code(2, "invalid arguments or configuration"),
+    error_code(3, "an input or output file could not be read or written"),
+    error_code(4, "an input file is not a valid image"),
+    error_code(5, "the image is valid, but not supported, e.g. too large"),
+    error_code(6, "a check failed, e.g. `compare --check` found differences")
+)]
 struct Cli {
     #[argh(subcommand)]
     command: Command,
@@ -21,6 +48,14 @@ enum Command {
     EncodeRaw(EncodeRaw),
     Decode(Decode),
     DecodeRaw(DecodeRaw),
+    DecodeMemdump(DecodeMemdump),
+    GrabRtt(GrabRtt),
+    Compare(Compare),
+    Montage(Montage),
+    Slice(Slice),
+    Replay(Replay),
+    Spec(Spec),
+    Bundle(BundleCommand),
 }
 
 #[derive(Debug)]
@@ -44,40 +79,385 @@ impl FromStr for Format {
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let Cli { command } = argh::from_env();
+#[derive(Debug, Clone, Copy)]
+struct TileSize {
+    width: u16,
+    height: u16,
+}
+
+impl FromStr for TileSize {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s.split_once('x').ok_or("expected WIDTHxHEIGHT")?;
+        let width = width.parse().map_err(|_| "invalid tile width")?;
+        let height = height.parse().map_err(|_| "invalid tile height")?;
+        if width == 0 || height == 0 {
+            return Err("tile size must be non-zero");
+        }
+
+        Ok(TileSize { width, height })
+    }
+}
+
+fn color_array_size(value: &str) -> Result<ColorArraySize, String> {
+    match value.parse() {
+        Ok(0) => Some(ColorArraySize::NoArray),
+        Ok(entries) => ColorArraySize::from_entries(entries),
+        Err(_) => None,
+    }
+    .ok_or_else(|| "expected 0, 16, 32, or 64".to_owned())
+}
+
+/// Failure classes, each with its own exit code, see the `error_code`s of [`Cli`].
+#[derive(Debug, Clone, Copy)]
+enum ErrorKind {
+    Usage,
+    Io,
+    InvalidInput,
+    Unsupported,
+    CheckFailed,
+}
+
+impl ErrorKind {
+    fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Usage => 2,
+            ErrorKind::Io => 3,
+            ErrorKind::InvalidInput => 4,
+            ErrorKind::Unsupported => 5,
+            ErrorKind::CheckFailed => 6,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Usage => "usage",
+            ErrorKind::Io => "io",
+            ErrorKind::InvalidInput => "invalid-input",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::CheckFailed => "check-failed",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CliError {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl CliError {
+    fn new(kind: ErrorKind, message: impl Display) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    /// Creates an error for an input the library rejected, including the chain of sources in the
+    /// message.
+    fn invalid_input(error: impl std::error::Error) -> Self {
+        let mut message = error.to_string();
+        let mut source = error.source();
+        while let Some(error) = source {
+            // the messages are sentences
+            message.truncate(message.trim_end_matches('.').len());
+            message.push_str(&format!(": {error}"));
+            source = error.source();
+        }
+        Self::new(ErrorKind::InvalidInput, message)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(error: std::io::Error) -> Self {
+        Self::new(ErrorKind::Io, error)
+    }
+}
+
+impl From<image::ImageError> for CliError {
+    fn from(error: image::ImageError) -> Self {
+        let kind = match error {
+            image::ImageError::IoError(_) => ErrorKind::Io,
+            image::ImageError::Unsupported(_) | image::ImageError::Limits(_) => {
+                ErrorKind::Unsupported
+            }
+            _ => ErrorKind::InvalidInput,
+        };
+        Self::new(kind, error)
+    }
+}
+
+/// Prints a progress message, unless the result is printed as JSON.
+macro_rules! info {
+    ($json:expr, $($arg:tt)*) => {
+        if !$json {
+            println!($($arg)*);
+        }
+    };
+}
+
+impl Command {
+    fn json(&self) -> bool {
+        match self {
+            Command::Encode(options) => options.json,
+            Command::EncodeRaw(options) => options.json,
+            Command::Decode(options) => options.json,
+            Command::DecodeRaw(options) => options.json,
+            Command::DecodeMemdump(options) => options.json,
+            Command::GrabRtt(options) => options.json,
+            Command::Compare(options) => options.json,
+            Command::Montage(options) => options.json,
+            Command::Slice(options) => options.json,
+            Command::Replay(options) => options.json,
+            Command::Spec(options) => options.json,
+            Command::Bundle(BundleCommand { command }) => match command {
+                BundleSubcommand::Verify(options) => options.json,
+            },
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (command_name, args) = args.split_first().expect("missing program name");
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
 
-    match command {
+    let Cli { command } = match Cli::from_args(&[command_name], &args) {
+        Ok(cli) => cli,
+        Err(argh::EarlyExit { output, status }) => match status {
+            // `--help`
+            Ok(()) => {
+                println!("{output}");
+                process::exit(0);
+            }
+            Err(()) => {
+                eprintln!("{output}\nRun {command_name} --help for more information.");
+                process::exit(ErrorKind::Usage.exit_code());
+            }
+        },
+    };
+
+    let json = command.json();
+    let result = match command {
         Command::Encode(options) => encode(options),
         Command::EncodeRaw(options) => encode_raw(options),
         Command::Decode(options) => decode(options),
         Command::DecodeRaw(options) => decode_raw(options),
+        Command::DecodeMemdump(options) => decode_memdump(options),
+        Command::GrabRtt(options) => grab_rtt(options),
+        Command::Compare(options) => compare(options),
+        Command::Montage(options) => montage(options),
+        Command::Slice(options) => slice(options),
+        Command::Replay(options) => replay(options),
+        Command::Spec(options) => spec(options),
+        Command::Bundle(BundleCommand { command }) => match command {
+            BundleSubcommand::Verify(options) => bundle_verify(options),
+        },
+    };
+
+    match result {
+        Ok(result) => {
+            if json {
+                println!("{result}");
+            }
+        }
+        Err(CliError { kind, message }) => {
+            if json {
+                let error = json!({
+                    "error": { "kind": kind.name(), "code": kind.exit_code(), "message": message }
+                });
+                println!("{error}");
+            } else {
+                eprintln!("error: {message}");
+            }
+            process::exit(kind.exit_code());
+        }
+    }
+}
+
+/// Returns the size of the encoded image relative to the raw RGB565 pixels.
+fn compression_ratio(encoded_len: usize, width: u16, height: u16) -> f64 {
+    encoded_len as f64 / (2.0 * f64::from(width) * f64::from(height))
+}
+
+/// Returns the JSON result of an encode.
+fn encode_result(input: &str, output: &str, config: &Config, encoded: &[u8]) -> Value {
+    // the encoders only produce valid headers
+    let (header, _) = q565::decode::Q565DecodeContext::decode_header(encoded).unwrap();
+    json!({
+        "input": input,
+        "output": output,
+        "config": config.path,
+        "width": header.width,
+        "height": header.height,
+        "color_array": header.color_array_size.entries(),
+        "raw": header.raw,
+        "size": encoded.len(),
+        "ratio": compression_ratio(encoded.len(), header.width, header.height),
+    })
+}
+
+/// Builds the encoder shared by the encode subcommands, from the command line or the config file.
+fn encoder(
+    config: &Config,
+    speed: Option<Speed>,
+    color_array: Option<ColorArraySize>,
+    version: Option<u8>,
+    dither: Option<Dither>,
+    quantization: Option<Quantization>,
+) -> Result<Encoder, CliError> {
+    let color_array = match (color_array, config.color_array) {
+        (Some(color_array), _) => color_array,
+        (None, Some(entries)) => color_array_size(&entries.to_string())
+            .map_err(|e| CliError::new(ErrorKind::Usage, format!("color-array: {e}")))?,
+        (None, None) => ColorArraySize::Entries64,
+    };
+
+    let version = match version.or(config.encoder_version) {
+        Some(number) => EncoderVersion::from_number(number).ok_or_else(|| {
+            CliError::new(
+                ErrorKind::Usage,
+                format!("unknown encoder version {number}"),
+            )
+        })?,
+        None => EncoderVersion::default(),
+    };
+
+    Ok(Encoder::new()
+        .speed(speed.or(config.speed).unwrap_or_default())
+        .dither(dither.or(config.dither).unwrap_or_default())
+        .quantization(quantization.or(config.quantization).unwrap_or_default())
+        .color_array_size(color_array)
+        .version(version))
+}
+
+/// Returns the output path given on the command line, or else the one from the config.
+fn output_path(
+    config: &Config,
+    output: Option<String>,
+    input: &str,
+    width: u16,
+    height: u16,
+) -> String {
+    output.unwrap_or_else(|| {
+        config
+            .output_path(input, width, height)
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+/// Writes the encoded image to `output`, or with `--check`, fails if `output` doesn't contain
+/// exactly the encoded image, e.g. to detect stale committed assets in CI.
+///
+/// The encoders are deterministic, so an up-to-date output only differs if it was encoded with
+/// other settings or a different encoder version.
+fn write_or_check(json: bool, check: bool, output: &str, encoded: &[u8]) -> Result<(), CliError> {
+    if !check {
+        if let Some(dir) = Path::new(output).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(output, encoded)?;
+        info!(json, "Written {} bytes to `{output}`", encoded.len());
+        return Ok(());
     }
+
+    let existing = match std::fs::read(output) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(CliError::new(
+                ErrorKind::CheckFailed,
+                format!("`{output}` does not exist"),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if existing != encoded {
+        let same_pixels =
+            q565::diff::compare(&existing, encoded).is_ok_and(|report| report.is_identical());
+        let message = if same_pixels {
+            format!("`{output}` has the same pixels, but is encoded differently")
+        } else {
+            format!("`{output}` is out of date")
+        };
+        return Err(CliError::new(ErrorKind::CheckFailed, message));
+    }
+
+    info!(json, "`{output}` is up to date");
+    Ok(())
 }
 
 /// Encodes an image as Q565.
+///
+/// Defaults for the options are read from the nearest `q565.toml` in the working directory or its
+/// parents.
 #[derive(FromArgs)]
 #[argh(subcommand, name = "encode")]
 struct Encode {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
+    /// don't write the output, but fail if the existing output differs from the encoded image
+    #[argh(switch)]
+    check: bool,
+    /// config file to use instead of the nearest `q565.toml`
+    #[argh(option)]
+    config: Option<PathBuf>,
     /// input format, optional (png, jpg, bmp)
     #[argh(option)]
     format: Option<Format>,
+    /// number of color array entries (16, 32, 64, or 0 for none), defaults to 64
+    #[argh(option, from_str_fn(color_array_size))]
+    color_array: Option<ColorArraySize>,
+    /// dithering when converting to RGB565 (none, ordered), defaults to none
+    #[argh(option)]
+    dither: Option<Dither>,
+    /// quantization when converting to RGB565 without dithering (truncate, round, luma, linear),
+    /// defaults to round
+    #[argh(option)]
+    quantization: Option<Quantization>,
+    /// encoder speed preset (fast, default, best), defaults to default
+    #[argh(option)]
+    speed: Option<Speed>,
+    /// encoder version, to keep the output of an older encoder byte-identical, defaults to 1
+    #[argh(option)]
+    encoder_version: Option<u8>,
 
     /// the input file. If none of the raw flags are set, this may be a PNG, JPG, or BMP.
     #[argh(positional)]
     input: String,
-    /// the output file
+    /// the output file, defaults to the `output-dir` and `naming` of the config
     #[argh(positional)]
-    output: String,
+    output: Option<String>,
 }
 
-fn encode(options: Encode) -> Result<(), Box<dyn std::error::Error>> {
+fn encode(options: Encode) -> Result<Value, CliError> {
     let Encode {
+        json,
+        check,
+        config,
         format,
+        color_array,
+        dither,
+        quantization,
+        speed,
+        encoder_version,
         input,
         output,
     } = options;
 
+    let config = Config::load(config.as_deref())?;
+    let encoder = encoder(
+        &config,
+        speed,
+        color_array,
+        encoder_version,
+        dither,
+        quantization,
+    )?;
+
     let image = match format {
         Some(Format::Png) => {
             image::io::Reader::with_format(BufReader::new(File::open(&input)?), ImageFormat::Png)
@@ -91,7 +471,7 @@ fn encode(options: Encode) -> Result<(), Box<dyn std::error::Error>> {
             image::io::Reader::with_format(BufReader::new(File::open(&input)?), ImageFormat::Bmp)
                 .decode()?
         }
-        None => image::io::Reader::open(input)?
+        None => image::io::Reader::open(&input)?
             .with_guessed_format()?
             .decode()?,
     };
@@ -99,100 +479,137 @@ fn encode(options: Encode) -> Result<(), Box<dyn std::error::Error>> {
     let width = image.width();
     let height = image.height();
 
-    println!("Encoding {width}x{height} image");
+    info!(json, "Encoding {width}x{height} image");
 
     if width > u16::MAX as u32 || height > u16::MAX as u32 {
-        return Err("image dimensions are too large".into());
+        return Err(CliError::new(
+            ErrorKind::Unsupported,
+            "image dimensions are too large",
+        ));
     }
+    let (width, height) = (width as u16, height as u16);
 
-    let rgb565_raw = image
-        .into_rgb8()
-        .pixels()
-        .map(|p| encode_rgb565_unchecked(rgb888_to_rgb565(p.0)))
-        .collect::<Vec<_>>();
+    let rgb888: Vec<[u8; 3]> = image.into_rgb8().pixels().map(|p| p.0).collect();
+    let mut v = Vec::new();
+    encoder
+        .encode_rgb888(width, height, &rgb888, &mut v)
+        .expect("the image has width * height pixels");
 
-    let mut v = Vec::with_capacity(1024 * 1024);
-    assert!(q565::encode::Q565EncodeContext::encode_to_vec(
-        width as u16,
-        height as u16,
-        &rgb565_raw,
-        &mut v
-    ));
-
-    std::fs::write(&output, &v)?;
-    println!("Written {} bytes to `{output}`", v.len());
+    let output = output_path(&config, output, &input, width, height);
+    write_or_check(json, check, &output, &v)?;
 
-    Ok(())
+    Ok(encode_result(&input, &output, &config, &v))
 }
 
 /// Encodes a raw RGB565LE image as Q565.
+///
+/// Defaults for the options are read from the nearest `q565.toml` in the working directory or its
+/// parents.
 #[derive(FromArgs)]
 #[argh(subcommand, name = "encode-raw")]
 struct EncodeRaw {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
+    /// don't write the output, but fail if the existing output differs from the encoded image
+    #[argh(switch)]
+    check: bool,
+    /// config file to use instead of the nearest `q565.toml`
+    #[argh(option)]
+    config: Option<PathBuf>,
     /// image width
     #[argh(option)]
     width: NonZeroU16,
     /// image height
     #[argh(option)]
     height: NonZeroU16,
+    /// number of color array entries (16, 32, 64, or 0 for none), defaults to 64
+    #[argh(option, from_str_fn(color_array_size))]
+    color_array: Option<ColorArraySize>,
+    /// encoder speed preset (fast, default, best), defaults to default
+    #[argh(option)]
+    speed: Option<Speed>,
+    /// encoder version, to keep the output of an older encoder byte-identical, defaults to 1
+    #[argh(option)]
+    encoder_version: Option<u8>,
 
     /// the input file. If none of the raw flags are set, this may be a PNG, JPG, or BMP.
     #[argh(positional)]
     input: String,
-    /// the output file
+    /// the output file, defaults to the `output-dir` and `naming` of the config
     #[argh(positional)]
-    output: String,
+    output: Option<String>,
 }
 
-fn encode_raw(options: EncodeRaw) -> Result<(), Box<dyn std::error::Error>> {
+fn encode_raw(options: EncodeRaw) -> Result<Value, CliError> {
     let EncodeRaw {
+        json,
+        check,
+        config,
         width,
         height,
+        color_array,
+        speed,
+        encoder_version,
         input,
         output,
     } = options;
 
-    println!("Encoding {width}x{height} image");
+    let config = Config::load(config.as_deref())?;
+    let encoder = encoder(&config, speed, color_array, encoder_version, None, None)?;
+
+    info!(json, "Encoding {width}x{height} image");
+
+    let rgb565_raw = std::fs::read(&input)?;
+    if rgb565_raw.len() % 2 != 0 {
+        return Err(CliError::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "input file size is odd ({} bytes), expected 2 bytes per pixel",
+                rgb565_raw.len()
+            ),
+        ));
+    }
+    let expected_size = 2 * width.get() as usize * height.get() as usize;
+    if rgb565_raw.len() != expected_size {
+        return Err(CliError::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "input file size is not correct, expected {} bytes, got {}",
+                expected_size,
+                rgb565_raw.len()
+            ),
+        ));
+    }
 
-    let rgb565_raw = std::fs::read(input)?;
     let rgb565_raw: Vec<_> = rgb565_raw
         .chunks_exact(2)
         .map(|c| {
             let &[a, b] = c else { unreachable!() };
 
-            u16::from_le_bytes([a, b])
+            u16::from_ne_bytes([a, b])
         })
         .collect();
 
-    let expected_size = width.get() as usize * height.get() as usize;
-    if rgb565_raw.len() != expected_size {
-        return Err(format!(
-            "input file size is not correct, expected {} bytes, got {}",
-            expected_size,
-            rgb565_raw.len()
-        )
-        .into());
-    }
+    let mut v = Vec::new();
+    encoder
+        .byte_order::<LittleEndian>()
+        .encode(width.get(), height.get(), &rgb565_raw, &mut v)
+        .expect("the size was checked");
 
-    let mut v = Vec::with_capacity(1024 * 1024);
-
-    assert!(q565::encode::Q565EncodeContext::encode_to_vec(
-        width.get(),
-        height.get(),
-        &rgb565_raw,
-        &mut v
-    ));
+    let output = output_path(&config, output, &input, width.get(), height.get());
+    write_or_check(json, check, &output, &v)?;
 
-    std::fs::write(&output, &v)?;
-    println!("Written {} bytes to `{output}`", v.len());
-
-    Ok(())
+    Ok(encode_result(&input, &output, &config, &v))
 }
 
 /// Decodes a Q565 image into a raw RGB565LE image.
 #[derive(FromArgs)]
 #[argh(subcommand, name = "decode")]
 struct Decode {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
     /// output format (png, jpg, bmp)
     #[argh(option)]
     format: Format,
@@ -205,8 +622,9 @@ struct Decode {
     output: String,
 }
 
-fn decode(options: Decode) -> Result<(), Box<dyn std::error::Error>> {
+fn decode(options: Decode) -> Result<Value, CliError> {
     let Decode {
+        json,
         format,
         input,
         output,
@@ -214,15 +632,28 @@ fn decode(options: Decode) -> Result<(), Box<dyn std::error::Error>> {
 
     let q565_input = std::fs::read(&input)?;
 
-    println!("Decoding `{input}`");
+    info!(json, "Decoding `{input}`");
+
+    let header = save_decoded(&q565_input, &output, format)?;
+    let q565::HeaderInfo { width, height, .. } = header;
+
+    info!(json, "Written {width}x{height} image to `{output}`");
+
+    Ok(decode_result(&input, &output, &header, q565_input.len()))
+}
 
+/// Decodes a Q565 image and saves it in the given format.
+fn save_decoded(
+    q565_input: &[u8],
+    output: &str,
+    format: Format,
+) -> Result<q565::HeaderInfo, CliError> {
     let mut v = Vec::with_capacity(1024 * 1024);
-    let (q565::HeaderInfo { width, height }, _) =
-        q565::decode::Q565DecodeContext::decode::<BigEndian>(
-            &q565_input,
-            q565::decode::VecDecodeOutput::<Rgb888>::new(&mut v),
-        )
-        .map_err(|e| format!("{e:?}"))?;
+    let (header, _) = q565::decode::Q565DecodeContext::decode::<BigEndian>(
+        q565_input,
+        q565::decode::VecDecodeOutput::<Rgb888>::new(&mut v),
+    )
+    .map_err(CliError::invalid_input)?;
 
     let len = v.len();
     let cap = v.capacity();
@@ -230,26 +661,52 @@ fn decode(options: Decode) -> Result<(), Box<dyn std::error::Error>> {
     std::mem::forget(v);
     let v = unsafe { Vec::from_raw_parts(raw.cast::<u8>(), len * 3, cap * 3) };
 
-    RgbImage::from_vec(width as u32, height as u32, v)
-        .ok_or("failed to create image")?
+    save_rgb(header.width, header.height, v, output, format)?;
+    Ok(header)
+}
+
+/// Saves RGB888 pixels in the given format.
+fn save_rgb(
+    width: u16,
+    height: u16,
+    rgb888: Vec<u8>,
+    output: &str,
+    format: Format,
+) -> Result<(), CliError> {
+    RgbImage::from_vec(width as u32, height as u32, rgb888)
+        .ok_or_else(|| CliError::new(ErrorKind::Unsupported, "failed to create image"))?
         .save_with_format(
-            &output,
+            output,
             match format {
                 Format::Png => ImageFormat::Png,
                 Format::Jpg => ImageFormat::Jpeg,
                 Format::Bmp => ImageFormat::Bmp,
             },
         )?;
-
-    println!("Written {width}x{height} image to `{output}`");
-
     Ok(())
 }
 
+/// Returns the JSON result of a decode.
+fn decode_result(input: &str, output: &str, header: &q565::HeaderInfo, input_len: usize) -> Value {
+    json!({
+        "input": input,
+        "output": output,
+        "width": header.width,
+        "height": header.height,
+        "color_array": header.color_array_size.entries(),
+        "raw": header.raw,
+        "size": input_len,
+        "ratio": compression_ratio(input_len, header.width, header.height),
+    })
+}
+
 /// Decodes a Q565 image into raw RGB565LE bytes.
 #[derive(FromArgs)]
 #[argh(subcommand, name = "decode-raw")]
 struct DecodeRaw {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
     /// the input file
     #[argh(positional)]
     input: String,
@@ -258,25 +715,904 @@ struct DecodeRaw {
     output: String,
 }
 
-fn decode_raw(options: DecodeRaw) -> Result<(), Box<dyn std::error::Error>> {
-    let DecodeRaw { input, output } = options;
+fn decode_raw(options: DecodeRaw) -> Result<Value, CliError> {
+    let DecodeRaw {
+        json,
+        input,
+        output,
+    } = options;
 
     let q565_input = std::fs::read(&input)?;
 
-    println!("Decoding `{input}`");
+    info!(json, "Decoding `{input}`");
 
     let mut v = Vec::with_capacity(1024 * 1024);
-    let (q565::HeaderInfo { width, height }, _) =
-        q565::decode::Q565DecodeContext::decode::<LittleEndian>(
-            &q565_input,
-            q565::decode::VecDecodeOutput::<Rgb565>::new(&mut v),
-        )
-        .map_err(|e| format!("{e:?}"))?;
+    let (header, _) = q565::decode::Q565DecodeContext::decode::<LittleEndian>(
+        &q565_input,
+        q565::decode::VecDecodeOutput::<Rgb565>::new(&mut v),
+    )
+    .map_err(CliError::invalid_input)?;
+    let q565::HeaderInfo { width, height, .. } = header;
 
     let bytes = unsafe { std::slice::from_raw_parts(v.as_ptr().cast::<u8>(), v.len() * 2) };
     std::fs::write(&output, bytes)?;
 
-    println!("Written {width}x{height} image to `{output}`");
+    info!(json, "Written {width}x{height} image to `{output}`");
 
-    Ok(())
+    Ok(decode_result(&input, &output, &header, q565_input.len()))
+}
+
+/// Decodes a memory dump, e.g. from a debugger, holding either a Q565 image or a raw RGB565
+/// framebuffer. Q565 images are detected by their magic bytes, anything else is taken as raw
+/// pixels and needs `--width` and `--height`.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "decode-memdump")]
+struct DecodeMemdump {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
+    /// width of a raw framebuffer
+    #[argh(option)]
+    width: Option<NonZeroU16>,
+    /// height of a raw framebuffer
+    #[argh(option)]
+    height: Option<NonZeroU16>,
+    /// pixels from the start of one row of a raw framebuffer to the next, defaults to the width
+    #[argh(option)]
+    stride: Option<NonZeroU16>,
+    /// the pixels of a raw framebuffer are big endian, e.g. because they are sent to the display
+    /// as is
+    #[argh(switch)]
+    big_endian: bool,
+    /// byte offset of the image in the dump, decimal or `0x` hex, defaults to 0
+    #[argh(option, from_str_fn(dump_offset), default = "0")]
+    offset: usize,
+    /// output format (png, jpg, bmp), png by default
+    #[argh(option, default = "Format::Png")]
+    format: Format,
+
+    /// the dump file
+    #[argh(positional)]
+    input: String,
+    /// the output file
+    #[argh(positional)]
+    output: String,
+}
+
+fn dump_offset(value: &str) -> Result<usize, String> {
+    match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|_| "expected a decimal or `0x` hex offset".to_owned())
+}
+
+fn decode_memdump(options: DecodeMemdump) -> Result<Value, CliError> {
+    let DecodeMemdump {
+        json,
+        width,
+        height,
+        stride,
+        big_endian,
+        offset,
+        format,
+        input,
+        output,
+    } = options;
+
+    let dump = std::fs::read(&input)?;
+    let data = dump.get(offset..).ok_or_else(|| {
+        CliError::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "offset {offset:#x} is past the end of the {} byte dump",
+                dump.len()
+            ),
+        )
+    })?;
+
+    let is_q565 = data.starts_with(q565::MAGIC) || data.starts_with(q565::EXTENDED_MAGIC);
+    let (width, height) = if is_q565 {
+        info!(json, "Decoding Q565 image at {offset:#x} of `{input}`");
+
+        // anything after the image, like erased flash, is ignored by the decoder
+        let header = save_decoded(data, &output, format)?;
+        (header.width, header.height)
+    } else {
+        let (Some(width), Some(height)) = (width, height) else {
+            return Err(CliError::new(
+                ErrorKind::Usage,
+                "no Q565 image found at the offset, `--width` and `--height` are needed to \
+                 decode raw pixels",
+            ));
+        };
+        let (width, height) = (width.get(), height.get());
+        let stride = stride.map_or(width, NonZeroU16::get);
+        if stride < width {
+            return Err(CliError::new(
+                ErrorKind::Usage,
+                "the stride is smaller than the width",
+            ));
+        }
+
+        info!(
+            json,
+            "Decoding raw {width}x{height} framebuffer at {offset:#x} of `{input}`"
+        );
+
+        let pixels: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|c| {
+                let &[a, b] = c else { unreachable!() };
+
+                if big_endian {
+                    u16::from_be_bytes([a, b])
+                } else {
+                    u16::from_le_bytes([a, b])
+                }
+            })
+            .collect();
+        // the dump may well continue after the framebuffer
+        let source =
+            StridedPixels::new(&pixels, width, height, usize::from(stride)).ok_or_else(|| {
+                CliError::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "the dump is too short for a {width}x{height} framebuffer at offset \
+                         {offset:#x}"
+                    ),
+                )
+            })?;
+
+        let rgb888 = source
+            .rows()
+            .flatten()
+            .flat_map(|&pixel| rgb565_to_rgb888(decode_565(pixel)))
+            .collect();
+        save_rgb(width, height, rgb888, &output, format)?;
+        (width, height)
+    };
+
+    info!(json, "Written {width}x{height} image to `{output}`");
+
+    Ok(json!({
+        "input": input,
+        "output": output,
+        "offset": offset,
+        "q565": is_q565,
+        "width": width,
+        "height": height,
+    }))
+}
+
+/// Grabs a screenshot sent by a device with `q565::screenshot::send`, e.g. over RTT.
+///
+/// Reads the RTT up channel through the first debug probe found, given the ELF file the device
+/// runs and its chip (`--elf`, `--chip`, needs the `probe-rs` feature). Otherwise reads the
+/// channel output from the RTT TCP server of a debugger (e.g. OpenOCD's `rtt server start`), or
+/// from a file it was dumped into. Stops once a complete screenshot arrived.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "grab-rtt")]
+struct GrabRtt {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
+    /// the ELF file the device runs, to find its RTT control block
+    #[argh(option)]
+    elf: Option<String>,
+    /// the probe-rs name of the chip, e.g. `RP2040`, with `--elf`
+    #[argh(option)]
+    chip: Option<String>,
+    /// the RTT up channel, 0 by default, with `--elf`
+    #[argh(option, default = "0")]
+    channel: usize,
+    /// address of the RTT TCP server, e.g. `localhost:9090`
+    #[argh(option)]
+    tcp: Option<String>,
+    /// file holding the channel output, or `-` for stdin
+    #[argh(option)]
+    input: Option<String>,
+    /// output format (png, jpg, bmp), png by default
+    #[argh(option, default = "Format::Png")]
+    format: Format,
+
+    /// the output file
+    #[argh(positional)]
+    output: String,
+}
+
+fn grab_rtt(options: GrabRtt) -> Result<Value, CliError> {
+    let GrabRtt {
+        json,
+        elf,
+        chip,
+        channel,
+        tcp,
+        input,
+        format,
+        output,
+    } = options;
+
+    let (input, mut reader): (String, Box<dyn Read>) = match (elf, tcp, input) {
+        (Some(elf), None, None) => {
+            let chip = chip.ok_or_else(|| CliError::new(ErrorKind::Usage, "expected `--chip`"))?;
+            (elf.clone(), rtt_reader(&elf, &chip, channel)?)
+        }
+        (None, Some(address), None) => {
+            let stream = TcpStream::connect(&address)?;
+            (address, Box::new(stream))
+        }
+        (None, None, Some(input)) if input == "-" => (input, Box::new(std::io::stdin())),
+        (None, None, Some(input)) => {
+            let file = File::open(&input)?;
+            (input, Box::new(file))
+        }
+        _ => {
+            return Err(CliError::new(
+                ErrorKind::Usage,
+                "expected one of `--elf`, `--tcp`, or `--input`",
+            ))
+        }
+    };
+
+    info!(json, "Waiting for a screenshot from `{input}`");
+
+    let mut received = Vec::new();
+    let mut buf = [0; 4096];
+    let q565_input = loop {
+        if let Some((image, _)) = q565::screenshot::find(&received) {
+            break image.to_vec();
+        }
+        let len = reader.read(&mut buf)?;
+        if len == 0 {
+            return Err(CliError::new(
+                ErrorKind::InvalidInput,
+                "the input ended before a complete screenshot was received",
+            ));
+        }
+        received.extend_from_slice(&buf[..len]);
+    };
+
+    let header = save_decoded(&q565_input, &output, format)?;
+    let q565::HeaderInfo { width, height, .. } = header;
+
+    info!(json, "Written {width}x{height} screenshot to `{output}`");
+
+    Ok(decode_result(&input, &output, &header, q565_input.len()))
+}
+
+#[cfg(feature = "probe-rs")]
+fn rtt_reader(elf: &str, chip: &str, channel: usize) -> Result<Box<dyn Read>, CliError> {
+    let reader = rtt::RttReader::attach(&std::fs::read(elf)?, chip, channel)?;
+    Ok(Box::new(reader))
+}
+
+#[cfg(not(feature = "probe-rs"))]
+fn rtt_reader(_elf: &str, _chip: &str, _channel: usize) -> Result<Box<dyn Read>, CliError> {
+    Err(CliError::new(
+        ErrorKind::Unsupported,
+        "`--elf` needs the `probe-rs` feature, e.g. `cargo install q565-cli --features probe-rs`",
+    ))
+}
+
+/// Compares two Q565 images.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "compare")]
+struct Compare {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
+    /// exit with an error if the images differ
+    #[argh(switch)]
+    check: bool,
+
+    /// the first image
+    #[argh(positional)]
+    a: String,
+    /// the second image
+    #[argh(positional)]
+    b: String,
+}
+
+fn compare(options: Compare) -> Result<Value, CliError> {
+    let Compare { json, check, a, b } = options;
+
+    let report = q565::diff::compare(&std::fs::read(&a)?, &std::fs::read(&b)?)
+        .map_err(CliError::invalid_input)?;
+    let [r, g, b] = report.rmse;
+    let [psnr_r, psnr_g, psnr_b] = report.psnr;
+
+    info!(json, "Compared {}x{} images", report.width, report.height);
+    info!(
+        json,
+        "Changed pixels: {} of {}",
+        report.changed_count,
+        report.changed.len()
+    );
+    info!(json, "RMSE (r/g/b): {r:.3} / {g:.3} / {b:.3}");
+    info!(
+        json,
+        "PSNR (r/g/b): {psnr_r:.2} / {psnr_g:.2} / {psnr_b:.2} dB"
+    );
+    info!(json, "SSIM (mean): {:.4}", report.mean_ssim());
+
+    if check && !report.is_identical() {
+        return Err(CliError::new(ErrorKind::CheckFailed, "images differ"));
+    }
+
+    // infinite PSNRs of identical channels are written as `null`
+    Ok(json!({
+        "width": report.width,
+        "height": report.height,
+        "changed_pixels": report.changed_count,
+        "total_pixels": report.changed.len(),
+        "identical": report.is_identical(),
+        "rmse": report.rmse,
+        "psnr": report.psnr,
+        "ssim": report.mean_ssim(),
+    }))
+}
+
+/// Stitches multiple Q565 images into one, e.g. to build sprite sheets.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "montage")]
+struct Montage {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
+    /// stack the images top to bottom instead of left to right
+    #[argh(switch)]
+    vertical: bool,
+
+    /// the output file
+    #[argh(positional)]
+    output: String,
+    /// the input files
+    #[argh(positional)]
+    inputs: Vec<String>,
+}
+
+fn montage(options: Montage) -> Result<Value, CliError> {
+    let Montage {
+        json,
+        vertical,
+        output,
+        inputs,
+    } = options;
+
+    let images = inputs
+        .iter()
+        .map(std::fs::read)
+        .collect::<Result<Vec<_>, _>>()?;
+    let images: Vec<&[u8]> = images.iter().map(Vec::as_slice).collect();
+
+    info!(json, "Stitching {} images", images.len());
+
+    let v = if vertical {
+        q565::edit::vconcat(&images)
+    } else {
+        q565::edit::hconcat(&images)
+    }
+    .map_err(CliError::invalid_input)?;
+
+    std::fs::write(&output, &v)?;
+    info!(json, "Written {} bytes to `{output}`", v.len());
+
+    let (header, _) =
+        q565::decode::Q565DecodeContext::decode_header(&v).map_err(CliError::invalid_input)?;
+    Ok(json!({
+        "inputs": inputs,
+        "output": output,
+        "width": header.width,
+        "height": header.height,
+        "size": v.len(),
+    }))
+}
+
+/// Cuts a Q565 sprite sheet into tiles, written as separate files and/or one bundle.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "slice")]
+struct Slice {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
+    /// tile size, e.g. `32x32`
+    #[argh(option)]
+    tile: TileSize,
+    /// output directory for the individual tiles (`tile_<index>.q565`)
+    #[argh(option)]
+    out: Option<String>,
+    /// output file for a bundle containing all tiles
+    #[argh(option)]
+    bundle: Option<String>,
+    /// store a CRC-32 per tile and a SHA-256 in the bundle, see `bundle verify`
+    #[argh(switch)]
+    checksums: bool,
+
+    /// the sprite sheet
+    #[argh(positional)]
+    input: String,
+}
+
+fn slice(options: Slice) -> Result<Value, CliError> {
+    let Slice {
+        json,
+        tile,
+        out,
+        bundle,
+        checksums,
+        input,
+    } = options;
+
+    if out.is_none() && bundle.is_none() {
+        return Err(CliError::new(
+            ErrorKind::Usage,
+            "either --out or --bundle is required",
+        ));
+    }
+
+    let tiles = q565::edit::slice(&std::fs::read(&input)?, tile.width, tile.height)
+        .map_err(CliError::invalid_input)?;
+    info!(json, "Cut `{input}` into {} tiles", tiles.len());
+
+    if let Some(out) = &out {
+        let out = std::path::Path::new(out);
+        std::fs::create_dir_all(out)?;
+        for (index, tile) in tiles.iter().enumerate() {
+            std::fs::write(out.join(format!("tile_{index}.q565")), tile)?;
+        }
+        info!(json, "Written {} tiles to `{}`", tiles.len(), out.display());
+    }
+
+    let mut bundle_size = None;
+    if let Some(bundle) = &bundle {
+        let entries: Vec<&[u8]> = tiles.iter().map(Vec::as_slice).collect();
+        let mut writer = q565::bundle::BundleWriter::new();
+        if checksums {
+            writer = writer.with_crcs().with_digest();
+        }
+        let v = writer
+            .write(&entries)
+            .map_err(|e| CliError::new(ErrorKind::Unsupported, e))?;
+        std::fs::write(bundle, &v)?;
+        info!(json, "Written {} bytes to `{bundle}`", v.len());
+        bundle_size = Some(v.len());
+    }
+
+    Ok(json!({
+        "input": input,
+        "tile_width": tile.width,
+        "tile_height": tile.height,
+        "tiles": tiles.len(),
+        "tile_sizes": tiles.iter().map(Vec::len).collect::<Vec<_>>(),
+        "out": out,
+        "bundle": bundle,
+        "bundle_size": bundle_size,
+    }))
+}
+
+/// Works with bundles of Q565 images.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "bundle")]
+struct BundleCommand {
+    #[argh(subcommand)]
+    command: BundleSubcommand,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum BundleSubcommand {
+    Verify(BundleVerify),
+}
+
+/// Checks a bundle against its SHA-256 and CRC-32s, e.g. after reading it back from flash.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "verify")]
+struct BundleVerify {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
+    /// fail if the bundle has no checksums to verify
+    #[argh(switch)]
+    require_checksums: bool,
+
+    /// the bundle
+    #[argh(positional)]
+    input: String,
+}
+
+fn bundle_verify(options: BundleVerify) -> Result<Value, CliError> {
+    use q565::bundle::{Bundle, BundleError};
+
+    let BundleVerify {
+        json,
+        require_checksums,
+        input,
+    } = options;
+
+    let data = std::fs::read(&input)?;
+    let bundle = Bundle::new(&data).map_err(CliError::invalid_input)?;
+    if require_checksums && !bundle.has_crcs() && !bundle.has_digest() {
+        return Err(CliError::new(
+            ErrorKind::CheckFailed,
+            format!("`{input}` has no checksums"),
+        ));
+    }
+
+    bundle.verify().map_err(|e| match e {
+        BundleError::CorruptEntry { index } => CliError::new(
+            ErrorKind::CheckFailed,
+            format!("`{input}`: entry {index} doesn't match its CRC-32"),
+        ),
+        BundleError::CorruptBundle => CliError::new(
+            ErrorKind::CheckFailed,
+            format!("`{input}` doesn't match its SHA-256"),
+        ),
+        e => CliError::invalid_input(e),
+    })?;
+
+    let checked = match (bundle.has_digest(), bundle.has_crcs()) {
+        (true, true) => "SHA-256 and CRC-32s",
+        (true, false) => "SHA-256",
+        (false, true) => "CRC-32s",
+        (false, false) => "nothing, no checksums",
+    };
+    info!(
+        json,
+        "`{input}`: {} entries, checked {checked}",
+        bundle.len()
+    );
+
+    Ok(json!({
+        "input": input,
+        "entries": bundle.len(),
+        "digest": bundle.has_digest(),
+        "crcs": bundle.has_crcs(),
+    }))
+}
+
+/// Replays a Q565 image op by op, printing each op with the pixels it produces and how it changes
+/// the decoder state, e.g. to find where a decoder in hardware goes wrong.
+///
+/// States are read and written in the text format of `q565::snapshot::StateSnapshot`.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "replay")]
+struct Replay {
+    /// print the result (or error) as JSON on stdout, including every op
+    #[argh(switch)]
+    json: bool,
+    /// state to start from instead of the initial one, e.g. for a frame of a frame sequence
+    #[argh(option)]
+    state: Option<String>,
+    /// stop after the op that produces this pixel index
+    #[argh(option)]
+    until: Option<usize>,
+    /// print the whole state after every op, instead of only the changed color array entries
+    #[argh(switch)]
+    full: bool,
+    /// fail if the state after the replay differs from this one, e.g. a dump of another decoder
+    #[argh(option)]
+    expect: Option<String>,
+
+    /// the Q565 image
+    #[argh(positional)]
+    input: String,
+}
+
+fn read_snapshot(path: &str) -> Result<StateSnapshot, CliError> {
+    std::fs::read_to_string(path)?
+        .parse()
+        .map_err(|e| CliError::new(ErrorKind::InvalidInput, format!("`{path}`: {e}")))
+}
+
+fn replay(options: Replay) -> Result<Value, CliError> {
+    let data = std::fs::read(&options.input)?;
+    let (header, ops) =
+        q565::decode::Q565DecodeContext::decode_header(&data).map_err(CliError::invalid_input)?;
+    if header.raw {
+        return Err(CliError::new(
+            ErrorKind::Unsupported,
+            format!("`{}` is a raw image, which has no ops", options.input),
+        ));
+    }
+
+    let header_len = data.len() - ops.len();
+    match header.color_array_size {
+        ColorArraySize::NoArray | ColorArraySize::Entries16 => {
+            replay_ops::<16>(options, &header, header_len, ops)
+        }
+        ColorArraySize::Entries32 => replay_ops::<32>(options, &header, header_len, ops),
+        ColorArraySize::Entries64 => replay_ops::<64>(options, &header, header_len, ops),
+    }
+}
+
+fn replay_ops<const N: usize>(
+    options: Replay,
+    header: &q565::HeaderInfo,
+    header_len: usize,
+    ops: &[u8],
+) -> Result<Value, CliError> {
+    let Replay {
+        json,
+        state,
+        until,
+        full,
+        expect,
+        input,
+    } = options;
+
+    let mut ctx = match &state {
+        Some(path) => {
+            let snapshot = read_snapshot(path)?;
+            q565::decode::Q565DecodeContext::<N>::restore(&snapshot).ok_or_else(|| {
+                CliError::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "`{path}` has {} color array entries, but the image uses {N}",
+                        snapshot.color_array().len()
+                    ),
+                )
+            })?
+        }
+        None => q565::decode::Q565DecodeContext::<N>::new_sized(),
+    };
+
+    info!(
+        json,
+        "`{input}`: {}x{} image, {N} color array entries", header.width, header.height
+    );
+    info!(json, "{}", ctx.snapshot());
+
+    let pixel_count = header.pixel_count().unwrap_or(usize::MAX);
+    let mut steps = Vec::new();
+    let mut position = 0;
+    let mut reader = q565::stream::OpReader::new(ops);
+    for (offset, op) in &mut reader {
+        let before = ctx.snapshot();
+        let (color, count) = ctx.apply_op(op);
+        let after = ctx.snapshot();
+        let changes: Vec<_> = after.changed_entries(&before).collect();
+
+        let offset = header_len + offset;
+        if !json {
+            let repeat = if count > 1 {
+                format!(" x{count}")
+            } else {
+                String::new()
+            };
+            println!("{offset:#07x} pixel {position:>7}: {op:?} -> {color:#06x}{repeat}");
+            if full {
+                println!("{after}");
+            } else {
+                for (index, new, old) in &changes {
+                    println!("    arr[{index}]: {old:#06x} -> {new:#06x}");
+                }
+            }
+        } else {
+            steps.push(json!({
+                "offset": offset,
+                "pixel": position,
+                "op": format!("{op:?}"),
+                "color": color,
+                "count": count,
+                "changes": changes
+                    .iter()
+                    .map(|(index, new, old)| json!({ "index": index, "old": old, "new": new }))
+                    .collect::<Vec<_>>(),
+            }));
+        }
+
+        position += count;
+        if until.is_some_and(|until| position > until) {
+            break;
+        }
+    }
+
+    let snapshot = ctx.snapshot();
+    if !full {
+        info!(json, "{snapshot}");
+    }
+    if until.is_none() && position != pixel_count {
+        info!(
+            json,
+            "warning: the ops produced {position} pixels, the header claims {pixel_count}"
+        );
+    }
+
+    if let Some(path) = &expect {
+        let expected = read_snapshot(path)?;
+        let mut mismatches = Vec::new();
+        if snapshot.prev != expected.prev {
+            mismatches.push(format!(
+                "prev: {:#06x}, expected {:#06x}",
+                snapshot.prev, expected.prev
+            ));
+        }
+        for (index, actual, expected) in snapshot.changed_entries(&expected) {
+            mismatches.push(format!(
+                "arr[{index}]: {actual:#06x}, expected {expected:#06x}"
+            ));
+        }
+        if !mismatches.is_empty() {
+            return Err(CliError::new(
+                ErrorKind::CheckFailed,
+                format!(
+                    "the state differs from `{path}`:\n{}",
+                    mismatches.join("\n")
+                ),
+            ));
+        }
+        info!(json, "The state matches `{path}`");
+    }
+
+    Ok(json!({
+        "input": input,
+        "width": header.width,
+        "height": header.height,
+        "color_array": N,
+        "pixels": position,
+        "steps": steps,
+        "prev": snapshot.prev,
+        "arr": snapshot.color_array(),
+    }))
+}
+
+/// Prints the bit layouts of all ops, generated from `q565::consts::OPS`.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "spec")]
+struct Spec {
+    /// print the result (or error) as JSON on stdout
+    #[argh(switch)]
+    json: bool,
+    /// output format (markdown, html), defaults to markdown
+    #[argh(option, default = "SpecFormat::Markdown")]
+    format: SpecFormat,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SpecFormat {
+    Markdown,
+    Html,
+}
+
+impl FromStr for SpecFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(SpecFormat::Markdown),
+            "html" => Ok(SpecFormat::Html),
+            _ => Err("expected markdown or html"),
+        }
+    }
+}
+
+/// Returns the bytes and bits `bits` bits starting at bit `offset` of an op occupy, e.g.
+/// `Byte[0] bits 5..=0`.
+fn bit_range(offset: usize, bits: usize) -> String {
+    let (first, last) = (offset / 8, (offset + bits - 1) / 8);
+    if offset.is_multiple_of(8) && bits.is_multiple_of(8) {
+        return if first == last {
+            format!("Byte[{first}]")
+        } else {
+            format!("Byte[{first}..={last}]")
+        };
+    }
+
+    let high = 7 - offset % 8;
+    let low = 7 - (offset + bits - 1) % 8;
+    if first == last {
+        format!("Byte[{first}] bits {high}..={low}")
+    } else {
+        format!("Byte[{first}] bit {high} to Byte[{last}] bit {low}")
+    }
+}
+
+/// Rows of the layout table of an op: bits, field name, description.
+fn layout_rows(op: &q565::consts::OpInfo) -> Vec<[String; 3]> {
+    let tag_bits = usize::from(op.tag_bits);
+    let tag = op.tag >> (8 - tag_bits);
+    let mut rows = vec![[
+        bit_range(0, tag_bits),
+        "tag".to_owned(),
+        format!("`0b{tag:0tag_bits$b}`"),
+    ]];
+
+    let mut offset = tag_bits;
+    for field in op.fields {
+        let bits = usize::from(field.bits);
+        rows.push([
+            bit_range(offset, bits),
+            field.name.to_owned(),
+            field.description.to_owned(),
+        ]);
+        offset += bits;
+    }
+    rows
+}
+
+fn byte_count(len: usize) -> String {
+    if len == 1 {
+        "1 byte".to_owned()
+    } else {
+        format!("{len} bytes")
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn spec(options: Spec) -> Result<Value, CliError> {
+    if options.json {
+        let ops: Vec<Value> = q565::consts::OPS
+            .iter()
+            .map(|op| {
+                let fields: Vec<Value> = op
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        json!({
+                            "name": field.name,
+                            "bits": field.bits,
+                            "description": field.description,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "name": op.name,
+                    "tag": op.tag,
+                    "tag_bits": op.tag_bits,
+                    "len": op.encoded_len(),
+                    "description": op.description,
+                    "fields": fields,
+                })
+            })
+            .collect();
+        return Ok(Value::Array(ops));
+    }
+
+    let mut out = String::new();
+    match options.format {
+        SpecFormat::Markdown => {
+            out.push_str("# Q565 ops\n");
+            for op in q565::consts::OPS {
+                out.push_str(&format!(
+                    "\n## `{}` ({})\n\n{}\n\n| Bits | Field | Description |\n|---|---|---|\n",
+                    op.name,
+                    byte_count(op.encoded_len()),
+                    op.description,
+                ));
+                for [bits, name, description] in layout_rows(op) {
+                    out.push_str(&format!("| {bits} | {name} | {description} |\n"));
+                }
+            }
+        }
+        SpecFormat::Html => {
+            out.push_str("<h1>Q565 ops</h1>\n");
+            for op in q565::consts::OPS {
+                out.push_str(&format!(
+                    "<h2><code>{}</code> ({})</h2>\n<p>{}</p>\n<table>\n\
+                     <tr><th>Bits</th><th>Field</th><th>Description</th></tr>\n",
+                    op.name,
+                    byte_count(op.encoded_len()),
+                    html_escape(op.description),
+                ));
+                for [bits, name, description] in layout_rows(op) {
+                    // the tag value is formatted as Markdown code
+                    let description = html_escape(&description).replace('`', "");
+                    out.push_str(&format!(
+                        "<tr><td>{bits}</td><td>{}</td><td>{description}</td></tr>\n",
+                        html_escape(&name),
+                    ));
+                }
+                out.push_str("</table>\n");
+            }
+        }
+    }
+
+    print!("{out}");
+    Ok(Value::Null)
 }
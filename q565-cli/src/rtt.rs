@@ -0,0 +1,75 @@
+//! Reading an RTT up channel through a debug probe with probe-rs, for `q565 grab-rtt --elf`.
+
+use crate::{CliError, ErrorKind};
+use probe_rs::{
+    probe::list::Lister,
+    rtt::{find_rtt_control_block_in_raw_file, Rtt},
+    Permissions, Session,
+};
+use std::{io, thread, time::Duration};
+
+/// Time between two reads of an empty channel.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// An RTT up channel of the first core, read through the first debug probe found. Reads block
+/// until data arrives.
+pub struct RttReader {
+    session: Session,
+    rtt: Rtt,
+    channel: usize,
+}
+
+fn probe_error(error: impl std::fmt::Display) -> CliError {
+    CliError::new(ErrorKind::Io, format!("debug probe: {error}"))
+}
+
+impl RttReader {
+    /// Attaches to `chip` without resetting it, and to the RTT control block at the address of
+    /// `_SEGGER_RTT` in the ELF file the device runs.
+    pub fn attach(elf: &[u8], chip: &str, channel: usize) -> Result<Self, CliError> {
+        let address = find_rtt_control_block_in_raw_file(elf)
+            .map_err(CliError::invalid_input)?
+            .ok_or_else(|| {
+                CliError::new(ErrorKind::InvalidInput, "the ELF file has no `_SEGGER_RTT`")
+            })?;
+
+        let probes = Lister::new().list_all();
+        let probe = probes
+            .first()
+            .ok_or_else(|| probe_error("no debug probe found"))?
+            .open()
+            .map_err(probe_error)?;
+        let mut session = probe
+            .attach(chip, Permissions::default())
+            .map_err(probe_error)?;
+        let mut rtt = Rtt::attach_at(&mut session.core(0).map_err(probe_error)?, address)
+            .map_err(probe_error)?;
+        if rtt.up_channel(channel).is_none() {
+            return Err(CliError::new(
+                ErrorKind::Usage,
+                format!("the device has no RTT up channel {channel}"),
+            ));
+        }
+
+        Ok(Self {
+            session,
+            rtt,
+            channel,
+        })
+    }
+}
+
+impl io::Read for RttReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut core = self.session.core(0).map_err(io::Error::other)?;
+            // checked in `attach`
+            let channel = self.rtt.up_channel(self.channel).unwrap();
+            let len = channel.read(&mut core, buf).map_err(io::Error::other)?;
+            if len > 0 {
+                return Ok(len);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! The ops of an image and the decoder state along the way, independent of the UI.
+
+use q565::{
+    byteorder::NativeEndian,
+    decode::{DecodeError, Q565DecodeContext},
+    snapshot::StateSnapshot,
+    stream::{Op, OpReader},
+    ColorArraySize, HeaderInfo, Rgb565,
+};
+
+/// Ops between two stored decoder states. The state at any other op is replayed from the one
+/// before it.
+const CHECKPOINT_INTERVAL: usize = 256;
+
+/// Kinds of ops, in the order of the heatmap legend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpKind {
+    Index,
+    Diff,
+    Luma,
+    DiffIndexed,
+    Run,
+    Rgb565,
+}
+
+impl OpKind {
+    pub const ALL: [OpKind; 6] = [
+        OpKind::Index,
+        OpKind::Diff,
+        OpKind::Luma,
+        OpKind::DiffIndexed,
+        OpKind::Run,
+        OpKind::Rgb565,
+    ];
+
+    /// Returns the kind of a pixel-producing op, `None` for [`Op::End`].
+    pub fn of(op: Op) -> Option<OpKind> {
+        Some(match op {
+            Op::Index(_) => OpKind::Index,
+            Op::Diff(_) => OpKind::Diff,
+            Op::Luma(..) => OpKind::Luma,
+            Op::DiffIndexed(..) => OpKind::DiffIndexed,
+            Op::Run(_) => OpKind::Run,
+            Op::Rgb565(_) => OpKind::Rgb565,
+            Op::End => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OpKind::Index => "INDEX",
+            OpKind::Diff => "DIFF",
+            OpKind::Luma => "LUMA",
+            OpKind::DiffIndexed => "DIFF_INDEXED",
+            OpKind::Run => "RUN",
+            OpKind::Rgb565 => "RGB565",
+        }
+    }
+}
+
+/// An op of the image, with the pixels it produced.
+#[derive(Debug, Clone, Copy)]
+pub struct OpSpan {
+    /// Byte offset of the op in the file.
+    pub offset: usize,
+    pub op: Op,
+    pub first_pixel: usize,
+}
+
+impl OpSpan {
+    /// Index of the color array entry the op reads, if any.
+    pub fn referenced_entry(&self) -> Option<usize> {
+        match self.op {
+            Op::Index(byte) | Op::DiffIndexed(_, byte) => Some(usize::from(byte & 0b0011_1111)),
+            _ => None,
+        }
+    }
+}
+
+/// A decoded image with its ops.
+pub struct Inspection {
+    pub header: HeaderInfo,
+    pub pixels: Vec<u16>,
+    /// The ops producing pixels, in stream order. Empty for raw images.
+    pub ops: Vec<OpSpan>,
+    /// The decoder state before every [`CHECKPOINT_INTERVAL`]th op.
+    checkpoints: Vec<StateSnapshot>,
+}
+
+impl Inspection {
+    pub fn new(data: &[u8]) -> Result<Self, DecodeError> {
+        let (header, pixels) = Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(data)?;
+        let (_, stream) = Q565DecodeContext::decode_header(data)?;
+        let header_len = data.len() - stream.len();
+
+        let (raw, color_array_size) = (header.raw, header.color_array_size);
+        let mut inspection = Inspection {
+            header,
+            pixels,
+            ops: Vec::new(),
+            checkpoints: Vec::new(),
+        };
+        if !raw {
+            match color_array_size {
+                ColorArraySize::NoArray | ColorArraySize::Entries16 => {
+                    inspection.read_ops::<16>(stream, header_len)
+                }
+                ColorArraySize::Entries32 => inspection.read_ops::<32>(stream, header_len),
+                ColorArraySize::Entries64 => inspection.read_ops::<64>(stream, header_len),
+            }
+        }
+        Ok(inspection)
+    }
+
+    fn read_ops<const N: usize>(&mut self, stream: &[u8], header_len: usize) {
+        let mut ctx = Q565DecodeContext::<N>::new_sized();
+        let mut position = 0;
+        for (offset, op) in OpReader::new(stream) {
+            if op == Op::End {
+                break;
+            }
+            if self.ops.len().is_multiple_of(CHECKPOINT_INTERVAL) {
+                self.checkpoints.push(ctx.snapshot());
+            }
+
+            self.ops.push(OpSpan {
+                offset: header_len + offset,
+                op,
+                first_pixel: position,
+            });
+            position += ctx.apply_op(op).1;
+        }
+    }
+
+    /// Index of the op that produced the pixel, `None` for raw images.
+    pub fn op_at(&self, pixel: usize) -> Option<usize> {
+        self.ops
+            .partition_point(|span| span.first_pixel <= pixel)
+            .checked_sub(1)
+    }
+
+    /// The decoder state before and after the op.
+    pub fn states_around(&self, op: usize) -> (StateSnapshot, StateSnapshot) {
+        match self.header.color_array_size {
+            ColorArraySize::NoArray | ColorArraySize::Entries16 => self.replay::<16>(op),
+            ColorArraySize::Entries32 => self.replay::<32>(op),
+            ColorArraySize::Entries64 => self.replay::<64>(op),
+        }
+    }
+
+    fn replay<const N: usize>(&self, op: usize) -> (StateSnapshot, StateSnapshot) {
+        let checkpoint = op / CHECKPOINT_INTERVAL;
+        // the checkpoints are taken with a context of the same size
+        let mut ctx = Q565DecodeContext::<N>::restore(&self.checkpoints[checkpoint]).unwrap();
+        for span in &self.ops[checkpoint * CHECKPOINT_INTERVAL..op] {
+            ctx.apply_op(span.op);
+        }
+
+        let before = ctx.snapshot();
+        ctx.apply_op(self.ops[op].op);
+        (before, ctx.snapshot())
+    }
+
+    /// Number of pixels produced by each kind of op, in the order of [`OpKind::ALL`].
+    pub fn pixels_per_kind(&self) -> [usize; 6] {
+        let mut counts = [0; 6];
+        for (index, span) in self.ops.iter().enumerate() {
+            let end = self
+                .ops
+                .get(index + 1)
+                .map_or(self.pixels.len(), |next| next.first_pixel);
+            if let Some(kind) = OpKind::of(span.op) {
+                counts[kind as usize] += end - span.first_pixel;
+            }
+        }
+        counts
+    }
+}
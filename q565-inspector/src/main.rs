@@ -0,0 +1,411 @@
+//! Interactive inspector for Q565 images: shows which ops produced which pixels as a heatmap over
+//! the image, and the color array before and after the op of any pixel.
+//!
+//! ```sh
+//! cargo run -p q565-inspector --release -- image.q565
+//! ```
+//!
+//! Further images can be opened by dropping them onto the window. Hovering a pixel shows its op
+//! and the decoder state, clicking pins it.
+//!
+//! It also runs in the browser, where images are opened by dropping them onto the page:
+//!
+//! ```sh
+//! cd q565-inspector && trunk serve --release
+//! ```
+
+use eframe::egui::{
+    self, Color32, ColorImage, Rect, Sense, Stroke, StrokeKind, TextureHandle, TextureOptions,
+};
+use inspection::{Inspection, OpKind};
+use q565::{
+    snapshot::StateSnapshot,
+    utils::{decode_565, rgb565_to_rgb888},
+};
+
+mod inspection;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> eframe::Result {
+    let path = std::env::args().nth(1);
+    eframe::run_native(
+        "Q565 inspector",
+        eframe::NativeOptions::default(),
+        Box::new(|cc| {
+            let mut app = InspectorApp::default();
+            if let Some(path) = path {
+                app.load(&cc.egui_ctx, &path, std::fs::read(&path));
+            }
+            Ok(Box::new(app))
+        }),
+    )
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use eframe::{wasm_bindgen::JsCast, web_sys};
+
+    let canvas = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id("inspector"))
+        .and_then(|element| element.dyn_into::<web_sys::HtmlCanvasElement>().ok())
+        .expect("no canvas with the id `inspector`");
+    wasm_bindgen_futures::spawn_local(async move {
+        let result = eframe::WebRunner::new()
+            .start(
+                canvas,
+                eframe::WebOptions::default(),
+                Box::new(|_| Ok(Box::new(InspectorApp::default()))),
+            )
+            .await;
+        if let Err(error) = result {
+            web_sys::console::error_1(&error);
+        }
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum View {
+    Image,
+    Heatmap,
+    #[default]
+    Overlay,
+}
+
+/// An image that was loaded, with its textures.
+struct Loaded {
+    name: String,
+    inspection: Inspection,
+    pixels_per_kind: [usize; 6],
+    image: TextureHandle,
+    heatmap: TextureHandle,
+}
+
+struct InspectorApp {
+    loaded: Option<Loaded>,
+    error: Option<String>,
+    view: View,
+    zoom: f32,
+    hovered: Option<usize>,
+    /// The pixel that was clicked, shown instead of the hovered one.
+    pinned: Option<usize>,
+}
+
+impl Default for InspectorApp {
+    fn default() -> Self {
+        Self {
+            loaded: None,
+            error: None,
+            view: View::default(),
+            zoom: 4.0,
+            hovered: None,
+            pinned: None,
+        }
+    }
+}
+
+fn color32(pixel: u16) -> Color32 {
+    let [r, g, b] = rgb565_to_rgb888(decode_565(pixel));
+    Color32::from_rgb(r, g, b)
+}
+
+fn kind_color(kind: OpKind) -> Color32 {
+    match kind {
+        OpKind::Index => Color32::from_rgb(0x3B, 0x82, 0xF6),
+        OpKind::Diff => Color32::from_rgb(0x22, 0xC5, 0x5E),
+        OpKind::Luma => Color32::from_rgb(0xEA, 0xB3, 0x08),
+        OpKind::DiffIndexed => Color32::from_rgb(0xA8, 0x55, 0xF7),
+        OpKind::Run => Color32::from_rgb(0x9C, 0xA3, 0xAF),
+        OpKind::Rgb565 => Color32::from_rgb(0xEF, 0x44, 0x44),
+    }
+}
+
+impl InspectorApp {
+    fn load(&mut self, ctx: &egui::Context, name: &str, data: std::io::Result<Vec<u8>>) {
+        let inspection = match data
+            .map_err(|e| e.to_string())
+            .and_then(|data| Inspection::new(&data).map_err(|e| e.to_string()))
+        {
+            Ok(inspection) => inspection,
+            Err(e) => {
+                self.error = Some(format!("`{name}`: {e}"));
+                return;
+            }
+        };
+
+        let size = [
+            usize::from(inspection.header.width),
+            usize::from(inspection.header.height),
+        ];
+        let image = ColorImage::new(
+            size,
+            inspection.pixels.iter().map(|&p| color32(p)).collect(),
+        );
+
+        // raw images have no ops, and are shown as if they were all RGB565 ops
+        let mut heatmap = vec![kind_color(OpKind::Rgb565); inspection.pixels.len()];
+        for (index, span) in inspection.ops.iter().enumerate() {
+            let end = inspection
+                .ops
+                .get(index + 1)
+                .map_or(heatmap.len(), |next| next.first_pixel);
+            if let Some(kind) = OpKind::of(span.op) {
+                heatmap[span.first_pixel..end].fill(kind_color(kind));
+            }
+        }
+        let heatmap = ColorImage::new(size, heatmap);
+
+        self.loaded = Some(Loaded {
+            name: name.to_owned(),
+            pixels_per_kind: inspection.pixels_per_kind(),
+            inspection,
+            image: ctx.load_texture("image", image, TextureOptions::NEAREST),
+            heatmap: ctx.load_texture("heatmap", heatmap, TextureOptions::NEAREST),
+        });
+        self.error = None;
+        self.hovered = None;
+        self.pinned = None;
+    }
+
+    fn open_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|input| input.raw.dropped_files.clone());
+        if let Some(file) = dropped.last() {
+            match (&file.path, &file.bytes) {
+                (_, Some(bytes)) => self.load(ctx, &file.name, Ok(bytes.to_vec())),
+                (Some(path), None) => {
+                    let name = path.display().to_string();
+                    self.load(ctx, &name, std::fs::read(path));
+                }
+                (None, None) => {}
+            }
+        }
+    }
+
+    fn top_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.view, View::Image, "Image");
+            ui.selectable_value(&mut self.view, View::Heatmap, "Ops");
+            ui.selectable_value(&mut self.view, View::Overlay, "Overlay");
+            ui.separator();
+            ui.add(egui::Slider::new(&mut self.zoom, 1.0..=32.0).text("zoom"));
+        });
+
+        let Some(loaded) = &self.loaded else {
+            return;
+        };
+        let header = &loaded.inspection.header;
+        ui.label(format!(
+            "{}: {}x{}, {} color array entries{}, {} ops",
+            loaded.name,
+            header.width,
+            header.height,
+            header.color_array_size.entries(),
+            if header.raw { ", raw" } else { "" },
+            loaded.inspection.ops.len(),
+        ));
+
+        let total = loaded.inspection.pixels.len().max(1);
+        ui.horizontal_wrapped(|ui| {
+            for (kind, pixels) in OpKind::ALL.into_iter().zip(loaded.pixels_per_kind) {
+                swatch(ui, kind_color(kind), false, None);
+                ui.label(format!(
+                    "{} {:.1}%",
+                    kind.name(),
+                    100.0 * pixels as f64 / total as f64
+                ));
+            }
+        });
+    }
+
+    fn image_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(loaded) = &self.loaded else {
+            ui.centered_and_justified(|ui| ui.label("Drop a Q565 image here"));
+            return;
+        };
+
+        let width = usize::from(loaded.inspection.header.width);
+        let size = loaded.image.size_vec2() * self.zoom;
+        let (rect, response) = ui.allocate_exact_size(size, Sense::click());
+        let uv = Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        let painter = ui.painter_at(rect);
+        match self.view {
+            View::Image => {
+                painter.image(loaded.image.id(), rect, uv, Color32::WHITE);
+            }
+            View::Heatmap => {
+                painter.image(loaded.heatmap.id(), rect, uv, Color32::WHITE);
+            }
+            View::Overlay => {
+                painter.image(loaded.image.id(), rect, uv, Color32::WHITE);
+                painter.image(
+                    loaded.heatmap.id(),
+                    rect,
+                    uv,
+                    Color32::from_white_alpha(160),
+                );
+            }
+        }
+
+        self.hovered = response.hover_pos().and_then(|pos| {
+            let pos = (pos - rect.min) / self.zoom;
+            let (x, y) = (pos.x as usize, pos.y as usize);
+            (x < width && pos.x >= 0.0 && pos.y >= 0.0)
+                .then_some(y * width + x)
+                .filter(|&pixel| pixel < loaded.inspection.pixels.len())
+        });
+        if response.clicked() {
+            self.pinned = if self.pinned == self.hovered {
+                None
+            } else {
+                self.hovered
+            };
+        }
+
+        // outline the pixels of the shown op
+        if let Some(op) = self
+            .pinned
+            .or(self.hovered)
+            .and_then(|pixel| loaded.inspection.op_at(pixel))
+        {
+            let first = loaded.inspection.ops[op].first_pixel;
+            let end = loaded
+                .inspection
+                .ops
+                .get(op + 1)
+                .map_or(loaded.inspection.pixels.len(), |next| next.first_pixel);
+            for pixel in first..end {
+                let min = rect.min
+                    + egui::vec2((pixel % width) as f32, (pixel / width) as f32) * self.zoom;
+                painter.rect_stroke(
+                    Rect::from_min_size(min, egui::vec2(self.zoom, self.zoom)),
+                    0.0,
+                    Stroke::new(1.0, Color32::WHITE),
+                    StrokeKind::Inside,
+                );
+            }
+        }
+    }
+
+    fn details_panel(&self, ui: &mut egui::Ui) {
+        let (Some(loaded), Some(pixel)) = (&self.loaded, self.pinned.or(self.hovered)) else {
+            ui.label("Hover a pixel to see its op, click to pin it");
+            return;
+        };
+        let inspection = &loaded.inspection;
+        let width = usize::from(inspection.header.width);
+
+        ui.heading(format!(
+            "Pixel ({}, {}){}",
+            pixel % width,
+            pixel / width,
+            if self.pinned.is_some() {
+                ", pinned"
+            } else {
+                ""
+            }
+        ));
+        ui.horizontal(|ui| {
+            let color = inspection.pixels[pixel];
+            swatch(ui, color32(color), false, None);
+            ui.monospace(format!("{color:#06x}"));
+        });
+
+        let Some(op) = inspection.op_at(pixel) else {
+            ui.label("Raw image, the pixels are stored as they are");
+            return;
+        };
+        let span = inspection.ops[op];
+        ui.separator();
+        ui.monospace(format!(
+            "op {op} at {:#x}: {:?}, {} bytes",
+            span.offset,
+            span.op,
+            span.op.encoded_len()
+        ));
+        ui.monospace(format!(
+            "pixels {}..{}",
+            span.first_pixel,
+            span.first_pixel + span.op.pixel_count()
+        ));
+
+        let (before, after) = inspection.states_around(op);
+        ui.separator();
+        ui.label("Before the op, with the entry it reads outlined:");
+        color_array(ui, "before", &before, span.referenced_entry(), None);
+        ui.label("After the op, with the changed entry outlined:");
+        color_array(
+            ui,
+            "after",
+            &after,
+            None,
+            after
+                .changed_entries(&before)
+                .next()
+                .map(|(index, ..)| index),
+        );
+    }
+}
+
+/// Draws a color swatch, with a tooltip if given.
+fn swatch(ui: &mut egui::Ui, color: Color32, outlined: bool, tooltip: Option<String>) {
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(18.0, 18.0), Sense::hover());
+    ui.painter().rect_filled(rect, 2.0, color);
+    if outlined {
+        ui.painter().rect_stroke(
+            rect,
+            2.0,
+            Stroke::new(2.0, Color32::YELLOW),
+            StrokeKind::Outside,
+        );
+    }
+    if let Some(tooltip) = tooltip {
+        response.on_hover_text(tooltip);
+    }
+}
+
+fn color_array(
+    ui: &mut egui::Ui,
+    id: &str,
+    state: &StateSnapshot,
+    read: Option<usize>,
+    written: Option<usize>,
+) {
+    ui.horizontal(|ui| {
+        ui.monospace("prev");
+        swatch(ui, color32(state.prev), false, None);
+        ui.monospace(format!("{:#06x}", state.prev));
+    });
+    egui::Grid::new(id).spacing([4.0, 4.0]).show(ui, |ui| {
+        for (index, &entry) in state.color_array().iter().enumerate() {
+            let outlined = read == Some(index) || written == Some(index);
+            swatch(
+                ui,
+                color32(entry),
+                outlined,
+                Some(format!("arr[{index}]: {entry:#06x}")),
+            );
+            if index % 8 == 7 {
+                ui.end_row();
+            }
+        }
+    });
+}
+
+impl eframe::App for InspectorApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.open_dropped_files(ctx);
+
+        egui::TopBottomPanel::top("top").show(ctx, |ui| {
+            self.top_panel(ui);
+            if let Some(error) = &self.error {
+                ui.colored_label(Color32::RED, error);
+            }
+        });
+        egui::SidePanel::right("details")
+            .min_width(220.0)
+            .show(ctx, |ui| self.details_panel(ui));
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::both().show(ui, |ui| self.image_panel(ui));
+        });
+    }
+}
@@ -1,7 +1,7 @@
-use byteorder::LittleEndian;
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 use image::ImageFormat;
 use q565::{
+    byteorder::LittleEndian,
     utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
     Rgb565, Rgb888,
 };
@@ -39,9 +39,11 @@ fn decode(c: &mut Criterion) {
             height as u16,
             &input,
             &mut encoded
-        ));
+        )
+        .is_some());
 
         group.throughput(criterion::Throughput::Elements(pixel_count as u64));
+        #[cfg(not(feature = "forbid-unsafe"))]
         group.bench_with_input(
             BenchmarkId::new("unsafe rgb565", &image_name),
             &encoded,
@@ -55,6 +57,7 @@ fn decode(c: &mut Criterion) {
                 })
             },
         );
+        #[cfg(not(feature = "forbid-unsafe"))]
         group.bench_with_input(
             BenchmarkId::new("unsafe rgb888", &image_name),
             &encoded,
@@ -81,6 +84,16 @@ fn decode(c: &mut Criterion) {
                 })
             },
         );
+        group.bench_with_input(
+            BenchmarkId::new("safe rgb565 to_vec", &image_name),
+            &encoded,
+            |b, input| {
+                // allocates the output on every iteration, reserved from the header
+                b.iter(|| {
+                    q565::decode::Q565DecodeContext::decode_to_vec::<LittleEndian, Rgb565>(input)
+                })
+            },
+        );
         group.bench_with_input(
             BenchmarkId::new("safe rgb888", &image_name),
             &encoded,
@@ -94,6 +107,7 @@ fn decode(c: &mut Criterion) {
                 })
             },
         );
+        #[cfg(not(feature = "forbid-unsafe"))]
         group.bench_with_input(
             BenchmarkId::new("streaming_no_header", &image_name),
             &encoded,
@@ -200,5 +214,123 @@ fn encode(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, decode, encode);
+/// UI-like frame: large flat rectangles, which are mostly encoded as runs.
+fn flat_frame(width: usize, height: usize) -> Vec<u16> {
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            match (x * 4 / width, y * 3 / height) {
+                (0, _) => 0x2104,
+                (_, 0) => 0xFFFF,
+                (3, _) => 0x07E0,
+                _ => 0xF800,
+            }
+        })
+        .collect()
+}
+
+fn encode_flat(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flat encode");
+
+    let (width, height) = (320, 240);
+    let input = flat_frame(width, height);
+    group.throughput(criterion::Throughput::Elements(input.len() as u64));
+
+    // the slice-based encoders detect runs 16 pixels at a time, the iterator one pixel by pixel
+    group.bench_function("encode_to_vec", |b| {
+        let mut encoded = Vec::with_capacity(input.len() * 2);
+        b.iter(|| {
+            encoded.clear();
+            q565::encode::Q565EncodeContext::encode_to_vec(
+                width as u16,
+                height as u16,
+                &input,
+                &mut encoded,
+            )
+        })
+    });
+    group.bench_function("encode_iter_to_vec", |b| {
+        let mut encoded = Vec::with_capacity(input.len() * 2);
+        b.iter(|| {
+            encoded.clear();
+            q565::encode::Q565EncodeContext::encode_iter_to_vec(
+                width as u16,
+                height as u16,
+                &input,
+                &mut encoded,
+            )
+        })
+    });
+    group.bench_function("encode_fast_rle", |b| {
+        let mut encoded = vec![0; q565::encode::fast_rle_max_len(width as u16, height as u16)];
+        b.iter(|| q565::encode::encode_fast_rle(width as u16, height as u16, &input, &mut encoded))
+    });
+}
+
+/// Solid full-screen frame, i.e. one long run of `Q565_OP_RUN`s of the maximum length.
+fn encode_solid(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solid encode");
+
+    let (width, height) = (800u16, 480u16);
+    let input = vec![0xF800; usize::from(width) * usize::from(height)];
+    group.throughput(criterion::Throughput::Elements(input.len() as u64));
+
+    group.bench_function("encode_to_vec", |b| {
+        let mut encoded = Vec::with_capacity(input.len() / 32);
+        b.iter(|| {
+            encoded.clear();
+            q565::encode::Q565EncodeContext::encode_to_vec(width, height, &input, &mut encoded)
+        })
+    });
+    group.bench_function("encode", |b| {
+        let mut encoded = Vec::with_capacity(input.len() / 32);
+        b.iter(|| {
+            encoded.clear();
+            q565::encode::Q565EncodeContext::encode(width, height, &input, &mut encoded)
+        })
+    });
+    group.bench_function("encode_fast_rle", |b| {
+        let mut encoded = vec![0; q565::encode::fast_rle_max_len(width, height)];
+        b.iter(|| q565::encode::encode_fast_rle(width, height, &input, &mut encoded))
+    });
+}
+
+/// Noise, where most pixels need a search of the color array for a `Q565_OP_DIFF_INDEXED`.
+fn encode_noise(c: &mut Criterion) {
+    let mut group = c.benchmark_group("noise encode");
+
+    let (width, height) = (256, 256);
+    let mut x = 1u32;
+    let input: Vec<u16> = (0..width * height)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x as u16
+        })
+        .collect();
+    group.throughput(criterion::Throughput::Elements(input.len() as u64));
+
+    group.bench_function("encode_to_vec", |b| {
+        let mut encoded = Vec::with_capacity(input.len() * 3);
+        b.iter(|| {
+            encoded.clear();
+            q565::encode::Q565EncodeContext::encode_to_vec(
+                width as u16,
+                height as u16,
+                &input,
+                &mut encoded,
+            )
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    decode,
+    encode,
+    encode_flat,
+    encode_solid,
+    encode_noise
+);
 criterion_main!(benches);
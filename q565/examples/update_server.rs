@@ -0,0 +1,192 @@
+//! Remote-framebuffer demo built on the dirty-rectangle update API.
+//!
+//! The server renders an animated 320x240 framebuffer, finds the changed 16x16 tiles every frame,
+//! and sends them as Q565 update messages to all connected viewers. The viewer applies the updates
+//! to its own framebuffer and prints throughput statistics.
+//!
+//! ```sh
+//! cargo run --release --example update_server -- serve 127.0.0.1:5565
+//! cargo run --release --example update_server -- view 127.0.0.1:5565
+//! ```
+//!
+//! Every message on the wire is prefixed with its length as u32le.
+
+use q565::{
+    byteorder::NativeEndian,
+    update::{apply_update, encode_update},
+    Rect, Rgb565,
+};
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const WIDTH: u16 = 320;
+const HEIGHT: u16 = 240;
+const TILE: u16 = 16;
+const FRAME_TIME: Duration = Duration::from_millis(33);
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1..) {
+        Some([mode, address]) if mode == "serve" => serve(address),
+        Some([mode, address]) if mode == "view" => view(address),
+        _ => Err("usage: update_server (serve|view) <address>".into()),
+    }
+}
+
+fn serve(address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(address)?;
+    println!("Serving {WIDTH}x{HEIGHT} framebuffer on {address}");
+
+    // new clients start from a black framebuffer and get a full update first
+    let clients: Arc<Mutex<Vec<(TcpStream, bool)>>> = Arc::default();
+    let accepting = Arc::clone(&clients);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            println!("Client connected: {:?}", stream.peer_addr());
+            accepting.lock().unwrap().push((stream, true));
+        }
+    });
+
+    let pixel_count = usize::from(WIDTH) * usize::from(HEIGHT);
+    let black = vec![0u16; pixel_count];
+    let mut previous = black.clone();
+    let mut current = vec![0u16; pixel_count];
+    let full_frame = [Rect {
+        x: 0,
+        y: 0,
+        width: WIDTH,
+        height: HEIGHT,
+    }];
+
+    for frame in 0u32.. {
+        let started = Instant::now();
+        render(frame, &mut current);
+        let dirty = dirty_tiles(&previous, &current);
+
+        let mut update = Vec::new();
+        let mut full_update = Vec::new();
+        let mut clients = clients.lock().unwrap();
+        clients.retain_mut(|(stream, needs_full_frame)| {
+            let message = if *needs_full_frame {
+                if full_update.is_empty() {
+                    encode_update(
+                        &black,
+                        &current,
+                        WIDTH,
+                        HEIGHT,
+                        &full_frame,
+                        &mut full_update,
+                    )
+                    .unwrap();
+                }
+                *needs_full_frame = false;
+                &full_update
+            } else {
+                if update.is_empty() {
+                    encode_update(&previous, &current, WIDTH, HEIGHT, &dirty, &mut update).unwrap();
+                }
+                &update
+            };
+
+            send(stream, message).is_ok()
+        });
+        drop(clients);
+
+        std::mem::swap(&mut previous, &mut current);
+        std::thread::sleep(FRAME_TIME.saturating_sub(started.elapsed()));
+    }
+
+    Ok(())
+}
+
+fn view(address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stream = TcpStream::connect(address)?;
+    let mut framebuffer = vec![0u16; usize::from(WIDTH) * usize::from(HEIGHT)];
+
+    let mut message = Vec::new();
+    let (mut frames, mut bytes, mut rects) = (0, 0, 0);
+    let mut last_report = Instant::now();
+    loop {
+        let mut length = [0; 4];
+        stream.read_exact(&mut length)?;
+        message.resize(u32::from_le_bytes(length) as usize, 0);
+        stream.read_exact(&mut message)?;
+
+        rects += apply_update::<NativeEndian, Rgb565>(&message, &mut framebuffer, WIDTH, HEIGHT)
+            .map_err(|e| format!("{e:?}"))?;
+        frames += 1;
+        bytes += message.len() + 4;
+
+        if last_report.elapsed() >= Duration::from_secs(1) {
+            println!(
+                "{frames} frames, {rects} rects, {:.1} KiB/s",
+                bytes as f64 / 1024.0 / last_report.elapsed().as_secs_f64()
+            );
+            (frames, bytes, rects) = (0, 0, 0);
+            last_report = Instant::now();
+        }
+    }
+}
+
+fn send(stream: &mut TcpStream, message: &[u8]) -> std::io::Result<()> {
+    stream.write_all(&(message.len() as u32).to_le_bytes())?;
+    stream.write_all(message)
+}
+
+/// Draws a static gradient background with a bouncing square and a progress bar on top.
+fn render(frame: u32, framebuffer: &mut [u16]) {
+    let (width, height) = (u32::from(WIDTH), u32::from(HEIGHT));
+    let square_x = bounce(frame * 3, width - 40);
+    let square_y = bounce(frame * 2, height - 60);
+    let bar = frame % width;
+
+    for (i, pixel) in framebuffer.iter_mut().enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        *pixel = if (square_x..square_x + 40).contains(&x) && (square_y..square_y + 40).contains(&y)
+        {
+            0xF800
+        } else if y >= height - 12 && x < bar {
+            0x07E0
+        } else {
+            (((y * 32 / height) as u16) << 11) | ((x * 32 / width) as u16)
+        };
+    }
+}
+
+fn bounce(t: u32, max: u32) -> u32 {
+    let t = t % (max * 2);
+    if t < max {
+        t
+    } else {
+        max * 2 - t
+    }
+}
+
+/// Returns every tile that differs between the two framebuffers.
+fn dirty_tiles(previous: &[u16], current: &[u16]) -> Vec<Rect> {
+    let mut dirty = Vec::new();
+    for tile_y in (0..HEIGHT).step_by(usize::from(TILE)) {
+        for tile_x in (0..WIDTH).step_by(usize::from(TILE)) {
+            let rect = Rect {
+                x: tile_x,
+                y: tile_y,
+                width: TILE.min(WIDTH - tile_x),
+                height: TILE.min(HEIGHT - tile_y),
+            };
+
+            let changed = (rect.y..rect.y + rect.height).any(|y| {
+                let start = usize::from(y) * usize::from(WIDTH) + usize::from(rect.x);
+                let end = start + usize::from(rect.width);
+                previous[start..end] != current[start..end]
+            });
+            if changed {
+                dirty.push(rect);
+            }
+        }
+    }
+    dirty
+}
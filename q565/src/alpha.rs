@@ -0,0 +1,229 @@
+//! Translucent images, blended onto a framebuffer while decoding.
+//!
+//! Until the alpha header extension ([`Capabilities::ALPHA`](crate::capabilities::Capabilities::ALPHA))
+//! is specified, a translucent image ([`Q565aImage`]) is stored as two Q565 images of the same
+//! size:
+//!
+//! - the colors, with any profile
+//! - an alpha mask with the default profile, whose green channel holds the 6-bit alpha of each
+//!   pixel, from 0 (transparent) to 63 (opaque). The red and blue channels are ignored;
+//!   [`alpha_mask_color`] sets them so that the mask looks like a grayscale image.
+//!
+//! Masks are mostly runs of fully opaque or transparent pixels, so they compress well.
+//!
+//! [`blit_over`] decodes both images at once and blends every pixel onto the framebuffer as it is
+//! decoded, without an intermediate buffer for the image.
+
+use crate::{
+    byteorder::{Endianness, NativeEndian},
+    decode::{
+        streaming_no_header::Q565StreamingDecodeContext, DecodeError, InfallibleDecodeOutput,
+        Q565DecodeContext,
+    },
+    utils::{decode_565, encode_rgb565_unchecked},
+    ColorArraySize, ColorFormat, Rgb565,
+};
+
+/// Most pixels a single byte of image data decodes to: a run of 62 pixels.
+const MAX_PIXELS_PER_BYTE: usize = 64;
+
+/// Alpha of a fully opaque pixel.
+pub const OPAQUE: u8 = 63;
+
+error_enum! {
+    pub enum AlphaError {
+        /// The color image or the alpha mask failed to decode.
+        Decode { source: DecodeError } = 1,
+        /// The color image and the alpha mask differ in size.
+        SizeMismatch = 2,
+        /// The alpha mask is raw or doesn't use the default color array profile.
+        UnsupportedMask = 3,
+    }
+}
+
+impl From<DecodeError> for AlphaError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// Returns the pixel of an alpha mask for the given 6-bit alpha, as a gray RGB565 color.
+///
+/// Alphas above [`OPAQUE`] are clamped.
+pub const fn alpha_mask_color(alpha: u8) -> u16 {
+    let alpha = if alpha > OPAQUE { OPAQUE } else { alpha };
+    encode_rgb565_unchecked([alpha >> 1, alpha, alpha >> 1])
+}
+
+/// Blends the RGB565 colors `src` over `dst`, with `src` having the 6-bit alpha `alpha`.
+///
+/// The channels are blended at their own bit depth, rounding to the nearest value.
+pub const fn blend_565(src: u16, dst: u16, alpha: u8) -> u16 {
+    match alpha {
+        0 => dst,
+        OPAQUE.. => src,
+        _ => {
+            let [sr, sg, sb] = decode_565(src);
+            let [dr, dg, db] = decode_565(dst);
+            encode_rgb565_unchecked([mix(sr, dr, alpha), mix(sg, dg, alpha), mix(sb, db, alpha)])
+        }
+    }
+}
+
+/// Mixes one channel of `src` and `dst`, rounding to the nearest value.
+const fn mix(src: u8, dst: u8, alpha: u8) -> u8 {
+    let alpha = alpha as u16;
+    ((src as u16 * alpha + dst as u16 * (OPAQUE as u16 - alpha) + 31) / OPAQUE as u16) as u8
+}
+
+/// A translucent image: a color image and its alpha mask, see the [module docs](self).
+#[derive(Debug, Clone, Copy)]
+pub struct Q565aImage<'a> {
+    color: &'a [u8],
+    /// The alpha mask, without its header.
+    alpha: &'a [u8],
+    width: u16,
+    height: u16,
+}
+
+impl<'a> Q565aImage<'a> {
+    /// Checks that the headers of `color` and `alpha` match.
+    pub fn new(color: &'a [u8], alpha: &'a [u8]) -> Result<Self, AlphaError> {
+        let (color_header, _) = Q565DecodeContext::decode_header(color)?;
+        let (alpha_header, alpha) = Q565DecodeContext::decode_header(alpha)?;
+
+        ensure!(
+            (color_header.width, color_header.height) == (alpha_header.width, alpha_header.height),
+            AlphaError::SizeMismatch
+        );
+        ensure!(
+            !alpha_header.raw && alpha_header.color_array_size == ColorArraySize::Entries64,
+            AlphaError::UnsupportedMask
+        );
+
+        Ok(Self {
+            color,
+            alpha,
+            width: color_header.width,
+            height: color_header.height,
+        })
+    }
+
+    /// Returns the width and height of the image.
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+}
+
+/// Decodes `src` and blends it over `dst` at (`x`, `y`).
+///
+/// `dst` is a framebuffer of pixels in the byte order `B`, whose rows are `dst_stride` pixels
+/// apart. Pixels falling outside of it are dropped.
+///
+/// An alpha mask that is shorter than the image, or otherwise invalid, doesn't cause undefined
+/// behavior, but blends the affected pixels with unspecified alphas.
+pub fn blit_over<B: Endianness>(
+    dst: &mut [u16],
+    dst_stride: usize,
+    src: &Q565aImage,
+    x: u16,
+    y: u16,
+) -> Result<(), AlphaError> {
+    let output = BlendDecodeOutput {
+        dst,
+        stride: dst_stride,
+        x: usize::from(x),
+        y: usize::from(y),
+        width: usize::from(src.width),
+        alpha: AlphaReader {
+            data: src.alpha,
+            context: Q565StreamingDecodeContext::new(),
+            pending: [0; MAX_PIXELS_PER_BYTE],
+            pending_start: 0,
+            pending_len: 0,
+        },
+        output_idx: 0,
+    };
+    Q565DecodeContext::decode::<B>(src.color, output)?;
+    Ok(())
+}
+
+/// Decodes an alpha mask a pixel at a time.
+struct AlphaReader<'a> {
+    data: &'a [u8],
+    context: Q565StreamingDecodeContext,
+    pending: [u16; MAX_PIXELS_PER_BYTE],
+    pending_start: usize,
+    pending_len: usize,
+}
+
+impl AlphaReader<'_> {
+    fn next(&mut self) -> u8 {
+        while self.pending_len == 0 {
+            let Some((&byte, rest)) = self.data.split_first() else {
+                return 0;
+            };
+            if self.context.is_finished() {
+                return 0;
+            }
+            self.data = rest;
+
+            // SAFETY: a single byte decodes to at most 62 pixels, so the pending buffer is always
+            // large enough, whatever the input
+            self.pending_len = unsafe {
+                self.context
+                    .streaming_decode_to_slice_unchecked::<NativeEndian>(&[byte], &mut self.pending)
+            };
+            self.pending_start = 0;
+        }
+
+        let [_, alpha, _] = decode_565(self.pending[self.pending_start]);
+        self.pending_start += 1;
+        self.pending_len -= 1;
+        alpha
+    }
+}
+
+struct BlendDecodeOutput<'d, 'a> {
+    dst: &'d mut [u16],
+    stride: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    alpha: AlphaReader<'a>,
+    output_idx: usize,
+}
+
+impl InfallibleDecodeOutput for BlendDecodeOutput<'_, '_> {
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.write_many_pixels::<B>(color, 1);
+    }
+
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        for _ in 0..count {
+            let alpha = self.alpha.next();
+            let x = self.x + self.output_idx % self.width;
+            let y = self.y + self.output_idx / self.width;
+            self.output_idx += 1;
+
+            if x >= self.stride {
+                continue;
+            }
+            if let Some(pixel) = self.dst.get_mut(y * self.stride + x) {
+                let below = Rgb565::to_output::<B>(*pixel);
+                *pixel = Rgb565::to_output::<B>(blend_565(color, below, alpha));
+            }
+        }
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
@@ -0,0 +1,85 @@
+//! Color statistics of Q565 images.
+
+use crate::byteorder::{Endianness, NativeEndian};
+use crate::decode::{DecodeError, InfallibleDecodeOutput, Q565DecodeContext};
+use alloc::{vec, vec::Vec};
+
+/// Number of colors reported in [`ImageStats::dominant_colors`].
+pub const DOMINANT_COLOR_COUNT: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct ImageStats {
+    /// Number of occurrences of every RGB565 color, indexed by the color value.
+    pub color_histogram: Vec<u32>,
+    /// Number of distinct colors in the image.
+    pub unique_colors: usize,
+    /// The (up to) [`DOMINANT_COLOR_COUNT`] most frequent colors with their pixel counts, most
+    /// frequent first.
+    pub dominant_colors: Vec<(u16, u32)>,
+}
+
+/// Decode output that only counts the occurrences of each color.
+pub struct HistogramDecodeOutput<'a> {
+    histogram: &'a mut [u32; 65536],
+    output_idx: usize,
+}
+
+impl<'a> HistogramDecodeOutput<'a> {
+    #[inline]
+    pub fn new(histogram: &'a mut [u32; 65536]) -> Self {
+        Self {
+            histogram,
+            output_idx: 0,
+        }
+    }
+}
+
+impl InfallibleDecodeOutput for HistogramDecodeOutput<'_> {
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.histogram[usize::from(color)] += 1;
+        self.output_idx += 1;
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        self.histogram[usize::from(color)] += count as u32;
+        self.output_idx += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
+
+/// Collects color statistics of a Q565 image in a single decode pass, without storing the decoded
+/// pixels.
+pub fn analyze(data: &[u8]) -> Result<ImageStats, DecodeError> {
+    let mut histogram = vec![0u32; 65536];
+    let histogram_array: &mut [u32; 65536] = histogram.as_mut_slice().try_into().unwrap();
+    Q565DecodeContext::decode::<NativeEndian>(data, HistogramDecodeOutput::new(histogram_array))?;
+
+    let mut colors: Vec<(u16, u32)> = histogram
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count > 0)
+        .map(|(color, &count)| (color as u16, count))
+        .collect();
+    let unique_colors = colors.len();
+
+    // stable sort keeps ties in ascending color order
+    colors.sort_by(|(_, a), (_, b)| b.cmp(a));
+    colors.truncate(DOMINANT_COLOR_COUNT);
+
+    Ok(ImageStats {
+        color_histogram: histogram,
+        unique_colors,
+        dominant_colors: colors,
+    })
+}
@@ -0,0 +1,569 @@
+//! Animations: a sequence of Q565 frames with per-frame durations and named segments, e.g. the
+//! "intro", "loop" and "outro" parts of a boot or charging animation.
+//!
+//! Every frame is a complete Q565 image. Frames after the first may be stored as a delta against
+//! the previous frame instead: every pixel XORed with the pixel at the same position in the
+//! previous frame, like the delta stage of a [`Pipeline`](crate::pipeline::Pipeline). The first
+//! frame of the animation and of every segment is always a keyframe, so playback can jump to the
+//! start of any segment.
+//!
+//! # Layout
+//!
+//! All integers are little-endian.
+//!
+//! - 4-byte magic: `q5an`
+//! - u16le width, u16le height
+//! - u16le frame count
+//! - u8 segment count
+//! - per segment:
+//!   - u8 name length, followed by the name in UTF-8
+//!   - u16le index of the first frame
+//!   - u16le number of frames
+//!   - u8 loop count, `0` to loop until [stopped](Playback::stop_looping)
+//! - one 11-byte record per frame:
+//!   - u32le offset of the frame data, from the start of the animation
+//!   - u32le length of the frame data
+//!   - u16le duration in milliseconds
+//!   - u8 flags, bit 0: [`DELTA_FLAG`]
+//! - frame data (the encoded images)
+//!
+//! # Playback
+//!
+//! [`Animation::play`] iterates over the frames of a segment, repeated as often as the segment
+//! loops. Each frame is decoded into the framebuffer with [`Frame::decode_into`], which applies
+//! deltas in place:
+//!
+//! ```
+//! # use q565::{animation::{Animation, AnimationWriter}, byteorder::LittleEndian};
+//! # let mut writer = AnimationWriter::new(2, 1);
+//! # writer.push_frame(&[0x0000, 0xFFFF], 40).unwrap();
+//! # writer.push_frame(&[0x0000, 0xF800], 40).unwrap();
+//! # writer.add_segment("loop", 0..2, 0).unwrap();
+//! # let data = writer.write().unwrap();
+//! # let mut framebuffer = [0; 2];
+//! let animation = Animation::new(&data)?;
+//! let mut playback = animation.play("loop")?;
+//! while let Some(frame) = playback.next() {
+//!     frame.decode_into::<LittleEndian>(&mut framebuffer)?;
+//!     // show the framebuffer for `frame.duration_ms`
+//! #   playback.stop_looping();
+//! }
+//! # Ok::<(), q565::animation::AnimationError>(())
+//! ```
+//!
+//! A [`Player`] does the same, paced by a clock, so the firmware only needs to call
+//! [`Player::update`] from its main loop.
+
+use crate::byteorder::Endianness;
+use crate::decode::{DecodeError, InfallibleDecodeOutput, Q565DecodeContext};
+use crate::Rgb565;
+use crate::{saturating_usize, ColorFormat};
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+mod player;
+
+pub use player::*;
+
+pub const ANIMATION_MAGIC: &[u8; 4] = b"q5an";
+/// Flag of a frame record marking a frame stored as a delta against the previous frame.
+pub const DELTA_FLAG: u8 = 0b1;
+
+const HEADER_LEN: usize = 11;
+/// Length of a segment record without its name.
+const SEGMENT_LEN: usize = 6;
+const RECORD_LEN: usize = 11;
+
+error_enum! {
+    pub enum AnimationError {
+        /// The data does not start with the magic bytes `q5an`.
+        InvalidMagic = 1,
+        /// The data ended before the segment table, the frame table or a frame.
+        UnexpectedEof = 2,
+        /// A segment is empty, lies outside of the frames, starts with a delta frame, or has an
+        /// invalid name.
+        InvalidSegment = 3,
+        /// The first frame is stored as a delta.
+        InvalidFrame = 4,
+        /// There is no segment with the given name.
+        NoSuchSegment = 5,
+        /// The number of pixels doesn't match the dimensions of the animation.
+        PixelCount = 6,
+        /// The framebuffer is smaller than a frame.
+        FramebufferTooSmall = 7,
+        /// A frame's dimensions don't match the animation.
+        FrameMismatch = 8,
+        /// The animation would have too many frames or segments, or be larger than 4 GiB.
+        TooLarge = 9,
+        /// A frame failed to decode.
+        Decode { source: DecodeError } = 10,
+    }
+}
+
+impl From<DecodeError> for AnimationError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// A parsed, borrowed animation.
+#[derive(Debug, Clone, Copy)]
+pub struct Animation<'a> {
+    data: &'a [u8],
+    width: u16,
+    height: u16,
+    frame_count: usize,
+    segment_count: usize,
+    table_start: usize,
+}
+
+impl<'a> Animation<'a> {
+    /// Parses an animation, checking that the segments are valid, and that the frame table and all
+    /// frames lie within `data`.
+    pub fn new(data: &'a [u8]) -> Result<Self, AnimationError> {
+        ensure!(data.len() >= 4, AnimationError::UnexpectedEof);
+        ensure!(&data[..4] == ANIMATION_MAGIC, AnimationError::InvalidMagic);
+        ensure!(data.len() >= HEADER_LEN, AnimationError::UnexpectedEof);
+
+        let width = u16::from_le_bytes([data[4], data[5]]);
+        let height = u16::from_le_bytes([data[6], data[7]]);
+        let frame_count = usize::from(u16::from_le_bytes([data[8], data[9]]));
+        let segment_count = usize::from(data[10]);
+        crate::pixel_count(width, height).ok_or(DecodeError::DimensionsTooLarge)?;
+
+        let mut table_start = HEADER_LEN;
+        for _ in 0..segment_count {
+            let name_len =
+                usize::from(*data.get(table_start).ok_or(AnimationError::UnexpectedEof)?);
+            table_start += name_len + SEGMENT_LEN;
+        }
+        ensure!(
+            data.len() >= table_start + frame_count * RECORD_LEN,
+            AnimationError::UnexpectedEof
+        );
+
+        let animation = Self {
+            data,
+            width,
+            height,
+            frame_count,
+            segment_count,
+            table_start,
+        };
+
+        for index in 0..frame_count {
+            let (offset, length, _, _) = animation.record(index);
+            ensure!(
+                offset
+                    .checked_add(length)
+                    .is_some_and(|end| end <= data.len()),
+                AnimationError::UnexpectedEof
+            );
+        }
+        ensure!(
+            frame_count == 0 || animation.record(0).3 & DELTA_FLAG == 0,
+            AnimationError::InvalidFrame
+        );
+
+        let mut position = HEADER_LEN;
+        for _ in 0..segment_count {
+            let (segment, next) = animation
+                .segment_at(position)
+                .ok_or(AnimationError::InvalidSegment)?;
+            ensure!(
+                segment.frame_count > 0
+                    && segment.frames().end <= frame_count
+                    && animation.record(segment.frames().start).3 & DELTA_FLAG == 0,
+                AnimationError::InvalidSegment
+            );
+            position = next;
+        }
+
+        Ok(animation)
+    }
+
+    #[inline]
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Number of pixels of every frame.
+    #[inline]
+    pub fn pixel_count(&self) -> usize {
+        // checked by `new`
+        crate::pixel_count(self.width, self.height).unwrap_or_default()
+    }
+
+    /// Number of frames in the animation.
+    #[inline]
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Returns the frame at `index`.
+    pub fn frame(&self, index: usize) -> Option<Frame<'a>> {
+        if index >= self.frame_count {
+            return None;
+        }
+
+        let (offset, length, duration_ms, flags) = self.record(index);
+        Some(Frame {
+            data: &self.data[offset..offset + length],
+            width: self.width,
+            height: self.height,
+            duration_ms,
+            delta: flags & DELTA_FLAG != 0,
+        })
+    }
+
+    /// Iterates over the segments, in the order they were written.
+    pub fn segments(&self) -> impl Iterator<Item = Segment<'a>> + '_ {
+        let mut position = HEADER_LEN;
+        (0..self.segment_count).filter_map(move |_| {
+            let (segment, next) = self.segment_at(position)?;
+            position = next;
+            Some(segment)
+        })
+    }
+
+    /// Returns the first segment with the given name.
+    pub fn segment(&self, name: &str) -> Option<Segment<'a>> {
+        self.segments().find(|segment| segment.name == name)
+    }
+
+    /// Starts playing the segment with the given name.
+    pub fn play(&self, name: &str) -> Result<Playback<'a>, AnimationError> {
+        let segment = self.segment(name).ok_or(AnimationError::NoSuchSegment)?;
+        Ok(self.play_segment(&segment))
+    }
+
+    /// Starts playing the given segment.
+    pub fn play_segment(&self, segment: &Segment<'_>) -> Playback<'a> {
+        Playback {
+            animation: *self,
+            frames: segment.frames(),
+            next: segment.frames().start,
+            loops_left: segment.loops,
+            forever: segment.loops_forever(),
+        }
+    }
+
+    fn segment_at(&self, position: usize) -> Option<(Segment<'a>, usize)> {
+        let name_len = usize::from(self.data[position]);
+        let name = &self.data[position + 1..position + 1 + name_len];
+        let name = core::str::from_utf8(name).ok()?;
+        let fields = &self.data[position + 1 + name_len..position + name_len + SEGMENT_LEN];
+        let segment = Segment {
+            name,
+            first_frame: u16::from_le_bytes([fields[0], fields[1]]),
+            frame_count: u16::from_le_bytes([fields[2], fields[3]]),
+            loops: fields[4],
+        };
+        Some((segment, position + name_len + SEGMENT_LEN))
+    }
+
+    fn record(&self, index: usize) -> (usize, usize, u16, u8) {
+        let start = self.table_start + index * RECORD_LEN;
+        let record = &self.data[start..start + RECORD_LEN];
+        let offset = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        let length = u32::from_le_bytes([record[4], record[5], record[6], record[7]]);
+        let duration_ms = u16::from_le_bytes([record[8], record[9]]);
+        (
+            saturating_usize(offset),
+            saturating_usize(length),
+            duration_ms,
+            record[10],
+        )
+    }
+}
+
+/// A named range of frames of an [`Animation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment<'a> {
+    pub name: &'a str,
+    pub first_frame: u16,
+    pub frame_count: u16,
+    /// How many times the segment is played, `0` to loop until
+    /// [stopped](Playback::stop_looping).
+    pub loops: u8,
+}
+
+impl Segment<'_> {
+    /// Indices of the frames of the segment.
+    #[inline]
+    pub fn frames(&self) -> Range<usize> {
+        let start = usize::from(self.first_frame);
+        start..start + usize::from(self.frame_count)
+    }
+
+    /// Returns whether the segment loops until stopped.
+    #[inline]
+    pub fn loops_forever(&self) -> bool {
+        self.loops == 0
+    }
+}
+
+/// A frame of an [`Animation`].
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
+    /// The encoded image.
+    pub data: &'a [u8],
+    /// How long the frame is shown, in milliseconds.
+    pub duration_ms: u16,
+    /// Whether the image is a delta against the previous frame, see [`DELTA_FLAG`].
+    pub delta: bool,
+    width: u16,
+    height: u16,
+}
+
+impl Frame<'_> {
+    /// Decodes the frame into `framebuffer`, which holds the previous frame for delta frames.
+    ///
+    /// The framebuffer holds the RGB565 pixels of the frame, row by row, in the byte order `B`.
+    pub fn decode_into<B: Endianness>(
+        &self,
+        framebuffer: &mut [u16],
+    ) -> Result<(), AnimationError> {
+        let pixel_count = crate::pixel_count(self.width, self.height).unwrap_or_default();
+        ensure!(
+            framebuffer.len() >= pixel_count,
+            AnimationError::FramebufferTooSmall
+        );
+
+        let (header, _) = Q565DecodeContext::decode_header(self.data)?;
+        ensure!(
+            header.width == self.width && header.height == self.height,
+            AnimationError::FrameMismatch
+        );
+
+        let output = FrameDecodeOutput {
+            framebuffer: &mut framebuffer[..pixel_count],
+            position: 0,
+            delta: self.delta,
+        };
+        Q565DecodeContext::decode::<B>(self.data, output)?;
+        Ok(())
+    }
+}
+
+/// Iterator over the frames of a segment, see [`Animation::play`].
+#[derive(Debug, Clone)]
+pub struct Playback<'a> {
+    animation: Animation<'a>,
+    frames: Range<usize>,
+    next: usize,
+    loops_left: u8,
+    forever: bool,
+}
+
+impl<'a> Playback<'a> {
+    /// Lets the segment play to its end instead of starting another loop, e.g. to move on to the
+    /// "outro" once a device has booted.
+    #[inline]
+    pub fn stop_looping(&mut self) {
+        self.forever = false;
+        self.loops_left = 1;
+    }
+
+    /// Index of the frame returned next, `None` once playback is done.
+    #[inline]
+    pub fn next_index(&self) -> Option<usize> {
+        (self.next < self.frames.end).then_some(self.next)
+    }
+}
+
+impl<'a> Iterator for Playback<'a> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next_index()?;
+        self.next += 1;
+        if self.next == self.frames.end && (self.forever || self.loops_left > 1) {
+            self.next = self.frames.start;
+            if !self.forever {
+                self.loops_left -= 1;
+            }
+        }
+
+        self.animation.frame(index)
+    }
+}
+
+/// Writes the pixels into the framebuffer, or XORs them into it for delta frames.
+struct FrameDecodeOutput<'a> {
+    framebuffer: &'a mut [u16],
+    position: usize,
+    delta: bool,
+}
+
+impl InfallibleDecodeOutput for FrameDecodeOutput<'_> {
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.write_many_pixels::<B>(color, 1);
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        let color = Rgb565::to_output::<B>(color);
+        let end = (self.position + count).min(self.framebuffer.len());
+        let pixels = &mut self.framebuffer[self.position.min(end)..end];
+        if self.delta {
+            pixels.iter_mut().for_each(|pixel| *pixel ^= color);
+        } else {
+            pixels.fill(color);
+        }
+        self.position += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        Some(self.framebuffer.len())
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.position
+    }
+}
+
+/// Writes animations from raw RGB565 frames.
+///
+/// Frames are stored as a delta against the previous frame wherever that is smaller, except for
+/// the first frame of the animation and of every segment.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct AnimationWriter {
+    width: u16,
+    height: u16,
+    frames: Vec<(Vec<u16>, u16)>,
+    segments: Vec<(String, u16, u16, u8)>,
+}
+
+#[cfg(feature = "alloc")]
+impl AnimationWriter {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            frames: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    /// Appends a frame, shown for `duration_ms` milliseconds.
+    pub fn push_frame(&mut self, pixels: &[u16], duration_ms: u16) -> Result<(), AnimationError> {
+        ensure!(
+            crate::pixel_count(self.width, self.height) == Some(pixels.len()),
+            AnimationError::PixelCount
+        );
+        ensure!(self.frames.len() < 0xFFFF, AnimationError::TooLarge);
+        self.frames.push((pixels.to_vec(), duration_ms));
+        Ok(())
+    }
+
+    /// Adds a segment of the given frames, played `loops` times, or until stopped for `0`.
+    ///
+    /// The frames may be pushed later on, but need to be there once the animation is
+    /// [written](Self::write).
+    pub fn add_segment(
+        &mut self,
+        name: &str,
+        frames: Range<u16>,
+        loops: u8,
+    ) -> Result<(), AnimationError> {
+        ensure!(
+            name.len() <= usize::from(u8::MAX) && frames.start < frames.end,
+            AnimationError::InvalidSegment
+        );
+        ensure!(self.segments.len() < 0xFF, AnimationError::TooLarge);
+        self.segments.push((
+            String::from(name),
+            frames.start,
+            frames.end - frames.start,
+            loops,
+        ));
+        Ok(())
+    }
+
+    /// Encodes the frames and writes the animation.
+    pub fn write(&self) -> Result<Vec<u8>, AnimationError> {
+        let mut keyframes = alloc::vec![false; self.frames.len()];
+        if let Some(first) = keyframes.first_mut() {
+            *first = true;
+        }
+        for &(_, first_frame, frame_count, _) in &self.segments {
+            let first_frame = usize::from(first_frame);
+            ensure!(
+                first_frame + usize::from(frame_count) <= self.frames.len(),
+                AnimationError::InvalidSegment
+            );
+            keyframes[first_frame] = true;
+        }
+
+        let mut encoded_frames = Vec::with_capacity(self.frames.len());
+        let mut previous: Option<&[u16]> = None;
+        for ((pixels, duration_ms), &keyframe) in self.frames.iter().zip(&keyframes) {
+            let mut encoded = self.encode(pixels)?;
+            let mut flags = 0;
+            if let (false, Some(previous)) = (keyframe, previous) {
+                let delta: Vec<u16> = pixels.iter().zip(previous).map(|(a, b)| a ^ b).collect();
+                let encoded_delta = self.encode(&delta)?;
+                if encoded_delta.len() < encoded.len() {
+                    encoded = encoded_delta;
+                    flags |= DELTA_FLAG;
+                }
+            }
+            encoded_frames.push((encoded, *duration_ms, flags));
+            previous = Some(pixels);
+        }
+
+        let mut data = Vec::new();
+        data.extend_from_slice(ANIMATION_MAGIC);
+        data.extend_from_slice(&self.width.to_le_bytes());
+        data.extend_from_slice(&self.height.to_le_bytes());
+        data.extend_from_slice(&(self.frames.len() as u16).to_le_bytes());
+        data.push(self.segments.len() as u8);
+        for (name, first_frame, frame_count, loops) in &self.segments {
+            data.push(name.len() as u8);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(&first_frame.to_le_bytes());
+            data.extend_from_slice(&frame_count.to_le_bytes());
+            data.push(*loops);
+        }
+
+        let mut offset = data.len() + encoded_frames.len() * RECORD_LEN;
+        for (encoded, duration_ms, flags) in &encoded_frames {
+            let start = u32::try_from(offset).map_err(|_| AnimationError::TooLarge)?;
+            data.extend_from_slice(&start.to_le_bytes());
+            data.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+            data.extend_from_slice(&duration_ms.to_le_bytes());
+            data.push(*flags);
+            offset += encoded.len();
+        }
+        ensure!(u32::try_from(offset).is_ok(), AnimationError::TooLarge);
+
+        for (encoded, _, _) in &encoded_frames {
+            data.extend_from_slice(encoded);
+        }
+
+        Ok(data)
+    }
+
+    fn encode(&self, pixels: &[u16]) -> Result<Vec<u8>, AnimationError> {
+        let mut encoded = Vec::new();
+        crate::encode::Q565EncodeContext::encode_to_vec(
+            self.width,
+            self.height,
+            pixels,
+            &mut encoded,
+        )
+        .ok_or(AnimationError::PixelCount)?;
+        Ok(encoded)
+    }
+}
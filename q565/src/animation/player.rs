@@ -0,0 +1,191 @@
+use super::{Animation, AnimationError, Frame, Playback, Segment};
+use crate::byteorder::Endianness;
+
+/// Plays the segments of an [`Animation`] in real time.
+///
+/// Time comes from the `now` closure, in milliseconds, e.g. read from a hardware timer. It may
+/// wrap around. Frames are scheduled relative to when the previous frame was due rather than when
+/// it was shown, so playback doesn't drift if [`update`](Self::update) is called late.
+///
+/// ```
+/// # use q565::{animation::{Animation, AnimationWriter, Player}, byteorder::LittleEndian};
+/// # let mut writer = AnimationWriter::new(2, 1);
+/// # writer.push_frame(&[0x0000, 0xFFFF], 40).unwrap();
+/// # writer.push_frame(&[0x0000, 0xF800], 40).unwrap();
+/// # writer.push_frame(&[0xF800, 0xF800], 40).unwrap();
+/// # writer.add_segment("loop", 0..2, 0).unwrap();
+/// # writer.add_segment("outro", 2..3, 1).unwrap();
+/// # let data = writer.write().unwrap();
+/// # let mut framebuffer = [0; 2];
+/// # let mut time = 0;
+/// # let mut booted = true;
+/// let animation = Animation::new(&data)?;
+/// let mut player = Player::new(animation, || { time += 10; time });
+/// player.play("loop")?;
+/// while !player.is_finished() {
+///     if player.update::<LittleEndian>(&mut framebuffer)?.is_some() {
+///         // send the framebuffer to the display
+///     }
+///     if core::mem::take(&mut booted) {
+///         player.queue("outro")?;
+///         player.stop_looping();
+///     }
+/// }
+/// # Ok::<(), q565::animation::AnimationError>(())
+/// ```
+pub struct Player<'a, F> {
+    animation: Animation<'a>,
+    now: F,
+    segment: Option<Segment<'a>>,
+    playback: Option<Playback<'a>>,
+    queued: Option<Segment<'a>>,
+    frame: Option<usize>,
+    /// Time at which the next frame is due.
+    due: u32,
+}
+
+impl<'a, F> Player<'a, F>
+where
+    F: FnMut() -> u32,
+{
+    /// Creates a player that doesn't play anything yet, see [`play`](Self::play).
+    pub fn new(animation: Animation<'a>, now: F) -> Self {
+        Self {
+            animation,
+            now,
+            segment: None,
+            playback: None,
+            queued: None,
+            frame: None,
+            due: 0,
+        }
+    }
+
+    /// Starts playing the segment with the given name right away, dropping any queued segment.
+    pub fn play(&mut self, name: &str) -> Result<(), AnimationError> {
+        let segment = self
+            .animation
+            .segment(name)
+            .ok_or(AnimationError::NoSuchSegment)?;
+        self.playback = Some(self.animation.play_segment(&segment));
+        self.segment = Some(segment);
+        self.queued = None;
+        self.due = (self.now)();
+        Ok(())
+    }
+
+    /// Queues the segment with the given name, to be played once the current segment is done,
+    /// without a gap. Replaces the segment queued before.
+    ///
+    /// A segment that loops until stopped is never done, see [`stop_looping`](Self::stop_looping).
+    pub fn queue(&mut self, name: &str) -> Result<(), AnimationError> {
+        let segment = self
+            .animation
+            .segment(name)
+            .ok_or(AnimationError::NoSuchSegment)?;
+        if self.playback.is_some() {
+            self.queued = Some(segment);
+        } else {
+            self.playback = Some(self.animation.play_segment(&segment));
+            self.segment = Some(segment);
+            self.due = (self.now)();
+        }
+        Ok(())
+    }
+
+    /// Lets the current segment play to its end instead of starting another loop.
+    pub fn stop_looping(&mut self) {
+        if let Some(playback) = &mut self.playback {
+            playback.stop_looping();
+        }
+    }
+
+    /// Shows the frame that is due, if any.
+    ///
+    /// Decodes every frame up to the one that should be displayed now into `framebuffer`, which
+    /// needs to hold the frame shown last, and returns its index. Returns `None` if the frame shown
+    /// last is still current, or if nothing is playing.
+    ///
+    /// To keep a stalled caller from decoding forever, at most as many frames as the animation has
+    /// are decoded per call.
+    pub fn update<B: Endianness>(
+        &mut self,
+        framebuffer: &mut [u16],
+    ) -> Result<Option<usize>, AnimationError> {
+        let now = (self.now)();
+        let mut shown = None;
+
+        for _ in 0..self.animation.frame_count() {
+            if !is_due(now, self.due) {
+                break;
+            }
+            let Some((index, frame)) = self.next_frame() else {
+                break;
+            };
+
+            frame.decode_into::<B>(framebuffer)?;
+            self.due = self.due.wrapping_add(u32::from(frame.duration_ms));
+            self.frame = Some(index);
+            shown = Some(index);
+        }
+
+        Ok(shown)
+    }
+
+    /// Milliseconds until the next frame is due, e.g. to sleep until then. `Some(0)` if it is due
+    /// already, `None` if nothing is left to play.
+    pub fn time_until_next(&mut self) -> Option<u32> {
+        self.playback.as_ref()?;
+        let now = (self.now)();
+        Some(if is_due(now, self.due) {
+            0
+        } else {
+            self.due.wrapping_sub(now)
+        })
+    }
+
+    /// Returns whether all segments are played to their end.
+    pub fn is_finished(&self) -> bool {
+        self.playback.is_none()
+    }
+
+    /// Index of the frame shown last.
+    pub fn frame(&self) -> Option<usize> {
+        self.frame
+    }
+
+    /// The segment that is playing.
+    pub fn segment(&self) -> Option<&Segment<'a>> {
+        self.segment.as_ref()
+    }
+
+    /// The segment that is played next.
+    pub fn queued(&self) -> Option<&Segment<'a>> {
+        self.queued.as_ref()
+    }
+
+    pub fn animation(&self) -> &Animation<'a> {
+        &self.animation
+    }
+
+    fn next_frame(&mut self) -> Option<(usize, Frame<'a>)> {
+        loop {
+            let playback = self.playback.as_mut()?;
+            if let Some(index) = playback.next_index() {
+                return playback.next().map(|frame| (index, frame));
+            }
+
+            self.segment = self.queued.take();
+            self.playback = self
+                .segment
+                .as_ref()
+                .map(|segment| self.animation.play_segment(segment));
+        }
+    }
+}
+
+/// Returns whether `due` is reached at `now`, with both allowed to wrap around.
+#[inline]
+fn is_due(now: u32, due: u32) -> bool {
+    (now.wrapping_sub(due) as i32) >= 0
+}
@@ -0,0 +1,218 @@
+//! Glyph atlases: fonts (or icon sets) stored as one Q565 image, with a table mapping glyph IDs to
+//! the areas of the image they occupy.
+//!
+//! The table is a plain slice of [`Glyph`]s, so it can live in a `static` next to the image:
+//!
+//! ```
+//! use q565::{
+//!     atlas::{Glyph, GlyphAtlas},
+//!     byteorder::BigEndian,
+//!     encode::Q565EncodeContext,
+//!     Rect,
+//! };
+//!
+//! // two 3x4 glyphs, side by side
+//! let mut image = Vec::new();
+//! Q565EncodeContext::encode_to_vec(6, 4, &[0xFFFF; 24], &mut image).unwrap();
+//! static GLYPHS: [Glyph; 2] = [
+//!     Glyph::new('a' as u32, Rect { x: 0, y: 0, width: 3, height: 4 }, 4),
+//!     Glyph::new('b' as u32, Rect { x: 3, y: 0, width: 3, height: 4 }, 4),
+//! ];
+//!
+//! let atlas = GlyphAtlas::new(&image, &GLYPHS).unwrap();
+//! let mut rows = 0;
+//! let glyph = atlas
+//!     .draw_glyph::<BigEndian>('b' as u32, |row: &[u16]| {
+//!         assert_eq!(row.len(), 3);
+//!         rows += 1;
+//!     })
+//!     .unwrap();
+//! assert_eq!((rows, glyph.advance), (4, 4));
+//! ```
+//!
+//! Since the ops of an image can only be decoded in order, drawing a glyph decodes the whole atlas.
+//! Keep atlases small, e.g. one per font size and only with the glyphs an app actually uses.
+
+use crate::{
+    byteorder::Endianness,
+    decode::{DecodeError, InfallibleDecodeOutput, PixelSink, Q565DecodeContext},
+    ColorFormat, HeaderInfo, Rect, Rgb565,
+};
+
+error_enum! {
+    pub enum AtlasError {
+        /// The atlas is not a valid Q565 image.
+        Decode { source: DecodeError } = 1,
+        /// A glyph lies (partly) outside of the atlas.
+        GlyphOutOfBounds = 2,
+        /// The glyphs are not sorted by their ID, or an ID is used twice.
+        UnsortedGlyphs = 3,
+        /// The atlas contains no glyph with the given ID.
+        NoSuchGlyph = 4,
+    }
+}
+
+impl From<DecodeError> for AtlasError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// A glyph of a [`GlyphAtlas`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyph {
+    /// The ID the glyph is looked up by, e.g. its Unicode scalar value.
+    pub id: u32,
+    /// The area of the atlas the glyph occupies.
+    pub rect: Rect,
+    /// Horizontal distance from the start of this glyph to the start of the next one, in pixels.
+    pub advance: u16,
+}
+
+impl Glyph {
+    pub const fn new(id: u32, rect: Rect, advance: u16) -> Self {
+        Self { id, rect, advance }
+    }
+}
+
+/// A Q565 image containing glyphs, and the table of where they are.
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphAtlas<'a> {
+    image: &'a [u8],
+    header: (u16, u16),
+    glyphs: &'a [Glyph],
+}
+
+impl<'a> GlyphAtlas<'a> {
+    /// Checks the atlas image and the glyph table.
+    ///
+    /// The glyphs must be sorted by their ID, so that they can be looked up by binary search.
+    pub fn new(image: &'a [u8], glyphs: &'a [Glyph]) -> Result<Self, AtlasError> {
+        let (HeaderInfo { width, height, .. }, _) = Q565DecodeContext::decode_header(image)?;
+
+        ensure!(
+            glyphs
+                .iter()
+                .all(|glyph| glyph.rect.fits_within(width, height)),
+            AtlasError::GlyphOutOfBounds
+        );
+        ensure!(
+            glyphs.windows(2).all(|pair| pair[0].id < pair[1].id),
+            AtlasError::UnsortedGlyphs
+        );
+
+        Ok(Self {
+            image,
+            header: (width, height),
+            glyphs,
+        })
+    }
+
+    /// Returns the width and height of the atlas image.
+    pub fn size(&self) -> (u16, u16) {
+        self.header
+    }
+
+    pub fn glyphs(&self) -> &'a [Glyph] {
+        self.glyphs
+    }
+
+    /// Looks up the glyph with the given ID.
+    pub fn glyph(&self, id: u32) -> Option<&'a Glyph> {
+        self.glyphs
+            .binary_search_by_key(&id, |glyph| glyph.id)
+            .ok()
+            .map(|index| &self.glyphs[index])
+    }
+
+    /// Decodes the glyph with the given ID and passes its pixels to `sink`, row by row.
+    ///
+    /// A row is passed in one chunk if it is at most 64 pixels wide, and in several otherwise;
+    /// chunks never span more than one row. Returns the glyph, e.g. to advance the pen by.
+    pub fn draw_glyph<B: Endianness>(
+        &self,
+        id: u32,
+        sink: impl PixelSink<u16>,
+    ) -> Result<&'a Glyph, AtlasError> {
+        let glyph = self.glyph(id).ok_or(AtlasError::NoSuchGlyph)?;
+        let output = GlyphDecodeOutput {
+            sink,
+            atlas_width: usize::from(self.header.0),
+            rect: glyph.rect,
+            row: [0; 64],
+            filled: 0,
+            output_idx: 0,
+        };
+        Q565DecodeContext::decode::<B>(self.image, output)?;
+        Ok(glyph)
+    }
+}
+
+/// Decode output passing on the pixels within `rect` of the decoded atlas.
+struct GlyphDecodeOutput<S> {
+    sink: S,
+    atlas_width: usize,
+    rect: Rect,
+    row: [u16; 64],
+    filled: usize,
+    output_idx: usize,
+}
+
+impl<S: PixelSink<u16>> GlyphDecodeOutput<S> {
+    fn flush(&mut self) {
+        if self.filled > 0 {
+            self.sink.push_chunk(&self.row[..self.filled]);
+            self.filled = 0;
+        }
+    }
+}
+
+impl<S: PixelSink<u16>> InfallibleDecodeOutput for GlyphDecodeOutput<S> {
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.write_many_pixels::<B>(color, 1);
+    }
+
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        let left = usize::from(self.rect.x);
+        let right = left + usize::from(self.rect.width);
+        let rows =
+            usize::from(self.rect.y)..usize::from(self.rect.y) + usize::from(self.rect.height);
+        let color = Rgb565::to_output::<B>(color);
+
+        let end = self.output_idx + count;
+        while self.output_idx < end {
+            let y = self.output_idx / self.atlas_width;
+            let x = self.output_idx % self.atlas_width;
+            let n = (end - self.output_idx).min(self.atlas_width - x);
+            self.output_idx += n;
+
+            if !rows.contains(&y) {
+                continue;
+            }
+            let mut remaining = (x + n).min(right).saturating_sub(x.max(left));
+            while remaining > 0 {
+                let fill = remaining.min(self.row.len() - self.filled);
+                self.row[self.filled..self.filled + fill].fill(color);
+                self.filled += fill;
+                remaining -= fill;
+                if self.filled == self.row.len() {
+                    self.flush();
+                }
+            }
+            if x + n >= right {
+                self.flush();
+            }
+        }
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
@@ -0,0 +1,487 @@
+//! Bundles of multiple Q565 images in one blob, e.g. all icons of a firmware image.
+//!
+//! # Layout
+//!
+//! All integers are little-endian.
+//!
+//! - 4-byte magic: `q5bn`
+//! - u16le entry count
+//! - one 8-byte record per entry:
+//!   - u32le offset of the entry data, from the start of the bundle
+//!   - u32le length of the entry data
+//! - entry data (the encoded images)
+//!
+//! Entries are addressed by their index in the entry table. Several records may point to the same
+//! data: [`write_bundle`] stores identical images only once.
+//!
+//! ## Extended layout
+//!
+//! Bundles with an index of [content hashes](content_hash) or checksums start with an extended
+//! header instead:
+//!
+//! - 4-byte magic: `q5bx`
+//! - u8 flags:
+//!   - bit 0: [`HASHES_FLAG`], every record is followed by the u64le content hash of its entry
+//!   - bit 1: [`CRCS_FLAG`], every record is followed by the u32le CRC-32 of its entry data (after
+//!     the content hash, if both are present)
+//!   - bit 2: [`DIGEST_FLAG`], the header is followed by the SHA-256 of the whole bundle
+//!   - bits 3..=7: reserved, must be zero
+//! - u16le entry count
+//! - if [`DIGEST_FLAG`] is set: 32-byte SHA-256 of all bytes of the bundle except these 32
+//!
+//! followed by the records and the entry data as above.
+//!
+//! # Caching
+//!
+//! An [`AssetCache`] keeps recently used entries decoded in a fixed RAM pool, for UIs that draw
+//! the same icons over and over.
+//!
+//! # Flash
+//!
+//! Bundles in flash that isn't memory-mapped can be read with a `FlashBundle` through the
+//! `embedded-storage` traits instead, with the `embedded-storage` feature.
+//!
+//! # Integrity
+//!
+//! Bundles stored in external flash can be checked at boot with [`Bundle::verify`], which checks
+//! the SHA-256 and all CRCs, or entry by entry with [`Bundle::get_verified`] right before drawing.
+//!
+//! # Updates
+//!
+//! [`diff`] computes a [`Patch`] from an old to a new bundle, which only holds the entries that
+//! changed. [`apply_patch`] turns the old bundle into the new one again, e.g. on a device receiving
+//! its assets over the air.
+
+use crate::byteorder::{Endianness, NativeEndian};
+use crate::decode::{InfallibleDecodeOutput, Q565DecodeContext};
+use crate::saturating_usize;
+#[cfg(feature = "alloc")]
+use crate::Rgb565;
+#[cfg(feature = "alloc")]
+use alloc::{collections::BTreeMap, vec::Vec};
+
+#[cfg(not(feature = "forbid-unsafe"))]
+mod cache;
+#[cfg(all(feature = "embedded-storage", not(feature = "forbid-unsafe")))]
+mod flash;
+mod integrity;
+#[cfg(feature = "alloc")]
+mod patch;
+
+#[cfg(not(feature = "forbid-unsafe"))]
+pub use cache::*;
+#[cfg(all(feature = "embedded-storage", not(feature = "forbid-unsafe")))]
+pub use flash::*;
+pub use integrity::*;
+#[cfg(feature = "alloc")]
+pub use patch::*;
+
+pub const BUNDLE_MAGIC: &[u8; 4] = b"q5bn";
+/// Magic bytes of the [extended layout](self#extended-layout).
+pub const EXTENDED_BUNDLE_MAGIC: &[u8; 4] = b"q5bx";
+/// Flag of the [extended layout](self#extended-layout) marking an index with content hashes.
+pub const HASHES_FLAG: u8 = 0b1;
+/// Flag of the [extended layout](self#extended-layout) marking an index with a CRC-32 per entry.
+pub const CRCS_FLAG: u8 = 0b10;
+/// Flag of the [extended layout](self#extended-layout) marking a bundle with a SHA-256 digest.
+pub const DIGEST_FLAG: u8 = 0b100;
+const SUPPORTED_FLAGS: u8 = HASHES_FLAG | CRCS_FLAG | DIGEST_FLAG;
+
+const HEADER_LEN: usize = 6;
+const EXTENDED_HEADER_LEN: usize = 7;
+const RECORD_LEN: usize = 8;
+const HASH_LEN: usize = 8;
+const CRC_LEN: usize = 4;
+const DIGEST_LEN: usize = 32;
+
+error_enum! {
+    pub enum BundleError {
+        /// The data does not start with the magic bytes `q5bn` or `q5bx`.
+        InvalidMagic = 1,
+        /// The extended header sets flags that aren't supported by this reader.
+        UnsupportedFlags = 2,
+        /// The data ended before the entry table or an entry.
+        UnexpectedEof = 3,
+        /// The bundle would hold more than 65535 entries.
+        TooManyEntries = 4,
+        /// The bundle would be larger than 4 GiB.
+        TooLarge = 5,
+        /// The patch is malformed.
+        InvalidPatch = 6,
+        /// An entry the patch takes from the old bundle isn't there.
+        PatchMismatch = 7,
+        /// There is no entry at the given index.
+        NoSuchEntry = 8,
+        /// An entry doesn't match its CRC-32.
+        CorruptEntry { index: usize } = 9,
+        /// The bundle doesn't match its SHA-256.
+        CorruptBundle = 10,
+    }
+}
+
+/// A parsed, borrowed bundle.
+#[derive(Debug, Clone, Copy)]
+pub struct Bundle<'a> {
+    data: &'a [u8],
+    len: usize,
+    flags: u8,
+}
+
+impl<'a> Bundle<'a> {
+    /// Parses a bundle, checking that the entry table and all entries lie within `data`.
+    pub fn new(data: &'a [u8]) -> Result<Self, BundleError> {
+        ensure!(data.len() >= HEADER_LEN, BundleError::UnexpectedEof);
+
+        let (flags, count) = if &data[..4] == BUNDLE_MAGIC {
+            (0, [data[4], data[5]])
+        } else if &data[..4] == EXTENDED_BUNDLE_MAGIC {
+            ensure!(
+                data.len() >= EXTENDED_HEADER_LEN,
+                BundleError::UnexpectedEof
+            );
+            ensure!(
+                data[4] & !SUPPORTED_FLAGS == 0,
+                BundleError::UnsupportedFlags
+            );
+            (data[4], [data[5], data[6]])
+        } else {
+            return Err(BundleError::InvalidMagic);
+        };
+
+        let len = usize::from(u16::from_le_bytes(count));
+        let bundle = Self { data, len, flags };
+        ensure!(
+            data.len() >= bundle.table_start() + len * bundle.record_len(),
+            BundleError::UnexpectedEof
+        );
+
+        for index in 0..len {
+            let (offset, length) = bundle.record(index);
+            ensure!(
+                offset
+                    .checked_add(length)
+                    .is_some_and(|end| end <= data.len()),
+                BundleError::UnexpectedEof
+            );
+        }
+
+        Ok(bundle)
+    }
+
+    /// Number of entries in the bundle.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether the index holds the content hash of every entry.
+    #[inline]
+    pub fn has_hashes(&self) -> bool {
+        self.flags & HASHES_FLAG != 0
+    }
+
+    /// Returns the data of the entry at `index`.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&'a [u8]> {
+        if index >= self.len {
+            return None;
+        }
+
+        let (offset, length) = self.record(index);
+        self.data.get(offset..offset + length)
+    }
+
+    /// Returns the [content hash](content_hash) of the entry at `index`, as stored in the index.
+    ///
+    /// `None` if the bundle has no hashes, see [`has_hashes`](Self::has_hashes).
+    pub fn hash(&self, index: usize) -> Option<u64> {
+        if index >= self.len || !self.has_hashes() {
+            return None;
+        }
+
+        let start = self.table_start() + index * self.record_len() + RECORD_LEN;
+        let hash = &self.data[start..start + HASH_LEN];
+        Some(u64::from_le_bytes([
+            hash[0], hash[1], hash[2], hash[3], hash[4], hash[5], hash[6], hash[7],
+        ]))
+    }
+
+    /// Iterates over the data of all entries, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        (0..self.len).filter_map(|index| self.get(index))
+    }
+
+    fn table_start(&self) -> usize {
+        if self.data[..4] == *BUNDLE_MAGIC {
+            HEADER_LEN
+        } else {
+            table_start(self.flags)
+        }
+    }
+
+    fn record_len(&self) -> usize {
+        record_len(self.flags)
+    }
+
+    fn record(&self, index: usize) -> (usize, usize) {
+        let start = self.table_start() + index * self.record_len();
+        let record = &self.data[start..start + RECORD_LEN];
+        let offset = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        let length = u32::from_le_bytes([record[4], record[5], record[6], record[7]]);
+        (saturating_usize(offset), saturating_usize(length))
+    }
+}
+
+/// Hashes the content of an entry: the dimensions and pixels of the decoded image, so that
+/// differently encoded but identical images have the same hash. Entries that aren't valid Q565
+/// images are hashed by their bytes instead.
+///
+/// The hash is 64-bit FNV-1a, which is fine for finding duplicates, but not for detecting
+/// tampering.
+pub fn content_hash(entry: &[u8]) -> u64 {
+    let mut output = HashDecodeOutput {
+        hash: Fnv1a::new(),
+        position: 0,
+    };
+
+    match Q565DecodeContext::decode::<NativeEndian>(entry, &mut output) {
+        Ok((header, _)) => {
+            output.hash.write(&header.width.to_le_bytes());
+            output.hash.write(&header.height.to_le_bytes());
+            output.hash.finish()
+        }
+        Err(_) => {
+            let mut hash = Fnv1a::new();
+            // keeps the bytes of an invalid entry from colliding with the pixels of a valid one
+            hash.write(&[0xFF]);
+            hash.write(entry);
+            hash.finish()
+        }
+    }
+}
+
+/// Writes the given entries into a new bundle, storing identical entries only once.
+///
+/// Entries are identical if they are byte-identical, or if they decode to the same image. The
+/// index doesn't hold content hashes, see [`BundleWriter::with_hashes`].
+#[cfg(feature = "alloc")]
+pub fn write_bundle(entries: &[&[u8]]) -> Result<Vec<u8>, BundleError> {
+    BundleWriter::new().write(entries)
+}
+
+/// Writes bundles, with options.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default)]
+pub struct BundleWriter {
+    flags: u8,
+}
+
+#[cfg(feature = "alloc")]
+impl BundleWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores the [content hash](content_hash) of every entry in the index, using the [extended
+    /// layout](self#extended-layout).
+    pub fn with_hashes(mut self) -> Self {
+        self.flags |= HASHES_FLAG;
+        self
+    }
+
+    /// Stores the CRC-32 of every entry in the index, see [`Bundle::get_verified`].
+    pub fn with_crcs(mut self) -> Self {
+        self.flags |= CRCS_FLAG;
+        self
+    }
+
+    /// Stores the SHA-256 of the whole bundle, see [`Bundle::verify`].
+    pub fn with_digest(mut self) -> Self {
+        self.flags |= DIGEST_FLAG;
+        self
+    }
+
+    /// Writes the given entries into a new bundle, storing identical entries only once, like
+    /// [`write_bundle`].
+    pub fn write(&self, entries: &[&[u8]]) -> Result<Vec<u8>, BundleError> {
+        let count = u16::try_from(entries.len())
+            .ok()
+            .ok_or(BundleError::TooManyEntries)?;
+
+        let hashes: Vec<u64> = entries.iter().map(|entry| content_hash(entry)).collect();
+
+        // index of the entry whose data is stored for each entry
+        let mut stored: Vec<usize> = Vec::with_capacity(entries.len());
+        let mut by_hash: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+        for (index, (entry, &hash)) in entries.iter().zip(&hashes).enumerate() {
+            let candidates = by_hash.entry(hash).or_default();
+            match candidates
+                .iter()
+                .find(|&&candidate| is_identical(entries[candidate], entry))
+            {
+                Some(&candidate) => stored.push(candidate),
+                None => {
+                    candidates.push(index);
+                    stored.push(index);
+                }
+            }
+        }
+
+        let table_start = if self.flags == 0 {
+            HEADER_LEN
+        } else {
+            table_start(self.flags)
+        };
+        let table_end = table_start + entries.len() * record_len(self.flags);
+        let data_len: usize = entries
+            .iter()
+            .zip(&stored)
+            .enumerate()
+            .filter(|&(index, (_, &stored))| index == stored)
+            .map(|(_, (entry, _))| entry.len())
+            .sum();
+        let total_len = table_end + data_len;
+        ensure!(u32::try_from(total_len).is_ok(), BundleError::TooLarge);
+
+        let mut output = Vec::with_capacity(total_len);
+        if self.flags == 0 {
+            output.extend_from_slice(BUNDLE_MAGIC);
+        } else {
+            output.extend_from_slice(EXTENDED_BUNDLE_MAGIC);
+            output.push(self.flags);
+        }
+        output.extend_from_slice(&count.to_le_bytes());
+        // filled in once the rest is written
+        output.resize(table_start, 0);
+
+        let mut offsets = Vec::with_capacity(entries.len());
+        let mut next_offset = table_end;
+        for (index, &stored) in stored.iter().enumerate() {
+            let offset = if index == stored {
+                let offset = next_offset;
+                next_offset += entries[index].len();
+                offset
+            } else {
+                offsets[stored]
+            };
+            offsets.push(offset);
+
+            output.extend_from_slice(&(offset as u32).to_le_bytes());
+            output.extend_from_slice(&(entries[stored].len() as u32).to_le_bytes());
+            if self.flags & HASHES_FLAG != 0 {
+                output.extend_from_slice(&hashes[index].to_le_bytes());
+            }
+            if self.flags & CRCS_FLAG != 0 {
+                output.extend_from_slice(&crc32(entries[stored]).to_le_bytes());
+            }
+        }
+        for (index, &stored) in stored.iter().enumerate() {
+            if index == stored {
+                output.extend_from_slice(entries[index]);
+            }
+        }
+
+        if self.flags & DIGEST_FLAG != 0 {
+            let digest = bundle_digest(&output);
+            output[EXTENDED_HEADER_LEN..table_start].copy_from_slice(&digest);
+        }
+
+        Ok(output)
+    }
+}
+
+/// Offset of the entry table in a bundle using the extended layout with the given flags.
+fn table_start(flags: u8) -> usize {
+    if flags & DIGEST_FLAG != 0 {
+        EXTENDED_HEADER_LEN + DIGEST_LEN
+    } else {
+        EXTENDED_HEADER_LEN
+    }
+}
+
+/// Length of a record in a bundle with the given flags.
+fn record_len(flags: u8) -> usize {
+    let mut len = RECORD_LEN;
+    if flags & HASHES_FLAG != 0 {
+        len += HASH_LEN;
+    }
+    if flags & CRCS_FLAG != 0 {
+        len += CRC_LEN;
+    }
+    len
+}
+
+/// Returns whether two entries with the same content hash are byte-identical, or decode to the
+/// same image.
+#[cfg(feature = "alloc")]
+fn is_identical(a: &[u8], b: &[u8]) -> bool {
+    if a == b {
+        return true;
+    }
+
+    let decode = Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>;
+    match (decode(a), decode(b)) {
+        (Ok((header_a, pixels_a)), Ok((header_b, pixels_b))) => {
+            header_a.width == header_b.width
+                && header_a.height == header_b.height
+                && pixels_a == pixels_b
+        }
+        _ => false,
+    }
+}
+
+/// 64-bit FNV-1a.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Decode output hashing the pixels instead of storing them.
+struct HashDecodeOutput {
+    hash: Fnv1a,
+    position: usize,
+}
+
+impl InfallibleDecodeOutput for HashDecodeOutput {
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.hash.write(&color.to_le_bytes());
+        self.position += 1;
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        for _ in 0..count {
+            self.hash.write(&color.to_le_bytes());
+        }
+        self.position += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.position
+    }
+}
@@ -0,0 +1,236 @@
+use super::Bundle;
+use crate::byteorder::Endianness;
+use crate::{
+    decode::{DecodeError, Q565DecodeContext, RectDecodeOutput},
+    Rect, Rgb565,
+};
+use core::marker::PhantomData;
+
+error_enum! {
+    pub enum AssetCacheError {
+        /// There is no entry at the given index.
+        NoSuchEntry = 1,
+        /// The entry is not a valid Q565 image.
+        Decode { source: DecodeError } = 2,
+        /// The decoded image is larger than the whole cache.
+        TooLarge = 3,
+    }
+}
+
+impl From<DecodeError> for AssetCacheError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// A decoded image borrowed from an [`AssetCache`].
+#[derive(Debug, Clone, Copy)]
+pub struct CachedImage<'c> {
+    pub width: u16,
+    pub height: u16,
+    /// The pixels, row by row, in the byte order of the cache.
+    pub pixels: &'c [u16],
+}
+
+/// A cached entry, occupying `len` pixels of the pool from `start` on.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    index: usize,
+    start: usize,
+    width: u16,
+    height: u16,
+    last_used: u32,
+}
+
+impl Slot {
+    fn len(&self) -> usize {
+        usize::from(self.width) * usize::from(self.height)
+    }
+}
+
+/// The pool, aligned so that it can be handed out as pixels.
+#[repr(C, align(2))]
+struct Pool<const BYTES: usize>([u8; BYTES]);
+
+/// Decodes the entries of a [`Bundle`] on demand into a fixed pool of `BYTES` bytes, evicting the
+/// least recently used ones when it runs full.
+///
+/// Meant for icon-heavy UIs: the bundle stays in (memory-mapped) flash, and the icons that are on
+/// screen stay decoded in RAM, ready to be copied to the display. At most `SLOTS` images are
+/// cached at once, which must be at least 1. The pixels are stored in the byte order `B`.
+///
+/// ```
+/// use q565::{
+///     bundle::{write_bundle, AssetCache, Bundle},
+///     byteorder::BigEndian,
+///     encode::Q565EncodeContext,
+/// };
+///
+/// let mut icon = Vec::new();
+/// Q565EncodeContext::encode_to_vec(2, 2, &[0xF800; 4], &mut icon).unwrap();
+/// let data = write_bundle(&[&icon]).unwrap();
+///
+/// let mut cache = AssetCache::<BigEndian, 1024>::new(Bundle::new(&data).unwrap());
+/// let image = cache.get(0).unwrap();
+/// assert_eq!((image.width, image.height), (2, 2));
+/// assert_eq!(image.pixels, [0xF800u16.to_be(); 4]);
+/// ```
+pub struct AssetCache<'a, B, const BYTES: usize, const SLOTS: usize = 16> {
+    bundle: Bundle<'a>,
+    pool: Pool<BYTES>,
+    /// The cached entries, ordered by their position in the pool.
+    slots: [Option<Slot>; SLOTS],
+    clock: u32,
+    _byte_order: PhantomData<B>,
+}
+
+impl<'a, B: Endianness, const BYTES: usize, const SLOTS: usize> AssetCache<'a, B, BYTES, SLOTS> {
+    /// Number of pixels the pool holds.
+    pub const CAPACITY: usize = BYTES / 2;
+
+    /// Without a slot, nothing can be evicted to make room, so inserting would never finish.
+    const HAS_SLOTS: () = assert!(SLOTS > 0, "the cache needs at least one slot");
+
+    pub fn new(bundle: Bundle<'a>) -> Self {
+        let () = Self::HAS_SLOTS;
+
+        Self {
+            bundle,
+            pool: Pool([0; BYTES]),
+            slots: [None; SLOTS],
+            clock: 0,
+            _byte_order: PhantomData,
+        }
+    }
+
+    pub fn bundle(&self) -> &Bundle<'a> {
+        &self.bundle
+    }
+
+    /// Returns whether the entry at `index` is currently decoded.
+    pub fn contains(&self, index: usize) -> bool {
+        self.slots.iter().flatten().any(|slot| slot.index == index)
+    }
+
+    /// Drops all decoded images.
+    pub fn clear(&mut self) {
+        self.slots = [None; SLOTS];
+    }
+
+    /// Returns the decoded entry at `index`, decoding it first if it isn't cached.
+    ///
+    /// To make room, the least recently returned images are evicted, and the remaining ones are
+    /// moved together.
+    pub fn get(&mut self, index: usize) -> Result<CachedImage<'_>, AssetCacheError> {
+        self.clock = self.clock.wrapping_add(1);
+
+        let position = match self
+            .slots
+            .iter()
+            .position(|slot| slot.is_some_and(|slot| slot.index == index))
+        {
+            Some(position) => position,
+            None => self.insert(index)?,
+        };
+
+        let slot = self.slots[position]
+            .as_mut()
+            .ok_or(AssetCacheError::NoSuchEntry)?;
+        slot.last_used = self.clock;
+        let slot = *slot;
+
+        Ok(CachedImage {
+            width: slot.width,
+            height: slot.height,
+            pixels: &self.pixels()[slot.start..slot.start + slot.len()],
+        })
+    }
+
+    /// Decodes the entry at `index` into the pool, returning the position of its slot.
+    fn insert(&mut self, index: usize) -> Result<usize, AssetCacheError> {
+        let data = self.bundle.get(index).ok_or(AssetCacheError::NoSuchEntry)?;
+        let (header, _) = Q565DecodeContext::decode_header(data)?;
+        let len = header
+            .pixel_count()
+            .filter(|&len| len <= Self::CAPACITY)
+            .ok_or(AssetCacheError::TooLarge)?;
+
+        while self.used() + len > Self::CAPACITY || self.slots.iter().all(Option::is_some) {
+            self.evict_least_recently_used();
+        }
+        let start = self.compact();
+
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: header.width,
+            height: header.height,
+        };
+        let output = &mut self.pixels_mut()[start..start + len];
+        let decoded = Q565DecodeContext::decode::<B>(
+            data,
+            RectDecodeOutput::<Rgb565>::new(output, usize::from(header.width), rect),
+        );
+        decoded?;
+
+        // `compact` moved all slots to the front
+        let position = self.slots.iter().take_while(|slot| slot.is_some()).count();
+        self.slots[position] = Some(Slot {
+            index,
+            start,
+            width: header.width,
+            height: header.height,
+            last_used: self.clock,
+        });
+        Ok(position)
+    }
+
+    /// Number of pixels used by cached images.
+    fn used(&self) -> usize {
+        self.slots.iter().flatten().map(Slot::len).sum()
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        let clock = self.clock;
+        let lru = self
+            .slots
+            .iter_mut()
+            .filter(|slot| slot.is_some())
+            .max_by_key(|slot| slot.map(|slot| clock.wrapping_sub(slot.last_used)));
+        if let Some(lru) = lru {
+            *lru = None;
+        }
+    }
+
+    /// Moves all cached images to the start of the pool, and their slots to the front. Returns the
+    /// start of the free space.
+    fn compact(&mut self) -> usize {
+        let mut next = 0;
+        let mut position = 0;
+        for index in 0..SLOTS {
+            let Some(mut slot) = self.slots[index].take() else {
+                continue;
+            };
+
+            let len = slot.len();
+            self.pixels_mut()
+                .copy_within(slot.start..slot.start + len, next);
+            slot.start = next;
+            next += len;
+
+            self.slots[position] = Some(slot);
+            position += 1;
+        }
+        next
+    }
+
+    fn pixels(&self) -> &[u16] {
+        // SAFETY: the pool is aligned for u16, and any bytes are valid pixels
+        unsafe { core::slice::from_raw_parts(self.pool.0.as_ptr().cast(), Self::CAPACITY) }
+    }
+
+    fn pixels_mut(&mut self) -> &mut [u16] {
+        // SAFETY: see `pixels`
+        unsafe { core::slice::from_raw_parts_mut(self.pool.0.as_mut_ptr().cast(), Self::CAPACITY) }
+    }
+}
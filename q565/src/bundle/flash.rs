@@ -0,0 +1,462 @@
+use super::{
+    BundleError, BUNDLE_MAGIC, EXTENDED_BUNDLE_MAGIC, EXTENDED_HEADER_LEN, HEADER_LEN, RECORD_LEN,
+    SUPPORTED_FLAGS,
+};
+use crate::byteorder::Endianness;
+use crate::{
+    decode::{
+        streaming_no_header::Q565StreamingDecodeContext, DecodeError, PixelSink, Q565DecodeContext,
+    },
+    saturating_usize, ColorArraySize, ColorFormat, HeaderInfo, Rgb565,
+};
+use embedded_storage::nor_flash::{NorFlashError, NorFlashErrorKind, ReadNorFlash};
+use embedded_storage_async::nor_flash::ReadNorFlash as AsyncReadNorFlash;
+
+/// Number of bytes read from flash at once. Flash with a larger read size is not supported.
+const READ_CHUNK_LEN: usize = 64;
+
+error_enum! {
+    pub enum FlashBundleError {
+        /// Reading from flash failed.
+        Flash { kind: NorFlashErrorKind } = 1,
+        /// The bundle in flash is malformed.
+        Bundle { source: BundleError } = 2,
+        /// The entry is not a valid Q565 image, or uses a color array profile other than the
+        /// default one.
+        Decode { source: DecodeError } = 3,
+        /// The read size of the flash is larger than 64 bytes.
+        UnsupportedReadSize = 4,
+    }
+}
+
+impl From<BundleError> for FlashBundleError {
+    fn from(source: BundleError) -> Self {
+        Self::Bundle { source }
+    }
+}
+
+impl From<DecodeError> for FlashBundleError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+fn flash_error(error: impl NorFlashError) -> FlashBundleError {
+    FlashBundleError::Flash { kind: error.kind() }
+}
+
+/// Location of an entry of a [`FlashBundle`], in absolute flash offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashEntry {
+    pub offset: u32,
+    pub length: u32,
+}
+
+/// A bundle in flash that isn't memory-mapped, e.g. external SPI NOR, read through the
+/// [`embedded_storage`] traits.
+///
+/// Unlike [`Bundle`](super::Bundle), nothing is borrowed: the entry table and the entries are read
+/// on demand, in chunks of 64 bytes, and entries are decoded straight into a [`PixelSink`]
+/// without holding them in RAM. See [`AsyncFlashBundle`] for `embedded-storage-async`.
+#[derive(Debug)]
+pub struct FlashBundle<F> {
+    flash: F,
+    layout: Layout,
+}
+
+/// An [`AsyncFlashBundle`] read through the [`embedded_storage_async`] traits, see
+/// [`FlashBundle`].
+#[derive(Debug)]
+pub struct AsyncFlashBundle<F> {
+    flash: F,
+    layout: Layout,
+}
+
+/// Where the parts of a bundle are, parsed from its header.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    base: u32,
+    /// Size of the flash from `base` on.
+    available: u32,
+    len: usize,
+    table_start: u32,
+    record_len: u32,
+}
+
+impl Layout {
+    /// Parses the header of the bundle at `base`. `header` holds its first 7 bytes, or less if the
+    /// flash ends before.
+    fn parse(base: u32, available: u32, header: &[u8]) -> Result<Self, BundleError> {
+        ensure!(header.len() >= HEADER_LEN, BundleError::UnexpectedEof);
+
+        let (flags, count, header_len) = if &header[..4] == BUNDLE_MAGIC {
+            (0, [header[4], header[5]], HEADER_LEN)
+        } else if &header[..4] == EXTENDED_BUNDLE_MAGIC {
+            ensure!(
+                header.len() >= EXTENDED_HEADER_LEN,
+                BundleError::UnexpectedEof
+            );
+            ensure!(
+                header[4] & !SUPPORTED_FLAGS == 0,
+                BundleError::UnsupportedFlags
+            );
+            (
+                header[4],
+                [header[5], header[6]],
+                super::table_start(header[4]),
+            )
+        } else {
+            return Err(BundleError::InvalidMagic);
+        };
+
+        let len = usize::from(u16::from_le_bytes(count));
+        let record_len = super::record_len(flags) as u32;
+        let table_start = header_len as u32;
+        ensure!(
+            u64::from(table_start) + len as u64 * u64::from(record_len) <= u64::from(available),
+            BundleError::UnexpectedEof
+        );
+
+        Ok(Self {
+            base,
+            available,
+            len,
+            table_start,
+            record_len,
+        })
+    }
+
+    /// Flash offset of the record of the entry at `index`.
+    fn record_offset(&self, index: usize) -> Result<u32, BundleError> {
+        ensure!(index < self.len, BundleError::NoSuchEntry);
+        Ok(self.base + self.table_start + index as u32 * self.record_len)
+    }
+
+    /// Turns a record into the location of the entry, checking that it lies within the flash.
+    fn entry(&self, record: [u8; RECORD_LEN]) -> Result<FlashEntry, BundleError> {
+        let offset = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        let length = u32::from_le_bytes([record[4], record[5], record[6], record[7]]);
+        ensure!(
+            u64::from(offset) + u64::from(length) <= u64::from(self.available),
+            BundleError::UnexpectedEof
+        );
+
+        Ok(FlashEntry {
+            offset: self.base + offset,
+            length,
+        })
+    }
+}
+
+/// Reads `out.len()` bytes at `offset`, which need not be aligned to the read size of the flash.
+macro_rules! read_unaligned {
+    ($flash:expr, $read_size:expr, $offset:expr, $out:expr $(, $await:tt)?) => {{
+        let flash = $flash;
+        let out: &mut [u8] = $out;
+        let read_size = read_size($read_size)?;
+
+        let mut offset: u32 = $offset;
+        let mut written = 0;
+        while written < out.len() {
+            let skip = (offset % read_size as u32) as usize;
+            let start = offset - skip as u32;
+            let wanted = (skip + out.len() - written).min(READ_CHUNK_LEN);
+            let len = wanted.next_multiple_of(read_size);
+
+            let mut chunk = [0; READ_CHUNK_LEN];
+            flash.read(start, &mut chunk[..len])$(.$await)?.map_err(flash_error)?;
+
+            let n = wanted - skip;
+            out[written..written + n].copy_from_slice(&chunk[skip..wanted]);
+            written += n;
+            offset += n as u32;
+        }
+
+        Ok::<(), FlashBundleError>(())
+    }};
+}
+
+/// Decodes the entry at `entry` into `sink`, reading it chunk by chunk.
+macro_rules! decode_entry {
+    ($flash:expr, $read_size:expr, $entry:expr, $sink:expr, $B:ty $(, $await:tt)?) => {{
+        let flash = $flash;
+        let FlashEntry { offset, length } = $entry;
+
+        // the image header, and the byte after it that `decode_header` wants to see
+        let mut header = [0; crate::EXTENDED_HEADER_LEN + 1];
+        let header_read = header.len().min(saturating_usize(length));
+        read_unaligned!(&mut *flash, $read_size, offset, &mut header[..header_read] $(, $await)?)?;
+        let (info, rest) = Q565DecodeContext::decode_header(&header[..header_read])?;
+        let header_len = (header_read - rest.len()) as u32;
+
+        let mut decoder = EntryDecoder::new(&info)?;
+        let mut sink = $sink;
+        let mut position = header_len;
+        while position < length && !decoder.is_finished() {
+            let mut chunk = [0; READ_CHUNK_LEN];
+            let n = READ_CHUNK_LEN.min(saturating_usize(length - position));
+            read_unaligned!(&mut *flash, $read_size, offset + position, &mut chunk[..n] $(, $await)?)?;
+            decoder.feed::<$B>(&chunk[..n], &mut sink)?;
+            position += n as u32;
+        }
+        decoder.finish(&mut sink)?;
+
+        Ok(info)
+    }};
+}
+
+impl<F: ReadNorFlash> FlashBundle<F> {
+    /// Reads the header of the bundle starting at flash offset `base`.
+    pub fn new(mut flash: F, base: u32) -> Result<Self, FlashBundleError> {
+        let available = (flash.capacity() as u32).saturating_sub(base);
+        let mut header = [0; EXTENDED_HEADER_LEN];
+        let header_read = header.len().min(saturating_usize(available));
+        read_unaligned!(&mut flash, F::READ_SIZE, base, &mut header[..header_read])?;
+
+        let layout = Layout::parse(base, available, &header[..header_read])?;
+        Ok(Self { flash, layout })
+    }
+
+    /// Number of entries in the bundle.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.layout.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.layout.len == 0
+    }
+
+    /// Returns the flash.
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    /// Returns the location of the entry at `index`.
+    pub fn entry(&mut self, index: usize) -> Result<FlashEntry, FlashBundleError> {
+        let mut record = [0; RECORD_LEN];
+        read_unaligned!(
+            &mut self.flash,
+            F::READ_SIZE,
+            self.layout.record_offset(index)?,
+            &mut record
+        )?;
+        Ok(self.layout.entry(record)?)
+    }
+
+    /// Reads `out.len()` bytes of the entry data, starting `offset` bytes into the entry.
+    pub fn read(
+        &mut self,
+        entry: FlashEntry,
+        offset: u32,
+        out: &mut [u8],
+    ) -> Result<(), FlashBundleError> {
+        ensure!(
+            u64::from(offset) + out.len() as u64 <= u64::from(entry.length),
+            BundleError::UnexpectedEof
+        );
+        read_unaligned!(&mut self.flash, F::READ_SIZE, entry.offset + offset, out)
+    }
+
+    /// Decodes the entry at `index` into `sink`, returning its header.
+    ///
+    /// The pixels are pushed in chunks of up to 128 pixels. Only the default color array profile
+    /// and raw images are supported.
+    pub fn decode_entry<B: Endianness>(
+        &mut self,
+        index: usize,
+        sink: impl PixelSink<u16>,
+    ) -> Result<HeaderInfo, FlashBundleError> {
+        let entry = self.entry(index)?;
+        decode_entry!(&mut self.flash, F::READ_SIZE, entry, sink, B)
+    }
+}
+
+impl<F: AsyncReadNorFlash> AsyncFlashBundle<F> {
+    /// Reads the header of the bundle starting at flash offset `base`.
+    pub async fn new(mut flash: F, base: u32) -> Result<Self, FlashBundleError> {
+        let available = (flash.capacity() as u32).saturating_sub(base);
+        let mut header = [0; EXTENDED_HEADER_LEN];
+        let header_read = header.len().min(saturating_usize(available));
+        read_unaligned!(
+            &mut flash,
+            F::READ_SIZE,
+            base,
+            &mut header[..header_read],
+            await
+        )?;
+
+        let layout = Layout::parse(base, available, &header[..header_read])?;
+        Ok(Self { flash, layout })
+    }
+
+    /// Number of entries in the bundle.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.layout.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.layout.len == 0
+    }
+
+    /// Returns the flash.
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    /// Returns the location of the entry at `index`.
+    pub async fn entry(&mut self, index: usize) -> Result<FlashEntry, FlashBundleError> {
+        let mut record = [0; RECORD_LEN];
+        read_unaligned!(
+            &mut self.flash,
+            F::READ_SIZE,
+            self.layout.record_offset(index)?,
+            &mut record,
+            await
+        )?;
+        Ok(self.layout.entry(record)?)
+    }
+
+    /// Reads `out.len()` bytes of the entry data, starting `offset` bytes into the entry.
+    pub async fn read(
+        &mut self,
+        entry: FlashEntry,
+        offset: u32,
+        out: &mut [u8],
+    ) -> Result<(), FlashBundleError> {
+        ensure!(
+            u64::from(offset) + out.len() as u64 <= u64::from(entry.length),
+            BundleError::UnexpectedEof
+        );
+        read_unaligned!(
+            &mut self.flash,
+            F::READ_SIZE,
+            entry.offset + offset,
+            out,
+            await
+        )
+    }
+
+    /// Decodes the entry at `index` into `sink`, returning its header, see
+    /// [`FlashBundle::decode_entry`].
+    pub async fn decode_entry<B: Endianness>(
+        &mut self,
+        index: usize,
+        sink: impl PixelSink<u16>,
+    ) -> Result<HeaderInfo, FlashBundleError> {
+        let entry = self.entry(index).await?;
+        decode_entry!(&mut self.flash, F::READ_SIZE, entry, sink, B, await)
+    }
+}
+
+/// Checks the read size of the flash.
+fn read_size(read_size: usize) -> Result<usize, FlashBundleError> {
+    ensure!(
+        read_size > 0 && READ_CHUNK_LEN.is_multiple_of(read_size),
+        FlashBundleError::UnsupportedReadSize
+    );
+    Ok(read_size)
+}
+
+/// Decodes the image data of an entry as it is read, chunk by chunk.
+struct EntryDecoder {
+    context: Q565StreamingDecodeContext,
+    raw: bool,
+    /// First byte of a raw pixel split across chunks.
+    raw_low: Option<u8>,
+    /// Pixels left until the image is complete.
+    remaining: usize,
+    pixels: [u16; 2 * Self::MAX_PIXELS_PER_BYTE],
+    filled: usize,
+}
+
+impl EntryDecoder {
+    /// Most pixels a single byte of image data decodes to: a run of 62 pixels.
+    const MAX_PIXELS_PER_BYTE: usize = 64;
+
+    fn new(header: &HeaderInfo) -> Result<Self, DecodeError> {
+        ensure!(
+            header.raw || header.color_array_size == ColorArraySize::Entries64,
+            DecodeError::UnsupportedFlags
+        );
+
+        Ok(Self {
+            context: Q565StreamingDecodeContext::new(),
+            raw: header.raw,
+            raw_low: None,
+            remaining: header
+                .pixel_count()
+                .ok_or(DecodeError::DimensionsTooLarge)?,
+            pixels: [0; 2 * Self::MAX_PIXELS_PER_BYTE],
+            filled: 0,
+        })
+    }
+
+    /// Returns whether the end marker was decoded. Raw images have none, they end with the entry.
+    fn is_finished(&self) -> bool {
+        !self.raw && self.context.is_finished()
+    }
+
+    fn feed<B: Endianness>(
+        &mut self,
+        data: &[u8],
+        sink: &mut impl PixelSink<u16>,
+    ) -> Result<(), DecodeError> {
+        for &byte in data {
+            if self.is_finished() {
+                // bytes after the end marker aren't part of the image
+                break;
+            }
+
+            if self.filled > Self::MAX_PIXELS_PER_BYTE {
+                self.flush(sink);
+            }
+
+            let decoded = if self.raw {
+                ensure!(self.remaining > 0, DecodeError::TooManyPixels);
+                match self.raw_low.take() {
+                    None => {
+                        self.raw_low = Some(byte);
+                        0
+                    }
+                    Some(low) => {
+                        self.pixels[self.filled] =
+                            Rgb565::to_output::<B>(u16::from_le_bytes([low, byte]));
+                        1
+                    }
+                }
+            } else {
+                // SAFETY: a single byte decodes to at most 62 pixels, and there's room for 64
+                unsafe {
+                    self.context.streaming_decode_to_slice_unchecked::<B>(
+                        &[byte],
+                        &mut self.pixels[self.filled..],
+                    )
+                }
+            };
+
+            ensure!(decoded <= self.remaining, DecodeError::TooManyPixels);
+            self.remaining -= decoded;
+            self.filled += decoded;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self, sink: &mut impl PixelSink<u16>) -> Result<(), DecodeError> {
+        self.flush(sink);
+        ensure!(self.raw || self.is_finished(), DecodeError::UnexpectedEof);
+        ensure!(self.remaining == 0, DecodeError::MissingData);
+        Ok(())
+    }
+
+    fn flush(&mut self, sink: &mut impl PixelSink<u16>) {
+        if self.filled > 0 {
+            sink.push_chunk(&self.pixels[..self.filled]);
+            self.filled = 0;
+        }
+    }
+}
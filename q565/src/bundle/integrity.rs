@@ -0,0 +1,234 @@
+use super::{
+    Bundle, BundleError, CRCS_FLAG, CRC_LEN, DIGEST_FLAG, DIGEST_LEN, EXTENDED_HEADER_LEN,
+    HASHES_FLAG, HASH_LEN, RECORD_LEN,
+};
+
+impl<'a> Bundle<'a> {
+    /// Returns whether the index holds the CRC-32 of every entry.
+    #[inline]
+    pub fn has_crcs(&self) -> bool {
+        self.flags & CRCS_FLAG != 0
+    }
+
+    /// Returns whether the bundle holds the SHA-256 of its contents.
+    #[inline]
+    pub fn has_digest(&self) -> bool {
+        self.flags & DIGEST_FLAG != 0
+    }
+
+    /// Returns the CRC-32 of the entry at `index`, as stored in the index.
+    ///
+    /// `None` if the bundle has no CRCs, see [`has_crcs`](Self::has_crcs).
+    pub fn crc(&self, index: usize) -> Option<u32> {
+        if index >= self.len || !self.has_crcs() {
+            return None;
+        }
+
+        let mut start = self.table_start() + index * self.record_len() + RECORD_LEN;
+        if self.flags & HASHES_FLAG != 0 {
+            start += HASH_LEN;
+        }
+        let crc = &self.data[start..start + CRC_LEN];
+        Some(u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]))
+    }
+
+    /// Returns the SHA-256 of the bundle, as stored in the header.
+    ///
+    /// `None` if the bundle has no digest, see [`has_digest`](Self::has_digest).
+    pub fn digest(&self) -> Option<[u8; DIGEST_LEN]> {
+        if !self.has_digest() {
+            return None;
+        }
+
+        let mut digest = [0; DIGEST_LEN];
+        digest.copy_from_slice(&self.data[EXTENDED_HEADER_LEN..EXTENDED_HEADER_LEN + DIGEST_LEN]);
+        Some(digest)
+    }
+
+    /// Returns the data of the entry at `index`, after checking it against its CRC-32.
+    ///
+    /// Entries of bundles without CRCs are returned unchecked.
+    pub fn get_verified(&self, index: usize) -> Result<&'a [u8], BundleError> {
+        let data = self.get(index).ok_or(BundleError::NoSuchEntry)?;
+        if let Some(crc) = self.crc(index) {
+            ensure!(crc32(data) == crc, BundleError::CorruptEntry { index });
+        }
+        Ok(data)
+    }
+
+    /// Checks the whole bundle against its SHA-256, and all entries against their CRC-32s.
+    ///
+    /// Only checks what the bundle holds, so a bundle without either always passes, see
+    /// [`has_digest`](Self::has_digest) and [`has_crcs`](Self::has_crcs).
+    pub fn verify(&self) -> Result<(), BundleError> {
+        if let Some(digest) = self.digest() {
+            // the bundle may be followed by e.g. the rest of a flash sector
+            let end = (0..self.len)
+                .map(|index| {
+                    let (offset, length) = self.record(index);
+                    offset + length
+                })
+                .fold(
+                    self.table_start() + self.len * self.record_len(),
+                    usize::max,
+                );
+            ensure!(
+                bundle_digest(&self.data[..end]) == digest,
+                BundleError::CorruptBundle
+            );
+        }
+        for index in 0..self.len {
+            self.get_verified(index)?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the CRC-32 (IEEE 802.3, as used by zlib and PNG) of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !data.iter().fold(!0, |crc, &byte| {
+        TABLE[usize::from(crc as u8 ^ byte)] ^ (crc >> 8)
+    })
+}
+
+/// Computes the SHA-256 of `data`.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut sha = Sha256::new();
+    sha.update(data);
+    sha.finish()
+}
+
+/// Computes the SHA-256 of a bundle with a digest, skipping the digest itself.
+pub(super) fn bundle_digest(data: &[u8]) -> [u8; DIGEST_LEN] {
+    let mut sha = Sha256::new();
+    sha.update(&data[..EXTENDED_HEADER_LEN]);
+    sha.update(&data[EXTENDED_HEADER_LEN + DIGEST_LEN..]);
+    sha.finish()
+}
+
+/// Minimal SHA-256, to check bundles without pulling in a dependency.
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Sha256 {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+
+    const fn new() -> Self {
+        Self {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let n = data.len().min(64 - self.block_len);
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&data[..n]);
+            self.block_len += n;
+            data = &data[n..];
+
+            if self.block_len == 64 {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bit_len = self.total_len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_be_bytes());
+
+        let mut digest = [0; 32];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (word, bytes) in w.iter_mut().zip(self.block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (k, w) in Self::K.into_iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(k)
+                .wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
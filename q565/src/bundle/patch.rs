@@ -0,0 +1,219 @@
+use super::{content_hash, Bundle, BundleError, BundleWriter, SUPPORTED_FLAGS};
+use crate::saturating_usize;
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// Magic bytes of a serialized [`Patch`].
+pub const PATCH_MAGIC: &[u8; 4] = b"q5bp";
+
+const PATCH_HEADER_LEN: usize = 7;
+
+const KIND_COPY: u8 = 0;
+const KIND_REPEAT: u8 = 1;
+const KIND_INSERT: u8 = 2;
+
+/// Changes turning one bundle into another, see [`diff`].
+///
+/// # Layout
+///
+/// - 4-byte magic: `q5bp`
+/// - u8 flags of the new bundle, see the [extended layout](super#extended-layout)
+/// - u16le entry count of the new bundle
+/// - one [`PatchEntry`] per entry of the new bundle, starting with a u8 kind:
+///   - `0`: [`Copy`](PatchEntry::Copy), u32le offset, u32le length, u64le hash
+///   - `1`: [`Repeat`](PatchEntry::Repeat), u16le index
+///   - `2`: [`Insert`](PatchEntry::Insert), u32le length, followed by the entry data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch<'a> {
+    flags: u8,
+    entries: Vec<PatchEntry<'a>>,
+}
+
+/// Where an entry of the new bundle comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchEntry<'a> {
+    /// The entry is unchanged, and taken from the old bundle.
+    Copy {
+        /// Offset of the entry data in the old bundle.
+        offset: u32,
+        length: u32,
+        /// [Content hash](content_hash) of the entry, checked when applying the patch.
+        hash: u64,
+    },
+    /// The entry is identical to an earlier entry of the new bundle.
+    Repeat(u16),
+    /// The entry is new, and its data is sent along.
+    Insert(&'a [u8]),
+}
+
+impl<'a> Patch<'a> {
+    /// The entries of the new bundle, in order.
+    pub fn entries(&self) -> &[PatchEntry<'a>] {
+        &self.entries
+    }
+
+    /// Parses a serialized patch.
+    pub fn parse(data: &'a [u8]) -> Result<Self, BundleError> {
+        ensure!(data.len() >= PATCH_HEADER_LEN, BundleError::UnexpectedEof);
+        ensure!(&data[..4] == PATCH_MAGIC, BundleError::InvalidPatch);
+
+        let flags = data[4];
+        ensure!(flags & !SUPPORTED_FLAGS == 0, BundleError::UnsupportedFlags);
+        let count = usize::from(u16::from_le_bytes([data[5], data[6]]));
+
+        let mut rest = &data[PATCH_HEADER_LEN..];
+        let mut entries = Vec::with_capacity(count);
+        for index in 0..count {
+            let (&kind, tail) = rest.split_first().ok_or(BundleError::UnexpectedEof)?;
+            rest = tail;
+            let entry = match kind {
+                KIND_COPY => {
+                    let offset = u32::from_le_bytes(take(&mut rest)?);
+                    let length = u32::from_le_bytes(take(&mut rest)?);
+                    let hash = u64::from_le_bytes(take(&mut rest)?);
+                    PatchEntry::Copy {
+                        offset,
+                        length,
+                        hash,
+                    }
+                }
+                KIND_REPEAT => {
+                    let earlier = u16::from_le_bytes(take(&mut rest)?);
+                    ensure!(usize::from(earlier) < index, BundleError::InvalidPatch);
+                    PatchEntry::Repeat(earlier)
+                }
+                KIND_INSERT => {
+                    let length = saturating_usize(u32::from_le_bytes(take(&mut rest)?));
+                    ensure!(rest.len() >= length, BundleError::UnexpectedEof);
+                    let (data, tail) = rest.split_at(length);
+                    rest = tail;
+                    PatchEntry::Insert(data)
+                }
+                _ => return Err(BundleError::InvalidPatch),
+            };
+            entries.push(entry);
+        }
+
+        Ok(Self { flags, entries })
+    }
+
+    /// Serializes the patch, see the [layout](Self#layout).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend_from_slice(PATCH_MAGIC);
+        output.push(self.flags);
+        output.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+
+        for entry in &self.entries {
+            match *entry {
+                PatchEntry::Copy {
+                    offset,
+                    length,
+                    hash,
+                } => {
+                    output.push(KIND_COPY);
+                    output.extend_from_slice(&offset.to_le_bytes());
+                    output.extend_from_slice(&length.to_le_bytes());
+                    output.extend_from_slice(&hash.to_le_bytes());
+                }
+                PatchEntry::Repeat(earlier) => {
+                    output.push(KIND_REPEAT);
+                    output.extend_from_slice(&earlier.to_le_bytes());
+                }
+                PatchEntry::Insert(data) => {
+                    output.push(KIND_INSERT);
+                    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                    output.extend_from_slice(data);
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// Computes the patch turning `old` into `new`.
+///
+/// Entries of `new` that are byte-identical to an entry of `old` are referenced by their offset in
+/// `old`, so only the changed entries are part of the patch.
+pub fn diff<'a>(old: &Bundle<'_>, new: &Bundle<'a>) -> Patch<'a> {
+    let mut old_by_hash: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    for index in 0..old.len() {
+        let hash = old
+            .hash(index)
+            .unwrap_or_else(|| content_hash(old.get(index).unwrap_or_default()));
+        old_by_hash.entry(hash).or_default().push(index);
+    }
+
+    let mut entries: Vec<PatchEntry<'a>> = Vec::with_capacity(new.len());
+    for (index, data) in new.iter().enumerate() {
+        let hash = new.hash(index).unwrap_or_else(|| content_hash(data));
+
+        let copied = old_by_hash.get(&hash).and_then(|candidates| {
+            candidates
+                .iter()
+                .find(|&&candidate| old.get(candidate) == Some(data))
+        });
+        let entry = if let Some(&copied) = copied {
+            let (offset, length) = old.record(copied);
+            PatchEntry::Copy {
+                offset: offset as u32,
+                length: length as u32,
+                hash,
+            }
+        } else if let Some(earlier) = new.iter().take(index).position(|earlier| earlier == data) {
+            PatchEntry::Repeat(earlier as u16)
+        } else {
+            PatchEntry::Insert(data)
+        };
+        entries.push(entry);
+    }
+
+    Patch {
+        flags: new.flags,
+        entries,
+    }
+}
+
+/// Applies a patch computed by [`diff`] to the old bundle, returning the new bundle.
+///
+/// The result holds the same entries as the new bundle, with the same layout, but isn't
+/// necessarily byte-identical to it if the new bundle wasn't written by a [`BundleWriter`].
+/// Returns [`BundleError::PatchMismatch`] if an entry the patch takes from `old` isn't there.
+pub fn apply_patch(old: &Bundle<'_>, patch: &Patch<'_>) -> Result<Vec<u8>, BundleError> {
+    let mut entries: Vec<&[u8]> = Vec::with_capacity(patch.entries.len());
+    for entry in &patch.entries {
+        let data = match *entry {
+            PatchEntry::Copy {
+                offset,
+                length,
+                hash,
+            } => {
+                let (offset, length) = (saturating_usize(offset), saturating_usize(length));
+                let data = offset
+                    .checked_add(length)
+                    .and_then(|end| old.data.get(offset..end))
+                    .ok_or(BundleError::PatchMismatch)?;
+                ensure!(content_hash(data) == hash, BundleError::PatchMismatch);
+                data
+            }
+            PatchEntry::Repeat(earlier) => *entries
+                .get(usize::from(earlier))
+                .ok_or(BundleError::InvalidPatch)?,
+            PatchEntry::Insert(data) => data,
+        };
+        entries.push(data);
+    }
+
+    BundleWriter { flags: patch.flags }.write(&entries)
+}
+
+/// Takes the next `N` bytes from `data`.
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], BundleError> {
+    ensure!(data.len() >= N, BundleError::UnexpectedEof);
+    let (bytes, rest) = data.split_at(N);
+    *data = rest;
+
+    let mut out = [0; N];
+    out.copy_from_slice(bytes);
+    Ok(out)
+}
@@ -0,0 +1,153 @@
+//! Byte orders of the decoded pixels, and of the pixels passed to some encoders.
+//!
+//! The decoders are generic over an [`Endianness`], which determines the order of the bytes of each
+//! pixel in the output buffer:
+//!
+//! - For [`Rgb565`](crate::Rgb565) outputs, each `u16` is stored so that its bytes *in memory* are
+//!   in that order. With [`NativeEndian`], each `u16` is the pixel value itself; with the other byte
+//!   order, it's byte-swapped. Most SPI displays expect [`BigEndian`] pixels, so that's what to use
+//!   to send the buffer to a display as bytes, e.g. via DMA.
+//! - For [`Rgb888`](crate::Rgb888) outputs, [`BigEndian`] gives `[r, g, b]`, and [`LittleEndian`]
+//!   gives `[b, g, r]`.
+//!
+//! The types mirror the ones of the [`byteorder`](https://docs.rs/byteorder) crate, which this
+//! crate re-exported in earlier versions, so code naming `q565::byteorder::LittleEndian` keeps
+//! working without depending on it. With the `byteorder` feature, the types of the `byteorder`
+//! crate implement [`Endianness`] as well.
+
+/// Byte order of the decoded pixels, see the [module docs](self).
+///
+/// Sealed: it's implemented by [`LittleEndian`] and [`BigEndian`] (and, with the `byteorder`
+/// feature, by their counterparts in the `byteorder` crate), and can't be implemented outside this
+/// crate. Decode outputs only need it as a bound, to pass it on or to call its methods.
+pub trait Endianness: sealed::Sealed {
+    /// Reads a u16 from the first 2 bytes of `buf`.
+    fn read_u16(buf: &[u8]) -> u16;
+    /// Writes a u16 into the first 2 bytes of `buf`.
+    fn write_u16(buf: &mut [u8], n: u16);
+    /// Reads a u24 from the first 3 bytes of `buf`.
+    fn read_u24(buf: &[u8]) -> u32;
+    /// Writes the lower 24 bits of `n` into the first 3 bytes of `buf`.
+    fn write_u24(buf: &mut [u8], n: u32);
+}
+
+/// The name of [`Endianness`] in earlier versions, kept so existing bounds keep compiling.
+pub use self::Endianness as ByteOrder;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Least significant byte first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LittleEndian {}
+
+/// Most significant byte first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BigEndian {}
+
+/// The byte order of the target.
+#[cfg(target_endian = "little")]
+pub type NativeEndian = LittleEndian;
+/// The byte order of the target.
+#[cfg(target_endian = "big")]
+pub type NativeEndian = BigEndian;
+
+/// The byte order of the [`Decoder`](crate::decode::Decoder) and
+/// [`Encoder`](crate::encode::Encoder) builders unless set otherwise: [`BigEndian`] with the
+/// `default-be` feature, [`NativeEndian`] without.
+///
+/// Projects whose displays take big-endian pixels can enable the feature once, instead of setting
+/// the byte order at every call site. As it changes the defaults for every crate in the build, it's
+/// meant to be enabled by the application, not by libraries.
+#[cfg(feature = "default-be")]
+pub type DefaultOrder = BigEndian;
+/// The byte order of the [`Decoder`](crate::decode::Decoder) and
+/// [`Encoder`](crate::encode::Encoder) builders unless set otherwise: [`BigEndian`] with the
+/// `default-be` feature, [`NativeEndian`] without.
+///
+/// Projects whose displays take big-endian pixels can enable the feature once, instead of setting
+/// the byte order at every call site. As it changes the defaults for every crate in the build, it's
+/// meant to be enabled by the application, not by libraries.
+#[cfg(not(feature = "default-be"))]
+pub type DefaultOrder = NativeEndian;
+
+impl sealed::Sealed for LittleEndian {}
+impl Endianness for LittleEndian {
+    #[inline]
+    fn read_u16(buf: &[u8]) -> u16 {
+        u16::from_le_bytes([buf[0], buf[1]])
+    }
+
+    #[inline]
+    fn write_u16(buf: &mut [u8], n: u16) {
+        buf[..2].copy_from_slice(&n.to_le_bytes());
+    }
+
+    #[inline]
+    fn read_u24(buf: &[u8]) -> u32 {
+        u32::from_le_bytes([buf[0], buf[1], buf[2], 0])
+    }
+
+    #[inline]
+    fn write_u24(buf: &mut [u8], n: u32) {
+        buf[..3].copy_from_slice(&n.to_le_bytes()[..3]);
+    }
+}
+
+impl sealed::Sealed for BigEndian {}
+impl Endianness for BigEndian {
+    #[inline]
+    fn read_u16(buf: &[u8]) -> u16 {
+        u16::from_be_bytes([buf[0], buf[1]])
+    }
+
+    #[inline]
+    fn write_u16(buf: &mut [u8], n: u16) {
+        buf[..2].copy_from_slice(&n.to_be_bytes());
+    }
+
+    #[inline]
+    fn read_u24(buf: &[u8]) -> u32 {
+        u32::from_be_bytes([0, buf[0], buf[1], buf[2]])
+    }
+
+    #[inline]
+    fn write_u24(buf: &mut [u8], n: u32) {
+        buf[..3].copy_from_slice(&n.to_be_bytes()[1..]);
+    }
+}
+
+/// Forwards to the crate-owned type with the same name.
+#[cfg(feature = "byteorder")]
+macro_rules! impl_external {
+    ($name:ident) => {
+        impl sealed::Sealed for ::byteorder::$name {}
+        impl Endianness for ::byteorder::$name {
+            #[inline]
+            fn read_u16(buf: &[u8]) -> u16 {
+                $name::read_u16(buf)
+            }
+
+            #[inline]
+            fn write_u16(buf: &mut [u8], n: u16) {
+                $name::write_u16(buf, n)
+            }
+
+            #[inline]
+            fn read_u24(buf: &[u8]) -> u32 {
+                $name::read_u24(buf)
+            }
+
+            #[inline]
+            fn write_u24(buf: &mut [u8], n: u32) {
+                $name::write_u24(buf, n)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "byteorder")]
+impl_external!(LittleEndian);
+#[cfg(feature = "byteorder")]
+impl_external!(BigEndian);
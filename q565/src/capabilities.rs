@@ -0,0 +1,222 @@
+//! Capability negotiation between devices and hosts.
+//!
+//! A device advertises the [`FormatVersion`] and the [`Capabilities`] of its decoder, e.g. in
+//! response to a query over its transport, and the host picks a profile the device can decode:
+//!
+//! ```
+//! use q565::capabilities::{Advertisement, Capabilities};
+//!
+//! // device
+//! let advertisement = Advertisement::new(Capabilities::decoder(32));
+//! let bytes = advertisement.to_bytes();
+//!
+//! // host
+//! let advertisement = Advertisement::parse(&bytes).unwrap();
+//! let profile = advertisement.capabilities.best_profile().unwrap();
+//! assert_eq!(profile.entries(), 32);
+//! ```
+//!
+//! # Layout
+//!
+//! - u8 major version
+//! - u8 minor version
+//! - u16le capability bits
+
+use crate::{ColorArraySize, HeaderInfo};
+use core::ops::{BitAnd, BitOr};
+
+/// Version of the format a decoder implements.
+///
+/// Minor versions only add header extensions that older decoders reject cleanly, so a host
+/// supporting a major version can talk to any device with the same major version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FormatVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl FormatVersion {
+    /// The original format: the `q565` header, with a 64-entry color array.
+    pub const V1_0: Self = Self { major: 1, minor: 0 };
+    /// The extended `q56x` header: [color array profiles](crate#color-array-profiles) and [raw
+    /// images](crate#raw-images).
+    pub const V1_1: Self = Self { major: 1, minor: 1 };
+    /// The version implemented by this crate.
+    pub const CURRENT: Self = Self::V1_1;
+
+    /// Returns all capabilities a decoder of this version can have.
+    pub const fn capabilities(self) -> Capabilities {
+        match (self.major, self.minor) {
+            (1, 0) => Capabilities::ENTRIES_64,
+            (1, _) => Capabilities::ALL_CURRENT,
+            _ => Capabilities::NONE,
+        }
+    }
+}
+
+/// Set of format features a decoder supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Capabilities(u16);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    /// Images with the default 64-entry color array.
+    pub const ENTRIES_64: Self = Self(1 << 0);
+    /// Images with a 32-entry color array.
+    pub const ENTRIES_32: Self = Self(1 << 1);
+    /// Images with a 16-entry color array.
+    pub const ENTRIES_16: Self = Self(1 << 2);
+    /// Images with the [no-array profile](crate#no-color-array).
+    pub const NO_ARRAY: Self = Self(1 << 3);
+    /// [Raw images](crate#raw-images).
+    pub const RAW: Self = Self(1 << 4);
+
+    /// Planned header extension: an alpha channel. Not implemented by this crate yet.
+    pub const ALPHA: Self = Self(1 << 8);
+    /// Planned header extension: images split into independently decodable tiles. Not
+    /// implemented by this crate yet.
+    pub const TILES: Self = Self(1 << 9);
+    /// Planned header extension: u32 width and height. Not implemented by this crate yet.
+    pub const LARGE_DIMENSIONS: Self = Self(1 << 10);
+
+    /// Everything the decoders of this crate support.
+    pub const ALL_CURRENT: Self = Self(0b1_1111);
+
+    /// Returns the capabilities of a [`Q565DecodeContext`](crate::decode::Q565DecodeContext) with
+    /// the given number of color array entries: all profiles up to that size, and raw images.
+    pub const fn decoder(entries: usize) -> Self {
+        let mut bits = Self::NO_ARRAY.0 | Self::RAW.0;
+        if entries >= 16 {
+            bits |= Self::ENTRIES_16.0;
+        }
+        if entries >= 32 {
+            bits |= Self::ENTRIES_32.0;
+        }
+        if entries >= 64 {
+            bits |= Self::ENTRIES_64.0;
+        }
+        Self(bits)
+    }
+
+    /// Returns the capabilities of a [`MiniDecoder`](crate::decode::MiniDecoder).
+    pub const fn mini_decoder() -> Self {
+        Self(Self::NO_ARRAY.0 | Self::RAW.0)
+    }
+
+    /// Creates a set from its bits, keeping unknown bits, e.g. of a newer minor version.
+    pub const fn from_bits_retain(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Returns whether all capabilities in `other` are in this set as well.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the capabilities a decoder needs to decode an image with the given header.
+    pub const fn required_by(header: &HeaderInfo) -> Self {
+        if header.raw {
+            return Self::RAW;
+        }
+
+        match header.color_array_size {
+            ColorArraySize::NoArray => Self::NO_ARRAY,
+            ColorArraySize::Entries16 => Self::ENTRIES_16,
+            ColorArraySize::Entries32 => Self::ENTRIES_32,
+            ColorArraySize::Entries64 => Self::ENTRIES_64,
+        }
+    }
+
+    /// Returns whether a decoder with these capabilities can decode an image with the given
+    /// header.
+    pub const fn supports(self, header: &HeaderInfo) -> bool {
+        self.contains(Self::required_by(header))
+    }
+
+    /// Returns the supported color array profile that compresses best, i.e. the one with the
+    /// largest color array.
+    pub const fn best_profile(self) -> Option<ColorArraySize> {
+        if self.contains(Self::ENTRIES_64) {
+            Some(ColorArraySize::Entries64)
+        } else if self.contains(Self::ENTRIES_32) {
+            Some(ColorArraySize::Entries32)
+        } else if self.contains(Self::ENTRIES_16) {
+            Some(ColorArraySize::Entries16)
+        } else if self.contains(Self::NO_ARRAY) {
+            Some(ColorArraySize::NoArray)
+        } else {
+            None
+        }
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+/// Length of an encoded [`Advertisement`], in bytes.
+pub const ADVERTISEMENT_LEN: usize = 4;
+
+error_enum! {
+    pub enum AdvertisementError {
+        /// The advertisement is shorter than [`ADVERTISEMENT_LEN`].
+        UnexpectedEof = 1,
+        /// The advertisement is for a major version this crate doesn't know.
+        UnsupportedVersion { major: u8 } = 2,
+    }
+}
+
+/// The format version and capabilities a device advertises, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Advertisement {
+    pub version: FormatVersion,
+    pub capabilities: Capabilities,
+}
+
+impl Advertisement {
+    /// Creates an advertisement of the given capabilities, for the version implemented by this
+    /// crate.
+    pub const fn new(capabilities: Capabilities) -> Self {
+        Self {
+            version: FormatVersion::CURRENT,
+            capabilities,
+        }
+    }
+
+    pub const fn to_bytes(&self) -> [u8; ADVERTISEMENT_LEN] {
+        let [c1, c2] = self.capabilities.0.to_le_bytes();
+        [self.version.major, self.version.minor, c1, c2]
+    }
+
+    /// Parses an advertisement. Unknown capabilities of newer minor versions are kept.
+    pub fn parse(data: &[u8]) -> Result<Self, AdvertisementError> {
+        let &[major, minor, c1, c2, ..] = data else {
+            return Err(AdvertisementError::UnexpectedEof);
+        };
+        ensure!(
+            major == FormatVersion::CURRENT.major,
+            AdvertisementError::UnsupportedVersion { major }
+        );
+
+        Ok(Self {
+            version: FormatVersion { major, minor },
+            capabilities: Capabilities(u16::from_le_bytes([c1, c2])),
+        })
+    }
+}
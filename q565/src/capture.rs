@@ -0,0 +1,161 @@
+//! Screen capture on desktops, with the `capture` feature.
+//!
+//! A [`Capture`] grabs a monitor or a window with [`xcap`], optionally crops it to a region, and
+//! converts and encodes it with a [`Pipeline`]. Handy for turning a desktop prototype of a device
+//! UI into Q565 assets, or as the frame source of a streaming server.
+//!
+//! ```no_run
+//! use q565::{capture::Capture, Rect};
+//!
+//! let region = Rect { x: 0, y: 0, width: 320, height: 240 };
+//! let encoded = Capture::window("UI mockup").with_region(region).encode_to_vec()?;
+//! std::fs::write("mockup.q565", encoded)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::byteorder::BigEndian;
+use crate::{pipeline::Pipeline, Rect};
+use xcap::{image::RgbaImage, Monitor, Window, XCapError};
+
+error_enum! {
+    pub enum CaptureError {
+        /// Capturing the screen failed.
+        Capture { source: XCapError } = 1,
+        /// No monitor or window matches the capture source.
+        NotFound = 2,
+        /// The region doesn't lie within the captured image.
+        RegionOutOfBounds = 3,
+        /// The captured image is wider or higher than 65535 pixels.
+        TooLarge = 4,
+    }
+}
+
+impl From<XCapError> for CaptureError {
+    fn from(source: XCapError) -> Self {
+        Self::Capture { source }
+    }
+}
+
+/// What to capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureSource {
+    /// The primary monitor.
+    PrimaryMonitor,
+    /// The monitor at this index in [`Monitor::all`].
+    Monitor(usize),
+    /// The first window whose title contains this string.
+    Window(String),
+}
+
+/// Captures and encodes the screen, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Capture {
+    source: CaptureSource,
+    region: Option<Rect>,
+    dither: bool,
+}
+
+impl Capture {
+    pub fn new(source: CaptureSource) -> Self {
+        Self {
+            source,
+            region: None,
+            dither: false,
+        }
+    }
+
+    pub fn primary_monitor() -> Self {
+        Self::new(CaptureSource::PrimaryMonitor)
+    }
+
+    pub fn monitor(index: usize) -> Self {
+        Self::new(CaptureSource::Monitor(index))
+    }
+
+    pub fn window(title: impl Into<String>) -> Self {
+        Self::new(CaptureSource::Window(title.into()))
+    }
+
+    /// Only encodes this region of the captured monitor or window, relative to its top left
+    /// corner.
+    pub fn with_region(mut self, region: Rect) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Enables ordered dithering when converting to RGB565, see [`Pipeline::with_dithering`].
+    pub fn with_dithering(mut self) -> Self {
+        self.dither = true;
+        self
+    }
+
+    /// Grabs the monitor or window as is, without cropping or converting it.
+    pub fn capture_rgba(&self) -> Result<RgbaImage, CaptureError> {
+        match &self.source {
+            CaptureSource::PrimaryMonitor => Monitor::all()?
+                .into_iter()
+                .find(Monitor::is_primary)
+                .ok_or(CaptureError::NotFound)?
+                .capture_image(),
+            CaptureSource::Monitor(index) => Monitor::all()?
+                .into_iter()
+                .nth(*index)
+                .ok_or(CaptureError::NotFound)?
+                .capture_image(),
+            CaptureSource::Window(title) => Window::all()?
+                .into_iter()
+                .find(|window| !window.is_minimized() && window.title().contains(title.as_str()))
+                .ok_or(CaptureError::NotFound)?
+                .capture_image(),
+        }
+        .map_err(CaptureError::from)
+    }
+
+    /// Grabs the monitor or window and encodes it, including the header.
+    pub fn encode_to_vec(&self) -> Result<Vec<u8>, CaptureError> {
+        self.encode_image(&self.capture_rgba()?)
+    }
+
+    /// Crops, converts, and encodes an image as captured by [`capture_rgba`](Self::capture_rgba).
+    ///
+    /// The alpha channel is ignored.
+    pub fn encode_image(&self, image: &RgbaImage) -> Result<Vec<u8>, CaptureError> {
+        let width = u16::try_from(image.width()).map_err(|_| CaptureError::TooLarge)?;
+        let height = u16::try_from(image.height()).map_err(|_| CaptureError::TooLarge)?;
+        let region = self.region.unwrap_or(Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        });
+        if !region.fits_within(width, height) || region.area() == 0 {
+            return Err(CaptureError::RegionOutOfBounds);
+        }
+
+        let mut pixels = Vec::with_capacity(region.area());
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let [r, g, b, _] = image.get_pixel(u32::from(x), u32::from(y)).0;
+                pixels.push([r, g, b]);
+            }
+        }
+
+        let mut pipeline = Pipeline::new(region.width, region.height);
+        if self.dither {
+            pipeline = pipeline.with_dithering();
+        }
+
+        // header, up to 3 bytes per pixel plus a pending run per row, and the end marker
+        let mut output = vec![
+            0;
+            crate::EXTENDED_HEADER_LEN
+                + usize::from(region.height) * pipeline.max_row_len()
+                + 2
+        ];
+        let len = pipeline
+            .encode_frame::<BigEndian>(&pixels, &mut output)
+            .expect("the frame size matches, and the output holds the largest possible frame");
+        output.truncate(len);
+        Ok(output)
+    }
+}
@@ -0,0 +1,198 @@
+//! Conformance checks for encoder output, e.g. as an oracle when testing a third-party or hardware
+//! encoder.
+//!
+//! Unlike the decoders, which stop at the first error and don't care about the rules that only
+//! apply to encoders, [`check_stream`] keeps going and reports every violation it finds.
+
+use crate::{
+    decode::{DecodeError, Q565DecodeContext},
+    stream::{Op, OpReader},
+    ColorArraySize, HeaderInfo,
+};
+use alloc::vec::Vec;
+
+/// A rule of the format that a stream breaks. Offsets are in bytes, from the start of the stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The stream does not start with the magic bytes `q565` or `q56x`.
+    InvalidMagic,
+    /// The extended header sets reserved flags, or combines the raw flag with a color array size.
+    ReservedFlags { flags: u8 },
+    /// The width or height is zero.
+    EmptyImage,
+    /// The header describes a different number of pixels than the reference has.
+    SizeMismatch { header: usize, reference: usize },
+    /// An op references a color array entry beyond the array size of the image's
+    /// [profile](crate#color-array-profiles).
+    IndexOutOfRange { offset: usize, index: u8 },
+    /// A [`Q565_OP_INDEX`](crate::consts::Q565_OP_INDEX) repeats the index of the op right before
+    /// it, instead of using a run.
+    RepeatedIndex { offset: usize, index: u8 },
+    /// An op produces a pixel that differs from the reference. Only the first differing pixel of
+    /// each op is reported.
+    PixelMismatch {
+        offset: usize,
+        pixel: usize,
+        expected: u16,
+        actual: u16,
+    },
+    /// An op produces pixels beyond the end of the image.
+    TooManyPixels { offset: usize },
+    /// The end marker comes before all pixels were produced.
+    MissingPixels {
+        offset: usize,
+        produced: usize,
+        expected: usize,
+    },
+    /// The stream ends before the end marker (or, for a [raw image](crate#raw-images), before the
+    /// last pixel), possibly in the middle of an op.
+    UnexpectedEof { offset: usize },
+}
+
+/// Checks that `data` decodes to exactly `reference_pixels`, and follows all rules of the format,
+/// including the ones the decoders don't enforce.
+///
+/// Returns all violations found, in stream order. An empty list means the stream is conformant.
+pub fn check_stream(data: &[u8], reference_pixels: &[u16]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    let (header, ops) = match Q565DecodeContext::decode_header(data) {
+        Ok(header) => header,
+        Err(error) => {
+            violations.push(match error {
+                DecodeError::InvalidMagic => Violation::InvalidMagic,
+                DecodeError::UnsupportedFlags => Violation::ReservedFlags { flags: data[4] },
+                _ => Violation::UnexpectedEof { offset: data.len() },
+            });
+            return violations;
+        }
+    };
+    let header_len = data.len() - ops.len();
+
+    if header.width == 0 || header.height == 0 {
+        violations.push(Violation::EmptyImage);
+    }
+    // an image that doesn't fit into memory can't match the reference
+    let pixel_count = header.pixel_count().unwrap_or(usize::MAX);
+    if pixel_count != reference_pixels.len() {
+        violations.push(Violation::SizeMismatch {
+            header: pixel_count,
+            reference: reference_pixels.len(),
+        });
+    }
+
+    let mut checker = Checker {
+        header_len,
+        pixel_count,
+        reference_pixels,
+        pixels_produced: 0,
+        violations,
+    };
+    if header.raw {
+        checker.check_raw(ops);
+    } else {
+        match header.color_array_size {
+            ColorArraySize::NoArray | ColorArraySize::Entries16 => {
+                checker.check_ops::<16>(&header, ops)
+            }
+            ColorArraySize::Entries32 => checker.check_ops::<32>(&header, ops),
+            ColorArraySize::Entries64 => checker.check_ops::<64>(&header, ops),
+        }
+    }
+
+    checker.violations
+}
+
+struct Checker<'a> {
+    header_len: usize,
+    pixel_count: usize,
+    reference_pixels: &'a [u16],
+    pixels_produced: usize,
+    violations: Vec<Violation>,
+}
+
+impl Checker<'_> {
+    /// Records `count` pixels of `color` produced by the op at `offset`.
+    fn produce(&mut self, offset: usize, color: u16, count: usize) {
+        let start = self.pixels_produced;
+        self.pixels_produced += count;
+
+        if self.pixels_produced > self.pixel_count {
+            self.violations.push(Violation::TooManyPixels { offset });
+        }
+
+        let end = self.pixels_produced.min(self.reference_pixels.len());
+        if let Some(pixel) = (start..end).find(|&i| self.reference_pixels[i] != color) {
+            self.violations.push(Violation::PixelMismatch {
+                offset,
+                pixel,
+                expected: self.reference_pixels[pixel],
+                actual: color,
+            });
+        }
+    }
+
+    fn check_raw(&mut self, data: &[u8]) {
+        for (i, pixel) in data.chunks_exact(2).take(self.pixel_count).enumerate() {
+            let color = u16::from_le_bytes([pixel[0], pixel[1]]);
+            self.produce(self.header_len + 2 * i, color, 1);
+        }
+
+        if self.pixels_produced < self.pixel_count {
+            self.violations.push(Violation::UnexpectedEof {
+                offset: self.header_len + data.len(),
+            });
+        }
+    }
+
+    fn check_ops<const N: usize>(&mut self, header: &HeaderInfo, data: &[u8]) {
+        let entries = header.color_array_size.entries();
+        let mut ctx = Q565DecodeContext::<N>::new_sized();
+        let mut previous_op = None;
+
+        let mut ops = OpReader::new(data);
+        for (offset, op) in ops.by_ref() {
+            let offset = self.header_len + offset;
+
+            match op {
+                Op::Index(index) => {
+                    if usize::from(index) >= entries {
+                        self.violations
+                            .push(Violation::IndexOutOfRange { offset, index });
+                    }
+                    if previous_op == Some(Op::Index(index)) {
+                        self.violations
+                            .push(Violation::RepeatedIndex { offset, index });
+                    }
+                }
+                Op::DiffIndexed(_, second_byte) => {
+                    let index = second_byte & 0b0011_1111;
+                    if usize::from(index) >= entries {
+                        self.violations
+                            .push(Violation::IndexOutOfRange { offset, index });
+                    }
+                }
+                Op::End => {
+                    if self.pixels_produced < self.pixel_count {
+                        self.violations.push(Violation::MissingPixels {
+                            offset,
+                            produced: self.pixels_produced,
+                            expected: self.pixel_count,
+                        });
+                    }
+                    return;
+                }
+                _ => {}
+            }
+
+            let (color, count) = ctx.apply_op(op);
+            self.produce(offset, color, count);
+            previous_op = Some(op);
+        }
+
+        // the loop only ends without returning if the end marker is missing
+        self.violations.push(Violation::UnexpectedEof {
+            offset: self.header_len + ops.offset(),
+        });
+    }
+}
@@ -0,0 +1,226 @@
+//! Container of length-prefixed chunks, e.g. for assets that are encrypted at rest and need to be
+//! decrypted and decoded incrementally.
+//!
+//! Each chunk is passed through a user-supplied [`ChunkTransform`] on both sides, e.g. AES-CTR
+//! with a counter derived from the chunk index, so chunks can be decrypted as they arrive without
+//! buffering the whole asset.
+//!
+//! # Layout
+//!
+//! - 4-byte magic: `q5ck`
+//! - per chunk:
+//!   - u32le payload length
+//!   - payload: the next bytes of the Q565 image (including its header), transformed
+//! - a chunk of length 0, ending the container
+
+use crate::decode::DecodeError;
+use crate::saturating_usize;
+
+#[cfg(feature = "alloc")]
+use crate::byteorder::Endianness;
+#[cfg(feature = "alloc")]
+use crate::decode::{InfallibleDecodeOutput, Q565DecodeContext};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+pub const CONTAINER_MAGIC: &[u8; 4] = b"q5ck";
+
+error_enum! {
+    pub enum ContainerError {
+        /// The container does not start with the magic bytes `q5ck`.
+        InvalidMagic = 1,
+        /// The container ended before its final chunk.
+        UnexpectedEof = 2,
+        /// The image in the container failed to decode.
+        Decode { source: DecodeError } = 3,
+    }
+}
+
+/// Transformation applied to the chunk payloads, e.g. encryption when writing and decryption when
+/// reading a container.
+///
+/// A chunk may be transformed in several pieces when it is read incrementally, so the transform
+/// gets the position of the piece within its chunk.
+pub trait ChunkTransform {
+    /// Transforms `data` in place, which are the bytes at `offset..offset + data.len()` of the
+    /// chunk with the given index.
+    fn transform(&mut self, index: u32, offset: usize, data: &mut [u8]);
+}
+
+impl<F> ChunkTransform for F
+where
+    F: FnMut(u32, usize, &mut [u8]),
+{
+    #[inline]
+    fn transform(&mut self, index: u32, offset: usize, data: &mut [u8]) {
+        self(index, offset, data)
+    }
+}
+
+/// Transform that leaves the chunks as they are.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoTransform;
+
+impl ChunkTransform for NoTransform {
+    #[inline]
+    fn transform(&mut self, _index: u32, _offset: usize, _data: &mut [u8]) {}
+}
+
+/// Writes the encoded `image` as a container to `w`, split into chunks of at most `chunk_len`
+/// bytes. Returns the number of chunks, not counting the final empty one.
+#[cfg(feature = "alloc")]
+pub fn encode_container(
+    image: &[u8],
+    chunk_len: u32,
+    transform: &mut impl ChunkTransform,
+    w: &mut Vec<u8>,
+) -> u32 {
+    w.extend_from_slice(CONTAINER_MAGIC);
+
+    let mut count = 0;
+    for chunk in image.chunks(chunk_len.max(1) as usize) {
+        w.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        let start = w.len();
+        w.extend_from_slice(chunk);
+        transform.transform(count, 0, &mut w[start..]);
+        count += 1;
+    }
+    w.extend_from_slice(&0u32.to_le_bytes());
+
+    count
+}
+
+/// Decodes the image in a container at once, using [`Q565DecodeContext::decode`].
+///
+/// Returns the number of pixels written.
+#[cfg(feature = "alloc")]
+pub fn decode_container<B, O>(
+    data: &[u8],
+    transform: &mut impl ChunkTransform,
+    output: O,
+) -> Result<usize, ContainerError>
+where
+    B: Endianness,
+    O: InfallibleDecodeOutput,
+{
+    let mut data = data.to_vec();
+    let mut image = Vec::with_capacity(data.len());
+
+    let mut reader = ContainerReader::new(|index, offset, data: &mut [u8]| {
+        transform.transform(index, offset, data)
+    });
+    reader.push(&mut data, |payload| image.extend_from_slice(payload))?;
+    ensure!(reader.is_finished(), ContainerError::UnexpectedEof);
+
+    let (_, pixels_written) = Q565DecodeContext::decode::<B>(&image, output)
+        .map_err(|source| ContainerError::Decode { source })?;
+    Ok(pixels_written)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ReaderState {
+    Magic { read: usize },
+    Length { bytes: [u8; 4], read: usize },
+    Payload { offset: usize, remaining: usize },
+    Finished,
+}
+
+/// Incremental reader of a container, handing out the transformed payloads as they arrive.
+///
+/// The payloads form the Q565 image, which can be decoded incrementally as well, e.g. by parsing
+/// the header with
+/// [`Q565DecodeContext::decode_header`](crate::decode::Q565DecodeContext::decode_header) and
+/// passing the rest to a [streaming decoder](crate::decode::streaming_no_header).
+pub struct ContainerReader<T> {
+    transform: T,
+    state: ReaderState,
+    index: u32,
+}
+
+impl<T> ContainerReader<T>
+where
+    T: ChunkTransform,
+{
+    pub const fn new(transform: T) -> Self {
+        Self {
+            transform,
+            state: ReaderState::Magic { read: 0 },
+            index: 0,
+        }
+    }
+
+    /// Returns whether the final chunk has been read.
+    pub const fn is_finished(&self) -> bool {
+        matches!(self.state, ReaderState::Finished)
+    }
+
+    /// Processes the next bytes of the container, which may be split across calls at any point.
+    ///
+    /// The payload bytes are transformed in place, and passed to `on_payload` in order. Returns
+    /// the number of bytes consumed, which is less than `data.len()` only if the container ended.
+    pub fn push(
+        &mut self,
+        data: &mut [u8],
+        mut on_payload: impl FnMut(&[u8]),
+    ) -> Result<usize, ContainerError> {
+        let mut pos = 0;
+        while pos < data.len() {
+            let rest = &mut data[pos..];
+            match &mut self.state {
+                ReaderState::Magic { read } => {
+                    let n = rest.len().min(CONTAINER_MAGIC.len() - *read);
+                    ensure!(
+                        rest[..n] == CONTAINER_MAGIC[*read..*read + n],
+                        ContainerError::InvalidMagic
+                    );
+                    *read += n;
+                    pos += n;
+
+                    if *read == CONTAINER_MAGIC.len() {
+                        self.state = ReaderState::Length {
+                            bytes: [0; 4],
+                            read: 0,
+                        };
+                    }
+                }
+                ReaderState::Length { bytes, read } => {
+                    let n = rest.len().min(bytes.len() - *read);
+                    bytes[*read..*read + n].copy_from_slice(&rest[..n]);
+                    *read += n;
+                    pos += n;
+
+                    if *read == bytes.len() {
+                        let len = saturating_usize(u32::from_le_bytes(*bytes));
+                        self.state = if len == 0 {
+                            ReaderState::Finished
+                        } else {
+                            ReaderState::Payload {
+                                offset: 0,
+                                remaining: len,
+                            }
+                        };
+                    }
+                }
+                ReaderState::Payload { offset, remaining } => {
+                    let n = rest.len().min(*remaining);
+                    let payload = &mut rest[..n];
+                    self.transform.transform(self.index, *offset, payload);
+                    on_payload(payload);
+                    *offset += n;
+                    *remaining -= n;
+                    pos += n;
+
+                    if *remaining == 0 {
+                        self.index += 1;
+                        self.state = ReaderState::Length {
+                            bytes: [0; 4],
+                            read: 0,
+                        };
+                    }
+                }
+                ReaderState::Finished => break,
+            }
+        }
+
+        Ok(pos)
+    }
+}
@@ -0,0 +1,85 @@
+//! Per-op cycle counting for the decode loops, reported via [`defmt`].
+//!
+//! Enabled with the `defmt-cycles` feature. The cycles are measured with the Cortex-M DWT cycle
+//! counter, which is not available on ARMv6-M (Cortex-M0/M0+) parts.
+//!
+//! ```ignore
+//! let mut cp = cortex_m::Peripherals::take().unwrap();
+//! q565::cycles::enable(&mut cp.DCB, &mut cp.DWT);
+//!
+//! // decode some images ...
+//!
+//! q565::cycles::report();
+//! ```
+//!
+//! The counters are not synchronized, decoding from multiple contexts (e.g. interrupt handlers)
+//! at the same time results in inaccurate numbers.
+
+use crate::consts::*;
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::peripheral::{DCB, DWT};
+
+const OP_NAMES: [&str; 6] = ["INDEX", "DIFF", "LUMA", "DIFF_INDEXED", "RUN", "RGB565"];
+
+static OP_COUNTS: [AtomicU32; 6] = [const { AtomicU32::new(0) }; 6];
+static OP_CYCLES: [AtomicU32; 6] = [const { AtomicU32::new(0) }; 6];
+
+/// Enables the DWT cycle counter.
+pub fn enable(dcb: &mut DCB, dwt: &mut DWT) {
+    dcb.enable_trace();
+    dwt.enable_cycle_counter();
+}
+
+/// Resets all counters to zero.
+pub fn reset() {
+    for counter in OP_COUNTS.iter().chain(&OP_CYCLES) {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Logs the number of decoded ops and the cycles spent on them, per op type.
+pub fn report() {
+    for (i, name) in OP_NAMES.iter().enumerate() {
+        let count = OP_COUNTS[i].load(Ordering::Relaxed);
+        let cycles = OP_CYCLES[i].load(Ordering::Relaxed);
+        defmt::info!(
+            "{=str}: {=u32} ops, {=u32} cycles, {=u32} cycles/op",
+            name,
+            count,
+            cycles,
+            cycles / count.max(1)
+        );
+    }
+}
+
+#[inline(always)]
+pub(crate) fn now() -> u32 {
+    DWT::cycle_count()
+}
+
+/// Adds the cycles since `start` to the counters of the op starting with `byte`.
+#[inline(always)]
+pub(crate) fn record(start: u32, byte: u8) {
+    let elapsed = now().wrapping_sub(start);
+
+    let index = match byte {
+        Q565_OP_RGB565 => 5,
+        _ if byte >> 6 == 0b11 => 4,
+        _ if byte >> 5 == Q565_OP_DIFF_INDEXED >> 5 => 3,
+        _ if byte >> 5 == Q565_OP_LUMA >> 5 => 2,
+        _ if byte >> 6 == Q565_OP_DIFF >> 6 => 1,
+        _ => 0,
+    };
+
+    // plain load + store, as ARMv6-M has no atomic read-modify-write operations
+    let count = &OP_COUNTS[index];
+    count.store(
+        count.load(Ordering::Relaxed).wrapping_add(1),
+        Ordering::Relaxed,
+    );
+    let cycles = &OP_CYCLES[index];
+    cycles.store(
+        cycles.load(Ordering::Relaxed).wrapping_add(elapsed),
+        Ordering::Relaxed,
+    );
+}
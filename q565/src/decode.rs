@@ -1,62 +1,153 @@
+use crate::byteorder::Endianness;
 use crate::{
+    consts::MAX_OP_PIXELS,
     decode::ops::{direct_bigger_diff, direct_small_diff, indexed_diff},
     utils::hash,
-    ColorFormat, HeaderInfo,
+    ColorArraySize, ColorFormat, HeaderInfo, EXTENDED_HEADER_LEN, EXTENDED_MAGIC, HEADER_LEN,
+    MAGIC, RAW_FLAG,
 };
-use byteorder::ByteOrder;
-use snafu::{ensure, Snafu};
 
+#[cfg(not(feature = "forbid-unsafe"))]
 pub mod streaming_no_header;
 
+/// Adds the cycles spent on the current op to the counters, if the `defmt-cycles` feature is
+/// enabled.
+macro_rules! record_op {
+    ($start:ident, $byte:expr) => {
+        #[cfg(feature = "defmt-cycles")]
+        crate::cycles::record($start, $byte);
+    };
+}
+
 #[cfg(feature = "alloc")]
 mod alloc_api;
-mod ops;
+mod chunked;
+mod decoder;
+#[cfg(feature = "alloc")]
+mod downscale;
+mod fallible;
+mod image_ref;
+mod iter;
+mod mini;
+pub(crate) mod ops;
+mod pixel_doubling;
+#[cfg(feature = "alloc")]
+mod pool;
+mod rect;
+mod remap;
+mod row_digest;
+mod spans;
+#[cfg(not(feature = "forbid-unsafe"))]
+mod uninit;
+#[cfg(not(feature = "forbid-unsafe"))]
+mod volatile;
 
 #[cfg(feature = "alloc")]
 pub use alloc_api::*;
-
+pub use chunked::*;
+pub use decoder::*;
+#[cfg(feature = "alloc")]
+pub use downscale::*;
+pub use fallible::*;
+pub use image_ref::*;
+pub use iter::*;
+pub use mini::*;
+pub use pixel_doubling::*;
+#[cfg(feature = "alloc")]
+pub use pool::*;
+pub use rect::*;
+pub use remap::*;
+pub use row_digest::*;
+pub use spans::*;
+#[cfg(not(feature = "forbid-unsafe"))]
+pub use uninit::*;
+#[cfg(not(feature = "forbid-unsafe"))]
+pub use volatile::*;
+
+/// Decoder state, with a color array of `N` entries.
+///
+/// `N` must be 16, 32, or 64, and limits which [color array
+/// profiles](crate#color-array-profiles) can be decoded with this context: images using a larger
+/// color array are rejected. The state takes up `2 + 2 * N` bytes, so 130 bytes for the default
+/// size and 34 bytes for a context that only decodes the 16-entry profile.
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
-pub struct Q565DecodeContext {
+pub struct Q565DecodeContext<const N: usize = 64> {
     pub prev: u16,
-    pub arr: [u16; 64],
+    pub arr: [u16; N],
 }
 
 impl Q565DecodeContext {
     pub const fn new() -> Self {
+        Self::new_sized()
+    }
+}
+
+impl<const N: usize> Q565DecodeContext<N> {
+    const COLOR_ARRAY_SIZE: ColorArraySize = match ColorArraySize::from_entries(N) {
+        Some(size) => size,
+        None => panic!("the color array must have 16, 32, or 64 entries"),
+    };
+
+    /// Creates a new context with a color array of `N` entries.
+    pub const fn new_sized() -> Self {
+        let _ = Self::COLOR_ARRAY_SIZE;
+
         Self {
-            arr: [0; 64],
+            arr: [0; N],
             prev: 0,
         }
     }
 }
 
-impl Default for Q565DecodeContext {
+// The decoder state is part of the C API, and the documented memory footprint.
+const _: () = {
+    assert!(core::mem::size_of::<Q565DecodeContext<16>>() == 34);
+    assert!(core::mem::size_of::<Q565DecodeContext<32>>() == 66);
+    assert!(core::mem::size_of::<Q565DecodeContext<64>>() == 130);
+};
+
+impl<const N: usize> Default for Q565DecodeContext<N> {
     fn default() -> Self {
-        Self::new()
+        Self::new_sized()
     }
 }
 
-#[derive(Debug, Snafu)]
-#[snafu(module)]
-pub enum DecodeUncheckedError {
-    /// The output is too small to hold the entire image as claimed by the header.
-    OutputTooSmall,
-    /// The decoded image data is shorter than the header claims.
-    MissingData,
+error_enum! {
+    #[non_exhaustive]
+    pub enum DecodeUncheckedError {
+        /// The output is too small to hold the entire image as claimed by the header.
+        OutputTooSmall = 1,
+        /// The image uses a larger color array than the decoder context provides.
+        ColorArrayTooSmall = 2,
+        /// The decoded image data is shorter than the header claims.
+        MissingData = 3,
+        /// The image has more pixels than can be addressed on this target.
+        DimensionsTooLarge = 4,
+    }
 }
 
-#[derive(Debug, Snafu)]
-#[snafu(module)]
-pub enum DecodeError {
-    /// The output is too small to hold the entire image as claimed by the header.
-    OutputTooSmall,
-    /// The input data ended before the image was fully decoded.
-    UnexpectedEof,
-    /// The image does not start with the magic bytes `q565`.
-    InvalidMagic,
-    /// The decoded image data is shorter than the header claims.
-    MissingData,
+error_enum! {
+    #[non_exhaustive]
+    pub enum DecodeError {
+        /// The output is too small to hold the entire image as claimed by the header.
+        OutputTooSmall = 1,
+        /// The input data ended before the image was fully decoded.
+        UnexpectedEof = 2,
+        /// The image does not start with the magic bytes `q565` or `q56x`.
+        InvalidMagic = 3,
+        /// The extended header sets flags that aren't supported by this decoder.
+        UnsupportedFlags = 4,
+        /// The image uses a larger color array than the decoder context provides, or references an
+        /// entry outside of the color array of its profile.
+        ColorArrayTooSmall = 5,
+        /// The decoded image data is shorter than the header claims.
+        MissingData = 6,
+        /// The image data contains more pixels than the header claims.
+        TooManyPixels = 7,
+        /// The image has more pixels than can be addressed on this target.
+        DimensionsTooLarge = 8,
+    }
 }
 
 impl Q565DecodeContext {
@@ -65,114 +156,343 @@ impl Q565DecodeContext {
         output: impl InfallibleDecodeOutput,
     ) -> Result<(HeaderInfo, usize), DecodeError>
     where
-        B: ByteOrder,
+        B: Endianness,
     {
         let mut state = Q565DecodeContext::new();
         state.decode_with_state::<B>(data, output)
     }
 
-    fn decode_header(data: &[u8]) -> Result<(HeaderInfo, &[u8]), DecodeError> {
-        // Header size plus 1 byte for the end marker
-        ensure!(data.len() >= 9, decode_error::UnexpectedEofSnafu);
+    /// Like [`decode`](Self::decode), but into an output whose writes can fail, see
+    /// [`FallibleDecodeOutput`].
+    pub fn decode_fallible<B, O>(
+        data: &[u8],
+        output: O,
+    ) -> Result<(HeaderInfo, usize), FallibleDecodeError<O::Error>>
+    where
+        B: Endianness,
+        O: FallibleDecodeOutput,
+    {
+        let mut state = Q565DecodeContext::new();
+        state.decode_fallible_with_state::<B, O>(data, output)
+    }
 
-        let (header, data) = data.split_at(8);
-        let magic = &header[0..4];
-        ensure!(magic == b"q565", decode_error::InvalidMagicSnafu);
+    /// Parses the header without decoding the image, e.g. to check its size before allocating the
+    /// output. Returns the header and the data following it.
+    pub fn decode_header(data: &[u8]) -> Result<(HeaderInfo, &[u8]), DecodeError> {
+        // Header size plus 1 byte for the end marker
+        ensure!(data.len() > HEADER_LEN, DecodeError::UnexpectedEof);
+
+        let (magic, data) = data.split_at(4);
+        let (color_array_size, raw, data) = if magic == MAGIC {
+            (ColorArraySize::Entries64, false, data)
+        } else if magic == EXTENDED_MAGIC {
+            ensure!(
+                data.len() > EXTENDED_HEADER_LEN - 4,
+                DecodeError::UnexpectedEof
+            );
+            let flags = data[0];
+            let color_array_size =
+                ColorArraySize::from_flags(flags).ok_or(DecodeError::UnsupportedFlags)?;
+            let raw = flags & RAW_FLAG != 0;
+            ensure!(
+                !raw || color_array_size == ColorArraySize::Entries64,
+                DecodeError::UnsupportedFlags
+            );
+            (color_array_size, raw, &data[1..])
+        } else {
+            return Err(DecodeError::InvalidMagic);
+        };
 
-        let width = u16::from_le_bytes([header[4], header[5]]);
-        let height = u16::from_le_bytes([header[6], header[7]]);
-        Ok((HeaderInfo { width, height }, data))
+        let width = u16::from_le_bytes([data[0], data[1]]);
+        let height = u16::from_le_bytes([data[2], data[3]]);
+        let header = HeaderInfo {
+            width,
+            height,
+            color_array_size,
+            raw,
+        };
+        Ok((header, &data[4..]))
     }
+}
 
+impl<const N: usize> Q565DecodeContext<N> {
     pub fn decode_with_state<B>(
         &mut self,
         data: &[u8],
-        mut output: impl InfallibleDecodeOutput,
+        output: impl InfallibleDecodeOutput,
     ) -> Result<(HeaderInfo, usize), DecodeError>
     where
-        B: ByteOrder,
+        B: Endianness,
     {
-        let (header, data) = Self::decode_header(data)?;
-        let (width, height) = (usize::from(header.width), usize::from(header.height));
-        let expected_size = width * height;
+        self.decode_fallible_with_state::<B, _>(data, AsFallible(output))
+            .map_err(Into::into)
+    }
 
-        ensure!(
-            output
-                .max_len()
-                .map(|max_len| max_len >= expected_size)
-                .unwrap_or(true),
-            decode_error::OutputTooSmallSnafu
-        );
+    /// Like [`decode_with_state`](Self::decode_with_state), but into an output whose writes can
+    /// fail, see [`FallibleDecodeOutput`]. Decoding stops at the first failed write.
+    pub fn decode_fallible_with_state<B, O>(
+        &mut self,
+        data: &[u8],
+        mut output: O,
+    ) -> Result<(HeaderInfo, usize), FallibleDecodeError<O::Error>>
+    where
+        B: Endianness,
+        O: FallibleDecodeOutput,
+    {
+        panic_free!({
+            let (header, data) = Q565DecodeContext::decode_header(data)?;
+            let expected_size = header
+                .pixel_count()
+                .ok_or(DecodeError::DimensionsTooLarge)?;
+
+            ensure!(
+                output
+                    .max_len()
+                    .map(|max_len| max_len >= expected_size)
+                    .unwrap_or(true),
+                DecodeError::OutputTooSmall
+            );
+            ensure!(
+                !header.raw || data.len() / 2 >= expected_size,
+                DecodeError::UnexpectedEof
+            );
+            output.reserve(reserved_pixels(header.raw, data, expected_size));
+
+            if header.raw {
+                decode_raw::<B, _>(data, expected_size, &mut output)
+                    .map_err(|source| FallibleDecodeError::Output { source })?;
+            } else {
+                self.decode_data::<B, _>(
+                    header.color_array_size,
+                    data,
+                    expected_size,
+                    &mut output,
+                )?;
+            }
+            let pixels_written = output.current_output_position();
 
-        self.decode_data::<B>(data, &mut output)?;
-        let pixels_written = output.current_output_position();
+            ensure!(pixels_written == expected_size, DecodeError::MissingData);
 
-        ensure!(
-            pixels_written == expected_size,
-            decode_error::MissingDataSnafu
-        );
+            Ok((header, pixels_written))
+        })
+    }
 
-        Ok((header, pixels_written))
+    /// Decodes the next frame of a [frame sequence](crate#frame-sequences), continuing from the
+    /// state left by the frames before it.
+    ///
+    /// Frames of a sequence encoded with
+    /// [`Q565EncodeContext::encode_frame`](crate::encode::Q565EncodeContext::encode_frame) are
+    /// decoded correctly only in order, starting with a fresh context. [Raw
+    /// frames](crate#raw-images) don't carry any state and are rejected with
+    /// [`DecodeError::UnsupportedFlags`].
+    pub fn decode_frame<B>(
+        &mut self,
+        data: &[u8],
+        output: impl InfallibleDecodeOutput,
+    ) -> Result<(HeaderInfo, usize), DecodeError>
+    where
+        B: Endianness,
+    {
+        let (header, _) = Q565DecodeContext::decode_header(data)?;
+        ensure!(!header.raw, DecodeError::UnsupportedFlags);
+        self.decode_with_state::<B>(data, output)
     }
 
-    fn decode_data<B>(
+    /// Like [`decode_frame`](Self::decode_frame), but also accepts
+    /// [`REPEAT_FRAME`](crate::REPEAT_FRAME), for which it returns `None` without writing to
+    /// `output`: the frame is the same as the one before.
+    ///
+    /// See [`Q565EncodeContext::encode_frame_or_repeat`](crate::encode::Q565EncodeContext::encode_frame_or_repeat).
+    pub fn decode_frame_or_repeat<B>(
         &mut self,
         data: &[u8],
-        output: &mut impl InfallibleDecodeOutput,
-    ) -> Result<(), DecodeError>
+        output: impl InfallibleDecodeOutput,
+    ) -> Result<Option<(HeaderInfo, usize)>, DecodeError>
     where
-        B: ByteOrder,
+        B: Endianness,
     {
-        let mut data = data.iter().copied();
-        let mut next = || data.next().ok_or(DecodeError::UnexpectedEof);
-        loop {
-            let byte = next()?;
-            let op = byte >> 6;
-
-            let pixel = match op {
-                0b00 => {
-                    let pixel = unsafe { *self.arr.get_unchecked(usize::from(byte)) };
-                    self.set_pixel_infallible_output::<B>(pixel, output);
-                    continue;
+        if data == [crate::REPEAT_FRAME] {
+            return Ok(None);
+        }
+        self.decode_frame::<B>(data, output).map(Some)
+    }
+
+    fn decode_data<B, O>(
+        &mut self,
+        color_array_size: ColorArraySize,
+        data: &[u8],
+        pixel_count: usize,
+        output: &mut O,
+    ) -> Result<(), FallibleDecodeError<O::Error>>
+    where
+        B: Endianness,
+        O: FallibleDecodeOutput,
+    {
+        let Self { prev, arr } = self;
+        let too_small = DecodeError::ColorArrayTooSmall;
+        match color_array_size {
+            // hashing the pixels into the smallest array is wasted effort, but harmless
+            ColorArraySize::NoArray => decode_ops::<B, 16, false, O>(
+                prev,
+                arr.first_chunk_mut().ok_or(too_small)?,
+                data,
+                pixel_count,
+                output,
+            ),
+            ColorArraySize::Entries16 => decode_ops::<B, 16, true, O>(
+                prev,
+                arr.first_chunk_mut().ok_or(too_small)?,
+                data,
+                pixel_count,
+                output,
+            ),
+            ColorArraySize::Entries32 => decode_ops::<B, 32, true, O>(
+                prev,
+                arr.first_chunk_mut().ok_or(too_small)?,
+                data,
+                pixel_count,
+                output,
+            ),
+            ColorArraySize::Entries64 => decode_ops::<B, 64, true, O>(
+                prev,
+                arr.first_chunk_mut().ok_or(too_small)?,
+                data,
+                pixel_count,
+                output,
+            ),
+        }
+    }
+}
+
+/// Writes up to `pixel_count` pixels of a [raw image](crate#raw-images).
+#[inline]
+fn decode_raw<B, O>(data: &[u8], pixel_count: usize, output: &mut O) -> Result<(), O::Error>
+where
+    B: Endianness,
+    O: FallibleDecodeOutput,
+{
+    for pixel in data.chunks_exact(2).take(pixel_count) {
+        output.write_pixel::<B>(u16::from_le_bytes([pixel[0], pixel[1]]))?;
+    }
+    Ok(())
+}
+
+/// Number of pixels to reserve in the output for an image of `pixel_count` pixels: at most what
+/// `data` can decode to, so that a header claiming a huge image doesn't allocate it up front.
+#[inline]
+fn reserved_pixels(raw: bool, data: &[u8], pixel_count: usize) -> usize {
+    let max_pixels = if raw {
+        data.len() / 2
+    } else {
+        data.len().saturating_mul(MAX_OP_PIXELS)
+    };
+    pixel_count.min(max_pixels)
+}
+
+/// Decodes ops until the end marker, writing at most `pixel_count` pixels. Data producing more
+/// pixels is rejected, so a few bytes of runs can't grow an unbounded output indefinitely.
+///
+/// Ops referencing an entry outside of the `N` entries of the color array, or any entry without
+/// `ARRAY`, are rejected as well, like by [`Q565Ref::new`] and [`MiniDecoder`].
+fn decode_ops<B, const N: usize, const ARRAY: bool, O>(
+    prev: &mut u16,
+    arr: &mut [u16; N],
+    data: &[u8],
+    pixel_count: usize,
+    output: &mut O,
+) -> Result<(), FallibleDecodeError<O::Error>>
+where
+    B: Endianness,
+    O: FallibleDecodeOutput,
+{
+    let output_error = |source| FallibleDecodeError::Output { source };
+    let mut data = data.iter().copied();
+    let mut next = || data.next().ok_or(DecodeError::UnexpectedEof);
+    let mut remaining = pixel_count;
+    loop {
+        #[cfg(feature = "defmt-cycles")]
+        let op_start = crate::cycles::now();
+        let byte = next()?;
+        let op = byte >> 6;
+
+        // every op but the end marker produces at least one pixel
+        ensure!(remaining != 0 || byte == 0xFF, DecodeError::TooManyPixels);
+
+        let pixel = match op {
+            0b00 => {
+                let index = usize::from(byte);
+                ensure!(ARRAY && index < N, DecodeError::ColorArrayTooSmall);
+                #[cfg(not(feature = "forbid-unsafe"))]
+                let pixel = unsafe { *arr.get_unchecked(index) };
+                #[cfg(feature = "forbid-unsafe")]
+                let pixel = arr[index];
+                remaining -= 1;
+                *prev = pixel;
+                output.write_pixel::<B>(pixel).map_err(output_error)?;
+                record_op!(op_start, byte);
+                continue;
+            }
+            0b01 => {
+                let pixel = direct_small_diff(*prev, byte);
+                remaining -= 1;
+                *prev = pixel;
+                output.write_pixel::<B>(pixel).map_err(output_error)?;
+                record_op!(op_start, byte);
+                continue;
+            }
+            0b10 => {
+                if byte & 0b0010_0000 == 0 {
+                    direct_bigger_diff(*prev, byte, next()?)
+                } else {
+                    let second_byte = next()?;
+                    ensure!(
+                        ARRAY && usize::from(second_byte & 0b0011_1111) < N,
+                        DecodeError::ColorArrayTooSmall
+                    );
+                    indexed_diff(arr, byte, second_byte)
                 }
-                0b01 => {
-                    let pixel = direct_small_diff(self.prev, byte);
-                    self.set_pixel_infallible_output::<B>(pixel, output);
+            }
+            0b11 => {
+                if byte == 0xFE {
+                    let pixel = [next()?, next()?];
+                    u16::from_le_bytes(pixel)
+                } else if byte != 0xFF {
+                    let count = (byte & 0b0011_1111) + 1;
+                    let count = usize::from(count);
+                    ensure!(count <= remaining, DecodeError::TooManyPixels);
+                    remaining -= count;
+
+                    output
+                        .write_many_pixels::<B>(*prev, count)
+                        .map_err(output_error)?;
+                    record_op!(op_start, byte);
                     continue;
+                } else {
+                    break;
                 }
-                0b10 => {
-                    if byte & 0b0010_0000 == 0 {
-                        direct_bigger_diff(self.prev, byte, next()?)
-                    } else {
-                        indexed_diff(&self.arr, byte, next()?)
-                    }
-                }
-                0b11 => {
-                    if byte == 0xFE {
-                        let pixel = [next()?, next()?];
-                        u16::from_le_bytes(pixel)
-                    } else if byte != 0xFF {
-                        let count = (byte & 0b0011_1111) + 1;
-                        let count = usize::from(count);
-
-                        output.write_many_pixels::<B>(self.prev, count);
-                        continue;
-                    } else {
-                        break;
-                    }
-                }
-                _ => unsafe { core::hint::unreachable_unchecked() },
-            };
-
-            let index = hash(pixel);
-            unsafe {
-                *self.arr.get_unchecked_mut(usize::from(index)) = pixel;
             }
-            self.set_pixel_infallible_output::<B>(pixel, output);
-        }
+            #[cfg(not(feature = "forbid-unsafe"))]
+            _ => unsafe { core::hint::unreachable_unchecked() },
+            #[cfg(feature = "forbid-unsafe")]
+            _ => unreachable!(),
+        };
 
-        Ok(())
+        let index = usize::from(hash(pixel)) & (N - 1);
+        #[cfg(not(feature = "forbid-unsafe"))]
+        unsafe {
+            *arr.get_unchecked_mut(index) = pixel;
+        }
+        #[cfg(feature = "forbid-unsafe")]
+        {
+            arr[index] = pixel;
+        }
+        remaining -= 1;
+        *prev = pixel;
+        output.write_pixel::<B>(pixel).map_err(output_error)?;
+        record_op!(op_start, byte);
     }
+
+    Ok(())
 }
 
 impl Q565DecodeContext {
@@ -187,17 +507,20 @@ impl Q565DecodeContext {
     ///
     /// The caller needs to ensure that the input is a valid Q565 image. Any failure to do so
     /// results in undefined behavior.
+    #[cfg(not(feature = "forbid-unsafe"))]
     pub unsafe fn decode_unchecked<B>(
         data: &[u8],
         output: impl InfallibleDecodeOutput,
     ) -> Result<(HeaderInfo, usize), DecodeUncheckedError>
     where
-        B: ByteOrder,
+        B: Endianness,
     {
         let mut state = Q565DecodeContext::new();
         state.decode_unchecked_with_state::<B>(data, output)
     }
+}
 
+impl<const N: usize> Q565DecodeContext<N> {
     /// Decodes a Q565 image into a buffer, with the given state (`self`) as starting state.
     ///
     /// Returns the number of pixels written to the output buffer, if successful.
@@ -209,48 +532,96 @@ impl Q565DecodeContext {
     ///
     /// The caller needs to ensure that the input is a valid Q565 image. Any failure to do so
     /// results in undefined behavior.
+    #[cfg(not(feature = "forbid-unsafe"))]
     pub unsafe fn decode_unchecked_with_state<B>(
         &mut self,
         data: &[u8],
         mut output: impl InfallibleDecodeOutput,
     ) -> Result<(HeaderInfo, usize), DecodeUncheckedError>
     where
-        B: ByteOrder,
+        B: Endianness,
     {
-        let (header, data) = Self::decode_header_unchecked(data);
-        let (width, height) = (usize::from(header.width), usize::from(header.height));
-        let expected_size = width * height;
-
-        if output
-            .max_len()
-            .map(|max_len| max_len < expected_size)
-            .unwrap_or(false)
-        {
-            return Err(DecodeUncheckedError::OutputTooSmall);
-        }
+        panic_free!({
+            let (header, data) = Self::decode_header_unchecked(data);
+            let expected_size = header
+                .pixel_count()
+                .ok_or(DecodeUncheckedError::DimensionsTooLarge)?;
 
-        self.decode_data_unchecked::<B>(data, &mut output);
-        let pixels_written = output.current_output_position();
+            if output
+                .max_len()
+                .map(|max_len| max_len < expected_size)
+                .unwrap_or(false)
+            {
+                return Err(DecodeUncheckedError::OutputTooSmall);
+            }
+            output.reserve(reserved_pixels(header.raw, data, expected_size));
+
+            let Self { prev, arr } = self;
+            let output = &mut output;
+            let too_small = DecodeUncheckedError::ColorArrayTooSmall;
+            if header.raw {
+                let Ok(()) = decode_raw::<B, _>(data, expected_size, &mut AsFallible(&mut *output));
+            } else {
+                match header.color_array_size {
+                    ColorArraySize::NoArray | ColorArraySize::Entries16 => {
+                        let arr = arr.first_chunk_mut().ok_or(too_small)?;
+                        decode_ops_unchecked::<B, 16>(prev, arr, data, output)
+                    }
+                    ColorArraySize::Entries32 => {
+                        let arr = arr.first_chunk_mut().ok_or(too_small)?;
+                        decode_ops_unchecked::<B, 32>(prev, arr, data, output)
+                    }
+                    ColorArraySize::Entries64 => {
+                        let arr = arr.first_chunk_mut().ok_or(too_small)?;
+                        decode_ops_unchecked::<B, 64>(prev, arr, data, output)
+                    }
+                }
+            }
+            let pixels_written = output.current_output_position();
 
-        ensure!(
-            pixels_written == expected_size,
-            decode_unchecked_error::MissingDataSnafu
-        );
+            ensure!(
+                pixels_written == expected_size,
+                DecodeUncheckedError::MissingData
+            );
 
-        Ok((header, pixels_written))
+            Ok((header, pixels_written))
+        })
     }
 
+    #[cfg(not(feature = "forbid-unsafe"))]
     unsafe fn decode_header_unchecked(data: &[u8]) -> (HeaderInfo, &[u8]) {
-        let width = u16::from_le_bytes([*data.get_unchecked(4), *data.get_unchecked(5)]);
-        let height = u16::from_le_bytes([*data.get_unchecked(6), *data.get_unchecked(7)]);
+        // the extended header only differs in the last magic byte, and has the flags in front of
+        // the dimensions
+        let (color_array_size, raw, data) = if *data.get_unchecked(3) == EXTENDED_MAGIC[3] {
+            let flags = *data.get_unchecked(4);
+            let color_array_size = ColorArraySize::from_flags(flags).unwrap_or_default();
+            (
+                color_array_size,
+                flags & RAW_FLAG != 0,
+                data.get_unchecked(5..),
+            )
+        } else {
+            (ColorArraySize::Entries64, false, data.get_unchecked(4..))
+        };
 
-        let data = data.get_unchecked(8..);
-        (HeaderInfo { width, height }, data)
+        let width = u16::from_le_bytes([*data.get_unchecked(0), *data.get_unchecked(1)]);
+        let height = u16::from_le_bytes([*data.get_unchecked(2), *data.get_unchecked(3)]);
+
+        let data = data.get_unchecked(4..);
+        let header = HeaderInfo {
+            width,
+            height,
+            color_array_size,
+            raw,
+        };
+        (header, data)
     }
 
     /// Decodes raw Q565 image data into a buffer, with the given state (`self`) as starting
     /// state.
     ///
+    /// The data must use the color array profile with `N` entries.
+    ///
     /// Returns the number of pixels written to the output buffer, if successful.
     ///
     /// # Safety
@@ -259,94 +630,150 @@ impl Q565DecodeContext {
     ///
     /// The caller needs to ensure that the input is valid Q565 image data and that the output
     /// is big enough.
+    #[cfg(not(feature = "forbid-unsafe"))]
     pub unsafe fn decode_data_unchecked<B>(
         &mut self,
         data: &[u8],
         output: &mut impl InfallibleDecodeOutput,
     ) where
-        B: ByteOrder,
+        B: Endianness,
     {
-        let mut input_idx = 0;
-        let mut next = || {
-            let b = *data.get_unchecked(input_idx);
-            input_idx += 1;
-            b
-        };
-
-        loop {
-            let byte = next();
-            let op = byte >> 6;
+        panic_free!({ decode_ops_unchecked::<B, N>(&mut self.prev, &mut self.arr, data, output) })
+    }
+}
 
-            let pixel = match op {
-                0b00 => {
-                    let pixel = *self.arr.get_unchecked(usize::from(byte));
-                    self.set_pixel_infallible_output::<B>(pixel, output);
-                    continue;
+/// # Safety
+///
+/// See [`Q565DecodeContext::decode_data_unchecked`].
+#[cfg(not(feature = "forbid-unsafe"))]
+unsafe fn decode_ops_unchecked<B, const N: usize>(
+    prev: &mut u16,
+    arr: &mut [u16; N],
+    data: &[u8],
+    output: &mut impl InfallibleDecodeOutput,
+) where
+    B: Endianness,
+{
+    let mut input_idx = 0;
+    let mut next = || {
+        let b = *data.get_unchecked(input_idx);
+        input_idx += 1;
+        b
+    };
+
+    loop {
+        #[cfg(feature = "defmt-cycles")]
+        let op_start = crate::cycles::now();
+        let byte = next();
+        let op = byte >> 6;
+
+        let pixel = match op {
+            0b00 => {
+                let pixel = *arr.get_unchecked(usize::from(byte) & (N - 1));
+                set_pixel::<B>(prev, pixel, output);
+                record_op!(op_start, byte);
+                continue;
+            }
+            0b01 => {
+                let pixel = direct_small_diff(*prev, byte);
+                set_pixel::<B>(prev, pixel, output);
+                record_op!(op_start, byte);
+                continue;
+            }
+            0b10 => {
+                if byte & 0b0010_0000 == 0 {
+                    direct_bigger_diff(*prev, byte, next())
+                } else {
+                    indexed_diff(arr, byte, next())
                 }
-                0b01 => {
-                    let pixel = direct_small_diff(self.prev, byte);
-                    self.set_pixel_infallible_output::<B>(pixel, output);
+            }
+            0b11 => {
+                if byte == 0xFE {
+                    let pixel = [next(), next()];
+                    u16::from_le_bytes(pixel)
+                } else if byte != 0xFF {
+                    let count = (byte & 0b0011_1111) + 1;
+                    let count = usize::from(count);
+
+                    output.write_many_pixels::<B>(*prev, count);
+                    record_op!(op_start, byte);
                     continue;
+                } else {
+                    break;
                 }
-                0b10 => {
-                    if byte & 0b0010_0000 == 0 {
-                        direct_bigger_diff(self.prev, byte, next())
-                    } else {
-                        indexed_diff(&self.arr, byte, next())
-                    }
-                }
-                0b11 => {
-                    if byte == 0xFE {
-                        let pixel = [next(), next()];
-                        u16::from_le_bytes(pixel)
-                    } else if byte != 0xFF {
-                        let count = (byte & 0b0011_1111) + 1;
-                        let count = usize::from(count);
-
-                        output.write_many_pixels::<B>(self.prev, count);
-                        continue;
-                    } else {
-                        break;
-                    }
-                }
-                _ => unsafe { core::hint::unreachable_unchecked() },
-            };
+            }
+            _ => unsafe { core::hint::unreachable_unchecked() },
+        };
 
-            let index = hash(pixel);
-            *self.arr.get_unchecked_mut(usize::from(index)) = pixel;
-            self.set_pixel_infallible_output::<B>(pixel, output);
-        }
+        let index = usize::from(hash(pixel)) & (N - 1);
+        *arr.get_unchecked_mut(index) = pixel;
+        set_pixel::<B>(prev, pixel, output);
+        record_op!(op_start, byte);
     }
 }
 
-impl Q565DecodeContext {
-    #[inline(always)]
-    fn set_pixel_infallible_output<B: ByteOrder>(
-        &mut self,
-        pixel: u16,
-        output: &mut impl InfallibleDecodeOutput,
-    ) {
-        self.prev = pixel;
-        output.write_pixel::<B>(pixel);
-    }
+#[cfg(not(feature = "forbid-unsafe"))]
+#[inline(always)]
+fn set_pixel<B: Endianness>(prev: &mut u16, pixel: u16, output: &mut impl InfallibleDecodeOutput) {
+    *prev = pixel;
+    output.write_pixel::<B>(pixel);
 }
 
 pub trait InfallibleDecodeOutput {
-    fn write_pixel<B: ByteOrder>(&mut self, color: u16);
-    fn write_many_pixels<B: ByteOrder>(&mut self, color: u16, count: usize);
+    fn write_pixel<B: Endianness>(&mut self, color: u16);
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize);
 
     /// Returns the maximum number of pixels that can be written to the output buffer.
     ///
     /// `None` if the output buffer is unbounded.
     fn max_len(&self) -> Option<usize>;
     fn current_output_position(&self) -> usize;
+
+    /// Called by the decoders once the header is parsed, before any pixels are written, with the
+    /// number of pixels in the image. Unbounded outputs can use it to reserve capacity up front.
+    #[inline]
+    fn reserve(&mut self, pixel_count: usize) {
+        let _ = pixel_count;
+    }
+}
+
+impl<O> InfallibleDecodeOutput for &mut O
+where
+    O: InfallibleDecodeOutput + ?Sized,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        (**self).write_pixel::<B>(color)
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        (**self).write_many_pixels::<B>(color, count)
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        (**self).max_len()
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        (**self).current_output_position()
+    }
+
+    #[inline]
+    fn reserve(&mut self, pixel_count: usize) {
+        (**self).reserve(pixel_count)
+    }
 }
 
+#[cfg(not(feature = "forbid-unsafe"))]
 pub struct UnsafeSliceDecodeOutput<'a, C: ColorFormat> {
     output: &'a mut [C::OutputElement],
     output_idx: usize,
 }
 
+#[cfg(not(feature = "forbid-unsafe"))]
 impl<'a, C> UnsafeSliceDecodeOutput<'a, C>
 where
     C: ColorFormat,
@@ -364,12 +791,13 @@ where
     }
 }
 
+#[cfg(not(feature = "forbid-unsafe"))]
 impl<C> InfallibleDecodeOutput for UnsafeSliceDecodeOutput<'_, C>
 where
     C: ColorFormat,
 {
     #[inline]
-    fn write_pixel<B: ByteOrder>(&mut self, color: u16) {
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
         unsafe {
             *self.output.get_unchecked_mut(self.output_idx) = C::to_output::<B>(color);
         }
@@ -377,7 +805,7 @@ where
     }
 
     #[inline]
-    fn write_many_pixels<B: ByteOrder>(&mut self, color: u16, count: usize) {
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
         let color = C::to_output::<B>(color);
         unsafe {
             self.output
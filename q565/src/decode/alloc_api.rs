@@ -1,6 +1,7 @@
-use super::{ColorFormat, InfallibleDecodeOutput};
+use super::{ColorFormat, DecodeError, InfallibleDecodeOutput, Q565DecodeContext};
+use crate::byteorder::Endianness;
+use crate::HeaderInfo;
 use alloc::vec::Vec;
-use byteorder::ByteOrder;
 
 pub struct VecDecodeOutput<'a, C: ColorFormat> {
     output: &'a mut Vec<C::OutputElement>,
@@ -25,15 +26,15 @@ where
     C: ColorFormat,
 {
     #[inline]
-    fn write_pixel<B: ByteOrder>(&mut self, color: u16) {
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
         self.output.push(C::to_output::<B>(color));
         self.output_idx += 1;
     }
 
     #[inline]
-    fn write_many_pixels<B: ByteOrder>(&mut self, color: u16, count: usize) {
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
         let color = C::to_output::<B>(color);
-        self.output.extend(core::iter::repeat(color).take(count));
+        self.output.extend(core::iter::repeat_n(color, count));
         self.output_idx += count;
     }
 
@@ -46,4 +47,24 @@ where
     fn current_output_position(&self) -> usize {
         self.output_idx
     }
+
+    #[inline]
+    fn reserve(&mut self, pixel_count: usize) {
+        self.output.reserve(pixel_count);
+    }
+}
+
+impl Q565DecodeContext {
+    /// Decodes a Q565 image into a newly allocated vector.
+    pub fn decode_to_vec<B, C>(
+        data: &[u8],
+    ) -> Result<(HeaderInfo, Vec<C::OutputElement>), DecodeError>
+    where
+        B: Endianness,
+        C: ColorFormat,
+    {
+        let mut output = Vec::new();
+        let (header, _) = Self::decode::<B>(data, VecDecodeOutput::<C>::new(&mut output))?;
+        Ok((header, output))
+    }
 }
@@ -0,0 +1,263 @@
+use super::{ColorFormat, InfallibleDecodeOutput};
+use crate::byteorder::Endianness;
+use core::mem;
+
+/// Receiver of decoded pixels in chunks, e.g. a display driver that writes each chunk out over
+/// SPI.
+///
+/// The chunk is only borrowed for the call. For transfers that continue after the call returns,
+/// like DMA, see [`DmaPixelSink`].
+pub trait PixelSink<T> {
+    /// Called with the next chunk of decoded pixels.
+    fn push_chunk(&mut self, pixels: &[T]);
+}
+
+impl<T, F> PixelSink<T> for F
+where
+    F: FnMut(&[T]),
+{
+    #[inline]
+    fn push_chunk(&mut self, pixels: &[T]) {
+        self(pixels)
+    }
+}
+
+/// Decode output that fills two buffers in turn and hands each full buffer to a [`PixelSink`].
+///
+/// The sink has to be done with a chunk when [`PixelSink::push_chunk`] returns. To keep decoding
+/// while a chunk is still being transferred, use [`DmaChunkedDecodeOutput`] instead.
+///
+/// Call [`finish`](Self::finish) after decoding to push out the last, partially filled chunk. To
+/// keep access to the output, pass it to the decoder by mutable reference.
+pub struct ChunkedDecodeOutput<'a, C: ColorFormat, S> {
+    buffers: [&'a mut [C::OutputElement]; 2],
+    active: usize,
+    filled: usize,
+    sink: S,
+    output_idx: usize,
+}
+
+impl<'a, C, S> ChunkedDecodeOutput<'a, C, S>
+where
+    C: ColorFormat,
+    S: PixelSink<C::OutputElement>,
+{
+    /// # Panics
+    ///
+    /// Panics if either buffer is empty.
+    pub fn new(
+        front: &'a mut [C::OutputElement],
+        back: &'a mut [C::OutputElement],
+        sink: S,
+    ) -> Self {
+        assert!(
+            !front.is_empty() && !back.is_empty(),
+            "chunk buffers must not be empty"
+        );
+
+        Self {
+            buffers: [front, back],
+            active: 0,
+            filled: 0,
+            sink,
+            output_idx: 0,
+        }
+    }
+
+    /// Pushes out the remaining pixels and returns the sink.
+    pub fn finish(mut self) -> S {
+        self.flush();
+        self.sink
+    }
+
+    /// Pushes out the pixels decoded so far, if any.
+    pub fn flush(&mut self) {
+        if self.filled > 0 {
+            self.sink
+                .push_chunk(&self.buffers[self.active][..self.filled]);
+            self.active ^= 1;
+            self.filled = 0;
+        }
+    }
+
+    #[inline]
+    fn fill(&mut self, color: C::OutputElement, count: usize) {
+        let mut remaining = count;
+        while remaining > 0 {
+            let buffer = &mut self.buffers[self.active];
+            let n = remaining.min(buffer.len() - self.filled);
+            buffer[self.filled..self.filled + n].fill(color.clone());
+            self.filled += n;
+            remaining -= n;
+
+            if self.filled == buffer.len() {
+                self.flush();
+            }
+        }
+    }
+}
+
+impl<C, S> InfallibleDecodeOutput for ChunkedDecodeOutput<'_, C, S>
+where
+    C: ColorFormat,
+    S: PixelSink<C::OutputElement>,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.fill(C::to_output::<B>(color), 1);
+        self.output_idx += 1;
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        self.fill(C::to_output::<B>(color), count);
+        self.output_idx += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
+
+/// Receiver of decoded pixels in chunks that transfers them in the background, e.g. via DMA, see
+/// [`DmaChunkedDecodeOutput`].
+///
+/// The sink owns a buffer from [`start`](Self::start) until it hands it back from
+/// [`wait`](Self::wait), which is why the buffers are `'static`: DMA keeps reading from them after
+/// the call returned.
+pub trait DmaPixelSink<T: 'static> {
+    /// Starts transferring the first `len` pixels of `buffer`. Only the last chunk of an image is
+    /// shorter than the buffer.
+    ///
+    /// Never called while a transfer is in progress: [`wait`](Self::wait) is called in between.
+    fn start(&mut self, buffer: &'static mut [T], len: usize);
+
+    /// Waits for the transfer started last to complete, and hands back its buffer.
+    fn wait(&mut self) -> &'static mut [T];
+}
+
+/// Decode output for double-buffered DMA: it fills one buffer while a [`DmaPixelSink`] transfers
+/// the other one.
+///
+/// Once a buffer is full, the output waits for the transfer of the other buffer to complete,
+/// starts transferring the full one, and continues with the other one. Decoding and transferring
+/// overlap this way, as long as decoding a chunk takes about as long as transferring one.
+///
+/// Call [`finish`](Self::finish) after decoding to transfer the last, partially filled chunk and
+/// get the buffers back. To keep access to the output, pass it to the decoder by mutable
+/// reference.
+pub struct DmaChunkedDecodeOutput<C: ColorFormat, S>
+where
+    C::OutputElement: 'static,
+{
+    /// The buffer being filled.
+    buffer: &'static mut [C::OutputElement],
+    /// The other buffer, `None` while the sink owns it.
+    spare: Option<&'static mut [C::OutputElement]>,
+    filled: usize,
+    sink: S,
+    output_idx: usize,
+}
+
+impl<C, S> DmaChunkedDecodeOutput<C, S>
+where
+    C: ColorFormat,
+    C::OutputElement: 'static,
+    S: DmaPixelSink<C::OutputElement>,
+{
+    /// # Panics
+    ///
+    /// Panics if either buffer is empty.
+    pub fn new(
+        front: &'static mut [C::OutputElement],
+        back: &'static mut [C::OutputElement],
+        sink: S,
+    ) -> Self {
+        assert!(
+            !front.is_empty() && !back.is_empty(),
+            "chunk buffers must not be empty"
+        );
+
+        Self {
+            buffer: front,
+            spare: Some(back),
+            filled: 0,
+            sink,
+            output_idx: 0,
+        }
+    }
+
+    /// Transfers the remaining pixels, waits for all transfers to complete, and returns the sink
+    /// and both buffers, e.g. to decode the next image.
+    pub fn finish(mut self) -> (S, [&'static mut [C::OutputElement]; 2]) {
+        self.flush();
+        let other = match self.spare.take() {
+            Some(spare) => spare,
+            None => self.sink.wait(),
+        };
+        (self.sink, [self.buffer, other])
+    }
+
+    /// Starts transferring the pixels decoded so far, if any.
+    pub fn flush(&mut self) {
+        if self.filled > 0 {
+            let next = match self.spare.take() {
+                Some(spare) => spare,
+                None => self.sink.wait(),
+            };
+            let full = mem::replace(&mut self.buffer, next);
+            self.sink.start(full, self.filled);
+            self.filled = 0;
+        }
+    }
+
+    #[inline]
+    fn fill(&mut self, color: C::OutputElement, count: usize) {
+        let mut remaining = count;
+        while remaining > 0 {
+            let n = remaining.min(self.buffer.len() - self.filled);
+            self.buffer[self.filled..self.filled + n].fill(color.clone());
+            self.filled += n;
+            remaining -= n;
+
+            if self.filled == self.buffer.len() {
+                self.flush();
+            }
+        }
+    }
+}
+
+impl<C, S> InfallibleDecodeOutput for DmaChunkedDecodeOutput<C, S>
+where
+    C: ColorFormat,
+    C::OutputElement: 'static,
+    S: DmaPixelSink<C::OutputElement>,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.fill(C::to_output::<B>(color), 1);
+        self.output_idx += 1;
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        self.fill(C::to_output::<B>(color), count);
+        self.output_idx += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
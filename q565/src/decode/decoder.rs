@@ -0,0 +1,192 @@
+#[cfg(not(feature = "forbid-unsafe"))]
+use super::UninitSliceDecodeOutput;
+#[cfg(feature = "alloc")]
+use super::VecDecodeOutput;
+use super::{ColorFormat, DecodeError, InfallibleDecodeOutput, Q565DecodeContext, Q565Ref};
+use crate::byteorder::{DefaultOrder, Endianness};
+use crate::{HeaderInfo, Rgb565};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(not(feature = "forbid-unsafe"))]
+use core::mem::MaybeUninit;
+use core::{fmt, marker::PhantomData};
+
+error_enum! {
+    #[non_exhaustive]
+    pub enum DecoderError {
+        /// The image has more pixels than the decoder allows.
+        TooLarge = 1,
+        /// The data continues after the end of the image.
+        TrailingData = 2,
+        /// The image is invalid.
+        Decode { source: DecodeError } = 3,
+    }
+}
+
+impl From<DecodeError> for DecoderError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// Decoder with all options in one place, for applications that don't need the low-level
+/// functions of [`Q565DecodeContext`].
+///
+/// The byte order `B` and color format `C` of the output are type parameters, set with
+/// [`byte_order`](Self::byte_order) and [`color_format`](Self::color_format), so the decode calls
+/// stay as fast as the low-level ones:
+///
+/// ```
+/// use q565::{byteorder::BigEndian, decode::Decoder, encode::Q565EncodeContext, Rgb888};
+///
+/// let mut encoded = Vec::new();
+/// Q565EncodeContext::encode_to_vec(2, 1, &[0xF800, 0x001F], &mut encoded).unwrap();
+///
+/// let decoder = Decoder::new()
+///     .byte_order::<BigEndian>()
+///     .color_format::<Rgb888>()
+///     .limit_pixels(320 * 240)
+///     .strict(true);
+/// let (header, pixels) = decoder.decode_to_vec(&encoded).unwrap();
+/// assert_eq!((header.width, header.height), (2, 1));
+/// assert_eq!(pixels, [[0xFF, 0, 0], [0, 0, 0xFF]]);
+/// ```
+///
+/// By default, the pixels are RGB565 in the [`DefaultOrder`] byte order, i.e. [`NativeEndian`]
+/// unless the `default-be` feature is enabled, any number of pixels is allowed, and the decoder
+/// isn't strict.
+///
+/// [`NativeEndian`]: crate::byteorder::NativeEndian
+pub struct Decoder<B: Endianness = DefaultOrder, C: ColorFormat = Rgb565> {
+    max_pixels: usize,
+    strict: bool,
+    _format: PhantomData<fn() -> (B, C)>,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            max_pixels: usize::MAX,
+            strict: false,
+            _format: PhantomData,
+        }
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Endianness, C: ColorFormat> Decoder<B, C> {
+    /// Sets the byte order of the decoded pixels, see [`byteorder`](crate::byteorder).
+    pub const fn byte_order<B2: Endianness>(self) -> Decoder<B2, C> {
+        Decoder {
+            max_pixels: self.max_pixels,
+            strict: self.strict,
+            _format: PhantomData,
+        }
+    }
+
+    /// Sets the color format of the decoded pixels, e.g. [`Rgb888`](crate::Rgb888).
+    pub const fn color_format<C2: ColorFormat>(self) -> Decoder<B, C2> {
+        Decoder {
+            max_pixels: self.max_pixels,
+            strict: self.strict,
+            _format: PhantomData,
+        }
+    }
+
+    /// Rejects images with more than `max_pixels` pixels with [`DecoderError::TooLarge`], based on
+    /// the header, before anything is decoded or allocated.
+    pub const fn limit_pixels(mut self, max_pixels: usize) -> Self {
+        self.max_pixels = max_pixels;
+        self
+    }
+
+    /// With `strict` set, the whole image is validated before anything is decoded, like by
+    /// [`Q565Ref::new`]: data after the end of the image is rejected with
+    /// [`DecoderError::TrailingData`], and nothing is written to the output if the image is
+    /// invalid. Without it, an invalid image is only rejected once the decoder gets to the invalid
+    /// op.
+    ///
+    /// That takes an extra pass over the data, so it's meant for images from untrusted sources.
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Parses and checks the header, e.g. to size the output.
+    pub fn header(&self, data: &[u8]) -> Result<HeaderInfo, DecoderError> {
+        let (header, _) = Q565DecodeContext::decode_header(data)?;
+        ensure!(
+            header
+                .pixel_count()
+                .is_some_and(|pixel_count| pixel_count <= self.max_pixels),
+            DecoderError::TooLarge
+        );
+
+        if self.strict {
+            let image = Q565Ref::new(data)?;
+            ensure!(image.data().len() == data.len(), DecoderError::TrailingData);
+        }
+        Ok(header)
+    }
+
+    /// Decodes an image into any output, returning the header and the number of pixels written.
+    ///
+    /// The output has its own color format, so only the byte order of the decoder applies.
+    pub fn decode(
+        &self,
+        data: &[u8],
+        output: impl InfallibleDecodeOutput,
+    ) -> Result<(HeaderInfo, usize), DecoderError> {
+        self.header(data)?;
+        Ok(Q565DecodeContext::decode::<B>(data, output)?)
+    }
+
+    /// Decodes an image into a possibly uninitialized buffer, see [`UninitSliceDecodeOutput`].
+    ///
+    /// Returns the header and the decoded pixels, which borrow from `output`.
+    #[cfg(not(feature = "forbid-unsafe"))]
+    pub fn decode_to_uninit<'a>(
+        &self,
+        data: &[u8],
+        output: &'a mut [MaybeUninit<C::OutputElement>],
+    ) -> Result<(HeaderInfo, &'a mut [C::OutputElement]), DecoderError> {
+        let mut output = UninitSliceDecodeOutput::<C>::new(output);
+        let (header, _) = self.decode(data, &mut output)?;
+        Ok((header, output.initialized()))
+    }
+
+    /// Decodes an image into a newly allocated vector.
+    #[cfg(feature = "alloc")]
+    pub fn decode_to_vec(
+        &self,
+        data: &[u8],
+    ) -> Result<(HeaderInfo, Vec<C::OutputElement>), DecoderError> {
+        let mut pixels = Vec::new();
+        let (header, _) = self.decode(data, VecDecodeOutput::<C>::new(&mut pixels))?;
+        Ok((header, pixels))
+    }
+}
+
+impl<B: Endianness, C: ColorFormat> Clone for Decoder<B, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<B: Endianness, C: ColorFormat> Copy for Decoder<B, C> {}
+
+impl<B: Endianness, C: ColorFormat> fmt::Debug for Decoder<B, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decoder")
+            .field("byte_order", &core::any::type_name::<B>())
+            .field("color_format", &core::any::type_name::<C>())
+            .field("max_pixels", &self.max_pixels)
+            .field("strict", &self.strict)
+            .finish()
+    }
+}
@@ -0,0 +1,180 @@
+use super::{ColorFormat, DecodeError, InfallibleDecodeOutput, Q565DecodeContext};
+use crate::byteorder::Endianness;
+use crate::{
+    utils::{decode_565, encode_rgb565_unchecked},
+    HeaderInfo,
+};
+use alloc::{vec, vec::Vec};
+
+/// Scaling factor for [`DownscaleDecodeOutput`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownscaleFactor {
+    /// Half the width and height.
+    Half,
+    /// A quarter of the width and height.
+    Quarter,
+}
+
+impl DownscaleFactor {
+    #[inline]
+    pub const fn divisor(self) -> u16 {
+        match self {
+            DownscaleFactor::Half => 2,
+            DownscaleFactor::Quarter => 4,
+        }
+    }
+
+    /// Returns the size of the downscaled image. Partial blocks at the right and bottom edges are
+    /// kept, so odd sizes are rounded up.
+    #[inline]
+    pub const fn scaled_size(self, width: u16, height: u16) -> (u16, u16) {
+        let divisor = self.divisor();
+        (width.div_ceil(divisor), height.div_ceil(divisor))
+    }
+}
+
+/// Decode output that downscales the image while decoding by averaging each block of
+/// `factor x factor` pixels into one output pixel.
+///
+/// Only one row of block sums is kept in memory, the full-size image is never materialized.
+pub struct DownscaleDecodeOutput<'a, C: ColorFormat> {
+    output: &'a mut Vec<C::OutputElement>,
+    divisor: usize,
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    /// Red, green, and blue sums, plus the pixel count, per output pixel of the current row.
+    sums: Vec<[u32; 4]>,
+    output_idx: usize,
+}
+
+impl<'a, C> DownscaleDecodeOutput<'a, C>
+where
+    C: ColorFormat,
+{
+    /// Creates a new downscaling output. `width` and `height` are the dimensions of the *encoded*
+    /// image.
+    pub fn new(
+        vec: &'a mut Vec<C::OutputElement>,
+        width: u16,
+        height: u16,
+        factor: DownscaleFactor,
+    ) -> Self {
+        let (scaled_width, _) = factor.scaled_size(width, height);
+        Self {
+            output: vec,
+            divisor: usize::from(factor.divisor()),
+            width: usize::from(width),
+            height: usize::from(height),
+            x: 0,
+            y: 0,
+            sums: vec![[0; 4]; usize::from(scaled_width)],
+            output_idx: 0,
+        }
+    }
+
+    #[inline]
+    fn accumulate<B: Endianness>(&mut self, color: u16, count: usize) {
+        let [r, g, b] = decode_565(color).map(u32::from);
+
+        let mut remaining = count;
+        while remaining > 0 && self.y < self.height {
+            let n = remaining.min(self.width - self.x);
+            for x in self.x..self.x + n {
+                let sum = &mut self.sums[x / self.divisor];
+                sum[0] += r;
+                sum[1] += g;
+                sum[2] += b;
+                sum[3] += 1;
+            }
+            remaining -= n;
+
+            self.x += n;
+            if self.x == self.width {
+                self.x = 0;
+                self.y += 1;
+                if self.y.is_multiple_of(self.divisor) || self.y == self.height {
+                    self.flush_row::<B>();
+                }
+            }
+        }
+    }
+
+    fn flush_row<B: Endianness>(&mut self) {
+        for sum in &mut self.sums {
+            let [r, g, b, count] = *sum;
+            let average = [r, g, b].map(|c| ((c + count / 2) / count.max(1)) as u8);
+            self.output
+                .push(C::to_output::<B>(encode_rgb565_unchecked(average)));
+            *sum = [0; 4];
+        }
+    }
+}
+
+impl<C> InfallibleDecodeOutput for DownscaleDecodeOutput<'_, C>
+where
+    C: ColorFormat,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.accumulate::<B>(color, 1);
+        self.output_idx += 1;
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        self.accumulate::<B>(color, count);
+        self.output_idx += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+
+    #[inline]
+    fn reserve(&mut self, pixel_count: usize) {
+        // one output pixel per block, of the pixels the data can actually produce
+        let blocks = self.sums.len() * self.height.div_ceil(self.divisor);
+        self.output.reserve(
+            pixel_count
+                .div_ceil(self.divisor * self.divisor)
+                .min(blocks),
+        );
+    }
+}
+
+impl Q565DecodeContext {
+    /// Decodes a Q565 image into a vector at a reduced resolution, see [`DownscaleDecodeOutput`].
+    ///
+    /// Returns the header info of the *downscaled* image.
+    pub fn decode_downscaled<B, C>(
+        data: &[u8],
+        factor: DownscaleFactor,
+        output: &mut Vec<C::OutputElement>,
+    ) -> Result<HeaderInfo, DecodeError>
+    where
+        B: Endianness,
+        C: ColorFormat,
+    {
+        let (header, _) = Self::decode_header(data)?;
+        let (width, height) = (header.width, header.height);
+        Self::decode::<B>(
+            data,
+            DownscaleDecodeOutput::<C>::new(output, width, height, factor),
+        )?;
+
+        let (width, height) = factor.scaled_size(width, height);
+        Ok(HeaderInfo {
+            width,
+            height,
+            ..header
+        })
+    }
+}
@@ -0,0 +1,239 @@
+use super::{DecodeError, InfallibleDecodeOutput};
+use crate::byteorder::Endianness;
+use core::{convert::Infallible, fmt};
+
+/// Decode output whose writes can fail, e.g. a display driver whose SPI transfer errors out, or a
+/// bounded ring buffer that may be full.
+///
+/// Decoding with
+/// [`decode_fallible_with_state`](super::Q565DecodeContext::decode_fallible_with_state) stops at
+/// the first failed write and returns its error in [`FallibleDecodeError::Output`]. Pixels written
+/// before the failure stay written.
+///
+/// ```
+/// use q565::{
+///     byteorder::{Endianness, NativeEndian},
+///     decode::{FallibleDecodeError, FallibleDecodeOutput, Q565DecodeContext},
+///     encode::Q565EncodeContext,
+/// };
+///
+/// /// A display that accepts only so many pixels before its transfer fails.
+/// struct Display {
+///     written: usize,
+///     limit: usize,
+/// }
+///
+/// #[derive(Debug, PartialEq)]
+/// struct TransferFailed;
+///
+/// impl FallibleDecodeOutput for Display {
+///     type Error = TransferFailed;
+///
+///     fn write_pixel<B: Endianness>(&mut self, color: u16) -> Result<(), TransferFailed> {
+///         self.write_many_pixels::<B>(color, 1)
+///     }
+///
+///     fn write_many_pixels<B: Endianness>(
+///         &mut self,
+///         _color: u16,
+///         count: usize,
+///     ) -> Result<(), TransferFailed> {
+///         if self.written + count > self.limit {
+///             return Err(TransferFailed);
+///         }
+///         self.written += count;
+///         Ok(())
+///     }
+///
+///     fn max_len(&self) -> Option<usize> {
+///         None
+///     }
+///
+///     fn current_output_position(&self) -> usize {
+///         self.written
+///     }
+/// }
+///
+/// let mut image = Vec::new();
+/// Q565EncodeContext::encode_to_vec(4, 4, &[0x1234; 16], &mut image);
+///
+/// let mut display = Display { written: 0, limit: 8 };
+/// let result = Q565DecodeContext::decode_fallible::<NativeEndian, _>(&image, &mut display);
+/// assert!(matches!(
+///     result,
+///     Err(FallibleDecodeError::Output { source: TransferFailed })
+/// ));
+/// ```
+pub trait FallibleDecodeOutput {
+    type Error;
+
+    fn write_pixel<B: Endianness>(&mut self, color: u16) -> Result<(), Self::Error>;
+    fn write_many_pixels<B: Endianness>(
+        &mut self,
+        color: u16,
+        count: usize,
+    ) -> Result<(), Self::Error>;
+
+    /// Returns the maximum number of pixels that can be written to the output buffer.
+    ///
+    /// `None` if the output buffer is unbounded, or its bound is only known when writing.
+    fn max_len(&self) -> Option<usize>;
+    fn current_output_position(&self) -> usize;
+
+    /// See [`InfallibleDecodeOutput::reserve`].
+    #[inline]
+    fn reserve(&mut self, pixel_count: usize) {
+        let _ = pixel_count;
+    }
+}
+
+impl<O> FallibleDecodeOutput for &mut O
+where
+    O: FallibleDecodeOutput + ?Sized,
+{
+    type Error = O::Error;
+
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) -> Result<(), Self::Error> {
+        (**self).write_pixel::<B>(color)
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(
+        &mut self,
+        color: u16,
+        count: usize,
+    ) -> Result<(), Self::Error> {
+        (**self).write_many_pixels::<B>(color, count)
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        (**self).max_len()
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        (**self).current_output_position()
+    }
+
+    #[inline]
+    fn reserve(&mut self, pixel_count: usize) {
+        (**self).reserve(pixel_count)
+    }
+}
+
+/// Adapts an [`InfallibleDecodeOutput`], so that the checked decoders share one decode loop. The
+/// [`Infallible`] results optimize away.
+pub(crate) struct AsFallible<O>(pub O);
+
+impl<O> FallibleDecodeOutput for AsFallible<O>
+where
+    O: InfallibleDecodeOutput,
+{
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) -> Result<(), Infallible> {
+        self.0.write_pixel::<B>(color);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn write_many_pixels<B: Endianness>(
+        &mut self,
+        color: u16,
+        count: usize,
+    ) -> Result<(), Infallible> {
+        self.0.write_many_pixels::<B>(color, count);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn max_len(&self) -> Option<usize> {
+        self.0.max_len()
+    }
+
+    #[inline(always)]
+    fn current_output_position(&self) -> usize {
+        self.0.current_output_position()
+    }
+
+    #[inline(always)]
+    fn reserve(&mut self, pixel_count: usize) {
+        self.0.reserve(pixel_count)
+    }
+}
+
+/// Error of decoding into a [`FallibleDecodeOutput`].
+#[derive(Debug)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum FallibleDecodeError<E> {
+    /// The image is invalid, or doesn't fit the output.
+    Decode { source: DecodeError } = 1,
+    /// Writing to the output failed.
+    Output { source: E } = 2,
+}
+
+impl<E> FallibleDecodeError<E> {
+    /// Returns the [error code](crate#error-codes) of the variant.
+    ///
+    /// Both variants have fields, so there's no `from_code`; report the code of the wrapped error
+    /// alongside if needed.
+    pub const fn as_code(&self) -> u8 {
+        match self {
+            Self::Decode { .. } => 1,
+            Self::Output { .. } => 2,
+        }
+    }
+
+    /// Returns the [`Display`](core::fmt::Display) message of the variant with the given
+    /// [error code](crate#error-codes).
+    pub const fn message_for_code(code: u8) -> Option<&'static str> {
+        match code {
+            1 => Some("The image is invalid, or doesn't fit the output."),
+            2 => Some("Writing to the output failed."),
+            _ => None,
+        }
+    }
+}
+
+impl<E> fmt::Display for FallibleDecodeError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match Self::message_for_code(self.as_code()) {
+            Some(message) => f.write_str(message),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for FallibleDecodeError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode { source } => Some(source),
+            Self::Output { source } => Some(source),
+        }
+    }
+}
+
+impl<E> From<DecodeError> for FallibleDecodeError<E> {
+    #[inline]
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+impl From<FallibleDecodeError<Infallible>> for DecodeError {
+    #[inline]
+    fn from(error: FallibleDecodeError<Infallible>) -> Self {
+        match error {
+            FallibleDecodeError::Decode { source } => source,
+            FallibleDecodeError::Output { source } => match source {},
+        }
+    }
+}
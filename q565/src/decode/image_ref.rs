@@ -0,0 +1,147 @@
+use super::{DecodeError, InfallibleDecodeOutput, PixelIter, Q565DecodeContext};
+use crate::{
+    byteorder::Endianness,
+    stream::{Op, OpReader},
+    ColorFormat, HeaderInfo, Rgb565,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::iter::Map;
+
+/// A borrowed Q565 image that was validated once, so it can be passed around and decoded without
+/// checking it again.
+///
+/// Validation checks the header, that the ops produce exactly `width * height` pixels up to the
+/// end marker (or, for [raw images](crate#raw-images), that all pixels are there), and that all
+/// indices fit the image's color array. The accessors are lazy: nothing is decoded until the
+/// pixels are asked for.
+///
+/// Bytes after the end of the image are not part of it, see [`data`](Self::data).
+#[derive(Debug, Clone)]
+pub struct Q565Ref<'a> {
+    data: &'a [u8],
+    header: HeaderInfo,
+    header_len: usize,
+}
+
+impl<'a> Q565Ref<'a> {
+    /// Validates the image at the start of `data`.
+    pub fn new(data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (header, body) = Q565DecodeContext::decode_header(data)?;
+        let header_len = data.len() - body.len();
+        let pixel_count = header
+            .pixel_count()
+            .ok_or(DecodeError::DimensionsTooLarge)?;
+
+        let body_len = if header.raw {
+            ensure!(body.len() / 2 >= pixel_count, DecodeError::UnexpectedEof);
+            2 * pixel_count
+        } else {
+            let entries = header.color_array_size.entries();
+            let mut remaining = pixel_count;
+            let mut reader = OpReader::new(body);
+            let mut ended = false;
+            for (_, op) in reader.by_ref() {
+                let index = match op {
+                    Op::Index(byte) => Some(byte & 0b0011_1111),
+                    Op::DiffIndexed(_, second_byte) => Some(second_byte & 0b0011_1111),
+                    Op::End => {
+                        ended = true;
+                        break;
+                    }
+                    _ => None,
+                };
+                ensure!(
+                    index.is_none_or(|index| usize::from(index) < entries),
+                    DecodeError::ColorArrayTooSmall
+                );
+                remaining = remaining
+                    .checked_sub(op.pixel_count())
+                    .ok_or(DecodeError::TooManyPixels)?;
+            }
+            ensure!(ended, DecodeError::UnexpectedEof);
+            ensure!(remaining == 0, DecodeError::MissingData);
+            reader.offset()
+        };
+
+        Ok(Self {
+            data: &data[..header_len + body_len],
+            header,
+            header_len,
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &HeaderInfo {
+        &self.header
+    }
+
+    /// Number of pixels of the image.
+    #[inline]
+    pub fn pixel_count(&self) -> usize {
+        // checked by `new`
+        self.header.pixel_count().unwrap_or_default()
+    }
+
+    /// The whole image, from the header up to and including the end marker.
+    #[inline]
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The data after the header: the ops, or the pixels of a raw image.
+    #[inline]
+    pub fn body(&self) -> &'a [u8] {
+        &self.data[self.header_len..]
+    }
+
+    /// Iterates over the ops, `None` for a raw image.
+    #[inline]
+    pub fn ops(&self) -> Option<OpReader<'a>> {
+        (!self.header.raw).then(|| OpReader::new(self.body()))
+    }
+
+    /// Iterates over the RGB565 pixels, in the byte order `B`, decoding them one by one.
+    pub fn pixels<B: Endianness>(&self) -> Map<PixelIter<'a>, fn(u16) -> u16> {
+        PixelIter::from_validated(self.header.clone(), self.body()).map(Rgb565::to_output::<B>)
+    }
+
+    /// Decodes the image into `output`, returning the number of pixels written.
+    ///
+    /// Fails only with [`DecodeError::OutputTooSmall`].
+    pub fn decode<B: Endianness>(
+        &self,
+        output: impl InfallibleDecodeOutput,
+    ) -> Result<usize, DecodeError> {
+        Q565DecodeContext::decode::<B>(self.data, output).map(|(_, written)| written)
+    }
+
+    /// Decodes the image into a newly allocated vector.
+    #[cfg(feature = "alloc")]
+    pub fn decode_to_vec<B, C>(&self) -> Vec<C::OutputElement>
+    where
+        B: Endianness,
+        C: ColorFormat,
+    {
+        // an unbounded output can't be too small
+        Q565DecodeContext::decode_to_vec::<B, C>(self.data)
+            .map(|(_, pixels)| pixels)
+            .unwrap_or_default()
+    }
+}
+
+impl AsRef<[u8]> for Q565Ref<'_> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.data
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Q565Ref<'a> {
+    type Error = DecodeError;
+
+    #[inline]
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        Self::new(data)
+    }
+}
@@ -0,0 +1,131 @@
+use super::{DecodeError, Q565DecodeContext, Q565Ref};
+use crate::{
+    stream::{Op, OpReader},
+    ColorArraySize, HeaderInfo,
+};
+use core::iter::FusedIterator;
+
+/// Iterator over the pixels of a Q565 image, as RGB565 values.
+///
+/// The image is validated up front, so iterating can't fail, and the iterator always yields
+/// exactly `width * height` pixels. It implements [`ExactSizeIterator`], so collecting it
+/// allocates once.
+///
+/// Decoding pixel by pixel is slower than the regular decoder writing into an output; prefer
+/// [`Q565DecodeContext::decode`] when that fits.
+#[derive(Debug, Clone)]
+pub struct PixelIter<'a> {
+    header: HeaderInfo,
+    source: Source<'a>,
+    color: u16,
+    /// How many more times `color` is yielded before the next op is read.
+    repeat: usize,
+    remaining: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Source<'a> {
+    Raw(&'a [u8]),
+    Ops(OpReader<'a>, State),
+}
+
+/// Decoder state for each color array size.
+#[derive(Debug, Clone)]
+enum State {
+    Entries16(Q565DecodeContext<16>),
+    Entries32(Q565DecodeContext<32>),
+    Entries64(Q565DecodeContext<64>),
+}
+
+impl State {
+    #[inline]
+    fn apply_op(&mut self, op: Op) -> (u16, usize) {
+        match self {
+            State::Entries16(state) => state.apply_op(op),
+            State::Entries32(state) => state.apply_op(op),
+            State::Entries64(state) => state.apply_op(op),
+        }
+    }
+}
+
+impl<'a> PixelIter<'a> {
+    /// Validates the image, see [`Q565Ref`].
+    pub fn new(data: &'a [u8]) -> Result<Self, DecodeError> {
+        Q565Ref::new(data).map(Self::from)
+    }
+
+    /// Creates an iterator over an image whose body (the data after the header) was validated.
+    pub(crate) fn from_validated(header: HeaderInfo, body: &'a [u8]) -> Self {
+        let pixel_count = header.pixel_count().unwrap_or_default();
+        let source = if header.raw {
+            Source::Raw(body)
+        } else {
+            let state = match header.color_array_size {
+                ColorArraySize::NoArray | ColorArraySize::Entries16 => {
+                    State::Entries16(Q565DecodeContext::new_sized())
+                }
+                ColorArraySize::Entries32 => State::Entries32(Q565DecodeContext::new_sized()),
+                ColorArraySize::Entries64 => State::Entries64(Q565DecodeContext::new_sized()),
+            };
+            Source::Ops(OpReader::new(body), state)
+        };
+
+        Self {
+            header,
+            source,
+            color: 0,
+            repeat: 0,
+            remaining: pixel_count,
+        }
+    }
+
+    pub fn header(&self) -> &HeaderInfo {
+        &self.header
+    }
+}
+
+impl Iterator for PixelIter<'_> {
+    type Item = u16;
+
+    #[inline]
+    fn next(&mut self) -> Option<u16> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        match &mut self.source {
+            Source::Raw(data) => {
+                let (pixel, rest) = data.split_first_chunk::<2>()?;
+                *data = rest;
+                Some(u16::from_le_bytes(*pixel))
+            }
+            Source::Ops(ops, state) => {
+                if self.repeat == 0 {
+                    // validated in `new`, so there's always a next op producing pixels
+                    let (_, op) = ops.next()?;
+                    (self.color, self.repeat) = state.apply_op(op);
+                }
+                self.repeat -= 1;
+                Some(self.color)
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> From<Q565Ref<'a>> for PixelIter<'a> {
+    #[inline]
+    fn from(image: Q565Ref<'a>) -> Self {
+        let body = image.body();
+        Self::from_validated(image.header().clone(), body)
+    }
+}
+
+impl ExactSizeIterator for PixelIter<'_> {}
+
+impl FusedIterator for PixelIter<'_> {}
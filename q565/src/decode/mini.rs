@@ -0,0 +1,117 @@
+use super::{
+    decode_raw,
+    fallible::AsFallible,
+    ops::{direct_bigger_diff, direct_small_diff},
+    reserved_pixels, DecodeError, InfallibleDecodeOutput, Q565DecodeContext,
+};
+use crate::byteorder::Endianness;
+use crate::{ColorArraySize, HeaderInfo};
+
+/// Decoder for images using the [no-array profile](crate#no-color-array), with the previous pixel
+/// as its only state.
+///
+/// Images using any other profile are rejected with [`DecodeError::ColorArrayTooSmall`], as are
+/// ops referencing the color array. [Raw images](crate#raw-images) are accepted as well.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MiniDecoder {
+    pub prev: u16,
+}
+
+impl MiniDecoder {
+    pub const fn new() -> Self {
+        Self { prev: 0 }
+    }
+
+    pub fn decode<B>(
+        data: &[u8],
+        output: impl InfallibleDecodeOutput,
+    ) -> Result<(HeaderInfo, usize), DecodeError>
+    where
+        B: Endianness,
+    {
+        Self::new().decode_with_state::<B>(data, output)
+    }
+
+    pub fn decode_with_state<B>(
+        &mut self,
+        data: &[u8],
+        mut output: impl InfallibleDecodeOutput,
+    ) -> Result<(HeaderInfo, usize), DecodeError>
+    where
+        B: Endianness,
+    {
+        panic_free!({
+            let (header, data) = Q565DecodeContext::decode_header(data)?;
+            ensure!(
+                header.raw || header.color_array_size == ColorArraySize::NoArray,
+                DecodeError::ColorArrayTooSmall
+            );
+            let expected_size = header
+                .pixel_count()
+                .ok_or(DecodeError::DimensionsTooLarge)?;
+
+            ensure!(
+                output
+                    .max_len()
+                    .map(|max_len| max_len >= expected_size)
+                    .unwrap_or(true),
+                DecodeError::OutputTooSmall
+            );
+            ensure!(
+                !header.raw || data.len() / 2 >= expected_size,
+                DecodeError::UnexpectedEof
+            );
+            output.reserve(reserved_pixels(header.raw, data, expected_size));
+
+            if header.raw {
+                let Ok(()) = decode_raw::<B, _>(data, expected_size, &mut AsFallible(&mut output));
+            } else {
+                self.decode_ops::<B>(data, expected_size, &mut output)?;
+            }
+            let pixels_written = output.current_output_position();
+
+            ensure!(pixels_written == expected_size, DecodeError::MissingData);
+
+            Ok((header, pixels_written))
+        })
+    }
+
+    fn decode_ops<B>(
+        &mut self,
+        data: &[u8],
+        mut remaining: usize,
+        output: &mut impl InfallibleDecodeOutput,
+    ) -> Result<(), DecodeError>
+    where
+        B: Endianness,
+    {
+        let mut data = data.iter().copied();
+        let mut next = || data.next().ok_or(DecodeError::UnexpectedEof);
+        loop {
+            let byte = next()?;
+            ensure!(remaining != 0 || byte == 0xFF, DecodeError::TooManyPixels);
+
+            let pixel = match byte >> 6 {
+                0b01 => direct_small_diff(self.prev, byte),
+                0b10 if byte & 0b0010_0000 == 0 => direct_bigger_diff(self.prev, byte, next()?),
+                0b11 if byte == 0xFE => u16::from_le_bytes([next()?, next()?]),
+                0b11 if byte != 0xFF => {
+                    let count = usize::from(byte & 0b0011_1111) + 1;
+                    ensure!(count <= remaining, DecodeError::TooManyPixels);
+                    remaining -= count;
+                    output.write_many_pixels::<B>(self.prev, count);
+                    continue;
+                }
+                0b11 => break,
+                // Q565_OP_INDEX, Q565_OP_DIFF_INDEXED
+                _ => return Err(DecodeError::ColorArrayTooSmall),
+            };
+
+            remaining -= 1;
+            self.prev = pixel;
+            output.write_pixel::<B>(pixel);
+        }
+
+        Ok(())
+    }
+}
@@ -2,11 +2,15 @@ use crate::utils::{decode_565, encode_rgb565_unchecked, sum_n};
 
 // OP: 0x101
 #[inline(always)]
-pub(crate) const fn indexed_diff(color_array: &[u16; 64], byte: u8, second_byte: u8) -> u16 {
+pub(crate) const fn indexed_diff<const N: usize>(
+    color_array: &[u16; N],
+    byte: u8,
+    second_byte: u8,
+) -> u16 {
     let g_diff = ((byte & 0b0001_1100) >> 2) as i8 - 4;
     let r_diff = (byte & 0b0000_0011) as i8 - 2;
     let b_diff = (second_byte >> 6) as i8 - 2;
-    let index = (second_byte & 0b0011_1111) as usize;
+    let index = (second_byte & 0b0011_1111) as usize & (N - 1);
 
     apply_diff(color_array[index], r_diff, g_diff, b_diff)
 }
@@ -0,0 +1,91 @@
+use super::{ColorFormat, InfallibleDecodeOutput};
+use crate::byteorder::Endianness;
+
+/// Decode output that emits every pixel as a 2x2 block, doubling both the width and the height of
+/// the decoded image.
+///
+/// This is meant for displays that accept half-resolution frames for quick partial refreshes: the
+/// image can be stored at half resolution and scaled up while decoding, without an intermediate
+/// scaling buffer.
+///
+/// The output slice needs to hold `(2 * width) * (2 * height)` elements. Writes that would fall
+/// outside of the slice are dropped.
+pub struct PixelDoublingDecodeOutput<'a, C: ColorFormat> {
+    output: &'a mut [C::OutputElement],
+    width: usize,
+    x: usize,
+    /// Index of the first element of the current (upper) output row.
+    row_start: usize,
+    output_idx: usize,
+}
+
+impl<'a, C> PixelDoublingDecodeOutput<'a, C>
+where
+    C: ColorFormat,
+{
+    /// Creates a new pixel doubling output. `width` is the width of the *encoded* image.
+    #[inline]
+    pub fn new(slice: &'a mut [C::OutputElement], width: u16) -> Self {
+        Self {
+            output: slice,
+            // a zero width is invalid anyway, but must not make `write_many_pixels` spin forever
+            width: usize::from(width).max(1),
+            x: 0,
+            row_start: 0,
+            output_idx: 0,
+        }
+    }
+
+    #[inline]
+    fn fill_block(&mut self, color: C::OutputElement, count: usize) {
+        let stride = self.width * 2;
+        let start = self.row_start + self.x * 2;
+        let end = start + count * 2;
+
+        if let Some(upper) = self.output.get_mut(start..end) {
+            upper.fill(color.clone());
+        }
+        if let Some(lower) = self.output.get_mut(start + stride..end + stride) {
+            lower.fill(color);
+        }
+
+        self.x += count;
+        if self.x == self.width {
+            self.x = 0;
+            self.row_start += stride * 2;
+        }
+    }
+}
+
+impl<C> InfallibleDecodeOutput for PixelDoublingDecodeOutput<'_, C>
+where
+    C: ColorFormat,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.fill_block(C::to_output::<B>(color), 1);
+        self.output_idx += 1;
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        let color = C::to_output::<B>(color);
+        let mut remaining = count;
+        while remaining > 0 {
+            let n = remaining.min(self.width - self.x);
+            self.fill_block(color.clone(), n);
+            remaining -= n;
+        }
+        self.output_idx += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        Some(self.output.len() / 4)
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
@@ -0,0 +1,163 @@
+use super::{ColorFormat, DecodeError, Q565DecodeContext, VecDecodeOutput};
+use crate::byteorder::Endianness;
+use crate::{HeaderInfo, Rgb565};
+use alloc::vec::Vec;
+
+error_enum! {
+    pub enum DecoderPoolError {
+        /// All decoders of the pool are in use.
+        Exhausted = 1,
+        /// The image has more pixels than the pool's buffers hold.
+        TooLarge = 2,
+        /// The image is invalid.
+        Decode { source: DecodeError } = 3,
+    }
+}
+
+impl From<DecodeError> for DecoderPoolError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// Bounded pool of decode contexts with output buffers, e.g. for a server decoding the streams of
+/// many devices at once.
+///
+/// At most `max_decoders` decoders are handed out at a time, each with a buffer for up to
+/// `max_pixels` pixels, so memory use is capped at roughly `max_decoders * max_pixels` output
+/// elements. Buffers are allocated with their full capacity when a decoder is first created and
+/// reused after [`release`](Self::release), so once the pool is warmed up (or created with
+/// [`new_preallocated`](Self::new_preallocated)), decoding doesn't allocate.
+///
+/// The pool itself isn't shared; wrap it in a `Mutex` to acquire decoders from several threads.
+pub struct DecoderPool<C: ColorFormat = Rgb565> {
+    max_decoders: usize,
+    max_pixels: usize,
+    free: Vec<PooledDecoder<C>>,
+    in_use: usize,
+}
+
+impl<C: ColorFormat> DecoderPool<C> {
+    /// Creates an empty pool, which creates decoders on demand.
+    pub fn new(max_decoders: usize, max_pixels: usize) -> Self {
+        Self {
+            max_decoders,
+            max_pixels,
+            free: Vec::with_capacity(max_decoders),
+            in_use: 0,
+        }
+    }
+
+    /// Creates a pool with all decoders and their buffers allocated up front.
+    pub fn new_preallocated(max_decoders: usize, max_pixels: usize) -> Self {
+        let mut pool = Self::new(max_decoders, max_pixels);
+        pool.free
+            .extend((0..max_decoders).map(|_| PooledDecoder::new(max_pixels)));
+        pool
+    }
+
+    /// Takes a decoder from the pool, creating it if needed.
+    pub fn acquire(&mut self) -> Result<PooledDecoder<C>, DecoderPoolError> {
+        ensure!(self.in_use < self.max_decoders, DecoderPoolError::Exhausted);
+
+        let decoder = self
+            .free
+            .pop()
+            .unwrap_or_else(|| PooledDecoder::new(self.max_pixels));
+        self.in_use += 1;
+        Ok(decoder)
+    }
+
+    /// Returns a decoder to the pool, resetting its state.
+    ///
+    /// Decoders of other pools are dropped instead, as their buffers may have a different size.
+    pub fn release(&mut self, mut decoder: PooledDecoder<C>) {
+        if decoder.max_pixels != self.max_pixels || self.in_use == 0 {
+            return;
+        }
+
+        decoder.reset();
+        self.in_use -= 1;
+        self.free.push(decoder);
+    }
+
+    /// Number of decoders currently handed out.
+    pub fn in_use(&self) -> usize {
+        self.in_use
+    }
+
+    /// Number of decoders that can still be acquired.
+    pub fn available(&self) -> usize {
+        self.max_decoders - self.in_use
+    }
+
+    pub fn max_pixels(&self) -> usize {
+        self.max_pixels
+    }
+}
+
+/// Decode context and output buffer taken from a [`DecoderPool`].
+pub struct PooledDecoder<C: ColorFormat = Rgb565> {
+    context: Q565DecodeContext,
+    pixels: Vec<C::OutputElement>,
+    max_pixels: usize,
+}
+
+impl<C: ColorFormat> PooledDecoder<C> {
+    fn new(max_pixels: usize) -> Self {
+        Self {
+            context: Q565DecodeContext::new(),
+            pixels: Vec::with_capacity(max_pixels),
+            max_pixels,
+        }
+    }
+
+    /// Decodes an image with a fresh state, replacing the pixels of the previous one.
+    pub fn decode<B: Endianness>(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(HeaderInfo, &[C::OutputElement]), DecoderPoolError> {
+        self.context = Q565DecodeContext::new();
+        self.decode_frame::<B>(data)
+    }
+
+    /// Decodes the next frame of a [frame sequence](crate#frame-sequences), continuing from the
+    /// state left by the frames decoded before. See
+    /// [`Q565DecodeContext::decode_frame`].
+    pub fn decode_frame<B: Endianness>(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(HeaderInfo, &[C::OutputElement]), DecoderPoolError> {
+        self.pixels.clear();
+        let (header, _) = Q565DecodeContext::decode_header(data)?;
+        ensure!(
+            header
+                .pixel_count()
+                .is_some_and(|pixel_count| pixel_count <= self.max_pixels),
+            DecoderPoolError::TooLarge
+        );
+
+        let result = if header.raw {
+            Q565DecodeContext::decode::<B>(data, VecDecodeOutput::<C>::new(&mut self.pixels))
+        } else {
+            self.context
+                .decode_frame::<B>(data, VecDecodeOutput::<C>::new(&mut self.pixels))
+        };
+        if let Err(error) = result {
+            self.pixels.clear();
+            return Err(error.into());
+        }
+        Ok((header, &self.pixels))
+    }
+
+    /// The pixels of the last decoded image.
+    pub fn pixels(&self) -> &[C::OutputElement] {
+        &self.pixels
+    }
+
+    /// Resets the state, e.g. to start over with a new frame sequence.
+    pub fn reset(&mut self) {
+        self.context = Q565DecodeContext::new();
+        self.pixels.clear();
+    }
+}
@@ -0,0 +1,83 @@
+use super::{ColorFormat, InfallibleDecodeOutput};
+use crate::byteorder::Endianness;
+use crate::Rect;
+
+/// Decode output that writes the image into a rectangular area of a larger framebuffer.
+///
+/// Pixels beyond the rectangle's area, or outside of the framebuffer, are dropped.
+pub struct RectDecodeOutput<'a, C: ColorFormat> {
+    framebuffer: &'a mut [C::OutputElement],
+    stride: usize,
+    rect: Rect,
+    x: usize,
+    y: usize,
+    output_idx: usize,
+}
+
+impl<'a, C> RectDecodeOutput<'a, C>
+where
+    C: ColorFormat,
+{
+    /// Creates a new output writing into `rect` of `framebuffer`, whose rows are `stride` elements
+    /// apart.
+    #[inline]
+    pub fn new(framebuffer: &'a mut [C::OutputElement], stride: usize, rect: Rect) -> Self {
+        Self {
+            framebuffer,
+            stride,
+            rect,
+            x: 0,
+            y: 0,
+            output_idx: 0,
+        }
+    }
+
+    #[inline]
+    fn fill(&mut self, color: C::OutputElement, count: usize) {
+        let width = usize::from(self.rect.width);
+        let mut remaining = count;
+        while remaining > 0 && self.y < usize::from(self.rect.height) {
+            let n = remaining.min(width - self.x);
+            let start = (usize::from(self.rect.y) + self.y) * self.stride
+                + usize::from(self.rect.x)
+                + self.x;
+            if let Some(row) = self.framebuffer.get_mut(start..start + n) {
+                row.fill(color.clone());
+            }
+            remaining -= n;
+
+            self.x += n;
+            if self.x == width {
+                self.x = 0;
+                self.y += 1;
+            }
+        }
+    }
+}
+
+impl<C> InfallibleDecodeOutput for RectDecodeOutput<'_, C>
+where
+    C: ColorFormat,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.fill(C::to_output::<B>(color), 1);
+        self.output_idx += 1;
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        self.fill(C::to_output::<B>(color), count);
+        self.output_idx += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        Some(self.rect.area())
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
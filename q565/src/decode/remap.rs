@@ -0,0 +1,91 @@
+use super::InfallibleDecodeOutput;
+use crate::byteorder::Endianness;
+
+/// Decode output wrapper that replaces some colors with others before passing the pixels on, e.g.
+/// to recolor an icon for the current theme.
+///
+/// `pairs` maps source colors to target colors, both as RGB565. The first pair matching a pixel is
+/// used, and pixels matching none are passed on as they are. The pairs are searched linearly, once
+/// per pixel or run, so keep the list short.
+///
+/// ```
+/// use q565::{
+///     byteorder::NativeEndian,
+///     decode::{Q565DecodeContext, RemapDecodeOutput, VecDecodeOutput},
+///     encode::Q565EncodeContext,
+///     Rgb565,
+/// };
+///
+/// let mut image = Vec::new();
+/// Q565EncodeContext::encode_to_vec(3, 1, &[0xFFFF, 0x0000, 0x001F], &mut image).unwrap();
+///
+/// // white on black to black on white
+/// let theme = [(0xFFFF, 0x0000), (0x0000, 0xFFFF)];
+/// let mut pixels = Vec::new();
+/// Q565DecodeContext::decode::<NativeEndian>(
+///     &image,
+///     RemapDecodeOutput::new(VecDecodeOutput::<Rgb565>::new(&mut pixels), &theme),
+/// )
+/// .unwrap();
+/// assert_eq!(pixels, [0x0000, 0xFFFF, 0x001F]);
+/// ```
+pub struct RemapDecodeOutput<'p, O> {
+    inner: O,
+    pairs: &'p [(u16, u16)],
+}
+
+impl<'p, O> RemapDecodeOutput<'p, O>
+where
+    O: InfallibleDecodeOutput,
+{
+    #[inline]
+    pub fn new(inner: O, pairs: &'p [(u16, u16)]) -> Self {
+        Self { inner, pairs }
+    }
+
+    /// Returns the wrapped output.
+    #[inline]
+    pub fn into_inner(self) -> O {
+        self.inner
+    }
+
+    #[inline]
+    fn remap(&self, color: u16) -> u16 {
+        self.pairs
+            .iter()
+            .find(|&&(from, _)| from == color)
+            .map_or(color, |&(_, to)| to)
+    }
+}
+
+impl<O> InfallibleDecodeOutput for RemapDecodeOutput<'_, O>
+where
+    O: InfallibleDecodeOutput,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        let color = self.remap(color);
+        self.inner.write_pixel::<B>(color);
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        let color = self.remap(color);
+        self.inner.write_many_pixels::<B>(color, count);
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        self.inner.max_len()
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.inner.current_output_position()
+    }
+
+    #[inline]
+    fn reserve(&mut self, pixel_count: usize) {
+        self.inner.reserve(pixel_count)
+    }
+}
@@ -0,0 +1,166 @@
+use super::InfallibleDecodeOutput;
+use crate::byteorder::Endianness;
+
+/// Receiver of the rows that changed since the previous frame, see [`RowDigestDecodeOutput`].
+pub trait ChangedRowSink {
+    /// Called with the index of a row that changed, once it is completely decoded.
+    fn row_changed(&mut self, y: usize);
+}
+
+impl<F> ChangedRowSink for F
+where
+    F: FnMut(usize),
+{
+    #[inline]
+    fn row_changed(&mut self, y: usize) {
+        self(y)
+    }
+}
+
+/// Decode output wrapper that computes a digest of every row and reports the rows whose digest
+/// differs from the previous frame's, so a display driver only needs to push those.
+///
+/// `digests` holds one digest per row and is kept from frame to frame: every completed row is
+/// compared with its entry, and the entry is updated. Initially, fill it with zeros, so that all
+/// rows of the first frame are reported. Rows without an entry are always reported.
+///
+/// The digest is a 32-bit FNV-1a hash of the row's pixels, so a change can be missed, with a
+/// probability of about one in four billion per changed row. Redraw everything now and then if
+/// that matters.
+///
+/// ```
+/// use q565::{
+///     byteorder::BigEndian,
+///     decode::{Q565DecodeContext, RectDecodeOutput, RowDigestDecodeOutput},
+///     encode::Q565EncodeContext,
+///     Rect, Rgb565,
+/// };
+///
+/// let rect = Rect { x: 0, y: 0, width: 4, height: 3 };
+/// let mut framebuffer = [0u16; 4 * 3];
+/// let mut digests = [0u32; 3];
+///
+/// let mut frame = [0x1111; 4 * 3];
+/// for expected in [&[0, 1, 2][..], &[1]] {
+///     let mut image = Vec::new();
+///     Q565EncodeContext::encode_to_vec(4, 3, &frame, &mut image).unwrap();
+///
+///     let mut changed = Vec::new();
+///     let output = RowDigestDecodeOutput::new(
+///         RectDecodeOutput::<Rgb565>::new(&mut framebuffer, 4, rect),
+///         4,
+///         &mut digests,
+///         |y| changed.push(y),
+///     );
+///     Q565DecodeContext::decode::<BigEndian>(&image, output).unwrap();
+///     assert_eq!(changed, expected);
+///
+///     frame[5] = 0x2222;
+/// }
+/// ```
+pub struct RowDigestDecodeOutput<'d, O, S> {
+    inner: O,
+    width: usize,
+    digests: &'d mut [u32],
+    sink: S,
+    /// Digest of the current row so far.
+    digest: u32,
+    x: usize,
+    y: usize,
+}
+
+impl<'d, O, S> RowDigestDecodeOutput<'d, O, S>
+where
+    O: InfallibleDecodeOutput,
+    S: ChangedRowSink,
+{
+    /// Wraps `inner`, tracking the rows of an image `width` pixels wide.
+    #[inline]
+    pub fn new(inner: O, width: u16, digests: &'d mut [u32], sink: S) -> Self {
+        Self {
+            inner,
+            // a zero width is invalid anyway, but must not make `write_many_pixels` spin forever
+            width: usize::from(width).max(1),
+            digests,
+            sink,
+            digest: FNV_OFFSET_BASIS,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    /// Returns the wrapped output and the sink.
+    #[inline]
+    pub fn into_inner(self) -> (O, S) {
+        (self.inner, self.sink)
+    }
+
+    #[inline]
+    fn hash(&mut self, color: u16, count: usize) {
+        let mut remaining = count;
+        while remaining > 0 {
+            let n = remaining.min(self.width - self.x);
+            for _ in 0..n {
+                for byte in color.to_le_bytes() {
+                    self.digest = (self.digest ^ u32::from(byte)).wrapping_mul(FNV_PRIME);
+                }
+            }
+            self.x += n;
+            remaining -= n;
+
+            if self.x == self.width {
+                self.finish_row();
+            }
+        }
+    }
+
+    fn finish_row(&mut self) {
+        let changed = match self.digests.get_mut(self.y) {
+            Some(previous) => core::mem::replace(previous, self.digest) != self.digest,
+            None => true,
+        };
+        if changed {
+            self.sink.row_changed(self.y);
+        }
+
+        self.digest = FNV_OFFSET_BASIS;
+        self.x = 0;
+        self.y += 1;
+    }
+}
+
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+impl<O, S> InfallibleDecodeOutput for RowDigestDecodeOutput<'_, O, S>
+where
+    O: InfallibleDecodeOutput,
+    S: ChangedRowSink,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.inner.write_pixel::<B>(color);
+        self.hash(color, 1);
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        self.inner.write_many_pixels::<B>(color, count);
+        self.hash(color, count);
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        self.inner.max_len()
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.inner.current_output_position()
+    }
+
+    #[inline]
+    fn reserve(&mut self, pixel_count: usize) {
+        self.inner.reserve(pixel_count)
+    }
+}
@@ -0,0 +1,100 @@
+use super::{ColorFormat, InfallibleDecodeOutput};
+use crate::byteorder::Endianness;
+use core::marker::PhantomData;
+
+/// Receiver of decoded pixels as single pixels and spans of one color, e.g. a blitter that can
+/// fill a span in hardware (a repeated RAMWR, 2D DMA, ...).
+pub trait SpanSink<T> {
+    /// Called for a single pixel.
+    fn pixel(&mut self, color: T);
+    /// Called for `count` consecutive pixels of the same color. `count` is at least 2.
+    fn span(&mut self, color: T, count: usize);
+}
+
+/// Decode output that hands the decoded pixels to a [`SpanSink`] instead of writing them to a
+/// buffer.
+///
+/// Runs are passed on as spans. A pixel directly followed by a run of the same color is merged
+/// into the run, so the sink gets the longest spans the stream describes. Since a single pixel may
+/// be merged with the next op, it is held back until then.
+///
+/// Call [`finish`](Self::finish) after decoding to pass on the last pixel. To keep access to the
+/// output, pass it to the decoder by mutable reference.
+pub struct SpanDecodeOutput<C: ColorFormat, S> {
+    sink: S,
+    /// Pixel held back, as decoded and converted.
+    pending: Option<(u16, C::OutputElement)>,
+    output_idx: usize,
+    _color_format: PhantomData<C>,
+}
+
+impl<C, S> SpanDecodeOutput<C, S>
+where
+    C: ColorFormat,
+    S: SpanSink<C::OutputElement>,
+{
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            pending: None,
+            output_idx: 0,
+            _color_format: PhantomData,
+        }
+    }
+
+    /// Passes on the pixel held back, if any, and returns the sink.
+    pub fn finish(mut self) -> S {
+        self.flush();
+        self.sink
+    }
+
+    /// Passes on the pixel held back, if any.
+    pub fn flush(&mut self) {
+        if let Some((_, color)) = self.pending.take() {
+            self.sink.pixel(color);
+        }
+    }
+}
+
+impl<C, S> InfallibleDecodeOutput for SpanDecodeOutput<C, S>
+where
+    C: ColorFormat,
+    S: SpanSink<C::OutputElement>,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.flush();
+        self.pending = Some((color, C::to_output::<B>(color)));
+        self.output_idx += 1;
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        match self.pending.take() {
+            Some((pending, output)) if pending == color => self.sink.span(output, count + 1),
+            pending => {
+                if let Some((_, output)) = pending {
+                    self.sink.pixel(output);
+                }
+
+                let output = C::to_output::<B>(color);
+                if count == 1 {
+                    self.sink.pixel(output);
+                } else {
+                    self.sink.span(output, count);
+                }
+            }
+        }
+        self.output_idx += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
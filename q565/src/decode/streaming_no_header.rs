@@ -1,8 +1,8 @@
+use crate::byteorder::{Endianness, NativeEndian};
 use crate::{
     decode::ops::{direct_bigger_diff, direct_small_diff, indexed_diff},
     utils::hash,
 };
-use byteorder::{ByteOrder, NativeEndian};
 use core::hint::unreachable_unchecked;
 
 #[repr(C)]
@@ -13,6 +13,24 @@ pub struct Q565StreamingDecodeContext {
     arr: [u16; 64],
 }
 
+/// Progress made by a call to
+/// [`streaming_decode_to_slice`](Q565StreamingDecodeContext::streaming_decode_to_slice),
+/// [`streaming_decode_to_slice_with_progress_unchecked`](Q565StreamingDecodeContext::streaming_decode_to_slice_with_progress_unchecked),
+/// or one of their budgeted variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamingDecodeProgress {
+    /// Number of input bytes processed. The next call needs to continue after these.
+    pub bytes_consumed: usize,
+    /// Number of pixels written to the output.
+    pub pixels_written: usize,
+    /// Whether the end marker was reached, so the image is complete. See
+    /// [`is_finished`](Q565StreamingDecodeContext::is_finished).
+    pub finished: bool,
+    /// Whether the call stopped because the output is full, with input left to decode. Only set
+    /// by the checked decoders.
+    pub output_full: bool,
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy)]
 enum Q565StreamingDecodeState {
@@ -20,6 +38,10 @@ enum Q565StreamingDecodeState {
     LumaOrDiffIndexedByte2(u8),
     RawRgb565Byte1,
     RawRgb565Byte2(u8),
+    /// The rest of a run that didn't fit into the output of the checked decoder.
+    Run(u8),
+    /// The end marker was reached.
+    Finished,
 }
 
 impl Default for Q565StreamingDecodeContext {
@@ -37,120 +59,293 @@ impl Q565StreamingDecodeContext {
         }
     }
 
+    /// Returns whether the end marker was reached. Any further input is ignored.
+    #[inline]
+    pub const fn is_finished(&self) -> bool {
+        matches!(self.state, Q565StreamingDecodeState::Finished)
+    }
+
     /// Decodes a Q565 image into a buffer in a streaming fashion, without the header.
     ///
     /// Returns the number of pixels written to the output buffer, if successful. Note that this
     /// doesn't accumulate over multiple calls. You'll need to keep track of the number of pixels
     /// written and pass the correct output slice to the next call.
     ///
+    /// To find out when the image is complete without counting pixels against the header, use
+    /// [`streaming_decode_to_slice_with_progress_unchecked`](Self::streaming_decode_to_slice_with_progress_unchecked)
+    /// instead.
+    ///
+    /// For input that can't be trusted to be a valid image, or an output that may be too small, use
+    /// the checked [`streaming_decode_to_slice`](Self::streaming_decode_to_slice).
+    ///
     /// # Safety
     ///
     /// This function does not do *any* output bounds checks.
     ///
     /// The caller needs to ensure that the input is a valid Q565 image. Any failure to do so
     /// results in undefined behavior.
-    pub unsafe fn streaming_decode_to_slice_unchecked<B: ByteOrder>(
+    pub unsafe fn streaming_decode_to_slice_unchecked<B: Endianness>(
         &mut self,
         input: &[u8],
         output: &mut [u16],
     ) -> usize {
-        let mut output_idx = 0;
-        let mut input_idx = 0;
-
-        macro_rules! next {
-            () => {
-                if let Some(&b) = input.get(input_idx) {
-                    input_idx += 1;
-                    b
-                } else {
-                    return output_idx;
-                }
-            };
-        }
+        panic_free!({
+            self.streaming_decode::<B, false, false>(input, output, usize::MAX)
+                .pixels_written
+        })
+    }
 
-        unsafe fn set_pixel<B: ByteOrder>(
-            state: &mut Q565StreamingDecodeContext,
-            pixel: u16,
-            output: &mut [u16],
-            output_idx: &mut usize,
-        ) {
-            state.prev = pixel;
+    /// Like [`streaming_decode_to_slice_unchecked`](Self::streaming_decode_to_slice_unchecked),
+    /// but also returns the number of input bytes that were processed, and whether the image is
+    /// complete.
+    ///
+    /// All input is processed, unless the end marker is reached. Any bytes after the end marker are
+    /// not part of the image, and are not counted.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`streaming_decode_to_slice_unchecked`](Self::streaming_decode_to_slice_unchecked).
+    pub unsafe fn streaming_decode_to_slice_with_progress_unchecked<B: Endianness>(
+        &mut self,
+        input: &[u8],
+        output: &mut [u16],
+    ) -> StreamingDecodeProgress {
+        panic_free!({ self.streaming_decode::<B, false, false>(input, output, usize::MAX) })
+    }
 
-            let mut buf = [0u8; 2];
-            NativeEndian::write_u16(&mut buf, pixel);
+    /// Like [`streaming_decode_to_slice_unchecked`](Self::streaming_decode_to_slice_unchecked),
+    /// but returns after at most `max_ops` ops have been decoded.
+    ///
+    /// This bounds the time spent in a single call, e.g. to get back to the main loop before a
+    /// watchdog deadline. The remaining input, starting at
+    /// [`bytes_consumed`](StreamingDecodeProgress::bytes_consumed), is passed to the next call.
+    /// An op split across calls counts towards the call it started in.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`streaming_decode_to_slice_unchecked`](Self::streaming_decode_to_slice_unchecked).
+    pub unsafe fn streaming_decode_to_slice_budgeted_unchecked<B: Endianness>(
+        &mut self,
+        input: &[u8],
+        output: &mut [u16],
+        max_ops: usize,
+    ) -> StreamingDecodeProgress {
+        panic_free!({ self.streaming_decode::<B, true, false>(input, output, max_ops) })
+    }
 
-            *output.get_unchecked_mut(*output_idx) = B::read_u16(&buf);
-            *output_idx += 1;
-        }
+    /// Decodes a Q565 image into a buffer in a streaming fashion, without the header, stopping
+    /// when the output is full.
+    ///
+    /// Unlike [`streaming_decode_to_slice_unchecked`](Self::streaming_decode_to_slice_unchecked),
+    /// this is safe for any input, e.g. chunks arriving from an untrusted source: every sequence of
+    /// bytes is a valid sequence of ops for this decoder, so the output bounds are all there is to
+    /// check.
+    ///
+    /// If the output is full before the input is used up, the call returns with
+    /// [`output_full`](StreamingDecodeProgress::output_full) set. The next call continues with the
+    /// input after [`bytes_consumed`](StreamingDecodeProgress::bytes_consumed), and the output
+    /// after the pixels written so far, e.g. the next line buffer. Runs that don't fit are split,
+    /// so the output is always filled completely.
+    pub fn streaming_decode_to_slice<B: Endianness>(
+        &mut self,
+        input: &[u8],
+        output: &mut [u16],
+    ) -> StreamingDecodeProgress {
+        // SAFETY: the checked decoder only writes within `output`
+        panic_free!({
+            unsafe { self.streaming_decode::<B, false, true>(input, output, usize::MAX) }
+        })
+    }
 
-        loop {
-            let byte = next!();
-            let pixel = match self.state {
-                Q565StreamingDecodeState::Default => {
-                    let op = byte >> 6;
-
-                    match op {
-                        0b00 => {
-                            let pixel = *self.arr.get_unchecked(usize::from(byte));
-                            set_pixel::<B>(self, pixel, output, &mut output_idx);
-                            continue;
-                        }
-                        0b01 => {
-                            let pixel = direct_small_diff(self.prev, byte);
-                            set_pixel::<B>(self, pixel, output, &mut output_idx);
+    /// Like [`streaming_decode_to_slice`](Self::streaming_decode_to_slice), but returns after at
+    /// most `max_ops` ops have been decoded, see
+    /// [`streaming_decode_to_slice_budgeted_unchecked`](Self::streaming_decode_to_slice_budgeted_unchecked).
+    pub fn streaming_decode_to_slice_budgeted<B: Endianness>(
+        &mut self,
+        input: &[u8],
+        output: &mut [u16],
+        max_ops: usize,
+    ) -> StreamingDecodeProgress {
+        // SAFETY: the checked decoder only writes within `output`
+        panic_free!({ unsafe { self.streaming_decode::<B, true, true>(input, output, max_ops) } })
+    }
 
-                            continue;
-                        }
-                        0b10 => {
-                            self.state = Q565StreamingDecodeState::LumaOrDiffIndexedByte2(byte);
-                            continue;
-                        }
-                        0b11 => {
-                            if byte == 0xFE {
-                                self.state = Q565StreamingDecodeState::RawRgb565Byte1;
-                                continue;
-                            } else if byte != 0xFF {
-                                let count = (byte & 0b0011_1111) + 1;
-                                let count = usize::from(count);
+    /// Decodes the ops of `input`. With `CHECKED`, stops before writing past the end of `output`.
+    #[inline(always)]
+    unsafe fn streaming_decode<B: Endianness, const BUDGETED: bool, const CHECKED: bool>(
+        &mut self,
+        input: &[u8],
+        output: &mut [u16],
+        max_ops: usize,
+    ) -> StreamingDecodeProgress {
+        {
+            let mut output_idx = 0;
+            let mut input_idx = 0;
+            let mut ops = 0;
+
+            macro_rules! progress {
+                () => {
+                    progress!(false)
+                };
+                ($output_full:expr) => {
+                    StreamingDecodeProgress {
+                        bytes_consumed: input_idx,
+                        pixels_written: output_idx,
+                        finished: self.is_finished(),
+                        output_full: $output_full,
+                    }
+                };
+            }
+
+            // Puts the byte that was just read back, if the output has no room for its pixel. The
+            // state isn't changed before this, so the op is decoded again by the next call.
+            macro_rules! ensure_room {
+                () => {
+                    if CHECKED && output_idx >= output.len() {
+                        input_idx -= 1;
+                        return progress!(true);
+                    }
+                };
+            }
 
-                                let mut buf = [0u8; 2];
-                                NativeEndian::write_u16(&mut buf, self.prev);
+            // Writes up to `count` pixels of a run, as many as fit with `CHECKED`. Leaves the rest
+            // for the next call.
+            macro_rules! fill_run {
+                ($count:expr) => {
+                    let count: usize = $count;
+                    let len = if CHECKED {
+                        count.min(output.len() - output_idx)
+                    } else {
+                        count
+                    };
 
-                                output
-                                    .get_unchecked_mut(output_idx..)
-                                    .get_unchecked_mut(..count)
-                                    .fill(B::read_u16(&buf));
-                                output_idx += count;
+                    let mut buf = [0u8; 2];
+                    NativeEndian::write_u16(&mut buf, self.prev);
 
+                    output
+                        .get_unchecked_mut(output_idx..)
+                        .get_unchecked_mut(..len)
+                        .fill(B::read_u16(&buf));
+                    output_idx += len;
+
+                    if len < count {
+                        self.state = Q565StreamingDecodeState::Run((count - len) as u8);
+                        return progress!(true);
+                    }
+                };
+            }
+
+            macro_rules! next {
+                () => {
+                    if let Some(&b) = input.get(input_idx) {
+                        input_idx += 1;
+                        b
+                    } else {
+                        return progress!();
+                    }
+                };
+            }
+
+            unsafe fn set_pixel<B: Endianness>(
+                state: &mut Q565StreamingDecodeContext,
+                pixel: u16,
+                output: &mut [u16],
+                output_idx: &mut usize,
+            ) {
+                state.prev = pixel;
+
+                let mut buf = [0u8; 2];
+                NativeEndian::write_u16(&mut buf, pixel);
+
+                *output.get_unchecked_mut(*output_idx) = B::read_u16(&buf);
+                *output_idx += 1;
+            }
+
+            match self.state {
+                Q565StreamingDecodeState::Finished => return progress!(),
+                Q565StreamingDecodeState::Run(count) => {
+                    fill_run!(usize::from(count));
+                    self.state = Q565StreamingDecodeState::Default;
+                }
+                _ => {}
+            }
+
+            loop {
+                // ops are only counted when they start, so that a call always finishes an op once
+                // it has the input for it
+                if BUDGETED && matches!(self.state, Q565StreamingDecodeState::Default) {
+                    if ops >= max_ops {
+                        return progress!();
+                    }
+                    ops += 1;
+                }
+
+                let byte = next!();
+                let pixel = match self.state {
+                    Q565StreamingDecodeState::Default => {
+                        let op = byte >> 6;
+
+                        match op {
+                            0b00 => {
+                                ensure_room!();
+                                let pixel = *self.arr.get_unchecked(usize::from(byte));
+                                set_pixel::<B>(self, pixel, output, &mut output_idx);
+                                continue;
+                            }
+                            0b01 => {
+                                ensure_room!();
+                                let pixel = direct_small_diff(self.prev, byte);
+                                set_pixel::<B>(self, pixel, output, &mut output_idx);
+
+                                continue;
+                            }
+                            0b10 => {
+                                self.state = Q565StreamingDecodeState::LumaOrDiffIndexedByte2(byte);
                                 continue;
-                            } else {
-                                return output_idx;
                             }
+                            0b11 => {
+                                if byte == 0xFE {
+                                    self.state = Q565StreamingDecodeState::RawRgb565Byte1;
+                                    continue;
+                                } else if byte != 0xFF {
+                                    fill_run!(usize::from((byte & 0b0011_1111) + 1));
+                                    continue;
+                                } else {
+                                    self.state = Q565StreamingDecodeState::Finished;
+                                    return progress!();
+                                }
+                            }
+                            _ => unsafe { unreachable_unchecked() },
                         }
-                        _ => unsafe { unreachable_unchecked() },
                     }
-                }
-                Q565StreamingDecodeState::LumaOrDiffIndexedByte2(byte1) => {
-                    let op = byte1 >> 5;
-                    match op {
-                        0b100 => direct_bigger_diff(self.prev, byte1, byte),
-                        0b101 => indexed_diff(&self.arr, byte1, byte),
-                        _ => unsafe { unreachable_unchecked() },
+                    Q565StreamingDecodeState::LumaOrDiffIndexedByte2(byte1) => {
+                        ensure_room!();
+                        let op = byte1 >> 5;
+                        match op {
+                            0b100 => direct_bigger_diff(self.prev, byte1, byte),
+                            0b101 => indexed_diff(&self.arr, byte1, byte),
+                            _ => unsafe { unreachable_unchecked() },
+                        }
                     }
-                }
-                Q565StreamingDecodeState::RawRgb565Byte1 => {
-                    self.state = Q565StreamingDecodeState::RawRgb565Byte2(byte);
-                    continue;
-                }
-                Q565StreamingDecodeState::RawRgb565Byte2(byte1) => {
-                    u16::from_le_bytes([byte1, byte])
-                }
-            };
+                    Q565StreamingDecodeState::RawRgb565Byte1 => {
+                        self.state = Q565StreamingDecodeState::RawRgb565Byte2(byte);
+                        continue;
+                    }
+                    Q565StreamingDecodeState::RawRgb565Byte2(byte1) => {
+                        ensure_room!();
+                        u16::from_le_bytes([byte1, byte])
+                    }
+                    // handled before the loop, and the loop is left as soon as the state is set
+                    Q565StreamingDecodeState::Finished | Q565StreamingDecodeState::Run(_) => unsafe {
+                        unreachable_unchecked()
+                    },
+                };
 
-            let index = hash(pixel);
-            *self.arr.get_unchecked_mut(usize::from(index)) = pixel;
-            set_pixel::<B>(self, pixel, output, &mut output_idx);
-            self.state = Q565StreamingDecodeState::Default;
+                let index = hash(pixel);
+                *self.arr.get_unchecked_mut(usize::from(index)) = pixel;
+                set_pixel::<B>(self, pixel, output, &mut output_idx);
+                self.state = Q565StreamingDecodeState::Default;
+            }
         }
     }
 }
@@ -0,0 +1,92 @@
+use super::{ColorFormat, DecodeError, InfallibleDecodeOutput, Q565DecodeContext};
+use crate::byteorder::Endianness;
+use crate::HeaderInfo;
+use core::mem::MaybeUninit;
+
+/// Decode output writing into a possibly uninitialized slice, e.g. a scratch buffer that is reused
+/// for every frame of a render loop, without clearing it or allocating.
+///
+/// Writes that would fall outside of the slice are dropped. The pixels are written front to back,
+/// so the first [`current_output_position`](InfallibleDecodeOutput::current_output_position)
+/// elements are initialized, see [`initialized`](Self::initialized).
+pub struct UninitSliceDecodeOutput<'a, C: ColorFormat> {
+    output: &'a mut [MaybeUninit<C::OutputElement>],
+    output_idx: usize,
+}
+
+impl<'a, C> UninitSliceDecodeOutput<'a, C>
+where
+    C: ColorFormat,
+{
+    #[inline]
+    pub fn new(slice: &'a mut [MaybeUninit<C::OutputElement>]) -> Self {
+        Self {
+            output: slice,
+            output_idx: 0,
+        }
+    }
+
+    /// Returns the pixels written so far.
+    #[inline]
+    pub fn initialized(self) -> &'a mut [C::OutputElement] {
+        let len = self.output_idx.min(self.output.len());
+        let initialized = &mut self.output[..len];
+        // SAFETY: `output_idx` only advances over elements that were written
+        unsafe {
+            core::slice::from_raw_parts_mut(initialized.as_mut_ptr().cast(), initialized.len())
+        }
+    }
+}
+
+impl<C> InfallibleDecodeOutput for UninitSliceDecodeOutput<'_, C>
+where
+    C: ColorFormat,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        if let Some(pixel) = self.output.get_mut(self.output_idx) {
+            pixel.write(C::to_output::<B>(color));
+            self.output_idx += 1;
+        }
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        let color = C::to_output::<B>(color);
+        let end = self.output.len().min(self.output_idx + count);
+        if let Some(pixels) = self.output.get_mut(self.output_idx..end) {
+            for pixel in pixels {
+                pixel.write(color.clone());
+            }
+            self.output_idx = end;
+        }
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        Some(self.output.len())
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
+
+impl Q565DecodeContext {
+    /// Decodes a Q565 image into a possibly uninitialized buffer, see [`UninitSliceDecodeOutput`].
+    ///
+    /// Returns the header info and the decoded pixels, which borrow from `output`.
+    pub fn decode_to_uninit<'a, B, C>(
+        data: &[u8],
+        output: &'a mut [MaybeUninit<C::OutputElement>],
+    ) -> Result<(HeaderInfo, &'a mut [C::OutputElement]), DecodeError>
+    where
+        B: Endianness,
+        C: ColorFormat,
+    {
+        let mut output = UninitSliceDecodeOutput::<C>::new(output);
+        let (header, _) = Self::decode::<B>(data, &mut output)?;
+        Ok((header, output.initialized()))
+    }
+}
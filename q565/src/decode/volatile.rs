@@ -0,0 +1,84 @@
+use super::{ColorFormat, InfallibleDecodeOutput};
+use crate::byteorder::Endianness;
+use core::marker::PhantomData;
+
+/// Decode output writing into memory-mapped framebuffer memory, e.g. an STM32 LTDC layer, with
+/// volatile writes.
+///
+/// Every pixel is written exactly once, in order, with [`write_volatile`](core::ptr::write_volatile),
+/// so the compiler can neither elide nor reorder the writes to the display memory. The pixels are
+/// converted with the byte order passed to the decoder, like for any other output, see
+/// [`byteorder`](crate::byteorder).
+///
+/// Writes that would fall outside of the framebuffer are dropped.
+pub struct VolatileSliceDecodeOutput<'a, C: ColorFormat> {
+    ptr: *mut C::OutputElement,
+    len: usize,
+    output_idx: usize,
+    _framebuffer: PhantomData<&'a mut [C::OutputElement]>,
+}
+
+impl<C> VolatileSliceDecodeOutput<'_, C>
+where
+    C: ColorFormat,
+{
+    /// Creates an output writing to the `len` elements starting at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for volatile writes of `len` elements, and properly aligned, for the
+    /// lifetime of the output.
+    #[inline]
+    pub unsafe fn new(ptr: *mut C::OutputElement, len: usize) -> Self {
+        Self {
+            ptr,
+            len,
+            output_idx: 0,
+            _framebuffer: PhantomData,
+        }
+    }
+}
+
+impl<'a, C> VolatileSliceDecodeOutput<'a, C>
+where
+    C: ColorFormat,
+{
+    /// Creates an output writing to a framebuffer that is regular memory, e.g. one that is read
+    /// by a DMA transfer.
+    #[inline]
+    pub fn from_slice(framebuffer: &'a mut [C::OutputElement]) -> Self {
+        // SAFETY: the slice is valid for writes of its length, and borrowed for `'a`
+        unsafe { Self::new(framebuffer.as_mut_ptr(), framebuffer.len()) }
+    }
+}
+
+impl<C> InfallibleDecodeOutput for VolatileSliceDecodeOutput<'_, C>
+where
+    C: ColorFormat,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.write_many_pixels::<B>(color, 1);
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        let color = C::to_output::<B>(color);
+        let end = self.len.min(self.output_idx.saturating_add(count));
+        for i in self.output_idx..end {
+            // SAFETY: `i < len`, see `new`
+            unsafe { self.ptr.add(i).write_volatile(color.clone()) };
+        }
+        self.output_idx = self.output_idx.saturating_add(count);
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        Some(self.len)
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
@@ -0,0 +1,200 @@
+//! Interleaving several headerless Q565 streams on one transport.
+//!
+//! Each stream is split into frames, which can be interleaved freely with the frames of other
+//! streams, e.g. one stream per display region or per client.
+//!
+//! # Layout
+//!
+//! - u8 stream id
+//! - u16le payload length
+//! - payload: the next bytes of the stream
+//!
+//! A stream carries one image after the other, each without the header but with its end marker.
+//! The end marker may be anywhere in a frame, the next image of the stream starts right after it.
+
+use crate::byteorder::Endianness;
+use crate::decode::streaming_no_header::Q565StreamingDecodeContext;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Length of a frame header, in bytes.
+pub const FRAME_HEADER_LEN: usize = 3;
+
+error_enum! {
+    pub enum DemuxError {
+        /// A frame belongs to a stream that the demuxer doesn't have.
+        UnknownStream { stream: u8 } = 1,
+        /// An image doesn't fit into the output of its stream.
+        OutputTooSmall { stream: u8 } = 2,
+    }
+}
+
+/// Returns the header of a frame with a payload of `len` bytes.
+pub const fn frame_header(stream: u8, len: u16) -> [u8; FRAME_HEADER_LEN] {
+    let [l1, l2] = len.to_le_bytes();
+    [stream, l1, l2]
+}
+
+/// Appends `data` as frames of the given stream to `w`, splitting it into frames of at most
+/// `max_payload` bytes.
+#[cfg(feature = "alloc")]
+pub fn write_frames(stream: u8, data: &[u8], max_payload: u16, w: &mut Vec<u8>) {
+    for payload in data.chunks(usize::from(max_payload.max(1))) {
+        w.extend_from_slice(&frame_header(stream, payload.len() as u16));
+        w.extend_from_slice(payload);
+    }
+}
+
+/// Decode state of a single stream of a [`Demuxer`].
+pub struct DemuxStream<'a> {
+    state: Q565StreamingDecodeContext,
+    output: &'a mut [u16],
+    pixels_written: usize,
+}
+
+impl<'a> DemuxStream<'a> {
+    /// Creates a stream that decodes its images into `output`, which needs to be large enough to
+    /// hold any of them.
+    pub const fn new(output: &'a mut [u16]) -> Self {
+        Self {
+            state: Q565StreamingDecodeContext::new(),
+            output,
+            pixels_written: 0,
+        }
+    }
+
+    /// Returns the number of pixels of the current image decoded so far.
+    pub const fn pixels_written(&self) -> usize {
+        self.pixels_written
+    }
+}
+
+/// Splits a transport carrying `S` interleaved streams into frames, and decodes each stream with
+/// its own decode context, see the [module docs](self).
+pub struct Demuxer<'a, const S: usize> {
+    streams: [DemuxStream<'a>; S],
+
+    header: [u8; FRAME_HEADER_LEN],
+    header_len: usize,
+    /// Stream of the current frame.
+    stream: usize,
+    /// Payload bytes left in the current frame.
+    remaining: usize,
+}
+
+impl<'a, const S: usize> Demuxer<'a, S> {
+    /// Creates a demuxer for the given streams. Stream ids are indices into `streams`.
+    pub const fn new(streams: [DemuxStream<'a>; S]) -> Self {
+        Self {
+            streams,
+            header: [0; FRAME_HEADER_LEN],
+            header_len: 0,
+            stream: 0,
+            remaining: 0,
+        }
+    }
+
+    /// Returns the given stream.
+    pub fn stream(&self, stream: usize) -> Option<&DemuxStream<'a>> {
+        self.streams.get(stream)
+    }
+
+    /// Processes the next bytes received from the transport. Frames may be split across calls at
+    /// any point.
+    ///
+    /// `on_image` is called with the stream id and the decoded pixels whenever a stream completes
+    /// an image. Afterwards, the stream starts decoding its next image into the same output.
+    ///
+    /// The streams are decoded with
+    /// [`Q565StreamingDecodeContext::streaming_decode_to_slice`], so any data is safe to push. An
+    /// error leaves the demuxer in the middle of the bad frame, so it can't be used further.
+    pub fn push<B: Endianness>(
+        &mut self,
+        data: &[u8],
+        on_image: impl FnMut(usize, &mut [u16]),
+    ) -> Result<(), DemuxError> {
+        // SAFETY: the checked decoder only writes within the outputs
+        unsafe { self.push_with::<B, true>(data, on_image) }
+    }
+
+    /// Like [`push`](Self::push), but decodes the streams without any checks.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Q565StreamingDecodeContext::streaming_decode_to_slice_unchecked`]: each stream
+    /// needs to consist of valid images that fit into the stream's output.
+    pub unsafe fn push_unchecked<B: Endianness>(
+        &mut self,
+        data: &[u8],
+        on_image: impl FnMut(usize, &mut [u16]),
+    ) -> Result<(), DemuxError> {
+        unsafe { self.push_with::<B, false>(data, on_image) }
+    }
+
+    /// # Safety
+    ///
+    /// Without `CHECKED`, same as [`push_unchecked`](Self::push_unchecked).
+    unsafe fn push_with<B: Endianness, const CHECKED: bool>(
+        &mut self,
+        mut data: &[u8],
+        mut on_image: impl FnMut(usize, &mut [u16]),
+    ) -> Result<(), DemuxError> {
+        while !data.is_empty() {
+            if self.remaining == 0 {
+                // collect the frame header
+                let n = data.len().min(FRAME_HEADER_LEN - self.header_len);
+                self.header[self.header_len..self.header_len + n].copy_from_slice(&data[..n]);
+                self.header_len += n;
+                data = &data[n..];
+
+                if self.header_len == FRAME_HEADER_LEN {
+                    let [stream, l1, l2] = self.header;
+                    ensure!(
+                        usize::from(stream) < S,
+                        DemuxError::UnknownStream { stream }
+                    );
+                    self.stream = usize::from(stream);
+                    self.remaining = usize::from(u16::from_le_bytes([l1, l2]));
+                    self.header_len = 0;
+                }
+                continue;
+            }
+
+            let (payload, rest) = data.split_at(data.len().min(self.remaining));
+            self.remaining -= payload.len();
+            data = rest;
+
+            let stream = &mut self.streams[self.stream];
+            let mut payload = payload;
+            while !payload.is_empty() {
+                let output = &mut stream.output[stream.pixels_written..];
+                let progress = if CHECKED {
+                    stream.state.streaming_decode_to_slice::<B>(payload, output)
+                } else {
+                    unsafe {
+                        stream
+                            .state
+                            .streaming_decode_to_slice_with_progress_unchecked::<B>(payload, output)
+                    }
+                };
+                ensure!(
+                    !progress.output_full,
+                    DemuxError::OutputTooSmall {
+                        stream: self.stream as u8
+                    }
+                );
+                stream.pixels_written += progress.pixels_written;
+                payload = &payload[progress.bytes_consumed..];
+
+                if progress.finished {
+                    on_image(self.stream, &mut stream.output[..stream.pixels_written]);
+                    stream.state = Q565StreamingDecodeContext::new();
+                    stream.pixels_written = 0;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+//! Comparison of two Q565 images.
+
+use crate::byteorder::NativeEndian;
+use crate::{
+    decode::{DecodeError, Q565DecodeContext},
+    utils::decode_565,
+    Rgb565,
+};
+
+/// Maximum values of the red, green, and blue channels.
+const CHANNEL_MAX: [f64; 3] = [31.0, 63.0, 31.0];
+
+/// Side length of the square SSIM window.
+const SSIM_WINDOW: usize = 8;
+/// Distance between two neighboring SSIM windows.
+const SSIM_STRIDE: usize = 4;
+
+error_enum! {
+    pub enum CompareError {
+        /// One of the images failed to decode.
+        Decode { source: DecodeError } = 1,
+        /// The images have different dimensions.
+        DimensionMismatch = 2,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffReport {
+    pub width: u16,
+    pub height: u16,
+    /// Root-mean-square error of the red, green, and blue channels, in steps of the respective
+    /// 5/6/5-bit channel.
+    pub rmse: [f64; 3],
+    /// Peak signal-to-noise ratio of the red, green, and blue channels in dB, relative to the
+    /// channel's maximum value. Infinite for identical channels.
+    pub psnr: [f64; 3],
+    /// Structural similarity index of the red, green, and blue channels, see [`ssim`].
+    pub ssim: [f64; 3],
+    /// One entry per pixel, `true` if the pixel differs between the images.
+    pub changed: Vec<bool>,
+    /// Number of `true` entries in [`changed`](Self::changed).
+    pub changed_count: usize,
+}
+
+impl DiffReport {
+    #[inline]
+    pub fn is_identical(&self) -> bool {
+        self.changed_count == 0
+    }
+
+    /// Mean SSIM over all three channels, weighted equally.
+    #[inline]
+    pub fn mean_ssim(&self) -> f64 {
+        self.ssim.iter().sum::<f64>() / 3.0
+    }
+}
+
+/// Decodes both images and compares them pixel by pixel.
+pub fn compare(a: &[u8], b: &[u8]) -> Result<DiffReport, CompareError> {
+    let (header_a, pixels_a) = Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(a)
+        .map_err(|source| CompareError::Decode { source })?;
+    let (header_b, pixels_b) = Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(b)
+        .map_err(|source| CompareError::Decode { source })?;
+
+    ensure!(
+        (header_a.width, header_a.height) == (header_b.width, header_b.height),
+        CompareError::DimensionMismatch
+    );
+
+    Ok(compare_pixels(
+        header_a.width,
+        header_a.height,
+        &pixels_a,
+        &pixels_b,
+    ))
+}
+
+/// Compares two decoded RGB565 images of the same size.
+///
+/// # Panics
+///
+/// Panics if either slice doesn't hold exactly `width * height` pixels.
+pub fn compare_pixels(width: u16, height: u16, a: &[u16], b: &[u16]) -> DiffReport {
+    assert_eq!(Some(a.len()), crate::pixel_count(width, height));
+    assert_eq!(a.len(), b.len());
+    let pixel_count = a.len();
+
+    let mut squared_errors = [0u64; 3];
+    let changed: Vec<bool> = a
+        .iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let (a, b) = (decode_565(a), decode_565(b));
+            for c in 0..3 {
+                let diff = u64::from(a[c].abs_diff(b[c]));
+                squared_errors[c] += diff * diff;
+            }
+            a != b
+        })
+        .collect();
+
+    let mut rmse = [0.0; 3];
+    let mut psnr = [f64::INFINITY; 3];
+    for c in 0..3 {
+        let mse = squared_errors[c] as f64 / pixel_count.max(1) as f64;
+        rmse[c] = mse.sqrt();
+        if mse > 0.0 {
+            psnr[c] = 10.0 * (CHANNEL_MAX[c] * CHANNEL_MAX[c] / mse).log10();
+        }
+    }
+
+    DiffReport {
+        width,
+        height,
+        rmse,
+        psnr,
+        ssim: ssim(width, height, a, b),
+        changed_count: changed.iter().filter(|&&c| c).count(),
+        changed,
+    }
+}
+
+/// Computes the structural similarity index (SSIM) of the red, green, and blue channels of two
+/// decoded RGB565 images of the same size.
+///
+/// Each channel is compared at its native 5/6/5-bit precision, with the SSIM constants scaled to
+/// the channel's range. The index is averaged over 8x8 windows spaced 4 pixels apart (or a single
+/// window covering the whole image, if it is smaller than that). `1.0` means identical.
+///
+/// # Panics
+///
+/// Panics if either slice doesn't hold exactly `width * height` pixels.
+pub fn ssim(width: u16, height: u16, a: &[u16], b: &[u16]) -> [f64; 3] {
+    let (width, height) = (usize::from(width), usize::from(height));
+    assert_eq!(a.len(), width * height);
+    assert_eq!(b.len(), width * height);
+
+    if width == 0 || height == 0 {
+        return [1.0; 3];
+    }
+
+    let (window_width, window_height) = (width.min(SSIM_WINDOW), height.min(SSIM_WINDOW));
+    let xs = window_starts(width, window_width);
+    let ys = window_starts(height, window_height);
+    let n = (window_width * window_height) as f64;
+
+    let mut result = [0.0; 3];
+    for (c, result) in result.iter_mut().enumerate() {
+        let c1 = (0.01 * CHANNEL_MAX[c]).powi(2);
+        let c2 = (0.03 * CHANNEL_MAX[c]).powi(2);
+
+        let mut total = 0.0;
+        for &y0 in &ys {
+            for &x0 in &xs {
+                let (mut sum_a, mut sum_b) = (0.0, 0.0);
+                let (mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0);
+                for y in y0..y0 + window_height {
+                    for x in x0..x0 + window_width {
+                        let va = f64::from(decode_565(a[y * width + x])[c]);
+                        let vb = f64::from(decode_565(b[y * width + x])[c]);
+                        sum_a += va;
+                        sum_b += vb;
+                        sum_aa += va * va;
+                        sum_bb += vb * vb;
+                        sum_ab += va * vb;
+                    }
+                }
+
+                let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+                let var_a = sum_aa / n - mean_a * mean_a;
+                let var_b = sum_bb / n - mean_b * mean_b;
+                let covar = sum_ab / n - mean_a * mean_b;
+
+                total += ((2.0 * mean_a * mean_b + c1) * (2.0 * covar + c2))
+                    / ((mean_a * mean_a + mean_b * mean_b + c1) * (var_a + var_b + c2));
+            }
+        }
+
+        *result = total / (xs.len() * ys.len()) as f64;
+    }
+
+    result
+}
+
+/// Start offsets of all windows along one axis, always including the last possible window so the
+/// edge pixels are covered.
+fn window_starts(len: usize, window: usize) -> Vec<usize> {
+    let last = len - window;
+    let mut starts: Vec<usize> = (0..=last).step_by(SSIM_STRIDE).collect();
+    if starts.last() != Some(&last) {
+        starts.push(last);
+    }
+    starts
+}
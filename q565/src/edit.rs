@@ -0,0 +1,241 @@
+//! Editing operations on encoded Q565 images.
+//!
+//! All operations decode their inputs, rearrange the pixels, and re-encode the result with a fresh
+//! encoder context.
+
+use crate::byteorder::NativeEndian;
+use crate::{
+    decode::{DecodeError, Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    stream::{Op, OpReader},
+    ColorArraySize, Rect, Rgb565,
+};
+use alloc::vec::Vec;
+
+error_enum! {
+    pub enum EditError {
+        /// One of the input images failed to decode.
+        Decode { source: DecodeError } = 1,
+        /// No input images were given.
+        NoImages = 2,
+        /// The dimensions of the input images don't line up.
+        DimensionMismatch = 3,
+        /// The resulting image would be larger than 65535 pixels in either dimension.
+        TooLarge = 4,
+        /// The rectangle doesn't fit within the image, or the number of pixels doesn't match its size.
+        InvalidRect = 5,
+    }
+}
+
+/// Places the given images next to each other, left to right. All images need to have the same
+/// height.
+pub fn hconcat(images: &[&[u8]]) -> Result<Vec<u8>, EditError> {
+    ensure!(!images.is_empty(), EditError::NoImages);
+
+    let mut decoded = Vec::with_capacity(images.len());
+    let mut width = 0u32;
+    for image in images {
+        let (header, pixels) = Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(image)
+            .map_err(|source| EditError::Decode { source })?;
+        width += u32::from(header.width);
+        decoded.push((header, pixels));
+    }
+
+    let height = decoded[0].0.height;
+    ensure!(
+        decoded.iter().all(|(header, _)| header.height == height),
+        EditError::DimensionMismatch
+    );
+    let width = u16::try_from(width).ok().ok_or(EditError::TooLarge)?;
+
+    // feed the rows straight from the decoded buffers into the encoder
+    let rows = (0..usize::from(height)).flat_map(|y| {
+        decoded.iter().flat_map(move |(header, pixels)| {
+            let width = usize::from(header.width);
+            &pixels[y * width..(y + 1) * width]
+        })
+    });
+
+    let mut output = Vec::new();
+    Q565EncodeContext::encode_iter_to_vec(width, height, rows, &mut output);
+    Ok(output)
+}
+
+/// Places the given images below each other, top to bottom. All images need to have the same
+/// width.
+pub fn vconcat(images: &[&[u8]]) -> Result<Vec<u8>, EditError> {
+    ensure!(!images.is_empty(), EditError::NoImages);
+
+    // the images can be decoded back to back into the same buffer
+    let mut pixels = Vec::new();
+    let mut width = None;
+    let mut height = 0u32;
+    for image in images {
+        let (header, _) = Q565DecodeContext::decode::<NativeEndian>(
+            image,
+            VecDecodeOutput::<Rgb565>::new(&mut pixels),
+        )
+        .map_err(|source| EditError::Decode { source })?;
+
+        ensure!(
+            *width.get_or_insert(header.width) == header.width,
+            EditError::DimensionMismatch
+        );
+        height += u32::from(header.height);
+    }
+
+    let width = width.unwrap_or_default();
+    let height = u16::try_from(height).ok().ok_or(EditError::TooLarge)?;
+
+    let mut output = Vec::new();
+    Q565EncodeContext::encode_to_vec(width, height, &pixels, &mut output);
+    Ok(output)
+}
+
+/// Cuts an image into tiles of `tile_width x tile_height` pixels, in row-major order. Tiles at the
+/// right and bottom edges are smaller if the image size isn't a multiple of the tile size.
+///
+/// This is the inverse of stitching the tiles back together with [`hconcat`] and [`vconcat`].
+pub fn slice(data: &[u8], tile_width: u16, tile_height: u16) -> Result<Vec<Vec<u8>>, EditError> {
+    ensure!(
+        tile_width > 0 && tile_height > 0,
+        EditError::DimensionMismatch
+    );
+
+    let (header, pixels) = Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(data)
+        .map_err(|source| EditError::Decode { source })?;
+    let (width, height) = (usize::from(header.width), usize::from(header.height));
+    let (tile_width, tile_height) = (usize::from(tile_width), usize::from(tile_height));
+
+    let mut tiles = Vec::new();
+    for y0 in (0..height).step_by(tile_height) {
+        for x0 in (0..width).step_by(tile_width) {
+            let w = tile_width.min(width - x0);
+            let h = tile_height.min(height - y0);
+            let rows = (y0..y0 + h).flat_map(|y| &pixels[y * width + x0..y * width + x0 + w]);
+
+            let mut tile = Vec::new();
+            Q565EncodeContext::encode_iter_to_vec(w as u16, h as u16, rows, &mut tile);
+            tiles.push(tile);
+        }
+    }
+
+    Ok(tiles)
+}
+
+/// Replaces the pixels inside `rect` with `new_pixels` (row-major, `rect.width * rect.height`
+/// pixels) by decoding and re-encoding the whole image.
+///
+/// The image keeps its [color array profile](crate#color-array-profiles). [Raw
+/// images](crate#raw-images) are encoded into ops.
+pub fn patch(original: &[u8], rect: Rect, new_pixels: &[u16]) -> Result<Vec<u8>, EditError> {
+    let (header, mut pixels) = Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(original)
+        .map_err(|source| EditError::Decode { source })?;
+    replace_rect(header.width, header.height, &mut pixels, rect, new_pixels)?;
+
+    let mut output = Vec::with_capacity(original.len());
+    Q565EncodeContext::encode_to_vec_sized(
+        header.color_array_size,
+        header.width,
+        header.height,
+        &pixels,
+        &mut output,
+    );
+    Ok(output)
+}
+
+/// Like [`patch`], but keeps the encoded data before the first affected pixel as-is and only
+/// re-encodes from there on, continuing from the decoder state at that point.
+///
+/// The result decodes to the same image as the one produced by [`patch`], but isn't necessarily
+/// byte-identical to it.
+///
+/// Images using a smaller [color array profile](crate#color-array-profiles), and [raw
+/// images](crate#raw-images), are re-encoded completely, like with [`patch`].
+pub fn patch_incremental(
+    original: &[u8],
+    rect: Rect,
+    new_pixels: &[u16],
+) -> Result<Vec<u8>, EditError> {
+    let (header, data) = Q565DecodeContext::decode_header(original)
+        .map_err(|source| EditError::Decode { source })?;
+    if header.color_array_size != ColorArraySize::Entries64 || header.raw {
+        return patch(original, rect, new_pixels);
+    }
+
+    let header_len = original.len() - data.len();
+    let (width, height) = (header.width, header.height);
+    let first_affected = usize::from(rect.y)
+        .saturating_mul(usize::from(width))
+        .saturating_add(usize::from(rect.x));
+
+    // decode op by op, remembering where the op producing the first affected pixel starts
+    let mut ctx = Q565DecodeContext::new();
+    let pixel_count = header.pixel_count().ok_or(EditError::Decode {
+        source: DecodeError::DimensionsTooLarge,
+    })?;
+    let mut pixels = Vec::with_capacity(pixel_count);
+    let mut checkpoint = None;
+    let mut ended = false;
+    let mut ops = OpReader::new(data);
+    for (offset, op) in ops.by_ref() {
+        if checkpoint.is_none() && pixels.len() + op.pixel_count() > first_affected {
+            checkpoint = Some((offset, pixels.len(), ctx));
+        }
+        if op == Op::End {
+            ended = true;
+            break;
+        }
+
+        let (color, count) = ctx.apply_op(op);
+        if pixels.len() + count > pixel_count {
+            return Err(DecodeError::TooManyPixels).map_err(|source| EditError::Decode { source });
+        }
+        pixels.extend(core::iter::repeat_n(color, count));
+    }
+
+    if !ended {
+        return Err(DecodeError::UnexpectedEof).map_err(|source| EditError::Decode { source });
+    }
+    if pixels.len() != pixel_count {
+        return Err(DecodeError::MissingData).map_err(|source| EditError::Decode { source });
+    }
+
+    replace_rect(width, height, &mut pixels, rect, new_pixels)?;
+
+    let Some((offset, position, ctx)) = checkpoint else {
+        // empty rectangle at the very end, nothing changes
+        return Ok(original[..header_len + ops.offset()].to_vec());
+    };
+
+    let mut encoder = Q565EncodeContext::from(ctx);
+
+    let mut output = original[..header_len + offset].to_vec();
+    encoder.encode_pixels_to_vec(&pixels[position..], &mut output);
+    Ok(output)
+}
+
+fn replace_rect(
+    width: u16,
+    height: u16,
+    pixels: &mut [u16],
+    rect: Rect,
+    new_pixels: &[u16],
+) -> Result<(), EditError> {
+    ensure!(
+        rect.fits_within(width, height) && new_pixels.len() == rect.area(),
+        EditError::InvalidRect
+    );
+
+    let (width, rect_width) = (usize::from(width), usize::from(rect.width));
+    if rect_width == 0 {
+        return Ok(());
+    }
+
+    for (row, new_row) in new_pixels.chunks_exact(rect_width).enumerate() {
+        let start = (usize::from(rect.y) + row) * width + usize::from(rect.x);
+        pixels[start..start + rect_width].copy_from_slice(new_row);
+    }
+
+    Ok(())
+}
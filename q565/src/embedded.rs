@@ -0,0 +1,14 @@
+//! Helpers for decoding on microcontrollers.
+//!
+//! `IsrFedDecoder` needs the `critical-section` feature, `ChannelRowDecoder` the `embassy`
+//! feature.
+
+#[cfg(feature = "embassy")]
+mod channel;
+#[cfg(feature = "critical-section")]
+mod isr_fed;
+
+#[cfg(feature = "embassy")]
+pub use channel::*;
+#[cfg(feature = "critical-section")]
+pub use isr_fed::*;
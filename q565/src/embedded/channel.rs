@@ -0,0 +1,103 @@
+use crate::byteorder::Endianness;
+use crate::decode::streaming_no_header::Q565StreamingDecodeContext;
+use embassy_sync::{blocking_mutex::raw::RawMutex, channel::Receiver};
+
+/// Most pixels a single byte of image data decodes to: a run of 62 pixels.
+const MAX_PIXELS_PER_BYTE: usize = 64;
+
+/// Async decoder reading the image data from an [`embassy_sync`] channel, one row at a time.
+///
+/// Meant for a task that receives the image from e.g. a UART or USB task, and hands every decoded
+/// row to the display driver before receiving the rest. The channel only needs to hold a few
+/// bytes, and the decoder only buffers the pixels of a single run besides the row passed in.
+///
+/// Like the streaming decoder, this decodes the image data without the header.
+///
+/// ```no_run
+/// use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+/// use q565::{byteorder::BigEndian, embedded::ChannelRowDecoder};
+///
+/// static BYTES: Channel<CriticalSectionRawMutex, u8, 64> = Channel::new();
+///
+/// async fn draw_row(y: usize, row: &[u16]) {
+///     // send the row to the display
+/// }
+///
+/// async fn decode_task(width: usize) {
+///     let mut decoder = ChannelRowDecoder::new(BYTES.receiver());
+///     let mut row = [0; 240];
+///     let row = &mut row[..width];
+///
+///     let mut y = 0;
+///     while decoder.next_row::<BigEndian>(row).await == width {
+///         draw_row(y, row).await;
+///         y += 1;
+///     }
+/// }
+/// ```
+pub struct ChannelRowDecoder<'ch, M: RawMutex, const N: usize> {
+    receiver: Receiver<'ch, M, u8, N>,
+    context: Q565StreamingDecodeContext,
+    /// Pixels decoded from the last byte that didn't fit into the previous row.
+    pending: [u16; MAX_PIXELS_PER_BYTE],
+    pending_start: usize,
+    pending_len: usize,
+}
+
+impl<'ch, M: RawMutex, const N: usize> ChannelRowDecoder<'ch, M, N> {
+    pub fn new(receiver: Receiver<'ch, M, u8, N>) -> Self {
+        Self {
+            receiver,
+            context: Q565StreamingDecodeContext::new(),
+            pending: [0; MAX_PIXELS_PER_BYTE],
+            pending_start: 0,
+            pending_len: 0,
+        }
+    }
+
+    /// Returns whether the end marker was received. Bytes after it are left in the channel.
+    pub fn is_finished(&self) -> bool {
+        self.context.is_finished()
+    }
+
+    /// Clears the decoder state, to start decoding the next image.
+    pub fn reset(&mut self) {
+        self.context = Q565StreamingDecodeContext::new();
+        self.pending_len = 0;
+    }
+
+    /// Receives bytes until `row` is filled with decoded pixels, or the end marker is received.
+    ///
+    /// Returns the number of pixels written, which is less than `row.len()` only for the end of
+    /// the image. The pixels of a run crossing the end of `row` are kept for the next call.
+    ///
+    /// Invalid input doesn't cause undefined behavior, but decodes to unspecified pixels.
+    pub async fn next_row<B: Endianness>(&mut self, row: &mut [u16]) -> usize {
+        let mut written = self.take_pending(row);
+
+        while written < row.len() && !self.context.is_finished() {
+            let byte = self.receiver.receive().await;
+
+            // SAFETY: a single byte decodes to at most 62 pixels, so the pending buffer is always
+            // large enough, whatever the input
+            let decoded = unsafe {
+                self.context
+                    .streaming_decode_to_slice_unchecked::<B>(&[byte], &mut self.pending)
+            };
+            self.pending_start = 0;
+            self.pending_len = decoded;
+            written += self.take_pending(&mut row[written..]);
+        }
+
+        written
+    }
+
+    /// Moves as many pending pixels as fit into `out`, returning their number.
+    fn take_pending(&mut self, out: &mut [u16]) -> usize {
+        let n = out.len().min(self.pending_len);
+        out[..n].copy_from_slice(&self.pending[self.pending_start..][..n]);
+        self.pending_start += n;
+        self.pending_len -= n;
+        n
+    }
+}
@@ -0,0 +1,256 @@
+use crate::byteorder::Endianness;
+use crate::decode::streaming_no_header::{Q565StreamingDecodeContext, StreamingDecodeProgress};
+use core::cell::{Cell, RefCell, UnsafeCell};
+use critical_section::Mutex;
+
+/// Number of bytes taken from the queue at once by [`IsrFedDecoder::drain`].
+const DRAIN_CHUNK_LEN: usize = 64;
+
+/// Streaming decoder fed from an interrupt handler, e.g. a UART RX interrupt, and drained from the
+/// main loop.
+///
+/// The interrupt handler pushes the received bytes into a queue of `CAP` bytes, and the main loop
+/// decodes them with a [`Q565StreamingDecodeContext`]. The queue is only locked (with a
+/// [critical section](critical_section)) to copy bytes in or out, never while decoding, so the
+/// interrupt handler is blocked for a few bytes' worth of copying at most.
+///
+/// Like the streaming decoder, this decodes the image data without the header.
+///
+/// ```no_run
+/// use q565::{byteorder::BigEndian, embedded::IsrFedDecoder};
+///
+/// static DECODER: IsrFedDecoder<256> = IsrFedDecoder::new();
+///
+/// fn uart_rx_interrupt(byte: u8) {
+///     if !DECODER.push_byte(byte) {
+///         // overrun: the main loop didn't keep up
+///     }
+/// }
+///
+/// fn main_loop(framebuffer: &mut [u16]) {
+///     let mut pixels = 0;
+///     loop {
+///         if let Some(progress) = DECODER.drain::<BigEndian>(&mut framebuffer[pixels..]) {
+///             pixels += progress.pixels_written;
+///             if progress.finished || progress.output_full {
+///                 break;
+///             }
+///         }
+///     }
+/// }
+/// ```
+pub struct IsrFedDecoder<const CAP: usize> {
+    queue: Mutex<RefCell<ByteQueue<CAP>>>,
+    /// Whether a drain is in progress, guarding `context`.
+    draining: Mutex<Cell<bool>>,
+    context: UnsafeCell<Q565StreamingDecodeContext>,
+}
+
+// SAFETY: the queue is behind a critical section, and `context` is only accessed by the holder of
+// the `draining` flag.
+unsafe impl<const CAP: usize> Sync for IsrFedDecoder<CAP> {}
+
+impl<const CAP: usize> IsrFedDecoder<CAP> {
+    pub const fn new() -> Self {
+        Self {
+            queue: Mutex::new(RefCell::new(ByteQueue::new())),
+            draining: Mutex::new(Cell::new(false)),
+            context: UnsafeCell::new(Q565StreamingDecodeContext::new()),
+        }
+    }
+
+    /// Queues a received byte. Returns `false` if the queue is full, in which case the byte is
+    /// dropped.
+    ///
+    /// Meant to be called from the interrupt handler.
+    #[inline]
+    pub fn push_byte(&self, byte: u8) -> bool {
+        self.push(&[byte]) == 1
+    }
+
+    /// Queues received bytes, e.g. from a DMA buffer. Returns the number of bytes queued; the rest
+    /// didn't fit and is dropped.
+    ///
+    /// Meant to be called from the interrupt handler.
+    pub fn push(&self, bytes: &[u8]) -> usize {
+        critical_section::with(|cs| self.queue.borrow_ref_mut(cs).push(bytes))
+    }
+
+    /// Returns the number of queued bytes that weren't decoded yet.
+    pub fn queued(&self) -> usize {
+        critical_section::with(|cs| self.queue.borrow_ref(cs).len)
+    }
+
+    /// Returns whether the end marker was decoded. Any further input is ignored until
+    /// [`reset`](Self::reset).
+    ///
+    /// Returns `false` while a drain is in progress.
+    pub fn is_finished(&self) -> bool {
+        self.with_context(|context| context.is_finished())
+            .unwrap_or(false)
+    }
+
+    /// Clears the queue and the decoder state, to start decoding the next image.
+    ///
+    /// Returns `false` if a drain is in progress, in which case nothing is reset.
+    pub fn reset(&self) -> bool {
+        self.with_context(|context| {
+            *context = Q565StreamingDecodeContext::new();
+            critical_section::with(|cs| self.queue.borrow_ref_mut(cs).clear());
+        })
+        .is_some()
+    }
+
+    /// Decodes all queued bytes into `output`, continuing where the previous call stopped.
+    ///
+    /// Like for [`Q565StreamingDecodeContext`], the progress doesn't accumulate over multiple
+    /// calls: the next call needs to be passed the output after the pixels written by this one.
+    /// Bytes after the end marker stay queued.
+    ///
+    /// The bytes are decoded with
+    /// [`streaming_decode_to_slice`](Q565StreamingDecodeContext::streaming_decode_to_slice), so
+    /// any bytes are safe to push. If `output` is full before the image is, the call returns with
+    /// [`output_full`](StreamingDecodeProgress::output_full) set, and the rest stays queued.
+    ///
+    /// Returns `None` if another drain is in progress, e.g. when called from an interrupt handler
+    /// that interrupted the main loop while draining.
+    pub fn drain<B: Endianness>(&self, output: &mut [u16]) -> Option<StreamingDecodeProgress> {
+        // SAFETY: the checked decoder only writes within `output`
+        unsafe { self.drain_with::<B, true>(output) }
+    }
+
+    /// Like [`drain`](Self::drain), but decodes without any checks.
+    ///
+    /// # Safety
+    ///
+    /// Same as
+    /// [`streaming_decode_to_slice_unchecked`](Q565StreamingDecodeContext::streaming_decode_to_slice_unchecked):
+    /// the pushed bytes need to be a valid Q565 image, and `output` needs to be large enough for
+    /// its remaining pixels.
+    pub unsafe fn drain_unchecked<B: Endianness>(
+        &self,
+        output: &mut [u16],
+    ) -> Option<StreamingDecodeProgress> {
+        unsafe { self.drain_with::<B, false>(output) }
+    }
+
+    /// # Safety
+    ///
+    /// Without `CHECKED`, same as [`drain_unchecked`](Self::drain_unchecked).
+    unsafe fn drain_with<B: Endianness, const CHECKED: bool>(
+        &self,
+        output: &mut [u16],
+    ) -> Option<StreamingDecodeProgress> {
+        self.with_context(|context| {
+            let mut progress = StreamingDecodeProgress::default();
+            let mut chunk = [0; DRAIN_CHUNK_LEN];
+
+            while !context.is_finished() {
+                let len = critical_section::with(|cs| self.queue.borrow_ref(cs).peek(&mut chunk));
+                if len == 0 {
+                    break;
+                }
+
+                // decoded outside of the critical section, so the interrupt handler can keep
+                // pushing in the meantime
+                let chunk_progress = if CHECKED {
+                    context.streaming_decode_to_slice::<B>(
+                        &chunk[..len],
+                        &mut output[progress.pixels_written..],
+                    )
+                } else {
+                    unsafe {
+                        context.streaming_decode_to_slice_with_progress_unchecked::<B>(
+                            &chunk[..len],
+                            output.get_unchecked_mut(progress.pixels_written..),
+                        )
+                    }
+                };
+                critical_section::with(|cs| {
+                    self.queue
+                        .borrow_ref_mut(cs)
+                        .pop(chunk_progress.bytes_consumed)
+                });
+
+                progress.bytes_consumed += chunk_progress.bytes_consumed;
+                progress.pixels_written += chunk_progress.pixels_written;
+                if chunk_progress.output_full {
+                    progress.output_full = true;
+                    break;
+                }
+            }
+
+            progress.finished = context.is_finished();
+            progress
+        })
+    }
+
+    /// Runs `f` with exclusive access to the decoder state, or returns `None` if a drain is in
+    /// progress.
+    fn with_context<R>(&self, f: impl FnOnce(&mut Q565StreamingDecodeContext) -> R) -> Option<R> {
+        let acquired = critical_section::with(|cs| !self.draining.borrow(cs).replace(true));
+        if !acquired {
+            return None;
+        }
+
+        // SAFETY: the `draining` flag was acquired above, so nothing else accesses the context
+        let result = f(unsafe { &mut *self.context.get() });
+        critical_section::with(|cs| self.draining.borrow(cs).set(false));
+        Some(result)
+    }
+}
+
+impl<const CAP: usize> Default for IsrFedDecoder<CAP> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Ring buffer of bytes.
+struct ByteQueue<const CAP: usize> {
+    buf: [u8; CAP],
+    /// Index of the first queued byte.
+    start: usize,
+    len: usize,
+}
+
+impl<const CAP: usize> ByteQueue<CAP> {
+    const fn new() -> Self {
+        Self {
+            buf: [0; CAP],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> usize {
+        let n = bytes.len().min(CAP - self.len);
+        for (i, &byte) in bytes[..n].iter().enumerate() {
+            self.buf[(self.start + self.len + i) % CAP] = byte;
+        }
+        self.len += n;
+        n
+    }
+
+    /// Copies queued bytes from the front of the queue into `out`, without removing them.
+    fn peek(&self, out: &mut [u8]) -> usize {
+        let n = out.len().min(self.len);
+        for (i, byte) in out[..n].iter_mut().enumerate() {
+            *byte = self.buf[(self.start + i) % CAP];
+        }
+        n
+    }
+
+    fn pop(&mut self, count: usize) {
+        let count = count.min(self.len);
+        if count > 0 {
+            self.start = (self.start + count) % CAP;
+            self.len -= count;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+}
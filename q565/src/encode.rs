@@ -1,236 +1,458 @@
 use crate::{
     consts::*,
+    decode::Q565DecodeContext,
     utils::{decode_565, diff_n, hash},
+    ColorArraySize, HeaderInfo,
 };
-use alloc::vec::Vec;
-use core::borrow::Borrow;
-use itertools::Itertools;
 
+#[cfg(feature = "alloc")]
+mod alloc_api;
+#[cfg(feature = "alloc")]
+mod builder;
+mod compact;
+#[cfg(feature = "alloc")]
+mod encoder;
+mod fast_rle;
+mod source;
 #[cfg(feature = "std")]
 mod std_api;
+mod streaming;
+
+#[cfg(feature = "alloc")]
+pub use builder::*;
+pub use compact::*;
+#[cfg(feature = "alloc")]
+pub use encoder::*;
+pub use fast_rle::*;
+pub use source::*;
 #[cfg(feature = "std")]
 pub use std_api::*;
-
+pub use streaming::*;
+
+/// Encoder state, with a color array of `N` entries.
+///
+/// `N` selects the [color array profile](crate#color-array-profiles) and must be 16, 32, or 64.
+///
+/// The color array is indexed by green channel as well, so it's only modified through
+/// [`set_color_array_entry`](Self::set_color_array_entry). To continue encoding from the state of
+/// a decoder, convert the decoder context with [`From`].
 #[derive(Debug, Clone, Copy)]
-pub struct Q565EncodeContext {
+pub struct Q565EncodeContext<const N: usize = 64> {
     pub prev: u16,
     pub prev_components: [u8; 3],
 
-    pub arr: [u16; 64],
-    pub arr_components: [[u8; 3]; 64],
+    arr: [u16; N],
+    arr_components: [[u8; 3]; N],
+    /// Bitmask of the color array entries per bucket of [`GREEN_BUCKET_WIDTH`] green values, to
+    /// find the candidates for [`Q565_OP_DIFF_INDEXED`] without checking the whole array.
+    green_buckets: [u64; GREEN_BUCKETS],
+}
+
+/// Number of green values sharing a bucket of the green index.
+const GREEN_BUCKET_WIDTH: u8 = 4;
+const GREEN_BUCKETS: usize = 64 / GREEN_BUCKET_WIDTH as usize;
+
+#[inline]
+const fn green_bucket(g: u8) -> usize {
+    (g / GREEN_BUCKET_WIDTH) as usize % GREEN_BUCKETS
 }
 
 impl Q565EncodeContext {
     pub const fn new() -> Self {
+        Self::new_sized()
+    }
+}
+
+impl<const N: usize> Q565EncodeContext<N> {
+    const COLOR_ARRAY_SIZE: ColorArraySize = match ColorArraySize::from_entries(N) {
+        Some(size) => size,
+        None => panic!("the color array must have 16, 32, or 64 entries"),
+    };
+
+    /// Creates a new context for the color array profile with `N` entries.
+    pub const fn new_sized() -> Self {
+        let _ = Self::COLOR_ARRAY_SIZE;
+
+        let mut green_buckets = [0; GREEN_BUCKETS];
+        // all entries start out black
+        green_buckets[0] = u64::MAX >> (64 - N);
+
         Self {
             prev: 0,
             prev_components: [0; 3],
 
-            arr: [0; 64],
-            arr_components: [[0; 3]; 64],
+            arr: [0; N],
+            arr_components: [[0; 3]; N],
+            green_buckets,
+        }
+    }
+
+    /// The color array.
+    #[inline]
+    pub const fn color_array(&self) -> &[u16; N] {
+        &self.arr
+    }
+
+    /// Sets the color array entry `index` to `pixel`, and updates the green channel index with it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is `N` or more.
+    #[inline]
+    pub fn set_color_array_entry(&mut self, index: usize, pixel: u16) {
+        assert!(index < N, "color array index out of bounds");
+        self.set_entry(index, pixel, decode_565(pixel));
+    }
+
+    /// Returns the header for an image of the given size, encoded with this context.
+    pub const fn header(width: u16, height: u16) -> HeaderInfo {
+        HeaderInfo {
+            width,
+            height,
+            color_array_size: Self::COLOR_ARRAY_SIZE,
+            raw: false,
+        }
+    }
+}
+
+impl<const N: usize> ColorArray for Q565EncodeContext<N> {
+    #[inline]
+    fn entry(&self, index: usize) -> u16 {
+        self.arr[index & (N - 1)]
+    }
+
+    #[inline]
+    fn set_entry(&mut self, index: usize, pixel: u16, components: [u8; 3]) {
+        let index = index & (N - 1);
+        let bit = 1 << index;
+        self.green_buckets[green_bucket(self.arr_components[index][1])] &= !bit;
+        self.green_buckets[green_bucket(components[1])] |= bit;
+
+        self.arr[index] = pixel;
+        self.arr_components[index] = components;
+    }
+
+    #[inline]
+    fn find_diff_indexed(&self, components: [u8; 3]) -> Option<[u8; 3]> {
+        // the entry's green value is within `g - 3..=g + 4`, which covers at most 3 buckets
+        let first = green_bucket(components[1].wrapping_sub(3) & 0b11_1111);
+        let mut candidates = self.green_buckets[first]
+            | self.green_buckets[(first + 1) % GREEN_BUCKETS]
+            | self.green_buckets[(first + 2) % GREEN_BUCKETS];
+
+        // in index order, like a scan of the whole array
+        while candidates != 0 {
+            let i = candidates.trailing_zeros() as usize;
+            candidates &= candidates - 1;
+
+            // only the bits of the `N` entries are ever set
+            if let Some(op) = diff_indexed_op(i, components, self.arr_components[i & (N - 1)]) {
+                return Some(op);
+            }
         }
+
+        None
     }
 }
 
-impl Default for Q565EncodeContext {
+impl<const N: usize> Default for Q565EncodeContext<N> {
     fn default() -> Self {
-        Self::new()
+        Self::new_sized()
     }
 }
 
-impl Q565EncodeContext {
-    pub fn encode_to_vec(width: u16, height: u16, pixels: &[u16], w: &mut Vec<u8>) -> bool {
-        let mut state = Q565EncodeContext::new();
-        state.encode_to_vec_with_state(width, height, pixels, w)
-    }
-
-    pub fn encode_to_vec_with_state(
-        &mut self,
-        width: u16,
-        height: u16,
-        pixels: &[u16],
-        w: &mut Vec<u8>,
-    ) -> bool {
-        if usize::from(width) * usize::from(height) != pixels.len() {
-            return false;
+/// State the [streaming encoder](Q565StreamingEncodeContext) keeps between calls: either
+/// [`Q565EncodeContext`], or the smaller [`Q565CompactEncodeContext`].
+///
+/// This trait is sealed and can't be implemented outside of this crate.
+pub trait EncoderState: sealed::EncoderState {}
+
+pub(crate) mod sealed {
+    pub trait EncoderState: Copy {
+        /// Returns the previous pixel.
+        fn prev(&self) -> u16;
+        /// Encodes a pixel that is different from the previous one, returning the bytes of the op
+        /// and its length.
+        fn encode_pixel(&mut self, pixel: u16) -> ([u8; 3], usize);
+    }
+}
+
+impl<const N: usize> sealed::EncoderState for Q565EncodeContext<N> {
+    #[inline]
+    fn prev(&self) -> u16 {
+        self.prev
+    }
+
+    #[inline]
+    fn encode_pixel(&mut self, pixel: u16) -> ([u8; 3], usize) {
+        Q565EncodeContext::encode_pixel(self, pixel)
+    }
+}
+
+impl<const N: usize> EncoderState for Q565EncodeContext<N> {}
+
+impl<const N: usize> From<Q565DecodeContext<N>> for Q565EncodeContext<N> {
+    /// Creates an encoder context that continues from the state of a decoder, e.g. to re-encode
+    /// the rest of an image after an edit.
+    fn from(ctx: Q565DecodeContext<N>) -> Self {
+        let mut encoder = Self::new_sized();
+        encoder.prev = ctx.prev;
+        encoder.prev_components = decode_565(ctx.prev);
+        for (index, pixel) in ctx.arr.into_iter().enumerate() {
+            encoder.set_entry(index, pixel, decode_565(pixel));
         }
+        encoder
+    }
+}
 
-        w.extend_from_slice(b"q565");
-        w.extend_from_slice(&width.to_le_bytes());
-        w.extend_from_slice(&height.to_le_bytes());
-
-        let mut pixels = pixels.iter();
-
-        loop {
-            let Some(&pixel) = pixels.next() else {
-                break;
-            };
-
-            if pixel == self.prev {
-                let slice = pixels.as_slice();
-                let repeats = slice.iter().take_while(|&&p| p == self.prev).count();
-                pixels = slice[repeats..].iter();
-
-                // initial pixel
-                let count = repeats + 1;
-
-                let max_count_count = count / 62;
-                let rest_count = count % 62;
-                for _ in 0..max_count_count {
-                    w.push(0b1100_0000 | (62 - 1));
-                }
-                if rest_count > 0 {
-                    w.push(0b1100_0000 | (rest_count - 1) as u8);
-                }
-
-                // already same as prev, no need to update
-                // already same as prev, already in arr
-                continue;
-            }
+/// Version of the encoder heuristics, i.e. of which ops are chosen for given pixels.
+///
+/// The encoded output only depends on the pixels, the color array profile, and the encoder
+/// version. Changes to the heuristics that change the output get a new version, which has to be
+/// opted into with [`encode_to_vec_versioned`](Q565EncodeContext::encode_to_vec_versioned). The
+/// output of an existing version stays byte-identical across releases, so committed assets don't
+/// churn on upgrades.
+///
+/// All other encode functions of [`Q565EncodeContext`] and [`Q565StreamingEncodeContext`] encode
+/// with [`EncoderVersion::V1`]. [`encode_fast_rle`] is a separate encoder, with output that is
+/// stable as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[non_exhaustive]
+pub enum EncoderVersion {
+    /// The encoder of the initial release.
+    #[default]
+    V1,
+}
+
+impl EncoderVersion {
+    /// The most recent version, with the best compression.
+    pub const LATEST: Self = Self::V1;
 
-            self.encode_pixel(pixel, w);
+    /// Returns the version with the given number, e.g. `1` for [`EncoderVersion::V1`].
+    pub const fn from_number(number: u8) -> Option<Self> {
+        match number {
+            1 => Some(Self::V1),
+            _ => None,
         }
+    }
 
-        w.push(Q565_OP_END);
-
-        true
-    }
-
-    pub fn encode_iter_to_vec<I>(width: u16, height: u16, pixels: I, w: &mut Vec<u8>) -> bool
-    where
-        I: IntoIterator,
-        I::Item: Borrow<u16>,
-    {
-        let mut state = Q565EncodeContext::new();
-        state.encode_iter_to_vec_with_state(width, height, pixels, w)
-    }
-
-    pub fn encode_iter_to_vec_with_state<I>(
-        &mut self,
-        width: u16,
-        height: u16,
-        pixels: I,
-        w: &mut Vec<u8>,
-    ) -> bool
-    where
-        I: IntoIterator,
-        I::Item: Borrow<u16>,
-    {
-        w.extend_from_slice(b"q565");
-        w.extend_from_slice(&width.to_le_bytes());
-        w.extend_from_slice(&height.to_le_bytes());
-
-        let mut pixels = pixels.into_iter().peekable();
-
-        loop {
-            let Some(pixel) = pixels.next() else {
-                break;
-            };
-
-            let pixel = *pixel.borrow();
-
-            if pixel == self.prev {
-                let repeats = pixels
-                    .peeking_take_while(|p| *p.borrow() == self.prev)
-                    .count();
-
-                // initial pixel
-                let count = repeats + 1;
-
-                let max_count_count = count / 62;
-                let rest_count = count % 62;
-                for _ in 0..max_count_count {
-                    w.push(0b1100_0000 | (62 - 1));
-                }
-                if rest_count > 0 {
-                    w.push(0b1100_0000 | (rest_count - 1) as u8);
-                }
-
-                // already same as prev, no need to update
-                // already same as prev, already in arr
-                continue;
-            }
+    pub const fn number(self) -> u8 {
+        match self {
+            Self::V1 => 1,
+        }
+    }
+}
 
-            self.encode_pixel(pixel, w);
+/// Number of ops of each kind in an encoded image, not counting the end marker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpCounts {
+    pub index: usize,
+    pub diff: usize,
+    pub luma: usize,
+    pub diff_indexed: usize,
+    pub run: usize,
+    pub rgb565: usize,
+}
+
+impl OpCounts {
+    /// Returns the total number of ops.
+    pub const fn total(&self) -> usize {
+        self.index + self.diff + self.luma + self.diff_indexed + self.run + self.rgb565
+    }
+
+    /// Counts the op starting with the byte `op`.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn record(&mut self, op: u8) {
+        match op {
+            Q565_OP_END => {}
+            Q565_OP_RGB565 => self.rgb565 += 1,
+            _ => match op >> 6 {
+                0b00 => self.index += 1,
+                0b01 => self.diff += 1,
+                0b10 if op & 0b0010_0000 == 0 => self.luma += 1,
+                0b10 => self.diff_indexed += 1,
+                _ => self.run += 1,
+            },
         }
+    }
+}
 
-        w.push(Q565_OP_END);
+/// Summary of an encoded image, e.g. to log the compression behavior per frame, or to detect
+/// content that would be better sent uncompressed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodeReport {
+    /// Number of bytes written, including the header (if any) and the end marker.
+    pub bytes_written: usize,
+    pub ops: OpCounts,
+    /// `bytes_written` relative to the size of the pixels as raw RGB565. Values above 1 mean that
+    /// the encoding expanded the data.
+    pub ratio: f32,
+}
 
-        true
+impl EncodeReport {
+    pub(crate) fn new(pixel_count: usize, bytes_written: usize, ops: OpCounts) -> Self {
+        let raw_len = 2 * pixel_count.max(1);
+        Self {
+            bytes_written,
+            ops,
+            ratio: bytes_written as f32 / raw_len as f32,
+        }
     }
 }
 
-impl Q565EncodeContext {
-    fn encode_pixel(&mut self, pixel: u16, w: &mut Vec<u8>) {
-        self.prev = pixel;
-        let [r, g, b] = decode_565(pixel);
-        let [r_prev, g_prev, b_prev] = self.prev_components;
-        self.prev_components = [r, g, b];
+/// Longest run a single [`Q565_OP_RUN`] can encode.
+pub(crate) const MAX_RUN: usize = MAX_OP_PIXELS;
 
-        let hash = hash(pixel);
-        let index = usize::from(hash);
+/// Returns the [`Q565_OP_RUN`] byte for a run of `count` (`1..=62`) pixels.
+#[inline]
+pub(crate) const fn run_op(count: usize) -> u8 {
+    Q565_OP_RUN | (count - 1) as u8
+}
 
-        if self.arr[index] == pixel {
-            w.push(hash);
-            // already in arr
-            return;
+/// Returns the number of pixels at the start of `pixels` that are equal to `color`.
+///
+/// Checks 16 pixels per iteration without branching on the individual pixels, which compiles to
+/// SIMD comparisons where available. Large flat regions, e.g. in UI screenshots, are skipped a lot
+/// faster than pixel by pixel.
+#[inline]
+pub(crate) fn run_length(pixels: &[u16], color: u16) -> usize {
+    const LANES: usize = 16;
+
+    let mut chunks = pixels.chunks_exact(LANES);
+    let mut count = 0;
+    for chunk in chunks.by_ref() {
+        if chunk.iter().fold(0, |acc, &p| acc | (p ^ color)) != 0 {
+            return count + chunk.iter().take_while(|&&p| p == color).count();
         }
+        count += LANES;
+    }
 
-        let (r_diff, g_diff, b_diff) = (
-            diff_n::<5>(r, r_prev),
-            diff_n::<6>(g, g_prev),
-            diff_n::<5>(b, b_prev),
-        );
-
-        if matches!((r_diff, g_diff, b_diff), (-2..=1, -2..=1, -2..=1)) {
-            let mut b = Q565_OP_DIFF;
-            b |= ((r_diff + 2) << 4) as u8;
-            b |= ((g_diff + 2) << 2) as u8;
-            b |= (b_diff + 2) as u8;
-            w.push(b);
-        } else {
-            let rg_diff = r_diff - g_diff;
-            let bg_diff = b_diff - g_diff;
-
-            if matches!((rg_diff, g_diff, bg_diff), (-8..=7, -16..=15, -8..=7)) {
-                let bytes = [
-                    (Q565_OP_LUMA | ((g_diff + 16) as u8)),
-                    (((rg_diff + 8) as u8) << 4 | (bg_diff + 8) as u8),
-                ];
-                w.extend_from_slice(&bytes);
-            } else if let Some(bytes) =
-                self.arr_components
-                    .iter()
-                    .enumerate()
-                    .find_map(|(i, &[r_arr, g_arr, b_arr])| {
-                        let (r_diff, g_diff, b_diff) = (
-                            diff_n::<5>(r, r_arr),
-                            diff_n::<6>(g, g_arr),
-                            diff_n::<5>(b, b_arr),
-                        );
-
-                        if matches!((r_diff, g_diff, b_diff), (-2..=1, -4..=3, -2..=1)) {
-                            let bytes = [
-                                (Q565_OP_DIFF_INDEXED
-                                    | ((g_diff + 4) as u8) << 2
-                                    | ((r_diff + 2) as u8)),
-                                (((b_diff + 2) as u8) << 6 | i as u8),
-                            ];
-                            Some(bytes)
-                        } else {
-                            None
-                        }
-                    })
-            {
-                w.extend_from_slice(&bytes);
-            } else {
-                let [a, b] = pixel.to_le_bytes();
-                w.extend_from_slice(&[Q565_OP_RGB565, a, b]);
-            }
+    count
+        + chunks
+            .remainder()
+            .iter()
+            .take_while(|&&p| p == color)
+            .count()
+}
 
-            // add to color array
-            self.arr[index] = pixel;
-            self.arr_components[index] = [r, g, b];
-        }
+impl<const N: usize> Q565EncodeContext<N> {
+    /// Encodes a pixel that is different from the previous one, returning the bytes of the op and
+    /// its length.
+    ///
+    /// Runs of the previous pixel need to be handled by the caller.
+    #[inline]
+    pub(crate) fn encode_pixel(&mut self, pixel: u16) -> ([u8; 3], usize) {
+        self.encode_pixel_with::<true>(pixel)
     }
+
+    /// Like [`encode_pixel`](Self::encode_pixel), but without the ops referencing the color array,
+    /// for the [no-array profile](crate#no-color-array).
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn encode_pixel_no_array(&mut self, pixel: u16) -> ([u8; 3], usize) {
+        self.encode_pixel_with::<false>(pixel)
+    }
+
+    #[inline(always)]
+    fn encode_pixel_with<const ARRAY: bool>(&mut self, pixel: u16) -> ([u8; 3], usize) {
+        self.prev = pixel;
+        let components = decode_565(pixel);
+        let prev_components = core::mem::replace(&mut self.prev_components, components);
+
+        encode_pixel_ops::<ARRAY, N>(self, pixel, components, prev_components)
+    }
+}
+
+/// Color array of an encoder context.
+///
+/// Indices are masked to the size of the array, like in the decoders, so that none of the encode
+/// paths can panic.
+pub(crate) trait ColorArray {
+    fn entry(&self, index: usize) -> u16;
+    /// Sets the entry `index` to `pixel`, whose components are given as well.
+    fn set_entry(&mut self, index: usize, pixel: u16, components: [u8; 3]);
+    /// Finds the first entry that a pixel with the given components can be encoded relative to
+    /// with a [`Q565_OP_DIFF_INDEXED`], returning the op.
+    fn find_diff_indexed(&self, components: [u8; 3]) -> Option<[u8; 3]>;
+}
+
+/// Returns the [`Q565_OP_DIFF_INDEXED`] encoding a pixel relative to the color array entry
+/// `index`, if the difference is small enough.
+#[inline]
+pub(crate) fn diff_indexed_op(
+    index: usize,
+    [r, g, b]: [u8; 3],
+    [r_arr, g_arr, b_arr]: [u8; 3],
+) -> Option<[u8; 3]> {
+    let (r_diff, g_diff, b_diff) = (
+        diff_n::<5>(r, r_arr),
+        diff_n::<6>(g, g_arr),
+        diff_n::<5>(b, b_arr),
+    );
+
+    matches!((r_diff, g_diff, b_diff), (-2..=1, -4..=3, -2..=1)).then(|| {
+        [
+            (Q565_OP_DIFF_INDEXED | ((g_diff + 4) as u8) << 2 | ((r_diff + 2) as u8)),
+            (((b_diff + 2) as u8) << 6 | index as u8),
+            0,
+        ]
+    })
+}
+
+/// Encodes a pixel that is different from the previous one, updating the color array with `N`
+/// entries if `ARRAY` is set.
+#[inline(always)]
+pub(crate) fn encode_pixel_ops<const ARRAY: bool, const N: usize>(
+    arr: &mut impl ColorArray,
+    pixel: u16,
+    [r, g, b]: [u8; 3],
+    [r_prev, g_prev, b_prev]: [u8; 3],
+) -> ([u8; 3], usize) {
+    let index = usize::from(hash(pixel)) & (N - 1);
+
+    if ARRAY && arr.entry(index) == pixel {
+        // already in arr
+        return ([Q565_OP_INDEX | index as u8, 0, 0], 1);
+    }
+
+    let (r_diff, g_diff, b_diff) = (
+        diff_n::<5>(r, r_prev),
+        diff_n::<6>(g, g_prev),
+        diff_n::<5>(b, b_prev),
+    );
+
+    if matches!((r_diff, g_diff, b_diff), (-2..=1, -2..=1, -2..=1)) {
+        let mut b = Q565_OP_DIFF;
+        b |= ((r_diff + 2) << 4) as u8;
+        b |= ((g_diff + 2) << 2) as u8;
+        b |= (b_diff + 2) as u8;
+
+        // not added to the color array
+        return ([b, 0, 0], 1);
+    }
+
+    let rg_diff = r_diff - g_diff;
+    let bg_diff = b_diff - g_diff;
+
+    let op = if matches!((rg_diff, g_diff, bg_diff), (-8..=7, -16..=15, -8..=7)) {
+        let bytes = [
+            (Q565_OP_LUMA | ((g_diff + 16) as u8)),
+            (((rg_diff + 8) as u8) << 4 | (bg_diff + 8) as u8),
+            0,
+        ];
+        (bytes, 2)
+    } else if let Some(bytes) = ARRAY.then(|| arr.find_diff_indexed([r, g, b])).flatten() {
+        (bytes, 2)
+    } else {
+        let [a, b] = pixel.to_le_bytes();
+        ([Q565_OP_RGB565, a, b], 3)
+    };
+
+    // add to color array
+    if ARRAY {
+        arr.set_entry(index, pixel, [r, g, b]);
+    }
+
+    op
 }
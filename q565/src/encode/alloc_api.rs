@@ -0,0 +1,386 @@
+use super::{
+    run_length, run_op, EncodeReport, EncoderVersion, OpCounts, Q565EncodeContext, MAX_RUN,
+};
+use crate::{consts::*, ColorArraySize, HeaderInfo};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use itertools::Itertools;
+
+impl Q565EncodeContext {
+    /// Encodes the image and appends it to `w`.
+    ///
+    /// Returns `None` if `pixels` doesn't hold `width * height` pixels.
+    pub fn encode_to_vec(
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        let mut state = Q565EncodeContext::new();
+        state.encode_to_vec_with_state(width, height, pixels, w)
+    }
+
+    /// Like [`encode_to_vec`](Self::encode_to_vec), but using the given color array profile.
+    pub fn encode_to_vec_sized(
+        color_array_size: ColorArraySize,
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        match color_array_size {
+            ColorArraySize::NoArray => Self::encode_no_array_to_vec(width, height, pixels, w),
+            ColorArraySize::Entries16 => Q565EncodeContext::<16>::new_sized()
+                .encode_to_vec_with_state(width, height, pixels, w),
+            ColorArraySize::Entries32 => Q565EncodeContext::<32>::new_sized()
+                .encode_to_vec_with_state(width, height, pixels, w),
+            ColorArraySize::Entries64 => Q565EncodeContext::<64>::new_sized()
+                .encode_to_vec_with_state(width, height, pixels, w),
+        }
+    }
+
+    /// Like [`encode_to_vec_sized`](Self::encode_to_vec_sized), but with the heuristics of the
+    /// given encoder version, see [`EncoderVersion`].
+    pub fn encode_to_vec_versioned(
+        version: EncoderVersion,
+        color_array_size: ColorArraySize,
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        match version {
+            EncoderVersion::V1 => {
+                Self::encode_to_vec_sized(color_array_size, width, height, pixels, w)
+            }
+        }
+    }
+
+    /// Like [`encode_to_vec`](Self::encode_to_vec), but using the [no-array
+    /// profile](crate#no-color-array), so the image can be decoded by a
+    /// [`MiniDecoder`](crate::decode::MiniDecoder).
+    pub fn encode_no_array_to_vec(
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        if crate::pixel_count(width, height) != Some(pixels.len()) {
+            return None;
+        }
+
+        let header = HeaderInfo {
+            width,
+            height,
+            color_array_size: ColorArraySize::NoArray,
+            raw: false,
+        };
+        let (header, header_len) = header.to_bytes();
+        w.extend_from_slice(&header[..header_len]);
+
+        // the color array is never used, so the smallest one will do
+        let report =
+            Q565EncodeContext::<16>::new_sized().encode_ops_to_vec::<false>(pixels, None, w);
+
+        Some(EncodeReport::new(
+            pixels.len(),
+            header_len + report.bytes_written,
+            report.ops,
+        ))
+    }
+
+    /// Like [`encode_to_vec`](Self::encode_to_vec), but stores the image as a [raw
+    /// image](crate#raw-images) instead if encoding it would expand the data, e.g. for noise.
+    ///
+    /// The report of a raw image counts no ops.
+    pub fn encode_auto(
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        let start = w.len();
+        let report = Self::encode_to_vec(width, height, pixels, w)?;
+
+        let header = HeaderInfo {
+            width,
+            height,
+            color_array_size: ColorArraySize::Entries64,
+            raw: true,
+        };
+        let (header, header_len) = header.to_bytes();
+        let raw_len = header_len + 2 * pixels.len();
+        if report.bytes_written <= raw_len {
+            return Some(report);
+        }
+
+        w.truncate(start);
+        w.reserve(raw_len);
+        w.extend_from_slice(&header[..header_len]);
+        w.extend(pixels.iter().flat_map(|pixel| pixel.to_le_bytes()));
+
+        Some(EncodeReport::new(
+            pixels.len(),
+            raw_len,
+            OpCounts::default(),
+        ))
+    }
+
+    /// Like [`encode_to_vec`](Self::encode_to_vec), but with [row-aligned
+    /// runs](crate#row-aligned-runs): no run crosses the end of a row.
+    pub fn encode_row_aligned_to_vec(
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        let mut state = Q565EncodeContext::new();
+        state.encode_row_aligned_to_vec_with_state(width, height, pixels, w)
+    }
+
+    /// Like [`encode_to_vec`](Self::encode_to_vec), but taking the pixels from an iterator.
+    ///
+    /// Unlike the slice variants, this can't return `None`: the number of pixels is only known once
+    /// the iterator is exhausted, and the image is written by then, so it isn't checked. If the
+    /// iterator yields more or fewer than `width * height` pixels, the decoders reject the image.
+    pub fn encode_iter_to_vec<I>(
+        width: u16,
+        height: u16,
+        pixels: I,
+        w: &mut Vec<u8>,
+    ) -> EncodeReport
+    where
+        I: IntoIterator,
+        I::Item: Borrow<u16>,
+    {
+        let mut state = Q565EncodeContext::new();
+        state.encode_iter_to_vec_with_state(width, height, pixels, w)
+    }
+}
+
+impl<const N: usize> Q565EncodeContext<N> {
+    pub fn encode_to_vec_with_state(
+        &mut self,
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        if crate::pixel_count(width, height) != Some(pixels.len()) {
+            return None;
+        }
+
+        let (header, header_len) = Self::header(width, height).to_bytes();
+        w.extend_from_slice(&header[..header_len]);
+
+        let report = self.encode_pixels_to_vec(pixels, w);
+
+        Some(EncodeReport::new(
+            pixels.len(),
+            header_len + report.bytes_written,
+            report.ops,
+        ))
+    }
+
+    /// Encodes the next frame of a [frame sequence](crate#frame-sequences), with the color array
+    /// and the previous pixel carried over from the frames before it.
+    ///
+    /// This encodes the same as [`encode_to_vec_with_state`](Self::encode_to_vec_with_state), but
+    /// the frames need to be decoded in order with
+    /// [`Q565DecodeContext::decode_frame`](crate::decode::Q565DecodeContext::decode_frame), with a
+    /// fresh decoder context for a fresh encoder context. It is kept as a separate name so that
+    /// frame sequences pair up with `decode_frame` and
+    /// [`encode_frame_or_repeat`](Self::encode_frame_or_repeat), while
+    /// `encode_to_vec_with_state` is the general building block, e.g. for keeping the state to
+    /// [snapshot](crate::snapshot) it.
+    pub fn encode_frame(
+        &mut self,
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        self.encode_to_vec_with_state(width, height, pixels, w)
+    }
+
+    /// Like [`encode_frame`](Self::encode_frame), but if `pixels` are the same as `previous`, the
+    /// pixels of the frame before, only appends [`REPEAT_FRAME`](crate::REPEAT_FRAME) and keeps the
+    /// state as it is.
+    ///
+    /// The frames need to be decoded with
+    /// [`Q565DecodeContext::decode_frame_or_repeat`](crate::decode::Q565DecodeContext::decode_frame_or_repeat).
+    pub fn encode_frame_or_repeat(
+        &mut self,
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        previous: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        if crate::pixel_count(width, height) != Some(pixels.len()) {
+            return None;
+        }
+        if pixels == previous {
+            w.push(crate::REPEAT_FRAME);
+            return Some(EncodeReport::new(pixels.len(), 1, OpCounts::default()));
+        }
+
+        self.encode_frame(width, height, pixels, w)
+    }
+
+    /// Encodes the pixels (without a header) and the end marker, continuing from the current
+    /// state.
+    pub fn encode_pixels_to_vec(&mut self, pixels: &[u16], w: &mut Vec<u8>) -> EncodeReport {
+        self.encode_ops_to_vec::<true>(pixels, None, w)
+    }
+
+    /// Like [`encode_to_vec_with_state`](Self::encode_to_vec_with_state), but with [row-aligned
+    /// runs](crate#row-aligned-runs): no run crosses the end of a row.
+    pub fn encode_row_aligned_to_vec_with_state(
+        &mut self,
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        w: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        if crate::pixel_count(width, height) != Some(pixels.len()) {
+            return None;
+        }
+
+        let (header, header_len) = Self::header(width, height).to_bytes();
+        w.extend_from_slice(&header[..header_len]);
+
+        let report = self.encode_ops_to_vec::<true>(pixels, Some(usize::from(width)), w);
+
+        Some(EncodeReport::new(
+            pixels.len(),
+            header_len + report.bytes_written,
+            report.ops,
+        ))
+    }
+
+    /// Encodes the pixels and the end marker. Runs are split at the end of every row of
+    /// `row_len` pixels, if given.
+    #[inline(always)]
+    fn encode_ops_to_vec<const ARRAY: bool>(
+        &mut self,
+        pixels: &[u16],
+        row_len: Option<usize>,
+        w: &mut Vec<u8>,
+    ) -> EncodeReport {
+        let start = w.len();
+        let pixel_count = pixels.len();
+        let mut ops = OpCounts::default();
+        for row in pixels.chunks(row_len.unwrap_or(pixel_count).max(1)) {
+            self.encode_row_ops_to_vec::<ARRAY>(row, &mut ops, w);
+        }
+
+        w.push(Q565_OP_END);
+
+        EncodeReport::new(pixel_count, w.len() - start, ops)
+    }
+
+    /// Encodes the pixels, with runs ending at the end of `pixels`.
+    #[inline(always)]
+    fn encode_row_ops_to_vec<const ARRAY: bool>(
+        &mut self,
+        pixels: &[u16],
+        ops: &mut OpCounts,
+        w: &mut Vec<u8>,
+    ) {
+        let mut pixels = pixels.iter();
+
+        while let Some(&pixel) = pixels.next() {
+            if pixel == self.prev {
+                let slice = pixels.as_slice();
+                let repeats = run_length(slice, self.prev);
+                pixels = slice[repeats..].iter();
+
+                // initial pixel
+                let count = repeats + 1;
+
+                let max_count_count = count / MAX_RUN;
+                let rest_count = count % MAX_RUN;
+                w.resize(w.len() + max_count_count, run_op(MAX_RUN));
+                if rest_count > 0 {
+                    w.push(run_op(rest_count));
+                }
+                ops.run += max_count_count + usize::from(rest_count > 0);
+
+                // already same as prev, no need to update
+                // already same as prev, already in arr
+                continue;
+            }
+
+            let (op, len) = if ARRAY {
+                self.encode_pixel(pixel)
+            } else {
+                self.encode_pixel_no_array(pixel)
+            };
+            w.extend_from_slice(&op[..len]);
+            ops.record(op[0]);
+        }
+    }
+
+    /// Like [`encode_iter_to_vec`](Self::encode_iter_to_vec), but continuing from the current
+    /// state. The number of pixels isn't checked either.
+    pub fn encode_iter_to_vec_with_state<I>(
+        &mut self,
+        width: u16,
+        height: u16,
+        pixels: I,
+        w: &mut Vec<u8>,
+    ) -> EncodeReport
+    where
+        I: IntoIterator,
+        I::Item: Borrow<u16>,
+    {
+        let start = w.len();
+        let mut pixel_count = 0;
+        let mut ops = OpCounts::default();
+
+        let (header, header_len) = Self::header(width, height).to_bytes();
+        w.extend_from_slice(&header[..header_len]);
+
+        let mut pixels = pixels.into_iter().peekable();
+
+        loop {
+            let Some(pixel) = pixels.next() else {
+                break;
+            };
+
+            let pixel = *pixel.borrow();
+            pixel_count += 1;
+
+            if pixel == self.prev {
+                let repeats = pixels
+                    .peeking_take_while(|p| *p.borrow() == self.prev)
+                    .count();
+
+                // initial pixel
+                let count = repeats + 1;
+                pixel_count += repeats;
+
+                let max_count_count = count / MAX_RUN;
+                let rest_count = count % MAX_RUN;
+                w.resize(w.len() + max_count_count, run_op(MAX_RUN));
+                if rest_count > 0 {
+                    w.push(run_op(rest_count));
+                }
+                ops.run += max_count_count + usize::from(rest_count > 0);
+
+                // already same as prev, no need to update
+                // already same as prev, already in arr
+                continue;
+            }
+
+            let (op, len) = self.encode_pixel(pixel);
+            w.extend_from_slice(&op[..len]);
+            ops.record(op[0]);
+        }
+
+        w.push(Q565_OP_END);
+
+        EncodeReport::new(pixel_count, w.len() - start, ops)
+    }
+}
@@ -0,0 +1,298 @@
+use super::{encode_fast_rle, fast_rle_max_len, EncodeReport, EncoderVersion, Q565EncodeContext};
+use crate::byteorder::{DefaultOrder, Endianness};
+use crate::utils::{encode_rgb565_unchecked, rgb888_to_rgb565_with, Quantization};
+use crate::{pipeline, ColorArraySize};
+use alloc::{borrow::Cow, vec::Vec};
+use core::{fmt, marker::PhantomData, str::FromStr};
+
+/// Encoder preset, trading encoding speed for size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Speed {
+    /// Only runs and literal pixels, see [`encode_fast_rle`].
+    Fast,
+    /// The regular encoder, with the chosen color array profile.
+    #[default]
+    Default,
+    /// Tries every color array profile and the [raw fallback](crate#raw-images), and keeps the
+    /// smallest result.
+    Best,
+}
+
+impl FromStr for Speed {
+    type Err = &'static str;
+
+    /// Parses `fast`, `default`, or `best`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("fast") {
+            Ok(Speed::Fast)
+        } else if s.eq_ignore_ascii_case("default") {
+            Ok(Speed::Default)
+        } else if s.eq_ignore_ascii_case("best") {
+            Ok(Speed::Best)
+        } else {
+            Err("expected fast, default, or best")
+        }
+    }
+}
+
+/// How RGB888 pixels are converted to RGB565.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Dither {
+    /// Quantize every pixel on its own, see [`Encoder::quantization`].
+    #[default]
+    None,
+    /// Ordered dithering, see [`pipeline::dither`].
+    Ordered,
+}
+
+impl FromStr for Dither {
+    type Err = &'static str;
+
+    /// Parses `none` or `ordered`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("none") {
+            Ok(Dither::None)
+        } else if s.eq_ignore_ascii_case("ordered") {
+            Ok(Dither::Ordered)
+        } else {
+            Err("expected none or ordered")
+        }
+    }
+}
+
+/// Encoder with all options in one place, the counterpart of
+/// [`Decoder`](crate::decode::Decoder).
+///
+/// ```
+/// use q565::{
+///     byteorder::{BigEndian, NativeEndian},
+///     decode::Decoder,
+///     encode::{Dither, Encoder, Speed},
+/// };
+///
+/// let encoder = Encoder::new()
+///     .speed(Speed::Best)
+///     .dither(Dither::Ordered)
+///     .byte_order::<BigEndian>();
+///
+/// let mut encoded = Vec::new();
+/// encoder
+///     .encode_rgb888(2, 1, &[[0xFF, 0, 0], [0, 0, 0xFF]], &mut encoded)
+///     .unwrap();
+/// let (_, pixels) = Decoder::new()
+///     .byte_order::<NativeEndian>()
+///     .decode_to_vec(&encoded)
+///     .unwrap();
+/// assert_eq!(pixels, [0xF800, 0x001F]);
+/// ```
+///
+/// By default, the encoder uses [`Speed::Default`] with the 64-entry color array and
+/// [`EncoderVersion::V1`], rounds to the nearest color without dithering, and takes RGB565 pixels
+/// in the [`DefaultOrder`] byte order: [`NativeEndian`], i.e. the pixel values themselves, unless
+/// the `default-be` feature is enabled.
+///
+/// [`NativeEndian`]: crate::byteorder::NativeEndian
+pub struct Encoder<B: Endianness = DefaultOrder> {
+    speed: Speed,
+    dither: Dither,
+    quantization: Quantization,
+    color_array_size: ColorArraySize,
+    version: EncoderVersion,
+    _byte_order: PhantomData<fn() -> B>,
+}
+
+impl Encoder {
+    pub const fn new() -> Self {
+        Self {
+            speed: Speed::Default,
+            dither: Dither::None,
+            quantization: Quantization::Round,
+            color_array_size: ColorArraySize::Entries64,
+            version: EncoderVersion::V1,
+            _byte_order: PhantomData,
+        }
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Endianness> Encoder<B> {
+    pub const fn speed(mut self, speed: Speed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Sets how [`encode_rgb888`](Self::encode_rgb888) converts the pixels to RGB565.
+    pub const fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Sets how [`encode_rgb888`](Self::encode_rgb888) quantizes the pixels without dithering.
+    pub const fn quantization(mut self, quantization: Quantization) -> Self {
+        self.quantization = quantization;
+        self
+    }
+
+    /// Sets the [color array profile](crate#color-array-profiles) of [`Speed::Default`]. The
+    /// other presets choose their own.
+    pub const fn color_array_size(mut self, color_array_size: ColorArraySize) -> Self {
+        self.color_array_size = color_array_size;
+        self
+    }
+
+    /// Sets the encoder version, to keep the output byte-identical across releases, see
+    /// [`EncoderVersion`]. [`Speed::Fast`] has stable output regardless.
+    pub const fn version(mut self, version: EncoderVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the byte order of the RGB565 pixels passed to [`encode`](Self::encode), e.g.
+    /// [`BigEndian`](crate::byteorder::BigEndian) for a buffer that is sent to a display as is.
+    pub const fn byte_order<B2: Endianness>(self) -> Encoder<B2> {
+        Encoder {
+            speed: self.speed,
+            dither: self.dither,
+            quantization: self.quantization,
+            color_array_size: self.color_array_size,
+            version: self.version,
+            _byte_order: PhantomData,
+        }
+    }
+
+    /// Encodes RGB565 pixels, in the byte order `B`, and appends the image to `output`.
+    ///
+    /// Returns `None` if `pixels` doesn't hold `width * height` pixels.
+    pub fn encode(
+        &self,
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        output: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        let pixels = if B::read_u16(&0x0102u16.to_ne_bytes()) == 0x0102 {
+            Cow::Borrowed(pixels)
+        } else {
+            Cow::Owned(pixels.iter().map(|pixel| pixel.swap_bytes()).collect())
+        };
+        self.encode_native(width, height, &pixels, output)
+    }
+
+    /// Converts RGB888 pixels to RGB565 as set with [`dither`](Self::dither), then encodes them
+    /// and appends the image to `output`.
+    ///
+    /// Returns `None` if `pixels` doesn't hold `width * height` pixels.
+    pub fn encode_rgb888(
+        &self,
+        width: u16,
+        height: u16,
+        pixels: &[[u8; 3]],
+        output: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        let width_usize = usize::from(width).max(1);
+        let pixels: Vec<u16> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, &pixel)| match self.dither {
+                Dither::None => {
+                    encode_rgb565_unchecked(rgb888_to_rgb565_with(pixel, self.quantization))
+                }
+                Dither::Ordered => pipeline::dither(pixel, i % width_usize, i / width_usize),
+            })
+            .collect();
+        self.encode_native(width, height, &pixels, output)
+    }
+
+    fn encode_native(
+        &self,
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        output: &mut Vec<u8>,
+    ) -> Option<EncodeReport> {
+        match self.speed {
+            Speed::Fast => {
+                let start = output.len();
+                output.resize(start + fast_rle_max_len(width, height), 0);
+                let report = encode_fast_rle(width, height, pixels, &mut output[start..]);
+                output.truncate(start + report.as_ref().map_or(0, |r| r.bytes_written));
+                report
+            }
+            Speed::Default => Q565EncodeContext::encode_to_vec_versioned(
+                self.version,
+                self.color_array_size,
+                width,
+                height,
+                pixels,
+                output,
+            ),
+            Speed::Best => {
+                let mut best = Vec::new();
+                // the raw fallback doesn't depend on the encoder version
+                let mut best_report = Q565EncodeContext::encode_to_vec_versioned(
+                    self.version,
+                    ColorArraySize::Entries64,
+                    width,
+                    height,
+                    pixels,
+                    &mut best,
+                )?;
+                let raw_len = crate::EXTENDED_HEADER_LEN + 2 * pixels.len();
+                if best.len() > raw_len {
+                    best.clear();
+                    best_report = Q565EncodeContext::encode_auto(width, height, pixels, &mut best)?;
+                }
+
+                let mut candidate = Vec::with_capacity(best.len());
+                for color_array_size in [
+                    ColorArraySize::Entries32,
+                    ColorArraySize::Entries16,
+                    ColorArraySize::NoArray,
+                ] {
+                    candidate.clear();
+                    let report = Q565EncodeContext::encode_to_vec_versioned(
+                        self.version,
+                        color_array_size,
+                        width,
+                        height,
+                        pixels,
+                        &mut candidate,
+                    )?;
+                    if candidate.len() < best.len() {
+                        core::mem::swap(&mut best, &mut candidate);
+                        best_report = report;
+                    }
+                }
+
+                output.extend_from_slice(&best);
+                Some(best_report)
+            }
+        }
+    }
+}
+
+impl<B: Endianness> Clone for Encoder<B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<B: Endianness> Copy for Encoder<B> {}
+
+impl<B: Endianness> fmt::Debug for Encoder<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encoder")
+            .field("speed", &self.speed)
+            .field("dither", &self.dither)
+            .field("quantization", &self.quantization)
+            .field("color_array_size", &self.color_array_size)
+            .field("version", &self.version)
+            .field("byte_order", &core::any::type_name::<B>())
+            .finish()
+    }
+}
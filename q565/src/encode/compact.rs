@@ -0,0 +1,107 @@
+use super::{
+    diff_indexed_op, encode_pixel_ops, sealed, ColorArray, EncoderState, Q565EncodeContext,
+    Q565StreamingEncodeContext,
+};
+use crate::{decode::Q565DecodeContext, utils::decode_565, HeaderInfo};
+
+/// Encoder state with the same memory footprint as the [decoder
+/// state](crate::decode::Q565DecodeContext): `2 + 2 * N` bytes, so 130 bytes for the default size.
+///
+/// [`Q565EncodeContext`] keeps the components of every color array entry, and an index of the
+/// entries by green channel, to find candidates for a
+/// [`Q565_OP_DIFF_INDEXED`](crate::consts::Q565_OP_DIFF_INDEXED) quickly. This context recomputes
+/// the components while scanning the whole array instead, which is slower for images that aren't
+/// mostly flat, but small enough to live on the stack of a small RTOS task. The encoded output is
+/// identical.
+///
+/// It is used with the streaming encoder, see [`Q565CompactStreamingEncodeContext`].
+#[derive(Debug, Clone, Copy)]
+pub struct Q565CompactEncodeContext<const N: usize = 64> {
+    pub prev: u16,
+    pub arr: [u16; N],
+}
+
+/// [`Q565StreamingEncodeContext`] using a [`Q565CompactEncodeContext`].
+pub type Q565CompactStreamingEncodeContext<const N: usize = 64> =
+    Q565StreamingEncodeContext<N, Q565CompactEncodeContext<N>>;
+
+const _: () = {
+    assert!(core::mem::size_of::<Q565CompactEncodeContext<16>>() == 34);
+    assert!(core::mem::size_of::<Q565CompactEncodeContext<32>>() == 66);
+    assert!(core::mem::size_of::<Q565CompactEncodeContext<64>>() == 130);
+};
+
+impl Q565CompactEncodeContext {
+    pub const fn new() -> Self {
+        Self::new_sized()
+    }
+}
+
+impl<const N: usize> Q565CompactEncodeContext<N> {
+    /// Creates a new context for the color array profile with `N` entries.
+    pub const fn new_sized() -> Self {
+        let _ = Q565EncodeContext::<N>::COLOR_ARRAY_SIZE;
+
+        Self {
+            prev: 0,
+            arr: [0; N],
+        }
+    }
+
+    /// Returns the header for an image of the given size, encoded with this context.
+    pub const fn header(width: u16, height: u16) -> HeaderInfo {
+        Q565EncodeContext::<N>::header(width, height)
+    }
+}
+
+impl<const N: usize> Default for Q565CompactEncodeContext<N> {
+    fn default() -> Self {
+        Self::new_sized()
+    }
+}
+
+impl<const N: usize> From<Q565DecodeContext<N>> for Q565CompactEncodeContext<N> {
+    /// Creates an encoder context that continues from the state of a decoder.
+    fn from(ctx: Q565DecodeContext<N>) -> Self {
+        Self {
+            prev: ctx.prev,
+            arr: ctx.arr,
+        }
+    }
+}
+
+impl<const N: usize> ColorArray for Q565CompactEncodeContext<N> {
+    #[inline]
+    fn entry(&self, index: usize) -> u16 {
+        self.arr[index & (N - 1)]
+    }
+
+    #[inline]
+    fn set_entry(&mut self, index: usize, pixel: u16, _components: [u8; 3]) {
+        self.arr[index & (N - 1)] = pixel;
+    }
+
+    #[inline]
+    fn find_diff_indexed(&self, components: [u8; 3]) -> Option<[u8; 3]> {
+        self.arr
+            .iter()
+            .enumerate()
+            .find_map(|(i, &entry)| diff_indexed_op(i, components, decode_565(entry)))
+    }
+}
+
+impl<const N: usize> sealed::EncoderState for Q565CompactEncodeContext<N> {
+    #[inline]
+    fn prev(&self) -> u16 {
+        self.prev
+    }
+
+    #[inline]
+    fn encode_pixel(&mut self, pixel: u16) -> ([u8; 3], usize) {
+        let prev_components = decode_565(self.prev);
+        self.prev = pixel;
+        encode_pixel_ops::<true, N>(self, pixel, decode_565(pixel), prev_components)
+    }
+}
+
+impl<const N: usize> EncoderState for Q565CompactEncodeContext<N> {}
@@ -0,0 +1,149 @@
+use super::{
+    run_op, EncoderState, PixelSource, Q565EncodeContext, Q565StreamingEncodeContext, MAX_RUN,
+};
+use crate::consts::*;
+use alloc::vec::Vec;
+
+/// Common interface of the encoders, so the encoding strategy can be chosen at runtime, e.g.
+/// with a `&mut dyn Q565Encoder`.
+///
+/// An image is encoded by calling [`encode_header`](Self::encode_header), then
+/// [`push_pixels`](Self::push_pixels) with all pixels of the image, in as many calls as
+/// convenient, then [`finish`](Self::finish). The output doesn't depend on how the pixels were
+/// split up. The encoder can be reused for the next image afterwards.
+///
+/// Implemented by:
+///
+/// - [`Q565StreamingEncodeContext`]: the regular encoder, with the same output as
+///   [`Q565EncodeContext::encode_to_vec_sized`].
+/// - [`FastRleEncoder`]: the same output as [`encode_fast_rle`](super::encode_fast_rle).
+/// - [`ReferenceEncoder`](crate::reference::ReferenceEncoder): the same output as the regular
+///   encoder, but slow.
+pub trait Q565Encoder {
+    /// Starts a new image of the given size, appending its header to `output`.
+    fn encode_header(&mut self, width: u16, height: u16, output: &mut Vec<u8>);
+
+    /// Encodes the next pixels of the image, appending the ops to `output`.
+    ///
+    /// Ops of pixels at the end may be held back until the next call or [`finish`](Self::finish),
+    /// e.g. an unfinished run.
+    fn push_pixels(&mut self, pixels: &[u16], output: &mut Vec<u8>);
+
+    /// Appends everything that was held back, and the end marker, to `output`.
+    fn finish(&mut self, output: &mut Vec<u8>);
+
+    /// Encodes a whole image, appending it to `output`.
+    ///
+    /// Returns the number of bytes written, or `None` if `pixels` doesn't hold `width * height`
+    /// pixels.
+    fn encode(
+        &mut self,
+        width: u16,
+        height: u16,
+        pixels: &[u16],
+        output: &mut Vec<u8>,
+    ) -> Option<usize> {
+        if crate::pixel_count(width, height) != Some(pixels.len()) {
+            return None;
+        }
+
+        let start = output.len();
+        self.encode_header(width, height, output);
+        self.push_pixels(pixels, output);
+        self.finish(output);
+        Some(output.len() - start)
+    }
+
+    /// Encodes a whole image from a [`PixelSource`], row by row, appending it to `output`.
+    ///
+    /// The output is the same as encoding the rows as one tight slice. Returns the number of bytes
+    /// written.
+    fn encode_source(&mut self, source: &dyn PixelSource, output: &mut Vec<u8>) -> usize {
+        let start = output.len();
+        self.encode_header(source.width(), source.height(), output);
+        for y in 0..source.height() {
+            self.push_pixels(source.row(y), output);
+        }
+        self.finish(output);
+        output.len() - start
+    }
+}
+
+impl<const N: usize, S> Q565Encoder for Q565StreamingEncodeContext<N, S>
+where
+    S: EncoderState,
+    Self: Default,
+{
+    fn encode_header(&mut self, width: u16, height: u16, output: &mut Vec<u8>) {
+        *self = Self::default();
+        let (header, header_len) = Q565EncodeContext::<N>::header(width, height).to_bytes();
+        output.extend_from_slice(&header[..header_len]);
+    }
+
+    fn push_pixels(&mut self, pixels: &[u16], output: &mut Vec<u8>) {
+        // at most 3 bytes per pixel, plus the pending run of the previous call
+        let start = output.len();
+        output.resize(start + MAX_OP_LEN * pixels.len() + 1, 0);
+        let progress = self.encode_to_slice(pixels, &mut output[start..], usize::MAX);
+        debug_assert_eq!(progress.pixels_consumed, pixels.len());
+        output.truncate(start + progress.bytes_written);
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) {
+        let start = output.len();
+        output.resize(start + 2, 0);
+        let len = Q565StreamingEncodeContext::finish(self, &mut output[start..]).unwrap_or(0);
+        output.truncate(start + len);
+    }
+}
+
+/// Incremental version of [`encode_fast_rle`](super::encode_fast_rle), see [`Q565Encoder`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FastRleEncoder {
+    prev: u16,
+    /// Length of the pending run of `prev`, not yet written to the output.
+    run: usize,
+}
+
+impl FastRleEncoder {
+    pub const fn new() -> Self {
+        Self { prev: 0, run: 0 }
+    }
+
+    fn flush_run(&mut self, output: &mut Vec<u8>) {
+        if self.run > 0 {
+            output.push(run_op(self.run));
+            self.run = 0;
+        }
+    }
+}
+
+impl Q565Encoder for FastRleEncoder {
+    fn encode_header(&mut self, width: u16, height: u16, output: &mut Vec<u8>) {
+        *self = Self::new();
+        let (header, header_len) = Q565EncodeContext::<64>::header(width, height).to_bytes();
+        output.extend_from_slice(&header[..header_len]);
+    }
+
+    fn push_pixels(&mut self, pixels: &[u16], output: &mut Vec<u8>) {
+        for &pixel in pixels {
+            if pixel == self.prev {
+                self.run += 1;
+                if self.run == MAX_RUN {
+                    self.flush_run(output);
+                }
+                continue;
+            }
+
+            self.flush_run(output);
+            let [a, b] = pixel.to_le_bytes();
+            output.extend_from_slice(&[Q565_OP_RGB565, a, b]);
+            self.prev = pixel;
+        }
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) {
+        self.flush_run(output);
+        output.push(Q565_OP_END);
+    }
+}
@@ -0,0 +1,80 @@
+use super::{run_length, run_op, EncodeReport, OpCounts, Q565EncodeContext, MAX_RUN};
+use crate::consts::*;
+
+/// Size of an image encoded with [`encode_fast_rle`] in the worst case: the header, a
+/// [`Q565_OP_RGB565`] per pixel, and the end marker.
+///
+/// Saturates at `usize::MAX` like [`max_stream_len`](crate::max_stream_len).
+pub const fn fast_rle_max_len(width: u16, height: u16) -> usize {
+    match crate::pixel_count(width, height) {
+        Some(pixels) => MAX_OP_LEN
+            .saturating_mul(pixels)
+            .saturating_add(crate::HEADER_LEN + 1),
+        None => usize::MAX,
+    }
+}
+
+/// Encodes an image into `output` using only [`Q565_OP_RUN`] and [`Q565_OP_RGB565`] ops.
+///
+/// There is no color array to maintain and no differences to compute, so this is considerably
+/// faster than the regular encoder, e.g. for encoding in interrupt context. Flat-color content
+/// like UI frames still compresses well, anything else ends up at up to 1.5 times the raw size.
+/// The result is a regular Q565 image.
+///
+/// Returns `None` if `pixels` doesn't hold `width * height` pixels, or if `output` is too small,
+/// see [`fast_rle_max_len`].
+pub fn encode_fast_rle(
+    width: u16,
+    height: u16,
+    pixels: &[u16],
+    output: &mut [u8],
+) -> Option<EncodeReport> {
+    panic_free!({
+        if crate::pixel_count(width, height) != Some(pixels.len()) {
+            return None;
+        }
+
+        let (header, header_len) = Q565EncodeContext::<64>::header(width, height).to_bytes();
+        output
+            .get_mut(..header_len)?
+            .copy_from_slice(&header[..header_len]);
+        let mut pos = header_len;
+
+        let mut ops = OpCounts::default();
+        // the decoder starts out with a black previous pixel
+        let mut prev = 0;
+        let mut rest = pixels;
+        while let Some((&pixel, tail)) = rest.split_first() {
+            if pixel == prev {
+                let run = run_length(rest, prev);
+                // `run` is at most `rest.len()`
+                rest = rest.get(run..).unwrap_or_default();
+
+                let full_runs = run / MAX_RUN;
+                output.get_mut(pos..pos + full_runs)?.fill(run_op(MAX_RUN));
+                pos += full_runs;
+                let rest_count = run % MAX_RUN;
+                if rest_count > 0 {
+                    *output.get_mut(pos)? = run_op(rest_count);
+                    pos += 1;
+                }
+                ops.run += run.div_ceil(MAX_RUN);
+                continue;
+            }
+
+            let [a, b] = pixel.to_le_bytes();
+            output
+                .get_mut(pos..pos + 3)?
+                .copy_from_slice(&[Q565_OP_RGB565, a, b]);
+            pos += 3;
+            ops.rgb565 += 1;
+            prev = pixel;
+            rest = tail;
+        }
+
+        *output.get_mut(pos)? = Q565_OP_END;
+        pos += 1;
+
+        Some(EncodeReport::new(pixels.len(), pos, ops))
+    })
+}
@@ -0,0 +1,134 @@
+use super::{Q565EncodeContext, Q565StreamingEncodeContext};
+
+/// Image to be encoded, provided row by row, so framebuffers with padding between rows or with
+/// the rows stored bottom-up don't need to be copied into a tight slice first.
+///
+/// See [`StridedPixels`] for slices, and
+/// [`Q565Encoder::encode_source`](super::Q565Encoder::encode_source) to encode a source.
+pub trait PixelSource {
+    fn width(&self) -> u16;
+    fn height(&self) -> u16;
+
+    /// Returns the `y`th row from the top, which must be `width` pixels long.
+    fn row(&self, y: u16) -> &[u16];
+
+    /// Returns the rows from top to bottom.
+    fn rows(&self) -> impl Iterator<Item = &[u16]>
+    where
+        Self: Sized,
+    {
+        (0..self.height()).map(move |y| self.row(y))
+    }
+}
+
+/// [`PixelSource`] for pixels in a slice, with the rows `stride` pixels apart.
+#[derive(Debug, Clone, Copy)]
+pub struct StridedPixels<'a> {
+    pixels: &'a [u16],
+    width: u16,
+    height: u16,
+    stride: usize,
+    bottom_up: bool,
+}
+
+impl<'a> StridedPixels<'a> {
+    /// Returns `None` if `stride` is smaller than `width`, or if `pixels` is too short to hold
+    /// `height` rows. The padding after the last row may be left out.
+    pub const fn new(pixels: &'a [u16], width: u16, height: u16, stride: usize) -> Option<Self> {
+        let len = if height == 0 {
+            0
+        } else {
+            match (height as usize - 1).checked_mul(stride) {
+                // a slice can't be that long anyway
+                Some(last_row) => last_row.saturating_add(width as usize),
+                None => return None,
+            }
+        };
+        if stride < width as usize || pixels.len() < len {
+            return None;
+        }
+
+        Some(Self {
+            pixels,
+            width,
+            height,
+            stride,
+            bottom_up: false,
+        })
+    }
+
+    /// Treats the rows as stored bottom-up, i.e. the first row in the slice is the bottom row of
+    /// the image, like e.g. in BMP files.
+    pub const fn bottom_up(mut self) -> Self {
+        self.bottom_up = true;
+        self
+    }
+}
+
+impl PixelSource for StridedPixels<'_> {
+    #[inline]
+    fn width(&self) -> u16 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u16 {
+        self.height
+    }
+
+    #[inline]
+    fn row(&self, y: u16) -> &[u16] {
+        let y = if self.bottom_up {
+            self.height - 1 - y
+        } else {
+            y
+        };
+        let start = usize::from(y) * self.stride;
+        &self.pixels[start..start + usize::from(self.width)]
+    }
+}
+
+/// Encodes `source`, including the header, and passes the image to `write` in pieces of at most
+/// `scratch.len()` bytes, which needs to be at least [`MAX_OP_LEN`](crate::consts::MAX_OP_LEN).
+/// Returns the number of bytes written.
+pub(crate) fn encode_source_in_pieces<E>(
+    source: &dyn PixelSource,
+    scratch: &mut [u8],
+    mut write: impl FnMut(&[u8]) -> Result<(), E>,
+) -> Result<usize, E> {
+    let (header, header_len) =
+        Q565EncodeContext::<64>::header(source.width(), source.height()).to_bytes();
+    write(&header[..header_len])?;
+    let mut total = header_len;
+
+    let mut encoder = Q565StreamingEncodeContext::new();
+    let mut len = 0;
+    for y in 0..source.height() {
+        let mut row = source.row(y);
+        while !row.is_empty() {
+            let progress = encoder.encode_to_slice(row, &mut scratch[len..], usize::MAX);
+            row = &row[progress.pixels_consumed..];
+            len += progress.bytes_written;
+            // the scratch buffer is full
+            if !row.is_empty() {
+                write(&scratch[..len])?;
+                total += len;
+                len = 0;
+            }
+        }
+    }
+
+    let finished = match encoder.finish(&mut scratch[len..]) {
+        Some(finished) => finished,
+        None => {
+            write(&scratch[..len])?;
+            total += len;
+            len = 0;
+            // two bytes are always enough
+            encoder.finish(scratch).unwrap_or(0)
+        }
+    };
+    len += finished;
+    write(&scratch[..len])?;
+    Ok(total + len)
+}
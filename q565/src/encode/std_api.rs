@@ -1,25 +1,75 @@
-use crate::{
-    consts::*,
-    encode::Q565EncodeContext,
-    utils::{decode_565, diff_n, hash},
-};
-use snafu::{ensure, ResultExt, Snafu};
+use super::{run_length, run_op, EncodeReport, OpCounts, Q565EncodeContext, MAX_RUN};
+use crate::consts::*;
+use core::fmt;
 use std::io::Write;
 
-#[derive(Debug, Snafu)]
+/// A batch of [`Q565_OP_RUN`]s of the maximum length, written at once for long runs.
+const MAX_RUNS: [u8; 64] = [run_op(MAX_RUN); 64];
+
+#[derive(Debug)]
+#[repr(u8)]
+#[non_exhaustive]
 pub enum EncodeError {
-    #[snafu(display(
-        "Specified image dimensions don't match the number of pixels: {width} * {height} == {} pixels, but {pixel_count} pixels were given",
-        width * height
-    ))]
     InvalidDimensions {
         width: usize,
         height: usize,
         pixel_count: usize,
-    },
+    } = 1,
     WriteIo {
         source: std::io::Error,
-    },
+    } = 2,
+}
+
+impl EncodeError {
+    /// Returns the [error code](crate#error-codes) of the variant.
+    pub const fn as_code(&self) -> u8 {
+        match self {
+            Self::InvalidDimensions { .. } => 1,
+            Self::WriteIo { .. } => 2,
+        }
+    }
+
+    /// Returns the variant with the given [error code](crate#error-codes). Always `None`, as both
+    /// variants have fields; see [`message_for_code`](Self::message_for_code).
+    pub fn from_code(code: u8) -> Option<Self> {
+        let _ = code;
+        None
+    }
+
+    /// Returns a message for the variant with the given [error code](crate#error-codes).
+    pub const fn message_for_code(code: u8) -> Option<&'static str> {
+        match code {
+            1 => Some("Specified image dimensions don't match the number of pixels"),
+            2 => Some("WriteIo"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidDimensions {
+                width,
+                height,
+                pixel_count,
+            } => write!(
+                f,
+                "Specified image dimensions don't match the number of pixels: {width} * {height} == {} pixels, but {pixel_count} pixels were given",
+                width * height
+            ),
+            Self::WriteIo { .. } => f.write_str("WriteIo"),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidDimensions { .. } => None,
+            Self::WriteIo { source } => Some(source),
+        }
+    }
 }
 
 impl Q565EncodeContext {
@@ -28,16 +78,26 @@ impl Q565EncodeContext {
         height: u16,
         pixels: &[u16],
         w: W,
-    ) -> Result<(), EncodeError> {
+    ) -> Result<EncodeReport, EncodeError> {
         let mut ctx = Q565EncodeContext::new();
         ctx.encode_with_state(width, height, pixels, w)
     }
 
-    pub fn encode_header<W: Write>(width: u16, height: u16, mut w: W) -> Result<(), EncodeError> {
-        let [w1, w2] = width.to_le_bytes();
-        let [h1, h2] = height.to_le_bytes();
-        let header = [b'q', b'5', b'6', b'5', w1, w2, h1, h2];
-        w.write_all(&header).context(WriteIoSnafu)
+    pub fn encode_header<W: Write>(width: u16, height: u16, w: W) -> Result<(), EncodeError> {
+        Self::encode_header_sized(width, height, w)
+    }
+}
+
+impl<const N: usize> Q565EncodeContext<N> {
+    /// Writes the header for an image of the given size, encoded with this context.
+    pub fn encode_header_sized<W: Write>(
+        width: u16,
+        height: u16,
+        mut w: W,
+    ) -> Result<(), EncodeError> {
+        let (header, header_len) = Self::header(width, height).to_bytes();
+        w.write_all(&header[..header_len])
+            .map_err(|source| EncodeError::WriteIo { source })
     }
 
     pub fn encode_with_state<W: Write>(
@@ -46,149 +106,82 @@ impl Q565EncodeContext {
         height: u16,
         pixels: &[u16],
         mut w: W,
-    ) -> Result<(), EncodeError> {
+    ) -> Result<EncodeReport, EncodeError> {
         ensure!(
-            usize::from(width) * usize::from(height) == pixels.len(),
-            InvalidDimensionsSnafu {
-                width,
-                height,
-                pixel_count: pixels.len()
+            crate::pixel_count(width, height) == Some(pixels.len()),
+            EncodeError::InvalidDimensions {
+                width: width.into(),
+                height: height.into(),
+                pixel_count: pixels.len(),
             }
         );
 
-        Self::encode_header(width, height, &mut w)?;
-        self.encode_pixels(pixels, w)?;
+        Self::encode_header_sized(width, height, &mut w)?;
+        let report = self.encode_pixels(pixels, w)?;
 
-        Ok(())
+        let (_, header_len) = Self::header(width, height).to_bytes();
+        Ok(EncodeReport::new(
+            pixels.len(),
+            header_len + report.bytes_written,
+            report.ops,
+        ))
     }
 
-    pub fn encode_pixels<W: Write>(&mut self, pixels: &[u16], mut w: W) -> Result<(), EncodeError> {
+    /// Encodes the pixels (without a header) and the end marker, continuing from the current
+    /// state.
+    pub fn encode_pixels<W: Write>(
+        &mut self,
+        pixels: &[u16],
+        mut w: W,
+    ) -> Result<EncodeReport, EncodeError> {
+        let mut bytes_written = 0;
         macro_rules! w {
-            ($bytes:expr) => {
-                w.write_all($bytes).context(WriteIoSnafu)
-            };
+            ($bytes:expr) => {{
+                let bytes: &[u8] = $bytes;
+                bytes_written += bytes.len();
+                w.write_all(bytes)
+                    .map_err(|source| EncodeError::WriteIo { source })
+            }};
         }
 
+        let pixel_count = pixels.len();
+        let mut ops = OpCounts::default();
         let mut pixels = pixels.iter();
 
-        loop {
-            let Some(&pixel) = pixels.next() else {
-                break;
-            };
-
+        while let Some(&pixel) = pixels.next() {
             if pixel == self.prev {
                 let slice = pixels.as_slice();
-                let repeats = slice.iter().take_while(|&&p| p == self.prev).count();
+                let repeats = run_length(slice, self.prev);
                 pixels = slice[repeats..].iter();
 
                 // account for initial `pixel` from above
                 let count = repeats + 1;
 
-                let max_count_count = count / 62;
-                let rest_count = count % 62;
-                for _ in 0..max_count_count {
-                    w!(&[Q565_OP_RUN | (62 - 1)])?;
+                let max_count_count = count / MAX_RUN;
+                let rest_count = count % MAX_RUN;
+                // full-screen runs take hundreds of these, so write them in batches
+                let mut remaining = max_count_count;
+                while remaining > 0 {
+                    let batch = remaining.min(MAX_RUNS.len());
+                    w!(&MAX_RUNS[..batch])?;
+                    remaining -= batch;
                 }
                 if rest_count > 0 {
-                    w!(&[Q565_OP_RUN | (rest_count - 1) as u8])?;
+                    w!(&[run_op(rest_count)])?;
                 }
+                ops.run += max_count_count + usize::from(rest_count > 0);
 
                 // already same as prev and already in color array
                 continue;
             }
 
-            self.encode_pixel_write(pixel, &mut w)?;
+            let (op, len) = self.encode_pixel(pixel);
+            w!(&op[..len])?;
+            ops.record(op[0]);
         }
 
         w!(&[Q565_OP_END])?;
 
-        Ok(())
-    }
-}
-
-impl Q565EncodeContext {
-    fn encode_pixel_write<W: Write>(&mut self, pixel: u16, mut w: W) -> Result<(), EncodeError> {
-        macro_rules! w {
-            ($bytes:expr) => {
-                w.write_all($bytes).context(WriteIoSnafu)
-            };
-        }
-
-        self.prev = pixel;
-        let [r, g, b] = decode_565(pixel);
-        let [r_prev, g_prev, b_prev] = self.prev_components;
-        self.prev_components = [r, g, b];
-
-        let hash = hash(pixel);
-        let index = usize::from(hash);
-
-        if self.arr[index] == pixel {
-            w!(&[Q565_OP_INDEX | hash])?;
-
-            // already in arr
-            return Ok(());
-        }
-
-        let (r_diff, g_diff, b_diff) = (
-            diff_n::<5>(r, r_prev),
-            diff_n::<6>(g, g_prev),
-            diff_n::<5>(b, b_prev),
-        );
-
-        if matches!((r_diff, g_diff, b_diff), (-2..=1, -2..=1, -2..=1)) {
-            let mut b = Q565_OP_DIFF;
-            b |= ((r_diff + 2) << 4) as u8;
-            b |= ((g_diff + 2) << 2) as u8;
-            b |= (b_diff + 2) as u8;
-
-            w!(&[b])?;
-        } else {
-            let rg_diff = r_diff - g_diff;
-            let bg_diff = b_diff - g_diff;
-
-            if matches!((rg_diff, g_diff, bg_diff), (-8..=7, -16..=15, -8..=7)) {
-                let bytes = [
-                    (Q565_OP_LUMA | ((g_diff + 16) as u8)),
-                    (((rg_diff + 8) as u8) << 4 | (bg_diff + 8) as u8),
-                ];
-
-                w!(&bytes)?;
-            } else if let Some(bytes) =
-                self.arr_components
-                    .iter()
-                    .enumerate()
-                    .find_map(|(i, &[r_arr, g_arr, b_arr])| {
-                        let (r_diff, g_diff, b_diff) = (
-                            diff_n::<5>(r, r_arr),
-                            diff_n::<6>(g, g_arr),
-                            diff_n::<5>(b, b_arr),
-                        );
-
-                        if matches!((r_diff, g_diff, b_diff), (-2..=1, -4..=3, -2..=1)) {
-                            let bytes = [
-                                (Q565_OP_DIFF_INDEXED
-                                    | ((g_diff + 4) as u8) << 2
-                                    | ((r_diff + 2) as u8)),
-                                (((b_diff + 2) as u8) << 6 | i as u8),
-                            ];
-                            Some(bytes)
-                        } else {
-                            None
-                        }
-                    })
-            {
-                w!(&bytes)?;
-            } else {
-                let [a, b] = pixel.to_le_bytes();
-
-                w!(&[Q565_OP_RGB565, a, b])?;
-            }
-
-            // add to color array
-            self.arr[index] = pixel;
-            self.arr_components[index] = [r, g, b];
-        }
-
-        Ok(())
+        Ok(EncodeReport::new(pixel_count, bytes_written, ops))
     }
 }
@@ -0,0 +1,173 @@
+use super::{run_op, EncoderState, Q565CompactEncodeContext, Q565EncodeContext, MAX_RUN};
+use crate::consts::*;
+
+/// Resumable encoder that writes ops into caller-provided buffers, without the header.
+///
+/// Unlike [`Q565EncodeContext::encode_pixels_to_vec`], a call can stop at any op boundary: when
+/// the output is full, or after a given number of ops. Feeding the rest of the pixels to the next
+/// call continues exactly where the previous one left off, so the concatenated output is identical
+/// to encoding all pixels at once.
+///
+/// The header is written separately, e.g. with [`Q565EncodeContext::header`].
+///
+/// The encoder state `S` is a [`Q565EncodeContext`] by default, or a [`Q565CompactEncodeContext`]
+/// to save memory, see [`Q565CompactStreamingEncodeContext`](super::Q565CompactStreamingEncodeContext).
+#[derive(Debug, Clone, Copy)]
+pub struct Q565StreamingEncodeContext<const N: usize = 64, S = Q565EncodeContext<N>> {
+    state: S,
+    /// Length of the pending run of `state.prev`, not yet written to the output.
+    run: usize,
+}
+
+/// Progress made by a call to [`Q565StreamingEncodeContext::encode_to_slice`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EncodeProgress {
+    /// Number of pixels taken from the input. The next call needs to continue after these.
+    pub pixels_consumed: usize,
+    /// Number of bytes written to the output.
+    pub bytes_written: usize,
+}
+
+impl Q565StreamingEncodeContext {
+    pub const fn new() -> Self {
+        Self::new_sized()
+    }
+}
+
+impl<const N: usize> Q565StreamingEncodeContext<N> {
+    /// Creates a new context for the color array profile with `N` entries.
+    pub const fn new_sized() -> Self {
+        Self {
+            state: Q565EncodeContext::new_sized(),
+            run: 0,
+        }
+    }
+}
+
+impl Q565StreamingEncodeContext<64, Q565CompactEncodeContext> {
+    pub const fn new_compact() -> Self {
+        Self::new_compact_sized()
+    }
+}
+
+impl<const N: usize> Q565StreamingEncodeContext<N, Q565CompactEncodeContext<N>> {
+    /// Creates a new context with a [`Q565CompactEncodeContext`], for the color array profile with
+    /// `N` entries.
+    pub const fn new_compact_sized() -> Self {
+        Self {
+            state: Q565CompactEncodeContext::new_sized(),
+            run: 0,
+        }
+    }
+}
+
+impl<const N: usize, S> Q565StreamingEncodeContext<N, S>
+where
+    S: EncoderState,
+{
+    /// Encodes as many of `pixels` as possible into `output`, writing at most `max_ops` ops.
+    ///
+    /// Returns early once `max_ops` ops have been written, or once `output` has no room for the
+    /// next op. Pass `usize::MAX` to only be limited by the output. Consecutive equal pixels are
+    /// collected into a single run op, so a call can consume up to 61 pixels without writing
+    /// anything.
+    ///
+    /// Each call makes progress as long as `max_ops` is non-zero and `output` has room for at
+    /// least 3 bytes, the longest op.
+    ///
+    /// Call [`finish`](Self::finish) after the last pixel to write the end marker.
+    pub fn encode_to_slice(
+        &mut self,
+        pixels: &[u16],
+        output: &mut [u8],
+        max_ops: usize,
+    ) -> EncodeProgress {
+        panic_free!({
+            let mut progress = EncodeProgress::default();
+            let mut ops = 0;
+
+            for &pixel in pixels {
+                if ops >= max_ops {
+                    break;
+                }
+
+                if pixel == self.state.prev() {
+                    if self.run + 1 == MAX_RUN {
+                        let Some(dst) = output.get_mut(progress.bytes_written) else {
+                            break;
+                        };
+                        *dst = run_op(MAX_RUN);
+                        progress.bytes_written += 1;
+                        ops += 1;
+                        self.run = 0;
+                    } else {
+                        self.run += 1;
+                    }
+
+                    progress.pixels_consumed += 1;
+                    continue;
+                }
+
+                if self.run > 0 {
+                    let Some(dst) = output.get_mut(progress.bytes_written) else {
+                        break;
+                    };
+                    *dst = run_op(self.run);
+                    progress.bytes_written += 1;
+                    ops += 1;
+                    self.run = 0;
+
+                    if ops >= max_ops {
+                        break;
+                    }
+                }
+
+                let Some(dst) = output.get_mut(progress.bytes_written..progress.bytes_written + 3)
+                else {
+                    break;
+                };
+                let (op, len) = self.state.encode_pixel(pixel);
+                dst.iter_mut().zip(op).take(len).for_each(|(d, o)| *d = o);
+                progress.bytes_written += len;
+                ops += 1;
+
+                progress.pixels_consumed += 1;
+            }
+
+            progress
+        })
+    }
+
+    /// Writes the pending run, if any, and the end marker.
+    ///
+    /// Returns the number of bytes written, or `None` if `output` is too small, in which case
+    /// nothing was written. Two bytes are always enough.
+    pub fn finish(&mut self, output: &mut [u8]) -> Option<usize> {
+        panic_free!({
+            let len = if self.run > 0 { 2 } else { 1 };
+            let dst = output.get_mut(..len)?;
+
+            if let [run, _] = dst {
+                *run = run_op(self.run);
+                self.run = 0;
+            }
+            if let Some(end) = dst.last_mut() {
+                *end = Q565_OP_END;
+            }
+
+            Some(len)
+        })
+    }
+}
+
+impl<const N: usize> Default for Q565StreamingEncodeContext<N> {
+    fn default() -> Self {
+        Self::new_sized()
+    }
+}
+
+impl<const N: usize> Default for Q565StreamingEncodeContext<N, Q565CompactEncodeContext<N>> {
+    fn default() -> Self {
+        Self::new_compact_sized()
+    }
+}
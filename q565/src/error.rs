@@ -0,0 +1,213 @@
+//! One error type for all of encoding and decoding, e.g. for applications that pass errors up with
+//! `?` and only care about what went wrong, not which function it came from.
+//!
+//! Every error of the codec functions converts into an [`Error`], and its [`kind`](Error::kind)
+//! tells the failures apart:
+//!
+//! ```
+//! use q565::{
+//!     byteorder::NativeEndian,
+//!     decode::Q565DecodeContext,
+//!     error::{Error, ErrorKind},
+//!     Rgb565,
+//! };
+//!
+//! fn pixel_count(data: &[u8]) -> Result<usize, Error> {
+//!     let (_, pixels) = Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(data)?;
+//!     Ok(pixels.len())
+//! }
+//!
+//! let error = pixel_count(b"not an image").unwrap_err();
+//! assert_eq!(error.kind(), ErrorKind::InvalidMagic);
+//! ```
+//!
+//! [`ErrorKind`] and the error enums it is built from are `#[non_exhaustive]`, so new failure
+//! cases can be added without breaking matches on them. The original error stays available, see
+//! [`Error::decode_error`] and the other accessors, e.g. to get its [error
+//! code](crate#error-codes).
+
+use crate::decode::{DecodeError, DecodeUncheckedError, DecoderError};
+#[cfg(feature = "std")]
+use crate::encode::EncodeError;
+use core::fmt;
+
+/// What went wrong, independent of the function that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The output is too small to hold the entire image.
+    OutputTooSmall,
+    /// The input data ended before the image was fully decoded.
+    UnexpectedEof,
+    /// The image does not start with the magic bytes `q565` or `q56x`.
+    InvalidMagic,
+    /// The extended header sets flags that aren't supported.
+    UnsupportedFlags,
+    /// The image uses a larger color array than the decoder context provides.
+    ColorArrayTooSmall,
+    /// The decoded image data is shorter than the header claims.
+    MissingData,
+    /// The image data contains more pixels than the header claims.
+    TooManyPixels,
+    /// The image has more pixels than allowed.
+    TooLarge,
+    /// The image has more pixels than can be addressed on this target.
+    DimensionsTooLarge,
+    /// The data continues after the end of the image.
+    TrailingData,
+    /// The image dimensions don't match the number of pixels.
+    InvalidDimensions,
+    /// Writing the output failed.
+    Io,
+}
+
+/// Any error of encoding or decoding, see the [module docs](self).
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    repr: Repr,
+}
+
+#[derive(Debug)]
+enum Repr {
+    Decode(DecodeError),
+    DecodeUnchecked(DecodeUncheckedError),
+    Decoder(DecoderError),
+    #[cfg(feature = "std")]
+    Encode(EncodeError),
+}
+
+impl Error {
+    #[inline]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The original error, if it came from one of the [`Q565DecodeContext`] functions.
+    ///
+    /// [`Q565DecodeContext`]: crate::decode::Q565DecodeContext
+    pub fn decode_error(&self) -> Option<&DecodeError> {
+        match &self.repr {
+            Repr::Decode(error) | Repr::Decoder(DecoderError::Decode { source: error }) => {
+                Some(error)
+            }
+            _ => None,
+        }
+    }
+
+    /// The original error, if it came from one of the unchecked decode functions.
+    pub fn decode_unchecked_error(&self) -> Option<&DecodeUncheckedError> {
+        match &self.repr {
+            Repr::DecodeUnchecked(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// The original error, if it came from a [`Decoder`](crate::decode::Decoder).
+    pub fn decoder_error(&self) -> Option<&DecoderError> {
+        match &self.repr {
+            Repr::Decoder(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// The original error, if it came from one of the encode functions writing to an
+    /// [`io::Write`](std::io::Write).
+    #[cfg(feature = "std")]
+    pub fn encode_error(&self) -> Option<&EncodeError> {
+        match &self.repr {
+            Repr::Encode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Self {
+            kind: decode_error_kind(&error),
+            repr: Repr::Decode(error),
+        }
+    }
+}
+
+impl From<DecodeUncheckedError> for Error {
+    fn from(error: DecodeUncheckedError) -> Self {
+        let kind = match error {
+            DecodeUncheckedError::OutputTooSmall => ErrorKind::OutputTooSmall,
+            DecodeUncheckedError::ColorArrayTooSmall => ErrorKind::ColorArrayTooSmall,
+            DecodeUncheckedError::MissingData => ErrorKind::MissingData,
+            DecodeUncheckedError::DimensionsTooLarge => ErrorKind::DimensionsTooLarge,
+        };
+        Self {
+            kind,
+            repr: Repr::DecodeUnchecked(error),
+        }
+    }
+}
+
+impl From<DecoderError> for Error {
+    fn from(error: DecoderError) -> Self {
+        let kind = match &error {
+            DecoderError::TooLarge => ErrorKind::TooLarge,
+            DecoderError::TrailingData => ErrorKind::TrailingData,
+            DecoderError::Decode { source } => decode_error_kind(source),
+        };
+        Self {
+            kind,
+            repr: Repr::Decoder(error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<EncodeError> for Error {
+    fn from(error: EncodeError) -> Self {
+        let kind = match error {
+            EncodeError::InvalidDimensions { .. } => ErrorKind::InvalidDimensions,
+            EncodeError::WriteIo { .. } => ErrorKind::Io,
+        };
+        Self {
+            kind,
+            repr: Repr::Encode(error),
+        }
+    }
+}
+
+fn decode_error_kind(error: &DecodeError) -> ErrorKind {
+    match error {
+        DecodeError::OutputTooSmall => ErrorKind::OutputTooSmall,
+        DecodeError::UnexpectedEof => ErrorKind::UnexpectedEof,
+        DecodeError::InvalidMagic => ErrorKind::InvalidMagic,
+        DecodeError::UnsupportedFlags => ErrorKind::UnsupportedFlags,
+        DecodeError::ColorArrayTooSmall => ErrorKind::ColorArrayTooSmall,
+        DecodeError::MissingData => ErrorKind::MissingData,
+        DecodeError::TooManyPixels => ErrorKind::TooManyPixels,
+        DecodeError::DimensionsTooLarge => ErrorKind::DimensionsTooLarge,
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Decode(error) => error.fmt(f),
+            Repr::DecodeUnchecked(error) => error.fmt(f),
+            // the kind is that of the wrapped error, so show its message
+            Repr::Decoder(DecoderError::Decode { source }) => source.fmt(f),
+            Repr::Decoder(error) => error.fmt(f),
+            #[cfg(feature = "std")]
+            Repr::Encode(error) => error.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    /// The source of the original error, whose message is already the one of this error.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.repr {
+            Repr::Encode(error) => std::error::Error::source(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Encoding images rendered with [`embedded_graphics`], with the `embedded-graphics` feature.
+//!
+//! [`encode_framebuffer`] snapshots a [`Framebuffer`] of [`Rgb565`] pixels, in either byte order,
+//! and [`encode_pixels`] encodes any iterator of [`Rgb565`] pixels, e.g. from an [`ImageRaw`] or a
+//! custom draw target. Both write into a caller-provided buffer and don't allocate, see
+//! [`max_stream_len`] for how large it needs to be.
+//!
+//! ```
+//! use embedded_graphics::{
+//!     framebuffer::{buffer_size, Framebuffer},
+//!     pixelcolor::{raw::{LittleEndian, RawU16}, Rgb565},
+//!     prelude::*,
+//!     primitives::{PrimitiveStyle, Rectangle},
+//! };
+//! use q565::max_stream_len;
+//!
+//! let mut framebuffer =
+//!     Framebuffer::<Rgb565, RawU16, LittleEndian, 32, 16, { buffer_size::<Rgb565>(32, 16) }>::new();
+//! Rectangle::new(Point::new(4, 4), Size::new(8, 8))
+//!     .into_styled(PrimitiveStyle::with_fill(Rgb565::RED))
+//!     .draw(&mut framebuffer)?;
+//!
+//! let mut output = [0; max_stream_len(32, 16)];
+//! let len = q565::graphics::encode_framebuffer(&framebuffer, &mut output)?;
+//! let encoded = &output[..len];
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [`ImageRaw`]: embedded_graphics::image::ImageRaw
+//! [`max_stream_len`]: crate::max_stream_len
+
+use crate::encode::{Q565EncodeContext, Q565StreamingEncodeContext};
+#[cfg(feature = "alloc")]
+use crate::max_stream_len;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use embedded_graphics::{
+    framebuffer::Framebuffer,
+    iterator::raw::RawDataSlice,
+    pixelcolor::{
+        raw::{ByteOrder, RawU16},
+        Rgb565,
+    },
+    prelude::RawData,
+};
+
+error_enum! {
+    pub enum GraphicsEncodeError {
+        /// The output can't hold the encoded image.
+        OutputTooSmall = 1,
+        /// The number of pixels doesn't match `width * height`.
+        PixelCount = 2,
+        /// The image is wider or higher than 65535 pixels.
+        TooLarge = 3,
+    }
+}
+
+/// Encodes a `width` x `height` image from `pixels` into `output`, returning the number of bytes
+/// written.
+///
+/// `pixels` has to yield exactly `width * height` pixels, row by row.
+pub fn encode_pixels(
+    width: u16,
+    height: u16,
+    pixels: impl IntoIterator<Item = Rgb565>,
+    output: &mut [u8],
+) -> Result<usize, GraphicsEncodeError> {
+    let (header, header_len) = Q565EncodeContext::<64>::header(width, height).to_bytes();
+    output
+        .get_mut(..header_len)
+        .ok_or(GraphicsEncodeError::OutputTooSmall)?
+        .copy_from_slice(&header[..header_len]);
+    let mut len = header_len;
+
+    let mut encoder = Q565StreamingEncodeContext::new();
+    let mut pixels = pixels.into_iter();
+    let mut remaining = crate::pixel_count(width, height).ok_or(GraphicsEncodeError::TooLarge)?;
+    let mut chunk = [0u16; 64];
+    loop {
+        let mut chunk_len = 0;
+        for (slot, color) in chunk.iter_mut().zip(pixels.by_ref()) {
+            *slot = RawU16::from(color).into_inner();
+            chunk_len += 1;
+        }
+        if chunk_len == 0 {
+            break;
+        }
+        remaining = remaining
+            .checked_sub(chunk_len)
+            .ok_or(GraphicsEncodeError::PixelCount)?;
+
+        let mut chunk = &chunk[..chunk_len];
+        while !chunk.is_empty() {
+            let progress = encoder.encode_to_slice(chunk, &mut output[len..], usize::MAX);
+            if progress.pixels_consumed == 0 {
+                return Err(GraphicsEncodeError::OutputTooSmall);
+            }
+            chunk = &chunk[progress.pixels_consumed..];
+            len += progress.bytes_written;
+        }
+    }
+    ensure!(remaining == 0, GraphicsEncodeError::PixelCount);
+
+    len += encoder
+        .finish(&mut output[len..])
+        .ok_or(GraphicsEncodeError::OutputTooSmall)?;
+    Ok(len)
+}
+
+/// Encodes the contents of `framebuffer` into `output`, returning the number of bytes written.
+pub fn encode_framebuffer<BO, const WIDTH: usize, const HEIGHT: usize, const N: usize>(
+    framebuffer: &Framebuffer<Rgb565, RawU16, BO, WIDTH, HEIGHT, N>,
+    output: &mut [u8],
+) -> Result<usize, GraphicsEncodeError>
+where
+    BO: ByteOrder,
+    for<'a> RawDataSlice<'a, RawU16, BO>: IntoIterator<Item = RawU16>,
+{
+    let (width, height, pixels) = framebuffer_pixels(framebuffer)?;
+    encode_pixels(width, height, pixels, output)
+}
+
+/// Like [`encode_pixels`], but appends the encoded image to `output`.
+#[cfg(feature = "alloc")]
+pub fn encode_pixels_to_vec(
+    width: u16,
+    height: u16,
+    pixels: impl IntoIterator<Item = Rgb565>,
+    output: &mut Vec<u8>,
+) -> Result<usize, GraphicsEncodeError> {
+    let start = output.len();
+    output.resize(start + max_stream_len(width, height), 0);
+    let result = encode_pixels(width, height, pixels, &mut output[start..]);
+    output.truncate(start + result.as_ref().map_or(0, |&len| len));
+    result
+}
+
+/// Like [`encode_framebuffer`], but appends the encoded image to `output`.
+#[cfg(feature = "alloc")]
+pub fn encode_framebuffer_to_vec<BO, const WIDTH: usize, const HEIGHT: usize, const N: usize>(
+    framebuffer: &Framebuffer<Rgb565, RawU16, BO, WIDTH, HEIGHT, N>,
+    output: &mut Vec<u8>,
+) -> Result<usize, GraphicsEncodeError>
+where
+    BO: ByteOrder,
+    for<'a> RawDataSlice<'a, RawU16, BO>: IntoIterator<Item = RawU16>,
+{
+    let (width, height, pixels) = framebuffer_pixels(framebuffer)?;
+    encode_pixels_to_vec(width, height, pixels, output)
+}
+
+fn framebuffer_pixels<BO, const WIDTH: usize, const HEIGHT: usize, const N: usize>(
+    framebuffer: &Framebuffer<Rgb565, RawU16, BO, WIDTH, HEIGHT, N>,
+) -> Result<(u16, u16, impl Iterator<Item = Rgb565> + '_), GraphicsEncodeError>
+where
+    BO: ByteOrder,
+    for<'a> RawDataSlice<'a, RawU16, BO>: IntoIterator<Item = RawU16>,
+{
+    let width = u16::try_from(WIDTH).map_err(|_| GraphicsEncodeError::TooLarge)?;
+    let height = u16::try_from(HEIGHT).map_err(|_| GraphicsEncodeError::TooLarge)?;
+    // `N` may be larger than needed
+    let data = &framebuffer.data()[..WIDTH * HEIGHT * 2];
+    let pixels = RawDataSlice::<RawU16, BO>::new(data)
+        .into_iter()
+        .map(Rgb565::from);
+    Ok((width, height, pixels))
+}
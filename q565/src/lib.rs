@@ -11,6 +11,64 @@
 //! - u16le width (non-zero)
 //! - u16le height (non-zero)
 //!
+//! ## Extended header
+//!
+//! Images using one of the [color array profiles](#color-array-profiles) start with an extended
+//! header instead:
+//!
+//! - 4-byte magic: `q56x`
+//! - u8 flags:
+//!   - bits 0..=1: color array size (`0`: 64 entries, `1`: 32 entries, `2`: 16 entries, `3`: [no
+//!     color array](#no-color-array))
+//!   - bit 2: [raw image](#raw-images)
+//!   - bits 3..=7: reserved, must be zero
+//! - u16le width (non-zero)
+//! - u16le height (non-zero)
+//!
+//! Encoders only emit the extended header if needed, so that images using the default profile stay
+//! readable by older decoders.
+//!
+//! ## Raw images
+//!
+//! Content like noise expands when encoded. With the raw flag ([`RAW_FLAG`]) set, the header is
+//! followed by the pixels as u16le RGB565 instead of ops, without an end marker. The color array
+//! size bits must be zero then.
+//!
+//! The decoders handle raw images transparently. Encoders only store an image raw when asked to,
+//! e.g. by [`Q565EncodeContext::encode_auto`](encode::Q565EncodeContext::encode_auto).
+//!
+//! ## Frame sequences
+//!
+//! The frames of e.g. a slowly changing UI can be encoded with one encoder context that is kept
+//! from frame to frame, see [`Q565EncodeContext::encode_frame`](encode::Q565EncodeContext::encode_frame).
+//! Every frame is a complete image with its own header, but its ops may reference the color array
+//! and the previous pixel as left by the frames before it. Colors that stay on screen are then
+//! mostly encoded as [`Q565_OP_INDEX`](consts::Q565_OP_INDEX) from the first pixel on.
+//!
+//! The frames can only be decoded in order, by a decoder context that is kept the same way, see
+//! [`Q565DecodeContext::decode_frame`](decode::Q565DecodeContext::decode_frame). To start over, e.g.
+//! after a lost frame, reset both contexts. For frames that only change in places, [delta
+//! encoding](pipeline::Pipeline::with_delta) saves a lot more, at the cost of a frame-sized buffer.
+//!
+//! A frame that is identical to the one before it may be replaced by the single byte
+//! [`REPEAT_FRAME`], which leaves the state of both contexts as it is, see
+//! [`Q565EncodeContext::encode_frame_or_repeat`](encode::Q565EncodeContext::encode_frame_or_repeat)
+//! and [`Q565DecodeContext::decode_frame_or_repeat`](decode::Q565DecodeContext::decode_frame_or_repeat).
+//! Static screens then cost one byte per frame.
+//!
+//! ## Row-aligned runs
+//!
+//! Images may optionally follow the row-aligned profile, in which no
+//! [`Q565_OP_RUN`](consts::Q565_OP_RUN) crosses the end of a row, so every row starts with an op of
+//! its own. They are regular images that any decoder reads, see
+//! [`Q565EncodeContext::encode_row_aligned_to_vec`](encode::Q565EncodeContext::encode_row_aligned_to_vec)
+//! and [`stream::has_row_aligned_runs`].
+//!
+//! With rows starting at op boundaries, the byte offset of a row and the decoder state at its start
+//! are enough to decode the image from that row on, e.g. to decode rows in parallel or to resume at
+//! the next row after corrupted data. On the test corpus, the profile costs 0.05% in total: up to
+//! 3% for small images with flat areas, and nothing measurable for photos.
+//!
 //! ## Color array
 //!
 //! Q565 uses a simplified color array compared to the one from QOI. The "hash" function was
@@ -23,6 +81,32 @@
 //! ([`Q565_OP_DIFF`](consts::Q565_OP_DIFF)). This helps keep the color array from being
 //! flooded with similar colors.
 //!
+//! ### Color array profiles
+//!
+//! Instead of the default 64 entries, the color array may be restricted to 32 or 16 entries (see
+//! [`ColorArraySize`]). The hash is then reduced to the lowest 5 or 4 bits, respectively, and all
+//! indices in the stream must be smaller than the array size. This shrinks the encoder state and
+//! cuts down its search for [`Q565_OP_DIFF_INDEXED`](consts::Q565_OP_DIFF_INDEXED) candidates, at
+//! the cost of a worse compression ratio.
+//!
+//! Decoders only need as many entries as the largest profile they accept: a
+//! [`Q565DecodeContext<16>`](decode::Q565DecodeContext) takes up 34 instead of 130 bytes, and
+//! rejects images using a larger color array.
+//!
+//! The encoder keeps more state per entry to speed up its search. Where RAM is scarcer than
+//! cycles, the streaming encoder can use a
+//! [`Q565CompactEncodeContext`](encode::Q565CompactEncodeContext) instead, which is the same size
+//! as the decoder state and produces the same output.
+//!
+//! ### No color array
+//!
+//! Images using the no-array profile ([`ColorArraySize::NoArray`]) never reference the color
+//! array: they contain no [`Q565_OP_INDEX`](consts::Q565_OP_INDEX) and no
+//! [`Q565_OP_DIFF_INDEXED`](consts::Q565_OP_DIFF_INDEXED) ops. They can be decoded by
+//! [`MiniDecoder`](decode::MiniDecoder), whose whole state is the previous pixel, e.g. on
+//! microcontrollers with only a few hundred bytes of RAM. Any other decoder context can decode them
+//! as well.
+//!
 //! ## [`Q565_OP_DIFF_INDEXED`](consts::Q565_OP_DIFF_INDEXED)
 //!
 //! Since we only have 5/6 bits per channel, `Q565_OP_LUMA` was reduced to represent the green
@@ -37,25 +121,484 @@
 //!
 //! # Stream format
 //!
-//! See [consts] for the different operation types.
+//! See [consts] for the different operation types. The `reference` module (with the `alloc`
+//! feature) contains a straightforward encoder that spells out which op is chosen for a pixel.
+//!
+//! # Stack usage
+//!
+//! All decoders are plain loops over the input: there is no recursion, no allocation (besides the
+//! `Vec`-backed outputs), and no temporary buffers. Their stack usage is therefore bounded by a
+//! fixed frame that doesn't depend on the image, plus:
+//!
+//! - the decoder state, if the function creates its own (e.g.
+//!   [`Q565DecodeContext::decode`](decode::Q565DecodeContext::decode)): 34, 66, or 130 bytes,
+//!   depending on the color array size. The `*_with_state` variants borrow the state instead, so
+//!   it can live in a `static`.
+//! - the decode output, which is passed by value.
+//!
+//! With optimizations, the decode loops are inlined into a single leaf function. See the `q565-c`
+//! crate for measured numbers of its exported functions. The `stack` integration test makes sure
+//! the decoders stay within a small, fixed stack even without optimizations.
+//!
+//! # Panics
+//!
+//! The decoders never panic on invalid input; the checked ones return an error instead. Neither do
+//! the `no_std` encoders, [`Q565StreamingEncodeContext`](encode::Q565StreamingEncodeContext) and
+//! [`encode_fast_rle`](encode::encode_fast_rle), which report a too small output instead. With the
+//! `panic-free` feature, this is enforced at link time: if any panicking branch survives
+//! optimization in one of these functions (including the decode output passed in), the program
+//! fails to link. The check relies on unwinding, so it only works in optimized builds with
+//! `panic = "unwind"`, like the `panic_free` integration test:
+//!
+//! ```sh
+//! cargo test --release --features panic-free --test panic_free
+//! ```
+//!
+//! This includes the decode outputs: an output with a bounds check that the optimizer can't
+//! remove makes the build fail, too.
+//!
+//! # Unsafe code
+//!
+//! For dependents that must not pull in any unsafe code, e.g. under a `cargo-geiger` policy, the
+//! `forbid-unsafe` feature compiles the crate with `#![forbid(unsafe_code)]`. It leaves out
+//! everything built on unsafe code:
+//!
+//! - the `*_unchecked` decoders, including the streaming decoder in
+//!   `decode::streaming_no_header`, and `UnsafeSliceDecodeOutput`,
+//!   `UninitSliceDecodeOutput`, and `VolatileSliceDecodeOutput`
+//! - the modules built on the streaming decoder: `alpha`, `demux`, `embedded`, and `FlashBundle`
+//! - `AssetCache`, which views its byte pool as pixels
+//!
+//! The checked decoders only index their color array with safe code instead, which the optimizer
+//! turns into the same code, and the encoders don't use unsafe code at all. The cost of the
+//! feature is losing the unchecked decoders. Throughput in RGB565 pixels per second, from
+//! `cargo bench --bench bench` on one core of an x86-64 Xeon VM with Rust 1.95, averaged over two
+//! runs:
+//!
+//! | image | default, `decode_unchecked` | default, `decode` | `forbid-unsafe`, `decode` | slowdown | default, `encode_to_vec` | `forbid-unsafe`, `encode_to_vec` |
+//! | --- | --- | --- | --- | --- | --- | --- |
+//! | `qoi_logo.png` | 3.2 G | 640 M | 510 M | 84 % | 1.24 G | 1.57 G |
+//! | `testcard.png` | 870 M | 340 M | 350 M | 60 % | 260 M | 415 M |
+//! | `wikipedia_008.png` | 112 M | 96 M | 99 M | 12 % | 60 M | 76 M |
+//! | `kodim23.png` | 102 M | 92 M | 98 M | 4 % | 57 M | 70 M |
+//!
+//! The slowdown is from the unchecked decoder of the default build to the checked decoder of the
+//! `forbid-unsafe` build; images with long runs lose the most, photos hardly anything. Between the
+//! two builds, the checked decoder and the encoder run the same code, and the remaining
+//! differences, in both directions, are noise and code layout: single runs varied by up to 30 %.
+//! The `benchmarks` CI job repeats the decoder measurements for both builds and lists them in its
+//! summary.
+//!
+//! Unlike the other features, `forbid-unsafe` removes API, so it should only be enabled by the
+//! final binary, not by libraries. It can't be combined with `panic-free`, whose link-time check
+//! needs an unsafe call.
+//!
+//! # Error codes
+//!
+//! Every variant of the crate's error enums has a stable `u8` code (its discriminant, starting at
+//! 1), so firmware can report errors compactly, e.g. in telemetry, and the host can turn them back
+//! into errors with the same crate:
+//!
+//! ```
+//! use q565::decode::DecodeError;
+//!
+//! // device
+//! let code = DecodeError::UnexpectedEof.as_code();
+//!
+//! // host
+//! assert!(matches!(DecodeError::from_code(code), Some(DecodeError::UnexpectedEof)));
+//! assert_eq!(
+//!     DecodeError::message_for_code(code),
+//!     Some("The input data ended before the image was fully decoded.")
+//! );
+//! ```
+//!
+//! Codes are unique within an enum, not across enums, and are never reused: new variants get the
+//! next free code. Variants with fields, e.g. a wrapped [`DecodeError`](decode::DecodeError), can't
+//! be restored from their code; report the code of the wrapped error alongside if needed.
+//!
+//! To handle the errors of all encode and decode functions in one place, convert them into an
+//! [`error::Error`], whose [`kind`](error::Error::kind) tells them apart.
+//!
+//! # Dependencies
+//!
+//! Without default features, the only dependency is `itertools` (without its default features),
+//! which keeps the dependency tree small for firmware audits:
+//!
+//! ```toml
+//! q565 = { version = "0.4", default-features = false }
+//! ```
+//!
+//! Byte orders are the crate's own [`byteorder`] types, and errors implement
+//! [`Display`](core::fmt::Display) (and `std::error::Error` with the `std` feature). The `byteorder`
+//! feature (on by default) additionally lets the types of the `byteorder` crate be used as byte
+//! orders.
 #![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![cfg_attr(feature = "forbid-unsafe", forbid(unsafe_code))]
+
+#[cfg(all(feature = "forbid-unsafe", feature = "panic-free"))]
+compile_error!("`panic-free` relies on unsafe code and can't be combined with `forbid-unsafe`");
 
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
-pub use byteorder;
-use byteorder::{BigEndian, ByteOrder, NativeEndian};
+use crate::byteorder::{BigEndian, Endianness, NativeEndian};
 use utils::{decode_565, rgb565_to_rgb888};
 
+/// Returns early with the given error if the condition doesn't hold.
+macro_rules! ensure {
+    ($cond:expr, $error:expr $(,)?) => {
+        if !$cond {
+            return Err($error.into());
+        }
+    };
+}
+
+/// Defines an error enum, with the doc comment of each variant as its [`Display`](core::fmt::Display)
+/// message. With the `std` feature, it implements [`std::error::Error`], with the `source` field of
+/// a variant as its source.
+///
+/// Every variant has an explicit, stable code, see [error codes](crate#error-codes). Codes start at
+/// 1 and are never reused; new variants get the next free one.
+macro_rules! error_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[doc = $doc:literal])+
+                $variant:ident $({ $($field:ident: $ty:ty),* $(,)? })? = $code:literal
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug)]
+        #[repr(u8)]
+        $vis enum $name {
+            $(
+                $(#[doc = $doc])+
+                $variant $({ $($field: $ty),* })? = $code,
+            )*
+        }
+
+        impl $name {
+            /// Returns the [error code](crate#error-codes) of the variant.
+            pub const fn as_code(&self) -> u8 {
+                match self {
+                    $(Self::$variant { .. } => $code,)*
+                }
+            }
+
+            /// Returns the variant with the given [error code](crate#error-codes).
+            ///
+            /// Returns `None` for unknown codes, and for variants with fields, which can't be
+            /// restored from the code alone. [`message_for_code`](Self::message_for_code) works for
+            /// those, too.
+            pub fn from_code(code: u8) -> Option<Self> {
+                match code {
+                    $($code => error_enum!(@construct $variant $({ $($field),* })?),)*
+                    _ => None,
+                }
+            }
+
+            /// Returns the [`Display`](core::fmt::Display) message of the variant with the given
+            /// [error code](crate#error-codes).
+            pub const fn message_for_code(code: u8) -> Option<&'static str> {
+                match code {
+                    $($code => Some(concat!($($doc),+).trim_ascii_start()),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    $(Self::$variant { .. } => f.write_str(concat!($($doc),+).trim_start()),)*
+                }
+            }
+        }
+
+        #[cfg(feature = "std")]
+        impl std::error::Error for $name {
+            #[allow(unused_variables, unreachable_code)]
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                match self {
+                    $(Self::$variant $({ $($field),* })? => {
+                        $($(error_enum!(@source [$field] $field);)*)?
+                        None
+                    })*
+                }
+            }
+        }
+    };
+    (@construct $variant:ident) => {
+        Some(Self::$variant)
+    };
+    (@construct $variant:ident { $($field:ident),* }) => {
+        None
+    };
+    (@source [source] $field:ident) => {
+        return Some($field)
+    };
+    (@source [$other:ident] $field:ident) => {};
+}
+
+/// Runs the body in a closure, making any panic that could unwind out of it a link error if the
+/// `panic-free` feature is enabled. See `utils::PanicGuard`.
+macro_rules! panic_free {
+    ($body:block) => {{
+        #[cfg(feature = "panic-free")]
+        let guard = crate::utils::PanicGuard;
+        #[allow(clippy::redundant_closure_call)]
+        let result = (move || $body)();
+        #[cfg(feature = "panic-free")]
+        core::mem::forget(guard);
+        result
+    }};
+}
+
+#[cfg(not(feature = "forbid-unsafe"))]
+pub mod alpha;
+#[cfg(feature = "alloc")]
+pub mod analyze;
+pub mod animation;
+pub mod atlas;
+pub mod bundle;
+pub mod byteorder;
+pub mod capabilities;
+#[cfg(feature = "capture")]
+pub mod capture;
+#[cfg(feature = "alloc")]
+pub mod conformance;
+pub mod container;
+#[cfg(feature = "defmt-cycles")]
+pub mod cycles;
 pub mod decode;
+#[cfg(not(feature = "forbid-unsafe"))]
+pub mod demux;
+#[cfg(feature = "std")]
+pub mod diff;
 #[cfg(feature = "alloc")]
+pub mod edit;
+#[cfg(all(
+    any(feature = "critical-section", feature = "embassy"),
+    not(feature = "forbid-unsafe")
+))]
+pub mod embedded;
 pub mod encode;
+pub mod error;
+#[cfg(feature = "embedded-graphics")]
+pub mod graphics;
+pub mod mirror;
+pub mod nine_patch;
+pub mod pipeline;
+#[cfg(feature = "alloc")]
+pub mod reference;
+pub mod screenshot;
+pub mod selftest;
+pub mod snapshot;
+#[cfg(feature = "srgb")]
+pub mod srgb;
+pub mod st77xx;
+pub mod stream;
+pub mod transport;
+pub mod update;
 pub mod utils;
+#[cfg(feature = "zune")]
+pub mod zune;
+
+/// Magic bytes of the regular header.
+pub const MAGIC: &[u8; 4] = b"q565";
+/// Magic bytes of the [extended header](crate#extended-header).
+pub const EXTENDED_MAGIC: &[u8; 4] = b"q56x";
+/// Length of the regular header, in bytes.
+pub const HEADER_LEN: usize = 8;
+/// Length of the [extended header](crate#extended-header), in bytes.
+pub const EXTENDED_HEADER_LEN: usize = 9;
+/// Flag of the [extended header](crate#extended-header) marking a [raw image](crate#raw-images).
+pub const RAW_FLAG: u8 = 0b100;
+/// A [frame](crate#frame-sequences) that repeats the frame before it: just the end marker, without
+/// a header.
+pub const REPEAT_FRAME: u8 = consts::Q565_OP_END;
+
+/// Size of the shortest valid image of the given size, in bytes: the regular header, runs of the
+/// initial (black) pixel, and the end marker.
+///
+/// Data shorter than this can't be a complete image, e.g. when validating a size received over a
+/// transport before decoding.
+pub const fn min_stream_len(width: u16, height: u16) -> usize {
+    let pixels = width as u32 * height as u32;
+    let len = HEADER_LEN as u32 + pixels.div_ceil(consts::MAX_OP_PIXELS as u32) + 1;
+    // only a 16-bit `usize` can't hold it
+    if len as u64 > usize::MAX as u64 {
+        usize::MAX
+    } else {
+        len as usize
+    }
+}
+
+/// Size of the longest valid image of the given size, in bytes: the extended header, a
+/// [`MAX_OP_LEN`](consts::MAX_OP_LEN)-byte op per pixel, and the end marker.
+///
+/// Any encoder's output fits into a buffer of this size, including [raw images](crate#raw-images)
+/// and [frames](crate#frame-sequences).
+///
+/// Saturates at `usize::MAX` for images that can't be held in memory on the target, e.g. a
+/// 65535x65535 image on a 32-bit target.
+pub const fn max_stream_len(width: u16, height: u16) -> usize {
+    match pixel_count(width, height) {
+        Some(pixels) => consts::MAX_OP_LEN
+            .saturating_mul(pixels)
+            .saturating_add(EXTENDED_HEADER_LEN + 1),
+        None => usize::MAX,
+    }
+}
+
+/// Number of pixels of an image of the given size, or `None` if it doesn't fit into `usize`.
+///
+/// That's only the case on targets with a 16-bit `usize`, e.g. AVR or MSP430, for images of more
+/// than 65535 pixels. The decoders reject those with [`DecodeError::DimensionsTooLarge`]
+/// instead of computing a wrapped-around size.
+///
+/// [`DecodeError::DimensionsTooLarge`]: decode::DecodeError::DimensionsTooLarge
+#[inline]
+pub const fn pixel_count(width: u16, height: u16) -> Option<usize> {
+    (width as usize).checked_mul(height as usize)
+}
+
+/// Converts a length or offset read from the data to `usize`, saturating where `usize` is 16 bits,
+/// so that the bounds checks on it fail instead of passing with a truncated value.
+#[inline]
+pub(crate) fn saturating_usize(value: u32) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
 
 #[derive(Debug, Clone)]
 pub struct HeaderInfo {
     pub width: u16,
     pub height: u16,
+    pub color_array_size: ColorArraySize,
+    /// The pixels are stored as raw RGB565 instead of ops, see [raw images](crate#raw-images).
+    pub raw: bool,
+}
+
+impl HeaderInfo {
+    /// Number of pixels of the image, see [`pixel_count`].
+    #[inline]
+    pub const fn pixel_count(&self) -> Option<usize> {
+        pixel_count(self.width, self.height)
+    }
+
+    /// Serializes the header, returning the buffer and the number of bytes used.
+    ///
+    /// The extended header is only used if the image doesn't fit the regular one.
+    pub fn to_bytes(&self) -> ([u8; EXTENDED_HEADER_LEN], usize) {
+        let [w1, w2] = self.width.to_le_bytes();
+        let [h1, h2] = self.height.to_le_bytes();
+
+        if self.raw {
+            let [m1, m2, m3, m4] = *EXTENDED_MAGIC;
+            (
+                [m1, m2, m3, m4, RAW_FLAG, w1, w2, h1, h2],
+                EXTENDED_HEADER_LEN,
+            )
+        } else if self.color_array_size == ColorArraySize::Entries64 {
+            let [m1, m2, m3, m4] = *MAGIC;
+            ([m1, m2, m3, m4, w1, w2, h1, h2, 0], HEADER_LEN)
+        } else {
+            let [m1, m2, m3, m4] = *EXTENDED_MAGIC;
+            let flags = self.color_array_size.flags();
+            ([m1, m2, m3, m4, flags, w1, w2, h1, h2], EXTENDED_HEADER_LEN)
+        }
+    }
+}
+
+/// Number of entries in the color array, see [color array profiles](crate#color-array-profiles).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorArraySize {
+    /// The [no-array profile](crate#no-color-array).
+    NoArray,
+    Entries16,
+    Entries32,
+    #[default]
+    Entries64,
+}
+
+impl ColorArraySize {
+    /// Returns the number of entries.
+    #[inline]
+    pub const fn entries(self) -> usize {
+        match self {
+            ColorArraySize::NoArray => 0,
+            ColorArraySize::Entries16 => 16,
+            ColorArraySize::Entries32 => 32,
+            ColorArraySize::Entries64 => 64,
+        }
+    }
+
+    /// Returns the size with the given number of entries, if it is one of the supported sizes.
+    ///
+    /// This doesn't include the [no-array profile](crate#no-color-array), which can't be used
+    /// with a decoder or encoder context.
+    #[inline]
+    pub const fn from_entries(entries: usize) -> Option<Self> {
+        match entries {
+            16 => Some(ColorArraySize::Entries16),
+            32 => Some(ColorArraySize::Entries32),
+            64 => Some(ColorArraySize::Entries64),
+            _ => None,
+        }
+    }
+
+    /// Parses the flags byte of the extended header, ignoring the [`RAW_FLAG`].
+    ///
+    /// Returns `None` if the flags contain reserved values.
+    #[inline]
+    pub const fn from_flags(flags: u8) -> Option<Self> {
+        match flags & !RAW_FLAG {
+            0 => Some(ColorArraySize::Entries64),
+            1 => Some(ColorArraySize::Entries32),
+            2 => Some(ColorArraySize::Entries16),
+            3 => Some(ColorArraySize::NoArray),
+            _ => None,
+        }
+    }
+
+    /// Returns the flags byte of the extended header.
+    #[inline]
+    pub const fn flags(self) -> u8 {
+        match self {
+            ColorArraySize::Entries64 => 0,
+            ColorArraySize::Entries32 => 1,
+            ColorArraySize::Entries16 => 2,
+            ColorArraySize::NoArray => 3,
+        }
+    }
+}
+
+/// A rectangular area of an image, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    /// Returns whether the rectangle lies completely within an image of the given size.
+    #[inline]
+    pub const fn fits_within(&self, width: u16, height: u16) -> bool {
+        self.x as u32 + self.width as u32 <= width as u32
+            && self.y as u32 + self.height as u32 <= height as u32
+    }
+
+    /// Number of pixels of the rectangle, saturating at `usize::MAX` on targets with a 16-bit
+    /// `usize`.
+    #[inline]
+    pub const fn area(&self) -> usize {
+        (self.width as usize).saturating_mul(self.height as usize)
+    }
 }
 
 pub mod consts {
@@ -71,7 +614,8 @@ pub mod consts {
     /// ```
     ///
     /// - 2-bit tag b00
-    /// - 6-bit index into the color array: 0..63
+    /// - 6-bit index into the color array: 0..63 (or smaller, depending on the
+    ///   [color array profile](crate#color-array-profiles))
     /// - A valid encoder must not issue 2 or more consecutive Q565_OP_INDEX chunks to the same
     ///   index. Q565_OP_RUN should be used instead.
     pub const Q565_OP_INDEX: u8 = 0b0000_0000;
@@ -135,7 +679,8 @@ pub mod consts {
     /// - 3-bit green channel difference from the indexed array pixel between -4..3
     /// - 2-bit   red channel difference from the indexed array pixel between -2..1
     /// - 2-bit  blue channel difference from the indexed array pixel between -2..1
-    /// - 6-bit index into the color array: 0..63
+    /// - 6-bit index into the color array: 0..63 (or smaller, depending on the
+    ///   [color array profile](crate#color-array-profiles))
     pub const Q565_OP_DIFF_INDEXED: u8 = 0b1010_0000;
 
     /// Repeats the last pixel.
@@ -181,19 +726,174 @@ pub mod consts {
     /// `-------------------------`
     /// ```
     pub const Q565_OP_END: u8 = 0b1111_1111;
+
+    /// Size of the longest op, [`Q565_OP_RGB565`], in bytes.
+    pub const MAX_OP_LEN: usize = 3;
+    /// Most pixels a single op produces: a [`Q565_OP_RUN`] of 62 pixels.
+    pub const MAX_OP_PIXELS: usize = 62;
+
+    /// A bit field of an op, see [`OpInfo`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OpField {
+        pub name: &'static str,
+        pub bits: u8,
+        pub description: &'static str,
+    }
+
+    /// Bit layout of an op: the tag in the most significant bits of the first byte, followed by the
+    /// fields, most significant bit first.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OpInfo {
+        pub name: &'static str,
+        /// The first byte of the op with all fields set to zero.
+        pub tag: u8,
+        pub tag_bits: u8,
+        pub fields: &'static [OpField],
+        pub description: &'static str,
+    }
+
+    impl OpInfo {
+        /// Size of the op, in bytes.
+        pub const fn encoded_len(&self) -> usize {
+            let mut bits = self.tag_bits as usize;
+            let mut i = 0;
+            while i < self.fields.len() {
+                bits += self.fields[i].bits as usize;
+                i += 1;
+            }
+            bits / 8
+        }
+    }
+
+    const fn field(name: &'static str, bits: u8, description: &'static str) -> OpField {
+        OpField {
+            name,
+            bits,
+            description,
+        }
+    }
+
+    /// The layouts of all ops, as documented above, e.g. for generating the format specification
+    /// (`q565 spec`). The `spec` integration test checks them against the decoder.
+    pub const OPS: &[OpInfo] = &[
+        OpInfo {
+            name: "Q565_OP_INDEX",
+            tag: Q565_OP_INDEX,
+            tag_bits: 2,
+            fields: &[field(
+                "index",
+                6,
+                "index into the color array, smaller than the array size",
+            )],
+            description: "Re-emit a pixel from the color array.",
+        },
+        OpInfo {
+            name: "Q565_OP_DIFF",
+            tag: Q565_OP_DIFF,
+            tag_bits: 2,
+            fields: &[
+                field(
+                    "dr",
+                    2,
+                    "red difference from the previous pixel (-2..1), bias 2",
+                ),
+                field(
+                    "dg",
+                    2,
+                    "green difference from the previous pixel (-2..1), bias 2",
+                ),
+                field(
+                    "db",
+                    2,
+                    "blue difference from the previous pixel (-2..1), bias 2",
+                ),
+            ],
+            description: "Calculate a pixel based on a 2-bit difference from the previous pixel.",
+        },
+        OpInfo {
+            name: "Q565_OP_LUMA",
+            tag: Q565_OP_LUMA,
+            tag_bits: 3,
+            fields: &[
+                field(
+                    "dg",
+                    5,
+                    "green difference from the previous pixel (-16..15), bias 16",
+                ),
+                field("dr - dg", 4, "red minus green difference (-8..7), bias 8"),
+                field("db - dg", 4, "blue minus green difference (-8..7), bias 8"),
+            ],
+            description: "Calculate a pixel based on a 5-bit green-channel difference from the \
+                previous pixel, and differences to the green-channel difference for red and blue.",
+        },
+        OpInfo {
+            name: "Q565_OP_DIFF_INDEXED",
+            tag: Q565_OP_DIFF_INDEXED,
+            tag_bits: 3,
+            fields: &[
+                field(
+                    "dg",
+                    3,
+                    "green difference from the array pixel (-4..3), bias 4",
+                ),
+                field(
+                    "dr",
+                    2,
+                    "red difference from the array pixel (-2..1), bias 2",
+                ),
+                field(
+                    "db",
+                    2,
+                    "blue difference from the array pixel (-2..1), bias 2",
+                ),
+                field(
+                    "index",
+                    6,
+                    "index into the color array, smaller than the array size",
+                ),
+            ],
+            description: "Calculate a pixel based on a color in the color array, and applying a \
+                difference to it.",
+        },
+        OpInfo {
+            name: "Q565_OP_RUN",
+            tag: Q565_OP_RUN,
+            tag_bits: 2,
+            fields: &[field(
+                "run",
+                6,
+                "run length (1..62), bias -1; 62 and 63 are the RGB565 and END tags",
+            )],
+            description: "Repeats the last pixel.",
+        },
+        OpInfo {
+            name: "Q565_OP_RGB565",
+            tag: Q565_OP_RGB565,
+            tag_bits: 8,
+            fields: &[field("rgb565", 16, "the pixel, little-endian")],
+            description: "Emits a full raw pixel.",
+        },
+        OpInfo {
+            name: "Q565_OP_END",
+            tag: Q565_OP_END,
+            tag_bits: 8,
+            fields: &[],
+            description: "Marks the end of the stream.",
+        },
+    ];
 }
 
 pub trait ColorFormat {
     type OutputElement: Clone;
 
-    fn to_output<B: ByteOrder>(color: u16) -> Self::OutputElement;
+    fn to_output<B: Endianness>(color: u16) -> Self::OutputElement;
 }
 
 pub enum Rgb565 {}
 impl ColorFormat for Rgb565 {
     type OutputElement = u16;
 
-    fn to_output<B: ByteOrder>(color: u16) -> Self::OutputElement {
+    fn to_output<B: Endianness>(color: u16) -> Self::OutputElement {
         let mut n = [0u8; 2];
         NativeEndian::write_u16(&mut n, color);
         B::read_u16(&n)
@@ -204,7 +904,7 @@ pub enum Rgb888 {}
 impl ColorFormat for Rgb888 {
     type OutputElement = [u8; 3];
 
-    fn to_output<B: ByteOrder>(color: u16) -> Self::OutputElement {
+    fn to_output<B: Endianness>(color: u16) -> Self::OutputElement {
         let rgb565_components = decode_565(color);
         let big_endian = rgb565_to_rgb888(rgb565_components);
         let u24 = BigEndian::read_u24(&big_endian);
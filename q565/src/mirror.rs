@@ -0,0 +1,342 @@
+//! Reference protocol for mirroring a device's screen to a host over USB bulk endpoints.
+//!
+//! The device encodes its framebuffer, usually as the next frame of a [frame
+//! sequence](crate#frame-sequences), and sends it with a small frame header. The host reassembles
+//! and decodes the frames with a [`MirrorReceiver`].
+//!
+//! With the `usb-device` feature, `MirrorClass` implements the device side as a `usb-device`
+//! class. `examples/usb-mirror-host` in the repository is a host that shows the mirrored screen's
+//! frames as they come in.
+//!
+//! # Protocol
+//!
+//! The device has a vendor-specific interface (class `0xFF`, subclass [`INTERFACE_SUBCLASS`],
+//! protocol [`INTERFACE_PROTOCOL`]) with one bulk IN and one bulk OUT endpoint. All integers are
+//! little-endian.
+//!
+//! The device sends each frame on the bulk IN endpoint as a 12-byte frame header followed by the
+//! payload, split into packets of the endpoint's maximum packet size without regard to where the
+//! header or a frame ends:
+//!
+//! - 4-byte magic: `q5mr`
+//! - u32le payload length
+//! - u16le sequence number, incremented by one per frame, wrapping around
+//! - u8 flags:
+//!   - bit 0: [`KEYFRAME_FLAG`], the payload is decoded with a fresh decoder context
+//!   - bits 1..=7: reserved, must be zero
+//! - u8 reserved, must be zero
+//! - payload: the Q565 image, or [`REPEAT_FRAME`] if the screen didn't change since the frame
+//!   before, which isn't allowed for keyframes
+//!
+//! Frames without [`KEYFRAME_FLAG`] continue the frame sequence of the frame before them. A host
+//! that missed a frame, noticed by a gap in the sequence numbers, drops frames until the next
+//! keyframe, and asks for one by sending [`REQUEST_KEYFRAME`] on the bulk OUT endpoint. The device
+//! also starts over with a keyframe after a USB reset.
+
+use crate::decode::DecodeError;
+#[cfg(feature = "alloc")]
+use crate::{
+    byteorder::Endianness,
+    decode::{Q565DecodeContext, VecDecodeOutput},
+    saturating_usize, HeaderInfo, Rgb565, REPEAT_FRAME,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "usb-device")]
+mod usb;
+
+#[cfg(feature = "usb-device")]
+pub use usb::*;
+
+pub const MIRROR_MAGIC: &[u8; 4] = b"q5mr";
+pub const MIRROR_HEADER_LEN: usize = 12;
+/// Flag of the frame header marking a frame that doesn't depend on the frames before it.
+pub const KEYFRAME_FLAG: u8 = 0b1;
+/// Request sent by the host on the bulk OUT endpoint to ask for a keyframe.
+pub const REQUEST_KEYFRAME: u8 = 0x01;
+/// Interface subclass of the mirroring interface.
+pub const INTERFACE_SUBCLASS: u8 = 0x65;
+/// Interface protocol of the mirroring interface.
+pub const INTERFACE_PROTOCOL: u8 = 0x01;
+
+error_enum! {
+    pub enum MirrorError {
+        /// The data does not start with the magic bytes `q5mr`.
+        InvalidMagic = 1,
+        /// The frame header sets flags that aren't supported by this receiver.
+        UnsupportedFlags = 2,
+        /// The payload is larger than 4 GiB, or larger than the receiver accepts.
+        TooLarge = 3,
+        /// A frame failed to decode.
+        Decode { source: DecodeError } = 4,
+    }
+}
+
+impl From<DecodeError> for MirrorError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// Header sent before each frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MirrorHeader {
+    pub payload_len: u32,
+    pub sequence: u16,
+    pub keyframe: bool,
+}
+
+impl MirrorHeader {
+    pub fn to_bytes(&self) -> [u8; MIRROR_HEADER_LEN] {
+        let mut bytes = [0; MIRROR_HEADER_LEN];
+        bytes[..4].copy_from_slice(MIRROR_MAGIC);
+        bytes[4..8].copy_from_slice(&self.payload_len.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[10] = if self.keyframe { KEYFRAME_FLAG } else { 0 };
+        bytes
+    }
+
+    /// Parses the frame header at the start of `data`. Returns `None` if `data` is shorter than a
+    /// header.
+    pub fn parse(data: &[u8]) -> Option<Result<Self, MirrorError>> {
+        let bytes = data.get(..MIRROR_HEADER_LEN)?;
+        if &bytes[..4] != MIRROR_MAGIC {
+            return Some(Err(MirrorError::InvalidMagic));
+        }
+        if bytes[10] & !KEYFRAME_FLAG != 0 || bytes[11] != 0 {
+            return Some(Err(MirrorError::UnsupportedFlags));
+        }
+
+        Some(Ok(Self {
+            payload_len: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            sequence: u16::from_le_bytes([bytes[8], bytes[9]]),
+            keyframe: bytes[10] & KEYFRAME_FLAG != 0,
+        }))
+    }
+}
+
+/// Splits a frame, header and payload, into packets.
+#[derive(Debug, Clone)]
+pub struct MirrorSender<'a> {
+    header: [u8; MIRROR_HEADER_LEN],
+    payload: &'a [u8],
+    position: usize,
+}
+
+impl<'a> MirrorSender<'a> {
+    pub fn new(payload: &'a [u8], sequence: u16, keyframe: bool) -> Result<Self, MirrorError> {
+        let header = MirrorHeader {
+            payload_len: u32::try_from(payload.len())
+                .ok()
+                .ok_or(MirrorError::TooLarge)?,
+            sequence,
+            keyframe,
+        };
+
+        Ok(Self {
+            header: header.to_bytes(),
+            payload,
+            position: 0,
+        })
+    }
+
+    /// Copies the next packet into `packet`, filling it unless the frame ends first. Returns the
+    /// length of the packet, `0` once the whole frame was sent.
+    ///
+    /// The packet counts as sent once it is [`advance`](Self::advance)d past.
+    pub fn packet(&self, packet: &mut [u8]) -> usize {
+        let mut len = 0;
+        if let Some(header) = self.header.get(self.position..) {
+            len = header.len().min(packet.len());
+            packet[..len].copy_from_slice(&header[..len]);
+        }
+
+        let payload_position = (self.position + len).saturating_sub(MIRROR_HEADER_LEN);
+        let payload = &self.payload[payload_position..];
+        let payload_len = payload.len().min(packet.len() - len);
+        packet[len..len + payload_len].copy_from_slice(&payload[..payload_len]);
+        len + payload_len
+    }
+
+    /// Marks the next `len` bytes as sent.
+    #[inline]
+    pub fn advance(&mut self, len: usize) {
+        self.position = (self.position + len).min(self.frame_len());
+    }
+
+    /// Length of the frame, header and payload.
+    #[inline]
+    pub fn frame_len(&self) -> usize {
+        MIRROR_HEADER_LEN + self.payload.len()
+    }
+
+    /// Returns whether the whole frame was sent.
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.position == self.frame_len()
+    }
+}
+
+/// A frame decoded by a [`MirrorReceiver`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct MirrorFrame<'a> {
+    pub header: HeaderInfo,
+    pub sequence: u16,
+    pub keyframe: bool,
+    /// Whether the frame was [`REPEAT_FRAME`], so the pixels are those of the frame before.
+    pub repeat: bool,
+    /// The RGB565 pixels, row by row.
+    pub pixels: &'a [u16],
+}
+
+/// Reassembles and decodes the frames sent by a device, from the bytes read from the bulk IN
+/// endpoint.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct MirrorReceiver {
+    buffer: Vec<u8>,
+    decoder: Q565DecodeContext,
+    pixels: Vec<u16>,
+    /// Header of the last decoded frame, which a repeated frame shows again.
+    image_header: Option<HeaderInfo>,
+    /// Sequence number of the next frame, `None` until the next keyframe.
+    expected: Option<u16>,
+    max_payload_len: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl Default for MirrorReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl MirrorReceiver {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            decoder: Q565DecodeContext::new(),
+            pixels: Vec::new(),
+            image_header: None,
+            expected: None,
+            max_payload_len: u32::MAX as usize,
+        }
+    }
+
+    /// Rejects frames with a longer payload with [`MirrorError::TooLarge`], instead of buffering
+    /// them.
+    pub fn with_max_payload_len(mut self, max_payload_len: usize) -> Self {
+        self.max_payload_len = max_payload_len;
+        self
+    }
+
+    /// Appends bytes read from the bulk IN endpoint.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns whether the receiver waits for a keyframe, so the host should send
+    /// [`REQUEST_KEYFRAME`].
+    #[inline]
+    pub fn needs_keyframe(&self) -> bool {
+        self.expected.is_none()
+    }
+
+    /// Decodes the next complete frame received so far, in the byte order `B`. Returns `None` if
+    /// there is none yet.
+    ///
+    /// Frames that can't be decoded because a frame before them was missed are dropped. After an
+    /// error, the receiver skips ahead to the next frame header and waits for a keyframe.
+    pub fn next_frame<B: Endianness>(&mut self) -> Result<Option<MirrorFrame<'_>>, MirrorError> {
+        loop {
+            let header = match MirrorHeader::parse(&self.buffer) {
+                None => return Ok(None),
+                Some(Ok(header)) => header,
+                Some(Err(error)) => {
+                    self.skip_to_next_header();
+                    return Err(error);
+                }
+            };
+
+            let payload_len = saturating_usize(header.payload_len);
+            if payload_len > self.max_payload_len {
+                self.skip_to_next_header();
+                return Err(MirrorError::TooLarge);
+            }
+            let frame_len = MIRROR_HEADER_LEN + payload_len;
+            if self.buffer.len() < frame_len {
+                return Ok(None);
+            }
+
+            let in_sequence = self.expected == Some(header.sequence);
+            if !header.keyframe && !in_sequence {
+                self.expected = None;
+                self.buffer.drain(..frame_len);
+                continue;
+            }
+
+            let payload = &self.buffer[MIRROR_HEADER_LEN..frame_len];
+            let repeated = if !header.keyframe && payload == [REPEAT_FRAME] {
+                self.image_header.clone()
+            } else {
+                None
+            };
+            if let Some(image_header) = repeated {
+                self.buffer.drain(..frame_len);
+                self.expected = Some(header.sequence.wrapping_add(1));
+                return Ok(Some(MirrorFrame {
+                    header: image_header,
+                    sequence: header.sequence,
+                    keyframe: false,
+                    repeat: true,
+                    pixels: &self.pixels,
+                }));
+            }
+
+            self.pixels.clear();
+            self.image_header = None;
+            let output = VecDecodeOutput::<Rgb565>::new(&mut self.pixels);
+            let decoded = if header.keyframe {
+                self.decoder = Q565DecodeContext::new();
+                self.decoder.decode_with_state::<B>(payload, output)
+            } else {
+                self.decoder.decode_frame::<B>(payload, output)
+            };
+            self.buffer.drain(..frame_len);
+
+            let (image_header, _) = match decoded {
+                Ok(decoded) => decoded,
+                Err(source) => {
+                    self.expected = None;
+                    return Err(MirrorError::Decode { source });
+                }
+            };
+            self.expected = Some(header.sequence.wrapping_add(1));
+            self.image_header = Some(image_header.clone());
+
+            return Ok(Some(MirrorFrame {
+                header: image_header,
+                sequence: header.sequence,
+                keyframe: header.keyframe,
+                repeat: false,
+                pixels: &self.pixels,
+            }));
+        }
+    }
+
+    /// Drops the buffered bytes up to the next frame header, after the one at the start.
+    fn skip_to_next_header(&mut self) {
+        self.expected = None;
+        let next = self.buffer[1..]
+            .windows(MIRROR_MAGIC.len())
+            .position(|window| window == MIRROR_MAGIC)
+            .map_or(
+                self.buffer.len().saturating_sub(MIRROR_MAGIC.len() - 1),
+                |position| position + 1,
+            );
+        self.buffer.drain(..next);
+    }
+}
@@ -0,0 +1,118 @@
+use super::{MirrorError, MirrorSender, INTERFACE_PROTOCOL, INTERFACE_SUBCLASS, REQUEST_KEYFRAME};
+use usb_device::class_prelude::*;
+
+/// Maximum packet size of the bulk endpoints, the largest one allowed for full-speed devices.
+pub const MAX_PACKET_SIZE: u16 = 64;
+
+/// Device side of the [mirroring protocol](super#protocol), as a `usb-device` class.
+///
+/// Start a frame with [`frame`](Self::frame), then call [`write_packets`](Self::write_packets)
+/// whenever the USB stack was polled, until it returns `true`:
+///
+/// ```ignore
+/// let keyframe = mirror.take_keyframe_request();
+/// if keyframe {
+///     encoder = Q565EncodeContext::new();
+/// }
+/// encoded.clear();
+/// encoder.encode_frame(WIDTH, HEIGHT, &framebuffer, &mut encoded);
+///
+/// let mut sender = mirror.frame(&encoded, keyframe)?;
+/// loop {
+///     usb_dev.poll(&mut [&mut mirror]);
+///     if mirror.write_packets(&mut sender)? {
+///         break;
+///     }
+/// }
+/// ```
+///
+/// The payload has to stay around until the frame is sent, so the next frame is usually encoded
+/// into a second buffer in the meantime.
+pub struct MirrorClass<'a, B: UsbBus> {
+    interface: InterfaceNumber,
+    ep_in: EndpointIn<'a, B>,
+    ep_out: EndpointOut<'a, B>,
+    sequence: u16,
+    keyframe_requested: bool,
+}
+
+impl<'a, B: UsbBus> MirrorClass<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Self {
+            interface: alloc.interface(),
+            ep_in: alloc.bulk(MAX_PACKET_SIZE),
+            ep_out: alloc.bulk(MAX_PACKET_SIZE),
+            sequence: 0,
+            keyframe_requested: true,
+        }
+    }
+
+    /// Starts sending a frame with the next sequence number. `payload` is the encoded image, which
+    /// for a keyframe needs to be encoded with a fresh encoder context.
+    pub fn frame<'p>(
+        &mut self,
+        payload: &'p [u8],
+        keyframe: bool,
+    ) -> Result<MirrorSender<'p>, MirrorError> {
+        let sender = MirrorSender::new(payload, self.sequence, keyframe)?;
+        self.sequence = self.sequence.wrapping_add(1);
+        if keyframe {
+            self.keyframe_requested = false;
+        }
+        Ok(sender)
+    }
+
+    /// Writes the next packets of the frame, as far as the endpoint takes them. Returns whether the
+    /// whole frame was written.
+    pub fn write_packets(&mut self, sender: &mut MirrorSender<'_>) -> Result<bool, UsbError> {
+        let mut packet = [0; MAX_PACKET_SIZE as usize];
+        loop {
+            let len = sender.packet(&mut packet[..usize::from(self.ep_in.max_packet_size())]);
+            if len == 0 {
+                return Ok(true);
+            }
+
+            match self.ep_in.write(&packet[..len]) {
+                Ok(written) => sender.advance(written),
+                Err(UsbError::WouldBlock) => return Ok(false),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Returns whether the next frame needs to be a keyframe, because the host asked for one or
+    /// the device was reset.
+    #[inline]
+    pub fn take_keyframe_request(&mut self) -> bool {
+        core::mem::take(&mut self.keyframe_requested)
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for MirrorClass<'_, B> {
+    fn get_configuration_descriptors(
+        &self,
+        writer: &mut DescriptorWriter,
+    ) -> usb_device::Result<()> {
+        writer.interface(self.interface, 0xFF, INTERFACE_SUBCLASS, INTERFACE_PROTOCOL)?;
+        writer.endpoint(&self.ep_in)?;
+        writer.endpoint(&self.ep_out)?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.keyframe_requested = true;
+    }
+
+    fn endpoint_out(&mut self, addr: EndpointAddress) {
+        if addr != self.ep_out.address() {
+            return;
+        }
+
+        let mut request = [0; MAX_PACKET_SIZE as usize];
+        if let Ok(len) = self.ep_out.read(&mut request) {
+            if request[..len].contains(&REQUEST_KEYFRAME) {
+                self.keyframe_requested = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! Nine-patch images: small images of e.g. a button or panel that are stretched to any size by
+//! keeping their corners and stretching their edges and center.
+//!
+//! # Layout
+//!
+//! A nine-patch is a regular Q565 image, followed by a metadata chunk with its insets:
+//!
+//! - u16le left, top, right, and bottom inset
+//! - 4-byte tag: `q59p`
+//!
+//! The chunk follows the image's end marker, so any decoder can still decode the image itself.
+//! The insets split the image into a 3x3 grid: the corners are drawn as they are, the edges are
+//! stretched along one axis, and the center along both, using nearest-neighbor scaling. The center
+//! must be at least one pixel wide and high.
+
+use crate::{
+    byteorder::Endianness,
+    decode::{DecodeError, PixelSink, Q565DecodeContext, RectDecodeOutput},
+    HeaderInfo, Rect, Rgb565,
+};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Tag ending the metadata chunk of a nine-patch.
+pub const NINE_PATCH_TAG: &[u8; 4] = b"q59p";
+/// Length of the metadata chunk, in bytes.
+pub const NINE_PATCH_CHUNK_LEN: usize = 12;
+
+error_enum! {
+    pub enum NinePatchError {
+        /// The data does not end with a nine-patch metadata chunk.
+        MissingChunk = 1,
+        /// The insets leave no center for the image size.
+        InvalidInsets = 2,
+        /// The image failed to decode.
+        Decode { source: DecodeError } = 3,
+        /// The requested size is smaller than the insets.
+        TooSmall = 4,
+    }
+}
+
+impl From<DecodeError> for NinePatchError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// Widths of the borders of a nine-patch that are not stretched, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Insets {
+    pub left: u16,
+    pub top: u16,
+    pub right: u16,
+    pub bottom: u16,
+}
+
+impl Insets {
+    /// Returns whether the insets leave a center of at least one pixel in an image of the given
+    /// size.
+    pub const fn fit_within(&self, width: u16, height: u16) -> bool {
+        (self.left as u32 + self.right as u32) < width as u32
+            && (self.top as u32 + self.bottom as u32) < height as u32
+    }
+}
+
+/// Appends the metadata chunk with `insets` to the encoded `image`, turning it into a nine-patch.
+#[cfg(feature = "alloc")]
+pub fn encode_nine_patch(image: &mut Vec<u8>, insets: Insets) -> Result<(), NinePatchError> {
+    let (header, _) = Q565DecodeContext::decode_header(image)?;
+    ensure!(
+        insets.fit_within(header.width, header.height),
+        NinePatchError::InvalidInsets
+    );
+
+    for inset in [insets.left, insets.top, insets.right, insets.bottom] {
+        image.extend_from_slice(&inset.to_le_bytes());
+    }
+    image.extend_from_slice(NINE_PATCH_TAG);
+    Ok(())
+}
+
+/// A parsed nine-patch.
+#[derive(Debug, Clone, Copy)]
+pub struct NinePatch<'a> {
+    image: &'a [u8],
+    width: u16,
+    height: u16,
+    insets: Insets,
+}
+
+impl<'a> NinePatch<'a> {
+    /// Parses the metadata chunk at the end of `data` and the header of the image before it.
+    pub fn parse(data: &'a [u8]) -> Result<Self, NinePatchError> {
+        let (image, chunk) = data
+            .len()
+            .checked_sub(NINE_PATCH_CHUNK_LEN)
+            .map(|at| data.split_at(at))
+            .ok_or(NinePatchError::MissingChunk)?;
+        let (insets, tag) = chunk.split_at(8);
+        ensure!(tag == NINE_PATCH_TAG, NinePatchError::MissingChunk);
+
+        let inset = |i: usize| u16::from_le_bytes([insets[2 * i], insets[2 * i + 1]]);
+        let insets = Insets {
+            left: inset(0),
+            top: inset(1),
+            right: inset(2),
+            bottom: inset(3),
+        };
+
+        let (HeaderInfo { width, height, .. }, _) = Q565DecodeContext::decode_header(image)?;
+        ensure!(
+            insets.fit_within(width, height),
+            NinePatchError::InvalidInsets
+        );
+
+        Ok(Self {
+            image,
+            width,
+            height,
+            insets,
+        })
+    }
+
+    /// Returns the encoded image, without the metadata chunk.
+    pub fn image(&self) -> &'a [u8] {
+        self.image
+    }
+
+    /// Returns the width and height of the unstretched image.
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    pub fn insets(&self) -> Insets {
+        self.insets
+    }
+
+    /// Decodes the image into `buffer` and passes it to `sink` stretched to `width` x `height`, row
+    /// by row.
+    ///
+    /// `buffer` must hold at least the unstretched image. A row is passed in one chunk if it is at
+    /// most 64 pixels wide, and in several otherwise; chunks never span more than one row.
+    pub fn draw<B: Endianness>(
+        &self,
+        buffer: &mut [u16],
+        width: u16,
+        height: u16,
+        mut sink: impl PixelSink<u16>,
+    ) -> Result<(), NinePatchError> {
+        let Insets {
+            left,
+            top,
+            right,
+            bottom,
+        } = self.insets;
+        ensure!(
+            u32::from(left) + u32::from(right) <= u32::from(width)
+                && u32::from(top) + u32::from(bottom) <= u32::from(height),
+            NinePatchError::TooSmall
+        );
+
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        };
+        let source = buffer
+            .get_mut(..rect.area())
+            .ok_or(DecodeError::OutputTooSmall)?;
+        Q565DecodeContext::decode::<B>(
+            self.image,
+            RectDecodeOutput::<Rgb565>::new(source, usize::from(self.width), rect),
+        )?;
+
+        let mut chunk = [0; 64];
+        for y in 0..height {
+            let source_y = stretch(y, height, self.height, top, bottom);
+            let row = &source[usize::from(source_y) * usize::from(self.width)..];
+
+            let mut filled = 0;
+            for x in 0..width {
+                let source_x = stretch(x, width, self.width, left, right);
+                chunk[filled] = row[usize::from(source_x)];
+                filled += 1;
+                if filled == chunk.len() {
+                    sink.push_chunk(&chunk);
+                    filled = 0;
+                }
+            }
+            if filled > 0 {
+                sink.push_chunk(&chunk[..filled]);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Maps the coordinate `x` of the stretched image to the unstretched one, along one axis.
+fn stretch(x: u16, len: u16, source_len: u16, start: u16, end: u16) -> u16 {
+    if x < start {
+        x
+    } else if x >= len - end {
+        source_len - (len - x)
+    } else {
+        let center = u32::from(len - start - end);
+        let source_center = u32::from(source_len - start - end);
+        start + (u32::from(x - start) * source_center / center) as u16
+    }
+}
@@ -0,0 +1,292 @@
+//! Fused capture → convert → encode pipeline.
+//!
+//! A [`Pipeline`] takes RGB888 rows as they are captured and turns them into Q565 ops right away,
+//! in chunks of [`CHUNK_LEN`] pixels on the stack. Neither the converted RGB565 frame nor the
+//! encoded image need to be held in memory in full; the only frame-sized buffer is the previous
+//! frame, and only if [delta encoding](Pipeline::with_delta) is enabled.
+//!
+//! The stages, in order:
+//!
+//! 1. RGB888 → RGB565 conversion, optionally with [ordered dithering](Pipeline::with_dithering).
+//! 2. Delta vs. the previous frame, if enabled: every pixel is XORed with the pixel at the same
+//!    position in the previous frame, so unchanged areas turn into runs of `0`. The receiver undoes
+//!    this with [`apply_delta`].
+//! 3. Encoding, with a [`Q565StreamingEncodeContext`].
+
+use crate::byteorder::{BigEndian, Endianness};
+use crate::{
+    consts::MAX_OP_LEN,
+    encode::{Q565EncodeContext, Q565StreamingEncodeContext},
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    HeaderInfo,
+};
+
+/// Number of pixels converted at once.
+pub const CHUNK_LEN: usize = 32;
+
+/// 4x4 Bayer matrix, with thresholds in `0..16`.
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+error_enum! {
+    pub enum PipelineError {
+        /// The frame doesn't hold `width * height` pixels.
+        FrameSize = 1,
+        /// The row doesn't hold `width` pixels.
+        RowLength = 2,
+        /// All rows of the frame were already pushed.
+        TooManyRows = 3,
+        /// The output can't hold the encoded row, see [`Pipeline::max_row_len`].
+        OutputTooSmall = 4,
+        /// The frame was finished before all rows were pushed.
+        IncompleteFrame = 5,
+        /// Rows of the current frame were already pushed.
+        FrameInProgress = 6,
+    }
+}
+
+/// Converts and encodes frames row by row, see the [module docs](self).
+#[derive(Debug)]
+pub struct Pipeline<'a, const N: usize = 64> {
+    width: u16,
+    height: u16,
+    dither: bool,
+    previous: Option<&'a mut [u16]>,
+
+    encoder: Q565StreamingEncodeContext<N>,
+    y: u16,
+}
+
+impl<'a> Pipeline<'a> {
+    /// Creates a pipeline for frames of the given size, using the default color array profile.
+    pub fn new(width: u16, height: u16) -> Self {
+        Self::new_sized(width, height)
+    }
+}
+
+impl<'a, const N: usize> Pipeline<'a, N> {
+    /// Creates a pipeline for frames of the given size, using the color array profile with `N`
+    /// entries.
+    pub fn new_sized(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            dither: false,
+            previous: None,
+
+            encoder: Q565StreamingEncodeContext::new_sized(),
+            y: 0,
+        }
+    }
+
+    /// Enables ordered dithering when converting to RGB565, trading banding in gradients for a
+    /// fine pattern (and usually a worse compression ratio).
+    pub fn with_dithering(mut self) -> Self {
+        self.dither = true;
+        self
+    }
+
+    /// Enables delta encoding against the previous frame.
+    ///
+    /// `previous` holds the previous frame, and is updated with the current one as its rows are
+    /// pushed. Start with all zeroes, so that the first frame is encoded as is.
+    pub fn with_delta(mut self, previous: &'a mut [u16]) -> Result<Self, PipelineError> {
+        ensure!(
+            crate::pixel_count(self.width, self.height) == Some(previous.len()),
+            PipelineError::FrameSize
+        );
+
+        self.previous = Some(previous);
+        Ok(self)
+    }
+
+    /// Returns the header of the encoded frames.
+    pub const fn header(&self) -> HeaderInfo {
+        Q565EncodeContext::<N>::header(self.width, self.height)
+    }
+
+    /// The number of bytes the output passed to [`push_row`](Self::push_row) needs to hold.
+    pub const fn max_row_len(&self) -> usize {
+        // every pixel takes up to 3 bytes, plus a run that was still pending from the row before
+        MAX_OP_LEN
+            .saturating_mul(self.width as usize)
+            .saturating_add(1)
+    }
+
+    /// Converts and encodes the next row of the frame into `output`, returning the number of bytes
+    /// written.
+    ///
+    /// `output` needs to hold at least [`max_row_len`](Self::max_row_len) bytes. The encoded frame
+    /// is the header, followed by the output of all rows and [`finish`](Self::finish).
+    ///
+    /// `B` is the byte order of the pixels, like for [`encode_frame`](Self::encode_frame).
+    pub fn push_row<B: Endianness>(
+        &mut self,
+        row: &[[u8; 3]],
+        output: &mut [u8],
+    ) -> Result<usize, PipelineError> {
+        ensure!(self.y < self.height, PipelineError::TooManyRows);
+        ensure!(
+            row.len() == usize::from(self.width),
+            PipelineError::RowLength
+        );
+        ensure!(
+            output.len() >= self.max_row_len(),
+            PipelineError::OutputTooSmall
+        );
+
+        let written = self.encode_row::<B>(row, output);
+        debug_assert!(written.is_some());
+        written.ok_or(PipelineError::OutputTooSmall)
+    }
+
+    /// Converts and encodes a whole frame into `output`, including the header and the end marker.
+    /// Returns the number of bytes written.
+    ///
+    /// Unlike with [`push_row`](Self::push_row), `output` only needs to hold the encoded frame.
+    /// If it doesn't, [`PipelineError::OutputTooSmall`] is returned and the frame is dropped. With
+    /// delta encoding, the previous frame then only holds the part of the frame that was encoded,
+    /// so the receiver needs to drop its delta state as well.
+    ///
+    /// `B` is the byte order of the pixels, like for the [`Rgb888`](crate::Rgb888) decode output:
+    /// `[r, g, b]` for [`BigEndian`], `[b, g, r]` for
+    /// [`LittleEndian`](byteorder::LittleEndian).
+    pub fn encode_frame<B: Endianness>(
+        &mut self,
+        pixels: &[[u8; 3]],
+        output: &mut [u8],
+    ) -> Result<usize, PipelineError> {
+        ensure!(self.y == 0, PipelineError::FrameInProgress);
+        let width = usize::from(self.width);
+        ensure!(
+            crate::pixel_count(self.width, self.height) == Some(pixels.len()),
+            PipelineError::FrameSize
+        );
+
+        let (header, header_len) = self.header().to_bytes();
+        let mut written = header_len;
+        output
+            .get_mut(..header_len)
+            .ok_or(PipelineError::OutputTooSmall)?
+            .copy_from_slice(&header[..header_len]);
+
+        for y in 0..usize::from(self.height) {
+            let row = &pixels[y * width..(y + 1) * width];
+            match self.encode_row::<B>(row, &mut output[written..]) {
+                Some(len) => written += len,
+                None => {
+                    self.reset();
+                    return Err(PipelineError::OutputTooSmall);
+                }
+            }
+        }
+
+        match self.finish(&mut output[written..]) {
+            Ok(len) => Ok(written + len),
+            Err(e) => {
+                self.reset();
+                Err(e)
+            }
+        }
+    }
+
+    /// Converts and encodes the next row, returning `None` if it didn't fit into `output`.
+    fn encode_row<B: Endianness>(&mut self, row: &[[u8; 3]], output: &mut [u8]) -> Option<usize> {
+        let y = usize::from(self.y);
+        let mut previous = self.previous.as_deref_mut().map(|previous| {
+            let start = y * usize::from(self.width);
+            previous[start..start + row.len()].iter_mut()
+        });
+
+        let mut written = 0;
+        let mut chunk = [0u16; CHUNK_LEN];
+        let mut current = [0u16; CHUNK_LEN];
+        for (i, pixels) in row.chunks(CHUNK_LEN).enumerate() {
+            let chunk = &mut chunk[..pixels.len()];
+            for (x, (pixel, &rgb)) in chunk.iter_mut().zip(pixels).enumerate() {
+                let mut buf = [0u8; 3];
+                BigEndian::write_u24(&mut buf, B::read_u24(&rgb));
+
+                *pixel = if self.dither {
+                    dither(buf, i * CHUNK_LEN + x, y)
+                } else {
+                    encode_rgb565_unchecked(rgb888_to_rgb565(buf))
+                };
+            }
+
+            if let Some(previous) = &previous {
+                current[..chunk.len()].copy_from_slice(chunk);
+                for (pixel, previous) in chunk.iter_mut().zip(previous.as_slice()) {
+                    *pixel ^= *previous;
+                }
+            }
+
+            let progress = self
+                .encoder
+                .encode_to_slice(chunk, &mut output[written..], usize::MAX);
+            written += progress.bytes_written;
+
+            // only the pixels that were encoded make it into the previous frame
+            if let Some(previous) = &mut previous {
+                // `current` goes first, so that `zip` doesn't take an extra pixel from `previous`
+                for (&current, previous) in current[..progress.pixels_consumed]
+                    .iter()
+                    .zip(previous.by_ref())
+                {
+                    *previous = current;
+                }
+            }
+
+            if progress.pixels_consumed < chunk.len() {
+                return None;
+            }
+        }
+
+        self.y += 1;
+        Some(written)
+    }
+
+    /// Writes the end of the frame into `output`, returning the number of bytes written, and resets
+    /// the pipeline for the next frame.
+    ///
+    /// `output` needs to hold at least 2 bytes.
+    pub fn finish(&mut self, output: &mut [u8]) -> Result<usize, PipelineError> {
+        ensure!(self.y == self.height, PipelineError::IncompleteFrame);
+
+        let written = self
+            .encoder
+            .finish(output)
+            .ok_or(PipelineError::OutputTooSmall)?;
+
+        self.reset();
+        Ok(written)
+    }
+
+    /// Starts over with a new frame.
+    fn reset(&mut self) {
+        self.encoder = Q565StreamingEncodeContext::new_sized();
+        self.y = 0;
+    }
+}
+
+/// Undoes the delta stage of a [`Pipeline`]: XORs the decoded `delta` frame into `frame`, which
+/// holds the previous frame.
+///
+/// Both need to use the same byte order.
+pub fn apply_delta(frame: &mut [u16], delta: &[u16]) {
+    for (pixel, delta) in frame.iter_mut().zip(delta) {
+        *pixel ^= delta;
+    }
+}
+
+/// Converts an RGB888 pixel at the given position of an image to RGB565, with the ordered
+/// dithering of [`Pipeline::with_dithering`].
+pub fn dither([r, g, b]: [u8; 3], x: usize, y: usize) -> u16 {
+    let threshold = u16::from(BAYER_4X4[y % 4][x % 4]);
+
+    // spread the threshold over one step of the target precision: 8 for 5 bits, 4 for 6 bits
+    let r = (u16::from(r) + threshold / 2).min(255) >> 3;
+    let g = (u16::from(g) + threshold / 4).min(255) >> 2;
+    let b = (u16::from(b) + threshold / 2).min(255) >> 3;
+
+    (r << 11) | (g << 5) | b
+}
@@ -0,0 +1,190 @@
+//! Straightforward, unoptimized encoder, following the description of the format step by step.
+//!
+//! It produces exactly the same output as [`Q565EncodeContext`](crate::encode::Q565EncodeContext)
+//! (with [`EncoderVersion::V1`](crate::encode::EncoderVersion::V1)), which is checked by the
+//! differential tests. It's meant as readable documentation of the encoder heuristics, e.g. for
+//! porting the format to other languages, and as an oracle in tests. Use the regular encoders for
+//! anything else; this one is a lot slower.
+
+use crate::{consts::*, encode::Q565Encoder, ColorArraySize, HeaderInfo};
+use alloc::vec::Vec;
+
+/// Longest run a single [`Q565_OP_RUN`] can encode.
+const MAX_RUN: usize = 62;
+
+/// Encodes an image with the given color array profile.
+///
+/// Returns `None` if `pixels` doesn't hold `width * height` pixels.
+pub fn encode(
+    color_array_size: ColorArraySize,
+    width: u16,
+    height: u16,
+    pixels: &[u16],
+) -> Option<Vec<u8>> {
+    if crate::pixel_count(width, height) != Some(pixels.len()) {
+        return None;
+    }
+
+    let mut output = Vec::new();
+    write_header(color_array_size, width, height, &mut output);
+    encode_pixels(color_array_size, pixels, &mut output);
+    Some(output)
+}
+
+fn write_header(color_array_size: ColorArraySize, width: u16, height: u16, output: &mut Vec<u8>) {
+    // The plain header for the default profile, so older decoders can read the image, and the
+    // extended header for all others.
+    let header = HeaderInfo {
+        width,
+        height,
+        color_array_size,
+        raw: false,
+    };
+    let (header, header_len) = header.to_bytes();
+    output.extend_from_slice(&header[..header_len]);
+}
+
+/// Encodes the pixels of an image and the end marker.
+fn encode_pixels(color_array_size: ColorArraySize, pixels: &[u16], output: &mut Vec<u8>) {
+    // The no-array profile doesn't have a color array, but an empty one behaves the same: there is
+    // never a match, and nothing is stored.
+    let mut array = alloc::vec![0u16; color_array_size.entries()];
+    // Both the encoder and the decoder start out with a black previous pixel.
+    let mut previous = 0u16;
+
+    let mut i = 0;
+    while i < pixels.len() {
+        let pixel = pixels[i];
+
+        // 1. Repeats of the previous pixel are encoded as runs, split into runs of at most 62
+        //    pixels, with the remainder last.
+        if pixel == previous {
+            let mut count = 0;
+            while i + count < pixels.len() && pixels[i + count] == previous {
+                count += 1;
+            }
+            i += count;
+
+            while count > MAX_RUN {
+                output.push(Q565_OP_RUN | (MAX_RUN - 1) as u8);
+                count -= MAX_RUN;
+            }
+            output.push(Q565_OP_RUN | (count - 1) as u8);
+            continue;
+        }
+        i += 1;
+
+        let (r, g, b) = channels(pixel);
+        let (r_prev, g_prev, b_prev) = channels(previous);
+        previous = pixel;
+
+        // 2. If the pixel is in the color array at the position of its hash, it's encoded as that
+        //    index.
+        let index = hash(pixel) % array.len().max(1);
+        if array.get(index) == Some(&pixel) {
+            output.push(Q565_OP_INDEX | index as u8);
+            continue;
+        }
+
+        // The differences to the previous pixel, wrapping around like the channels do.
+        let dr = wrapping_diff(r, r_prev, 5);
+        let dg = wrapping_diff(g, g_prev, 6);
+        let db = wrapping_diff(b, b_prev, 5);
+
+        // 3. Small differences in all channels fit into a single byte. These pixels are not stored
+        //    in the color array, as they are cheap to encode anyway.
+        if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+            output.push(Q565_OP_DIFF | ((dr + 2) << 4 | (dg + 2) << 2 | (db + 2)) as u8);
+            continue;
+        }
+
+        // 4. Larger differences, with red and blue relative to the green difference.
+        let dr_dg = dr - dg;
+        let db_dg = db - dg;
+        if (-16..=15).contains(&dg) && (-8..=7).contains(&dr_dg) && (-8..=7).contains(&db_dg) {
+            output.push(Q565_OP_LUMA | (dg + 16) as u8);
+            output.push(((dr_dg + 8) << 4 | (db_dg + 8)) as u8);
+        }
+        // 5. Small differences to any entry of the color array, the first one that fits.
+        else if let Some((index, dr, dg, db)) =
+            array.iter().enumerate().find_map(|(index, &entry)| {
+                let (r_entry, g_entry, b_entry) = channels(entry);
+                let dr = wrapping_diff(r, r_entry, 5);
+                let dg = wrapping_diff(g, g_entry, 6);
+                let db = wrapping_diff(b, b_entry, 5);
+                ((-2..=1).contains(&dr) && (-4..=3).contains(&dg) && (-2..=1).contains(&db))
+                    .then_some((index, dr, dg, db))
+            })
+        {
+            output.push(Q565_OP_DIFF_INDEXED | ((dg + 4) << 2 | (dr + 2)) as u8);
+            output.push(((db + 2) << 6) as u8 | index as u8);
+        }
+        // 6. Otherwise, the whole pixel.
+        else {
+            output.push(Q565_OP_RGB565);
+            output.extend_from_slice(&pixel.to_le_bytes());
+        }
+
+        // Every pixel that didn't fit into a single byte is stored in the color array, at the
+        // position of its hash.
+        if let Some(entry) = array.get_mut(index) {
+            *entry = pixel;
+        }
+    }
+
+    output.push(Q565_OP_END);
+}
+
+/// [`encode`] as a [`Q565Encoder`]. The pixels are collected until
+/// [`finish`](Q565Encoder::finish), and encoded all at once.
+#[derive(Debug, Clone)]
+pub struct ReferenceEncoder {
+    color_array_size: ColorArraySize,
+    pixels: Vec<u16>,
+}
+
+impl ReferenceEncoder {
+    /// Creates an encoder for the given color array profile.
+    pub const fn new(color_array_size: ColorArraySize) -> Self {
+        Self {
+            color_array_size,
+            pixels: Vec::new(),
+        }
+    }
+}
+
+impl Q565Encoder for ReferenceEncoder {
+    fn encode_header(&mut self, width: u16, height: u16, output: &mut Vec<u8>) {
+        self.pixels.clear();
+        write_header(self.color_array_size, width, height, output);
+    }
+
+    fn push_pixels(&mut self, pixels: &[u16], _output: &mut Vec<u8>) {
+        self.pixels.extend_from_slice(pixels);
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) {
+        encode_pixels(self.color_array_size, &self.pixels, output);
+        self.pixels.clear();
+    }
+}
+
+/// Splits a pixel into its 5-bit red, 6-bit green, and 5-bit blue channels.
+fn channels(pixel: u16) -> (i32, i32, i32) {
+    let pixel = i32::from(pixel);
+    (pixel >> 11, (pixel >> 5) & 0b11_1111, pixel & 0b1_1111)
+}
+
+/// Position of a pixel in the 64-entry color array: the sum of its two bytes, modulo 64. Smaller
+/// arrays use the hash modulo their size.
+fn hash(pixel: u16) -> usize {
+    let [low, high] = pixel.to_le_bytes();
+    (usize::from(low) + usize::from(high)) % 64
+}
+
+/// Returns `a - b` for channels with `bits` bits, wrapped around into the range
+/// `-2^(bits-1)..2^(bits-1)`.
+fn wrapping_diff(a: i32, b: i32, bits: u32) -> i32 {
+    let range = 1 << bits;
+    (a - b + range / 2).rem_euclid(range) - range / 2
+}
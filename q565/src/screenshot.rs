@@ -0,0 +1,101 @@
+//! Sending device screenshots over a debug channel like [RTT], and finding them again on the host.
+//!
+//! On the device, [`send`] encodes a framebuffer in small pieces and passes them to a write
+//! function, e.g. of an RTT up channel, so no buffer for the whole encoded image is needed. Each
+//! screenshot is prefixed with [`SCREENSHOT_TAG`], so it can be picked out of a channel that also
+//! carries other data.
+//!
+//! ```ignore
+//! // with `rtt-target`, in blocking mode so no bytes are dropped
+//! let mut channel: UpChannel = /* ... */;
+//! let source = StridedPixels::new(&framebuffer, 240, 135, 240).unwrap();
+//! let mut scratch = [0; 64];
+//! q565::screenshot::send(&source, &mut scratch, |bytes| {
+//!     channel.write(bytes);
+//! })?;
+//! ```
+//!
+//! On the host, [`find`] finds the next screenshot in the bytes read from the channel so far.
+//! `q565 grab-rtt` does this with the RTT output of a debug probe and saves the screenshot as an
+//! image.
+//!
+//! [RTT]: https://wiki.segger.com/RTT
+
+use crate::{
+    consts::MAX_OP_LEN,
+    decode::{DecodeError, Q565DecodeContext},
+    encode::{encode_source_in_pieces, PixelSource},
+    stream::{Op, OpReader},
+};
+use core::convert::Infallible;
+
+/// Bytes sent before each screenshot.
+pub const SCREENSHOT_TAG: [u8; 4] = *b"q5ss";
+
+/// Minimum length of the scratch buffer passed to [`send`]. Longer buffers mean fewer, larger
+/// writes.
+pub const MIN_SCRATCH_LEN: usize = MAX_OP_LEN;
+
+error_enum! {
+    pub enum ScreenshotError {
+        /// The scratch buffer is shorter than [`MIN_SCRATCH_LEN`].
+        ScratchTooSmall = 1,
+    }
+}
+
+/// Encodes `source` and passes the tagged screenshot to `write`, in pieces of at most
+/// `scratch.len()` bytes. Returns the number of bytes written, including the tag.
+pub fn send(
+    source: &dyn PixelSource,
+    scratch: &mut [u8],
+    mut write: impl FnMut(&[u8]),
+) -> Result<usize, ScreenshotError> {
+    ensure!(
+        scratch.len() >= MIN_SCRATCH_LEN,
+        ScreenshotError::ScratchTooSmall
+    );
+
+    write(&SCREENSHOT_TAG);
+    let written = encode_source_in_pieces(source, scratch, |piece| {
+        write(piece);
+        Ok::<_, Infallible>(())
+    });
+    match written {
+        Ok(len) => Ok(SCREENSHOT_TAG.len() + len),
+        Err(never) => match never {},
+    }
+}
+
+/// Finds the first complete screenshot in `data`.
+///
+/// Returns the encoded image, without the tag, and the number of bytes up to its end, after which
+/// the search for the next screenshot continues. Returns `None` if `data` holds no screenshot, or
+/// only the start of one; in that case, retry once more data was received.
+///
+/// Tags followed by an invalid header are skipped. The ops aren't validated beyond finding the end
+/// marker, that's up to the decoder.
+pub fn find(data: &[u8]) -> Option<(&[u8], usize)> {
+    let mut start = 0;
+    loop {
+        let tag = data[start..]
+            .windows(SCREENSHOT_TAG.len())
+            .position(|window| window == SCREENSHOT_TAG)?;
+        let image_start = start + tag + SCREENSHOT_TAG.len();
+        let image = &data[image_start..];
+
+        match Q565DecodeContext::decode_header(image) {
+            Ok((_, ops)) => {
+                let header_len = image.len() - ops.len();
+                let mut reader = OpReader::new(ops);
+                let has_end = reader.by_ref().any(|(_, op)| op == Op::End);
+                if !has_end {
+                    return None;
+                }
+                let len = header_len + reader.offset();
+                return Some((&image[..len], image_start + len));
+            }
+            Err(DecodeError::UnexpectedEof) => return None,
+            Err(_) => start = image_start,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Power-on self test of the encoder and the decoders, e.g. for products that need to check their
+//! code paths at startup.
+//!
+//! [`run`] encodes a small built-in image that uses every op, compares the result byte by byte
+//! with the expected stream, and decodes it again with the regular and the streaming decoder (the
+//! latter is skipped with the `forbid-unsafe` feature, which leaves it out of the crate). It
+//! uses the same code as the rest of the crate, compiled for the device, so it catches
+//! miscompilations and corrupted firmware images as well as regressions.
+//!
+//! ```
+//! let mut scratch = [0; q565::selftest::SCRATCH_LEN];
+//! q565::selftest::run(&mut scratch).unwrap();
+//! ```
+
+use crate::{
+    byteorder::{Endianness, NativeEndian},
+    decode::{DecodeError, InfallibleDecodeOutput, Q565DecodeContext},
+    encode::{Q565EncodeContext, Q565StreamingEncodeContext},
+    max_stream_len,
+};
+#[cfg(not(feature = "forbid-unsafe"))]
+use crate::{decode::streaming_no_header::Q565StreamingDecodeContext, HEADER_LEN};
+
+const WIDTH: u16 = 8;
+const HEIGHT: u16 = 4;
+
+/// The test image, using every op at least once.
+#[rustfmt::skip]
+const PIXELS: [u16; WIDTH as usize * HEIGHT as usize] = [
+    0x0000, 0x0000, 0x0000, 0x0841, 0x0862, 0xF800, 0xF800, 0x07E0,
+    0x0841, 0x0862, 0x0883, 0x18C3, 0xF800, 0xF821, 0x001F, 0x001F,
+    0x001F, 0x001F, 0xFFFF, 0xFFDF, 0x07E0, 0x0000, 0x8410, 0x8430,
+    0x8431, 0x1234, 0xABCD, 0xABCD, 0x0841, 0x1255, 0x0000, 0x0000,
+];
+
+/// The test image, encoded.
+#[rustfmt::skip]
+const ENCODED: [u8; 47] = [
+    b'q', b'5', b'6', b'5', 0x08, 0x00, 0x04, 0x00,
+    0xc2, 0x92, 0x77, 0x6f, 0x8d, 0x99, 0xc0, 0x76,
+    0x09, 0x6f, 0x6f, 0x92, 0x86, 0x38, 0x6f, 0x74,
+    0xc2, 0x56, 0x66, 0x7f, 0x00, 0xfe, 0x10, 0x84,
+    0x6e, 0x6b, 0xfe, 0x34, 0x12, 0xfe, 0xcd, 0xab,
+    0xc0, 0x09, 0xb6, 0xc6, 0x00, 0xc0, 0xff,
+];
+
+/// Size of the scratch buffer [`run`] needs, in bytes.
+pub const SCRATCH_LEN: usize = max_stream_len(WIDTH, HEIGHT);
+
+error_enum! {
+    pub enum SelfTestError {
+        /// The scratch buffer is smaller than `SCRATCH_LEN`.
+        ScratchTooSmall = 1,
+        /// The encoder produced a different stream than expected.
+        EncodeMismatch = 2,
+        /// The decoder rejected the test image.
+        Decode { source: DecodeError } = 3,
+        /// A decoder produced different pixels than expected.
+        DecodeMismatch = 4,
+    }
+}
+
+impl From<DecodeError> for SelfTestError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// Runs the self test, using `scratch` for the encoded image. See the [module docs](self).
+pub fn run(scratch: &mut [u8]) -> Result<(), SelfTestError> {
+    let scratch = scratch
+        .get_mut(..SCRATCH_LEN)
+        .ok_or(SelfTestError::ScratchTooSmall)?;
+
+    // encode
+    let (header, header_len) = Q565EncodeContext::<64>::header(WIDTH, HEIGHT).to_bytes();
+    scratch[..header_len].copy_from_slice(&header[..header_len]);
+    let mut encoder = Q565StreamingEncodeContext::new();
+    let progress = encoder.encode_to_slice(&PIXELS, &mut scratch[header_len..], usize::MAX);
+    ensure!(
+        progress.pixels_consumed == PIXELS.len(),
+        SelfTestError::EncodeMismatch
+    );
+    let mut len = header_len + progress.bytes_written;
+    len += encoder
+        .finish(&mut scratch[len..])
+        .ok_or(SelfTestError::EncodeMismatch)?;
+    ensure!(scratch[..len] == ENCODED, SelfTestError::EncodeMismatch);
+
+    // decode
+    let mut output = CompareDecodeOutput {
+        position: 0,
+        matches: true,
+    };
+    Q565DecodeContext::decode::<NativeEndian>(&scratch[..len], &mut output)?;
+    ensure!(output.matches, SelfTestError::DecodeMismatch);
+
+    // decode, streaming
+    #[cfg(not(feature = "forbid-unsafe"))]
+    {
+        let mut pixels = [0; PIXELS.len()];
+        // SAFETY: the input is the built-in test image, which is valid and decodes to exactly as
+        // many pixels as the output holds
+        let written = unsafe {
+            Q565StreamingDecodeContext::new().streaming_decode_to_slice_unchecked::<NativeEndian>(
+                &ENCODED[HEADER_LEN..],
+                &mut pixels,
+            )
+        };
+        ensure!(
+            written == PIXELS.len() && pixels == PIXELS,
+            SelfTestError::DecodeMismatch
+        );
+    }
+
+    Ok(())
+}
+
+/// Decode output comparing the pixels with the test image instead of storing them.
+struct CompareDecodeOutput {
+    position: usize,
+    matches: bool,
+}
+
+impl InfallibleDecodeOutput for CompareDecodeOutput {
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.write_many_pixels::<B>(color, 1);
+    }
+
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        for _ in 0..count {
+            self.matches &= PIXELS.get(self.position) == Some(&color);
+            self.position += 1;
+        }
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        Some(PIXELS.len())
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.position
+    }
+}
@@ -0,0 +1,180 @@
+//! Dumps of the encoder and decoder state, to compare the state of another decoder, e.g. one in
+//! hardware, with the reference op by op (see `q565 replay`).
+//!
+//! A [`StateSnapshot`] holds the state the encoder and decoder share: the previous pixel and the
+//! color array. It's printed as text, one row of 8 color array entries per line, and parsed back
+//! from the same text:
+//!
+//! ```text
+//! prev: 0x07e0
+//! arr[0..8]: 0x0000 0x0000 0x0000 0x0000 0x0000 0x0000 0x0000 0x0000
+//! arr[8..16]: 0x0000 0x0000 0x0000 0x0000 0x0000 0x07e0 0x0000 0x0000
+//! ```
+//!
+//! so that snapshots can be stored as golden files and diffed. [`StateSnapshot::from_bytes`] reads
+//! a memory dump of a [`Q565DecodeContext`] instead.
+
+use crate::{
+    byteorder::Endianness, decode::Q565DecodeContext, encode::Q565EncodeContext, ColorArraySize,
+};
+use core::{fmt, str::FromStr};
+
+/// Entries per line of the text format.
+const ENTRIES_PER_LINE: usize = 8;
+
+/// The previous pixel and the color array of an encoder or decoder, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateSnapshot {
+    pub prev: u16,
+    arr: [u16; 64],
+    entries: usize,
+}
+
+impl StateSnapshot {
+    /// Creates a snapshot of the given state. Returns `None` if the color array doesn't have 16,
+    /// 32, or 64 entries.
+    pub fn new(prev: u16, color_array: &[u16]) -> Option<Self> {
+        ColorArraySize::from_entries(color_array.len())?;
+
+        let mut arr = [0; 64];
+        arr[..color_array.len()].copy_from_slice(color_array);
+        Some(Self {
+            prev,
+            arr,
+            entries: color_array.len(),
+        })
+    }
+
+    #[inline]
+    pub fn color_array(&self) -> &[u16] {
+        &self.arr[..self.entries]
+    }
+
+    /// Reads a memory dump of a [`Q565DecodeContext`], i.e. the previous pixel followed by the
+    /// color array, in the byte order `B` of the target it was dumped from. The number of entries
+    /// follows from the length of `data`, which must be 34, 66, or 130 bytes.
+    pub fn from_bytes<B>(data: &[u8]) -> Option<Self>
+    where
+        B: Endianness,
+    {
+        let (prev, arr) = data.split_first_chunk::<2>()?;
+        if arr.len() % 2 != 0 {
+            return None;
+        }
+
+        let mut color_array = [0; 64];
+        let entries = arr.len() / 2;
+        for (entry, bytes) in color_array.iter_mut().zip(arr.chunks_exact(2)) {
+            *entry = B::read_u16(bytes);
+        }
+        Self::new(B::read_u16(prev), color_array.get(..entries)?)
+    }
+
+    /// Iterates over the color array entries that differ from the ones of `other`, yielding the
+    /// index, the entry of `self`, and the entry of `other`.
+    ///
+    /// Entries only one of the snapshots has are compared with `0`, the initial value.
+    pub fn changed_entries<'a>(
+        &'a self,
+        other: &'a StateSnapshot,
+    ) -> impl Iterator<Item = (usize, u16, u16)> + 'a {
+        let entries = self.entries.max(other.entries);
+        (0..entries)
+            .map(|index| (index, self.arr[index], other.arr[index]))
+            .filter(|(_, a, b)| a != b)
+    }
+}
+
+impl fmt::Display for StateSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "prev: {:#06x}", self.prev)?;
+        for (row, entries) in self.color_array().chunks(ENTRIES_PER_LINE).enumerate() {
+            let start = row * ENTRIES_PER_LINE;
+            write!(f, "\narr[{start}..{}]:", start + entries.len())?;
+            for entry in entries {
+                write!(f, " {entry:#06x}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_hex(s: &str) -> Option<u16> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+    u16::from_str_radix(digits, 16).ok()
+}
+
+impl FromStr for StateSnapshot {
+    type Err = &'static str;
+
+    /// Parses the text format of the [module docs](self). Empty lines are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut prev = None;
+        let mut arr = [0; 64];
+        let mut entries = 0;
+
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, values) = line.split_once(':').ok_or("expected `key: values`")?;
+            if key == "prev" {
+                prev = Some(parse_hex(values.trim()).ok_or("expected a `0x` hex previous pixel")?);
+                continue;
+            }
+
+            let range = key
+                .strip_prefix("arr[")
+                .and_then(|key| key.strip_suffix(']'))
+                .and_then(|range| range.split_once(".."))
+                .ok_or("expected `prev` or `arr[start..end]`")?;
+            let (Ok(start), Ok(end)) = (range.0.parse::<usize>(), range.1.parse::<usize>()) else {
+                return Err("expected `prev` or `arr[start..end]`");
+            };
+            if start != entries || end < start || end > arr.len() {
+                return Err("the color array rows must be consecutive and within 64 entries");
+            }
+
+            let mut values = values.split_whitespace();
+            for entry in &mut arr[start..end] {
+                let value = values.next().ok_or("too few entries in a row")?;
+                *entry = parse_hex(value).ok_or("expected `0x` hex entries")?;
+            }
+            if values.next().is_some() {
+                return Err("too many entries in a row");
+            }
+            entries = end;
+        }
+
+        let prev = prev.ok_or("missing `prev`")?;
+        Self::new(prev, &arr[..entries]).ok_or("the color array must have 16, 32, or 64 entries")
+    }
+}
+
+impl<const N: usize> Q565DecodeContext<N> {
+    #[inline]
+    pub fn snapshot(&self) -> StateSnapshot {
+        // `N` is checked when creating the context
+        StateSnapshot::new(self.prev, &self.arr).unwrap()
+    }
+
+    /// Creates a context with the state of `snapshot`. Returns `None` if the snapshot doesn't have
+    /// `N` color array entries.
+    pub fn restore(snapshot: &StateSnapshot) -> Option<Self> {
+        let mut ctx = Self::new_sized();
+        ctx.prev = snapshot.prev;
+        ctx.arr = *snapshot.color_array().first_chunk()?;
+        (snapshot.entries == N).then_some(ctx)
+    }
+}
+
+impl<const N: usize> Q565EncodeContext<N> {
+    #[inline]
+    pub fn snapshot(&self) -> StateSnapshot {
+        // `N` is checked when creating the context
+        StateSnapshot::new(self.prev, self.color_array()).unwrap()
+    }
+
+    /// Creates a context with the state of `snapshot`, including the color array index, see
+    /// [`Q565DecodeContext::restore`].
+    pub fn restore(snapshot: &StateSnapshot) -> Option<Self> {
+        Q565DecodeContext::restore(snapshot).map(Self::from)
+    }
+}
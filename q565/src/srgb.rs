@@ -0,0 +1,133 @@
+//! RGB888/RGB565 conversions in linear light, for panels where the fidelity of gradients matters.
+//!
+//! The conversions in [`utils`](crate::utils) work on the sRGB-encoded values, which is what
+//! displays expect and what most tools do. Picking the nearest RGB565 level in linear light instead
+//! gives a slightly different result for some values, and decoding to linear intensities lets
+//! applications blend and scale the pixels correctly.
+//!
+//! Both directions are selectable per call: [`Quantization::Linear`] for the encoders, and the
+//! [`LinearRgb`] color format for the decoders.
+//!
+//! ```
+//! use q565::{decode::Decoder, encode::Encoder, srgb::LinearRgb, utils::Quantization};
+//!
+//! let mut encoded = Vec::new();
+//! Encoder::new()
+//!     .quantization(Quantization::Linear)
+//!     .encode_rgb888(2, 1, &[[0x80, 0x80, 0x80], [0xFF, 0xFF, 0xFF]], &mut encoded)
+//!     .unwrap();
+//!
+//! let (_, pixels) = Decoder::new()
+//!     .color_format::<LinearRgb>()
+//!     .decode_to_vec(&encoded)
+//!     .unwrap();
+//! assert_eq!(pixels[1], [0xFFFF; 3]);
+//! ```
+//!
+//! The conversions are table lookups, with 704 bytes of tables in total.
+
+use crate::{
+    byteorder::{Endianness, NativeEndian},
+    utils::decode_565,
+    ColorFormat,
+};
+
+/// Converts an sRGB-encoded RGB888 pixel into an RGB565 pixel, picking the level of every channel
+/// that is nearest to it in linear light.
+#[inline]
+pub const fn rgb888_to_rgb565([r, g, b]: [u8; 3]) -> [u8; 3] {
+    [
+        NEAREST_LEVEL_5[r as usize],
+        NEAREST_LEVEL_6[g as usize],
+        NEAREST_LEVEL_5[b as usize],
+    ]
+}
+
+/// Converts an RGB565 pixel into linear intensities, scaled to `0..=65535`.
+#[inline]
+pub const fn rgb565_to_linear([r, g, b]: [u8; 3]) -> [u16; 3] {
+    [
+        LINEAR_5[r as usize & 31],
+        LINEAR_6[g as usize & 63],
+        LINEAR_5[b as usize & 31],
+    ]
+}
+
+/// Decodes to linear intensities, see [`rgb565_to_linear`]. Every channel is a `u16` in the
+/// byte order of the decoder.
+pub enum LinearRgb {}
+impl ColorFormat for LinearRgb {
+    type OutputElement = [u16; 3];
+
+    fn to_output<B: Endianness>(color: u16) -> Self::OutputElement {
+        rgb565_to_linear(decode_565(color)).map(|channel| {
+            let mut n = [0u8; 2];
+            NativeEndian::write_u16(&mut n, channel);
+            B::read_u16(&n)
+        })
+    }
+}
+
+/// The 5-bit level nearest to every 8-bit sRGB value, in linear light.
+#[rustfmt::skip]
+const NEAREST_LEVEL_5: [u8; 256] = [
+    0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2,
+    2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 3, 3, 4, 4,
+    4, 4, 4, 4, 4, 4, 5, 5, 5, 5, 5, 5, 5, 5, 6, 6,
+    6, 6, 6, 6, 6, 6, 7, 7, 7, 7, 7, 7, 7, 7, 8, 8,
+    8, 8, 8, 8, 8, 8, 8, 9, 9, 9, 9, 9, 9, 9, 9, 10,
+    10, 10, 10, 10, 10, 10, 10, 11, 11, 11, 11, 11, 11, 11, 11, 12,
+    12, 12, 12, 12, 12, 12, 12, 13, 13, 13, 13, 13, 13, 13, 13, 13,
+    14, 14, 14, 14, 14, 14, 14, 14, 15, 15, 15, 15, 15, 15, 15, 15,
+    16, 16, 16, 16, 16, 16, 16, 16, 17, 17, 17, 17, 17, 17, 17, 17,
+    17, 18, 18, 18, 18, 18, 18, 18, 18, 19, 19, 19, 19, 19, 19, 19,
+    19, 20, 20, 20, 20, 20, 20, 20, 20, 21, 21, 21, 21, 21, 21, 21,
+    21, 22, 22, 22, 22, 22, 22, 22, 22, 22, 23, 23, 23, 23, 23, 23,
+    23, 23, 24, 24, 24, 24, 24, 24, 24, 24, 25, 25, 25, 25, 25, 25,
+    25, 25, 26, 26, 26, 26, 26, 26, 26, 26, 26, 27, 27, 27, 27, 27,
+    27, 27, 27, 28, 28, 28, 28, 28, 28, 28, 28, 29, 29, 29, 29, 29,
+    29, 29, 29, 30, 30, 30, 30, 30, 30, 30, 30, 31, 31, 31, 31, 31,
+];
+
+/// The 6-bit level nearest to every 8-bit sRGB value, in linear light.
+#[rustfmt::skip]
+const NEAREST_LEVEL_6: [u8; 256] = [
+    0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4,
+    4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 6, 7, 7, 7, 7, 8,
+    8, 8, 8, 9, 9, 9, 9, 10, 10, 10, 10, 11, 11, 11, 11, 12,
+    12, 12, 12, 13, 13, 13, 13, 14, 14, 14, 14, 15, 15, 15, 15, 16,
+    16, 16, 16, 17, 17, 17, 17, 18, 18, 18, 18, 19, 19, 19, 19, 20,
+    20, 20, 20, 20, 21, 21, 21, 21, 22, 22, 22, 22, 23, 23, 23, 23,
+    24, 24, 24, 24, 25, 25, 25, 25, 26, 26, 26, 26, 27, 27, 27, 27,
+    28, 28, 28, 28, 29, 29, 29, 29, 30, 30, 30, 30, 31, 31, 31, 31,
+    32, 32, 32, 32, 33, 33, 33, 33, 34, 34, 34, 34, 35, 35, 35, 35,
+    36, 36, 36, 36, 37, 37, 37, 37, 38, 38, 38, 38, 39, 39, 39, 39,
+    40, 40, 40, 40, 41, 41, 41, 41, 42, 42, 42, 42, 42, 43, 43, 43,
+    43, 44, 44, 44, 44, 45, 45, 45, 45, 46, 46, 46, 46, 47, 47, 47,
+    47, 48, 48, 48, 48, 49, 49, 49, 49, 50, 50, 50, 50, 51, 51, 51,
+    51, 52, 52, 52, 52, 53, 53, 53, 53, 54, 54, 54, 54, 55, 55, 55,
+    55, 56, 56, 56, 56, 57, 57, 57, 57, 58, 58, 58, 58, 59, 59, 59,
+    59, 60, 60, 60, 60, 61, 61, 61, 61, 62, 62, 62, 62, 63, 63, 63,
+];
+
+/// The linear intensity of every 5-bit level, scaled to `0..=65535`.
+#[rustfmt::skip]
+const LINEAR_5: [u16; 32] = [
+    0, 164, 352, 625, 992, 1461, 2040, 2734,
+    3550, 4492, 5565, 6775, 8127, 9623, 11269, 13069,
+    15026, 17143, 19426, 21877, 24499, 27295, 30270, 33426,
+    36766, 40292, 44009, 47918, 52022, 56325, 60828, 65535,
+];
+
+/// The linear intensity of every 6-bit level, scaled to `0..=65535`.
+#[rustfmt::skip]
+const LINEAR_6: [u16; 64] = [
+    0, 81, 161, 244, 345, 466, 609, 776,
+    965, 1180, 1420, 1687, 1980, 2301, 2651, 3030,
+    3439, 3879, 4349, 4851, 5386, 5953, 6554, 7189,
+    7858, 8562, 9302, 10078, 10890, 11739, 12626, 13551,
+    14513, 15515, 16556, 17636, 18757, 19918, 21120, 22363,
+    23648, 24974, 26344, 27756, 29211, 30710, 32253, 33840,
+    35471, 37148, 38870, 40638, 42452, 44312, 46219, 48173,
+    50174, 52223, 54320, 56465, 58659, 60901, 63193, 65535,
+];
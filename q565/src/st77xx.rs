@@ -0,0 +1,212 @@
+//! Direct conversion of Q565 images and [update messages](crate::update) into the SPI command
+//! stream of ST7789/ST7735/ILI9341-style display controllers.
+//!
+//! Every image (or rectangle of an update) becomes a window command sequence, followed by its
+//! pixels as RGB565 memory write data:
+//!
+//! - [`CASET`]: start and end column, as u16be
+//! - [`RASET`]: start and end row, as u16be
+//! - [`RAMWR`], followed by the pixels as u16be, the panel's native 16-bit color format
+//!
+//! The bytes are handed to a [`CommandSink`], one command or data block at a time, so the MCU can
+//! send each block verbatim via DMA and only needs to toggle the D/C line between them.
+
+use crate::byteorder::{BigEndian, Endianness};
+use crate::{
+    decode::{DecodeError, InfallibleDecodeOutput, Q565DecodeContext},
+    update::{for_each_rect, UpdateError},
+    Rect,
+};
+
+/// Column address set.
+pub const CASET: u8 = 0x2A;
+/// Row address set.
+pub const RASET: u8 = 0x2B;
+/// Memory write.
+pub const RAMWR: u8 = 0x2C;
+
+/// Receiver of the command stream, e.g. an SPI bus with a D/C line.
+pub trait CommandSink {
+    /// Sends a command byte (D/C low).
+    fn command(&mut self, command: u8);
+    /// Sends parameter or pixel data (D/C high).
+    fn data(&mut self, data: &[u8]);
+}
+
+/// Decode output that sends the pixels to a [`CommandSink`] as [`RAMWR`] data, after setting up
+/// the window they are drawn to.
+///
+/// The pixels are collected in `buffer` as u16be, regardless of the byte order passed to the
+/// decoder, and the buffer is sent whenever it is full. Runs fill the buffer with the repeated
+/// color, so a long run is sent as a few identical blocks.
+///
+/// Call [`finish`](Self::finish) after decoding to send the last, partially filled block. To keep
+/// access to the output, pass it to the decoder by mutable reference.
+pub struct St77xxDecodeOutput<'a, S> {
+    sink: S,
+    buffer: &'a mut [u8],
+    filled: usize,
+    output_idx: usize,
+}
+
+impl<'a, S> St77xxDecodeOutput<'a, S>
+where
+    S: CommandSink,
+{
+    /// Sends the window commands for `window`, and prepares to send its pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` can't hold a single pixel.
+    pub fn new(mut sink: S, buffer: &'a mut [u8], window: Rect) -> Self {
+        assert!(buffer.len() >= 2, "buffer must hold at least one pixel");
+
+        set_window(&mut sink, window);
+        sink.command(RAMWR);
+
+        // only ever send whole pixels
+        let len = buffer.len() & !1;
+        Self {
+            sink,
+            buffer: &mut buffer[..len],
+            filled: 0,
+            output_idx: 0,
+        }
+    }
+
+    /// Sends the remaining pixels and returns the sink.
+    pub fn finish(mut self) -> S {
+        self.flush();
+        self.sink
+    }
+
+    /// Sends the pixels collected so far, if any.
+    pub fn flush(&mut self) {
+        if self.filled > 0 {
+            self.sink.data(&self.buffer[..self.filled]);
+            self.filled = 0;
+        }
+    }
+
+    #[inline]
+    fn fill(&mut self, color: u16, count: usize) {
+        let color = color.to_be_bytes();
+
+        let mut remaining = count;
+        while remaining > 0 {
+            let free = &mut self.buffer[self.filled..];
+            let n = remaining.min(free.len() / 2);
+            for pixel in free[..2 * n].chunks_exact_mut(2) {
+                pixel.copy_from_slice(&color);
+            }
+            self.filled += 2 * n;
+            remaining -= n;
+
+            if self.filled == self.buffer.len() {
+                self.flush();
+            }
+        }
+    }
+}
+
+impl<S> InfallibleDecodeOutput for St77xxDecodeOutput<'_, S>
+where
+    S: CommandSink,
+{
+    #[inline]
+    fn write_pixel<B: Endianness>(&mut self, color: u16) {
+        self.fill(color, 1);
+        self.output_idx += 1;
+    }
+
+    #[inline]
+    fn write_many_pixels<B: Endianness>(&mut self, color: u16, count: usize) {
+        self.fill(color, count);
+        self.output_idx += count;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn current_output_position(&self) -> usize {
+        self.output_idx
+    }
+}
+
+/// Sends the [`CASET`] and [`RASET`] commands selecting `window`.
+pub fn set_window(sink: &mut impl CommandSink, window: Rect) {
+    // the end addresses are inclusive
+    let x_end = window.x.wrapping_add(window.width).wrapping_sub(1);
+    let y_end = window.y.wrapping_add(window.height).wrapping_sub(1);
+
+    let [x1, x2] = window.x.to_be_bytes();
+    let [x3, x4] = x_end.to_be_bytes();
+    sink.command(CASET);
+    sink.data(&[x1, x2, x3, x4]);
+
+    let [y1, y2] = window.y.to_be_bytes();
+    let [y3, y4] = y_end.to_be_bytes();
+    sink.command(RASET);
+    sink.data(&[y1, y2, y3, y4]);
+}
+
+/// Converts an image into the command stream drawing it at `(x, y)`, using `buffer` for the pixel
+/// data.
+///
+/// Returns the number of pixels sent.
+pub fn image_to_commands<S: CommandSink>(
+    data: &[u8],
+    x: u16,
+    y: u16,
+    sink: &mut S,
+    buffer: &mut [u8],
+) -> Result<usize, DecodeError> {
+    let (header, _) = Q565DecodeContext::decode_header(data)?;
+    let window = Rect {
+        x,
+        y,
+        width: header.width,
+        height: header.height,
+    };
+
+    let mut output = St77xxDecodeOutput::new(sink, buffer, window);
+    // the output ignores the byte order
+    let (_, pixels_written) = Q565DecodeContext::decode::<BigEndian>(data, &mut output)?;
+    output.finish();
+
+    Ok(pixels_written)
+}
+
+/// Converts an [update message](crate::update) into the command stream drawing each of its
+/// rectangles, using `buffer` for the pixel data.
+///
+/// The rectangles are not checked against the size of the panel. Returns the number of rectangles.
+pub fn update_to_commands<S: CommandSink>(
+    data: &[u8],
+    sink: &mut S,
+    buffer: &mut [u8],
+) -> Result<usize, UpdateError> {
+    for_each_rect(data, |x, y, payload| {
+        image_to_commands(payload, x, y, sink, buffer)
+            .map(|_| ())
+            .map_err(|source| UpdateError::Decode { source })
+    })
+}
+
+impl<S> CommandSink for &mut S
+where
+    S: CommandSink + ?Sized,
+{
+    #[inline]
+    fn command(&mut self, command: u8) {
+        (**self).command(command)
+    }
+
+    #[inline]
+    fn data(&mut self, data: &[u8]) {
+        (**self).data(data)
+    }
+}
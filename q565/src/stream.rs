@@ -0,0 +1,229 @@
+//! Op-level access to Q565 image data, for tooling that needs to know which bytes produced which
+//! pixels.
+//!
+//! This is considerably slower than the regular decoders, which should be preferred whenever
+//! only the pixels are needed.
+
+use crate::{
+    consts::*,
+    decode::{
+        ops::{direct_bigger_diff, direct_small_diff, indexed_diff},
+        DecodeError, Q565DecodeContext,
+    },
+    utils::hash,
+};
+
+/// A single operation of a Q565 stream, see [`consts`](crate::consts) for the bit layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Index into the color array: `0..=63`.
+    Index(u8),
+    /// The raw `Q565_OP_DIFF` byte.
+    Diff(u8),
+    /// The raw `Q565_OP_LUMA` bytes.
+    Luma(u8, u8),
+    /// The raw `Q565_OP_DIFF_INDEXED` bytes.
+    DiffIndexed(u8, u8),
+    /// Run length: `1..=62`.
+    Run(u8),
+    /// The raw pixel.
+    Rgb565(u16),
+    End,
+}
+
+impl Op {
+    /// Parses the op at the start of `data`. Returns `None` if `data` ends in the middle of the op.
+    pub fn parse(data: &[u8]) -> Option<Op> {
+        let &byte = data.first()?;
+
+        let op = match byte {
+            Q565_OP_END => Op::End,
+            Q565_OP_RGB565 => Op::Rgb565(u16::from_le_bytes([*data.get(1)?, *data.get(2)?])),
+            _ => match byte >> 6 {
+                0b00 => Op::Index(byte),
+                0b01 => Op::Diff(byte),
+                0b10 if byte & 0b0010_0000 == 0 => Op::Luma(byte, *data.get(1)?),
+                0b10 => Op::DiffIndexed(byte, *data.get(1)?),
+                _ => Op::Run((byte & 0b0011_1111) + 1),
+            },
+        };
+
+        Some(op)
+    }
+
+    /// Size of the encoded op, in bytes.
+    #[inline]
+    pub const fn encoded_len(&self) -> usize {
+        match self {
+            Op::Index(_) | Op::Diff(_) | Op::Run(_) | Op::End => 1,
+            Op::Luma(..) | Op::DiffIndexed(..) => 2,
+            Op::Rgb565(_) => 3,
+        }
+    }
+
+    /// Number of pixels the op produces.
+    #[inline]
+    pub const fn pixel_count(&self) -> usize {
+        match self {
+            Op::Run(count) => *count as usize,
+            Op::End => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// Iterator over the ops of Q565 image data (without the header), yielding each op together with
+/// its byte offset.
+///
+/// Iteration stops after [`Op::End`], or when the data ends (possibly in the middle of an op).
+#[derive(Debug, Clone)]
+pub struct OpReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    finished: bool,
+}
+
+impl<'a> OpReader<'a> {
+    #[inline]
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            offset: 0,
+            finished: false,
+        }
+    }
+
+    /// Byte offset of the next op.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The data after the last op that was read.
+    #[inline]
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.offset..]
+    }
+}
+
+impl Iterator for OpReader<'_> {
+    type Item = (usize, Op);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let Some(op) = Op::parse(self.remaining()) else {
+            self.finished = true;
+            return None;
+        };
+
+        let offset = self.offset;
+        self.offset += op.encoded_len();
+        self.finished = op == Op::End;
+        Some((offset, op))
+    }
+}
+
+/// Returns whether the image has [row-aligned runs](crate#row-aligned-runs), i.e. whether no run
+/// crosses the end of a row. [Raw images](crate#raw-images) have no runs at all.
+///
+/// Only the ops are checked, not whether they produce the right number of pixels.
+pub fn has_row_aligned_runs(data: &[u8]) -> Result<bool, DecodeError> {
+    let (header, data) = Q565DecodeContext::decode_header(data)?;
+    let width = usize::from(header.width);
+    if header.raw || width == 0 {
+        return Ok(true);
+    }
+
+    let mut position = 0;
+    for (_, op) in OpReader::new(data) {
+        let count = op.pixel_count();
+        if matches!(op, Op::Run(_)) && position % width + count > width {
+            return Ok(false);
+        }
+        position += count;
+    }
+    Ok(true)
+}
+
+/// Finds the next plausible image in `data`, returning its offset.
+///
+/// Meant for receivers of images sent back to back, e.g. the frames of a live view: after data
+/// was lost, the rest of the current image is useless, but decoding can pick up again at the next
+/// image instead of dropping everything up to the next transfer.
+///
+/// An image is plausible if its header is valid and its ops, as far as `data` goes, fit the
+/// header: they don't produce more pixels than it claims (or fewer, if the end marker is reached),
+/// and they only reference the color array entries its profile has. An image that `data` only
+/// holds the start of is plausible, too, so the rest can be awaited. Returns `None` if no offset
+/// holds a plausible image.
+///
+/// The format has no markers within an image, so this finds image starts only. The check makes
+/// it unlikely that op data which happens to look like a header is taken for an image, but not
+/// impossible.
+pub fn resync(data: &[u8]) -> Option<usize> {
+    (0..data.len()).find(|&offset| is_plausible_image(&data[offset..]))
+}
+
+fn is_plausible_image(data: &[u8]) -> bool {
+    let Ok((header, data)) = Q565DecodeContext::decode_header(data) else {
+        return false;
+    };
+    let Some(mut remaining) = header.pixel_count() else {
+        return false;
+    };
+    if remaining == 0 {
+        return false;
+    }
+    if header.raw {
+        return true;
+    }
+
+    let entries = header.color_array_size.entries();
+    for (_, op) in OpReader::new(data) {
+        let index = match op {
+            Op::Index(byte) => Some(byte & 0b0011_1111),
+            Op::DiffIndexed(_, second_byte) => Some(second_byte & 0b0011_1111),
+            Op::End => return remaining == 0,
+            _ => None,
+        };
+        if index.is_some_and(|index| usize::from(index) >= entries) {
+            return false;
+        }
+
+        let Some(rest) = remaining.checked_sub(op.pixel_count()) else {
+            return false;
+        };
+        remaining = rest;
+    }
+    // the data ends within the image
+    true
+}
+
+impl<const N: usize> Q565DecodeContext<N> {
+    /// Applies a single op to the decoder state, returning the produced color and the number of
+    /// times it is repeated (`0` for [`Op::End`]).
+    pub fn apply_op(&mut self, op: Op) -> (u16, usize) {
+        let pixel = match op {
+            Op::Index(index) => {
+                self.prev = self.arr[usize::from(index) & (N - 1)];
+                return (self.prev, 1);
+            }
+            Op::Diff(byte) => {
+                self.prev = direct_small_diff(self.prev, byte);
+                return (self.prev, 1);
+            }
+            Op::Run(count) => return (self.prev, usize::from(count)),
+            Op::End => return (self.prev, 0),
+            Op::Luma(byte, second_byte) => direct_bigger_diff(self.prev, byte, second_byte),
+            Op::DiffIndexed(byte, second_byte) => indexed_diff(&self.arr, byte, second_byte),
+            Op::Rgb565(pixel) => pixel,
+        };
+
+        self.arr[usize::from(hash(pixel)) & (N - 1)] = pixel;
+        self.prev = pixel;
+        (pixel, 1)
+    }
+}
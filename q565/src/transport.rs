@@ -0,0 +1,379 @@
+//! Sending Q565 images over any byte transport: TCP, a serial port, USB, a radio, ...
+//!
+//! A transport only needs to implement [`FrameTransport`], which moves bytes. [`send_frame`]
+//! encodes an image straight into the transport, in pieces of [`CHUNK_LEN`] bytes, and a
+//! [`FrameReceiver`] reads and decodes the images on the other side. No framing is added: the
+//! images are sent as they are, and their ends are found by following the ops up to the end
+//! marker.
+//!
+//! For transports that may be slower than the frames are produced, an [`AdaptiveSender`] queues
+//! the encoded frames and drops or coalesces them once too many bytes are waiting.
+//!
+//! Implementations are provided for [`TcpStream`](std::net::TcpStream) with the `std` feature,
+//! for the ports of the `serialport` crate with the `serialport` feature, and for an in-memory
+//! [`Loopback`].
+//!
+//! ```
+//! # use q565::{byteorder::LittleEndian, encode::StridedPixels, Rgb565};
+//! use q565::transport::{send_frame, FrameReceiver, Loopback};
+//!
+//! let pixels = [0xF800, 0xF800, 0x07E0, 0x001F];
+//! let mut loopback = Loopback::new();
+//! send_frame(&mut loopback, &StridedPixels::new(&pixels, 2, 2, 2).unwrap())?;
+//!
+//! let mut receiver = FrameReceiver::new(loopback);
+//! let (header, decoded) = receiver.recv_frame::<LittleEndian, Rgb565>()?;
+//! assert_eq!((header.width, header.height), (2, 2));
+//! assert_eq!(decoded, pixels);
+//! # Ok::<(), q565::transport::TransportError<core::convert::Infallible>>(())
+//! ```
+
+use crate::decode::DecodeError;
+use crate::encode::{encode_source_in_pieces, PixelSource};
+#[cfg(feature = "alloc")]
+use crate::{
+    byteorder::Endianness, decode::Q565DecodeContext, stream::Op, ColorFormat, HeaderInfo,
+};
+#[cfg(feature = "alloc")]
+use alloc::{collections::VecDeque, vec::Vec};
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+mod adaptive;
+
+#[cfg(feature = "alloc")]
+pub use adaptive::*;
+
+/// Length of the pieces [`send_frame`] sends, and of the reads of a [`FrameReceiver`].
+pub const CHUNK_LEN: usize = 256;
+
+/// A bidirectional byte transport.
+pub trait FrameTransport {
+    type Error;
+
+    /// Sends all of `chunk`.
+    fn send_chunk(&mut self, chunk: &[u8]) -> Result<(), Self::Error>;
+
+    /// Receives the next bytes into `buf`, waiting until at least one is available. Returns the
+    /// number of bytes received, `0` if the transport is closed.
+    fn recv_chunk(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+impl<T> FrameTransport for &mut T
+where
+    T: FrameTransport + ?Sized,
+{
+    type Error = T::Error;
+
+    #[inline]
+    fn send_chunk(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        (**self).send_chunk(chunk)
+    }
+
+    #[inline]
+    fn recv_chunk(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        (**self).recv_chunk(buf)
+    }
+}
+
+#[derive(Debug)]
+#[repr(u8)]
+pub enum TransportError<E> {
+    /// The transport failed.
+    Transport { source: E } = 1,
+    /// The transport was closed before the image was complete.
+    Closed = 2,
+    /// The received image is invalid.
+    Decode { source: DecodeError } = 3,
+    /// The received image has more pixels than allowed, see [`FrameReceiver::with_max_pixels`].
+    TooLarge = 4,
+}
+
+impl<E> TransportError<E> {
+    /// Returns the [error code](crate#error-codes) of the variant.
+    pub const fn as_code(&self) -> u8 {
+        match self {
+            Self::Transport { .. } => 1,
+            Self::Closed => 2,
+            Self::Decode { .. } => 3,
+            Self::TooLarge => 4,
+        }
+    }
+
+    /// Returns the variant with the given [error code](crate#error-codes).
+    ///
+    /// Returns `None` for unknown codes, and for variants with fields, which can't be
+    /// constructed from a code alone; see [`message_for_code`](Self::message_for_code).
+    pub const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            2 => Some(Self::Closed),
+            4 => Some(Self::TooLarge),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`Display`](core::fmt::Display) message of the variant with the given
+    /// [error code](crate#error-codes).
+    pub const fn message_for_code(code: u8) -> Option<&'static str> {
+        match code {
+            1 => Some("The transport failed."),
+            2 => Some("The transport was closed before the image was complete."),
+            3 => Some("The received image is invalid."),
+            4 => Some("The received image has more pixels than allowed."),
+            _ => None,
+        }
+    }
+}
+
+impl<E> fmt::Display for TransportError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match Self::message_for_code(self.as_code()) {
+            Some(message) => f.write_str(message),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for TransportError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport { source } => Some(source),
+            Self::Decode { source } => Some(source),
+            Self::Closed | Self::TooLarge => None,
+        }
+    }
+}
+
+impl<E> From<DecodeError> for TransportError<E> {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// Encodes `source` and sends it over `transport`, in pieces of at most [`CHUNK_LEN`] bytes.
+/// Returns the number of bytes sent.
+pub fn send_frame<T>(
+    mut transport: T,
+    source: &dyn PixelSource,
+) -> Result<usize, TransportError<T::Error>>
+where
+    T: FrameTransport,
+{
+    let mut scratch = [0; CHUNK_LEN];
+    encode_source_in_pieces(source, &mut scratch, |piece| {
+        if piece.is_empty() {
+            return Ok(());
+        }
+        transport
+            .send_chunk(piece)
+            .map_err(|source| TransportError::Transport { source })
+    })
+}
+
+/// Header and pixels of an image received by a [`FrameReceiver`].
+#[cfg(feature = "alloc")]
+pub type DecodedFrame<C> = (HeaderInfo, Vec<<C as ColorFormat>::OutputElement>);
+
+/// Receives the images sent with [`send_frame`], or any other Q565 images sent back to back.
+///
+/// Bytes received past the end of an image are kept for the next one.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct FrameReceiver<T> {
+    transport: T,
+    buffer: Vec<u8>,
+    /// Length of the image at the start of `buffer` that was returned last.
+    consumed: usize,
+    max_pixels: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> FrameReceiver<T>
+where
+    T: FrameTransport,
+{
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            buffer: Vec::new(),
+            consumed: 0,
+            max_pixels: usize::MAX,
+        }
+    }
+
+    /// Rejects images with more than `max_pixels` pixels with [`TransportError::TooLarge`] as soon
+    /// as their header is received, instead of buffering them.
+    pub fn with_max_pixels(mut self, max_pixels: usize) -> Self {
+        self.max_pixels = max_pixels;
+        self
+    }
+
+    /// Receives the next image and decodes it.
+    pub fn recv_frame<B, C>(&mut self) -> Result<DecodedFrame<C>, TransportError<T::Error>>
+    where
+        B: Endianness,
+        C: ColorFormat,
+    {
+        let data = self.recv_encoded()?;
+        Ok(Q565DecodeContext::decode_to_vec::<B, C>(data)?)
+    }
+
+    /// Receives the next image, without decoding it.
+    ///
+    /// Only the header and the op boundaries are checked, the ops are validated when decoding.
+    pub fn recv_encoded(&mut self) -> Result<&[u8], TransportError<T::Error>> {
+        self.buffer.drain(..self.consumed);
+        self.consumed = 0;
+
+        let (header, header_len) = loop {
+            match Q565DecodeContext::decode_header(&self.buffer) {
+                Ok((header, ops)) => break (header, self.buffer.len() - ops.len()),
+                Err(DecodeError::UnexpectedEof) => self.fill()?,
+                Err(source) => return Err(TransportError::Decode { source }),
+            }
+        };
+
+        let pixel_count = header
+            .pixel_count()
+            .filter(|&pixel_count| pixel_count <= self.max_pixels)
+            .ok_or(TransportError::TooLarge)?;
+
+        let len = if header.raw {
+            pixel_count
+                .checked_mul(2)
+                .and_then(|len| len.checked_add(header_len))
+                .ok_or(TransportError::TooLarge)?
+        } else {
+            let mut offset = header_len;
+            let mut pixels = 0;
+            loop {
+                match Op::parse(&self.buffer[offset..]) {
+                    Some(Op::End) => break offset + 1,
+                    Some(op) => {
+                        offset += op.encoded_len();
+                        pixels += op.pixel_count();
+                        ensure!(pixels <= pixel_count, DecodeError::TooManyPixels);
+                    }
+                    None => self.fill()?,
+                }
+            }
+        };
+        while self.buffer.len() < len {
+            self.fill()?;
+        }
+
+        self.consumed = len;
+        Ok(&self.buffer[..len])
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Returns the transport. Bytes received past the image returned last are lost.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Appends the next bytes from the transport to the buffer.
+    fn fill(&mut self) -> Result<(), TransportError<T::Error>> {
+        let start = self.buffer.len();
+        self.buffer.resize(start + CHUNK_LEN, 0);
+        let received = self.transport.recv_chunk(&mut self.buffer[start..]);
+        let received = match received {
+            Ok(received) => received,
+            Err(source) => {
+                self.buffer.truncate(start);
+                return Err(TransportError::Transport { source });
+            }
+        };
+        self.buffer.truncate(start + received);
+
+        ensure!(received > 0, TransportError::Closed);
+        Ok(())
+    }
+}
+
+/// In-memory transport that receives what was sent to it, e.g. for tests.
+///
+/// Receiving from an empty loopback returns `0`, like a closed transport.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default)]
+pub struct Loopback {
+    buffer: VecDeque<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl Loopback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of bytes sent, but not received yet.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl FrameTransport for Loopback {
+    type Error = core::convert::Infallible;
+
+    fn send_chunk(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        self.buffer.extend(chunk);
+        Ok(())
+    }
+
+    fn recv_chunk(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let len = buf.len().min(self.buffer.len());
+        for (dst, src) in buf.iter_mut().zip(self.buffer.drain(..len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "std")]
+impl FrameTransport for std::net::TcpStream {
+    type Error = std::io::Error;
+
+    fn send_chunk(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        std::io::Write::write_all(self, chunk)
+    }
+
+    fn recv_chunk(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        std::io::Read::read(self, buf)
+    }
+}
+
+#[cfg(feature = "serialport")]
+impl FrameTransport for Box<dyn serialport::SerialPort> {
+    type Error = std::io::Error;
+
+    fn send_chunk(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        std::io::Write::write_all(self, chunk)
+    }
+
+    /// Reads from the port. Fails with [`TimedOut`](std::io::ErrorKind::TimedOut) if nothing is
+    /// received within the port's timeout.
+    fn recv_chunk(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        std::io::Read::read(self, buf)
+    }
+}
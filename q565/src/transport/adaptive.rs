@@ -0,0 +1,218 @@
+use super::{FrameTransport, TransportError, CHUNK_LEN};
+use crate::encode::{encode_source_in_pieces, PixelSource};
+use alloc::{collections::VecDeque, vec::Vec};
+use core::convert::Infallible;
+
+/// What an [`AdaptiveSender`] does with a new frame while its backlog is over the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BacklogPolicy {
+    /// Drops the new frame, so that the frames queued before it are sent as they are.
+    #[default]
+    Drop,
+    /// Queues the new frame in place of the queued frames that haven't started sending yet, so
+    /// that the receiver gets the latest frame next.
+    Coalesce,
+}
+
+/// What an [`AdaptiveSender`] did with a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameOutcome {
+    /// The frame was queued.
+    Queued,
+    /// The backlog is over the limit, so the frame was dropped.
+    Dropped,
+    /// The backlog is over the limit, so the frame was queued in place of `replaced` waiting
+    /// frames.
+    Coalesced { replaced: usize },
+}
+
+/// Frame counts of an [`AdaptiveSender`], e.g. to show the effective frame rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SenderStats {
+    /// Frames that were queued, including coalesced ones.
+    pub queued: usize,
+    /// Frames that were sent completely.
+    pub sent: usize,
+    /// Frames that were dropped, or replaced by coalescing.
+    pub dropped: usize,
+}
+
+/// Queues encoded frames for a transport that may be slower than they are produced, and drops or
+/// coalesces frames once more than `max_backlog` bytes are waiting, instead of building up
+/// latency without bound.
+///
+/// The application sends the queued bytes with [`poll`](Self::poll), e.g. as much as the link
+/// takes per tick, and checks [`is_ready`](Self::is_ready) before encoding a frame, to skip frames
+/// that would be dropped anyway.
+///
+/// Frames need to be complete images that don't depend on the frames before them, like those of
+/// [`push_frame`](Self::push_frame). Dropping a frame of a [frame
+/// sequence](crate#frame-sequences) would leave the receiver out of sync.
+///
+/// ```
+/// # use q565::{byteorder::LittleEndian, encode::StridedPixels, Rgb565};
+/// use q565::transport::{AdaptiveSender, FrameOutcome, FrameReceiver, Loopback};
+///
+/// let frames = [[0xF800; 4], [0x07E0; 4], [0x001F; 4]];
+/// let mut sender = AdaptiveSender::new(Loopback::new(), 0);
+/// for pixels in &frames {
+///     sender.push_frame(&StridedPixels::new(pixels, 2, 2, 2).unwrap());
+/// }
+/// // only the first frame fit into the backlog
+/// assert_eq!(sender.stats().dropped, 2);
+/// sender.flush()?;
+///
+/// let mut receiver = FrameReceiver::new(sender.into_inner());
+/// let (_, decoded) = receiver.recv_frame::<LittleEndian, Rgb565>()?;
+/// assert_eq!(decoded, frames[0]);
+/// # Ok::<(), q565::transport::TransportError<core::convert::Infallible>>(())
+/// ```
+#[derive(Debug)]
+pub struct AdaptiveSender<T> {
+    transport: T,
+    queue: VecDeque<Vec<u8>>,
+    /// Bytes of the frame at the front of the queue that were sent already.
+    front_sent: usize,
+    backlog: usize,
+    max_backlog: usize,
+    policy: BacklogPolicy,
+    stats: SenderStats,
+}
+
+impl<T> AdaptiveSender<T>
+where
+    T: FrameTransport,
+{
+    /// Creates a sender that accepts new frames while at most `max_backlog` bytes are waiting.
+    pub fn new(transport: T, max_backlog: usize) -> Self {
+        Self {
+            transport,
+            queue: VecDeque::new(),
+            front_sent: 0,
+            backlog: 0,
+            max_backlog,
+            policy: BacklogPolicy::Drop,
+            stats: SenderStats::default(),
+        }
+    }
+
+    /// Sets what happens to new frames while the backlog is over the limit, [`BacklogPolicy::Drop`]
+    /// by default.
+    pub fn with_policy(mut self, policy: BacklogPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Number of bytes waiting to be sent.
+    #[inline]
+    pub fn backlog(&self) -> usize {
+        self.backlog
+    }
+
+    /// Number of frames waiting to be sent, including the one being sent.
+    #[inline]
+    pub fn queued_frames(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns whether the next frame would be queued as is, i.e. the backlog is within the limit.
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.backlog <= self.max_backlog
+    }
+
+    #[inline]
+    pub fn stats(&self) -> SenderStats {
+        self.stats
+    }
+
+    /// Encodes `source` and queues it, see [`push_encoded`](Self::push_encoded).
+    pub fn push_frame(&mut self, source: &dyn PixelSource) -> FrameOutcome {
+        let mut frame = Vec::new();
+        let mut scratch = [0; CHUNK_LEN];
+        let _ = encode_source_in_pieces(source, &mut scratch, |piece| {
+            frame.extend_from_slice(piece);
+            Ok::<(), Infallible>(())
+        });
+        self.push_encoded(frame)
+    }
+
+    /// Queues an encoded frame, or drops or coalesces it according to the policy if the backlog is
+    /// over the limit.
+    pub fn push_encoded(&mut self, frame: Vec<u8>) -> FrameOutcome {
+        let outcome = if self.is_ready() {
+            FrameOutcome::Queued
+        } else {
+            match self.policy {
+                BacklogPolicy::Drop => {
+                    self.stats.dropped += 1;
+                    return FrameOutcome::Dropped;
+                }
+                BacklogPolicy::Coalesce => {
+                    // the frame being sent can't be taken back
+                    let keep = usize::from(self.front_sent > 0);
+                    let replaced = self.queue.len() - keep;
+                    for frame in self.queue.drain(keep..) {
+                        self.backlog -= frame.len();
+                    }
+                    self.stats.dropped += replaced;
+                    FrameOutcome::Coalesced { replaced }
+                }
+            }
+        };
+
+        self.backlog += frame.len();
+        self.queue.push_back(frame);
+        self.stats.queued += 1;
+        outcome
+    }
+
+    /// Sends up to `max_bytes` of the queued frames, in pieces of at most [`CHUNK_LEN`] bytes.
+    /// Returns the number of bytes sent.
+    pub fn poll(&mut self, max_bytes: usize) -> Result<usize, TransportError<T::Error>> {
+        let mut sent = 0;
+        while sent < max_bytes {
+            let Some(frame) = self.queue.front() else {
+                break;
+            };
+
+            let len = (frame.len() - self.front_sent)
+                .min(CHUNK_LEN)
+                .min(max_bytes - sent);
+            self.transport
+                .send_chunk(&frame[self.front_sent..self.front_sent + len])
+                .map_err(|source| TransportError::Transport { source })?;
+            self.front_sent += len;
+            self.backlog -= len;
+            sent += len;
+
+            if self.front_sent == frame.len() {
+                self.queue.pop_front();
+                self.front_sent = 0;
+                self.stats.sent += 1;
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Sends all queued frames, returning the number of bytes sent.
+    pub fn flush(&mut self) -> Result<usize, TransportError<T::Error>> {
+        self.poll(usize::MAX)
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Returns the transport, dropping the frames that weren't sent.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
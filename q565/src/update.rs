@@ -0,0 +1,177 @@
+//! Incremental screen updates: only the changed rectangles of a framebuffer are encoded and sent,
+//! e.g. for remote-framebuffer protocols.
+//!
+//! # Layout
+//!
+//! All integers are little-endian.
+//!
+//! - 4-byte magic: `q5up`
+//! - u16le rectangle count
+//! - per rectangle:
+//!   - u16le x, u16le y: position of the rectangle in the framebuffer
+//!   - u32le payload length
+//!   - payload: a complete Q565 image (including its header) holding the rectangle's pixels
+
+use crate::byteorder::Endianness;
+use crate::{
+    decode::{DecodeError, Q565DecodeContext, RectDecodeOutput},
+    saturating_usize, ColorFormat, Rect,
+};
+
+#[cfg(feature = "alloc")]
+use crate::encode::Q565EncodeContext;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+pub const UPDATE_MAGIC: &[u8; 4] = b"q5up";
+
+error_enum! {
+    pub enum UpdateError {
+        /// The message does not start with the magic bytes `q5up`.
+        InvalidMagic = 1,
+        /// The message ended before all rectangles were read.
+        UnexpectedEof = 2,
+        /// A rectangle doesn't fit within the framebuffer.
+        InvalidRect = 3,
+        /// The framebuffers don't hold `width * height` pixels.
+        FramebufferSize = 4,
+        /// More than 65535 rectangles were given.
+        TooManyRects = 5,
+        /// The payload of a rectangle failed to decode.
+        Decode { source: DecodeError } = 6,
+    }
+}
+
+/// Encodes the `dirty` rectangles of `current` into an update message, appended to `w`.
+///
+/// Rectangles whose contents are the same in `previous` and `current` are skipped. Returns the
+/// number of rectangles written.
+#[cfg(feature = "alloc")]
+pub fn encode_update(
+    previous: &[u16],
+    current: &[u16],
+    width: u16,
+    height: u16,
+    dirty: &[Rect],
+    w: &mut Vec<u8>,
+) -> Result<usize, UpdateError> {
+    let pixel_count = crate::pixel_count(width, height);
+    ensure!(
+        pixel_count == Some(previous.len()) && pixel_count == Some(current.len()),
+        UpdateError::FramebufferSize
+    );
+    ensure!(
+        dirty.len() <= usize::from(u16::MAX),
+        UpdateError::TooManyRects
+    );
+
+    w.extend_from_slice(UPDATE_MAGIC);
+    let count_offset = w.len();
+    w.extend_from_slice(&[0, 0]);
+
+    let stride = usize::from(width);
+    let mut count = 0u16;
+    for rect in dirty {
+        ensure!(rect.fits_within(width, height), UpdateError::InvalidRect);
+
+        if rect.area() == 0
+            || rect_rows(previous, stride, *rect).eq(rect_rows(current, stride, *rect))
+        {
+            continue;
+        }
+
+        w.extend_from_slice(&rect.x.to_le_bytes());
+        w.extend_from_slice(&rect.y.to_le_bytes());
+        let length_offset = w.len();
+        w.extend_from_slice(&[0; 4]);
+
+        Q565EncodeContext::encode_iter_to_vec(
+            rect.width,
+            rect.height,
+            rect_rows(current, stride, *rect).flatten(),
+            w,
+        );
+        let length = (w.len() - length_offset - 4) as u32;
+        w[length_offset..length_offset + 4].copy_from_slice(&length.to_le_bytes());
+
+        count += 1;
+    }
+
+    w[count_offset..count_offset + 2].copy_from_slice(&count.to_le_bytes());
+    Ok(usize::from(count))
+}
+
+#[cfg(feature = "alloc")]
+fn rect_rows(framebuffer: &[u16], stride: usize, rect: Rect) -> impl Iterator<Item = &[u16]> {
+    let (x, y) = (usize::from(rect.x), usize::from(rect.y));
+    (y..y + usize::from(rect.height)).map(move |y| {
+        let start = y * stride + x;
+        &framebuffer[start..start + usize::from(rect.width)]
+    })
+}
+
+/// Applies an update message to a framebuffer of `width * height` pixels, decoding every
+/// rectangle in place.
+///
+/// Returns the number of rectangles that were applied.
+pub fn apply_update<B, C>(
+    data: &[u8],
+    framebuffer: &mut [C::OutputElement],
+    width: u16,
+    height: u16,
+) -> Result<usize, UpdateError>
+where
+    B: Endianness,
+    C: ColorFormat,
+{
+    ensure!(
+        crate::pixel_count(width, height) == Some(framebuffer.len()),
+        UpdateError::FramebufferSize
+    );
+
+    for_each_rect(data, |x, y, payload| {
+        let (header, _) = Q565DecodeContext::decode_header(payload)
+            .map_err(|source| UpdateError::Decode { source })?;
+        let rect = Rect {
+            x,
+            y,
+            width: header.width,
+            height: header.height,
+        };
+        ensure!(rect.fits_within(width, height), UpdateError::InvalidRect);
+
+        Q565DecodeContext::decode::<B>(
+            payload,
+            RectDecodeOutput::<C>::new(framebuffer, usize::from(width), rect),
+        )
+        .map_err(|source| UpdateError::Decode { source })?;
+        Ok(())
+    })
+}
+
+/// Calls `f` with the position and payload of every rectangle in an update message.
+///
+/// Returns the number of rectangles.
+pub(crate) fn for_each_rect(
+    data: &[u8],
+    mut f: impl FnMut(u16, u16, &[u8]) -> Result<(), UpdateError>,
+) -> Result<usize, UpdateError> {
+    ensure!(data.len() >= 6, UpdateError::UnexpectedEof);
+    ensure!(&data[..4] == UPDATE_MAGIC, UpdateError::InvalidMagic);
+
+    let count = usize::from(u16::from_le_bytes([data[4], data[5]]));
+    let mut data = &data[6..];
+    for _ in 0..count {
+        ensure!(data.len() >= 8, UpdateError::UnexpectedEof);
+        let x = u16::from_le_bytes([data[0], data[1]]);
+        let y = u16::from_le_bytes([data[2], data[3]]);
+        let length = saturating_usize(u32::from_le_bytes([data[4], data[5], data[6], data[7]]));
+        ensure!(data.len() - 8 >= length, UpdateError::UnexpectedEof);
+        let (payload, rest) = data[8..].split_at(length);
+        data = rest;
+
+        f(x, y, payload)?;
+    }
+
+    Ok(count)
+}
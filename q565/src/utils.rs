@@ -1,3 +1,5 @@
+use core::str::FromStr;
+
 #[inline]
 pub(crate) const fn hash(pixel: u16) -> u8 {
     // Sicne the bytes are just added together, native endianness is fine here.
@@ -6,7 +8,6 @@ pub(crate) const fn hash(pixel: u16) -> u8 {
 }
 
 /// Computes the signed difference between two numbers. (N-bit numbers)
-#[cfg(feature = "alloc")]
 pub const fn diff_n<const N: u8>(a: u8, b: u8) -> i8 {
     (a.wrapping_sub(b) as i8) << (8 - N) >> (8 - N)
 }
@@ -35,6 +36,9 @@ pub const fn encode_rgb565_unchecked([r, g, b]: [u8; 3]) -> u16 {
 }
 
 /// Converts an RGB888 pixel into an RGB565 pixel.
+///
+/// Every channel is rounded to the nearest level, i.e. `round(r * 31 / 255)` for red and blue and
+/// `round(g * 63 / 255)` for green.
 #[inline]
 pub const fn rgb888_to_rgb565([r, g, b]: [u8; 3]) -> [u8; 3] {
     // https://stackoverflow.com/questions/2442576/how-does-one-convert-16-bit-rgb565-to-24-bit-rgb888
@@ -45,7 +49,113 @@ pub const fn rgb888_to_rgb565([r, g, b]: [u8; 3]) -> [u8; 3] {
     [r as u8, g as u8, b as u8]
 }
 
+/// How [`rgb888_to_rgb565_with`] quantizes RGB888 pixels to RGB565.
+///
+/// Display vendors differ in what they recommend, so images converted on the device and ones
+/// converted ahead of time may only match with the same mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Quantization {
+    /// Drops the low bits of every channel, like a plain shift.
+    Truncate,
+    /// Rounds every channel to the nearest level, see [`rgb888_to_rgb565`].
+    #[default]
+    Round,
+    /// Rounds every channel up or down so that the BT.601 luma of the result is as close as
+    /// possible to that of the original pixel, e.g. to keep the brightness of gradients. Ties are
+    /// broken by the error of the channels, and then in favor of [`Round`](Self::Round).
+    Luma,
+    /// Picks the level of every channel that is nearest in linear light, see
+    /// [`srgb::rgb888_to_rgb565`](crate::srgb::rgb888_to_rgb565).
+    #[cfg(feature = "srgb")]
+    Linear,
+}
+
+impl FromStr for Quantization {
+    type Err = &'static str;
+
+    /// Parses `truncate`, `round`, `luma`, or with the `srgb` feature, `linear`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        #[cfg(feature = "srgb")]
+        if s.eq_ignore_ascii_case("linear") {
+            return Ok(Quantization::Linear);
+        }
+
+        if s.eq_ignore_ascii_case("truncate") {
+            Ok(Quantization::Truncate)
+        } else if s.eq_ignore_ascii_case("round") {
+            Ok(Quantization::Round)
+        } else if s.eq_ignore_ascii_case("luma") {
+            Ok(Quantization::Luma)
+        } else {
+            Err("expected truncate, round, or luma")
+        }
+    }
+}
+
+/// Converts an RGB888 pixel into an RGB565 pixel, quantizing the channels as given.
+pub const fn rgb888_to_rgb565_with(pixel: [u8; 3], quantization: Quantization) -> [u8; 3] {
+    match quantization {
+        Quantization::Truncate => [pixel[0] >> 3, pixel[1] >> 2, pixel[2] >> 3],
+        Quantization::Round => rgb888_to_rgb565(pixel),
+        Quantization::Luma => rgb888_to_rgb565_luma(pixel),
+        #[cfg(feature = "srgb")]
+        Quantization::Linear => crate::srgb::rgb888_to_rgb565(pixel),
+    }
+}
+
+const fn rgb888_to_rgb565_luma(pixel: [u8; 3]) -> [u8; 3] {
+    // BT.601 luma weights, in thousandths
+    const WEIGHTS: [i32; 3] = [299, 587, 114];
+    const MAX: [u8; 3] = [31, 63, 31];
+
+    let rounded = rgb888_to_rgb565(pixel);
+    let mut best = rounded;
+    let mut best_error = luma_error(pixel, rounded, WEIGHTS);
+
+    // every combination of rounding the channels down or up
+    let mut combination = 0;
+    while combination < 8 {
+        let mut candidate = [0; 3];
+        let mut i = 0;
+        while i < 3 {
+            let down = (pixel[i] as u32 * MAX[i] as u32 / 255) as u8;
+            let up = combination >> i & 1 == 1 && down < MAX[i];
+            candidate[i] = down + up as u8;
+            i += 1;
+        }
+
+        let error = luma_error(pixel, candidate, WEIGHTS);
+        if error[0] < best_error[0] || (error[0] == best_error[0] && error[1] < best_error[1]) {
+            best = candidate;
+            best_error = error;
+        }
+        combination += 1;
+    }
+    best
+}
+
+/// The absolute luma error and the squared channel error of `quantized` in RGB888.
+const fn luma_error(pixel: [u8; 3], quantized: [u8; 3], weights: [i32; 3]) -> [i32; 2] {
+    let expanded = rgb565_to_rgb888(quantized);
+    let mut luma = 0;
+    let mut squared = 0;
+    let mut i = 0;
+    while i < 3 {
+        let error = expanded[i] as i32 - pixel[i] as i32;
+        luma += weights[i] * error;
+        squared += error * error;
+        i += 1;
+    }
+    [luma.abs(), squared]
+}
+
 /// Converts an RGB565 pixel into an RGB888 pixel.
+///
+/// Every channel is scaled to the full range and rounded to the nearest value, i.e.
+/// `round(r * 255 / 31)` for red and blue and `round(g * 255 / 63)` for green.
+///
+/// Converting the result back with [`rgb888_to_rgb565`] gives the original pixel, for all 65536
+/// pixels, so RGB565 images can go through an RGB888 pipeline without changing.
 #[inline]
 pub const fn rgb565_to_rgb888([r, g, b]: [u8; 3]) -> [u8; 3] {
     // https://stackoverflow.com/questions/2442576/how-does-one-convert-16-bit-rgb565-to-24-bit-rgb888
@@ -56,3 +166,24 @@ pub const fn rgb565_to_rgb888([r, g, b]: [u8; 3]) -> [u8; 3] {
 
     [r as u8, g as u8, b as u8]
 }
+
+/// Turns a panic unwinding out of a `panic_free!` body into a link error, see the `panic-free`
+/// feature.
+///
+/// The guard is forgotten at the end of the body, so its destructor is only referenced from unwind
+/// paths. If the optimizer can prove that there are none, the undefined symbol disappears.
+#[cfg(feature = "panic-free")]
+pub(crate) struct PanicGuard;
+
+#[cfg(feature = "panic-free")]
+impl Drop for PanicGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        extern "C" {
+            #[link_name = "\n\nERROR[q565]: a function covered by the `panic-free` feature may panic\n\n"]
+            fn panic_path_found() -> !;
+        }
+
+        unsafe { panic_path_found() }
+    }
+}
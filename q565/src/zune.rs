@@ -0,0 +1,140 @@
+//! Decoding Q565 images in the style of the [`zune`](https://github.com/etemesi254/zune-image)
+//! codecs, with the `zune` feature.
+//!
+//! [`Q565ZuneDecoder`] has the same shape as the decoders of `zune-jpeg` or `zune-png`: it takes
+//! [`DecoderOptions`] (only the size limits apply), reads the header with
+//! [`decode_headers`](Q565ZuneDecoder::decode_headers), and decodes into interleaved 8-bit RGB
+//! with [`decode`](Q565ZuneDecoder::decode) or [`decode_into`](Q565ZuneDecoder::decode_into).
+//! Wrapping it in zune-image's `DecoderTrait` is a matter of forwarding each method.
+//!
+//! ```
+//! use q565::zune::Q565ZuneDecoder;
+//! use zune_core::options::DecoderOptions;
+//!
+//! # let mut data = Vec::new();
+//! # q565::encode::Q565EncodeContext::encode_to_vec(2, 2, &[0xF800; 4], &mut data).unwrap();
+//! let options = DecoderOptions::default().set_max_width(1024).set_max_height(1024);
+//! let mut decoder = Q565ZuneDecoder::new_with_options(&data, options);
+//! decoder.decode_headers()?;
+//! let (width, height) = decoder.dimensions().unwrap();
+//! let rgb = decoder.decode()?;
+//! assert_eq!(rgb.len(), width * height * 3);
+//! # Ok::<(), q565::zune::ZuneDecodeError>(())
+//! ```
+
+use crate::{
+    byteorder::BigEndian,
+    decode::{ChunkedDecodeOutput, DecodeError, Q565DecodeContext},
+    HeaderInfo, Rgb888,
+};
+use alloc::{vec, vec::Vec};
+use zune_core::{bit_depth::BitDepth, colorspace::ColorSpace, options::DecoderOptions};
+
+error_enum! {
+    pub enum ZuneDecodeError {
+        /// The image is invalid.
+        Decode { source: DecodeError } = 1,
+        /// The image is larger than the maximum size set in the [`DecoderOptions`].
+        TooLarge = 2,
+        /// The output is shorter than [`Q565ZuneDecoder::output_buffer_size`].
+        OutputTooSmall = 3,
+    }
+}
+
+impl From<DecodeError> for ZuneDecodeError {
+    fn from(source: DecodeError) -> Self {
+        Self::Decode { source }
+    }
+}
+
+/// Q565 decoder following the API of the zune codecs, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Q565ZuneDecoder<'a> {
+    data: &'a [u8],
+    options: DecoderOptions,
+    header: Option<HeaderInfo>,
+}
+
+impl<'a> Q565ZuneDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::new_with_options(data, DecoderOptions::default())
+    }
+
+    pub fn new_with_options(data: &'a [u8], options: DecoderOptions) -> Self {
+        Self {
+            data,
+            options,
+            header: None,
+        }
+    }
+
+    /// Reads the header and checks the image size against the limits of the options.
+    pub fn decode_headers(&mut self) -> Result<(), ZuneDecodeError> {
+        if self.header.is_some() {
+            return Ok(());
+        }
+
+        let (header, _) = Q565DecodeContext::decode_header(self.data)?;
+        ensure!(
+            usize::from(header.width) <= self.options.get_max_width()
+                && usize::from(header.height) <= self.options.get_max_height(),
+            ZuneDecodeError::TooLarge
+        );
+        self.header = Some(header);
+        Ok(())
+    }
+
+    /// The width and height, once the header was read.
+    pub fn dimensions(&self) -> Option<(usize, usize)> {
+        self.header
+            .as_ref()
+            .map(|header| (usize::from(header.width), usize::from(header.height)))
+    }
+
+    /// Always [`ColorSpace::RGB`], once the header was read.
+    pub fn get_output_colorspace(&self) -> Option<ColorSpace> {
+        self.header.as_ref().map(|_| ColorSpace::RGB)
+    }
+
+    /// Always [`BitDepth::Eight`], once the header was read.
+    pub fn get_output_depth(&self) -> Option<BitDepth> {
+        self.header.as_ref().map(|_| BitDepth::Eight)
+    }
+
+    /// Number of bytes [`decode_into`](Self::decode_into) needs, once the header was read.
+    pub fn output_buffer_size(&self) -> Option<usize> {
+        self.dimensions().map(|(width, height)| width * height * 3)
+    }
+
+    /// Decodes the image into `output` as interleaved RGB, reading the header first if needed.
+    pub fn decode_into(&mut self, output: &mut [u8]) -> Result<(), ZuneDecodeError> {
+        self.decode_headers()?;
+        let len = self.output_buffer_size().unwrap_or(0);
+        let output = output
+            .get_mut(..len)
+            .ok_or(ZuneDecodeError::OutputTooSmall)?;
+
+        let (mut front, mut back) = ([[0; 3]; 64], [[0; 3]; 64]);
+        let mut position = 0;
+        let mut decode_output =
+            ChunkedDecodeOutput::<Rgb888, _>::new(&mut front, &mut back, |pixels: &[[u8; 3]]| {
+                let bytes = pixels.as_flattened();
+                // the decoder never produces more pixels than the header claims
+                if let Some(output) = output.get_mut(position..position + bytes.len()) {
+                    output.copy_from_slice(bytes);
+                }
+                position += bytes.len();
+            });
+        Q565DecodeContext::decode::<BigEndian>(self.data, &mut decode_output)?;
+        decode_output.flush();
+        Ok(())
+    }
+
+    /// Decodes the image into a new buffer, see [`decode_into`](Self::decode_into).
+    pub fn decode(&mut self) -> Result<Vec<u8>, ZuneDecodeError> {
+        self.decode_headers()?;
+        let mut output = vec![0; self.output_buffer_size().unwrap_or(0)];
+        self.decode_into(&mut output)?;
+        Ok(output)
+    }
+}
@@ -0,0 +1,133 @@
+use q565::{
+    byteorder::LittleEndian,
+    encode::{Q565EncodeContext, StridedPixels},
+    transport::{
+        AdaptiveSender, BacklogPolicy, FrameOutcome, FrameReceiver, Loopback, SenderStats,
+    },
+    Rgb565,
+};
+
+const WIDTH: u16 = 32;
+const HEIGHT: u16 = 16;
+
+/// A frame that doesn't compress well, so that it takes several chunks.
+fn frame(n: u16) -> Vec<u16> {
+    (0..WIDTH * HEIGHT)
+        .map(|i| i.wrapping_mul(0x9E37) ^ n.wrapping_mul(0x1234))
+        .collect()
+}
+
+fn encoded(n: u16) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(WIDTH, HEIGHT, &frame(n), &mut encoded).unwrap();
+    encoded
+}
+
+fn received(loopback: Loopback) -> Vec<Vec<u16>> {
+    let mut receiver = FrameReceiver::new(loopback);
+    let mut frames = Vec::new();
+    while !receiver.get_ref().is_empty() {
+        let (_, pixels) = receiver.recv_frame::<LittleEndian, Rgb565>().unwrap();
+        frames.push(pixels);
+    }
+    frames
+}
+
+#[test]
+fn sends_everything_while_keeping_up() {
+    let mut sender = AdaptiveSender::new(Loopback::new(), 0);
+    for n in 0..4 {
+        let pixels = frame(n);
+        let source = StridedPixels::new(&pixels, WIDTH, HEIGHT, usize::from(WIDTH)).unwrap();
+        assert!(sender.is_ready());
+        assert_eq!(sender.push_frame(&source), FrameOutcome::Queued);
+        assert_eq!(sender.backlog(), encoded(n).len());
+        sender.flush().unwrap();
+        assert_eq!(sender.backlog(), 0);
+    }
+
+    assert_eq!(
+        sender.stats(),
+        SenderStats {
+            queued: 4,
+            sent: 4,
+            dropped: 0,
+        }
+    );
+    assert_eq!(
+        received(sender.into_inner()),
+        (0..4).map(frame).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn drops_frames_over_the_backlog() {
+    let frame_len = encoded(0).len();
+    let mut sender = AdaptiveSender::new(Loopback::new(), frame_len);
+
+    assert_eq!(sender.push_encoded(encoded(0)), FrameOutcome::Queued);
+    // at the limit, not over it
+    assert_eq!(sender.push_encoded(encoded(1)), FrameOutcome::Queued);
+    assert!(!sender.is_ready());
+    assert_eq!(sender.push_encoded(encoded(2)), FrameOutcome::Dropped);
+
+    // sending a part of the backlog makes room again
+    let half = encoded(1).len() / 2;
+    let sent = sender.poll(frame_len + half).unwrap();
+    assert_eq!(sent, frame_len + half);
+    assert_eq!(sender.backlog(), encoded(1).len() - half);
+    assert!(sender.is_ready());
+    assert_eq!(sender.queued_frames(), 1);
+    assert_eq!(sender.push_encoded(encoded(3)), FrameOutcome::Queued);
+    sender.flush().unwrap();
+
+    assert_eq!(sender.stats().dropped, 1);
+    assert_eq!(
+        received(sender.into_inner()),
+        [frame(0), frame(1), frame(3)]
+    );
+}
+
+#[test]
+fn coalesces_waiting_frames() {
+    let mut sender = AdaptiveSender::new(Loopback::new(), 0).with_policy(BacklogPolicy::Coalesce);
+
+    assert_eq!(sender.push_encoded(encoded(0)), FrameOutcome::Queued);
+    sender.poll(100).unwrap();
+    assert_eq!(
+        sender.push_encoded(encoded(1)),
+        FrameOutcome::Coalesced { replaced: 0 }
+    );
+    assert_eq!(
+        sender.push_encoded(encoded(2)),
+        FrameOutcome::Coalesced { replaced: 1 }
+    );
+    assert_eq!(
+        sender.push_encoded(encoded(3)),
+        FrameOutcome::Coalesced { replaced: 1 }
+    );
+    assert_eq!(sender.queued_frames(), 2);
+    assert_eq!(sender.backlog(), encoded(0).len() - 100 + encoded(3).len());
+
+    // the partly sent frame is completed, then the latest one follows
+    sender.flush().unwrap();
+    assert_eq!(
+        sender.stats(),
+        SenderStats {
+            queued: 4,
+            sent: 2,
+            dropped: 2,
+        }
+    );
+    assert_eq!(received(sender.into_inner()), [frame(0), frame(3)]);
+
+    // nothing is being sent, so all waiting frames are replaced
+    let mut sender = AdaptiveSender::new(Loopback::new(), 0).with_policy(BacklogPolicy::Coalesce);
+    sender.push_encoded(encoded(0));
+    assert_eq!(
+        sender.push_encoded(encoded(1)),
+        FrameOutcome::Coalesced { replaced: 1 }
+    );
+    sender.flush().unwrap();
+    assert_eq!(received(sender.into_inner()), [frame(1)]);
+}
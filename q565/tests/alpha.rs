@@ -0,0 +1,99 @@
+#[cfg(not(feature = "forbid-unsafe"))]
+mod common;
+
+#[cfg(not(feature = "forbid-unsafe"))]
+use common::{encode, test_pattern};
+#[cfg(not(feature = "forbid-unsafe"))]
+use q565::{
+    alpha::{alpha_mask_color, blend_565, blit_over, AlphaError, Q565aImage, OPAQUE},
+    byteorder::BigEndian,
+    encode::Q565EncodeContext,
+    ColorArraySize,
+};
+
+#[cfg(not(feature = "forbid-unsafe"))]
+const WIDTH: u16 = 9;
+#[cfg(not(feature = "forbid-unsafe"))]
+const HEIGHT: u16 = 7;
+
+#[cfg(not(feature = "forbid-unsafe"))]
+fn colors() -> Vec<u16> {
+    test_pattern(WIDTH, HEIGHT)
+}
+
+/// Opaque on the left, transparent on the right, and a gradient in between.
+#[cfg(not(feature = "forbid-unsafe"))]
+fn alphas() -> Vec<u8> {
+    let row = [OPAQUE, OPAQUE, OPAQUE, 50, 31, 7, 0, 0, 0];
+    (0..HEIGHT)
+        .flat_map(|y| row.map(|alpha| (alpha + (y % 2) as u8).min(OPAQUE)))
+        .collect()
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn blends_onto_framebuffer() {
+    let colors = colors();
+    let alphas = alphas();
+    let mask: Vec<u16> = alphas.iter().map(|&a| alpha_mask_color(a)).collect();
+    let (color, alpha) = (encode(WIDTH, HEIGHT, &colors), encode(WIDTH, HEIGHT, &mask));
+    let image = Q565aImage::new(&color, &alpha).unwrap();
+
+    // 12x10 framebuffer, image placed at (5, 4) so that it's clipped on the right and bottom
+    let (stride, rows) = (12usize, 10usize);
+    let background: Vec<u16> = (0..stride * rows)
+        .map(|i| (i as u16).wrapping_mul(0x0841))
+        .collect();
+    let mut framebuffer: Vec<u16> = background.iter().map(|p| p.to_be()).collect();
+
+    blit_over::<BigEndian>(&mut framebuffer, stride, &image, 5, 4).unwrap();
+
+    for y in 0..rows {
+        for x in 0..stride {
+            let i = y * stride + x;
+            let expected = if (5..).contains(&x) && (4..).contains(&y) {
+                let j = (y - 4) * usize::from(WIDTH) + (x - 5);
+                blend_565(colors[j], background[i], alphas[j])
+            } else {
+                background[i]
+            };
+            assert_eq!(u16::from_be(framebuffer[i]), expected, "at ({x}, {y})");
+        }
+    }
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn blends_per_channel() {
+    assert_eq!(blend_565(0xFFFF, 0x0000, OPAQUE), 0xFFFF);
+    assert_eq!(blend_565(0xFFFF, 0x0000, 0), 0x0000);
+    // about half
+    assert_eq!(blend_565(0xFFFF, 0x0000, 31), 0x7BEF);
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn checks_mask() {
+    let color = encode(WIDTH, HEIGHT, &colors());
+
+    let small = encode(WIDTH, 1, &[0xFFFF; WIDTH as usize]);
+    assert!(matches!(
+        Q565aImage::new(&color, &small),
+        Err(AlphaError::SizeMismatch)
+    ));
+
+    let mask = vec![alpha_mask_color(OPAQUE); usize::from(WIDTH * HEIGHT)];
+    let mut profiled = Vec::new();
+    Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::Entries16,
+        WIDTH,
+        HEIGHT,
+        &mask,
+        &mut profiled,
+    )
+    .unwrap();
+    assert!(matches!(
+        Q565aImage::new(&color, &profiled),
+        Err(AlphaError::UnsupportedMask)
+    ));
+}
@@ -0,0 +1,22 @@
+use q565::{analyze::analyze, encode::Q565EncodeContext};
+
+#[test]
+fn histogram_and_dominant_colors() {
+    let mut input = vec![0x1234; 100];
+    input.extend([0xFFFF; 30]);
+    input.extend([0x0001; 30]);
+    input.extend((0..40).map(|i| 0x8000 + i));
+
+    let mut encoded = Vec::new();
+    assert!(Q565EncodeContext::encode_to_vec(20, 10, &input, &mut encoded).is_some());
+
+    let stats = analyze(&encoded).unwrap();
+    assert_eq!(stats.unique_colors, 43);
+    assert_eq!(stats.color_histogram[0x1234], 100);
+    assert_eq!(stats.color_histogram[0x8005], 1);
+    assert_eq!(
+        stats.dominant_colors[..3],
+        [(0x1234, 100), (0x0001, 30), (0xFFFF, 30)]
+    );
+    assert_eq!(stats.dominant_colors.len(), 8);
+}
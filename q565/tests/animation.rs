@@ -0,0 +1,194 @@
+use q565::{
+    animation::{Animation, AnimationError, AnimationWriter, Player, ANIMATION_MAGIC},
+    byteorder::LittleEndian,
+};
+use std::cell::Cell;
+
+const WIDTH: u16 = 8;
+const HEIGHT: u16 = 4;
+
+/// A bar growing from the left, one column per frame.
+fn frame(step: usize) -> Vec<u16> {
+    (0..usize::from(WIDTH) * usize::from(HEIGHT))
+        .map(|i| {
+            if i % usize::from(WIDTH) <= step {
+                0x07E0
+            } else {
+                0x0000
+            }
+        })
+        .collect()
+}
+
+fn boot_animation() -> Vec<u8> {
+    let mut writer = AnimationWriter::new(WIDTH, HEIGHT);
+    for step in 0..6 {
+        writer.push_frame(&frame(step), 40 + step as u16).unwrap();
+    }
+    writer.add_segment("intro", 0..2, 1).unwrap();
+    writer.add_segment("loop", 2..5, 0).unwrap();
+    writer.add_segment("outro", 5..6, 2).unwrap();
+    writer.write().unwrap()
+}
+
+#[test]
+fn segments_roundtrip() {
+    let data = boot_animation();
+    let animation = Animation::new(&data).unwrap();
+    assert_eq!((animation.width(), animation.height()), (WIDTH, HEIGHT));
+    assert_eq!(animation.frame_count(), 6);
+
+    let names: Vec<_> = animation.segments().map(|segment| segment.name).collect();
+    assert_eq!(names, ["intro", "loop", "outro"]);
+
+    let outro = animation.segment("outro").unwrap();
+    assert_eq!(outro.frames(), 5..6);
+    assert_eq!(outro.loops, 2);
+    assert!(animation.segment("loop").unwrap().loops_forever());
+    assert!(animation.segment("missing").is_none());
+    assert!(matches!(
+        animation.play("missing"),
+        Err(AnimationError::NoSuchSegment)
+    ));
+
+    // segment starts are keyframes, the frames in between are stored as deltas
+    let deltas: Vec<_> = (0..6)
+        .map(|index| animation.frame(index).unwrap().delta)
+        .collect();
+    assert_eq!(deltas, [false, true, false, true, true, false]);
+    assert_eq!(animation.frame(3).unwrap().duration_ms, 43);
+    assert!(animation.frame(6).is_none());
+}
+
+#[test]
+fn playback_decodes_and_loops() {
+    let data = boot_animation();
+    let animation = Animation::new(&data).unwrap();
+    let mut framebuffer = vec![0; animation.pixel_count()];
+
+    let mut shown = Vec::new();
+    for name in ["intro", "loop", "outro"] {
+        let mut playback = animation.play(name).unwrap();
+        while let Some(index) = playback.next_index() {
+            let frame = playback.next().unwrap();
+            frame.decode_into::<LittleEndian>(&mut framebuffer).unwrap();
+            assert_eq!(framebuffer, self::frame(index));
+            shown.push(index);
+
+            // the device finished booting during the second pass of the loop
+            if shown.len() == 7 {
+                playback.stop_looping();
+            }
+        }
+    }
+
+    assert_eq!(shown, [0, 1, 2, 3, 4, 2, 3, 4, 5, 5]);
+}
+
+#[test]
+fn player_paces_frames() {
+    let data = boot_animation();
+    let animation = Animation::new(&data).unwrap();
+    let mut framebuffer = vec![0; animation.pixel_count()];
+
+    // starts just before the clock wraps around
+    let time = Cell::new(u32::MAX - 50);
+    let mut player = Player::new(animation, || time.get());
+    assert!(player.is_finished());
+    assert_eq!(
+        player.update::<LittleEndian>(&mut framebuffer).unwrap(),
+        None
+    );
+
+    player.play("intro").unwrap();
+    player.queue("loop").unwrap();
+    assert_eq!(
+        player.update::<LittleEndian>(&mut framebuffer).unwrap(),
+        Some(0)
+    );
+    assert_eq!(framebuffer, frame(0));
+    assert_eq!(player.time_until_next(), Some(40));
+
+    time.set(time.get().wrapping_add(39));
+    assert_eq!(
+        player.update::<LittleEndian>(&mut framebuffer).unwrap(),
+        None
+    );
+    time.set(time.get().wrapping_add(1));
+    assert_eq!(
+        player.update::<LittleEndian>(&mut framebuffer).unwrap(),
+        Some(1)
+    );
+
+    // late by more than a frame: frame 2 is only decoded, and frame 3 is still due on schedule
+    time.set(time.get().wrapping_add(41 + 42));
+    assert_eq!(
+        player.update::<LittleEndian>(&mut framebuffer).unwrap(),
+        Some(3)
+    );
+    assert_eq!(framebuffer, frame(3));
+    assert_eq!(player.segment().unwrap().name, "loop");
+    assert_eq!(player.time_until_next(), Some(43));
+
+    player.queue("outro").unwrap();
+    player.stop_looping();
+    let mut shown = Vec::new();
+    while !player.is_finished() {
+        time.set(time.get().wrapping_add(1));
+        if let Some(index) = player.update::<LittleEndian>(&mut framebuffer).unwrap() {
+            assert_eq!(framebuffer, frame(index));
+            shown.push(index);
+        }
+    }
+    assert_eq!(shown, [4, 5, 5]);
+    assert_eq!(player.time_until_next(), None);
+}
+
+#[test]
+fn invalid_animations() {
+    let data = boot_animation();
+    assert!(matches!(
+        Animation::new(&data[..data.len() - 1]),
+        Err(AnimationError::UnexpectedEof)
+    ));
+    assert!(matches!(
+        Animation::new(b"q5bn"),
+        Err(AnimationError::InvalidMagic)
+    ));
+    assert!(matches!(
+        Animation::new(ANIMATION_MAGIC),
+        Err(AnimationError::UnexpectedEof)
+    ));
+
+    // "loop" reaching past the last frame
+    let mut broken = data.clone();
+    let intro_len = 1 + "intro".len() + 5;
+    let loop_frame_count = 11 + intro_len + 1 + "loop".len() + 2;
+    broken[loop_frame_count] = 5;
+    assert!(matches!(
+        Animation::new(&broken),
+        Err(AnimationError::InvalidSegment)
+    ));
+
+    let mut writer = AnimationWriter::new(WIDTH, HEIGHT);
+    assert!(matches!(
+        writer.push_frame(&[0; 3], 40),
+        Err(AnimationError::PixelCount)
+    ));
+    writer.push_frame(&frame(0), 40).unwrap();
+    writer.add_segment("all", 0..2, 1).unwrap();
+    assert!(matches!(
+        writer.write(),
+        Err(AnimationError::InvalidSegment)
+    ));
+
+    let animation = Animation::new(&data).unwrap();
+    let mut framebuffer = vec![0; 4];
+    assert!(matches!(
+        animation
+            .frame(0)
+            .unwrap()
+            .decode_into::<LittleEndian>(&mut framebuffer),
+        Err(AnimationError::FramebufferTooSmall)
+    ));
+}
@@ -0,0 +1,98 @@
+#[cfg(not(feature = "forbid-unsafe"))]
+mod common;
+
+#[cfg(not(feature = "forbid-unsafe"))]
+use common::{encode, test_pattern};
+#[cfg(not(feature = "forbid-unsafe"))]
+use q565::{
+    bundle::{write_bundle, AssetCache, AssetCacheError, Bundle},
+    byteorder::LittleEndian,
+};
+
+#[cfg(not(feature = "forbid-unsafe"))]
+fn icon(width: u16, height: u16, seed: u16) -> (Vec<u16>, Vec<u8>) {
+    let pixels: Vec<u16> = test_pattern(width, height)
+        .iter()
+        .map(|p| p ^ seed)
+        .collect();
+    let encoded = encode(width, height, &pixels);
+    (pixels, encoded)
+}
+
+#[cfg(not(feature = "forbid-unsafe"))]
+fn icons() -> (Vec<Vec<u16>>, Vec<u8>) {
+    let icons = [
+        icon(8, 8, 0x1234),
+        icon(16, 4, 0xF800),
+        icon(10, 10, 0x07E0),
+    ];
+    let entries: Vec<&[u8]> = icons.iter().map(|(_, e)| e.as_slice()).collect();
+    let bundle = write_bundle(&entries).unwrap();
+    (icons.into_iter().map(|(p, _)| p).collect(), bundle)
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn decodes_and_caches_entries() {
+    let (pixels, data) = icons();
+    let mut cache = AssetCache::<LittleEndian, 1024>::new(Bundle::new(&data).unwrap());
+
+    for (index, expected) in pixels.iter().enumerate() {
+        assert!(!cache.contains(index));
+        assert_eq!(cache.get(index).unwrap().pixels, expected.as_slice());
+        assert!(cache.contains(index));
+    }
+
+    let image = cache.get(2).unwrap();
+    assert_eq!((image.width, image.height), (10, 10));
+    assert_eq!(image.pixels, pixels[2].as_slice());
+
+    assert!(matches!(cache.get(3), Err(AssetCacheError::NoSuchEntry)));
+
+    cache.clear();
+    assert!(!cache.contains(0));
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn evicts_least_recently_used() {
+    let (pixels, data) = icons();
+    // room for 192 pixels: entries 0 and 2 (64 + 100) or 1 and 2 (64 + 100), but not all three
+    let mut cache = AssetCache::<LittleEndian, 384>::new(Bundle::new(&data).unwrap());
+
+    cache.get(0).unwrap();
+    cache.get(1).unwrap();
+    cache.get(0).unwrap();
+
+    assert_eq!(cache.get(2).unwrap().pixels, pixels[2].as_slice());
+    assert!(cache.contains(0) && !cache.contains(1) && cache.contains(2));
+
+    // entry 0 was moved to make room, and is still intact
+    assert_eq!(cache.get(0).unwrap().pixels, pixels[0].as_slice());
+
+    assert_eq!(cache.get(1).unwrap().pixels, pixels[1].as_slice());
+    assert!(cache.contains(0) && !cache.contains(2));
+    assert_eq!(cache.get(0).unwrap().pixels, pixels[0].as_slice());
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn limits_number_of_slots() {
+    let (pixels, data) = icons();
+    let mut cache = AssetCache::<LittleEndian, 1024, 2>::new(Bundle::new(&data).unwrap());
+
+    cache.get(0).unwrap();
+    cache.get(1).unwrap();
+    assert_eq!(cache.get(2).unwrap().pixels, pixels[2].as_slice());
+    assert!(!cache.contains(0) && cache.contains(1));
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn rejects_entries_larger_than_the_pool() {
+    let (_, data) = icons();
+    let mut cache = AssetCache::<LittleEndian, 128>::new(Bundle::new(&data).unwrap());
+
+    assert!(matches!(cache.get(2), Err(AssetCacheError::TooLarge)));
+    assert_eq!(cache.get(0).unwrap().pixels.len(), 64);
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::{encode, test_pattern};
+use q565::{
+    atlas::{AtlasError, Glyph, GlyphAtlas},
+    byteorder::LittleEndian,
+    Rect,
+};
+
+const WIDTH: u16 = 100;
+const HEIGHT: u16 = 20;
+
+fn atlas_pixels() -> Vec<u16> {
+    test_pattern(WIDTH, HEIGHT)
+}
+
+fn glyphs() -> [Glyph; 3] {
+    [
+        Glyph::new(
+            'a' as u32,
+            Rect {
+                x: 0,
+                y: 0,
+                width: 7,
+                height: 9,
+            },
+            8,
+        ),
+        Glyph::new(
+            'b' as u32,
+            Rect {
+                x: 13,
+                y: 4,
+                width: 5,
+                height: 16,
+            },
+            6,
+        ),
+        // wider than the row buffer
+        Glyph::new(
+            'w' as u32,
+            Rect {
+                x: 10,
+                y: 10,
+                width: 90,
+                height: 3,
+            },
+            90,
+        ),
+    ]
+}
+
+fn expected(pixels: &[u16], rect: Rect) -> Vec<u16> {
+    (rect.y..rect.y + rect.height)
+        .flat_map(|y| {
+            let start = usize::from(y) * usize::from(WIDTH) + usize::from(rect.x);
+            pixels[start..start + usize::from(rect.width)].to_vec()
+        })
+        .collect()
+}
+
+#[test]
+fn draws_glyphs_row_by_row() {
+    let pixels = atlas_pixels();
+    let image = encode(WIDTH, HEIGHT, &pixels);
+    let glyphs = glyphs();
+    let atlas = GlyphAtlas::new(&image, &glyphs).unwrap();
+    assert_eq!(atlas.size(), (WIDTH, HEIGHT));
+
+    for glyph in &glyphs {
+        let mut drawn = Vec::new();
+        let mut chunks = Vec::new();
+        let returned = atlas
+            .draw_glyph::<LittleEndian>(glyph.id, |chunk: &[u16]| {
+                drawn.extend_from_slice(chunk);
+                chunks.push(chunk.len());
+            })
+            .unwrap();
+
+        assert_eq!(returned, glyph);
+        assert_eq!(drawn, expected(&pixels, glyph.rect));
+        // chunks never span rows
+        let mut row = 0;
+        for len in chunks {
+            assert!(len <= 64);
+            row += len;
+            assert!(row <= usize::from(glyph.rect.width));
+            if row == usize::from(glyph.rect.width) {
+                row = 0;
+            }
+        }
+    }
+
+    assert!(matches!(
+        atlas.draw_glyph::<LittleEndian>('c' as u32, |_: &[u16]| {}),
+        Err(AtlasError::NoSuchGlyph)
+    ));
+}
+
+#[test]
+fn checks_glyph_table() {
+    let image = encode(WIDTH, HEIGHT, &atlas_pixels());
+
+    let mut glyphs = glyphs();
+    glyphs.swap(0, 1);
+    assert!(matches!(
+        GlyphAtlas::new(&image, &glyphs),
+        Err(AtlasError::UnsortedGlyphs)
+    ));
+
+    let mut glyphs = self::glyphs();
+    glyphs[2].rect.x = 11;
+    assert!(matches!(
+        GlyphAtlas::new(&image, &glyphs),
+        Err(AtlasError::GlyphOutOfBounds)
+    ));
+
+    assert!(matches!(
+        GlyphAtlas::new(&image[..4], &glyphs),
+        Err(AtlasError::Decode { .. })
+    ));
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::test_images;
+use q565::encode::{
+    Q565CompactStreamingEncodeContext, Q565EncodeContext, Q565StreamingEncodeContext,
+};
+#[cfg(not(feature = "forbid-unsafe"))]
+use q565::{byteorder::LittleEndian, decode::streaming_no_header::Q565StreamingDecodeContext};
+
+#[test]
+fn budgeted_streaming_encode() {
+    for (width, height, pixels) in test_images() {
+        let mut expected = Vec::new();
+        assert!(Q565EncodeContext::encode_to_vec(width, height, &pixels, &mut expected).is_some());
+
+        for (max_ops, chunk_len) in [(1, 3), (7, 5), (100, 64), (usize::MAX, 3)] {
+            let (header, header_len) = Q565EncodeContext::<64>::header(width, height).to_bytes();
+            let mut encoded = header[..header_len].to_vec();
+
+            let mut state = Q565StreamingEncodeContext::new();
+            let mut chunk = vec![0u8; chunk_len];
+            let mut remaining = &pixels[..];
+            while !remaining.is_empty() {
+                let progress = state.encode_to_slice(remaining, &mut chunk, max_ops);
+                assert!(progress.pixels_consumed > 0 || progress.bytes_written > 0);
+                encoded.extend_from_slice(&chunk[..progress.bytes_written]);
+                remaining = &remaining[progress.pixels_consumed..];
+            }
+            let len = state.finish(&mut chunk).unwrap();
+            encoded.extend_from_slice(&chunk[..len]);
+
+            assert_eq!(
+                expected, encoded,
+                "mismatch for max_ops = {max_ops}, chunk_len = {chunk_len}"
+            );
+        }
+    }
+}
+
+#[test]
+fn compact_streaming_encode() {
+    for (width, height, pixels) in test_images() {
+        let mut expected = Vec::new();
+        assert!(Q565EncodeContext::<16>::new_sized()
+            .encode_to_vec_with_state(width, height, &pixels, &mut expected)
+            .is_some());
+
+        let (header, header_len) = Q565EncodeContext::<16>::header(width, height).to_bytes();
+        let mut encoded = header[..header_len].to_vec();
+
+        let mut state = Q565CompactStreamingEncodeContext::<16>::new_compact_sized();
+        let mut chunk = vec![0u8; 64];
+        let mut remaining = &pixels[..];
+        while !remaining.is_empty() {
+            let progress = state.encode_to_slice(remaining, &mut chunk, usize::MAX);
+            encoded.extend_from_slice(&chunk[..progress.bytes_written]);
+            remaining = &remaining[progress.pixels_consumed..];
+        }
+        let len = state.finish(&mut chunk).unwrap();
+        encoded.extend_from_slice(&chunk[..len]);
+
+        assert_eq!(expected, encoded);
+    }
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn budgeted_streaming_decode() {
+    for (width, height, pixels) in test_images() {
+        let mut encoded = Vec::new();
+        assert!(Q565EncodeContext::encode_to_vec(width, height, &pixels, &mut encoded).is_some());
+        let data = &encoded[8..];
+
+        for (max_ops, chunk_len) in [(1, 1), (3, 2), (50, 7), (usize::MAX, 1)] {
+            let mut state = Q565StreamingDecodeContext::new();
+            let mut decoded = vec![0u16; pixels.len()];
+            let mut output_idx = 0;
+
+            for input in data.chunks(chunk_len) {
+                let mut input = input;
+                loop {
+                    let progress = unsafe {
+                        state.streaming_decode_to_slice_budgeted_unchecked::<LittleEndian>(
+                            input,
+                            &mut decoded[output_idx..],
+                            max_ops,
+                        )
+                    };
+                    assert!(progress.bytes_consumed > 0);
+                    output_idx += progress.pixels_written;
+                    input = &input[progress.bytes_consumed..];
+                    if input.is_empty() {
+                        break;
+                    }
+                }
+            }
+
+            assert_eq!(output_idx, pixels.len());
+            assert_eq!(
+                pixels, decoded,
+                "mismatch for max_ops = {max_ops}, chunk_len = {chunk_len}"
+            );
+        }
+    }
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn budgets_stop_at_op_boundaries() {
+    // all different from their predecessor, so every pixel is a single op
+    let pixels = [0x1234u16, 0x4321, 0xABCD, 0xDCBA];
+
+    let mut state = Q565StreamingEncodeContext::new();
+    let mut output = [0u8; 64];
+    let first = state.encode_to_slice(&pixels, &mut output, 2);
+    assert_eq!(first.pixels_consumed, 2);
+    let second = state.encode_to_slice(&pixels[2..], &mut output[first.bytes_written..], 2);
+    assert_eq!(second.pixels_consumed, 2);
+    let end = first.bytes_written + second.bytes_written;
+    let len = state.finish(&mut output[end..]).unwrap();
+    let encoded = &output[..end + len];
+
+    let mut state = Q565StreamingDecodeContext::new();
+    let mut decoded = [0u16; 4];
+    let progress = unsafe {
+        state.streaming_decode_to_slice_budgeted_unchecked::<LittleEndian>(encoded, &mut decoded, 2)
+    };
+    assert_eq!(progress.pixels_written, 2);
+    assert_eq!(progress.bytes_consumed, first.bytes_written);
+
+    let progress = unsafe {
+        state.streaming_decode_to_slice_budgeted_unchecked::<LittleEndian>(
+            &encoded[first.bytes_written..],
+            &mut decoded[2..],
+            usize::MAX,
+        )
+    };
+    assert_eq!(progress.pixels_written, 2);
+    assert_eq!(progress.bytes_consumed, encoded.len() - first.bytes_written);
+    assert_eq!(pixels, decoded);
+}
@@ -0,0 +1,212 @@
+use q565::{
+    bundle::{
+        apply_patch, content_hash, crc32, diff, sha256, write_bundle, Bundle, BundleError,
+        BundleWriter, Patch, PatchEntry, EXTENDED_BUNDLE_MAGIC,
+    },
+    encode::Q565EncodeContext,
+    ColorArraySize,
+};
+
+#[test]
+fn bundle_roundtrip() {
+    let entries: [&[u8]; 3] = [b"first", b"", b"third entry"];
+    let data = write_bundle(&entries).unwrap();
+
+    let bundle = Bundle::new(&data).unwrap();
+    assert_eq!(bundle.len(), 3);
+    assert_eq!(bundle.iter().collect::<Vec<_>>(), entries);
+    assert_eq!(bundle.get(3), None);
+
+    assert!(matches!(
+        Bundle::new(&data[..data.len() - 1]),
+        Err(BundleError::UnexpectedEof)
+    ));
+}
+
+#[test]
+fn identical_entries_are_stored_once() {
+    let pixels = [0x1234u16, 0x1234, 0xF800, 0x07E0, 0x07E0, 0x07E0];
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(3, 2, &pixels, &mut encoded).unwrap();
+    let mut small_array = Vec::new();
+    Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::Entries16,
+        3,
+        2,
+        &pixels,
+        &mut small_array,
+    )
+    .unwrap();
+    assert_ne!(encoded, small_array);
+
+    let entries: [&[u8]; 4] = [&encoded, b"other", &encoded, &small_array];
+    let data = write_bundle(&entries).unwrap();
+    let bundle = Bundle::new(&data).unwrap();
+    assert_eq!(bundle.len(), 4);
+    assert!(!bundle.has_hashes());
+    assert_eq!(bundle.hash(0), None);
+
+    // the other profile decodes to the same pixels, so all three point to the first entry's data
+    assert_eq!(bundle.get(0), Some(&encoded[..]));
+    assert_eq!(bundle.get(2), Some(&encoded[..]));
+    assert_eq!(bundle.get(3), Some(&encoded[..]));
+    assert_eq!(data.len(), 6 + 4 * 8 + encoded.len() + 5);
+}
+
+#[test]
+fn index_with_hashes() {
+    let pixels = [0x1234u16; 16];
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(4, 4, &pixels, &mut encoded).unwrap();
+
+    let entries: [&[u8]; 3] = [b"first", &encoded, b"first"];
+    let data = BundleWriter::new().with_hashes().write(&entries).unwrap();
+    assert_eq!(&data[..4], EXTENDED_BUNDLE_MAGIC);
+
+    let bundle = Bundle::new(&data).unwrap();
+    assert!(bundle.has_hashes());
+    assert_eq!(bundle.iter().collect::<Vec<_>>(), entries);
+    for (index, entry) in entries.iter().enumerate() {
+        assert_eq!(bundle.hash(index), Some(content_hash(entry)));
+    }
+    assert_eq!(bundle.hash(0), bundle.hash(2));
+    assert_ne!(bundle.hash(0), bundle.hash(1));
+    assert_eq!(bundle.hash(3), None);
+
+    let mut unsupported = data.clone();
+    unsupported[4] |= 0b1000_0000;
+    assert!(matches!(
+        Bundle::new(&unsupported),
+        Err(BundleError::UnsupportedFlags)
+    ));
+}
+
+#[test]
+fn patch_only_holds_changed_entries() {
+    let old_entries: [&[u8]; 3] = [b"unchanged icon", b"old logo", b"removed"];
+    let new_entries: [&[u8]; 4] = [b"new logo", b"unchanged icon", b"new logo", b"added"];
+    let old_data = write_bundle(&old_entries).unwrap();
+    let new_data = BundleWriter::new()
+        .with_hashes()
+        .write(&new_entries)
+        .unwrap();
+    let (old, new) = (
+        Bundle::new(&old_data).unwrap(),
+        Bundle::new(&new_data).unwrap(),
+    );
+
+    let patch = diff(&old, &new);
+    assert!(matches!(
+        patch.entries()[0],
+        PatchEntry::Insert(b"new logo")
+    ));
+    assert!(matches!(
+        patch.entries()[1],
+        PatchEntry::Copy { length: 14, .. }
+    ));
+    assert_eq!(patch.entries()[2], PatchEntry::Repeat(0));
+    assert!(matches!(patch.entries()[3], PatchEntry::Insert(b"added")));
+
+    let bytes = patch.to_bytes();
+    assert!(bytes.len() < new_data.len());
+    let parsed = Patch::parse(&bytes).unwrap();
+    assert_eq!(parsed, patch);
+    assert_eq!(apply_patch(&old, &parsed).unwrap(), new_data);
+
+    assert!(matches!(
+        Patch::parse(&bytes[..bytes.len() - 1]),
+        Err(BundleError::UnexpectedEof)
+    ));
+
+    // the unchanged entry differs on the device
+    let mut corrupted = old_data.clone();
+    corrupted[6 + 3 * 8] ^= 1;
+    assert!(matches!(
+        apply_patch(&Bundle::new(&corrupted).unwrap(), &parsed),
+        Err(BundleError::PatchMismatch)
+    ));
+}
+
+#[test]
+fn checksums() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(
+        sha256(b"abc"),
+        [
+            0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+            0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+            0xf2, 0x00, 0x15, 0xad,
+        ]
+    );
+    // two blocks of padding
+    assert_eq!(sha256(&[b'a'; 56])[..4], [0xb3, 0x54, 0x39, 0xa4]);
+}
+
+#[test]
+fn verify_integrity() {
+    let entries: [&[u8]; 3] = [b"first", b"second", b"first"];
+    let data = BundleWriter::new()
+        .with_hashes()
+        .with_crcs()
+        .with_digest()
+        .write(&entries)
+        .unwrap();
+
+    // trailing bytes, e.g. the rest of a flash sector
+    let mut flash = data.clone();
+    flash.extend_from_slice(&[0xFF; 16]);
+    let bundle = Bundle::new(&flash).unwrap();
+    assert!(bundle.has_crcs() && bundle.has_digest());
+    assert_eq!(bundle.digest().map(|_| ()), Some(()));
+    assert_eq!(bundle.crc(1), Some(crc32(b"second")));
+    assert_eq!(bundle.iter().collect::<Vec<_>>(), entries);
+    bundle.verify().unwrap();
+    assert_eq!(bundle.get_verified(2).unwrap(), b"first");
+    assert!(matches!(
+        bundle.get_verified(3),
+        Err(BundleError::NoSuchEntry)
+    ));
+
+    // flip a bit in the data of the second entry
+    let second = data.len() - b"second".len();
+    flash[second] ^= 1;
+    let bundle = Bundle::new(&flash).unwrap();
+    assert!(matches!(bundle.verify(), Err(BundleError::CorruptBundle)));
+    assert_eq!(bundle.get_verified(0).unwrap(), b"first");
+    assert!(matches!(
+        bundle.get_verified(1),
+        Err(BundleError::CorruptEntry { index: 1 })
+    ));
+
+    // bundles without checksums always pass
+    let plain = write_bundle(&entries).unwrap();
+    Bundle::new(&plain).unwrap().verify().unwrap();
+}
+
+#[test]
+fn verify_deduplicated_profiles() {
+    let pixels: Vec<u16> = (0..64u16)
+        .map(|i| i.wrapping_mul(0x0841) ^ (i % 5))
+        .collect();
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(8, 8, &pixels, &mut encoded).unwrap();
+    let mut small_array = Vec::new();
+    Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::Entries16,
+        8,
+        8,
+        &pixels,
+        &mut small_array,
+    )
+    .unwrap();
+    assert_ne!(encoded, small_array);
+
+    // the second entry is stored as the first one, its CRC has to match that data
+    let entries: [&[u8]; 2] = [&encoded, &small_array];
+    let data = BundleWriter::new().with_crcs().write(&entries).unwrap();
+    let bundle = Bundle::new(&data).unwrap();
+    assert_eq!(bundle.get(1), Some(&encoded[..]));
+    assert_eq!(bundle.crc(1), Some(crc32(&encoded)));
+    bundle.verify().unwrap();
+    assert_eq!(bundle.get_verified(1).unwrap(), &encoded[..]);
+}
@@ -0,0 +1,39 @@
+use q565::{
+    byteorder::{BigEndian, Endianness, LittleEndian, NativeEndian},
+    decode::{Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    utils, ColorFormat, Rgb565, Rgb888,
+};
+
+const PIXEL: u16 = 0x1234;
+
+fn decode<B: Endianness, C: ColorFormat>() -> C::OutputElement {
+    let mut data = Vec::new();
+    Q565EncodeContext::encode(1, 1, &[PIXEL], &mut data).unwrap();
+
+    let mut output = Vec::new();
+    Q565DecodeContext::decode::<B>(&data, VecDecodeOutput::<C>::new(&mut output)).unwrap();
+    output[0].clone()
+}
+
+#[test]
+fn rgb565_outputs() {
+    // the bytes in memory are in the requested order
+    assert_eq!(decode::<NativeEndian, Rgb565>(), PIXEL);
+    assert_eq!(
+        decode::<BigEndian, Rgb565>().to_ne_bytes(),
+        PIXEL.to_be_bytes()
+    );
+    assert_eq!(
+        decode::<LittleEndian, Rgb565>().to_ne_bytes(),
+        PIXEL.to_le_bytes()
+    );
+}
+
+#[test]
+fn rgb888_outputs() {
+    let [r, g, b] = decode::<BigEndian, Rgb888>();
+    assert_eq!(decode::<LittleEndian, Rgb888>(), [b, g, r]);
+    let rgb565 = utils::rgb888_to_rgb565([r, g, b]);
+    assert_eq!(utils::encode_rgb565_unchecked(rgb565), PIXEL);
+}
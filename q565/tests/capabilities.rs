@@ -0,0 +1,90 @@
+use q565::{
+    byteorder::LittleEndian,
+    capabilities::{Advertisement, AdvertisementError, Capabilities, FormatVersion},
+    decode::{MiniDecoder, Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    ColorArraySize, Rgb565,
+};
+
+#[test]
+fn advertisement_roundtrip() {
+    let advertisement = Advertisement::new(Capabilities::decoder(16));
+    let parsed = Advertisement::parse(&advertisement.to_bytes()).unwrap();
+    assert_eq!(parsed, advertisement);
+    assert_eq!(parsed.version, FormatVersion::CURRENT);
+
+    // newer minor versions may add capabilities
+    let parsed = Advertisement::parse(&[1, 7, 0xFF, 0xFF]).unwrap();
+    assert!(parsed.capabilities.contains(Capabilities::TILES));
+
+    assert!(matches!(
+        Advertisement::parse(&[1, 1, 0]),
+        Err(AdvertisementError::UnexpectedEof)
+    ));
+    assert!(matches!(
+        Advertisement::parse(&[2, 0, 0, 0]),
+        Err(AdvertisementError::UnsupportedVersion { major: 2 })
+    ));
+}
+
+#[test]
+fn capabilities_match_decoders() {
+    let pixels: Vec<u16> = (0..64u16).map(|i| i.wrapping_mul(0x0841) ^ 0x1F).collect();
+
+    for size in [
+        ColorArraySize::NoArray,
+        ColorArraySize::Entries16,
+        ColorArraySize::Entries32,
+        ColorArraySize::Entries64,
+    ] {
+        let mut encoded = Vec::new();
+        assert!(
+            Q565EncodeContext::encode_to_vec_sized(size, 8, 8, &pixels, &mut encoded).is_some()
+        );
+        let (header, _) = Q565DecodeContext::decode_header(&encoded).unwrap();
+
+        let mut output = Vec::new();
+        let small = Q565DecodeContext::<16>::new_sized()
+            .decode_with_state::<LittleEndian>(
+                &encoded,
+                VecDecodeOutput::<Rgb565>::new(&mut output),
+            )
+            .is_ok();
+        assert_eq!(Capabilities::decoder(16).supports(&header), small);
+
+        let mut output = Vec::new();
+        let mini = MiniDecoder::decode::<LittleEndian>(
+            &encoded,
+            VecDecodeOutput::<Rgb565>::new(&mut output),
+        )
+        .is_ok();
+        assert_eq!(Capabilities::mini_decoder().supports(&header), mini);
+
+        assert!(Capabilities::decoder(64).supports(&header));
+        assert!(FormatVersion::CURRENT.capabilities().supports(&header));
+    }
+}
+
+#[test]
+fn best_profile() {
+    assert_eq!(
+        Capabilities::decoder(64).best_profile(),
+        Some(ColorArraySize::Entries64)
+    );
+    assert_eq!(
+        Capabilities::decoder(32).best_profile(),
+        Some(ColorArraySize::Entries32)
+    );
+    assert_eq!(
+        Capabilities::mini_decoder().best_profile(),
+        Some(ColorArraySize::NoArray)
+    );
+    assert_eq!(Capabilities::RAW.best_profile(), None);
+    assert_eq!(
+        FormatVersion::V1_0.capabilities().best_profile(),
+        Some(ColorArraySize::Entries64)
+    );
+    assert!(!FormatVersion::V1_0
+        .capabilities()
+        .contains(Capabilities::ENTRIES_16 | Capabilities::RAW));
+}
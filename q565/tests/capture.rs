@@ -0,0 +1,61 @@
+mod common;
+
+use common::decode;
+use q565::{
+    capture::{Capture, CaptureError},
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    Rect,
+};
+use xcap::image::{Rgba, RgbaImage};
+
+fn test_image() -> RgbaImage {
+    RgbaImage::from_fn(40, 30, |x, y| {
+        Rgba([(x * 6) as u8, (y * 8) as u8, ((x + y) * 3) as u8, 255])
+    })
+}
+
+#[test]
+fn encodes_region() {
+    let image = test_image();
+    let region = Rect {
+        x: 5,
+        y: 10,
+        width: 20,
+        height: 15,
+    };
+    let encoded = Capture::primary_monitor()
+        .with_region(region)
+        .encode_image(&image)
+        .unwrap();
+
+    let (width, height, pixels) = decode(&encoded);
+    assert_eq!((width, height), (20, 15));
+    for (i, &pixel) in pixels.iter().enumerate() {
+        let (x, y) = (5 + i as u32 % 20, 10 + i as u32 / 20);
+        let [r, g, b, _] = image.get_pixel(x, y).0;
+        assert_eq!(pixel, encode_rgb565_unchecked(rgb888_to_rgb565([r, g, b])));
+    }
+}
+
+#[test]
+fn encodes_whole_image_by_default() {
+    let (width, height, pixels) = decode(&Capture::monitor(0).encode_image(&test_image()).unwrap());
+    assert_eq!((width, height), (40, 30));
+    assert_eq!(pixels.len(), 40 * 30);
+}
+
+#[test]
+fn rejects_region_out_of_bounds() {
+    let region = Rect {
+        x: 30,
+        y: 0,
+        width: 20,
+        height: 10,
+    };
+    assert!(matches!(
+        Capture::window("test")
+            .with_region(region)
+            .encode_image(&test_image()),
+        Err(CaptureError::RegionOutOfBounds)
+    ));
+}
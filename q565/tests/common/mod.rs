@@ -0,0 +1,117 @@
+//! Helpers shared by the integration tests.
+//!
+//! Every test target includes this module with `mod common;` and uses a different part of it.
+#![allow(dead_code)]
+
+use image::ImageFormat;
+use q565::{
+    byteorder::NativeEndian,
+    decode::Q565DecodeContext,
+    encode::Q565EncodeContext,
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    Rgb565, HEADER_LEN,
+};
+use std::io::BufReader;
+
+/// Encodes an image with the default color array.
+pub fn encode(width: u16, height: u16, pixels: &[u16]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(width, height, pixels, &mut encoded).unwrap();
+    encoded
+}
+
+/// Encodes an image like [`encode`], returning only the ops after the header, e.g. for the
+/// streaming decoders.
+pub fn encode_ops(width: u16, height: u16, pixels: &[u16]) -> Vec<u8> {
+    encode(width, height, pixels).split_off(HEADER_LEN)
+}
+
+/// Decodes an image into its size and RGB565 pixels.
+pub fn decode(data: &[u8]) -> (u16, u16, Vec<u16>) {
+    let (header, pixels) = Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(data).unwrap();
+    (header.width, header.height, pixels)
+}
+
+/// Converts RGB888 pixels to RGB565, rounding to the nearest color.
+pub fn to_rgb565(pixels: &[[u8; 3]]) -> Vec<u16> {
+    pixels
+        .iter()
+        .map(|&p| encode_rgb565_unchecked(rgb888_to_rgb565(p)))
+        .collect()
+}
+
+/// Loads an image of `test_images` as RGB888.
+pub fn load_rgb888(name: &str) -> (u16, u16, Vec<[u8; 3]>) {
+    let image = image::load(
+        BufReader::new(std::fs::File::open(format!("../test_images/{name}")).unwrap()),
+        ImageFormat::Png,
+    )
+    .unwrap()
+    .into_rgb8();
+    let (width, height) = (image.width() as u16, image.height() as u16);
+    (width, height, image.pixels().map(|p| p.0).collect())
+}
+
+/// Loads an image of `test_images` as RGB565.
+pub fn load(name: &str) -> (u16, u16, Vec<u16>) {
+    let (width, height, pixels) = load_rgb888(name);
+    (width, height, to_rgb565(&pixels))
+}
+
+/// Loads all images of `test_images` as RGB888.
+pub fn test_images_rgb888() -> impl Iterator<Item = (u16, u16, Vec<[u8; 3]>)> {
+    std::fs::read_dir("../test_images").unwrap().map(|image| {
+        let name = image.unwrap().file_name();
+        load_rgb888(name.to_str().unwrap())
+    })
+}
+
+/// Loads all images of `test_images` as RGB565.
+pub fn test_images() -> impl Iterator<Item = (u16, u16, Vec<u16>)> {
+    test_images_rgb888().map(|(width, height, pixels)| (width, height, to_rgb565(&pixels)))
+}
+
+/// Runs of red, `run` out of every `period` pixels, with pixels that change with every step in
+/// between.
+fn runs(width: u16, height: u16, period: usize, run: usize) -> Vec<u16> {
+    (0..usize::from(width) * usize::from(height))
+        .map(|i| {
+            if i % period < run {
+                0xF800
+            } else {
+                (i as u16).wrapping_mul(31)
+            }
+        })
+        .collect()
+}
+
+/// Short runs, with pixels that change with every step in between, so that the image contains all
+/// ops.
+pub fn test_pattern(width: u16, height: u16) -> Vec<u16> {
+    runs(width, height, 7, 3)
+}
+
+/// Runs of 90 pixels, longer than a run op and most rows, mixed with other ops.
+pub fn long_runs(width: u16, height: u16) -> Vec<u16> {
+    runs(width, height, 170, 90)
+}
+
+/// Smooth gradient, which encodes to mostly small differences.
+pub fn gradient(width: u16, height: u16) -> Vec<u16> {
+    (0..usize::from(width) * usize::from(height))
+        .map(|i| ((i % 31) as u16) << 11 | ((i / 7 % 63) as u16) << 5 | (i % 3) as u16)
+        .collect()
+}
+
+/// Pseudo-random pixels (xorshift32), which hardly compress.
+pub fn noise(len: usize) -> Vec<u16> {
+    let mut state = 0x1234_5678u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u16
+        })
+        .collect()
+}
@@ -0,0 +1,98 @@
+use q565::{
+    conformance::{check_stream, Violation},
+    encode::Q565EncodeContext,
+    ColorArraySize,
+};
+
+fn pixels() -> Vec<u16> {
+    (0..256u32).map(|i| (i / 5 * 0x0821) as u16).collect()
+}
+
+#[test]
+fn encoder_output_is_conformant() {
+    let pixels = pixels();
+    for size in [
+        ColorArraySize::NoArray,
+        ColorArraySize::Entries16,
+        ColorArraySize::Entries32,
+        ColorArraySize::Entries64,
+    ] {
+        let mut encoded = Vec::new();
+        Q565EncodeContext::encode_to_vec_sized(size, 16, 16, &pixels, &mut encoded).unwrap();
+        assert_eq!(check_stream(&encoded, &pixels), [], "{size:?}");
+    }
+
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_auto(16, 16, &pixels, &mut encoded).unwrap();
+    assert_eq!(check_stream(&encoded, &pixels), []);
+}
+
+#[test]
+fn header_violations() {
+    assert_eq!(
+        check_stream(b"qoif\x01\x00\x01\x00\xFF", &[0]),
+        [Violation::InvalidMagic]
+    );
+    assert_eq!(
+        check_stream(b"q56x\x08\x01\x00\x01\x00\xFF", &[0]),
+        [Violation::ReservedFlags { flags: 0x08 }]
+    );
+    assert_eq!(
+        check_stream(b"q565\x00\x00\x01\x00\xFF", &[]),
+        [Violation::EmptyImage]
+    );
+    assert_eq!(
+        check_stream(b"q565\x02\x00\x01\x00\xFF", &[0]),
+        [
+            Violation::SizeMismatch {
+                header: 2,
+                reference: 1
+            },
+            Violation::MissingPixels {
+                offset: 8,
+                produced: 0,
+                expected: 2
+            }
+        ]
+    );
+}
+
+#[test]
+fn op_violations() {
+    // an RGB565 op hashing to index 2, followed by two INDEX ops to it
+    let color = 0x0101u16;
+    let [a, b] = color.to_le_bytes();
+    let mut stream = b"q56x\x02\x04\x00\x01\x00".to_vec();
+    stream.extend([0xFE, a, b, 0x02, 0x02, 0x30, 0xFF]);
+
+    assert_eq!(
+        check_stream(&stream, &[color, color, color, 0x1234]),
+        [
+            Violation::RepeatedIndex {
+                offset: 13,
+                index: 2
+            },
+            Violation::IndexOutOfRange {
+                offset: 14,
+                index: 0x30
+            },
+            Violation::PixelMismatch {
+                offset: 14,
+                pixel: 3,
+                expected: 0x1234,
+                actual: 0
+            },
+        ]
+    );
+
+    // a run past the end of the image, then a truncated op
+    let mut stream = b"q565\x02\x00\x01\x00".to_vec();
+    stream.extend([0xFE, a, b, 0xC1, 0xFE, a]);
+    assert_eq!(
+        check_stream(&stream, &[color, color]),
+        [
+            Violation::TooManyPixels { offset: 11 },
+            Violation::UnexpectedEof { offset: 12 }
+        ]
+    );
+}
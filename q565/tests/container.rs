@@ -0,0 +1,96 @@
+use q565::{
+    byteorder::LittleEndian,
+    container::{decode_container, encode_container, ContainerError, ContainerReader, NoTransform},
+    decode::{Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    Rgb565,
+};
+
+/// Stand-in for a stream cipher: XORs each byte with a keystream derived from its position.
+fn xor_keystream(index: u32, offset: usize, data: &mut [u8]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= (index as u8).wrapping_mul(31) ^ ((offset + i) as u8).wrapping_mul(7) ^ 0x5A;
+    }
+}
+
+fn image() -> (Vec<u16>, Vec<u8>) {
+    let pixels: Vec<u16> = (0..400u16).map(|i| (i / 3).wrapping_mul(4099)).collect();
+    let mut encoded = Vec::new();
+    assert!(Q565EncodeContext::encode_to_vec(20, 20, &pixels, &mut encoded).is_some());
+    (pixels, encoded)
+}
+
+#[test]
+fn container_roundtrip() {
+    let (pixels, encoded) = image();
+
+    let mut container = Vec::new();
+    let chunks = encode_container(&encoded, 64, &mut xor_keystream, &mut container);
+    assert_eq!(chunks as usize, encoded.len().div_ceil(64));
+
+    let mut decoded = Vec::new();
+    let pixels_written = decode_container::<LittleEndian, _>(
+        &container,
+        &mut xor_keystream,
+        VecDecodeOutput::<Rgb565>::new(&mut decoded),
+    )
+    .unwrap();
+    assert_eq!(pixels_written, pixels.len());
+    assert_eq!(decoded, pixels);
+
+    // the wrong transform garbles the image
+    assert!(decode_container::<LittleEndian, _>(
+        &container,
+        &mut NoTransform,
+        VecDecodeOutput::<Rgb565>::new(&mut Vec::new()),
+    )
+    .is_err());
+}
+
+#[test]
+fn container_incremental() {
+    let (pixels, encoded) = image();
+    let mut container = Vec::new();
+    encode_container(&encoded, 50, &mut xor_keystream, &mut container);
+    container.extend_from_slice(b"trailing");
+
+    // pieces that don't line up with the chunks
+    let mut reader = ContainerReader::new(xor_keystream);
+    let mut image = Vec::new();
+    let mut consumed = 0;
+    for piece in container.chunks_mut(7) {
+        consumed += reader
+            .push(piece, |payload| image.extend_from_slice(payload))
+            .unwrap();
+    }
+    assert!(reader.is_finished());
+    assert_eq!(consumed, container.len() - b"trailing".len());
+    assert_eq!(image, encoded);
+
+    let mut decoded = Vec::new();
+    Q565DecodeContext::decode::<LittleEndian>(&image, VecDecodeOutput::<Rgb565>::new(&mut decoded))
+        .unwrap();
+    assert_eq!(decoded, pixels);
+}
+
+#[test]
+fn container_errors() {
+    let mut reader = ContainerReader::new(NoTransform);
+    assert!(matches!(
+        reader.push(&mut b"q5up".to_vec(), |_| ()),
+        Err(ContainerError::InvalidMagic)
+    ));
+
+    let (_, encoded) = image();
+    let mut container = Vec::new();
+    encode_container(&encoded, 64, &mut NoTransform, &mut container);
+    container.truncate(container.len() - 1);
+    assert!(matches!(
+        decode_container::<LittleEndian, _>(
+            &container,
+            &mut NoTransform,
+            VecDecodeOutput::<Rgb565>::new(&mut Vec::new()),
+        ),
+        Err(ContainerError::UnexpectedEof)
+    ));
+}
@@ -0,0 +1,133 @@
+use q565::utils::{
+    decode_565, encode_rgb565_unchecked, rgb565_to_rgb888, rgb888_to_rgb565, rgb888_to_rgb565_with,
+    Quantization,
+};
+
+/// `value * 255 / max`, rounded to the nearest integer.
+fn expand(value: u8, max: u32) -> u8 {
+    ((u32::from(value) * 255 * 2 + max) / (2 * max)) as u8
+}
+
+/// `value * max / 255`, rounded to the nearest integer.
+fn quantize(value: u8, max: u32) -> u8 {
+    ((u32::from(value) * max * 2 + 255) / (2 * 255)) as u8
+}
+
+#[test]
+fn rgb565_to_rgb888_rounds_to_nearest() {
+    for pixel in 0..=u16::MAX {
+        let [r, g, b] = decode_565(pixel);
+        assert_eq!(
+            rgb565_to_rgb888([r, g, b]),
+            [expand(r, 31), expand(g, 63), expand(b, 31)],
+            "{pixel:#06x}"
+        );
+    }
+}
+
+#[test]
+fn rgb888_to_rgb565_rounds_to_nearest() {
+    for value in 0..=u8::MAX {
+        assert_eq!(
+            rgb888_to_rgb565([value, value, value]),
+            [
+                quantize(value, 31),
+                quantize(value, 63),
+                quantize(value, 31)
+            ],
+            "{value:#04x}"
+        );
+    }
+
+    // the channels are independent
+    for r in (0..=u8::MAX).step_by(3) {
+        for g in (0..=u8::MAX).step_by(5) {
+            for b in (0..=u8::MAX).step_by(7) {
+                assert_eq!(
+                    rgb888_to_rgb565([r, g, b]),
+                    [quantize(r, 31), quantize(g, 63), quantize(b, 31)],
+                    "{:?}",
+                    [r, g, b]
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn rgb565_round_trips_through_rgb888() {
+    for pixel in 0..=u16::MAX {
+        let rgb888 = rgb565_to_rgb888(decode_565(pixel));
+        assert_eq!(
+            encode_rgb565_unchecked(rgb888_to_rgb565(rgb888)),
+            pixel,
+            "{pixel:#06x}"
+        );
+    }
+}
+
+#[test]
+fn conversion_extremes() {
+    assert_eq!(rgb565_to_rgb888([0, 0, 0]), [0, 0, 0]);
+    assert_eq!(rgb565_to_rgb888([31, 63, 31]), [0xFF, 0xFF, 0xFF]);
+    assert_eq!(rgb888_to_rgb565([0, 0, 0]), [0, 0, 0]);
+    assert_eq!(rgb888_to_rgb565([0xFF, 0xFF, 0xFF]), [31, 63, 31]);
+    // the midpoints between two levels
+    assert_eq!(rgb888_to_rgb565([4, 2, 4]), [0, 0, 0]);
+    assert_eq!(rgb888_to_rgb565([5, 3, 5]), [1, 1, 1]);
+}
+
+/// BT.601 luma of an RGB888 pixel, in thousandths.
+fn luma([r, g, b]: [u8; 3]) -> i32 {
+    299 * i32::from(r) + 587 * i32::from(g) + 114 * i32::from(b)
+}
+
+#[test]
+fn quantization_modes() {
+    for r in (0..=u8::MAX).step_by(3) {
+        for g in (0..=u8::MAX).step_by(5) {
+            for b in (0..=u8::MAX).step_by(7) {
+                let pixel = [r, g, b];
+                assert_eq!(
+                    rgb888_to_rgb565_with(pixel, Quantization::Truncate),
+                    [r >> 3, g >> 2, b >> 3]
+                );
+                let rounded = rgb888_to_rgb565_with(pixel, Quantization::Round);
+                assert_eq!(rounded, rgb888_to_rgb565(pixel));
+
+                // every channel is off by less than one level, and the luma is no further off
+                // than when rounding
+                let luma_preserving = rgb888_to_rgb565_with(pixel, Quantization::Luma);
+                for (quantized, rounded) in luma_preserving.into_iter().zip(rounded) {
+                    assert!(quantized.abs_diff(rounded) <= 1, "{pixel:?}");
+                }
+                let luma_error =
+                    |quantized| (luma(rgb565_to_rgb888(quantized)) - luma(pixel)).abs();
+                assert!(
+                    luma_error(luma_preserving) <= luma_error(rounded),
+                    "{pixel:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn luma_quantization_keeps_exact_colors() {
+    for pixel in 0..=u16::MAX {
+        let rgb888 = rgb565_to_rgb888(decode_565(pixel));
+        assert_eq!(
+            rgb888_to_rgb565_with(rgb888, Quantization::Luma),
+            decode_565(pixel),
+            "{pixel:#06x}"
+        );
+    }
+}
+
+#[test]
+fn quantization_from_str() {
+    assert_eq!("truncate".parse(), Ok(Quantization::Truncate));
+    assert_eq!("Round".parse(), Ok(Quantization::Round));
+    assert_eq!("LUMA".parse(), Ok(Quantization::Luma));
+    assert!("nearest".parse::<Quantization>().is_err());
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use common::gradient;
+use q565::{
+    byteorder::{BigEndian, NativeEndian},
+    decode::{DecodeError, Decoder, DecoderError, Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    ColorArraySize, Rgb565, Rgb888,
+};
+
+#[test]
+fn matches_low_level_functions() {
+    let pixels = gradient(20, 15);
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(20, 15, &pixels, &mut encoded).unwrap();
+
+    let (header, decoded) = Decoder::new()
+        .byte_order::<NativeEndian>()
+        .decode_to_vec(&encoded)
+        .unwrap();
+    assert_eq!((header.width, header.height), (20, 15));
+    assert_eq!(decoded, pixels);
+
+    let decoder = Decoder::new().byte_order::<BigEndian>();
+    assert_eq!(
+        decoder.decode_to_vec(&encoded).unwrap().1,
+        Q565DecodeContext::decode_to_vec::<BigEndian, Rgb565>(&encoded)
+            .unwrap()
+            .1
+    );
+    assert_eq!(
+        decoder
+            .color_format::<Rgb888>()
+            .decode_to_vec(&encoded)
+            .unwrap()
+            .1,
+        Q565DecodeContext::decode_to_vec::<BigEndian, Rgb888>(&encoded)
+            .unwrap()
+            .1
+    );
+
+    #[cfg(not(feature = "forbid-unsafe"))]
+    {
+        use q565::byteorder::LittleEndian;
+        use std::mem::MaybeUninit;
+
+        let mut uninit = vec![MaybeUninit::uninit(); 300];
+        let (_, decoded) = Decoder::new()
+            .byte_order::<LittleEndian>()
+            .decode_to_uninit(&encoded, &mut uninit)
+            .unwrap();
+        assert_eq!(decoded, &pixels[..]);
+    }
+
+    let mut output = Vec::new();
+    let (_, written) = Decoder::new()
+        .byte_order::<NativeEndian>()
+        .strict(true)
+        .decode(&encoded, VecDecodeOutput::<Rgb565>::new(&mut output))
+        .unwrap();
+    assert_eq!(written, 300);
+    assert_eq!(output, pixels);
+}
+
+#[test]
+fn limits_pixels() {
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(20, 15, &gradient(20, 15), &mut encoded).unwrap();
+
+    assert!(Decoder::new()
+        .limit_pixels(300)
+        .decode_to_vec(&encoded)
+        .is_ok());
+    assert!(matches!(
+        Decoder::new().limit_pixels(299).header(&encoded),
+        Err(DecoderError::TooLarge)
+    ));
+    assert!(matches!(
+        Decoder::new().limit_pixels(299).decode_to_vec(&encoded),
+        Err(DecoderError::TooLarge)
+    ));
+}
+
+#[test]
+fn strict_validation() {
+    // repeating colors, so there are index ops
+    let pixels: Vec<u16> = (0..300).map(|i| [0x8410, 0x4208, 0xC618][i % 3]).collect();
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::Entries32,
+        20,
+        15,
+        &pixels,
+        &mut encoded,
+    )
+    .unwrap();
+
+    let mut trailing = encoded.clone();
+    trailing.push(0);
+    assert!(Decoder::new().decode_to_vec(&trailing).is_ok());
+    assert!(matches!(
+        Decoder::new().strict(true).decode_to_vec(&trailing),
+        Err(DecoderError::TrailingData)
+    ));
+
+    // the lenient decoder wraps indices around, the strict one rejects them up front
+    let mut no_array = encoded;
+    no_array[4] = ColorArraySize::NoArray.flags();
+    let mut output = Vec::new();
+    assert!(matches!(
+        Decoder::new()
+            .strict(true)
+            .decode(&no_array, VecDecodeOutput::<Rgb565>::new(&mut output)),
+        Err(DecoderError::Decode {
+            source: DecodeError::ColorArrayTooSmall
+        })
+    ));
+    assert!(output.is_empty());
+}
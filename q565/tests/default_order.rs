@@ -0,0 +1,46 @@
+use q565::{
+    byteorder::{BigEndian, DefaultOrder, Endianness, NativeEndian},
+    decode::{Decoder, Q565DecodeContext},
+    encode::{Encoder, Q565EncodeContext},
+    Rgb565,
+};
+
+fn is_big_endian<B: Endianness>() -> bool {
+    B::read_u16(&[0x12, 0x34]) == 0x1234
+}
+
+#[test]
+fn default_order_follows_the_feature() {
+    assert_eq!(
+        is_big_endian::<DefaultOrder>(),
+        cfg!(feature = "default-be") || cfg!(target_endian = "big")
+    );
+}
+
+#[test]
+fn builders_use_the_default_order() {
+    let pixels = [0xF800, 0x07E0, 0x001F, 0x1234];
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(2, 2, &pixels, &mut encoded).unwrap();
+
+    let (_, decoded) = Decoder::new().decode_to_vec(&encoded).unwrap();
+    let (_, expected) = Q565DecodeContext::decode_to_vec::<DefaultOrder, Rgb565>(&encoded).unwrap();
+    assert_eq!(decoded, expected);
+    if is_big_endian::<DefaultOrder>() != is_big_endian::<NativeEndian>() {
+        assert_ne!(decoded, pixels);
+    }
+
+    // the encoder takes pixels in the same order, so the two cancel out
+    let mut reencoded = Vec::new();
+    Encoder::new()
+        .encode(2, 2, &decoded, &mut reencoded)
+        .unwrap();
+    assert_eq!(reencoded, encoded);
+
+    // an explicit byte order still wins
+    let (_, big_endian) = Decoder::new()
+        .byte_order::<BigEndian>()
+        .decode_to_vec(&encoded)
+        .unwrap();
+    assert_eq!(big_endian, pixels.map(u16::to_be));
+}
@@ -0,0 +1,92 @@
+#[cfg(not(feature = "forbid-unsafe"))]
+mod common;
+
+#[cfg(not(feature = "forbid-unsafe"))]
+use common::{encode_ops, noise};
+#[cfg(not(feature = "forbid-unsafe"))]
+use q565::{
+    byteorder::LittleEndian,
+    demux::{frame_header, write_frames, DemuxError, DemuxStream, Demuxer},
+};
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn demux_interleaved_streams() {
+    let images: [Vec<Vec<u16>>; 2] = [
+        vec![noise(100), vec![0x1234; 80]],
+        vec![(0..60u16).map(|i| i / 8 * 0x0821).collect()],
+    ];
+
+    // interleave small frames of both streams
+    let streams: Vec<Vec<u8>> = images
+        .iter()
+        .map(|images| {
+            images
+                .iter()
+                .flat_map(|image| encode_ops(image.len() as u16, 1, image))
+                .collect()
+        })
+        .collect();
+    let mut transport = Vec::new();
+    let mut offsets = [0, 0];
+    while offsets.iter().zip(&streams).any(|(&o, s)| o < s.len()) {
+        for (id, stream) in streams.iter().enumerate() {
+            let chunk = &stream[offsets[id]..stream.len().min(offsets[id] + 5)];
+            write_frames(id as u8, chunk, 3, &mut transport);
+            offsets[id] += chunk.len();
+        }
+    }
+    // empty frames are skipped
+    transport.extend_from_slice(&frame_header(1, 0));
+
+    for checked in [true, false] {
+        let mut output0 = vec![0u16; 100];
+        let mut output1 = vec![0u16; 60];
+        let mut demuxer = Demuxer::new([
+            DemuxStream::new(&mut output0),
+            DemuxStream::new(&mut output1),
+        ]);
+
+        let mut decoded: [Vec<Vec<u16>>; 2] = Default::default();
+        let mut on_image =
+            |stream: usize, pixels: &mut [u16]| decoded[stream].push(pixels.to_vec());
+        // transport chunks that don't line up with the frames
+        for chunk in transport.chunks(7) {
+            if checked {
+                demuxer.push::<LittleEndian>(chunk, &mut on_image).unwrap();
+            } else {
+                unsafe { demuxer.push_unchecked::<LittleEndian>(chunk, &mut on_image) }.unwrap();
+            }
+        }
+
+        assert_eq!(decoded, images);
+        assert_eq!(demuxer.stream(0).unwrap().pixels_written(), 0);
+    }
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn demux_output_too_small() {
+    let mut transport = Vec::new();
+    write_frames(0, &encode_ops(8, 1, &[0x1234; 8]), 16, &mut transport);
+
+    let mut output = [0u16; 4];
+    let mut demuxer = Demuxer::new([DemuxStream::new(&mut output)]);
+    let result = demuxer.push::<LittleEndian>(&transport, |_, _| unreachable!());
+    assert!(matches!(
+        result,
+        Err(DemuxError::OutputTooSmall { stream: 0 })
+    ));
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn demux_unknown_stream() {
+    let mut output = [0u16; 4];
+    let mut demuxer = Demuxer::new([DemuxStream::new(&mut output)]);
+    let result = demuxer.push::<LittleEndian>(&frame_header(1, 1), |_, _| unreachable!());
+    assert!(matches!(
+        result,
+        Err(DemuxError::UnknownStream { stream: 1 })
+    ));
+}
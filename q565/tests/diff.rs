@@ -0,0 +1,63 @@
+mod common;
+
+use common::encode;
+use q565::diff::{compare, compare_pixels, ssim, CompareError};
+
+#[test]
+fn compare_rmse_and_psnr() {
+    let a: Vec<u16> = (0..64u16)
+        .map(|i| (i % 8) << 11 | (i % 32) << 5 | (i / 8))
+        .collect();
+    let report = compare(&encode(8, 8, &a), &encode(8, 8, &a)).unwrap();
+    assert!(report.is_identical());
+    assert_eq!(report.rmse, [0.0; 3]);
+    assert_eq!(report.psnr, [f64::INFINITY; 3]);
+
+    // raise green by 2 steps in every pixel
+    let b: Vec<u16> = a.iter().map(|&p| p + (2 << 5)).collect();
+    let report = compare(&encode(8, 8, &a), &encode(8, 8, &b)).unwrap();
+    assert_eq!(report.changed_count, 64);
+    assert!(report.changed.iter().all(|&c| c));
+    assert_eq!(report.rmse, [0.0, 2.0, 0.0]);
+    let expected_psnr = 10.0 * (63.0f64 * 63.0 / 4.0).log10();
+    assert!((report.psnr[1] - expected_psnr).abs() < 1e-9);
+    assert!(report.psnr[0].is_infinite() && report.psnr[2].is_infinite());
+
+    // only the first pixel differs, by 1 red step
+    let mut c = a.clone();
+    c[0] ^= 1 << 11;
+    let report = compare_pixels(8, 8, &a, &c);
+    assert_eq!(report.changed_count, 1);
+    assert!(report.changed[0] && !report.changed[1]);
+    assert_eq!(report.rmse[0], (1.0f64 / 64.0).sqrt());
+
+    assert!(matches!(
+        compare(&encode(8, 8, &a), &encode(4, 16, &a)),
+        Err(CompareError::DimensionMismatch)
+    ));
+    assert!(matches!(
+        compare(b"not an image", &encode(8, 8, &a)),
+        Err(CompareError::Decode { .. })
+    ));
+}
+
+#[test]
+fn ssim_identical_and_degraded() {
+    let (width, height) = (24, 16);
+    let a: Vec<u16> = (0..width * height)
+        .map(|i| ((i % 24) as u16) << 11 | ((i / 24) as u16 * 3) << 5 | (i % 7) as u16)
+        .collect();
+    assert_eq!(ssim(width as u16, height as u16, &a, &a), [1.0; 3]);
+
+    // flip the lowest red bit of every other pixel
+    let b: Vec<u16> = a
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| if i % 2 == 0 { p ^ 0x0800 } else { p })
+        .collect();
+    let report = compare_pixels(width as u16, height as u16, &a, &b);
+    assert_eq!(report.changed_count, a.len() / 2);
+    assert!(report.ssim[0] < 1.0 && report.ssim[0] > 0.9);
+    assert_eq!(report.ssim[1..], [1.0; 2]);
+    assert!(report.psnr[0].is_finite() && report.psnr[1].is_infinite());
+}
@@ -0,0 +1,481 @@
+//! Differential tests of the optimized decoders against a naive decoder, which follows the format
+//! description as literally as possible: no lookup tricks, no unchecked accesses, plain integer
+//! arithmetic on the channels. The optimized encoder is checked against [`q565::reference`] the
+//! same way.
+//!
+//! The inputs are random images encoded with every profile, random op streams, and mutations of
+//! both, from a seeded generator so failures are reproducible.
+
+#[cfg(not(feature = "forbid-unsafe"))]
+use q565::decode::streaming_no_header::Q565StreamingDecodeContext;
+use q565::{
+    byteorder::LittleEndian,
+    decode::{MiniDecoder, Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    reference,
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    ColorArraySize, Rgb565,
+};
+use std::io::BufReader;
+
+const SIZES: [ColorArraySize; 4] = [
+    ColorArraySize::Entries64,
+    ColorArraySize::Entries32,
+    ColorArraySize::Entries16,
+    ColorArraySize::NoArray,
+];
+
+/// Decodes an image into RGB565 pixels, or returns `None` if it's invalid.
+///
+/// Beyond what the format description says, this matches the behavior the decoders of this crate
+/// have always had, as encoders don't produce such streams:
+/// - indices beyond the array size of the profile wrap around,
+/// - images using the no-array profile are decoded with a 16-entry array,
+/// - any data after the end marker, or after the pixels of a raw image, is ignored.
+fn naive_decode(data: &[u8]) -> Option<(u16, u16, Vec<u16>)> {
+    // the smallest image is a header and the end marker
+    if data.len() < 9 {
+        return None;
+    }
+
+    let (entries, raw, dimensions, mut ops) = match &data[..4] {
+        b"q565" => (64, false, &data[4..8], &data[8..]),
+        b"q56x" => {
+            if data.len() < 10 {
+                return None;
+            }
+            let flags = data[4];
+            if flags >> 3 != 0 {
+                return None;
+            }
+            let raw = flags & 0b100 != 0;
+            let entries = match flags & 0b11 {
+                0 => 64,
+                1 => 32,
+                2 => 16,
+                // no color array
+                _ => 0,
+            };
+            if raw && flags & 0b11 != 0 {
+                return None;
+            }
+            (entries, raw, &data[5..9], &data[9..])
+        }
+        _ => return None,
+    };
+    let width = u16::from_le_bytes([dimensions[0], dimensions[1]]);
+    let height = u16::from_le_bytes([dimensions[2], dimensions[3]]);
+    let pixel_count = usize::from(width) * usize::from(height);
+
+    if raw {
+        if ops.len() < 2 * pixel_count {
+            return None;
+        }
+        let pixels = (0..pixel_count)
+            .map(|i| u16::from_le_bytes([ops[2 * i], ops[2 * i + 1]]))
+            .collect();
+        return Some((width, height, pixels));
+    }
+
+    let mut pixels: Vec<u16> = Vec::new();
+    let mut array = vec![0u16; entries];
+    let mut prev = 0u16;
+
+    loop {
+        let (&byte, rest) = ops.split_first()?;
+        ops = rest;
+        if byte == 0xFF {
+            break;
+        }
+        if pixels.len() == pixel_count {
+            return None;
+        }
+
+        if byte >> 6 == 0b11 && byte != 0xFE {
+            let count = usize::from(byte & 0b11_1111) + 1;
+            if pixels.len() + count > pixel_count {
+                return None;
+            }
+            for _ in 0..count {
+                pixels.push(prev);
+            }
+            continue;
+        }
+
+        let (r, g, b) = channels(prev);
+        let pixel = match byte >> 6 {
+            0b00 => {
+                let pixel = *array.get(usize::from(byte & 0b11_1111))?;
+                pixels.push(pixel);
+                prev = pixel;
+                continue;
+            }
+            0b01 => {
+                let dr = i32::from(byte >> 4 & 0b11) - 2;
+                let dg = i32::from(byte >> 2 & 0b11) - 2;
+                let db = i32::from(byte & 0b11) - 2;
+                let pixel = compose(r + dr, g + dg, b + db);
+                pixels.push(pixel);
+                prev = pixel;
+                continue;
+            }
+            0b10 => {
+                let (&second, rest) = ops.split_first()?;
+                ops = rest;
+                if byte & 0b0010_0000 == 0 {
+                    let dg = i32::from(byte & 0b1_1111) - 16;
+                    let dr = i32::from(second >> 4) - 8 + dg;
+                    let db = i32::from(second & 0b1111) - 8 + dg;
+                    compose(r + dr, g + dg, b + db)
+                } else {
+                    let dg = i32::from(byte >> 2 & 0b111) - 4;
+                    let dr = i32::from(byte & 0b11) - 2;
+                    let db = i32::from(second >> 6) - 2;
+                    let base = *array.get(usize::from(second & 0b11_1111))?;
+                    let (r, g, b) = channels(base);
+                    compose(r + dr, g + dg, b + db)
+                }
+            }
+            _ => {
+                if ops.len() < 2 {
+                    return None;
+                }
+                let pixel = u16::from_le_bytes([ops[0], ops[1]]);
+                ops = &ops[2..];
+                pixel
+            }
+        };
+
+        let [low, high] = pixel.to_le_bytes();
+        let hash = (usize::from(low) + usize::from(high)) % 64;
+        if entries > 0 {
+            array[hash % entries] = pixel;
+        }
+        pixels.push(pixel);
+        prev = pixel;
+    }
+
+    if pixels.len() != pixel_count {
+        return None;
+    }
+    Some((width, height, pixels))
+}
+
+fn channels(pixel: u16) -> (i32, i32, i32) {
+    let pixel = i32::from(pixel);
+    (pixel >> 11, (pixel >> 5) & 63, pixel & 31)
+}
+
+/// Composes a pixel from channels that may have over- or underflowed, wrapping them around.
+fn compose(r: i32, g: i32, b: i32) -> u16 {
+    (r.rem_euclid(32) << 11 | g.rem_euclid(64) << 5 | b.rem_euclid(32)) as u16
+}
+
+/// xorshift64*, seeded per test.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() >> 32) as usize % n
+    }
+
+    fn byte(&mut self) -> u8 {
+        (self.next() >> 56) as u8
+    }
+}
+
+/// A small image of random size, with regions of flat color, gradients, and noise, so all ops
+/// show up when it's encoded.
+fn random_image(rng: &mut Rng) -> (u16, u16, Vec<u16>) {
+    let width = 1 + rng.below(24) as u16;
+    let height = 1 + rng.below(8) as u16;
+    let mut pixels = Vec::new();
+    let mut pixel = rng.next() as u16;
+    for _ in 0..usize::from(width) * usize::from(height) {
+        match rng.below(8) {
+            0..=2 => {}
+            3..=5 => pixel = pixel.wrapping_add(rng.below(3) as u16 * 0x0821),
+            6 => pixel = rng.next() as u16,
+            _ => {
+                pixel = pixels
+                    .get(rng.below(pixels.len().max(1)))
+                    .copied()
+                    .unwrap_or(0)
+            }
+        }
+        pixels.push(pixel);
+    }
+    (width, height, pixels)
+}
+
+/// A header followed by random ops, usually ending with an end marker.
+fn random_stream(rng: &mut Rng) -> Vec<u8> {
+    let (width, height) = (1 + rng.below(8) as u16, 1 + rng.below(4) as u16);
+    let mut data = match rng.below(4) {
+        0 => b"q565".to_vec(),
+        n => {
+            let mut header = b"q56x".to_vec();
+            header.push(n as u8);
+            header
+        }
+    };
+    data.extend_from_slice(&width.to_le_bytes());
+    data.extend_from_slice(&height.to_le_bytes());
+
+    let pixel_count = usize::from(width) * usize::from(height);
+    let mut pixels = 0;
+    while pixels < pixel_count {
+        let mut byte = rng.byte();
+        // mostly keep runs within the image, and don't end it early
+        if (0xC0..0xFE).contains(&byte) && rng.below(4) != 0 {
+            let count = usize::from(byte & 0b11_1111) % (pixel_count - pixels) + 1;
+            byte = 0xC0 | (count - 1) as u8;
+        } else if byte == 0xFF && rng.below(4) != 0 {
+            byte = 0xFE;
+        }
+        data.push(byte);
+        match byte {
+            0xFE => {
+                data.extend_from_slice(&[rng.byte(), rng.byte()]);
+                pixels += 1;
+            }
+            0xFF => {}
+            0xC0.. => pixels += usize::from(byte & 0b11_1111) + 1,
+            0x80.. => {
+                data.push(rng.byte());
+                pixels += 1;
+            }
+            _ => pixels += 1,
+        }
+    }
+    if rng.below(8) != 0 {
+        data.push(0xFF);
+    }
+    data
+}
+
+/// Flips, inserts, or removes a few bytes, or truncates the data.
+fn mutate(rng: &mut Rng, data: &mut Vec<u8>) {
+    for _ in 0..1 + rng.below(3) {
+        let position = rng.below(data.len().max(1));
+        match rng.below(4) {
+            0 => data.truncate(position),
+            1 => data.insert(position, rng.byte()),
+            2 if position < data.len() => {
+                data.remove(position);
+            }
+            _ if position < data.len() => data[position] ^= 1 << rng.below(8),
+            _ => {}
+        }
+    }
+}
+
+/// Decodes `data` with every decoder that accepts it, and compares the results with the naive
+/// decoder.
+fn check(data: &[u8]) {
+    let expected = naive_decode(data);
+    let expected_pixels = expected.as_ref().map(|(_, _, pixels)| pixels);
+
+    let mut pixels = Vec::new();
+    let result = Q565DecodeContext::decode::<LittleEndian>(
+        data,
+        VecDecodeOutput::<Rgb565>::new(&mut pixels),
+    );
+    assert_eq!(
+        result.as_ref().ok().map(|_| &pixels),
+        expected_pixels,
+        "decode: {data:02x?}, {result:?}"
+    );
+    let Some((width, height, expected)) = expected else {
+        return;
+    };
+    let (header, _) = Q565DecodeContext::decode_header(data).unwrap();
+    assert_eq!((header.width, header.height), (width, height));
+
+    // smaller contexts decode exactly the images that fit
+    let mut pixels = Vec::new();
+    let result = Q565DecodeContext::<16>::new_sized()
+        .decode_with_state::<LittleEndian>(data, VecDecodeOutput::<Rgb565>::new(&mut pixels));
+    if header.raw || header.color_array_size.entries() <= 16 {
+        assert_eq!(pixels, expected, "decode 16: {data:02x?}, {result:?}");
+    } else {
+        assert!(result.is_err());
+    }
+
+    // the mini decoder rejects ops referencing the color array
+    let mut pixels = Vec::new();
+    if MiniDecoder::decode::<LittleEndian>(data, VecDecodeOutput::<Rgb565>::new(&mut pixels))
+        .is_ok()
+    {
+        assert_eq!(pixels, expected, "mini decode: {data:02x?}");
+    }
+
+    // the unchecked decoders aren't available with `forbid-unsafe`
+    #[cfg(not(feature = "forbid-unsafe"))]
+    {
+        let mut pixels = Vec::new();
+        // SAFETY: the naive decoder accepted the image
+        unsafe {
+            Q565DecodeContext::decode_unchecked::<LittleEndian>(
+                data,
+                VecDecodeOutput::<Rgb565>::new(&mut pixels),
+            )
+        }
+        .unwrap();
+        assert_eq!(pixels, expected, "unchecked decode: {data:02x?}");
+
+        // the streaming decoder only knows the default profile, fed in two parts
+        if !header.raw && header.color_array_size == ColorArraySize::Entries64 {
+            let (_, ops) = Q565DecodeContext::decode_header(data).unwrap();
+            let mut state = Q565StreamingDecodeContext::new();
+            let mut pixels = vec![0; expected.len()];
+            let split = ops.len() / 2;
+            // SAFETY: the naive decoder accepted the image, so the output is large enough
+            let written = unsafe {
+                let written = state.streaming_decode_to_slice_unchecked::<LittleEndian>(
+                    &ops[..split],
+                    &mut pixels,
+                );
+                written
+                    + state.streaming_decode_to_slice_unchecked::<LittleEndian>(
+                        &ops[split..],
+                        &mut pixels[written..],
+                    )
+            };
+            assert_eq!(written, expected.len());
+            assert_eq!(pixels, expected, "streaming decode: {data:02x?}");
+        }
+    }
+}
+
+#[test]
+fn naive_decoder_roundtrip() {
+    let mut rng = Rng(0x5eed_0001);
+    for _ in 0..200 {
+        let (width, height, pixels) = random_image(&mut rng);
+        let mut encoded = Vec::new();
+        assert!(Q565EncodeContext::encode_auto(width, height, &pixels, &mut encoded).is_some());
+        assert_eq!(naive_decode(&encoded), Some((width, height, pixels)));
+    }
+}
+
+#[test]
+fn encoded_images() {
+    let mut rng = Rng(0x5eed_0002);
+    for _ in 0..200 {
+        let (width, height, pixels) = random_image(&mut rng);
+        for size in SIZES {
+            let mut encoded = Vec::new();
+            assert!(Q565EncodeContext::encode_to_vec_sized(
+                size,
+                width,
+                height,
+                &pixels,
+                &mut encoded
+            )
+            .is_some());
+            assert_eq!(naive_decode(&encoded).unwrap().2, pixels);
+            check(&encoded);
+
+            for _ in 0..4 {
+                let mut mutated = encoded.clone();
+                mutate(&mut rng, &mut mutated);
+                check(&mutated);
+            }
+        }
+    }
+}
+
+#[test]
+fn random_streams() {
+    let mut rng = Rng(0x5eed_0003);
+    let mut valid = 0;
+    for _ in 0..5000 {
+        let mut data = random_stream(&mut rng);
+        if rng.below(4) == 0 {
+            mutate(&mut rng, &mut data);
+        }
+        valid += usize::from(naive_decode(&data).is_some());
+        check(&data);
+    }
+    // make sure the generator doesn't only produce garbage
+    assert!(valid > 1000, "only {valid} valid streams");
+}
+
+#[test]
+fn reference_encoder_random_images() {
+    let mut rng = Rng(0x5eed_0004);
+    for _ in 0..500 {
+        let (width, height, pixels) = random_image(&mut rng);
+        for size in SIZES {
+            let mut encoded = Vec::new();
+            assert!(Q565EncodeContext::encode_to_vec_sized(
+                size,
+                width,
+                height,
+                &pixels,
+                &mut encoded
+            )
+            .is_some());
+            assert_eq!(
+                reference::encode(size, width, height, &pixels),
+                Some(encoded),
+                "{size:?}, {width}x{height}: {pixels:04x?}"
+            );
+        }
+    }
+
+    // long runs, and runs of the initial black pixel
+    let pixels: Vec<u16> = [vec![0; 200], vec![0xF800; 124], vec![0; 63]].concat();
+    for size in SIZES {
+        let mut encoded = Vec::new();
+        assert!(
+            Q565EncodeContext::encode_to_vec_sized(size, 387, 1, &pixels, &mut encoded).is_some()
+        );
+        assert_eq!(reference::encode(size, 387, 1, &pixels), Some(encoded));
+    }
+    assert_eq!(
+        reference::encode(ColorArraySize::Entries64, 2, 2, &[0; 3]),
+        None
+    );
+}
+
+#[test]
+fn reference_encoder_test_images() {
+    for image in std::fs::read_dir("../test_images").unwrap() {
+        let image = image::load(
+            BufReader::new(std::fs::File::open(image.unwrap().path()).unwrap()),
+            image::ImageFormat::Png,
+        )
+        .unwrap();
+        let (width, height) = (image.width() as u16, image.height() as u16);
+        // the reference encoder is slow, so only the first rows of the large images
+        let height = height.min(16);
+        let pixels: Vec<u16> = image
+            .into_rgb8()
+            .pixels()
+            .take(usize::from(width) * usize::from(height))
+            .map(|p| encode_rgb565_unchecked(rgb888_to_rgb565(p.0)))
+            .collect();
+
+        for size in SIZES {
+            let mut encoded = Vec::new();
+            assert!(Q565EncodeContext::encode_to_vec_sized(
+                size,
+                width,
+                height,
+                &pixels,
+                &mut encoded
+            )
+            .is_some());
+            assert!(reference::encode(size, width, height, &pixels) == Some(encoded));
+        }
+    }
+}
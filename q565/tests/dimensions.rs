@@ -0,0 +1,137 @@
+//! Images of up to 65535x65535 pixels, whose pixel count doesn't fit into a 16-bit `usize`, and
+//! whose raw or worst-case encoded size doesn't fit into a 32-bit one.
+
+use q565::{
+    decode::{DecodeError, Decoder, DecoderError, Q565Ref},
+    encode::{encode_fast_rle, Encoder, Q565EncodeContext, StridedPixels},
+    max_stream_len, min_stream_len, pixel_count, ColorArraySize, HeaderInfo,
+};
+
+const MAX_PIXELS: u64 = u16::MAX as u64 * u16::MAX as u64;
+
+fn header(raw: bool) -> HeaderInfo {
+    HeaderInfo {
+        width: u16::MAX,
+        height: u16::MAX,
+        // so that the mini decoder takes it, too
+        color_array_size: if raw {
+            ColorArraySize::Entries64
+        } else {
+            ColorArraySize::NoArray
+        },
+        raw,
+    }
+}
+
+/// A 65535x65535 image that ends after a few runs.
+fn truncated(raw: bool) -> Vec<u8> {
+    let (header, len) = header(raw).to_bytes();
+    let mut data = header[..len].to_vec();
+    data.extend_from_slice(&[0b1111_1101; 4]);
+    if !raw {
+        data.push(0xFF);
+    }
+    data
+}
+
+#[test]
+fn pixel_counts() {
+    assert_eq!(pixel_count(0, u16::MAX), Some(0));
+    assert_eq!(pixel_count(255, 257), Some(65535));
+    #[cfg(target_pointer_width = "16")]
+    assert_eq!(pixel_count(u16::MAX, u16::MAX), None);
+    #[cfg(not(target_pointer_width = "16"))]
+    assert_eq!(
+        header(false).pixel_count(),
+        Some(usize::try_from(MAX_PIXELS).unwrap())
+    );
+}
+
+#[test]
+fn stream_lengths_saturate() {
+    let max_pixels = u128::from(MAX_PIXELS);
+    assert_eq!(
+        min_stream_len(u16::MAX, u16::MAX) as u128,
+        8 + max_pixels.div_ceil(62) + 1
+    );
+
+    let max_len = 9 + 3 * max_pixels + 1;
+    let saturated = |len: u128| usize::try_from(len).unwrap_or(usize::MAX);
+    assert_eq!(max_stream_len(u16::MAX, u16::MAX), saturated(max_len));
+    assert_eq!(
+        q565::encode::fast_rle_max_len(u16::MAX, u16::MAX),
+        saturated(max_len - 1)
+    );
+}
+
+#[test]
+fn decoders_reject_without_overflowing() {
+    for raw in [false, true] {
+        let data = truncated(raw);
+
+        #[cfg(not(feature = "forbid-unsafe"))]
+        {
+            use q565::{
+                byteorder::LittleEndian,
+                decode::{MiniDecoder, Q565DecodeContext, UninitSliceDecodeOutput},
+                Rgb565,
+            };
+            use std::mem::MaybeUninit;
+
+            let mut output = [MaybeUninit::uninit(); 64];
+            assert!(matches!(
+                Q565DecodeContext::decode_to_uninit::<LittleEndian, Rgb565>(&data, &mut output),
+                Err(DecodeError::OutputTooSmall | DecodeError::DimensionsTooLarge)
+            ));
+            assert!(matches!(
+                MiniDecoder::decode::<LittleEndian>(
+                    &data,
+                    UninitSliceDecodeOutput::<Rgb565>::new(&mut output)
+                ),
+                Err(DecodeError::OutputTooSmall | DecodeError::DimensionsTooLarge)
+            ));
+        }
+
+        let expected = if raw {
+            DecodeError::UnexpectedEof
+        } else {
+            DecodeError::MissingData
+        };
+        let error = Q565Ref::new(&data).unwrap_err();
+        assert!(
+            error.as_code() == expected.as_code()
+                || matches!(error, DecodeError::DimensionsTooLarge),
+            "{error:?}"
+        );
+
+        assert!(matches!(
+            Decoder::new().limit_pixels(usize::MAX - 1).header(&data),
+            Ok(_) | Err(DecoderError::TooLarge)
+        ));
+        assert!(matches!(
+            Decoder::new().limit_pixels(1 << 16).header(&data),
+            Err(DecoderError::TooLarge)
+        ));
+    }
+    // the start of a huge image is plausible, one that ends early isn't
+    let data = truncated(false);
+    assert_eq!(q565::stream::resync(&data[..data.len() - 1]), Some(0));
+    assert_eq!(q565::stream::resync(&data), None);
+}
+
+#[test]
+fn encoders_check_pixel_counts_without_overflowing() {
+    let pixels = [0; 16];
+    let mut output = Vec::new();
+    assert!(Q565EncodeContext::encode_to_vec(u16::MAX, u16::MAX, &pixels, &mut output).is_none());
+    assert!(Q565EncodeContext::encode_auto(u16::MAX, u16::MAX, &pixels, &mut output).is_none());
+    assert!(Encoder::new()
+        .encode(u16::MAX, u16::MAX, &pixels, &mut output)
+        .is_none());
+    assert!(encode_fast_rle(u16::MAX, u16::MAX, &pixels, &mut [0; 64]).is_none());
+    assert!(Q565EncodeContext::encode(u16::MAX, u16::MAX, &pixels, Vec::new()).is_err());
+    assert!(output.is_empty());
+
+    assert!(StridedPixels::new(&pixels, 4, u16::MAX, usize::MAX).is_none());
+    assert!(StridedPixels::new(&pixels, 4, 4, 4).is_some());
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{decode, encode, test_pattern};
+use q565::{edit, Rect};
+
+#[test]
+fn concat() {
+    let a = encode(2, 2, &[1, 2, 3, 4]);
+    let b = encode(3, 2, &[5, 6, 7, 8, 9, 10]);
+    let c = encode(2, 1, &[11, 12]);
+
+    assert_eq!(
+        decode(&edit::hconcat(&[&a, &b]).unwrap()),
+        (5, 2, vec![1, 2, 5, 6, 7, 3, 4, 8, 9, 10])
+    );
+    assert_eq!(
+        decode(&edit::vconcat(&[&a, &c]).unwrap()),
+        (2, 3, vec![1, 2, 3, 4, 11, 12])
+    );
+    assert!(matches!(
+        edit::vconcat(&[&a, &b]),
+        Err(edit::EditError::DimensionMismatch)
+    ));
+}
+
+#[test]
+fn slice_into_tiles() {
+    let sheet = encode(3, 3, &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    let tiles: Vec<_> = edit::slice(&sheet, 2, 2)
+        .unwrap()
+        .iter()
+        .map(|tile| decode(tile))
+        .collect();
+
+    assert_eq!(
+        tiles,
+        [
+            (2, 2, vec![1, 2, 4, 5]),
+            (1, 2, vec![3, 6]),
+            (2, 1, vec![7, 8]),
+            (1, 1, vec![9]),
+        ]
+    );
+}
+
+#[test]
+fn patch() {
+    let (width, height) = (40u16, 30u16);
+    let pixels = test_pattern(width, height);
+    let original = encode(width, height, &pixels);
+
+    let rect = Rect {
+        x: 5,
+        y: 12,
+        width: 7,
+        height: 4,
+    };
+    let new_pixels: Vec<u16> = (0..rect.area() as u16).map(|i| i * 1000).collect();
+
+    let mut expected = pixels.clone();
+    for y in 0..4 {
+        for x in 0..7 {
+            expected[(12 + y) * 40 + 5 + x] = new_pixels[y * 7 + x];
+        }
+    }
+
+    let patched = edit::patch(&original, rect, &new_pixels).unwrap();
+    assert_eq!(decode(&patched), (width, height, expected.clone()));
+
+    let patched = edit::patch_incremental(&original, rect, &new_pixels).unwrap();
+    assert_eq!(original[..200], patched[..200]);
+    assert_eq!(decode(&patched), (width, height, expected));
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{encode_ops, long_runs};
+use embassy_futures::{block_on, join::join};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use q565::{byteorder::LittleEndian, embedded::ChannelRowDecoder};
+
+const WIDTH: usize = 50;
+const HEIGHT: usize = 20;
+
+fn test_image() -> (Vec<u16>, Vec<u8>) {
+    // runs crossing row boundaries, some longer than a whole row
+    let pixels = long_runs(WIDTH as u16, HEIGHT as u16);
+    let encoded = encode_ops(WIDTH as u16, HEIGHT as u16, &pixels);
+    (pixels, encoded)
+}
+
+#[test]
+fn decodes_rows_from_channel() {
+    let channel = Channel::<CriticalSectionRawMutex, u8, 8>::new();
+    let (pixels, data) = test_image();
+
+    let send = async {
+        for &byte in &data {
+            channel.send(byte).await;
+        }
+    };
+
+    let receive = async {
+        let mut decoder = ChannelRowDecoder::new(channel.receiver());
+        let mut rows = Vec::new();
+        let mut row = [0u16; WIDTH];
+        loop {
+            let written = decoder.next_row::<LittleEndian>(&mut row).await;
+            if written < WIDTH {
+                assert_eq!(written, 0);
+                break;
+            }
+            rows.extend_from_slice(&row);
+        }
+        assert!(decoder.is_finished());
+        rows
+    };
+
+    let ((), rows) = block_on(join(send, receive));
+    assert_eq!(rows.len(), HEIGHT * WIDTH);
+    assert_eq!(rows, pixels);
+}
+
+#[test]
+fn bytes_after_the_end_marker_stay_in_the_channel() {
+    let channel = Channel::<CriticalSectionRawMutex, u8, 8>::new();
+    // a run of 3 pixels, the end marker, and the start of the next image
+    for byte in [0xC2, 0xFF, 0xC0] {
+        channel.try_send(byte).unwrap();
+    }
+
+    let mut decoder = ChannelRowDecoder::new(channel.receiver());
+    let mut row = [1u16; 2];
+    assert_eq!(block_on(decoder.next_row::<LittleEndian>(&mut row)), 2);
+    assert_eq!(block_on(decoder.next_row::<LittleEndian>(&mut row)), 1);
+    assert_eq!(row[0], 0);
+    assert!(decoder.is_finished());
+    assert_eq!(channel.len(), 1);
+
+    decoder.reset();
+    assert!(!decoder.is_finished());
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use common::{load_rgb888, to_rgb565};
+use q565::{
+    byteorder::{BigEndian, LittleEndian, NativeEndian},
+    decode::Q565DecodeContext,
+    encode::{encode_fast_rle, fast_rle_max_len, Dither, Encoder, Q565EncodeContext, Speed},
+    pipeline,
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565_with, Quantization},
+    ColorArraySize, Rgb565,
+};
+
+#[test]
+fn presets_match_low_level_functions() {
+    let (width, height, rgb888) = load_rgb888("testcard.png");
+    let pixels = to_rgb565(&rgb888);
+
+    let mut fast = vec![0; fast_rle_max_len(width, height)];
+    let report = encode_fast_rle(width, height, &pixels, &mut fast).unwrap();
+    fast.truncate(report.bytes_written);
+    let mut encoded = Vec::new();
+    Encoder::new()
+        .byte_order::<NativeEndian>()
+        .speed(Speed::Fast)
+        .encode(width, height, &pixels, &mut encoded)
+        .unwrap();
+    assert_eq!(encoded, fast);
+
+    let mut regular = Vec::new();
+    Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::Entries32,
+        width,
+        height,
+        &pixels,
+        &mut regular,
+    )
+    .unwrap();
+    let mut encoded = Vec::new();
+    let report = Encoder::new()
+        .byte_order::<NativeEndian>()
+        .color_array_size(ColorArraySize::Entries32)
+        .encode(width, height, &pixels, &mut encoded)
+        .unwrap();
+    assert_eq!(encoded, regular);
+    assert_eq!(report.bytes_written, encoded.len());
+
+    let mut best = Vec::new();
+    let report = Encoder::new()
+        .byte_order::<NativeEndian>()
+        .speed(Speed::Best)
+        .encode(width, height, &pixels, &mut best)
+        .unwrap();
+    assert_eq!(report.bytes_written, best.len());
+    assert!(best.len() <= regular.len());
+    assert!(best.len() <= fast.len());
+    assert_eq!(
+        Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(&best)
+            .unwrap()
+            .1,
+        pixels
+    );
+}
+
+#[test]
+fn byte_order_of_the_input() {
+    let (width, height, rgb888) = load_rgb888("qoi_logo.png");
+    let pixels = to_rgb565(&rgb888);
+
+    let mut native = Vec::new();
+    Encoder::new()
+        .byte_order::<NativeEndian>()
+        .encode(width, height, &pixels, &mut native)
+        .unwrap();
+
+    for speed in [Speed::Fast, Speed::Default, Speed::Best] {
+        let encoder = Encoder::new().byte_order::<NativeEndian>().speed(speed);
+        let mut expected = Vec::new();
+        encoder
+            .encode(width, height, &pixels, &mut expected)
+            .unwrap();
+
+        let big_endian: Vec<u16> = pixels
+            .iter()
+            .map(|p| u16::from_ne_bytes(p.to_be_bytes()))
+            .collect();
+        let mut encoded = Vec::new();
+        encoder
+            .byte_order::<BigEndian>()
+            .encode(width, height, &big_endian, &mut encoded)
+            .unwrap();
+        assert_eq!(encoded, expected, "{speed:?}");
+
+        let little_endian: Vec<u16> = pixels
+            .iter()
+            .map(|p| u16::from_ne_bytes(p.to_le_bytes()))
+            .collect();
+        encoded.clear();
+        encoder
+            .byte_order::<LittleEndian>()
+            .encode(width, height, &little_endian, &mut encoded)
+            .unwrap();
+        assert_eq!(encoded, expected, "{speed:?}");
+    }
+}
+
+#[test]
+fn rgb888_with_dithering() {
+    let (width, height, rgb888) = load_rgb888("testcard.png");
+
+    let mut expected = Vec::new();
+    Encoder::new()
+        .byte_order::<NativeEndian>()
+        .encode(width, height, &to_rgb565(&rgb888), &mut expected)
+        .unwrap();
+    let mut encoded = Vec::new();
+    Encoder::new()
+        .encode_rgb888(width, height, &rgb888, &mut encoded)
+        .unwrap();
+    assert_eq!(encoded, expected);
+
+    let dithered: Vec<u16> = rgb888
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| pipeline::dither(p, i % usize::from(width), i / usize::from(width)))
+        .collect();
+    expected.clear();
+    Encoder::new()
+        .byte_order::<NativeEndian>()
+        .encode(width, height, &dithered, &mut expected)
+        .unwrap();
+    encoded.clear();
+    Encoder::new()
+        .dither(Dither::Ordered)
+        .encode_rgb888(width, height, &rgb888, &mut encoded)
+        .unwrap();
+    assert_eq!(encoded, expected);
+}
+
+#[test]
+fn rgb888_with_quantization() {
+    let (width, height, rgb888) = load_rgb888("testcard.png");
+
+    for quantization in [Quantization::Truncate, Quantization::Luma] {
+        let rgb565: Vec<u16> = rgb888
+            .iter()
+            .map(|&p| encode_rgb565_unchecked(rgb888_to_rgb565_with(p, quantization)))
+            .collect();
+        let mut expected = Vec::new();
+        Encoder::new()
+            .byte_order::<NativeEndian>()
+            .encode(width, height, &rgb565, &mut expected)
+            .unwrap();
+        let mut encoded = Vec::new();
+        Encoder::new()
+            .quantization(quantization)
+            .encode_rgb888(width, height, &rgb888, &mut encoded)
+            .unwrap();
+        assert_eq!(encoded, expected, "{quantization:?}");
+    }
+}
+
+#[test]
+fn options_from_strings() {
+    assert_eq!("fast".parse(), Ok(Speed::Fast));
+    assert_eq!("Default".parse(), Ok(Speed::Default));
+    assert_eq!("BEST".parse(), Ok(Speed::Best));
+    assert!("slow".parse::<Speed>().is_err());
+    assert_eq!("none".parse(), Ok(Dither::None));
+    assert_eq!("ordered".parse(), Ok(Dither::Ordered));
+    assert!("random".parse::<Dither>().is_err());
+}
+
+#[test]
+fn checks_dimensions() {
+    for speed in [Speed::Fast, Speed::Default, Speed::Best] {
+        let mut encoded = Vec::new();
+        assert!(Encoder::new()
+            .speed(speed)
+            .encode(4, 4, &[0; 15], &mut encoded)
+            .is_none());
+        assert!(Encoder::new()
+            .speed(speed)
+            .encode_rgb888(4, 4, &[[0; 3]; 17], &mut encoded)
+            .is_none());
+    }
+}
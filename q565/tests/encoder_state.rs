@@ -0,0 +1,77 @@
+mod common;
+
+use q565::{
+    byteorder::LittleEndian,
+    decode::{Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    utils::decode_565,
+    Rgb565,
+};
+
+/// Noise with mostly small steps, for a mix of all ops.
+fn noise(len: usize) -> Vec<u16> {
+    let mut pixels = common::noise(len);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        if i % 4 != 0 {
+            *pixel &= 0x18E3;
+        }
+    }
+    pixels
+}
+
+#[test]
+fn encoder_continues_from_decoder_state() {
+    let pixels = noise(4096);
+    let (first, second) = pixels.split_at(2048);
+
+    let mut encoder = Q565EncodeContext::new();
+    let mut encoded = Vec::new();
+    assert!(encoder
+        .encode_to_vec_with_state(64, 32, first, &mut encoded)
+        .is_some());
+
+    let mut decoder = Q565DecodeContext::new();
+    let mut decoded = Vec::new();
+    decoder
+        .decode_with_state::<LittleEndian>(&encoded, VecDecodeOutput::<Rgb565>::new(&mut decoded))
+        .unwrap();
+    assert_eq!(decoded, first);
+
+    // the rest of the image is encoded the same, including the ops referencing the color array
+    let mut expected = Vec::new();
+    let report = encoder.encode_pixels_to_vec(second, &mut expected);
+    assert!(report.ops.diff_indexed > 0 && report.ops.index > 0);
+
+    let mut continued = Vec::new();
+    Q565EncodeContext::from(decoder).encode_pixels_to_vec(second, &mut continued);
+    assert_eq!(continued, expected);
+}
+
+#[test]
+fn color_array_entries_update_the_index() {
+    let pixels = noise(4096);
+    let (first, second) = pixels.split_at(2048);
+
+    let mut encoder = Q565EncodeContext::new();
+    let mut encoded = Vec::new();
+    assert!(encoder
+        .encode_to_vec_with_state(64, 32, first, &mut encoded)
+        .is_some());
+
+    // the same state, set entry by entry
+    let mut copy = Q565EncodeContext::new();
+    copy.prev = encoder.prev;
+    copy.prev_components = decode_565(encoder.prev);
+    for (index, &pixel) in encoder.color_array().iter().enumerate() {
+        copy.set_color_array_entry(index, pixel);
+    }
+    assert_eq!(copy.color_array(), encoder.color_array());
+
+    let mut expected = Vec::new();
+    let report = encoder.encode_pixels_to_vec(second, &mut expected);
+    assert!(report.ops.diff_indexed > 0);
+
+    let mut continued = Vec::new();
+    copy.encode_pixels_to_vec(second, &mut continued);
+    assert_eq!(continued, expected);
+}
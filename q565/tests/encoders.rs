@@ -0,0 +1,135 @@
+mod common;
+
+use common::load;
+use q565::{
+    byteorder::NativeEndian,
+    decode::Q565DecodeContext,
+    encode::{
+        encode_fast_rle, fast_rle_max_len, FastRleEncoder, PixelSource,
+        Q565CompactStreamingEncodeContext, Q565EncodeContext, Q565Encoder,
+        Q565StreamingEncodeContext, StridedPixels,
+    },
+    reference::{self, ReferenceEncoder},
+    ColorArraySize, Rgb565,
+};
+
+/// All encoders, with the output each one has to match.
+fn encoders(
+    width: u16,
+    height: u16,
+    pixels: &[u16],
+) -> Vec<(&'static str, Box<dyn Q565Encoder>, Vec<u8>)> {
+    let regular = |size| {
+        let mut output = Vec::new();
+        Q565EncodeContext::encode_to_vec_sized(size, width, height, pixels, &mut output).unwrap();
+        output
+    };
+    let mut fast_rle = vec![0; fast_rle_max_len(width, height)];
+    let len = encode_fast_rle(width, height, pixels, &mut fast_rle)
+        .unwrap()
+        .bytes_written;
+    fast_rle.truncate(len);
+
+    vec![
+        (
+            "streaming",
+            Box::new(Q565StreamingEncodeContext::new()),
+            regular(ColorArraySize::Entries64),
+        ),
+        (
+            "streaming 16",
+            Box::new(Q565StreamingEncodeContext::<16>::new_sized()),
+            regular(ColorArraySize::Entries16),
+        ),
+        (
+            "compact streaming 32",
+            Box::new(Q565CompactStreamingEncodeContext::<32>::new_compact_sized()),
+            regular(ColorArraySize::Entries32),
+        ),
+        ("fast rle", Box::new(FastRleEncoder::new()), fast_rle),
+        (
+            "reference",
+            Box::new(ReferenceEncoder::new(ColorArraySize::Entries64)),
+            reference::encode(ColorArraySize::Entries64, width, height, pixels).unwrap(),
+        ),
+        (
+            "reference no array",
+            Box::new(ReferenceEncoder::new(ColorArraySize::NoArray)),
+            regular(ColorArraySize::NoArray),
+        ),
+    ]
+}
+
+#[test]
+fn encoder_matrix() {
+    for name in ["qoi_logo.png", "testcard.png", "edgecase.png"] {
+        let (width, height, pixels) = load(name);
+
+        for (encoder_name, mut encoder, expected) in encoders(width, height, &pixels) {
+            // reused for a second image, pushing the pixels in uneven chunks
+            for chunk_len in [pixels.len(), 1000] {
+                let mut output = Vec::new();
+                encoder.encode_header(width, height, &mut output);
+                for chunk in pixels.chunks(chunk_len) {
+                    encoder.push_pixels(chunk, &mut output);
+                }
+                encoder.finish(&mut output);
+                assert!(output == expected, "{encoder_name}, {name}");
+
+                let (header, decoded) =
+                    Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(&output).unwrap();
+                assert_eq!((header.width, header.height), (width, height));
+                assert!(decoded == pixels, "{encoder_name}, {name}");
+            }
+        }
+    }
+}
+
+#[test]
+fn encode_checks_dimensions() {
+    let mut encoder = FastRleEncoder::new();
+    let mut output = Vec::new();
+    assert_eq!(encoder.encode(2, 2, &[0; 3], &mut output), None);
+    assert!(output.is_empty());
+
+    let len = encoder.encode(2, 2, &[0; 4], &mut output).unwrap();
+    assert_eq!(len, output.len());
+}
+
+#[test]
+fn strided_sources() {
+    let (width, height, pixels) = load("qoi_logo.png");
+    let (w, stride) = (usize::from(width), usize::from(width) + 3);
+
+    // padding after every row but the last, and the same rows stored bottom-up
+    let mut padded = Vec::new();
+    for row in pixels.chunks(w) {
+        padded.extend_from_slice(row);
+        padded.extend_from_slice(&[0xDEAD; 3]);
+    }
+    padded.truncate(padded.len() - 3);
+    let mut flipped = Vec::new();
+    for row in padded.chunks(stride).rev() {
+        flipped.extend_from_slice(&row[..w]);
+        flipped.extend_from_slice(&[0xBEEF; 3]);
+    }
+
+    let padded = StridedPixels::new(&padded, width, height, stride).unwrap();
+    let flipped = StridedPixels::new(&flipped, width, height, stride)
+        .unwrap()
+        .bottom_up();
+    assert!(padded.rows().flatten().eq(&pixels));
+    assert!(flipped.rows().flatten().eq(&pixels));
+
+    for (encoder_name, mut encoder, expected) in encoders(width, height, &pixels) {
+        for source in [&padded, &flipped] {
+            let mut output = Vec::new();
+            let len = encoder.encode_source(source, &mut output);
+            assert_eq!(len, output.len());
+            assert!(output == expected, "{encoder_name}");
+        }
+    }
+
+    assert!(StridedPixels::new(&pixels, width, height, w - 1).is_none());
+    assert!(StridedPixels::new(&pixels[1..], width, height, w).is_none());
+}
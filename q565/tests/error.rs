@@ -0,0 +1,77 @@
+use q565::{
+    byteorder::NativeEndian,
+    decode::{DecodeError, Decoder, DecoderError, Q565DecodeContext},
+    encode::{EncodeError, Q565EncodeContext},
+    error::{Error, ErrorKind},
+    Rgb565,
+};
+use std::error::Error as _;
+
+#[test]
+fn kinds_of_decode_errors() {
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(4, 4, &[0x1234; 16], &mut encoded).unwrap();
+
+    let error: Error =
+        Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(&encoded[..encoded.len() - 1])
+            .unwrap_err()
+            .into();
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    assert!(matches!(
+        error.decode_error(),
+        Some(DecodeError::UnexpectedEof)
+    ));
+    assert_eq!(error.to_string(), DecodeError::UnexpectedEof.to_string());
+
+    // errors of the decoder builder take the kind of the wrapped error
+    let error: Error = Decoder::new()
+        .decode_to_vec(b"q566 not an image")
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), ErrorKind::InvalidMagic);
+    assert!(error.decode_error().is_some());
+    assert!(matches!(
+        error.decoder_error(),
+        Some(DecoderError::Decode { .. })
+    ));
+    assert_eq!(error.to_string(), DecodeError::InvalidMagic.to_string());
+
+    let error: Error = Decoder::new()
+        .limit_pixels(15)
+        .decode_to_vec(&encoded)
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), ErrorKind::TooLarge);
+    assert!(error.decode_error().is_none());
+}
+
+#[test]
+fn kinds_of_encode_errors() {
+    let error: Error = Q565EncodeContext::encode(4, 4, &[0; 15], Vec::new())
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), ErrorKind::InvalidDimensions);
+    assert!(matches!(
+        error.encode_error(),
+        Some(EncodeError::InvalidDimensions { .. })
+    ));
+
+    struct Failing;
+    impl std::io::Write for Failing {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let error: Error = Q565EncodeContext::encode(4, 4, &[0; 16], Failing)
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), ErrorKind::Io);
+    let source = error.source().unwrap();
+    assert_eq!(
+        source.downcast_ref::<std::io::Error>().unwrap().kind(),
+        std::io::ErrorKind::BrokenPipe
+    );
+}
@@ -0,0 +1,52 @@
+use q565::{
+    bundle::BundleError, decode::DecodeError, pipeline::PipelineError, update::UpdateError,
+};
+
+#[test]
+fn codes_are_stable() {
+    // telemetry decoded by older hosts depends on these never changing
+    let codes = [
+        (DecodeError::OutputTooSmall, 1),
+        (DecodeError::UnexpectedEof, 2),
+        (DecodeError::InvalidMagic, 3),
+        (DecodeError::UnsupportedFlags, 4),
+        (DecodeError::ColorArrayTooSmall, 5),
+        (DecodeError::MissingData, 6),
+        (DecodeError::TooManyPixels, 7),
+        (DecodeError::DimensionsTooLarge, 8),
+    ];
+    for (error, code) in codes {
+        assert_eq!(error.as_code(), code);
+    }
+    assert_eq!(BundleError::CorruptEntry { index: 3 }.as_code(), 9);
+}
+
+#[test]
+fn codes_round_trip() {
+    for code in 0..=255 {
+        let error = DecodeError::from_code(code);
+        assert_eq!(
+            error.as_ref().map(DecodeError::as_code),
+            (1..=8).contains(&code).then_some(code)
+        );
+        assert_eq!(
+            DecodeError::message_for_code(code),
+            error.map(|error| error.to_string()).as_deref()
+        );
+
+        if let Some(error) = PipelineError::from_code(code) {
+            assert_eq!(error.as_code(), code);
+        }
+    }
+
+    // variants with fields only have a message
+    let decode = UpdateError::Decode {
+        source: DecodeError::MissingData,
+    };
+    assert!(UpdateError::from_code(decode.as_code()).is_none());
+    assert_eq!(
+        UpdateError::message_for_code(decode.as_code()),
+        Some(decode.to_string().as_str())
+    );
+    assert!(UpdateError::message_for_code(0).is_none());
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use common::{encode, test_pattern};
+use q565::{
+    byteorder::{Endianness, LittleEndian},
+    decode::{DecodeError, FallibleDecodeError, FallibleDecodeOutput, Q565DecodeContext},
+    Rgb565,
+};
+
+/// A bounded ring buffer that fails once it holds `capacity` pixels, like a display FIFO.
+struct Fifo {
+    pixels: Vec<u16>,
+    capacity: usize,
+    writes: usize,
+}
+
+#[derive(Debug, PartialEq)]
+struct FifoFull {
+    at: usize,
+}
+
+impl Fifo {
+    fn new(capacity: usize) -> Self {
+        Self {
+            pixels: Vec::new(),
+            capacity,
+            writes: 0,
+        }
+    }
+}
+
+impl FallibleDecodeOutput for Fifo {
+    type Error = FifoFull;
+
+    fn write_pixel<B: Endianness>(&mut self, color: u16) -> Result<(), FifoFull> {
+        self.write_many_pixels::<B>(color, 1)
+    }
+
+    fn write_many_pixels<B: Endianness>(
+        &mut self,
+        color: u16,
+        count: usize,
+    ) -> Result<(), FifoFull> {
+        self.writes += 1;
+        let fits = count.min(self.capacity - self.pixels.len());
+        self.pixels.extend(std::iter::repeat_n(color, fits));
+        if fits < count {
+            return Err(FifoFull {
+                at: self.pixels.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn current_output_position(&self) -> usize {
+        self.pixels.len()
+    }
+}
+
+#[test]
+fn decodes_like_the_infallible_decoder() {
+    let pixels = test_pattern(32, 24);
+    let encoded = encode(32, 24, &pixels);
+
+    let mut fifo = Fifo::new(pixels.len());
+    let (header, written) =
+        Q565DecodeContext::decode_fallible::<LittleEndian, _>(&encoded, &mut fifo).unwrap();
+    assert_eq!((header.width, header.height), (32, 24));
+    assert_eq!(written, pixels.len());
+    assert_eq!(fifo.pixels, pixels);
+
+    let (_, expected) = Q565DecodeContext::decode_to_vec::<LittleEndian, Rgb565>(&encoded).unwrap();
+    assert_eq!(fifo.pixels, expected);
+}
+
+#[test]
+fn stops_at_the_first_failed_write() {
+    let pixels = test_pattern(32, 24);
+    let encoded = encode(32, 24, &pixels);
+
+    for capacity in [0, 1, 100, pixels.len() - 1] {
+        let mut fifo = Fifo::new(capacity);
+        let result = Q565DecodeContext::decode_fallible::<LittleEndian, _>(&encoded, &mut fifo);
+        assert!(matches!(
+            result,
+            Err(FallibleDecodeError::Output {
+                source: FifoFull { at }
+            }) if at == capacity
+        ));
+
+        // the pixels before the failure are written, and nothing is written after it
+        assert_eq!(fifo.pixels, pixels[..capacity]);
+        let mut retry = Fifo::new(capacity);
+        let _ = Q565DecodeContext::decode_fallible::<LittleEndian, _>(&encoded, &mut retry);
+        assert_eq!(fifo.writes, retry.writes);
+        assert!(fifo.writes < pixels.len());
+    }
+}
+
+#[test]
+fn raw_images() {
+    let pixels = test_pattern(8, 8);
+    let header = q565::HeaderInfo {
+        width: 8,
+        height: 8,
+        color_array_size: q565::ColorArraySize::Entries64,
+        raw: true,
+    };
+    let (header, header_len) = header.to_bytes();
+    let mut encoded = header[..header_len].to_vec();
+    encoded.extend(pixels.iter().flat_map(|p| p.to_le_bytes()));
+
+    let mut fifo = Fifo::new(64);
+    Q565DecodeContext::decode_fallible::<LittleEndian, _>(&encoded, &mut fifo).unwrap();
+    assert_eq!(fifo.pixels, pixels);
+
+    let mut fifo = Fifo::new(10);
+    assert!(matches!(
+        Q565DecodeContext::decode_fallible::<LittleEndian, _>(&encoded, &mut fifo),
+        Err(FallibleDecodeError::Output {
+            source: FifoFull { at: 10 }
+        })
+    ));
+}
+
+#[test]
+fn invalid_images_are_decode_errors() {
+    let mut fifo = Fifo::new(100);
+    let error = Q565DecodeContext::decode_fallible::<LittleEndian, _>(b"not an image", &mut fifo)
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        FallibleDecodeError::Decode {
+            source: DecodeError::InvalidMagic
+        }
+    ));
+    assert_eq!(error.as_code(), 1);
+    assert_eq!(
+        error.to_string(),
+        FallibleDecodeError::<FifoFull>::message_for_code(1).unwrap()
+    );
+
+    // the state continues like with the infallible decoder
+    let pixels = test_pattern(8, 8);
+    let encoded = encode(8, 8, &pixels);
+    let mut fallible = Q565DecodeContext::new();
+    fallible
+        .decode_fallible_with_state::<LittleEndian, _>(&encoded, Fifo::new(64))
+        .unwrap();
+    let mut infallible = Q565DecodeContext::new();
+    infallible
+        .decode_with_state::<LittleEndian>(
+            &encoded,
+            q565::decode::VecDecodeOutput::<Rgb565>::new(&mut Vec::new()),
+        )
+        .unwrap();
+    assert_eq!(
+        (fallible.prev, fallible.arr),
+        (infallible.prev, infallible.arr)
+    );
+}
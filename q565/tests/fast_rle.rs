@@ -0,0 +1,53 @@
+use image::ImageFormat;
+use q565::{
+    byteorder::LittleEndian,
+    decode::Q565DecodeContext,
+    encode::{encode_fast_rle, fast_rle_max_len},
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    Rgb565,
+};
+use std::io::BufReader;
+
+#[test]
+fn fast_rle_roundtrip() {
+    for image in std::fs::read_dir("../test_images").unwrap() {
+        let image = image::load(
+            BufReader::new(std::fs::File::open(image.unwrap().path()).unwrap()),
+            ImageFormat::Png,
+        )
+        .unwrap();
+        let (width, height) = (image.width() as u16, image.height() as u16);
+        let pixels: Vec<u16> = image
+            .into_rgb8()
+            .pixels()
+            .map(|p| encode_rgb565_unchecked(rgb888_to_rgb565(p.0)))
+            .collect();
+
+        let mut output = vec![0u8; fast_rle_max_len(width, height)];
+        let report = encode_fast_rle(width, height, &pixels, &mut output).unwrap();
+        let encoded = &output[..report.bytes_written];
+        assert_eq!(report.ops.total(), report.ops.run + report.ops.rgb565);
+
+        let (_, decoded) =
+            Q565DecodeContext::decode_to_vec::<LittleEndian, Rgb565>(encoded).unwrap();
+        assert_eq!(decoded, pixels);
+    }
+}
+
+#[test]
+fn fast_rle_flat_frame() {
+    let mut pixels = vec![0u16; 320 * 240];
+    pixels[320 * 100..320 * 140].fill(0xF800);
+
+    let mut output = vec![0u8; fast_rle_max_len(320, 240)];
+    let report = encode_fast_rle(320, 240, &pixels, &mut output).unwrap();
+    assert_eq!(report.ops.rgb565, 2);
+
+    assert!(report.ratio < 0.01);
+
+    // an output that fits exactly is enough, a smaller one isn't
+    let len = report.bytes_written;
+    assert!(encode_fast_rle(320, 240, &pixels, &mut output[..len]).is_some());
+    assert!(encode_fast_rle(320, 240, &pixels, &mut output[..len - 1]).is_none());
+    assert!(encode_fast_rle(320, 239, &pixels, &mut output).is_none());
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::{encode, long_runs, noise};
+use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+use q565::{
+    bundle::{write_bundle, AsyncFlashBundle, FlashBundle, FlashBundleError},
+    byteorder::LittleEndian,
+    decode::DecodeError,
+    encode::Q565EncodeContext,
+    ColorArraySize,
+};
+
+/// SPI NOR flash, as far as reading is concerned: reads need to be aligned to 4 bytes.
+struct Flash {
+    data: Vec<u8>,
+    reads: usize,
+}
+
+impl ErrorType for Flash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for Flash {
+    const READ_SIZE: usize = 4;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        if !offset.is_multiple_of(4) || !bytes.len().is_multiple_of(4) {
+            return Err(NorFlashErrorKind::NotAligned);
+        }
+        let data = self
+            .data
+            .get(offset..offset + bytes.len())
+            .ok_or(NorFlashErrorKind::OutOfBounds)?;
+        bytes.copy_from_slice(data);
+        self.reads += 1;
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl embedded_storage_async::nor_flash::ReadNorFlash for Flash {
+    const READ_SIZE: usize = 4;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        ReadNorFlash::read(self, offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// A bundle at an unaligned offset, followed by erased flash.
+fn flash_with_bundle(entries: &[&[u8]]) -> (Flash, u32) {
+    let mut data = vec![0xFF; 3];
+    data.extend_from_slice(&write_bundle(entries).unwrap());
+    data.resize(data.len().next_multiple_of(4) + 64, 0xFF);
+    (Flash { data, reads: 0 }, 3)
+}
+
+fn decode_entry(
+    bundle: &mut FlashBundle<Flash>,
+    index: usize,
+) -> Result<Vec<u16>, FlashBundleError> {
+    let mut pixels = Vec::new();
+    bundle.decode_entry::<LittleEndian>(index, |chunk: &[u16]| {
+        assert!(chunk.len() <= 128);
+        pixels.extend_from_slice(chunk)
+    })?;
+    Ok(pixels)
+}
+
+#[test]
+fn decodes_entries_from_flash() {
+    let pixels = long_runs(40, 30);
+    let encoded = encode(40, 30, &pixels);
+    let noise = noise(7 * 5);
+    let mut raw = Vec::new();
+    Q565EncodeContext::encode_auto(7, 5, &noise, &mut raw).unwrap();
+
+    let (flash, base) = flash_with_bundle(&[&encoded, b"not an image", &raw]);
+    let mut bundle = FlashBundle::new(flash, base).unwrap();
+    assert_eq!(bundle.len(), 3);
+
+    assert_eq!(decode_entry(&mut bundle, 0).unwrap(), pixels);
+    assert_eq!(decode_entry(&mut bundle, 2).unwrap(), noise);
+    assert!(matches!(
+        decode_entry(&mut bundle, 1),
+        Err(FlashBundleError::Decode {
+            source: DecodeError::InvalidMagic
+        })
+    ));
+    assert!(matches!(
+        bundle.entry(3),
+        Err(FlashBundleError::Bundle { .. })
+    ));
+
+    let entry = bundle.entry(1).unwrap();
+    assert_eq!(entry.length, 12);
+    let mut bytes = [0; 5];
+    bundle.read(entry, 4, &mut bytes).unwrap();
+    assert_eq!(&bytes, b"an im");
+    assert!(bundle.read(entry, 8, &mut bytes).is_err());
+
+    // read in chunks, never as a whole
+    assert!(bundle.into_inner().reads > encoded.len() / 64);
+}
+
+#[test]
+fn rejects_unsupported_profiles() {
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::Entries16,
+        40,
+        30,
+        &long_runs(40, 30),
+        &mut encoded,
+    )
+    .unwrap();
+
+    let (flash, base) = flash_with_bundle(&[&encoded]);
+    let mut bundle = FlashBundle::new(flash, base).unwrap();
+    assert!(matches!(
+        decode_entry(&mut bundle, 0),
+        Err(FlashBundleError::Decode {
+            source: DecodeError::UnsupportedFlags
+        })
+    ));
+}
+
+#[test]
+fn reads_through_async_traits() {
+    let pixels = long_runs(40, 30);
+    let encoded = encode(40, 30, &pixels);
+    let (flash, base) = flash_with_bundle(&[b"first", &encoded]);
+
+    let decoded = embassy_futures::block_on(async {
+        let mut bundle = AsyncFlashBundle::new(flash, base).await.unwrap();
+        assert_eq!(bundle.len(), 2);
+
+        let mut decoded = Vec::new();
+        let header = bundle
+            .decode_entry::<LittleEndian>(1, |chunk: &[u16]| decoded.extend_from_slice(chunk))
+            .await
+            .unwrap();
+        assert_eq!((header.width, header.height), (40, 30));
+        decoded
+    });
+    assert_eq!(decoded, pixels);
+}
@@ -0,0 +1,142 @@
+use q565::{
+    byteorder::LittleEndian,
+    decode::{DecodeError, Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    Rgb565, REPEAT_FRAME,
+};
+
+const WIDTH: u16 = 64;
+const HEIGHT: u16 = 48;
+
+/// UI-like frame: a few flat panels in fixed colors, and a progress bar that grows every frame.
+fn ui_frame(frame: usize) -> Vec<u16> {
+    (0..usize::from(WIDTH) * usize::from(HEIGHT))
+        .map(|i| {
+            let (x, y) = (i % usize::from(WIDTH), i / usize::from(WIDTH));
+            match y {
+                0..8 => 0x18E3 + (x as u16 & 0b111),
+                20..24 if x < frame * 6 => 0x07E0 - (x as u16 & 0b11),
+                40.. => 0xA514 ^ ((x / 8) as u16 * 0x0841),
+                _ => 0xFFFF,
+            }
+        })
+        .collect()
+}
+
+fn decode_frame(decoder: &mut Q565DecodeContext, data: &[u8]) -> Result<Vec<u16>, DecodeError> {
+    let mut pixels = Vec::new();
+    decoder.decode_frame::<LittleEndian>(data, VecDecodeOutput::<Rgb565>::new(&mut pixels))?;
+    Ok(pixels)
+}
+
+#[test]
+fn frame_sequence_stays_in_sync() {
+    let mut encoder = Q565EncodeContext::new();
+    let mut decoder = Q565DecodeContext::new();
+
+    let mut sequence_len = 0;
+    let mut standalone_len = 0;
+    for frame in 0..8 {
+        let pixels = ui_frame(frame);
+
+        let mut encoded = Vec::new();
+        assert!(encoder
+            .encode_frame(WIDTH, HEIGHT, &pixels, &mut encoded)
+            .is_some());
+        assert_eq!(decode_frame(&mut decoder, &encoded).unwrap(), pixels);
+        assert_eq!(
+            (decoder.prev, decoder.arr),
+            (encoder.prev, *encoder.color_array()),
+            "frame {frame}"
+        );
+
+        let mut standalone = Vec::new();
+        assert!(
+            Q565EncodeContext::encode_to_vec(WIDTH, HEIGHT, &pixels, &mut standalone).is_some()
+        );
+        sequence_len += encoded.len();
+        standalone_len += standalone.len();
+    }
+
+    // the colors of the previous frames are already in the color array
+    assert!(sequence_len < standalone_len);
+}
+
+#[test]
+fn frames_depend_on_previous_frames() {
+    let mut encoder = Q565EncodeContext::new();
+    let mut frames = Vec::new();
+    for frame in 0..2 {
+        let mut encoded = Vec::new();
+        assert!(encoder
+            .encode_frame(WIDTH, HEIGHT, &ui_frame(frame), &mut encoded)
+            .is_some());
+        frames.push(encoded);
+    }
+
+    // skipping the first frame leaves the decoder with the wrong color array
+    let mut decoder = Q565DecodeContext::new();
+    assert_ne!(
+        decode_frame(&mut decoder, &frames[1]).ok(),
+        Some(ui_frame(1))
+    );
+
+    // raw frames don't carry state
+    let mut raw = Vec::new();
+    let (header, _) = Q565DecodeContext::decode_header(&frames[0]).unwrap();
+    let header = q565::HeaderInfo {
+        raw: true,
+        ..header
+    };
+    let (header, header_len) = header.to_bytes();
+    raw.extend_from_slice(&header[..header_len]);
+    raw.extend(ui_frame(0).iter().flat_map(|p| p.to_le_bytes()));
+    assert!(matches!(
+        decode_frame(&mut Q565DecodeContext::new(), &raw),
+        Err(DecodeError::UnsupportedFlags)
+    ));
+}
+
+#[test]
+fn unchanged_frames_are_repeated() {
+    let mut encoder = Q565EncodeContext::new();
+    let mut decoder = Q565DecodeContext::new();
+
+    let mut previous = vec![0; usize::from(WIDTH) * usize::from(HEIGHT)];
+    let mut shown = Vec::new();
+    for frame in [0, 1, 1, 1, 2, 2, 3] {
+        let pixels = ui_frame(frame);
+        let mut encoded = Vec::new();
+        let report = encoder
+            .encode_frame_or_repeat(WIDTH, HEIGHT, &pixels, &previous, &mut encoded)
+            .unwrap();
+        assert_eq!(report.bytes_written, encoded.len());
+        assert_eq!(encoded == [REPEAT_FRAME], pixels == previous);
+
+        let mut decoded = Vec::new();
+        let result = decoder
+            .decode_frame_or_repeat::<LittleEndian>(
+                &encoded,
+                VecDecodeOutput::<Rgb565>::new(&mut decoded),
+            )
+            .unwrap();
+        match result {
+            Some((header, written)) => {
+                assert_eq!((header.width, header.height), (WIDTH, HEIGHT));
+                assert_eq!(written, pixels.len());
+                shown = decoded;
+            }
+            None => assert!(decoded.is_empty()),
+        }
+        assert_eq!(shown, pixels);
+        assert_eq!(
+            (decoder.prev, decoder.arr),
+            (encoder.prev, *encoder.color_array())
+        );
+
+        previous = pixels;
+    }
+
+    // a repeated frame isn't an image
+    assert!(decode_frame(&mut decoder, &[REPEAT_FRAME]).is_err());
+}
@@ -0,0 +1,114 @@
+//! Locks the output of [`EncoderVersion::V1`] for the test corpus. These hashes must never change;
+//! heuristic changes belong in a new encoder version.
+
+use image::ImageFormat;
+use q565::{
+    encode::{EncoderVersion, Q565EncodeContext},
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    ColorArraySize,
+};
+use std::io::BufReader;
+
+/// FNV-1a, 64 bit.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Image, color array entries, encoded length, hash of the encoded image.
+const GOLDEN_V1: &[(&str, usize, usize, u64)] = &[
+    ("dice.png", 64, 127007, 0xf5fac8d307add40d),
+    ("dice.png", 32, 130248, 0xd145582c0cf30227),
+    ("dice.png", 16, 132354, 0x73301a25770ce49f),
+    ("dice.png", 0, 134732, 0x316159478e294669),
+    ("edgecase.png", 64, 2101, 0x2d6fccc45df7efb3),
+    ("edgecase.png", 32, 2102, 0xaa2bd7f9cd580f51),
+    ("edgecase.png", 16, 2102, 0x781d19c150dcbffe),
+    ("edgecase.png", 0, 2102, 0x983af2279062bd60),
+    ("kodim10.png", 64, 394289, 0x9b1309addd039a65),
+    ("kodim10.png", 32, 405208, 0x9f4168f6678b6230),
+    ("kodim10.png", 16, 409941, 0xa9ff218defa301c6),
+    ("kodim10.png", 0, 421583, 0x95c6cf2c7f7fd665),
+    ("kodim23.png", 64, 376339, 0x943e7cedf22c3f49),
+    ("kodim23.png", 32, 379086, 0xb94c408011ddf162),
+    ("kodim23.png", 16, 380285, 0xf8d27097e1c0831d),
+    ("kodim23.png", 0, 383673, 0x177ffc8e225a73f1),
+    ("qoi_logo.png", 64, 5429, 0x6b9f5b20b0b54459),
+    ("qoi_logo.png", 32, 5774, 0xa28c73803c2ae9e7),
+    ("qoi_logo.png", 16, 5820, 0x708b35fc48f0f8fe),
+    ("qoi_logo.png", 0, 9068, 0x05f05fd40077600d),
+    ("testcard.png", 64, 15132, 0x0a3bbc8455cb9fbb),
+    ("testcard.png", 32, 15786, 0xd8b7486e3e7a68f2),
+    ("testcard.png", 16, 16296, 0xc26c9281a772e41b),
+    ("testcard.png", 0, 24692, 0x1a9a95eff99a2f2d),
+    ("testcard_rgba.png", 64, 13635, 0x5107a151f74aad2e),
+    ("testcard_rgba.png", 32, 14283, 0x9569b3865738a4db),
+    ("testcard_rgba.png", 16, 14771, 0x4afbaf608cb65c9b),
+    ("testcard_rgba.png", 0, 21960, 0x921d9b1b35f5e623),
+    ("wikipedia_008.png", 64, 778595, 0xdb66e3e05fb073ce),
+    ("wikipedia_008.png", 32, 803190, 0x7d0256e02985c1d4),
+    ("wikipedia_008.png", 16, 815678, 0x4af1b8488f67e9a9),
+    ("wikipedia_008.png", 0, 862206, 0x71adb4b33810fd93),
+];
+
+#[test]
+fn v1_output_is_stable() {
+    let mut actual = Vec::new();
+    let mut paths: Vec<_> = std::fs::read_dir("../test_images")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let image = image::load(
+            BufReader::new(std::fs::File::open(&path).unwrap()),
+            ImageFormat::Png,
+        )
+        .unwrap();
+        let (width, height) = (image.width() as u16, image.height() as u16);
+        let pixels: Vec<u16> = image
+            .into_rgb8()
+            .pixels()
+            .map(|p| encode_rgb565_unchecked(rgb888_to_rgb565(p.0)))
+            .collect();
+        let name = path.file_name().unwrap().to_str().unwrap().to_owned();
+
+        for size in [
+            ColorArraySize::Entries64,
+            ColorArraySize::Entries32,
+            ColorArraySize::Entries16,
+            ColorArraySize::NoArray,
+        ] {
+            let mut encoded = Vec::new();
+            assert!(Q565EncodeContext::encode_to_vec_versioned(
+                EncoderVersion::V1,
+                size,
+                width,
+                height,
+                &pixels,
+                &mut encoded
+            )
+            .is_some());
+            actual.push((name.clone(), size.entries(), encoded.len(), fnv1a(&encoded)));
+        }
+    }
+
+    let golden: Vec<_> = GOLDEN_V1
+        .iter()
+        .map(|&(name, entries, len, hash)| (name.to_owned(), entries, len, hash))
+        .collect();
+    assert_eq!(actual, golden);
+}
+
+#[test]
+fn encoder_versions() {
+    assert_eq!(EncoderVersion::default(), EncoderVersion::V1);
+    assert_eq!(EncoderVersion::from_number(1), Some(EncoderVersion::V1));
+    assert_eq!(EncoderVersion::from_number(0), None);
+    assert_eq!(
+        EncoderVersion::from_number(EncoderVersion::LATEST.number()),
+        Some(EncoderVersion::LATEST)
+    );
+}
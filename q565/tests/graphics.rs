@@ -0,0 +1,106 @@
+use embedded_graphics::{
+    framebuffer::{buffer_size, Framebuffer},
+    pixelcolor::{
+        raw::{BigEndian, ByteOrder, LittleEndian, RawU16},
+        Rgb565,
+    },
+    prelude::*,
+    primitives::{Circle, PrimitiveStyle, Rectangle},
+};
+use q565::{
+    encode::Q565EncodeContext,
+    graphics::{
+        encode_framebuffer, encode_framebuffer_to_vec, encode_pixels, encode_pixels_to_vec,
+        GraphicsEncodeError,
+    },
+    max_stream_len,
+};
+
+const WIDTH: usize = 37;
+const HEIGHT: usize = 21;
+const N: usize = buffer_size::<Rgb565>(WIDTH, HEIGHT);
+
+fn draw<BO: ByteOrder>(framebuffer: &mut Framebuffer<Rgb565, RawU16, BO, WIDTH, HEIGHT, N>)
+where
+    Framebuffer<Rgb565, RawU16, BO, WIDTH, HEIGHT, N>: DrawTarget<Color = Rgb565>,
+{
+    framebuffer.clear(Rgb565::CSS_SLATE_GRAY).ok();
+    Rectangle::new(Point::new(3, 2), Size::new(20, 9))
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::RED))
+        .draw(framebuffer)
+        .ok();
+    Circle::new(Point::new(15, 5), 14)
+        .into_styled(PrimitiveStyle::with_stroke(Rgb565::new(7, 50, 19), 2))
+        .draw(framebuffer)
+        .ok();
+}
+
+fn pixels() -> Vec<u16> {
+    let mut framebuffer = Framebuffer::<Rgb565, RawU16, LittleEndian, WIDTH, HEIGHT, N>::new();
+    draw(&mut framebuffer);
+    framebuffer
+        .data()
+        .chunks(2)
+        .map(|p| u16::from_le_bytes([p[0], p[1]]))
+        .collect()
+}
+
+fn expected() -> Vec<u8> {
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(WIDTH as u16, HEIGHT as u16, &pixels(), &mut encoded).unwrap();
+    encoded
+}
+
+#[test]
+fn framebuffers() {
+    let expected = expected();
+    let mut output = [0; max_stream_len(WIDTH as u16, HEIGHT as u16)];
+
+    let mut little = Framebuffer::<Rgb565, RawU16, LittleEndian, WIDTH, HEIGHT, N>::new();
+    draw(&mut little);
+    let len = encode_framebuffer(&little, &mut output).unwrap();
+    assert!(output[..len] == expected);
+
+    let mut big = Framebuffer::<Rgb565, RawU16, BigEndian, WIDTH, HEIGHT, N>::new();
+    draw(&mut big);
+    let len = encode_framebuffer(&big, &mut output).unwrap();
+    assert!(output[..len] == expected);
+
+    let mut vec = vec![1, 2, 3];
+    let len = encode_framebuffer_to_vec(&big, &mut vec).unwrap();
+    assert_eq!(vec.len(), 3 + len);
+    assert!(vec[3..] == expected);
+}
+
+#[test]
+fn pixel_iterators() {
+    let expected = expected();
+    let colors = pixels().into_iter().map(|p| Rgb565::from(RawU16::new(p)));
+    let (width, height) = (WIDTH as u16, HEIGHT as u16);
+
+    let mut output = vec![0; max_stream_len(width, height)];
+    let len = encode_pixels(width, height, colors.clone(), &mut output).unwrap();
+    assert!(output[..len] == expected);
+
+    let mut vec = Vec::new();
+    encode_pixels_to_vec(width, height, colors.clone(), &mut vec).unwrap();
+    assert!(vec == expected);
+
+    assert!(matches!(
+        encode_pixels(width, height, colors.clone().skip(1), &mut output),
+        Err(GraphicsEncodeError::PixelCount)
+    ));
+    assert!(matches!(
+        encode_pixels(width, height - 1, colors.clone(), &mut output),
+        Err(GraphicsEncodeError::PixelCount)
+    ));
+    assert!(matches!(
+        encode_pixels_to_vec(width, height, colors.clone().take(5), &mut vec),
+        Err(GraphicsEncodeError::PixelCount)
+    ));
+    assert!(vec == expected);
+    assert!(matches!(
+        encode_pixels(width, height, colors, &mut output[..expected.len() - 1]),
+        Err(GraphicsEncodeError::OutputTooSmall)
+    ));
+}
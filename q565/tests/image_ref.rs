@@ -0,0 +1,123 @@
+use q565::{
+    byteorder::{BigEndian, LittleEndian},
+    decode::{DecodeError, Q565DecodeContext, Q565Ref, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    stream::Op,
+    ColorArraySize, Rgb565,
+};
+
+/// Stripes of a few colors, so the encoder uses all kinds of ops.
+fn stripes(width: u16, height: u16) -> Vec<u16> {
+    let colors = [0xF800, 0x07E0, 0x001F, 0x7BEF, 0x8410, 0xFFE0];
+    (0..usize::from(width) * usize::from(height))
+        .map(|i| colors[(i / 3 + i / usize::from(width)) % colors.len()] + (i % 2) as u16)
+        .collect()
+}
+
+#[test]
+fn validates_once_and_borrows() {
+    let pixels = stripes(12, 10);
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::Entries32,
+        12,
+        10,
+        &pixels,
+        &mut encoded,
+    )
+    .unwrap();
+    let len = encoded.len();
+    // trailing bytes, like the next image of a stream
+    encoded.extend_from_slice(b"next image");
+
+    let image = Q565Ref::new(&encoded).unwrap();
+    assert_eq!((image.header().width, image.header().height), (12, 10));
+    assert_eq!(image.header().color_array_size, ColorArraySize::Entries32);
+    assert_eq!(image.pixel_count(), 120);
+    assert_eq!(image.data(), &encoded[..len]);
+    assert_eq!(image.body().len(), len - 9);
+    assert_eq!(image.ops().unwrap().last().unwrap().1, Op::End);
+
+    assert!(image.pixels::<LittleEndian>().eq(pixels.iter().copied()));
+    assert_eq!(
+        image.decode_to_vec::<BigEndian, Rgb565>(),
+        Q565DecodeContext::decode_to_vec::<BigEndian, Rgb565>(&encoded)
+            .unwrap()
+            .1
+    );
+
+    let mut output = Vec::new();
+    assert_eq!(
+        image
+            .decode::<LittleEndian>(VecDecodeOutput::<Rgb565>::new(&mut output))
+            .unwrap(),
+        120
+    );
+    assert_eq!(output, pixels);
+}
+
+#[test]
+fn rejects_invalid_images() {
+    let pixels = stripes(12, 10);
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::Entries32,
+        12,
+        10,
+        &pixels,
+        &mut encoded,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        Q565Ref::try_from(&encoded[..encoded.len() - 1]),
+        Err(DecodeError::UnexpectedEof)
+    ));
+    let mut bigger = encoded.clone();
+    bigger[7] = 11;
+    assert!(matches!(
+        Q565Ref::new(&bigger),
+        Err(DecodeError::MissingData)
+    ));
+
+    // the stripes repeat, so there are index ops, which a header without a color array can't have
+    let image = Q565Ref::new(&encoded).unwrap();
+    assert!(image
+        .ops()
+        .unwrap()
+        .any(|(_, op)| matches!(op, Op::Index(_) | Op::DiffIndexed(..))));
+    let mut no_array = encoded;
+    no_array[4] = ColorArraySize::NoArray.flags();
+    assert!(matches!(
+        Q565Ref::new(&no_array),
+        Err(DecodeError::ColorArrayTooSmall)
+    ));
+}
+
+#[test]
+fn raw_images() {
+    // noise, which doesn't compress
+    let mut state = 0x2545_F491u32;
+    let pixels: Vec<u16> = (0..64)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u16
+        })
+        .collect();
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_auto(8, 8, &pixels, &mut encoded).unwrap();
+
+    let image = Q565Ref::new(&encoded).unwrap();
+    assert!(image.header().raw);
+    assert!(image.ops().is_none());
+    assert_eq!(image.body().len(), 128);
+    assert!(image.pixels::<LittleEndian>().eq(pixels.iter().copied()));
+    assert_eq!(image.decode_to_vec::<LittleEndian, Rgb565>(), pixels);
+
+    assert!(matches!(
+        Q565Ref::new(&encoded[..encoded.len() - 1]),
+        Err(DecodeError::UnexpectedEof)
+    ));
+}
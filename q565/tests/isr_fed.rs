@@ -0,0 +1,94 @@
+mod common;
+
+use common::{encode_ops, test_pattern};
+use q565::{byteorder::LittleEndian, embedded::IsrFedDecoder};
+
+fn test_image() -> (Vec<u16>, Vec<u8>) {
+    let pixels = test_pattern(64, 64);
+    let encoded = encode_ops(64, 64, &pixels);
+    (pixels, encoded)
+}
+
+#[test]
+fn fed_from_another_thread() {
+    static DECODER: IsrFedDecoder<32> = IsrFedDecoder::new();
+    let (pixels, data) = test_image();
+
+    let sender = std::thread::spawn(move || {
+        // pieces of varying size, retrying whatever didn't fit
+        let mut rest = &data[..];
+        let mut piece = 1;
+        while !rest.is_empty() {
+            let n = DECODER.push(&rest[..piece.min(rest.len())]);
+            rest = &rest[n..];
+            piece = piece % 13 + 1;
+            std::thread::yield_now();
+        }
+    });
+
+    let mut output = vec![0u16; pixels.len()];
+    let mut written = 0;
+    loop {
+        let progress = DECODER
+            .drain::<LittleEndian>(&mut output[written..])
+            .unwrap();
+        written += progress.pixels_written;
+        if progress.finished {
+            break;
+        }
+        std::thread::yield_now();
+    }
+    sender.join().unwrap();
+
+    assert_eq!(written, pixels.len());
+    assert_eq!(output, pixels);
+    assert!(DECODER.is_finished());
+}
+
+#[test]
+fn queue_overrun_and_reset() {
+    let decoder = IsrFedDecoder::<4>::new();
+    assert_eq!(decoder.push(&[1, 2, 3]), 3);
+    assert!(decoder.push_byte(4));
+    assert!(!decoder.push_byte(5));
+    assert_eq!(decoder.queued(), 4);
+
+    assert!(decoder.reset());
+    assert_eq!(decoder.queued(), 0);
+    assert!(!decoder.is_finished());
+}
+
+#[test]
+fn bytes_after_the_end_marker_stay_queued() {
+    let decoder = IsrFedDecoder::<64>::new();
+    // a run of 2 pixels, the end marker, and the start of the next image
+    assert_eq!(decoder.push(&[0xC1, 0xFF, 0xC0]), 3);
+
+    let mut output = [1u16; 2];
+    let progress = unsafe { decoder.drain_unchecked::<LittleEndian>(&mut output) }.unwrap();
+    assert_eq!(progress.pixels_written, 2);
+    assert_eq!(progress.bytes_consumed, 2);
+    assert!(progress.finished);
+    assert_eq!(output, [0, 0]);
+    assert_eq!(decoder.queued(), 1);
+}
+
+#[test]
+fn full_output_leaves_the_rest_queued() {
+    let decoder = IsrFedDecoder::<64>::new();
+    // a run of 3 pixels, an index op, and the end marker
+    assert_eq!(decoder.push(&[0xC2, 0x00, 0xFF]), 3);
+
+    let mut output = [1u16; 2];
+    let progress = decoder.drain::<LittleEndian>(&mut output).unwrap();
+    assert_eq!(progress.pixels_written, 2);
+    assert!(progress.output_full && !progress.finished);
+    assert_eq!(output, [0, 0]);
+
+    let mut rest = [1u16; 4];
+    let progress = decoder.drain::<LittleEndian>(&mut rest).unwrap();
+    assert_eq!(progress.pixels_written, 2);
+    assert!(progress.finished && !progress.output_full);
+    assert_eq!(rest, [0, 0, 1, 1]);
+    assert_eq!(decoder.queued(), 0);
+}
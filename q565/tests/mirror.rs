@@ -0,0 +1,207 @@
+use q565::{
+    byteorder::LittleEndian,
+    decode::DecodeError,
+    encode::Q565EncodeContext,
+    mirror::{MirrorError, MirrorHeader, MirrorReceiver, MirrorSender, MIRROR_HEADER_LEN},
+};
+
+const WIDTH: u16 = 16;
+const HEIGHT: u16 = 8;
+
+/// A box moving to the right, one pixel per frame.
+fn screen(frame: usize) -> Vec<u16> {
+    (0..usize::from(WIDTH) * usize::from(HEIGHT))
+        .map(|i| {
+            let (x, y) = (i % usize::from(WIDTH), i / usize::from(WIDTH));
+            if (frame..frame + 4).contains(&x) && (2..6).contains(&y) {
+                0xFFE0
+            } else {
+                0x0010 + frame as u16
+            }
+        })
+        .collect()
+}
+
+/// Encodes the frames as a frame sequence, starting over at every keyframe, and sends them in
+/// packets of `packet_len` bytes.
+fn send(frames: &[(usize, bool)], packet_len: usize) -> Vec<Vec<u8>> {
+    let mut encoder = Q565EncodeContext::new();
+    let mut packets = Vec::new();
+    for (sequence, &(frame, keyframe)) in frames.iter().enumerate() {
+        if keyframe {
+            encoder = Q565EncodeContext::new();
+        }
+        let mut encoded = Vec::new();
+        encoder
+            .encode_frame(WIDTH, HEIGHT, &screen(frame), &mut encoded)
+            .unwrap();
+
+        let mut sender = MirrorSender::new(&encoded, sequence as u16, keyframe).unwrap();
+        let mut packet = vec![0; packet_len];
+        loop {
+            let len = sender.packet(&mut packet);
+            if len == 0 {
+                break;
+            }
+            packets.push(packet[..len].to_vec());
+            sender.advance(len);
+        }
+        assert!(sender.is_done());
+    }
+    packets
+}
+
+#[test]
+fn header_roundtrip() {
+    let header = MirrorHeader {
+        payload_len: 1234,
+        sequence: 0xFFFF,
+        keyframe: true,
+    };
+    let bytes = header.to_bytes();
+    assert_eq!(MirrorHeader::parse(&bytes).unwrap().unwrap(), header);
+    assert!(MirrorHeader::parse(&bytes[..MIRROR_HEADER_LEN - 1]).is_none());
+
+    let mut flags = bytes;
+    flags[10] = 0b10;
+    assert!(matches!(
+        MirrorHeader::parse(&flags),
+        Some(Err(MirrorError::UnsupportedFlags))
+    ));
+}
+
+#[test]
+fn frames_are_reassembled_across_packets() {
+    let frames = [(0, true), (1, false), (2, false), (3, true), (4, false)];
+    for packet_len in [8, 13, 64] {
+        let mut receiver = MirrorReceiver::new();
+        assert!(receiver.needs_keyframe());
+
+        let mut received = Vec::new();
+        for packet in send(&frames, packet_len) {
+            assert!(packet.len() <= packet_len);
+            receiver.push(&packet);
+            while let Some(frame) = receiver.next_frame::<LittleEndian>().unwrap() {
+                assert_eq!(frame.pixels, screen(frame.sequence as usize));
+                received.push((frame.sequence, frame.keyframe));
+            }
+        }
+
+        assert_eq!(
+            received,
+            [(0, true), (1, false), (2, false), (3, true), (4, false)]
+        );
+        assert!(!receiver.needs_keyframe());
+    }
+}
+
+#[test]
+fn repeated_frames() {
+    let mut encoder = Q565EncodeContext::new();
+    let mut packets = Vec::new();
+    let mut previous = Vec::new();
+    for (sequence, frame) in [0, 1, 1, 2].into_iter().enumerate() {
+        let mut encoded = Vec::new();
+        encoder
+            .encode_frame_or_repeat(WIDTH, HEIGHT, &screen(frame), &previous, &mut encoded)
+            .unwrap();
+        previous = screen(frame);
+
+        let mut sender = MirrorSender::new(&encoded, sequence as u16, sequence == 0).unwrap();
+        let mut packet = vec![0; 64];
+        while !sender.is_done() {
+            let len = sender.packet(&mut packet);
+            packets.extend_from_slice(&packet[..len]);
+            sender.advance(len);
+        }
+    }
+
+    let mut receiver = MirrorReceiver::new();
+    receiver.push(&packets);
+    let mut received = Vec::new();
+    while let Some(frame) = receiver.next_frame::<LittleEndian>().unwrap() {
+        assert_eq!((frame.header.width, frame.header.height), (WIDTH, HEIGHT));
+        received.push((frame.sequence, frame.repeat, frame.pixels.to_vec()));
+    }
+    assert_eq!(
+        received,
+        [
+            (0, false, screen(0)),
+            (1, false, screen(1)),
+            (2, true, screen(1)),
+            (3, false, screen(2)),
+        ]
+    );
+
+    // a keyframe can't be a repeat
+    let mut receiver = MirrorReceiver::new();
+    let mut sender = MirrorSender::new(&[q565::REPEAT_FRAME], 0, true).unwrap();
+    let mut packet = vec![0; 64];
+    let len = sender.packet(&mut packet);
+    sender.advance(len);
+    receiver.push(&packet[..len]);
+    assert!(receiver.next_frame::<LittleEndian>().is_err());
+}
+
+#[test]
+fn waits_for_keyframe_after_a_gap() {
+    let frames = [(0, true), (1, false), (2, false), (3, false), (4, true)];
+    let mut receiver = MirrorReceiver::new();
+
+    let mut received = Vec::new();
+    for (index, packet) in send(&frames, 1024).into_iter().enumerate() {
+        // frame 1 gets lost
+        if index == 1 {
+            continue;
+        }
+        receiver.push(&packet);
+        while let Some(frame) = receiver.next_frame::<LittleEndian>().unwrap() {
+            assert_eq!(frame.pixels, screen(frame.sequence as usize));
+            received.push(frame.sequence);
+        }
+        if index == 3 {
+            assert!(receiver.needs_keyframe());
+        }
+    }
+
+    assert_eq!(received, [0, 4]);
+}
+
+#[test]
+fn skips_garbage_and_invalid_frames() {
+    let packets = send(&[(0, true), (1, false)], 1024);
+    let mut receiver = MirrorReceiver::new();
+    receiver.push(b"garbage from before the device was reset..");
+    receiver.push(&packets[0]);
+    assert!(matches!(
+        receiver.next_frame::<LittleEndian>(),
+        Err(MirrorError::InvalidMagic)
+    ));
+    assert_eq!(
+        receiver
+            .next_frame::<LittleEndian>()
+            .unwrap()
+            .unwrap()
+            .sequence,
+        0
+    );
+
+    let mut corrupt = packets[1].clone();
+    corrupt.truncate(corrupt.len() - 1);
+    corrupt[4] -= 1;
+    receiver.push(&corrupt);
+    assert!(matches!(
+        receiver.next_frame::<LittleEndian>(),
+        Err(MirrorError::Decode {
+            source: DecodeError::UnexpectedEof | DecodeError::MissingData
+        })
+    ));
+    assert!(receiver.needs_keyframe());
+
+    let mut receiver = MirrorReceiver::new().with_max_payload_len(16);
+    receiver.push(&packets[0]);
+    assert!(matches!(
+        receiver.next_frame::<LittleEndian>(),
+        Err(MirrorError::TooLarge)
+    ));
+}
@@ -0,0 +1,112 @@
+use q565::{
+    byteorder::LittleEndian,
+    decode::DecodeError,
+    encode::Q565EncodeContext,
+    nine_patch::{encode_nine_patch, Insets, NinePatch, NinePatchError},
+};
+
+/// 5x4 image of distinct colors, with a 1x1 center.
+fn button() -> (Vec<u16>, Vec<u8>) {
+    let pixels: Vec<u16> = (0..20).map(|i| 0x0841 * i).collect();
+    let mut image = Vec::new();
+    Q565EncodeContext::encode_to_vec(5, 4, &pixels, &mut image).unwrap();
+    let insets = Insets {
+        left: 2,
+        top: 2,
+        right: 2,
+        bottom: 1,
+    };
+    encode_nine_patch(&mut image, insets).unwrap();
+    (pixels, image)
+}
+
+fn draw(patch: &NinePatch, width: u16, height: u16) -> Result<Vec<Vec<u16>>, NinePatchError> {
+    let mut buffer = [0; 20];
+    let mut pixels = Vec::new();
+    patch.draw::<LittleEndian>(&mut buffer, width, height, |chunk: &[u16]| {
+        pixels.extend_from_slice(chunk)
+    })?;
+    Ok(pixels
+        .chunks(usize::from(width))
+        .map(<[u16]>::to_vec)
+        .collect())
+}
+
+#[test]
+fn stretches_edges_and_center() {
+    let (source, data) = button();
+    let patch = NinePatch::parse(&data).unwrap();
+    assert_eq!(patch.size(), (5, 4));
+    assert_eq!(patch.insets().bottom, 1);
+
+    // at its own size, the image comes out unchanged
+    let same = draw(&patch, 5, 4).unwrap();
+    assert_eq!(same.concat(), source);
+
+    let rows = draw(&patch, 8, 6).unwrap();
+    let src = |x: usize, y: usize| source[y * 5 + x];
+    let expected_row = |y: usize| {
+        vec![
+            src(0, y),
+            src(1, y),
+            src(2, y),
+            src(2, y),
+            src(2, y),
+            src(2, y),
+            src(3, y),
+            src(4, y),
+        ]
+    };
+    let expected: Vec<_> = [0, 1, 2, 2, 2, 3].into_iter().map(expected_row).collect();
+    assert_eq!(rows, expected);
+
+    // only the corners
+    let corners = draw(&patch, 4, 3).unwrap();
+    assert_eq!(corners[0], [src(0, 0), src(1, 0), src(3, 0), src(4, 0)]);
+    assert_eq!(corners[2], [src(0, 3), src(1, 3), src(3, 3), src(4, 3)]);
+}
+
+#[test]
+fn passes_wide_rows_in_chunks() {
+    let (_, data) = button();
+    let patch = NinePatch::parse(&data).unwrap();
+
+    let mut chunks = Vec::new();
+    patch
+        .draw::<LittleEndian>(&mut [0; 20], 100, 3, |chunk: &[u16]| {
+            chunks.push(chunk.len())
+        })
+        .unwrap();
+    assert_eq!(chunks, [64, 36, 64, 36, 64, 36]);
+}
+
+#[test]
+fn rejects_invalid_input() {
+    let (_, data) = button();
+    let patch = NinePatch::parse(&data).unwrap();
+
+    assert!(matches!(draw(&patch, 3, 6), Err(NinePatchError::TooSmall)));
+    assert!(matches!(
+        patch.draw::<LittleEndian>(&mut [0; 19], 8, 8, |_: &[u16]| {}),
+        Err(NinePatchError::Decode {
+            source: DecodeError::OutputTooSmall
+        })
+    ));
+
+    // the plain image has no metadata chunk
+    assert!(matches!(
+        NinePatch::parse(patch.image()),
+        Err(NinePatchError::MissingChunk)
+    ));
+
+    let mut image = patch.image().to_vec();
+    let no_center = Insets {
+        left: 3,
+        right: 2,
+        ..Insets::default()
+    };
+    assert!(matches!(
+        encode_nine_patch(&mut image, no_center),
+        Err(NinePatchError::InvalidInsets)
+    ));
+}
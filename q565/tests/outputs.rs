@@ -0,0 +1,275 @@
+mod common;
+
+use common::{encode, test_pattern};
+#[cfg(not(feature = "forbid-unsafe"))]
+use q565::decode::{UninitSliceDecodeOutput, VolatileSliceDecodeOutput};
+use q565::{
+    byteorder::LittleEndian,
+    decode::{
+        ChunkedDecodeOutput, DmaChunkedDecodeOutput, DmaPixelSink, DownscaleFactor,
+        PixelDoublingDecodeOutput, Q565DecodeContext, RemapDecodeOutput, RowDigestDecodeOutput,
+        VecDecodeOutput,
+    },
+    utils::{decode_565, encode_rgb565_unchecked},
+    Rgb565,
+};
+#[cfg(not(feature = "forbid-unsafe"))]
+use std::mem::MaybeUninit;
+
+#[test]
+fn pixel_doubling() {
+    let (width, height) = (13, 5);
+    let input = test_pattern(width, height);
+
+    let encoded = encode(width, height, &input);
+
+    let mut doubled = vec![0u16; input.len() * 4];
+    Q565DecodeContext::decode::<LittleEndian>(
+        &encoded,
+        PixelDoublingDecodeOutput::<Rgb565>::new(&mut doubled, width),
+    )
+    .unwrap();
+
+    let width = usize::from(width);
+    for (i, &pixel) in doubled.iter().enumerate() {
+        let (x, y) = (i % (width * 2), i / (width * 2));
+        assert_eq!(pixel, input[y / 2 * width + x / 2], "mismatch at {x}x{y}");
+    }
+}
+
+#[test]
+fn downscale() {
+    let (width, height) = (9, 6);
+    let input = test_pattern(width, height);
+
+    let encoded = encode(width, height, &input);
+
+    let mut scaled = Vec::new();
+    let header = Q565DecodeContext::decode_downscaled::<LittleEndian, Rgb565>(
+        &encoded,
+        DownscaleFactor::Half,
+        &mut scaled,
+    )
+    .unwrap();
+    assert_eq!((header.width, header.height), (5, 3));
+    assert_eq!(scaled.len(), 15);
+
+    // the right edge column only averages the single remaining source column
+    let (width, scaled_width) = (usize::from(width), usize::from(header.width));
+    for y in 0..usize::from(header.height) {
+        let block = [input[y * 2 * width + 8], input[(y * 2 + 1) * width + 8]];
+        let [r, g, b] = [0, 1, 2].map(|c| {
+            let sum: u32 = block.iter().map(|&p| u32::from(decode_565(p)[c])).sum();
+            sum.div_ceil(2) as u8
+        });
+        assert_eq!(
+            scaled[y * scaled_width + 4],
+            encode_rgb565_unchecked([r, g, b])
+        );
+    }
+}
+
+#[test]
+fn chunked() {
+    let (width, height) = (10, 7);
+    let input = test_pattern(width, height);
+
+    let encoded = encode(width, height, &input);
+
+    let (mut front, mut back) = ([0u16; 16], [0u16; 16]);
+    let mut chunks = Vec::new();
+    let mut output =
+        ChunkedDecodeOutput::<Rgb565, _>::new(&mut front, &mut back, |chunk: &[u16]| {
+            chunks.push(chunk.to_vec())
+        });
+    Q565DecodeContext::decode::<LittleEndian>(&encoded, &mut output).unwrap();
+    output.flush();
+
+    assert_eq!(
+        chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+        [16, 16, 16, 16, 6]
+    );
+    assert_eq!(chunks.concat(), input);
+}
+
+/// A DMA channel that completes each transfer when it's waited for.
+#[derive(Default)]
+struct FakeDma {
+    in_flight: Option<(&'static mut [u16], usize)>,
+    transferred: Vec<Vec<u16>>,
+}
+
+impl DmaPixelSink<u16> for FakeDma {
+    fn start(&mut self, buffer: &'static mut [u16], len: usize) {
+        assert!(self.in_flight.is_none(), "transfer started while busy");
+        self.in_flight = Some((buffer, len));
+    }
+
+    fn wait(&mut self) -> &'static mut [u16] {
+        let (buffer, len) = self.in_flight.take().expect("no transfer in progress");
+        self.transferred.push(buffer[..len].to_vec());
+        buffer
+    }
+}
+
+#[test]
+fn dma_chunked() {
+    let (width, height) = (10, 7);
+    let input = test_pattern(width, height);
+
+    let encoded = encode(width, height, &input);
+
+    let front = Box::leak(Box::new([0u16; 16]));
+    let back = Box::leak(Box::new([0u16; 16]));
+    let (front_ptr, back_ptr) = (front.as_ptr(), back.as_ptr());
+    let mut output = DmaChunkedDecodeOutput::<Rgb565, _>::new(front, back, FakeDma::default());
+    Q565DecodeContext::decode::<LittleEndian>(&encoded, &mut output).unwrap();
+    let (dma, [a, b]) = output.finish();
+
+    assert!(dma.in_flight.is_none());
+    assert_eq!(
+        dma.transferred.iter().map(Vec::len).collect::<Vec<_>>(),
+        [16, 16, 16, 16, 6]
+    );
+    assert_eq!(dma.transferred.concat(), input);
+    // both buffers come back, in either order
+    let mut returned = [a.as_ptr(), b.as_ptr()];
+    returned.sort();
+    let mut given = [front_ptr, back_ptr];
+    given.sort();
+    assert_eq!(returned, given);
+}
+
+#[test]
+fn vec_output_reserves_from_header() {
+    let pixels = test_pattern(40, 25);
+    let encoded = encode(40, 25, &pixels);
+
+    let mut output = Vec::new();
+    Q565DecodeContext::decode::<LittleEndian>(
+        &encoded,
+        VecDecodeOutput::<Rgb565>::new(&mut output),
+    )
+    .unwrap();
+    assert_eq!(output.len(), 1000);
+    // reserved once up front, instead of growing by doubling
+    assert!(output.capacity() < 1024);
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn uninit_output() {
+    let pixels = test_pattern(40, 25);
+    let encoded = encode(40, 25, &pixels);
+
+    // the same scratch buffer serves every decode
+    let mut scratch = vec![MaybeUninit::<u16>::uninit(); 1200];
+    for _ in 0..2 {
+        let (header, decoded) =
+            Q565DecodeContext::decode_to_uninit::<LittleEndian, Rgb565>(&encoded, &mut scratch)
+                .unwrap();
+        assert_eq!((header.width, header.height), (40, 25));
+        assert_eq!(decoded, &pixels[..]);
+    }
+
+    assert!(Q565DecodeContext::decode_to_uninit::<LittleEndian, Rgb565>(
+        &encoded,
+        &mut scratch[..999]
+    )
+    .is_err());
+
+    // truncated data only hands out the pixels that were written
+    let mut output = UninitSliceDecodeOutput::<Rgb565>::new(&mut scratch);
+    assert!(
+        Q565DecodeContext::decode::<LittleEndian>(&encoded[..encoded.len() / 2], &mut output)
+            .is_err()
+    );
+    let decoded = output.initialized();
+    assert!(!decoded.is_empty() && decoded.len() < 1000);
+    assert_eq!(decoded, &pixels[..decoded.len()]);
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn volatile_output() {
+    let pixels = test_pattern(40, 25);
+    let encoded = encode(40, 25, &pixels);
+
+    let mut framebuffer = vec![0u16; 1000];
+    let mut output = unsafe {
+        VolatileSliceDecodeOutput::<Rgb565>::new(framebuffer.as_mut_ptr(), framebuffer.len())
+    };
+    Q565DecodeContext::decode::<LittleEndian>(&encoded, &mut output).unwrap();
+    assert_eq!(framebuffer, pixels);
+
+    // writes past the end are dropped
+    let mut framebuffer = vec![0u16; 999];
+    assert!(Q565DecodeContext::decode::<LittleEndian>(
+        &encoded,
+        VolatileSliceDecodeOutput::<Rgb565>::from_slice(&mut framebuffer)
+    )
+    .is_err());
+}
+
+#[test]
+fn remap() {
+    let (width, height) = (17, 6);
+    let input = test_pattern(width, height);
+
+    let encoded = encode(width, height, &input);
+
+    // the first matching pair wins, and remapped colors aren't remapped again
+    let pairs = [(0xF800, 0x07E0), (0x07E0, 0x001F), (0xF800, 0xFFFF)];
+    let mut remapped = Vec::new();
+    let (_, written) = Q565DecodeContext::decode::<LittleEndian>(
+        &encoded,
+        RemapDecodeOutput::new(VecDecodeOutput::<Rgb565>::new(&mut remapped), &pairs),
+    )
+    .unwrap();
+
+    assert_eq!(written, input.len());
+    let expected: Vec<u16> = input
+        .iter()
+        .map(|&color| match color {
+            0xF800 => 0x07E0,
+            0x07E0 => 0x001F,
+            color => color,
+        })
+        .collect();
+    assert_eq!(remapped, expected);
+}
+
+#[test]
+fn row_digests() {
+    let (width, height) = (23, 9);
+    let first = test_pattern(width, height);
+    let mut second = first.clone();
+    // rows 2 and 7, with a run crossing into row 3
+    second[2 * 23 + 5] ^= 1;
+    second[2 * 23 + 20..3 * 23 + 4].fill(0x07E0);
+    second[7 * 23] ^= 1;
+
+    let mut digests = [0u32; 8];
+    let mut frames = Vec::new();
+    for pixels in [&first, &second, &second] {
+        let encoded = encode(width, height, pixels);
+
+        let mut decoded = Vec::new();
+        let mut changed = Vec::new();
+        Q565DecodeContext::decode::<LittleEndian>(
+            &encoded,
+            RowDigestDecodeOutput::new(
+                VecDecodeOutput::<Rgb565>::new(&mut decoded),
+                width,
+                &mut digests,
+                |y| changed.push(y),
+            ),
+        )
+        .unwrap();
+        assert_eq!(&decoded, pixels);
+        frames.push(changed);
+    }
+
+    // the last row has no digest, so it's always reported
+    assert_eq!(frames, [(0..9).collect(), vec![2, 3, 7, 8], vec![8]]);
+}
@@ -0,0 +1,135 @@
+//! Link-time check that the decoders and the `no_std` encoders contain no panicking branches.
+//!
+//! Only meaningful in optimized builds with the `panic-free` feature enabled:
+//!
+//! ```sh
+//! cargo test --release --features panic-free --test panic_free
+//! ```
+//!
+//! If any of the functions instantiated here can panic, this test fails to link.
+
+mod common;
+
+use common::{encode, noise, test_pattern};
+#[cfg(not(feature = "forbid-unsafe"))]
+use q565::decode::{
+    streaming_no_header::Q565StreamingDecodeContext, MiniDecoder, UnsafeSliceDecodeOutput,
+    VolatileSliceDecodeOutput,
+};
+use q565::{
+    byteorder::{BigEndian, Endianness, LittleEndian},
+    decode::{PixelDoublingDecodeOutput, Q565DecodeContext, RectDecodeOutput},
+    encode::{encode_fast_rle, Q565CompactStreamingEncodeContext, Q565StreamingEncodeContext},
+    ColorFormat, Rect, Rgb565, Rgb888,
+};
+#[cfg(not(feature = "forbid-unsafe"))]
+use std::mem::MaybeUninit;
+
+#[inline(never)]
+fn decode_all<B: Endianness, C: ColorFormat>(data: &[u8], output: &mut [C::OutputElement]) {
+    #[cfg(not(feature = "forbid-unsafe"))]
+    {
+        let mut small = Q565DecodeContext::<16>::new_sized();
+        let _ = small
+            .decode_with_state::<B>(data, unsafe { UnsafeSliceDecodeOutput::<C>::new(output) });
+        let _ = Q565DecodeContext::decode::<B>(data, unsafe {
+            UnsafeSliceDecodeOutput::<C>::new(output)
+        });
+        let _ = Q565DecodeContext::decode::<B>(
+            data,
+            VolatileSliceDecodeOutput::<C>::from_slice(output),
+        );
+        let _ =
+            MiniDecoder::decode::<B>(data, unsafe { UnsafeSliceDecodeOutput::<C>::new(output) });
+
+        let mut uninit = [const { MaybeUninit::uninit() }; 64];
+        let _ = Q565DecodeContext::decode_to_uninit::<B, C>(data, &mut uninit);
+    }
+    let _ = Q565DecodeContext::decode::<B>(data, PixelDoublingDecodeOutput::<C>::new(output, 8));
+
+    let rect = Rect {
+        x: 1,
+        y: 1,
+        width: 4,
+        height: 4,
+    };
+    let _ = Q565DecodeContext::decode::<B>(data, RectDecodeOutput::<C>::new(output, 8, rect));
+}
+
+#[inline(never)]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn decode_streaming<B: Endianness>(data: &[u8], output: &mut [u16]) {
+    let mut state = Q565StreamingDecodeContext::new();
+    let _ = state.streaming_decode_to_slice::<B>(&data[8..], output);
+    let _ = Q565StreamingDecodeContext::new().streaming_decode_to_slice_budgeted::<B>(
+        &data[8..],
+        output,
+        16,
+    );
+}
+
+#[inline(never)]
+#[cfg(not(feature = "forbid-unsafe"))]
+unsafe fn decode_all_unchecked<B: Endianness>(data: &[u8], output: &mut [u16]) {
+    let _ = Q565DecodeContext::decode_unchecked::<B>(
+        data,
+        UnsafeSliceDecodeOutput::<Rgb565>::new(output),
+    );
+    Q565DecodeContext::new().decode_data_unchecked::<B>(
+        &data[8..],
+        &mut UnsafeSliceDecodeOutput::<Rgb565>::new(output),
+    );
+    Q565StreamingDecodeContext::new().streaming_decode_to_slice_unchecked::<B>(&data[8..], output);
+    Q565StreamingDecodeContext::new().streaming_decode_to_slice_budgeted_unchecked::<B>(
+        &data[8..],
+        output,
+        16,
+    );
+}
+
+#[inline(never)]
+fn encode_all<const N: usize>(pixels: &[u16], output: &mut [u8]) -> usize {
+    let mut state = Q565CompactStreamingEncodeContext::<N>::new_compact_sized();
+    let _ = state.encode_to_slice(pixels, output, 16);
+    let _ = state.finish(output);
+    let _ = encode_fast_rle(8, 8, pixels, output);
+
+    let mut state = Q565StreamingEncodeContext::<N>::new_sized();
+    let progress = state.encode_to_slice(pixels, output, usize::MAX);
+    let end = output.get_mut(progress.bytes_written..).unwrap_or_default();
+    progress.bytes_written + state.finish(end).unwrap_or(0)
+}
+
+#[test]
+fn encoders_are_panic_free() {
+    let pixels = test_pattern(8, 8);
+    let mut output = [0u8; 256];
+    assert!(encode_all::<64>(&pixels, &mut output) > 0);
+    assert!(encode_all::<32>(&pixels, &mut output[..10]) > 0);
+    assert!(encode_all::<16>(&pixels, &mut output) > 0);
+}
+
+#[test]
+fn decoders_are_panic_free() {
+    let pixels = noise(64);
+    let encoded = encode(8, 8, &pixels);
+
+    let mut rgb565 = [0u16; 256];
+    let mut rgb888 = [[0u8; 3]; 256];
+    decode_all::<LittleEndian, Rgb565>(&encoded, &mut rgb565);
+    decode_all::<BigEndian, Rgb888>(&encoded, &mut rgb888);
+    #[cfg(not(feature = "forbid-unsafe"))]
+    {
+        decode_streaming::<LittleEndian>(&encoded, &mut rgb565[..10]);
+        decode_streaming::<BigEndian>(&encoded, &mut rgb565);
+        unsafe {
+            decode_all_unchecked::<LittleEndian>(&encoded, &mut rgb565);
+            decode_all_unchecked::<BigEndian>(&encoded, &mut rgb565);
+        }
+
+        // the unchecked decoders above ran last, and need to produce the same image
+        let mut expected = pixels.clone();
+        expected.iter_mut().for_each(|p| *p = p.swap_bytes());
+        assert_eq!(rgb565[..64], expected[..]);
+    }
+}
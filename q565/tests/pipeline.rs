@@ -0,0 +1,143 @@
+mod common;
+
+use common::{decode, encode, test_images_rgb888, to_rgb565};
+use q565::{
+    byteorder::{BigEndian, Endianness, LittleEndian},
+    pipeline::{apply_delta, Pipeline, PipelineError},
+};
+
+fn run_pipeline<B: Endianness>(pipeline: &mut Pipeline, rows: &[[u8; 3]]) -> Vec<u8> {
+    let (header, header_len) = pipeline.header().to_bytes();
+    let mut encoded = header[..header_len].to_vec();
+
+    let width = usize::from(pipeline.header().width);
+    let mut output = vec![0u8; pipeline.max_row_len()];
+    for row in rows.chunks(width) {
+        let len = pipeline.push_row::<B>(row, &mut output).unwrap();
+        encoded.extend_from_slice(&output[..len]);
+    }
+    let len = pipeline.finish(&mut output).unwrap();
+    encoded.extend_from_slice(&output[..len]);
+
+    encoded
+}
+
+#[test]
+fn pipeline_matches_encoder() {
+    for (width, height, rgb888) in test_images_rgb888() {
+        let expected = encode(width, height, &to_rgb565(&rgb888));
+
+        let mut pipeline = Pipeline::new(width, height);
+        assert_eq!(run_pipeline::<BigEndian>(&mut pipeline, &rgb888), expected);
+        // the pipeline is reset after finishing a frame
+        assert_eq!(run_pipeline::<BigEndian>(&mut pipeline, &rgb888), expected);
+        // the byte order is applied to the rows
+        let bgr888: Vec<[u8; 3]> = rgb888.iter().map(|&[r, g, b]| [b, g, r]).collect();
+        assert_eq!(
+            run_pipeline::<LittleEndian>(&mut pipeline, &bgr888),
+            expected
+        );
+
+        let mut dithered = Pipeline::new(width, height).with_dithering();
+        assert_eq!(
+            decode(&run_pipeline::<BigEndian>(&mut dithered, &rgb888))
+                .2
+                .len(),
+            rgb888.len()
+        );
+    }
+}
+
+#[test]
+fn pipeline_delta() {
+    let (width, height) = (40u16, 8u16);
+    let frame1: Vec<[u8; 3]> = (0..320u32)
+        .map(|i| [(i * 7) as u8, (i * 3) as u8, i as u8])
+        .collect();
+    let mut frame2 = frame1.clone();
+    frame2[45..50].fill([255, 0, 0]);
+
+    let mut previous = vec![0u16; frame1.len()];
+    let mut pipeline = Pipeline::new(width, height)
+        .with_delta(&mut previous)
+        .unwrap();
+    let encoded1 = run_pipeline::<BigEndian>(&mut pipeline, &frame1);
+    let encoded2 = run_pipeline::<BigEndian>(&mut pipeline, &frame2);
+    // only the changed pixels are left, surrounded by runs of zeroes
+    assert!(encoded2.len() < 32);
+
+    let mut framebuffer = vec![0u16; frame1.len()];
+    apply_delta(&mut framebuffer, &decode(&encoded1).2);
+    let expected1 = to_rgb565(&frame1);
+    assert_eq!(framebuffer, expected1);
+
+    apply_delta(&mut framebuffer, &decode(&encoded2).2);
+    let expected2 = to_rgb565(&frame2);
+    assert_eq!(framebuffer, expected2);
+    assert_eq!(previous, expected2);
+}
+
+#[test]
+fn pipeline_errors() {
+    assert!(matches!(
+        Pipeline::new(4, 4).with_delta(&mut [0; 15]),
+        Err(PipelineError::FrameSize)
+    ));
+
+    let mut pipeline = Pipeline::new(4, 1);
+    let mut output = [0u8; 13];
+    assert!(matches!(
+        pipeline.push_row::<BigEndian>(&[[0; 3]; 4], &mut output[..12]),
+        Err(PipelineError::OutputTooSmall)
+    ));
+    assert!(matches!(
+        pipeline.push_row::<BigEndian>(&[[0; 3]; 3], &mut output),
+        Err(PipelineError::RowLength)
+    ));
+    assert!(matches!(
+        pipeline.finish(&mut output),
+        Err(PipelineError::IncompleteFrame)
+    ));
+    pipeline
+        .push_row::<BigEndian>(&[[0; 3]; 4], &mut output)
+        .unwrap();
+    assert!(matches!(
+        pipeline.push_row::<BigEndian>(&[[0; 3]; 4], &mut output),
+        Err(PipelineError::TooManyRows)
+    ));
+}
+
+#[test]
+fn pipeline_encode_frame() {
+    let (width, height) = (40u16, 8u16);
+    let frame: Vec<[u8; 3]> = (0..320u32)
+        .map(|i| [(i * 7) as u8, (i / 40 * 30) as u8, 80])
+        .collect();
+    let expected = run_pipeline::<BigEndian>(&mut Pipeline::new(width, height), &frame);
+
+    let mut pipeline = Pipeline::new(width, height);
+    let mut output = vec![0u8; expected.len()];
+    let len = pipeline
+        .encode_frame::<BigEndian>(&frame, &mut output)
+        .unwrap();
+    assert_eq!(output[..len], expected);
+
+    // an exactly sized output is enough, and the byte order is applied to the input
+    let bgr: Vec<[u8; 3]> = frame.iter().map(|&[r, g, b]| [b, g, r]).collect();
+    let len = pipeline
+        .encode_frame::<LittleEndian>(&bgr, &mut output)
+        .unwrap();
+    assert_eq!(output[..len], expected);
+
+    for len in [0, 5, expected.len() / 2, expected.len() - 1] {
+        assert!(matches!(
+            pipeline.encode_frame::<BigEndian>(&frame, &mut output[..len]),
+            Err(PipelineError::OutputTooSmall)
+        ));
+    }
+    // the pipeline was reset after the failed frame
+    let len = pipeline
+        .encode_frame::<BigEndian>(&frame, &mut output)
+        .unwrap();
+    assert_eq!(output[..len], expected);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::load;
+use q565::{
+    decode::{DecodeError, PixelIter},
+    encode::Q565EncodeContext,
+    ColorArraySize,
+};
+
+#[test]
+fn yields_all_pixels() {
+    for name in ["qoi_logo.png", "testcard.png", "edgecase.png"] {
+        let (width, height, pixels) = load(name);
+
+        for size in [
+            ColorArraySize::NoArray,
+            ColorArraySize::Entries16,
+            ColorArraySize::Entries32,
+            ColorArraySize::Entries64,
+        ] {
+            let mut encoded = Vec::new();
+            Q565EncodeContext::encode_to_vec_sized(size, width, height, &pixels, &mut encoded)
+                .unwrap();
+
+            let mut iter = PixelIter::new(&encoded).unwrap();
+            assert_eq!(iter.header().color_array_size, size);
+            assert_eq!(iter.len(), pixels.len());
+            // partially consumed
+            let first: Vec<u16> = iter.by_ref().take(100).collect();
+            assert_eq!(
+                iter.size_hint(),
+                (pixels.len() - 100, Some(pixels.len() - 100))
+            );
+            let rest: Vec<u16> = iter.by_ref().collect();
+            assert!(first.iter().chain(&rest).eq(&pixels), "{name}, {size:?}");
+            assert_eq!(iter.len(), 0);
+            assert_eq!(iter.next(), None);
+        }
+    }
+}
+
+#[test]
+fn yields_raw_pixels() {
+    // noise, which doesn't compress
+    let mut state = 0x2545_F491u32;
+    let pixels: Vec<u16> = (0..64)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u16
+        })
+        .collect();
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_auto(8, 8, &pixels, &mut encoded).unwrap();
+
+    let iter = PixelIter::new(&encoded).unwrap();
+    assert!(iter.header().raw);
+    assert_eq!(iter.len(), 64);
+    assert!(iter.eq(pixels));
+}
+
+#[test]
+fn validates_up_front() {
+    let pixels = [0x1234, 0x1234, 0x1234, 0xF800, 0x07E0, 0x07E0];
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(3, 2, &pixels, &mut encoded).unwrap();
+    assert!(PixelIter::new(&encoded).unwrap().eq(pixels));
+
+    assert!(matches!(
+        PixelIter::new(&encoded[..encoded.len() - 1]),
+        Err(DecodeError::UnexpectedEof)
+    ));
+
+    // header claiming more or fewer pixels than the ops produce
+    let mut bigger = encoded.clone();
+    bigger[4] = 4;
+    assert!(matches!(
+        PixelIter::new(&bigger),
+        Err(DecodeError::MissingData)
+    ));
+    let mut smaller = encoded;
+    smaller[4] = 2;
+    assert!(matches!(
+        PixelIter::new(&smaller),
+        Err(DecodeError::TooManyPixels)
+    ));
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{encode, test_pattern};
+use q565::{
+    byteorder::NativeEndian,
+    decode::{DecodeError, DecoderPool, DecoderPoolError, Q565DecodeContext},
+    encode::Q565EncodeContext,
+    Rgb565, Rgb888,
+};
+
+#[test]
+fn caps_decoders_and_reuses_buffers() {
+    let mut pool = DecoderPool::<Rgb565>::new_preallocated(2, 16 * 16);
+    let pixels = test_pattern(16, 16);
+    let encoded = encode(16, 16, &pixels);
+
+    let mut first = pool.acquire().unwrap();
+    let buffer = first.pixels().as_ptr();
+    let (header, decoded) = first.decode::<NativeEndian>(&encoded).unwrap();
+    assert_eq!((header.width, header.height), (16, 16));
+    assert!(decoded == pixels);
+    // decoded into the preallocated buffer
+    assert_eq!(decoded.as_ptr(), buffer);
+
+    let second = pool.acquire().unwrap();
+    assert_eq!((pool.in_use(), pool.available()), (2, 0));
+    assert!(matches!(pool.acquire(), Err(DecoderPoolError::Exhausted)));
+
+    pool.release(first);
+    assert_eq!(pool.available(), 1);
+    let mut reused = pool.acquire().unwrap();
+    assert!(reused.pixels().is_empty());
+    let smaller_pixels = test_pattern(7, 5);
+    let smaller = encode(7, 5, &smaller_pixels);
+    let (_, decoded) = reused.decode::<NativeEndian>(&smaller).unwrap();
+    assert!(decoded == smaller_pixels);
+    assert_eq!(decoded.as_ptr(), buffer);
+
+    pool.release(reused);
+    pool.release(second);
+    assert_eq!((pool.in_use(), pool.available()), (0, 2));
+}
+
+#[test]
+fn rejects_large_and_invalid_images() {
+    let mut pool = DecoderPool::<Rgb888>::new(1, 100);
+    let mut decoder = pool.acquire().unwrap();
+
+    let large = encode(11, 10, &test_pattern(11, 10));
+    assert!(matches!(
+        decoder.decode::<NativeEndian>(&large),
+        Err(DecoderPoolError::TooLarge)
+    ));
+
+    let valid = encode(10, 10, &test_pattern(10, 10));
+    assert!(matches!(
+        decoder.decode::<NativeEndian>(&valid[..valid.len() - 1]),
+        Err(DecoderPoolError::Decode {
+            source: DecodeError::UnexpectedEof
+        })
+    ));
+    assert!(decoder.pixels().is_empty());
+
+    let (_, decoded) = decoder.decode::<NativeEndian>(&valid).unwrap();
+    assert_eq!(decoded.len(), 100);
+}
+
+#[test]
+fn decodes_frame_sequences() {
+    let frames: Vec<Vec<u16>> = (0..3u16)
+        .map(|frame| test_pattern(8, 8).iter().map(|p| p ^ frame).collect())
+        .collect();
+    let mut context = Q565EncodeContext::new();
+    let encoded: Vec<Vec<u8>> = frames
+        .iter()
+        .map(|frame| {
+            let mut encoded = Vec::new();
+            context.encode_frame(8, 8, frame, &mut encoded).unwrap();
+            encoded
+        })
+        .collect();
+
+    let mut pool = DecoderPool::<Rgb565>::new(1, 64);
+    let mut decoder = pool.acquire().unwrap();
+    for (frame, encoded) in frames.iter().zip(&encoded) {
+        let (_, decoded) = decoder.decode_frame::<NativeEndian>(encoded).unwrap();
+        assert!(decoded == frame);
+    }
+
+    // the state is reset when the decoder goes back to the pool
+    pool.release(decoder);
+    let mut decoder = pool.acquire().unwrap();
+    let (_, expected) =
+        Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(&encoded[0]).unwrap();
+    let (_, decoded) = decoder.decode_frame::<NativeEndian>(&encoded[0]).unwrap();
+    assert!(decoded == expected);
+}
@@ -0,0 +1,231 @@
+use image::ImageFormat;
+use q565::{
+    byteorder::LittleEndian,
+    decode::{DecodeError, MiniDecoder, Q565DecodeContext, Q565Ref, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    ColorArraySize, Rgb565,
+};
+use std::io::BufReader;
+
+#[test]
+fn roundtrip_color_array_profiles() {
+    for image in std::fs::read_dir("../test_images").unwrap() {
+        let image = image::load(
+            BufReader::new(std::fs::File::open(image.unwrap().path()).unwrap()),
+            ImageFormat::Png,
+        )
+        .unwrap();
+
+        let (width, height) = (image.width() as u16, image.height() as u16);
+        let input: Vec<u16> = image
+            .into_rgb8()
+            .pixels()
+            .map(|p| encode_rgb565_unchecked(rgb888_to_rgb565(p.0)))
+            .collect();
+
+        for size in [
+            ColorArraySize::NoArray,
+            ColorArraySize::Entries16,
+            ColorArraySize::Entries32,
+            ColorArraySize::Entries64,
+        ] {
+            let mut encoded = Vec::new();
+            assert!(Q565EncodeContext::encode_to_vec_sized(
+                size,
+                width,
+                height,
+                &input,
+                &mut encoded
+            )
+            .is_some());
+            let magic: &[u8] = if size == ColorArraySize::Entries64 {
+                b"q565"
+            } else {
+                b"q56x"
+            };
+            assert_eq!(&encoded[..4], magic);
+
+            if size == ColorArraySize::Entries16 {
+                let mut encoded2 = Vec::new();
+                Q565EncodeContext::<16>::new_sized()
+                    .encode_with_state(width, height, &input, &mut encoded2)
+                    .unwrap();
+                assert_eq!(encoded, encoded2, "mismatch with encode to writer");
+            }
+
+            let mut decoded = Vec::new();
+            let (header, _) = Q565DecodeContext::decode::<LittleEndian>(
+                &encoded,
+                VecDecodeOutput::<Rgb565>::new(&mut decoded),
+            )
+            .unwrap();
+            assert_eq!(header.color_array_size, size);
+            assert_eq!(input, decoded, "safe decoding failed for {size:?}");
+
+            #[cfg(not(feature = "forbid-unsafe"))]
+            {
+                let mut decoded = vec![0u16; input.len()];
+                unsafe {
+                    Q565DecodeContext::decode_unchecked::<LittleEndian>(
+                        &encoded,
+                        q565::decode::UnsafeSliceDecodeOutput::<Rgb565>::new(&mut decoded),
+                    )
+                    .unwrap();
+                }
+                assert_eq!(input, decoded, "unsafe decoding failed for {size:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn reject_reserved_flags() {
+    let image = [b'q', b'5', b'6', b'x', 0b1000, 1, 0, 1, 0, 0xFF];
+    let mut decoded = Vec::new();
+    assert!(matches!(
+        Q565DecodeContext::decode::<LittleEndian>(
+            &image,
+            VecDecodeOutput::<Rgb565>::new(&mut decoded)
+        ),
+        Err(q565::decode::DecodeError::UnsupportedFlags)
+    ));
+}
+
+#[test]
+fn reject_indices_outside_the_color_array() {
+    let decode = |image: &[u8]| {
+        let mut decoded = Vec::new();
+        Q565DecodeContext::decode::<LittleEndian>(
+            image,
+            VecDecodeOutput::<Rgb565>::new(&mut decoded),
+        )
+        .map(|_| decoded)
+    };
+
+    // 16 entries: Q565_OP_INDEX of entry 15, then 16
+    let mut image = vec![b'q', b'5', b'6', b'x', 0b10, 2, 0, 1, 0, 0x0F, 0x0F, 0xFF];
+    assert_eq!(decode(&image).unwrap(), [0, 0]);
+    image[10] = 0x10;
+    assert!(matches!(
+        decode(&image),
+        Err(DecodeError::ColorArrayTooSmall)
+    ));
+    assert!(matches!(
+        Q565DecodeContext::<16>::new_sized().decode_with_state::<LittleEndian>(
+            &image,
+            VecDecodeOutput::<Rgb565>::new(&mut Vec::new())
+        ),
+        Err(DecodeError::ColorArrayTooSmall)
+    ));
+    assert!(matches!(
+        Q565Ref::new(&image),
+        Err(DecodeError::ColorArrayTooSmall)
+    ));
+
+    // Q565_OP_DIFF_INDEXED of entry 20
+    let image = [
+        b'q',
+        b'5',
+        b'6',
+        b'x',
+        0b10,
+        1,
+        0,
+        1,
+        0,
+        0xB2,
+        0x80 | 20,
+        0xFF,
+    ];
+    assert!(matches!(
+        decode(&image),
+        Err(DecodeError::ColorArrayTooSmall)
+    ));
+
+    // no color array: Q565_OP_INDEX of entry 0
+    let image = [b'q', b'5', b'6', b'x', 0b11, 1, 0, 1, 0, 0x00, 0xFF];
+    assert!(matches!(
+        decode(&image),
+        Err(DecodeError::ColorArrayTooSmall)
+    ));
+    assert!(matches!(
+        MiniDecoder::decode::<LittleEndian>(
+            &image,
+            VecDecodeOutput::<Rgb565>::new(&mut Vec::new())
+        ),
+        Err(DecodeError::ColorArrayTooSmall)
+    ));
+}
+
+#[test]
+fn small_decode_context() {
+    assert_eq!(std::mem::size_of::<Q565DecodeContext<16>>(), 34);
+
+    let pixels: Vec<u16> = (0..64 * 64u32).map(|i| (i * 37 % 4099) as u16).collect();
+    let mut encoded = Vec::new();
+    assert!(Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::Entries16,
+        64,
+        64,
+        &pixels,
+        &mut encoded
+    )
+    .is_some());
+
+    let mut decoded = Vec::new();
+    Q565DecodeContext::<16>::new_sized()
+        .decode_with_state::<LittleEndian>(&encoded, VecDecodeOutput::<Rgb565>::new(&mut decoded))
+        .unwrap();
+    assert_eq!(pixels, decoded);
+
+    encoded.clear();
+    assert!(Q565EncodeContext::encode_to_vec(64, 64, &pixels, &mut encoded).is_some());
+    assert!(matches!(
+        Q565DecodeContext::<16>::new_sized().decode_with_state::<LittleEndian>(
+            &encoded,
+            VecDecodeOutput::<Rgb565>::new(&mut Vec::new())
+        ),
+        Err(q565::decode::DecodeError::ColorArrayTooSmall)
+    ));
+}
+
+#[test]
+fn mini_decoder() {
+    let pixels: Vec<u16> = (0..64 * 64u32).map(|i| (i * 37 % 4099) as u16).collect();
+
+    let mut encoded = Vec::new();
+    let report = Q565EncodeContext::encode_to_vec_sized(
+        ColorArraySize::NoArray,
+        64,
+        64,
+        &pixels,
+        &mut encoded,
+    )
+    .unwrap();
+    assert_eq!(report.ops.index + report.ops.diff_indexed, 0);
+
+    let mut decoded = Vec::new();
+    let (header, _) =
+        MiniDecoder::decode::<LittleEndian>(&encoded, VecDecodeOutput::<Rgb565>::new(&mut decoded))
+            .unwrap();
+    assert_eq!(header.color_array_size, ColorArraySize::NoArray);
+    assert_eq!(decoded, pixels);
+
+    // the smallest regular context decodes it as well
+    let mut decoded = Vec::new();
+    Q565DecodeContext::<16>::new_sized()
+        .decode_with_state::<LittleEndian>(&encoded, VecDecodeOutput::<Rgb565>::new(&mut decoded))
+        .unwrap();
+    assert_eq!(decoded, pixels);
+
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(64, 64, &pixels, &mut encoded).unwrap();
+    assert!(matches!(
+        MiniDecoder::decode::<LittleEndian>(
+            &encoded,
+            VecDecodeOutput::<Rgb565>::new(&mut Vec::new())
+        ),
+        Err(DecodeError::ColorArrayTooSmall)
+    ));
+}
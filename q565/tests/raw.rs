@@ -0,0 +1,70 @@
+mod common;
+
+use common::noise;
+use q565::{
+    byteorder::LittleEndian,
+    decode::{DecodeError, Q565DecodeContext},
+    encode::Q565EncodeContext,
+    Rgb565, EXTENDED_HEADER_LEN,
+};
+
+#[test]
+fn encode_auto_picks_raw_for_noise() {
+    let pixels = noise(256);
+    let mut encoded = Vec::new();
+    let report = Q565EncodeContext::encode_auto(16, 16, &pixels, &mut encoded).unwrap();
+    assert_eq!(report.bytes_written, EXTENDED_HEADER_LEN + 512);
+    assert_eq!(report.bytes_written, encoded.len());
+    assert_eq!(report.ops.total(), 0);
+
+    let (header, _) = Q565DecodeContext::decode_header(&encoded).unwrap();
+    assert!(header.raw);
+
+    let (_, decoded) = Q565DecodeContext::decode_to_vec::<LittleEndian, Rgb565>(&encoded).unwrap();
+    assert_eq!(decoded, pixels);
+
+    #[cfg(not(feature = "forbid-unsafe"))]
+    {
+        let mut decoded = vec![0u16; pixels.len()];
+        let (_, pixels_written) = unsafe {
+            Q565DecodeContext::decode_unchecked::<LittleEndian>(
+                &encoded,
+                q565::decode::UnsafeSliceDecodeOutput::<Rgb565>::new(&mut decoded),
+            )
+        }
+        .unwrap();
+        assert_eq!(pixels_written, pixels.len());
+        assert_eq!(decoded, pixels);
+    }
+}
+
+#[test]
+fn encode_auto_keeps_compressible_images() {
+    let pixels = vec![0x1234; 256];
+    let mut expected = Vec::new();
+    Q565EncodeContext::encode_to_vec(16, 16, &pixels, &mut expected).unwrap();
+
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_auto(16, 16, &pixels, &mut encoded).unwrap();
+    assert_eq!(encoded, expected);
+    assert!(!Q565DecodeContext::decode_header(&encoded).unwrap().0.raw);
+}
+
+#[test]
+fn invalid_raw_images() {
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_auto(16, 16, &noise(256), &mut encoded).unwrap();
+
+    let truncated = &encoded[..encoded.len() - 1];
+    assert!(matches!(
+        Q565DecodeContext::decode_to_vec::<LittleEndian, Rgb565>(truncated),
+        Err(DecodeError::UnexpectedEof)
+    ));
+
+    // the raw flag can't be combined with a color array size
+    encoded[4] |= 1;
+    assert!(matches!(
+        Q565DecodeContext::decode_to_vec::<LittleEndian, Rgb565>(&encoded),
+        Err(DecodeError::UnsupportedFlags)
+    ));
+}
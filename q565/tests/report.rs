@@ -0,0 +1,76 @@
+use image::ImageFormat;
+use q565::{
+    encode::{OpCounts, Q565EncodeContext},
+    stream::{Op, OpReader},
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    HEADER_LEN,
+};
+use std::io::BufReader;
+
+/// Counts the ops of an encoded image by parsing it.
+fn count_ops(data: &[u8]) -> OpCounts {
+    let mut counts = OpCounts::default();
+    for (_, op) in OpReader::new(data) {
+        match op {
+            Op::Index(_) => counts.index += 1,
+            Op::Diff(_) => counts.diff += 1,
+            Op::Luma(..) => counts.luma += 1,
+            Op::DiffIndexed(..) => counts.diff_indexed += 1,
+            Op::Run(_) => counts.run += 1,
+            Op::Rgb565(_) => counts.rgb565 += 1,
+            Op::End => {}
+        }
+    }
+    counts
+}
+
+#[test]
+fn reports_match_output() {
+    for image in std::fs::read_dir("../test_images").unwrap() {
+        let image = image::load(
+            BufReader::new(std::fs::File::open(image.unwrap().path()).unwrap()),
+            ImageFormat::Png,
+        )
+        .unwrap();
+        let (width, height) = (image.width() as u16, image.height() as u16);
+        let pixels: Vec<u16> = image
+            .into_rgb8()
+            .pixels()
+            .map(|p| encode_rgb565_unchecked(rgb888_to_rgb565(p.0)))
+            .collect();
+
+        let mut encoded = Vec::new();
+        let report =
+            Q565EncodeContext::encode_to_vec(width, height, &pixels, &mut encoded).unwrap();
+        assert_eq!(report.bytes_written, encoded.len());
+        assert_eq!(report.ops, count_ops(&encoded[HEADER_LEN..]));
+        assert_eq!(
+            report.ratio,
+            encoded.len() as f32 / (2 * pixels.len()) as f32
+        );
+
+        let mut written = Vec::new();
+        let std_report = Q565EncodeContext::encode(width, height, &pixels, &mut written).unwrap();
+        assert_eq!(std_report, report);
+
+        let iter_report =
+            Q565EncodeContext::encode_iter_to_vec(width, height, &pixels, &mut Vec::new());
+        assert_eq!(iter_report, report);
+    }
+}
+
+#[test]
+fn report_detects_expansion() {
+    // noise: every pixel needs a full RGB565 op
+    let pixels: Vec<u16> = (0..256u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 16) as u16)
+        .collect();
+    let report = Q565EncodeContext::encode_to_vec(16, 16, &pixels, &mut Vec::new()).unwrap();
+    assert!(report.ratio > 1.0);
+
+    let report = Q565EncodeContext::encode_to_vec(16, 16, &[0x1234; 256], &mut Vec::new()).unwrap();
+    assert_eq!(report.ops.total(), 6);
+    assert!(report.ratio < 0.1);
+
+    assert!(Q565EncodeContext::encode_to_vec(16, 15, &pixels, &mut Vec::new()).is_none());
+}
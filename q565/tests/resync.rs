@@ -0,0 +1,80 @@
+mod common;
+
+use common::test_pattern;
+use q565::{
+    byteorder::NativeEndian, decode::Q565DecodeContext, encode::Q565EncodeContext, stream::resync,
+    ColorArraySize, Rgb565,
+};
+
+fn frame(seed: u16, size: ColorArraySize) -> (Vec<u16>, Vec<u8>) {
+    let pixels: Vec<u16> = test_pattern(24, 16).iter().map(|p| p ^ seed).collect();
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec_sized(size, 24, 16, &pixels, &mut encoded).unwrap();
+    (pixels, encoded)
+}
+
+#[test]
+fn finds_next_frame_after_loss() {
+    let (_, first) = frame(0x1111, ColorArraySize::Entries64);
+    let (pixels, second) = frame(0x2222, ColorArraySize::Entries32);
+    let (_, third) = frame(0x3333, ColorArraySize::Entries64);
+
+    // the middle of the first frame got lost
+    let mut received = first[..10].to_vec();
+    received.extend_from_slice(&first[first.len() / 2..]);
+    received.extend_from_slice(&second);
+    received.extend_from_slice(&third);
+
+    let after_loss = 1;
+    let offset = after_loss + resync(&received[after_loss..]).unwrap();
+    assert_eq!(offset, 10 + (first.len() - first.len() / 2));
+
+    let (_, decoded) =
+        Q565DecodeContext::decode_to_vec::<NativeEndian, Rgb565>(&received[offset..]).unwrap();
+    assert!(decoded == pixels);
+
+    let next = offset + 1;
+    assert_eq!(
+        next + resync(&received[next..]).unwrap(),
+        offset + second.len()
+    );
+}
+
+#[test]
+fn accepts_incomplete_frames() {
+    let (_, encoded) = frame(0x4444, ColorArraySize::Entries64);
+    let mut received = b"garbage".to_vec();
+    received.extend_from_slice(&encoded[..encoded.len() / 3]);
+    assert_eq!(resync(&received), Some(7));
+}
+
+#[test]
+fn rejects_implausible_headers() {
+    assert_eq!(resync(&[]), None);
+    assert_eq!(resync(b"no image here at all"), None);
+
+    let (_, encoded) = frame(0x5555, ColorArraySize::Entries64);
+    let ops = &encoded[8..];
+
+    // header claiming fewer pixels than the ops produce
+    let mut small = b"q565\x02\x00\x02\x00".to_vec();
+    small.extend_from_slice(ops);
+    assert_eq!(resync(&small), None);
+
+    // header claiming more pixels than the ops produce before the end marker
+    let mut large = b"q565\x00\x01\x00\x01".to_vec();
+    large.extend_from_slice(ops);
+    assert_eq!(resync(&large), None);
+
+    // zero width
+    let mut empty = b"q565\x00\x00\x10\x00".to_vec();
+    empty.extend_from_slice(ops);
+    assert_eq!(resync(&empty), None);
+
+    // index beyond the 16 entries of the profile
+    let mut profiled = b"q56x\x02\x01\x00\x01\x00".to_vec();
+    profiled.extend_from_slice(&[0x20, 0xFF]);
+    assert_eq!(resync(&profiled), None);
+    profiled[9] = 0x0F;
+    assert_eq!(resync(&profiled), Some(0));
+}
@@ -35,7 +35,8 @@ fn roundtrip() {
             height as u16,
             &input,
             &mut encoded
-        ));
+        )
+        .is_some());
 
         let mut encoded2 = Vec::with_capacity(pixel_count * 2);
         q565::encode::Q565EncodeContext::encode(width as u16, height as u16, &input, &mut encoded2)
@@ -45,12 +46,13 @@ fn roundtrip() {
             "encoding mismatch between encode_to_vec and encode to writer"
         );
         encoded2.clear();
-        assert!(q565::encode::Q565EncodeContext::encode_iter_to_vec(
+        let report = q565::encode::Q565EncodeContext::encode_iter_to_vec(
             width as u16,
             height as u16,
             &input,
             &mut encoded2,
-        ));
+        );
+        assert_eq!(report.bytes_written, encoded2.len());
         assert_eq!(
             encoded, encoded2,
             "encoding mismatch between encode_to_vec and encode_iter_to_vec"
@@ -63,33 +65,38 @@ fn roundtrip() {
             .unwrap();
         assert_eq!(input, decoded_to_vec, "safe decoding failed");
 
-        let mut unsafe_decoded_to_slice = vec![0u16; pixel_count];
-        unsafe {
-            let unsafe_decoded_to_slice_output =
-                q565::decode::UnsafeSliceDecodeOutput::<Rgb565>::new(&mut unsafe_decoded_to_slice);
-            q565::decode::Q565DecodeContext::decode_unchecked::<LittleEndian>(
-                &encoded,
-                unsafe_decoded_to_slice_output,
-            )
-            .unwrap()
-        };
-        assert_eq!(input, unsafe_decoded_to_slice, "unsafe decoding failed");
-
-        let mut streaming_decoded = vec![0; pixel_count];
-        let mut state = q565::decode::streaming_no_header::Q565StreamingDecodeContext::new();
-        let mut streaming_output_buf = &mut streaming_decoded[..];
-        for chunk in encoded[8..].chunks(512) {
-            let pixels_written = unsafe {
-                state.streaming_decode_to_slice_unchecked::<LittleEndian>(
-                    chunk,
-                    streaming_output_buf,
+        #[cfg(not(feature = "forbid-unsafe"))]
+        {
+            let mut unsafe_decoded_to_slice = vec![0u16; pixel_count];
+            unsafe {
+                let unsafe_decoded_to_slice_output =
+                    q565::decode::UnsafeSliceDecodeOutput::<Rgb565>::new(
+                        &mut unsafe_decoded_to_slice,
+                    );
+                q565::decode::Q565DecodeContext::decode_unchecked::<LittleEndian>(
+                    &encoded,
+                    unsafe_decoded_to_slice_output,
                 )
+                .unwrap()
             };
-            streaming_output_buf = &mut streaming_output_buf[pixels_written..];
+            assert_eq!(input, unsafe_decoded_to_slice, "unsafe decoding failed");
+
+            let mut streaming_decoded = vec![0; pixel_count];
+            let mut state = q565::decode::streaming_no_header::Q565StreamingDecodeContext::new();
+            let mut streaming_output_buf = &mut streaming_decoded[..];
+            for chunk in encoded[8..].chunks(512) {
+                let pixels_written = unsafe {
+                    state.streaming_decode_to_slice_unchecked::<LittleEndian>(
+                        chunk,
+                        streaming_output_buf,
+                    )
+                };
+                streaming_output_buf = &mut streaming_output_buf[pixels_written..];
+            }
+            assert_eq!(
+                input, streaming_decoded,
+                "streaming_no_header decoding failed"
+            );
         }
-        assert_eq!(
-            input, streaming_decoded,
-            "streaming_no_header decoding failed"
-        );
     }
 }
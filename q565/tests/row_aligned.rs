@@ -0,0 +1,66 @@
+use image::ImageFormat;
+use q565::{
+    byteorder::LittleEndian,
+    decode::{Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    stream::has_row_aligned_runs,
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    Rgb565,
+};
+use std::io::BufReader;
+
+#[test]
+fn row_aligned_runs_on_corpus() {
+    let (mut total, mut total_aligned) = (0, 0);
+
+    for image in std::fs::read_dir("../test_images").unwrap() {
+        let path = image.unwrap().path();
+        let image = image::load(
+            BufReader::new(std::fs::File::open(&path).unwrap()),
+            ImageFormat::Png,
+        )
+        .unwrap();
+
+        let (width, height) = (image.width() as u16, image.height() as u16);
+        let input: Vec<u16> = image
+            .into_rgb8()
+            .pixels()
+            .map(|p| encode_rgb565_unchecked(rgb888_to_rgb565(p.0)))
+            .collect();
+
+        let mut regular = Vec::new();
+        Q565EncodeContext::encode_to_vec(width, height, &input, &mut regular).unwrap();
+        let mut aligned = Vec::new();
+        Q565EncodeContext::encode_row_aligned_to_vec(width, height, &input, &mut aligned).unwrap();
+
+        assert!(has_row_aligned_runs(&aligned).unwrap());
+        let mut decoded = Vec::new();
+        Q565DecodeContext::decode::<LittleEndian>(
+            &aligned,
+            VecDecodeOutput::<Rgb565>::new(&mut decoded),
+        )
+        .unwrap();
+        assert_eq!(decoded, input, "{}", path.display());
+
+        assert!(aligned.len() >= regular.len());
+        total += regular.len();
+        total_aligned += aligned.len();
+    }
+
+    // the overhead documented for the profile
+    assert!(total_aligned * 1000 < total * 1005);
+}
+
+#[test]
+fn detects_runs_crossing_rows() {
+    // one color: a single run over both rows, or one per row
+    let pixels = [0x1234; 8];
+    let mut regular = Vec::new();
+    Q565EncodeContext::encode_to_vec(4, 2, &pixels, &mut regular).unwrap();
+    let mut aligned = Vec::new();
+    Q565EncodeContext::encode_row_aligned_to_vec(4, 2, &pixels, &mut aligned).unwrap();
+
+    assert!(!has_row_aligned_runs(&regular).unwrap());
+    assert!(has_row_aligned_runs(&aligned).unwrap());
+    assert_eq!(aligned.len(), regular.len() + 1);
+}
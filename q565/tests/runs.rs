@@ -0,0 +1,82 @@
+use q565::{
+    byteorder::LittleEndian,
+    decode::Q565DecodeContext,
+    encode::{encode_fast_rle, fast_rle_max_len, Q565EncodeContext},
+    Rgb565,
+};
+
+/// Runs of lengths around the 16-pixel chunks of the run detection and the longest run op.
+fn runs_image() -> Vec<u16> {
+    let mut pixels = Vec::new();
+    for (i, len) in [1, 2, 15, 16, 17, 31, 32, 33, 61, 62, 63, 124, 125, 1000]
+        .into_iter()
+        .enumerate()
+    {
+        pixels.extend(std::iter::repeat_n(0x1234 * i as u16, len));
+        // a single different pixel in between
+        pixels.push(0xFFFF - i as u16);
+    }
+    pixels.resize(2000, 0);
+    pixels
+}
+
+#[test]
+fn runs_match_pixelwise_encoder() {
+    let pixels = runs_image();
+
+    let mut expected = Vec::new();
+    Q565EncodeContext::encode_iter_to_vec(40, 50, &pixels, &mut expected);
+
+    let mut encoded = Vec::new();
+    assert!(Q565EncodeContext::encode_to_vec(40, 50, &pixels, &mut encoded).is_some());
+    assert_eq!(encoded, expected);
+
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode(40, 50, &pixels, &mut encoded).unwrap();
+    assert_eq!(encoded, expected);
+
+    let mut output = vec![0; fast_rle_max_len(40, 50)];
+    let report = encode_fast_rle(40, 50, &pixels, &mut output).unwrap();
+    let (_, decoded) =
+        Q565DecodeContext::decode_to_vec::<LittleEndian, Rgb565>(&output[..report.bytes_written])
+            .unwrap();
+    assert_eq!(decoded, pixels);
+}
+
+/// Runs that take many maximum-length run ops, written in batches.
+#[test]
+fn long_runs_match_pixelwise_encoder() {
+    let mut pixels = Vec::new();
+    for (i, len) in [62 * 64, 62 * 64 + 1, 62 * 65, 62 * 128 + 61]
+        .into_iter()
+        .enumerate()
+    {
+        pixels.extend(std::iter::repeat_n(0x1234 * i as u16, len));
+        pixels.push(0xFFFF - i as u16);
+    }
+    // a solid 800x480 frame
+    pixels.resize(800 * 480, 0xF800);
+    let (width, height) = (800, 480);
+
+    let mut expected = Vec::new();
+    let expected_report =
+        Q565EncodeContext::encode_iter_to_vec(width, height, &pixels, &mut expected);
+
+    let mut encoded = Vec::new();
+    let report = Q565EncodeContext::encode_to_vec(width, height, &pixels, &mut encoded).unwrap();
+    assert_eq!(encoded, expected);
+    assert_eq!(report, expected_report);
+
+    let mut encoded = Vec::new();
+    let report = Q565EncodeContext::encode(width, height, &pixels, &mut encoded).unwrap();
+    assert_eq!(encoded, expected);
+    assert_eq!(report, expected_report);
+
+    let mut output = vec![0; fast_rle_max_len(width, height)];
+    let report = encode_fast_rle(width, height, &pixels, &mut output).unwrap();
+    assert_eq!(report.ops.run, expected_report.ops.run);
+    let (_, decoded) =
+        Q565DecodeContext::decode_to_vec::<LittleEndian, Rgb565>(&output[..report.bytes_written])
+            .unwrap();
+    assert_eq!(decoded, pixels);
+}
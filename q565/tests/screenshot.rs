@@ -0,0 +1,78 @@
+mod common;
+
+use common::{encode, test_pattern};
+use q565::{
+    encode::StridedPixels,
+    screenshot::{find, send, ScreenshotError, MIN_SCRATCH_LEN, SCREENSHOT_TAG},
+};
+
+const WIDTH: u16 = 23;
+const HEIGHT: u16 = 11;
+const STRIDE: usize = 25;
+
+fn pixels() -> Vec<u16> {
+    test_pattern(STRIDE as u16, HEIGHT)
+}
+
+fn expected(pixels: &[u16]) -> Vec<u8> {
+    let tight: Vec<u16> = pixels
+        .chunks(STRIDE)
+        .flat_map(|row| &row[..usize::from(WIDTH)])
+        .copied()
+        .collect();
+    encode(WIDTH, HEIGHT, &tight)
+}
+
+#[test]
+fn sends_and_finds() {
+    let pixels = pixels();
+    let expected = expected(&pixels);
+    let source = StridedPixels::new(&pixels, WIDTH, HEIGHT, STRIDE).unwrap();
+
+    for scratch_len in [MIN_SCRATCH_LEN, 4, 64, 4096] {
+        // log output and a tag with garbage after it, before the screenshot
+        let mut channel = b"boot ok\nq5ssq56x\xFFfoo".to_vec();
+        let mut scratch = vec![0; scratch_len];
+        let len = send(&source, &mut scratch, |bytes| {
+            assert!(bytes.len() <= scratch_len.max(SCREENSHOT_TAG.len() + 9));
+            channel.extend_from_slice(bytes);
+        })
+        .unwrap();
+        let screenshot_end = channel.len();
+        assert_eq!(len, SCREENSHOT_TAG.len() + expected.len());
+        channel.extend_from_slice(b"more log output");
+
+        let (image, end) = find(&channel).unwrap();
+        assert!(image == expected, "{scratch_len}");
+        assert_eq!(end, screenshot_end);
+        assert_eq!(find(&channel[end..]), None);
+    }
+}
+
+#[test]
+fn waits_for_incomplete_screenshots() {
+    let pixels = pixels();
+    let source = StridedPixels::new(&pixels, WIDTH, HEIGHT, STRIDE).unwrap();
+    let mut channel = Vec::new();
+    send(&source, &mut [0; 16], |bytes| {
+        channel.extend_from_slice(bytes)
+    })
+    .unwrap();
+
+    for len in 0..channel.len() {
+        assert_eq!(find(&channel[..len]), None, "{len}");
+    }
+    assert!(find(&channel).is_some());
+}
+
+#[test]
+fn checks_scratch_len() {
+    let pixels = pixels();
+    let source = StridedPixels::new(&pixels, WIDTH, HEIGHT, STRIDE).unwrap();
+    let mut written = false;
+    assert!(matches!(
+        send(&source, &mut [0; MIN_SCRATCH_LEN - 1], |_| written = true),
+        Err(ScreenshotError::ScratchTooSmall)
+    ));
+    assert!(!written);
+}
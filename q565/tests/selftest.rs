@@ -0,0 +1,18 @@
+use q565::selftest::{run, SelfTestError, SCRATCH_LEN};
+
+#[test]
+fn self_test_passes() {
+    let mut scratch = [0xAA; SCRATCH_LEN + 3];
+    run(&mut scratch).unwrap();
+    // the scratch contents don't matter
+    run(&mut scratch).unwrap();
+}
+
+#[test]
+fn self_test_needs_scratch() {
+    let mut scratch = [0; SCRATCH_LEN - 1];
+    assert!(matches!(
+        run(&mut scratch),
+        Err(SelfTestError::ScratchTooSmall)
+    ));
+}
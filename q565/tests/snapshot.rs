@@ -0,0 +1,130 @@
+mod common;
+
+use common::gradient;
+use q565::{
+    byteorder::{BigEndian, LittleEndian},
+    decode::{Q565DecodeContext, VecDecodeOutput},
+    encode::Q565EncodeContext,
+    snapshot::StateSnapshot,
+    Rgb565,
+};
+
+fn decoded_context() -> Q565DecodeContext {
+    let pixels = gradient(32, 32);
+    let mut encoded = Vec::new();
+    assert!(Q565EncodeContext::encode_to_vec(32, 32, &pixels, &mut encoded).is_some());
+
+    let mut decoder = Q565DecodeContext::new();
+    let mut decoded = Vec::new();
+    decoder
+        .decode_with_state::<LittleEndian>(&encoded, VecDecodeOutput::<Rgb565>::new(&mut decoded))
+        .unwrap();
+    assert_eq!(decoded, pixels);
+    decoder
+}
+
+#[test]
+fn snapshot_text_round_trips() {
+    let decoder = decoded_context();
+    let snapshot = decoder.snapshot();
+    assert_eq!(snapshot.prev, decoder.prev);
+    assert_eq!(snapshot.color_array(), decoder.arr);
+
+    let text = snapshot.to_string();
+    assert!(text.starts_with(&format!("prev: {:#06x}\narr[0..8]: ", decoder.prev)));
+    assert_eq!(text.lines().count(), 1 + 64 / 8);
+    assert_eq!(text.parse(), Ok(snapshot));
+
+    let restored = Q565DecodeContext::<64>::restore(&snapshot).unwrap();
+    assert_eq!((restored.prev, restored.arr), (decoder.prev, decoder.arr));
+    // the color array size has to match
+    assert!(Q565DecodeContext::<32>::restore(&snapshot).is_none());
+
+    let small = Q565DecodeContext::<16>::new_sized().snapshot();
+    assert_eq!(small.color_array().len(), 16);
+    assert_eq!(small.to_string().parse(), Ok(small));
+}
+
+#[test]
+fn snapshot_rejects_malformed_text() {
+    let valid = Q565DecodeContext::<16>::new_sized().snapshot().to_string();
+    assert!(valid.parse::<StateSnapshot>().is_ok());
+
+    for invalid in [
+        // no previous pixel
+        valid.lines().skip(1).collect::<Vec<_>>().join("\n"),
+        // 8 entries
+        valid.lines().take(2).collect::<Vec<_>>().join("\n"),
+        // a row is missing
+        valid.replacen("arr[0..8]", "arr[8..16]", 1),
+        valid.replacen("0x0000", "0000", 1),
+        valid.replacen(" 0x0000", "", 1),
+        valid + "\nfoo: 0x0000",
+    ] {
+        assert!(invalid.parse::<StateSnapshot>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn snapshot_from_memory_dump() {
+    let decoder = decoded_context();
+    let dump: Vec<u8> = [decoder.prev]
+        .iter()
+        .chain(&decoder.arr)
+        .flat_map(|value| value.to_be_bytes())
+        .collect();
+    assert_eq!(dump.len(), 130);
+
+    assert_eq!(
+        StateSnapshot::from_bytes::<BigEndian>(&dump),
+        Some(decoder.snapshot())
+    );
+    assert_ne!(
+        StateSnapshot::from_bytes::<LittleEndian>(&dump),
+        Some(decoder.snapshot())
+    );
+    assert_eq!(
+        StateSnapshot::from_bytes::<BigEndian>(&dump[..34])
+            .unwrap()
+            .color_array(),
+        &decoder.arr[..16]
+    );
+    assert!(StateSnapshot::from_bytes::<BigEndian>(&dump[..128]).is_none());
+    assert!(StateSnapshot::from_bytes::<BigEndian>(&dump[..1]).is_none());
+}
+
+#[test]
+fn restored_encoder_continues_identically() {
+    let pixels = gradient(32, 64);
+    let (first, second) = pixels.split_at(1024);
+
+    let mut encoder = Q565EncodeContext::new();
+    let mut encoded = Vec::new();
+    assert!(encoder
+        .encode_to_vec_with_state(32, 32, first, &mut encoded)
+        .is_some());
+    let snapshot = encoder.snapshot();
+    assert_eq!(decoded_context().snapshot(), snapshot);
+
+    let mut expected = Vec::new();
+    encoder.encode_pixels_to_vec(second, &mut expected);
+    let mut continued = Vec::new();
+    Q565EncodeContext::<64>::restore(&snapshot)
+        .unwrap()
+        .encode_pixels_to_vec(second, &mut continued);
+    assert_eq!(continued, expected);
+}
+
+#[test]
+fn changed_entries() {
+    let mut decoder = Q565DecodeContext::new();
+    let before = decoder.snapshot();
+    decoder.apply_op(q565::stream::Op::Rgb565(0x07E0));
+    let after = decoder.snapshot();
+
+    let changes: Vec<_> = after.changed_entries(&before).collect();
+    assert_eq!(changes.len(), 1);
+    let (index, new, old) = changes[0];
+    assert_eq!((decoder.arr[index], new, old), (0x07E0, 0x07E0, 0));
+    assert_eq!(after.changed_entries(&after).count(), 0);
+}
@@ -0,0 +1,73 @@
+use image::ImageFormat;
+use q565::{
+    byteorder::LittleEndian,
+    decode::{Q565DecodeContext, SpanDecodeOutput, SpanSink},
+    encode::Q565EncodeContext,
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    Rgb565,
+};
+use std::io::BufReader;
+
+#[derive(Default)]
+struct Spans {
+    pixels: Vec<u16>,
+    spans: Vec<(u16, usize)>,
+}
+
+impl SpanSink<u16> for Spans {
+    fn pixel(&mut self, color: u16) {
+        self.pixels.push(color);
+    }
+
+    fn span(&mut self, color: u16, count: usize) {
+        assert!(count >= 2);
+        self.spans.push((color, count));
+        self.pixels.extend(std::iter::repeat_n(color, count));
+    }
+}
+
+#[test]
+fn decode_to_spans() {
+    for image in std::fs::read_dir("../test_images").unwrap() {
+        let image = image::load(
+            BufReader::new(std::fs::File::open(image.unwrap().path()).unwrap()),
+            ImageFormat::Png,
+        )
+        .unwrap();
+
+        let (width, height) = (image.width() as u16, image.height() as u16);
+        let input: Vec<u16> = image
+            .into_rgb8()
+            .pixels()
+            .map(|p| encode_rgb565_unchecked(rgb888_to_rgb565(p.0)))
+            .collect();
+
+        let mut encoded = Vec::new();
+        assert!(Q565EncodeContext::encode_to_vec(width, height, &input, &mut encoded).is_some());
+
+        let mut output = SpanDecodeOutput::<Rgb565, _>::new(Spans::default());
+        let (_, pixels_written) =
+            Q565DecodeContext::decode::<LittleEndian>(&encoded, &mut output).unwrap();
+        let spans = output.finish();
+
+        assert_eq!(pixels_written, input.len());
+        assert_eq!(spans.pixels, input);
+    }
+}
+
+#[test]
+fn spans_are_merged() {
+    // a single pixel, then a run of it, split into two run ops
+    let mut input = vec![0x1234u16; 100];
+    input.push(0x4321);
+
+    let mut encoded = Vec::new();
+    assert!(Q565EncodeContext::encode_to_vec(101, 1, &input, &mut encoded).is_some());
+
+    let mut output = SpanDecodeOutput::<Rgb565, _>::new(Spans::default());
+    Q565DecodeContext::decode::<LittleEndian>(&encoded, &mut output).unwrap();
+    let spans = output.finish();
+
+    assert_eq!(spans.spans, [(0x1234, 63), (0x1234, 37)]);
+    assert_eq!(spans.pixels, input);
+}
@@ -0,0 +1,96 @@
+use q565::{
+    consts::{OpInfo, MAX_OP_LEN, MAX_OP_PIXELS, OPS},
+    encode::Q565EncodeContext,
+    max_stream_len, min_stream_len,
+    stream::Op,
+};
+
+fn tag_mask(op: &OpInfo) -> u8 {
+    !(0xffu16 >> op.tag_bits) as u8
+}
+
+fn const_name(op: Op) -> &'static str {
+    match op {
+        Op::Index(_) => "Q565_OP_INDEX",
+        Op::Diff(_) => "Q565_OP_DIFF",
+        Op::Luma(..) => "Q565_OP_LUMA",
+        Op::DiffIndexed(..) => "Q565_OP_DIFF_INDEXED",
+        Op::Run(_) => "Q565_OP_RUN",
+        Op::Rgb565(_) => "Q565_OP_RGB565",
+        Op::End => "Q565_OP_END",
+    }
+}
+
+#[test]
+fn ops_match_decoder() {
+    for op in OPS {
+        let bits =
+            usize::from(op.tag_bits) + op.fields.iter().map(|f| usize::from(f.bits)).sum::<usize>();
+        assert!(bits.is_multiple_of(8), "{}", op.name);
+        assert_eq!(op.tag & !tag_mask(op), 0, "{}", op.name);
+
+        let mut bytes = vec![0u8; op.encoded_len()];
+        bytes[0] = op.tag;
+        let parsed = Op::parse(&bytes).unwrap();
+        assert_eq!(const_name(parsed), op.name);
+        assert_eq!(parsed.encoded_len(), op.encoded_len(), "{}", op.name);
+    }
+}
+
+#[test]
+fn first_byte_selects_longest_tag() {
+    // e.g. the RUN tag also matches the RGB565 and END bytes, which take precedence
+    for byte in 0..=255u8 {
+        let op = OPS
+            .iter()
+            .filter(|op| byte & tag_mask(op) == op.tag)
+            .max_by_key(|op| op.tag_bits)
+            .unwrap();
+        assert_eq!(
+            OPS.iter()
+                .filter(|other| other.tag_bits == op.tag_bits
+                    && byte & tag_mask(other) == other.tag)
+                .count(),
+            1
+        );
+
+        let parsed = Op::parse(&[byte, 0, 0]).unwrap();
+        assert_eq!(const_name(parsed), op.name, "{byte:#010b}");
+    }
+}
+
+#[test]
+fn op_bounds() {
+    assert_eq!(OPS.iter().map(OpInfo::encoded_len).max(), Some(MAX_OP_LEN));
+    let longest_run = (0..=255u8)
+        .filter_map(|byte| Op::parse(&[byte, 0, 0]))
+        .map(|op| op.pixel_count())
+        .max();
+    assert_eq!(longest_run, Some(MAX_OP_PIXELS));
+}
+
+#[test]
+fn stream_bounds() {
+    for (width, height) in [(1, 1), (62, 1), (63, 1), (100, 37)] {
+        let pixels = usize::from(width) * usize::from(height);
+
+        // the initial pixel is black, so a black image is all runs
+        let mut black = Vec::new();
+        Q565EncodeContext::encode_to_vec(width, height, &vec![0; pixels], &mut black).unwrap();
+        assert_eq!(black.len(), min_stream_len(width, height));
+
+        // alternating colors that are too far apart for anything but RGB565 ops
+        let noise: Vec<u16> = (0..pixels)
+            .map(|i| if i % 2 == 0 { 0xF81F } else { 0x07E0 } ^ (i as u16 & 0x0801))
+            .collect();
+        let mut encoded = Vec::new();
+        Q565EncodeContext::encode_to_vec(width, height, &noise, &mut encoded).unwrap();
+        assert!(encoded.len() <= max_stream_len(width, height));
+        let mut raw = Vec::new();
+        Q565EncodeContext::encode_auto(width, height, &noise, &mut raw).unwrap();
+        assert!(raw.len() <= max_stream_len(width, height));
+    }
+
+    assert_eq!(min_stream_len(63, 1), 8 + 2 + 1);
+    assert_eq!(max_stream_len(2, 2), 9 + 12 + 1);
+}
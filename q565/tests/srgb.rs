@@ -0,0 +1,102 @@
+use q565::{
+    byteorder::{BigEndian, NativeEndian},
+    decode::Q565DecodeContext,
+    encode::Q565EncodeContext,
+    srgb::{self, LinearRgb},
+    utils::{decode_565, rgb565_to_rgb888, rgb888_to_rgb565, rgb888_to_rgb565_with, Quantization},
+};
+
+fn to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn level_linear(level: u8, max: u8) -> f64 {
+    to_linear(f64::from(level) / f64::from(max))
+}
+
+#[test]
+fn picks_the_nearest_level_in_linear_light() {
+    for value in 0..=u8::MAX {
+        let linear = to_linear(f64::from(value) / 255.0);
+        let [r, g, _] = srgb::rgb888_to_rgb565([value, value, value]);
+        for (level, max) in [(r, 31), (g, 63)] {
+            let error = (level_linear(level, max) - linear).abs();
+            for other in 0..=max {
+                assert!(
+                    error <= (level_linear(other, max) - linear).abs(),
+                    "{value}: {level} vs {other}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn differs_from_rounding_only_by_one_level() {
+    let mut differing = 0;
+    for value in 0..=u8::MAX {
+        let pixel = [value, value, value];
+        let linear = srgb::rgb888_to_rgb565(pixel);
+        let rounded = rgb888_to_rgb565(pixel);
+        for (linear, rounded) in linear.into_iter().zip(rounded) {
+            assert!(linear.abs_diff(rounded) <= 1, "{value}");
+        }
+        differing += usize::from(linear != rounded);
+
+        assert_eq!(rgb888_to_rgb565_with(pixel, Quantization::Linear), linear);
+    }
+    assert!(differing > 0);
+}
+
+#[test]
+fn rgb565_round_trips() {
+    for pixel in 0..=u16::MAX {
+        let rgb565 = decode_565(pixel);
+        assert_eq!(srgb::rgb888_to_rgb565(rgb565_to_rgb888(rgb565)), rgb565);
+    }
+}
+
+#[test]
+fn linear_intensities() {
+    assert_eq!(srgb::rgb565_to_linear([0, 0, 0]), [0; 3]);
+    assert_eq!(srgb::rgb565_to_linear([31, 63, 31]), [0xFFFF; 3]);
+
+    let mut previous = [0; 3];
+    for level in 1..=31 {
+        let linear = srgb::rgb565_to_linear([level, level * 2, level]);
+        assert!(linear.iter().zip(previous).all(|(&l, p)| l > p), "{level}");
+        let expected = (level_linear(level, 31) * 65535.0).round() as u16;
+        assert_eq!(linear[0], expected);
+        previous = linear;
+    }
+}
+
+#[test]
+fn decodes_to_linear_rgb() {
+    let pixels = [0x0000, 0xF800, 0x8410, 0xFFFF];
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(4, 1, &pixels, &mut encoded).unwrap();
+
+    let (_, native) =
+        Q565DecodeContext::decode_to_vec::<NativeEndian, LinearRgb>(&encoded).unwrap();
+    let expected: Vec<[u16; 3]> = pixels
+        .iter()
+        .map(|&pixel| srgb::rgb565_to_linear(decode_565(pixel)))
+        .collect();
+    assert_eq!(native, expected);
+
+    let (_, big_endian) =
+        Q565DecodeContext::decode_to_vec::<BigEndian, LinearRgb>(&encoded).unwrap();
+    for (big_endian, native) in big_endian.iter().zip(&native) {
+        assert_eq!(big_endian.map(u16::from_be), *native);
+    }
+}
+
+#[test]
+fn parses_linear() {
+    assert_eq!("linear".parse(), Ok(Quantization::Linear));
+}
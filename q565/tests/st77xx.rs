@@ -0,0 +1,128 @@
+use q565::{
+    encode::Q565EncodeContext,
+    st77xx::{image_to_commands, update_to_commands, CommandSink, CASET, RAMWR, RASET},
+    update::encode_update,
+    Rect,
+};
+
+/// Simulates a panel's frame memory.
+struct Panel {
+    width: usize,
+    memory: Vec<u16>,
+    command: u8,
+    params: Vec<u8>,
+    window: [usize; 4],
+    cursor: (usize, usize),
+    data_blocks: usize,
+}
+
+impl Panel {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            memory: vec![0; width * height],
+            command: 0,
+            params: Vec::new(),
+            window: [0; 4],
+            cursor: (0, 0),
+            data_blocks: 0,
+        }
+    }
+}
+
+impl CommandSink for Panel {
+    fn command(&mut self, command: u8) {
+        self.command = command;
+        self.params.clear();
+        if command == RAMWR {
+            self.cursor = (self.window[0], self.window[2]);
+        }
+    }
+
+    fn data(&mut self, data: &[u8]) {
+        match self.command {
+            CASET | RASET => {
+                self.params.extend_from_slice(data);
+                let start = u16::from_be_bytes([self.params[0], self.params[1]]) as usize;
+                let end = u16::from_be_bytes([self.params[2], self.params[3]]) as usize;
+                let offset = if self.command == CASET { 0 } else { 2 };
+                self.window[offset] = start;
+                self.window[offset + 1] = end;
+            }
+            RAMWR => {
+                assert_eq!(data.len() % 2, 0);
+                self.data_blocks += 1;
+                for pixel in data.chunks_exact(2) {
+                    let (x, y) = self.cursor;
+                    self.memory[y * self.width + x] = u16::from_be_bytes([pixel[0], pixel[1]]);
+                    self.cursor = if x == self.window[1] {
+                        (self.window[0], y + 1)
+                    } else {
+                        (x + 1, y)
+                    };
+                }
+            }
+            command => panic!("unexpected command {command:#x}"),
+        }
+    }
+}
+
+#[test]
+fn draw_image() {
+    let pixels: Vec<u16> = (0..12 * 5u16).map(|i| i.wrapping_mul(997)).collect();
+    let mut encoded = Vec::new();
+    assert!(Q565EncodeContext::encode_to_vec(12, 5, &pixels, &mut encoded).is_some());
+
+    let mut panel = Panel::new(20, 10);
+    let mut buffer = [0u8; 15];
+    let count = image_to_commands(&encoded, 3, 4, &mut panel, &mut buffer).unwrap();
+    assert_eq!(count, pixels.len());
+    // 7 pixels per block
+    assert_eq!(panel.data_blocks, pixels.len().div_ceil(7));
+    assert_eq!(panel.window, [3, 14, 4, 8]);
+
+    for y in 0..10 {
+        for x in 0..20 {
+            let expected = if (3..15).contains(&x) && (4..9).contains(&y) {
+                pixels[(y - 4) * 12 + (x - 3)]
+            } else {
+                0
+            };
+            assert_eq!(panel.memory[y * 20 + x], expected, "at {x}, {y}");
+        }
+    }
+}
+
+#[test]
+fn draw_update() {
+    let (width, height) = (32u16, 16u16);
+    let previous = vec![0u16; 512];
+    let mut current = previous.clone();
+    current[2 * 32 + 4..2 * 32 + 10].fill(0xF800);
+    current[10 * 32..11 * 32].fill(0x07E0);
+
+    let dirty = [
+        Rect {
+            x: 4,
+            y: 2,
+            width: 6,
+            height: 1,
+        },
+        Rect {
+            x: 0,
+            y: 10,
+            width: 32,
+            height: 1,
+        },
+    ];
+    let mut update = Vec::new();
+    encode_update(&previous, &current, width, height, &dirty, &mut update).unwrap();
+
+    let mut panel = Panel::new(32, 16);
+    let mut buffer = [0u8; 64];
+    assert_eq!(
+        update_to_commands(&update, &mut panel, &mut buffer).unwrap(),
+        2
+    );
+    assert_eq!(panel.memory, current);
+}
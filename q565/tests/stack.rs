@@ -0,0 +1,89 @@
+#[cfg(not(feature = "forbid-unsafe"))]
+use image::ImageFormat;
+#[cfg(not(feature = "forbid-unsafe"))]
+use q565::{
+    byteorder::LittleEndian,
+    decode::{
+        streaming_no_header::Q565StreamingDecodeContext, Q565DecodeContext, UnsafeSliceDecodeOutput,
+    },
+    encode::Q565EncodeContext,
+    utils::{encode_rgb565_unchecked, rgb888_to_rgb565},
+    Rgb565, Rgb888,
+};
+#[cfg(not(feature = "forbid-unsafe"))]
+use std::io::BufReader;
+
+/// Stack available to the decoders, including the test harness' share of the thread's stack. Even
+/// unoptimized, the decode paths only need a fixed amount of stack, independent of the image.
+#[cfg(not(feature = "forbid-unsafe"))]
+const STACK_SIZE: usize = 16 * 1024;
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn decode_with_bounded_stack() {
+    let images: Vec<(usize, Vec<u8>)> = std::fs::read_dir("../test_images")
+        .unwrap()
+        .map(|image| {
+            let image = image::load(
+                BufReader::new(std::fs::File::open(image.unwrap().path()).unwrap()),
+                ImageFormat::Png,
+            )
+            .unwrap();
+
+            let (width, height) = (image.width() as u16, image.height() as u16);
+            let pixels: Vec<u16> = image
+                .into_rgb8()
+                .pixels()
+                .map(|p| encode_rgb565_unchecked(rgb888_to_rgb565(p.0)))
+                .collect();
+
+            let mut encoded = Vec::new();
+            assert!(
+                Q565EncodeContext::encode_to_vec(width, height, &pixels, &mut encoded).is_some()
+            );
+            (pixels.len(), encoded)
+        })
+        .collect();
+
+    // all buffers are allocated up front, so that only the decoders run on the small stack
+    let mut rgb565 = images
+        .iter()
+        .map(|(len, _)| vec![0u16; *len])
+        .collect::<Vec<_>>();
+    let mut rgb888 = images
+        .iter()
+        .map(|(len, _)| vec![[0u8; 3]; *len])
+        .collect::<Vec<_>>();
+
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            for (((_, encoded), rgb565), rgb888) in images.iter().zip(&mut rgb565).zip(&mut rgb888)
+            {
+                unsafe {
+                    Q565DecodeContext::decode::<LittleEndian>(
+                        encoded,
+                        UnsafeSliceDecodeOutput::<Rgb565>::new(rgb565),
+                    )
+                    .unwrap();
+                    Q565DecodeContext::decode::<LittleEndian>(
+                        encoded,
+                        UnsafeSliceDecodeOutput::<Rgb888>::new(rgb888),
+                    )
+                    .unwrap();
+                    Q565DecodeContext::decode_unchecked::<LittleEndian>(
+                        encoded,
+                        UnsafeSliceDecodeOutput::<Rgb565>::new(rgb565),
+                    )
+                    .unwrap();
+
+                    let mut state = Q565StreamingDecodeContext::new();
+                    state
+                        .streaming_decode_to_slice_unchecked::<LittleEndian>(&encoded[8..], rgb565);
+                }
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -0,0 +1,224 @@
+#[cfg(not(feature = "forbid-unsafe"))]
+mod common;
+
+#[cfg(not(feature = "forbid-unsafe"))]
+use common::{encode_ops, noise, test_pattern};
+#[cfg(not(feature = "forbid-unsafe"))]
+use q565::{byteorder::LittleEndian, decode::streaming_no_header::Q565StreamingDecodeContext};
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn bytes_consumed_stops_at_end_marker() {
+    let pixels = noise(100);
+    let mut data = encode_ops(pixels.len() as u16, 1, &pixels);
+    let image_len = data.len();
+    data.extend_from_slice(b"next frame");
+
+    let mut state = Q565StreamingDecodeContext::new();
+    let mut decoded = vec![0u16; pixels.len()];
+    let mut input = &data[..];
+    let mut output_idx = 0;
+    let mut consumed = 0;
+    // chunks that don't line up with the ops, or the end of the image
+    while consumed < image_len {
+        let chunk = &input[..input.len().min(7)];
+        let progress = unsafe {
+            state.streaming_decode_to_slice_with_progress_unchecked::<LittleEndian>(
+                chunk,
+                &mut decoded[output_idx..],
+            )
+        };
+        output_idx += progress.pixels_written;
+        consumed += progress.bytes_consumed;
+        input = &input[progress.bytes_consumed..];
+
+        if progress.bytes_consumed < chunk.len() {
+            break;
+        }
+    }
+
+    assert_eq!(consumed, image_len);
+    assert_eq!(input, b"next frame");
+    assert_eq!(output_idx, pixels.len());
+    assert_eq!(decoded, pixels);
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn finished_after_end_marker() {
+    let pixels = [0x1234u16, 0x1234, 0xF00F];
+    let data = encode_ops(pixels.len() as u16, 1, &pixels);
+
+    let mut state = Q565StreamingDecodeContext::new();
+    let mut decoded = [0u16; 3];
+    let mut output_idx = 0;
+    for (i, byte) in data.iter().enumerate() {
+        assert!(!state.is_finished());
+        output_idx += unsafe {
+            state.streaming_decode_to_slice_unchecked::<LittleEndian>(
+                std::slice::from_ref(byte),
+                &mut decoded[output_idx..],
+            )
+        };
+        assert_eq!(state.is_finished(), i == data.len() - 1);
+    }
+    assert_eq!(decoded, pixels);
+
+    // anything after the end marker is ignored
+    let progress = unsafe {
+        state.streaming_decode_to_slice_with_progress_unchecked::<LittleEndian>(
+            &[0x00, 0x40],
+            &mut [],
+        )
+    };
+    assert_eq!(progress.bytes_consumed, 0);
+    assert_eq!(progress.pixels_written, 0);
+    assert!(state.is_finished());
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn decode_until_finished() {
+    let pixels = test_pattern(1000, 1);
+    let encoded = encode_ops(pixels.len() as u16, 1, &pixels);
+
+    // the caller only needs to know the maximum image size, not the exact pixel count
+    let mut state = Q565StreamingDecodeContext::new();
+    let mut decoded = vec![0u16; 4096];
+    let mut output_idx = 0;
+    let mut chunks = encoded.chunks(16);
+    loop {
+        let progress = unsafe {
+            state.streaming_decode_to_slice_with_progress_unchecked::<LittleEndian>(
+                chunks.next().unwrap(),
+                &mut decoded[output_idx..],
+            )
+        };
+        output_idx += progress.pixels_written;
+        if progress.finished {
+            break;
+        }
+    }
+
+    assert!(chunks.next().is_none());
+    assert_eq!(decoded[..output_idx], pixels);
+}
+
+/// Pixels with runs longer than the line buffers of the tests, and a mix of the other ops.
+#[cfg(not(feature = "forbid-unsafe"))]
+fn runs_and_noise() -> Vec<u16> {
+    let mut x = 0x2545_F491u32;
+    (0..1200)
+        .map(|i| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            match i % 300 {
+                0..100 => 0x07E0,
+                100..200 => (x as u16) & 0x18E3,
+                _ => x as u16,
+            }
+        })
+        .collect()
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn checked_decode_fills_line_buffers() {
+    let pixels = runs_and_noise();
+    let encoded = encode_ops(pixels.len() as u16, 1, &pixels);
+
+    let mut state = Q565StreamingDecodeContext::new();
+    let mut decoded = Vec::new();
+    let mut line = [0u16; 7];
+    let mut line_len = 0;
+    let mut full_lines = 0;
+    for chunk in encoded.chunks(5) {
+        let mut chunk = chunk;
+        loop {
+            let progress =
+                state.streaming_decode_to_slice::<LittleEndian>(chunk, &mut line[line_len..]);
+            line_len += progress.pixels_written;
+            chunk = &chunk[progress.bytes_consumed..];
+            if !progress.output_full {
+                assert!(chunk.is_empty() || progress.finished);
+                break;
+            }
+
+            // runs are split, so the line is always filled completely
+            assert_eq!(line_len, line.len());
+            decoded.extend_from_slice(&line);
+            line_len = 0;
+            full_lines += 1;
+        }
+    }
+    decoded.extend_from_slice(&line[..line_len]);
+
+    assert!(state.is_finished());
+    assert!(full_lines > 0);
+    assert_eq!(decoded, pixels);
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn checked_decode_finishes_into_exact_output() {
+    let pixels = runs_and_noise();
+    let encoded = encode_ops(pixels.len() as u16, 1, &pixels);
+
+    let mut state = Q565StreamingDecodeContext::new();
+    let mut decoded = vec![0u16; pixels.len()];
+    let progress = state.streaming_decode_to_slice::<LittleEndian>(&encoded, &mut decoded);
+    assert_eq!(progress.bytes_consumed, encoded.len());
+    assert_eq!(progress.pixels_written, pixels.len());
+    assert!(progress.finished && !progress.output_full);
+    assert_eq!(decoded, pixels);
+
+    // one pixel short
+    let mut state = Q565StreamingDecodeContext::new();
+    let progress = state.streaming_decode_to_slice::<LittleEndian>(&encoded, &mut decoded[1..]);
+    assert_eq!(progress.pixels_written, pixels.len() - 1);
+    assert!(!progress.finished && progress.output_full);
+    let rest = &encoded[progress.bytes_consumed..];
+    let progress = state.streaming_decode_to_slice::<LittleEndian>(rest, &mut decoded[..1]);
+    assert_eq!(progress.pixels_written, 1);
+    assert!(progress.finished && !progress.output_full);
+}
+
+#[test]
+#[cfg(not(feature = "forbid-unsafe"))]
+fn checked_decode_of_untrusted_input() {
+    let mut x = 0x9E37_79B9u32;
+    let garbage: Vec<u8> = (0..4096)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            // no end marker, so that all of it is decoded
+            (x as u8).min(0xFE)
+        })
+        .collect();
+
+    let mut state = Q565StreamingDecodeContext::new();
+    let mut output = [0u16; 100];
+    let mut input = &garbage[..];
+    let mut total = 0;
+    while !input.is_empty() {
+        let progress = state.streaming_decode_to_slice_budgeted::<LittleEndian>(
+            input,
+            &mut output[total % 100..],
+            16,
+        );
+        assert!(progress.pixels_written <= 100 - total % 100);
+        total += progress.pixels_written;
+        input = &input[progress.bytes_consumed..];
+    }
+    assert!(total > garbage.len() / 3);
+
+    // the same pixels as the unchecked decoder with a large enough output
+    let mut expected = vec![0u16; garbage.len() * 62];
+    let written = unsafe {
+        Q565StreamingDecodeContext::new()
+            .streaming_decode_to_slice_unchecked::<LittleEndian>(&garbage, &mut expected)
+    };
+    assert_eq!(written, total);
+}
@@ -0,0 +1,128 @@
+use q565::{
+    byteorder::LittleEndian,
+    decode::{DecodeError, DownscaleFactor, MiniDecoder, Q565DecodeContext, VecDecodeOutput},
+    ColorArraySize, HeaderInfo, Rgb565,
+};
+
+/// A 4x4 image whose data keeps going with runs after the last pixel.
+fn amplifying_image(header: &HeaderInfo, runs: usize) -> Vec<u8> {
+    let (header, len) = header.to_bytes();
+    let mut data = header[..len].to_vec();
+    // 16 pixels are exactly the image...
+    data.push(0b1100_1111);
+    // ...everything else is too many
+    data.extend(std::iter::repeat_n(0b1111_1101, runs));
+    data.push(0xFF);
+    data
+}
+
+#[test]
+fn checked_decoders_reject_extra_pixels() {
+    let header = HeaderInfo {
+        width: 4,
+        height: 4,
+        color_array_size: ColorArraySize::Entries64,
+        raw: false,
+    };
+
+    let valid = amplifying_image(&header, 0);
+    let mut output = Vec::new();
+    Q565DecodeContext::decode::<LittleEndian>(&valid, VecDecodeOutput::<Rgb565>::new(&mut output))
+        .unwrap();
+    assert_eq!(output.len(), 16);
+
+    // 1 KiB of input would produce 62 KiB of pixels
+    let data = amplifying_image(&header, 1024);
+    let mut output = Vec::new();
+    assert!(matches!(
+        Q565DecodeContext::decode::<LittleEndian>(
+            &data,
+            VecDecodeOutput::<Rgb565>::new(&mut output)
+        ),
+        Err(DecodeError::TooManyPixels)
+    ));
+    assert!(output.len() <= 16);
+
+    // a single pixel op after the last pixel
+    let mut data = valid.clone();
+    data.insert(data.len() - 1, 0b0110_1010);
+    assert!(matches!(
+        Q565DecodeContext::decode_to_vec::<LittleEndian, Rgb565>(&data),
+        Err(DecodeError::TooManyPixels)
+    ));
+
+    let no_array = HeaderInfo {
+        color_array_size: ColorArraySize::NoArray,
+        ..header
+    };
+    let data = amplifying_image(&no_array, 1024);
+    let mut output = Vec::new();
+    assert!(matches!(
+        MiniDecoder::decode::<LittleEndian>(&data, VecDecodeOutput::<Rgb565>::new(&mut output)),
+        Err(DecodeError::TooManyPixels)
+    ));
+    assert!(output.len() <= 16);
+}
+
+#[test]
+fn huge_headers_dont_reserve_up_front() {
+    let header = HeaderInfo {
+        width: u16::MAX,
+        height: u16::MAX,
+        color_array_size: ColorArraySize::Entries64,
+        raw: false,
+    };
+
+    // a few runs, far from the 4 Gpx the header claims
+    let data = amplifying_image(&header, 4);
+    let mut output = Vec::new();
+    assert!(matches!(
+        Q565DecodeContext::decode::<LittleEndian>(
+            &data,
+            VecDecodeOutput::<Rgb565>::new(&mut output)
+        ),
+        Err(DecodeError::MissingData)
+    ));
+    assert!(output.capacity() <= data.len() * 62);
+
+    let mut output = Vec::new();
+    assert!(matches!(
+        Q565DecodeContext::decode_downscaled::<LittleEndian, Rgb565>(
+            &data,
+            DownscaleFactor::Half,
+            &mut output
+        ),
+        Err(DecodeError::MissingData)
+    ));
+    assert!(output.capacity() <= data.len() * 62 / 4);
+
+    let raw = HeaderInfo {
+        raw: true,
+        ..header
+    };
+    let (bytes, len) = raw.to_bytes();
+    let mut data = bytes[..len].to_vec();
+    data.extend_from_slice(&[0x12; 64]);
+    let mut output = Vec::new();
+    assert!(matches!(
+        Q565DecodeContext::decode::<LittleEndian>(
+            &data,
+            VecDecodeOutput::<Rgb565>::new(&mut output)
+        ),
+        Err(DecodeError::UnexpectedEof)
+    ));
+    assert_eq!(output.capacity(), 0);
+
+    let no_array = HeaderInfo {
+        color_array_size: ColorArraySize::NoArray,
+        ..header
+    };
+    let data = amplifying_image(&no_array, 4);
+    let mut output = Vec::new();
+    assert!(MiniDecoder::decode::<LittleEndian>(
+        &data,
+        VecDecodeOutput::<Rgb565>::new(&mut output)
+    )
+    .is_err());
+    assert!(output.capacity() <= data.len() * 62);
+}
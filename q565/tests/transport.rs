@@ -0,0 +1,129 @@
+mod common;
+
+use common::{gradient, noise};
+use q565::{
+    byteorder::LittleEndian,
+    decode::DecodeError,
+    encode::{Q565EncodeContext, StridedPixels},
+    transport::{send_frame, FrameReceiver, FrameTransport, Loopback, TransportError},
+    Rgb565,
+};
+use std::net::{TcpListener, TcpStream};
+
+/// Hands out the received bytes one at a time.
+struct Trickle(Loopback);
+
+impl FrameTransport for Trickle {
+    type Error = core::convert::Infallible;
+
+    fn send_chunk(&mut self, chunk: &[u8]) -> Result<(), Self::Error> {
+        self.0.send_chunk(chunk)
+    }
+
+    fn recv_chunk(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let len = buf.len().min(1);
+        self.0.recv_chunk(&mut buf[..len])
+    }
+}
+
+#[test]
+fn frames_back_to_back() {
+    let first = gradient(40, 30);
+    let second = gradient(7, 3);
+    let mut raw = Vec::new();
+    let raw_pixels = noise(64);
+    Q565EncodeContext::encode_auto(8, 8, &raw_pixels, &mut raw).unwrap();
+
+    let mut loopback = Trickle(Loopback::new());
+    let sent = send_frame(
+        &mut loopback,
+        &StridedPixels::new(&first, 40, 30, 40).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(sent, loopback.0.len());
+    send_frame(
+        &mut loopback,
+        &StridedPixels::new(&second, 7, 3, 7).unwrap(),
+    )
+    .unwrap();
+    loopback.send_chunk(&raw).unwrap();
+
+    let mut receiver = FrameReceiver::new(loopback);
+    let (header, pixels) = receiver.recv_frame::<LittleEndian, Rgb565>().unwrap();
+    assert_eq!((header.width, header.height), (40, 30));
+    assert_eq!(pixels, first);
+    assert_eq!(
+        receiver.recv_frame::<LittleEndian, Rgb565>().unwrap().1,
+        second
+    );
+    assert_eq!(receiver.recv_encoded().unwrap(), raw);
+    assert!(matches!(
+        receiver.recv_encoded(),
+        Err(TransportError::Closed)
+    ));
+}
+
+#[test]
+fn rejects_invalid_frames() {
+    let pixels = gradient(16, 16);
+    let source = StridedPixels::new(&pixels, 16, 16, 16).unwrap();
+
+    let mut loopback = Loopback::new();
+    send_frame(&mut loopback, &source).unwrap();
+    let mut receiver = FrameReceiver::new(loopback).with_max_pixels(100);
+    assert!(matches!(
+        receiver.recv_encoded(),
+        Err(TransportError::TooLarge)
+    ));
+
+    // cut off before the end marker
+    let mut loopback = Loopback::new();
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(16, 16, &pixels, &mut encoded).unwrap();
+    loopback.send_chunk(&encoded[..encoded.len() - 1]).unwrap();
+    let mut receiver = FrameReceiver::new(loopback);
+    assert!(matches!(
+        receiver.recv_encoded(),
+        Err(TransportError::Closed)
+    ));
+
+    let mut loopback = Loopback::new();
+    loopback.send_chunk(b"nope, not an image").unwrap();
+    let mut receiver = FrameReceiver::new(loopback);
+    assert!(matches!(
+        receiver.recv_encoded(),
+        Err(TransportError::Decode {
+            source: DecodeError::InvalidMagic
+        })
+    ));
+}
+
+#[test]
+fn tcp_roundtrip() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let pixels = gradient(64, 48);
+
+    let sender = std::thread::spawn({
+        let pixels = pixels.clone();
+        move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let source = StridedPixels::new(&pixels, 64, 48, 64).unwrap();
+            for _ in 0..3 {
+                send_frame(&mut stream, &source).unwrap();
+            }
+        }
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut receiver = FrameReceiver::new(stream);
+    for _ in 0..3 {
+        let (_, decoded) = receiver.recv_frame::<LittleEndian, Rgb565>().unwrap();
+        assert_eq!(decoded, pixels);
+    }
+    sender.join().unwrap();
+    assert!(matches!(
+        receiver.recv_encoded(),
+        Err(TransportError::Closed)
+    ));
+}
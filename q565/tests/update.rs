@@ -0,0 +1,50 @@
+use q565::{
+    byteorder::NativeEndian,
+    update::{apply_update, encode_update},
+    Rect, Rgb565,
+};
+
+#[test]
+fn dirty_rect_update() {
+    let (width, height) = (32u16, 24u16);
+    let previous: Vec<u16> = (0..32 * 24).map(|i| (i % 5) as u16 * 0x1000).collect();
+    let mut current = previous.clone();
+    for y in 3..9 {
+        for x in 10..20 {
+            current[y * 32 + x] = 0x07E0 + (x as u16);
+        }
+    }
+    current[23 * 32 + 31] = 0xFFFF;
+
+    let dirty = [
+        Rect {
+            x: 10,
+            y: 3,
+            width: 10,
+            height: 6,
+        },
+        // unchanged, skipped
+        Rect {
+            x: 0,
+            y: 20,
+            width: 4,
+            height: 4,
+        },
+        Rect {
+            x: 31,
+            y: 23,
+            width: 1,
+            height: 1,
+        },
+    ];
+
+    let mut message = Vec::new();
+    let count = encode_update(&previous, &current, width, height, &dirty, &mut message).unwrap();
+    assert_eq!(count, 2);
+
+    let mut framebuffer = previous.clone();
+    let applied =
+        apply_update::<NativeEndian, Rgb565>(&message, &mut framebuffer, width, height).unwrap();
+    assert_eq!(applied, 2);
+    assert_eq!(framebuffer, current);
+}
@@ -0,0 +1,74 @@
+use q565::{
+    byteorder::BigEndian,
+    decode::Q565DecodeContext,
+    encode::Q565EncodeContext,
+    zune::{Q565ZuneDecoder, ZuneDecodeError},
+    Rgb888,
+};
+use zune_core::{bit_depth::BitDepth, colorspace::ColorSpace, options::DecoderOptions};
+
+const WIDTH: u16 = 29;
+const HEIGHT: u16 = 13;
+
+fn encoded() -> Vec<u8> {
+    let pixels: Vec<u16> = (0..WIDTH * HEIGHT)
+        .map(|i| {
+            if i % 9 < 4 {
+                0x07E0
+            } else {
+                i.wrapping_mul(0x1357)
+            }
+        })
+        .collect();
+    let mut encoded = Vec::new();
+    Q565EncodeContext::encode_to_vec(WIDTH, HEIGHT, &pixels, &mut encoded).unwrap();
+    encoded
+}
+
+#[test]
+fn decodes_like_the_regular_decoder() {
+    let encoded = encoded();
+    let (_, expected) = Q565DecodeContext::decode_to_vec::<BigEndian, Rgb888>(&encoded).unwrap();
+    let expected = expected.as_flattened();
+
+    let mut decoder = Q565ZuneDecoder::new(&encoded);
+    assert_eq!(decoder.dimensions(), None);
+    decoder.decode_headers().unwrap();
+    assert_eq!(
+        decoder.dimensions(),
+        Some((usize::from(WIDTH), usize::from(HEIGHT)))
+    );
+    assert_eq!(decoder.get_output_colorspace(), Some(ColorSpace::RGB));
+    assert_eq!(decoder.get_output_depth(), Some(BitDepth::Eight));
+    assert_eq!(decoder.output_buffer_size(), Some(expected.len()));
+    assert!(decoder.decode().unwrap() == expected);
+
+    let mut output = vec![0xAA; expected.len() + 5];
+    Q565ZuneDecoder::new(&encoded)
+        .decode_into(&mut output)
+        .unwrap();
+    assert!(output[..expected.len()] == *expected);
+    assert_eq!(output[expected.len()..], [0xAA; 5]);
+}
+
+#[test]
+fn checks_limits_and_output() {
+    let encoded = encoded();
+
+    let options = DecoderOptions::default().set_max_width(usize::from(WIDTH) - 1);
+    assert!(matches!(
+        Q565ZuneDecoder::new_with_options(&encoded, options).decode(),
+        Err(ZuneDecodeError::TooLarge)
+    ));
+
+    let mut output = vec![0; usize::from(WIDTH * HEIGHT) * 3 - 1];
+    assert!(matches!(
+        Q565ZuneDecoder::new(&encoded).decode_into(&mut output),
+        Err(ZuneDecodeError::OutputTooSmall)
+    ));
+
+    assert!(matches!(
+        Q565ZuneDecoder::new(&encoded[..encoded.len() - 1]).decode(),
+        Err(ZuneDecodeError::Decode { .. })
+    ));
+}